@@ -0,0 +1,16 @@
+//! Evaluate well-formed rules, checking that the evaluator never panics,
+//! and that printing a rule and parsing it again produces the same rule.
+#![no_main]
+
+use jsonlogic_plus::{ArbitraryValue, Rule, WellFormed};
+use libfuzzer_sys::fuzz_target;
+use serde_json::Value;
+
+fuzz_target!(|input: (WellFormed, ArbitraryValue)| {
+    let (rule, ArbitraryValue(data)) = input;
+    let rule = Rule::from_value(&Value::from(rule)).expect("well-formed rules parse");
+    let printed = Value::from(rule.clone());
+    let reparsed = Rule::from_value(&printed).expect("printed rules parse");
+    assert_eq!(Value::from(reparsed), printed);
+    let _ = rule.apply(&data);
+});
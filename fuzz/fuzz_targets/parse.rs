@@ -0,0 +1,14 @@
+//! Parse arbitrary trees, which mostly aren't valid rules, checking that
+//! the parser never panics.
+#![no_main]
+
+use jsonlogic_plus::{ArbitraryValue, Ast, Rule};
+use libfuzzer_sys::fuzz_target;
+use serde_json::Value;
+
+fuzz_target!(|input: (Ast, ArbitraryValue)| {
+    let (ast, ArbitraryValue(data)) = input;
+    if let Ok(rule) = Rule::from_value(&Value::from(ast)) {
+        let _ = rule.apply(&data);
+    }
+});
@@ -0,0 +1,35 @@
+/*
+ * C interface to json-logic-plus, built with the `ffi` feature.
+ *
+ * Rules, data, and results are null-terminated JSON strings. Strings
+ * returned by the library are owned by the caller, and must be released
+ * with jlp_free().
+ */
+#ifndef JSONLOGIC_PLUS_H
+#define JSONLOGIC_PLUS_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/*
+ * Evaluate a rule against data. Returns the result as JSON, or NULL if
+ * evaluation fails, in which case the error may be retrieved with
+ * jlp_last_error().
+ */
+char *jlp_apply(const char *rule_json, const char *data_json);
+
+/*
+ * The error from the last call to jlp_apply() on this thread, as a JSON
+ * object with its "code", "message", and "details", or NULL if it succeeded.
+ */
+char *jlp_last_error(void);
+
+/* Free a string returned by the library. NULL is ignored. */
+void jlp_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,408 @@
+//! Arenas
+//!
+//! A rule's identifiers, argument lists, and `var` path segments are
+//! allocated in a bump arena owned by the rule, rather than each on its own,
+//! so that parsing a large rule makes a few large allocations rather than
+//! several per node. Each distinct name or path is allocated once, and shared
+//! by every node that refers to it. Everything in the arena is freed along
+//! with the rule.
+//!
+//! Nodes refer to what's allocated in an arena through `ArenaStr`s and
+//! `ArenaSlice`s, which don't keep the arena alive themselves. Whatever owns
+//! the nodes parsed into an arena (a `Rule`, and the `FunctionBody`s it may
+//! call) also owns the arena, and drops the nodes first. Cloning a node copies
+//! whatever it refers to in an arena onto the heap, so that clones may
+//! outlive the arena.
+
+use alloc::sync::Arc;
+use core::borrow::Borrow;
+use core::cell::RefCell;
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::ops::Deref;
+use core::ptr::{self, NonNull};
+
+use bumpalo::Bump;
+
+use crate::collections::HashMap;
+use crate::op::data::PathSegment;
+use crate::prelude::*;
+
+/// A slice allocated in an arena, which is only valid while the arena is
+/// alive
+pub(crate) struct Allocation<T> {
+    ptr: NonNull<T>,
+    len: usize,
+}
+impl<T> Clone for Allocation<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T> Copy for Allocation<T> {}
+impl<T> Allocation<T> {
+    fn new(slice: &[T]) -> Self {
+        Self {
+            ptr: NonNull::from(slice).cast(),
+            len: slice.len(),
+        }
+    }
+
+    /// The slice.
+    ///
+    /// # Safety
+    ///
+    /// The arena the slice was allocated in must still be alive.
+    unsafe fn get<'a>(self) -> &'a [T] {
+        core::slice::from_raw_parts(self.ptr.as_ptr(), self.len)
+    }
+}
+
+/// A name allocated in an arena, by which the arena's maps are keyed
+#[derive(Clone, Copy)]
+struct Key(Allocation<u8>);
+impl Key {
+    fn as_str(&self) -> &str {
+        // Keys only live in the maps of the arena they were allocated in
+        unsafe { core::str::from_utf8_unchecked(self.0.get()) }
+    }
+}
+impl Borrow<str> for Key {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+impl PartialEq for Key {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+impl Eq for Key {}
+impl PartialOrd for Key {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Key {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+impl Hash for Key {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+/// The elements of a slice in an arena, which the bump allocator doesn't
+/// drop itself
+struct Elements {
+    ptr: NonNull<u8>,
+    len: usize,
+    drop: unsafe fn(NonNull<u8>, usize),
+}
+
+/// Drop the elements of a slice in place.
+///
+/// # Safety
+///
+/// The slice must be one of `T`s that haven't been dropped yet.
+unsafe fn drop_elements<T>(ptr: NonNull<u8>, len: usize) {
+    ptr::drop_in_place(ptr::slice_from_raw_parts_mut(ptr.cast::<T>().as_ptr(), len))
+}
+
+/// A bump arena that rules are parsed into
+///
+/// An arena is only allocated in while parsing, after which it's `freeze()`d
+/// so that it may be shared between threads along with the rule.
+#[derive(Default)]
+pub(crate) struct Arena {
+    bump: Bump,
+    elements: RefCell<Vec<Elements>>,
+    names: RefCell<HashMap<Key, ()>>,
+    paths: RefCell<HashMap<Key, Allocation<PathSegment>>>,
+}
+impl Arena {
+    /// Allocate a name, or share the allocation of an equal one.
+    pub(crate) fn name(&self, name: &str) -> ArenaStr {
+        ArenaStr::Arena(self.key(name).0)
+    }
+
+    fn key(&self, name: &str) -> Key {
+        let mut names = self.names.borrow_mut();
+        if let Some((key, _)) = names.get_key_value(name) {
+            return *key;
+        }
+        let key = Key(Allocation::new(self.bump.alloc_str(name).as_bytes()));
+        names.insert(key, ());
+        key
+    }
+
+    /// Allocate the path for a `var` key, splitting the key only if it
+    /// hasn't been seen before.
+    pub(crate) fn path<F>(&self, key: &str, split: F) -> ArenaSlice<PathSegment>
+    where
+        F: FnOnce() -> Vec<PathSegment>,
+    {
+        if let Some(path) = self.paths.borrow().get(key) {
+            return ArenaSlice::Arena(*path);
+        }
+        let path = self.own(self.bump.alloc_slice_fill_iter(split()));
+        self.paths.borrow_mut().insert(self.key(key), path);
+        ArenaSlice::Arena(path)
+    }
+
+    /// Allocate a list of values, stopping at the first that fails.
+    pub(crate) fn slice<T, E, I>(&self, items: I) -> Result<ArenaSlice<T>, E>
+    where
+        I: IntoIterator<Item = Result<T, E>>,
+    {
+        let items = items.into_iter();
+        let mut slice = bumpalo::collections::Vec::with_capacity_in(
+            items.size_hint().0,
+            &self.bump,
+        );
+        for item in items {
+            slice.push(item?);
+        }
+        Ok(ArenaSlice::Arena(self.own(slice.into_bump_slice_mut())))
+    }
+
+    /// Take ownership of the elements of a slice allocated in the arena, so
+    /// that they're dropped along with it.
+    fn own<T>(&self, slice: &mut [T]) -> Allocation<T> {
+        if core::mem::needs_drop::<T>() && !slice.is_empty() {
+            self.elements.borrow_mut().push(Elements {
+                ptr: NonNull::from(&mut *slice).cast(),
+                len: slice.len(),
+                drop: drop_elements::<T>,
+            });
+        }
+        Allocation::new(slice)
+    }
+
+    /// Stop allocating in the arena, so that it may be shared.
+    pub(crate) fn freeze(self) -> Arc<FrozenArena> {
+        Arc::new(FrozenArena(self))
+    }
+}
+impl Drop for Arena {
+    fn drop(&mut self) {
+        for elements in self.elements.get_mut().drain(..).rev() {
+            // Each slice is dropped once, and nothing refers to it any more
+            unsafe { (elements.drop)(elements.ptr, elements.len) }
+        }
+    }
+}
+impl fmt::Debug for Arena {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Arena")
+            .field("allocated", &self.bump.allocated_bytes())
+            .finish()
+    }
+}
+
+/// An arena that's no longer allocated in
+pub(crate) struct FrozenArena(Arena);
+impl fmt::Debug for FrozenArena {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+// A frozen arena is only ever read, through the slices allocated in it, and
+// dropped, so it may be shared between threads as its elements may be. The
+// elements are `Parsed` nodes and `PathSegment`s, which are `Send` and `Sync`.
+unsafe impl Send for FrozenArena {}
+unsafe impl Sync for FrozenArena {}
+
+/// A string allocated in an arena, or on the heap
+pub(crate) enum ArenaStr {
+    Arena(Allocation<u8>),
+    Heap(Arc<str>),
+}
+impl Deref for ArenaStr {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        match self {
+            // Strings in an arena are only reachable while it's alive
+            Self::Arena(name) => unsafe { core::str::from_utf8_unchecked(name.get()) },
+            Self::Heap(s) => s,
+        }
+    }
+}
+impl Clone for ArenaStr {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Arena(_) => Self::Heap((**self).into()),
+            Self::Heap(s) => Self::Heap(s.clone()),
+        }
+    }
+}
+impl From<&str> for ArenaStr {
+    fn from(s: &str) -> Self {
+        Self::Heap(s.into())
+    }
+}
+impl fmt::Debug for ArenaStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+impl fmt::Display for ArenaStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+// What's allocated in an arena is only read, as by shared references
+unsafe impl Send for ArenaStr {}
+unsafe impl Sync for ArenaStr {}
+
+/// A slice allocated in an arena, or a vector on the heap
+pub(crate) enum ArenaSlice<T> {
+    Arena(Allocation<T>),
+    Heap(Vec<T>),
+}
+impl<T: Clone> ArenaSlice<T> {
+    /// The values, as a vector that may be modified, copied onto the heap
+    /// first if they're in an arena.
+    pub(crate) fn to_mut(&mut self) -> &mut Vec<T> {
+        if let Self::Arena(_) = self {
+            *self = Self::Heap(self.to_vec());
+        }
+        match self {
+            Self::Heap(items) => items,
+            Self::Arena(_) => unreachable!(),
+        }
+    }
+}
+impl<T> Deref for ArenaSlice<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        match self {
+            // Slices in an arena are only reachable while it's alive
+            Self::Arena(items) => unsafe { items.get() },
+            Self::Heap(items) => items,
+        }
+    }
+}
+impl<T: Clone> Clone for ArenaSlice<T> {
+    fn clone(&self) -> Self {
+        Self::Heap(self.to_vec())
+    }
+}
+impl<T> From<Vec<T>> for ArenaSlice<T> {
+    fn from(items: Vec<T>) -> Self {
+        Self::Heap(items)
+    }
+}
+impl<T: Clone> IntoIterator for ArenaSlice<T> {
+    type Item = T;
+    type IntoIter = alloc::vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let items = match self {
+            Self::Arena(_) => self.to_vec(),
+            Self::Heap(items) => items,
+        };
+        items.into_iter()
+    }
+}
+impl<T: fmt::Debug> fmt::Debug for ArenaSlice<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+unsafe impl<T: Send + Sync> Send for ArenaSlice<T> {}
+unsafe impl<T: Sync> Sync for ArenaSlice<T> {}
+
+#[cfg(test)]
+mod test_arena {
+    use super::*;
+    use serde_json::{json, Value};
+
+    use crate::context::Env;
+    use crate::namespace::Scope;
+    use crate::value::Parsed;
+    use crate::Rule;
+
+    #[test]
+    fn test_shared_allocations() {
+        let arena = Arena::default();
+        let a = arena.name("a");
+        assert!(ptr::eq(a.as_ptr(), arena.name("a").as_ptr()));
+        assert!(!ptr::eq(a.as_ptr(), arena.name("b").as_ptr()));
+
+        let path = arena.path("a.b", || vec![PathSegment::new("a")]);
+        let again = arena.path("a.b", || unreachable!());
+        assert!(ptr::eq(path.as_ptr(), again.as_ptr()));
+
+        // Repeated variables in a rule share their path
+        let rule = json!({"+": [{"var": "x.y"}, {"var": "x.y"}]});
+        Parsed::from_value(&rule, &Scope::default().with_arena(&arena)).unwrap();
+        assert_eq!(arena.paths.borrow().len(), 2);
+        assert_eq!(
+            &*arena.path("x.y", || unreachable!()),
+            &[PathSegment::new("x"), PathSegment::new("y")]
+        );
+    }
+
+    /// Whether the arena's chunks hold the given pointer
+    fn holds<T>(arena: &Arena, ptr: *const T) -> bool {
+        let ptr = ptr.cast::<u8>();
+        unsafe { arena.bump.iter_allocated_chunks_raw() }
+            .any(|(start, len)| ptr >= start && ptr < start.wrapping_add(len))
+    }
+
+    #[test]
+    fn test_argument_lists() {
+        let arena = Arena::default();
+        let rule = json!({"if": [{"==": [{"var": "a"}, "x"]}, [1, 2], {"cat": []}]});
+        let parsed =
+            Parsed::from_value(&rule, &Scope::default().with_arena(&arena)).unwrap();
+        let arguments = match &parsed {
+            Parsed::LazyOperation(op) => op.arguments(),
+            parsed => panic!("{:?}", parsed),
+        };
+        assert!(holds(&arena, arguments.as_ptr()));
+        match &arguments[0] {
+            Parsed::Operation(op) => assert!(holds(&arena, op.arguments().as_ptr())),
+            parsed => panic!("{:?}", parsed),
+        }
+
+        // Clones are copied onto the heap, and outlive the arena
+        let clone = parsed.clone();
+        match &clone {
+            Parsed::LazyOperation(op) => {
+                assert!(!holds(&arena, op.arguments().as_ptr()))
+            }
+            parsed => panic!("{:?}", parsed),
+        }
+        drop(parsed);
+        drop(arena);
+        let value = clone.evaluate(&json!({"a": "x"}), &Env::default());
+        assert_eq!(Value::from(value.unwrap()), json!([1, 2]));
+
+        // Arguments parsed before one that fails are dropped with the arena
+        let arena = Arena::default();
+        let rule = json!({"+": [{"var": "a"}, {"var": [1, 2, 3, 4]}]});
+        Parsed::from_value(&rule, &Scope::default().with_arena(&arena)).unwrap_err();
+    }
+
+    #[test]
+    fn test_rule_clones() {
+        let rule =
+            Rule::from_value(&json!({"if": [{"==": [{"var": "a.b"}, "hi"]}, 1, 2]}))
+                .unwrap();
+        let mutants = rule.mutants();
+        drop(rule.clone());
+        drop(rule);
+        assert!(!mutants.is_empty());
+        for mutant in mutants {
+            mutant.rule.apply(&json!({"a": {"b": "hi"}})).unwrap();
+        }
+    }
+}
@@ -0,0 +1,400 @@
+//! Abstract Syntax Tree
+//!
+//! The `Ast` is a public, typed view of a parsed rule, for tooling that needs
+//! to inspect or rewrite rules (linters, translators, UI builders, etc.)
+//! without reimplementing JsonLogic parsing. Obtain one from a parsed rule
+//! with `Rule::ast()`, or for a namespace's functions with
+//! `Namespace::definitions()`, and convert it back into a `Value` to parse it
+//! again.
+
+use serde_json::{Map, Value};
+
+use crate::op::data::{self, Variable};
+use crate::op::func::{Function, Param};
+use crate::op::object::{pairs, OBJ};
+use crate::op::pattern;
+use crate::prelude::*;
+use crate::value::{Parsed, Raw};
+
+/// A node in a parsed rule
+#[derive(Clone, Debug, PartialEq)]
+pub enum Ast {
+    /// A raw JSON value
+    Literal(Value),
+    /// A `var` lookup into the data. With no path, the whole of the data is
+    /// returned.
+    Var {
+        path: Option<Box<Ast>>,
+        default: Option<Box<Ast>>,
+    },
+    /// An operation using a built-in or custom operator
+    Operator { name: String, args: Vec<Ast> },
+    /// A function definition (a `defn` expression)
+    FunctionDef {
+        name: String,
+        params: Vec<String>,
+        body: Box<Ast>,
+    },
+    /// A call to a function in a namespace
+    FunctionCall { name: String, args: Vec<Ast> },
+    /// A reference to a parameter of the enclosing function
+    Param(String),
+    /// An object whose values are evaluated (see `MultiKeyObjects`)
+    Object(Vec<(String, Ast)>),
+}
+impl Ast {
+    /// Walk the children of this node with a visitor.
+    ///
+    /// This calls the visitor method for this node's kind, whose default
+    /// implementation visits each of the node's children in turn.
+    pub fn walk<V: AstVisitor + ?Sized>(&self, visitor: &mut V) {
+        match self {
+            Self::Literal(value) => visitor.visit_literal(value),
+            Self::Var { path, default } => {
+                visitor.visit_var(path.as_deref(), default.as_deref())
+            }
+            Self::Operator { name, args } => visitor.visit_operator(name, args),
+            Self::FunctionDef { name, params, body } => {
+                visitor.visit_function_def(name, params, body)
+            }
+            Self::FunctionCall { name, args } => {
+                visitor.visit_function_call(name, args)
+            }
+            Self::Param(name) => visitor.visit_param(name),
+            Self::Object(entries) => visitor.visit_object(entries),
+        }
+    }
+
+    /// Walk the children of this node with a mutating visitor.
+    ///
+    /// See [`walk`](#method.walk).
+    pub fn walk_mut<V: AstVisitorMut + ?Sized>(&mut self, visitor: &mut V) {
+        match self {
+            Self::Literal(value) => visitor.visit_literal_mut(value),
+            Self::Var { path, default } => {
+                visitor.visit_var_mut(path.as_deref_mut(), default.as_deref_mut())
+            }
+            Self::Operator { name, args } => visitor.visit_operator_mut(name, args),
+            Self::FunctionDef { name, params, body } => {
+                visitor.visit_function_def_mut(name, params, body)
+            }
+            Self::FunctionCall { name, args } => {
+                visitor.visit_function_call_mut(name, args)
+            }
+            Self::Param(name) => visitor.visit_param_mut(name),
+            Self::Object(entries) => visitor.visit_object_mut(entries),
+        }
+    }
+
+    /// Construct the definition node for a function with a parsed body.
+    pub(crate) fn function_def(func: &Function, body: &Parsed) -> Self {
+        Self::FunctionDef {
+            name: func.name().value().into(),
+            params: func.params().iter().map(|p| p.value().into()).collect(),
+            body: Box::new(body.into()),
+        }
+    }
+}
+impl From<&Parsed> for Ast {
+    fn from(parsed: &Parsed) -> Self {
+        fn operator(name: &str, args: &[Parsed]) -> Ast {
+            Ast::Operator {
+                name: name.into(),
+                args: args.iter().map(Ast::from).collect(),
+            }
+        }
+        let boxed = |arg: Option<&Parsed>| arg.map(|arg| Box::new(Ast::from(arg)));
+
+        match parsed {
+            Parsed::Operation(op) => operator(op.symbol(), op.arguments()),
+            Parsed::LazyOperation(op) => operator(op.symbol(), op.arguments()),
+            Parsed::DataOperation(op) => operator(op.symbol(), op.arguments()),
+            Parsed::CustomOperation(op) => {
+                operator(op.operator().name(), op.arguments())
+            }
+            Parsed::Variable(var) => Self::Var {
+                path: boxed(var.arguments().first()),
+                default: boxed(var.arguments().get(1)),
+            },
+            Parsed::Call(call) => Self::FunctionCall {
+                name: call.name().into(),
+                args: call.arguments().iter().map(Ast::from).collect(),
+            },
+            Parsed::Param(param) => Self::Param(param.name().into()),
+            Parsed::Object(obj) => Self::Object(
+                obj.entries()
+                    .iter()
+                    .map(|(key, value)| (key.clone(), Ast::from(value)))
+                    .collect(),
+            ),
+            Parsed::Raw(raw) => Self::Literal(raw.value().clone()),
+        }
+    }
+}
+impl From<Ast> for Value {
+    fn from(ast: Ast) -> Self {
+        fn object(key: String, value: Value) -> Value {
+            let mut rv = Map::with_capacity(1);
+            rv.insert(key, value);
+            Value::Object(rv)
+        }
+        let values =
+            |args: Vec<Ast>| Value::Array(args.into_iter().map(Value::from).collect());
+
+        match ast {
+            // Objects with a single key might be parsed as operations, or in
+            // a namespace, calls, so they're quoted
+            Ast::Literal(value) => match value {
+                Value::Object(ref obj) if obj.len() == 1 => {
+                    object(Raw::OPERATOR.into(), value)
+                }
+                value => value,
+            },
+            Ast::Var { path, default } => {
+                // Pointers are parsed as such within `var`, so needn't be quoted
+                let path = path.map(|path| match *path {
+                    Ast::Literal(value) if data::pointer(&value).is_some() => value,
+                    path => Value::from(path),
+                });
+                let args = path
+                    .into_iter()
+                    .chain(default.map(|arg| Value::from(*arg)))
+                    .collect();
+                object(Variable::OPERATOR.into(), Value::Array(args))
+            }
+            // Patterns are literals already
+            Ast::Operator { name, args } if name == pattern::OPERATOR => {
+                let len = args.len();
+                let args = args.into_iter().enumerate().map(|(i, arg)| match arg {
+                    Ast::Literal(value) if pattern::is_pattern(i, len) => value,
+                    arg => Value::from(arg),
+                });
+                object(name, Value::Array(args.collect()))
+            }
+            // Arguments are flattened from [key, value] pairs
+            Ast::Operator { name, args } if name == OBJ => {
+                let args = args.into_iter().map(Value::from).collect();
+                object(name, Value::Array(pairs(args)))
+            }
+            Ast::Operator { name, args } => object(name, values(args)),
+            Ast::FunctionDef { name, params, body } => object(
+                Function::OPERATOR.into(),
+                Value::Array(vec![
+                    Value::String(name),
+                    Value::Array(params.into_iter().map(Value::String).collect()),
+                    Value::from(*body),
+                ]),
+            ),
+            Ast::FunctionCall { name, args } => object(name, values(args)),
+            Ast::Param(name) => object(Param::OPERATOR.into(), Value::String(name)),
+            Ast::Object(entries) => Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, Value::from(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// A visitor over an `Ast`
+///
+/// Every method has a default implementation that simply visits the node's
+/// children, so implementors need only override the methods for the nodes
+/// they're interested in. An overridden method can call `Ast::walk()` on a
+/// node to continue visiting its children.
+pub trait AstVisitor {
+    /// Visit any node.
+    fn visit(&mut self, ast: &Ast) {
+        ast.walk(self)
+    }
+
+    fn visit_literal(&mut self, _value: &Value) {}
+
+    fn visit_var(&mut self, path: Option<&Ast>, default: Option<&Ast>) {
+        path.into_iter()
+            .chain(default)
+            .for_each(|arg| self.visit(arg))
+    }
+
+    fn visit_operator(&mut self, _name: &str, args: &[Ast]) {
+        args.iter().for_each(|arg| self.visit(arg))
+    }
+
+    fn visit_function_def(&mut self, _name: &str, _params: &[String], body: &Ast) {
+        self.visit(body)
+    }
+
+    fn visit_function_call(&mut self, _name: &str, args: &[Ast]) {
+        args.iter().for_each(|arg| self.visit(arg))
+    }
+
+    fn visit_param(&mut self, _name: &str) {}
+
+    fn visit_object(&mut self, entries: &[(String, Ast)]) {
+        entries.iter().for_each(|(_, value)| self.visit(value))
+    }
+}
+
+/// A visitor that may rewrite an `Ast` in place
+///
+/// To replace a node entirely, override `visit_mut()`, assign to the node,
+/// and call `Ast::walk_mut()` to continue visiting its (new) children.
+pub trait AstVisitorMut {
+    /// Visit any node.
+    fn visit_mut(&mut self, ast: &mut Ast) {
+        ast.walk_mut(self)
+    }
+
+    fn visit_literal_mut(&mut self, _value: &mut Value) {}
+
+    fn visit_var_mut(&mut self, path: Option<&mut Ast>, default: Option<&mut Ast>) {
+        path.into_iter()
+            .chain(default)
+            .for_each(|arg| self.visit_mut(arg))
+    }
+
+    fn visit_operator_mut(&mut self, _name: &mut String, args: &mut Vec<Ast>) {
+        args.iter_mut().for_each(|arg| self.visit_mut(arg))
+    }
+
+    fn visit_function_def_mut(
+        &mut self,
+        _name: &mut String,
+        _params: &mut Vec<String>,
+        body: &mut Ast,
+    ) {
+        self.visit_mut(body)
+    }
+
+    fn visit_function_call_mut(&mut self, _name: &mut String, args: &mut Vec<Ast>) {
+        args.iter_mut().for_each(|arg| self.visit_mut(arg))
+    }
+
+    fn visit_param_mut(&mut self, _name: &mut String) {}
+
+    fn visit_object_mut(&mut self, entries: &mut Vec<(String, Ast)>) {
+        entries
+            .iter_mut()
+            .for_each(|(_, value)| self.visit_mut(value))
+    }
+}
+
+#[cfg(test)]
+mod test_ast {
+    use super::*;
+    use serde_json::json;
+
+    use crate::{Namespace, Rule};
+
+    /// Collect the names of all variables used in a rule
+    #[derive(Default)]
+    struct Vars(Vec<Value>);
+    impl AstVisitor for Vars {
+        fn visit_var(&mut self, path: Option<&Ast>, default: Option<&Ast>) {
+            if let Some(Ast::Literal(path)) = path {
+                self.0.push(path.clone())
+            }
+            default.into_iter().for_each(|default| self.visit(default))
+        }
+    }
+
+    /// Rename a variable throughout a rule
+    struct Rename(&'static str, &'static str);
+    impl AstVisitorMut for Rename {
+        fn visit_mut(&mut self, ast: &mut Ast) {
+            if let Ast::Var {
+                path: Some(path), ..
+            } = ast
+            {
+                if **path == Ast::Literal(json!(self.0)) {
+                    **path = Ast::Literal(json!(self.1));
+                }
+            }
+            ast.walk_mut(self)
+        }
+    }
+
+    #[test]
+    fn test_visit() {
+        let rule = Rule::from_value(&json!({"if": [
+            {">": [{"var": "a"}, 1]},
+            {"var": ["b", {"var": "c"}]},
+            [{"var": "d"}]
+        ]}))
+        .unwrap();
+        let mut vars = Vars::default();
+        vars.visit(&rule.ast());
+        assert_eq!(vars.0, vec![json!("a"), json!("b"), json!("c")]);
+    }
+
+    #[test]
+    fn test_rewrite() {
+        let rule =
+            Rule::from_value(&json!({"+": [{"var": "a"}, {"var": "b"}]})).unwrap();
+        let mut ast = rule.ast();
+        Rename("a", "x").visit_mut(&mut ast);
+        let rule = Rule::from_value(&Value::from(ast)).unwrap();
+        assert_eq!(rule.apply(&json!({"x": 1, "b": 2})).unwrap(), json!(3));
+    }
+
+    #[test]
+    fn test_pointer_round_trip() {
+        let value = json!({"var": [{"pointer": "/a.b"}, 1]});
+        let ast = Rule::from_value(&value).unwrap().ast();
+        assert_eq!(Value::from(ast), value);
+        // Pointers in a namespace aren't mistaken for calls
+        let rule = Namespace::new().parse(&value).unwrap();
+        assert_eq!(rule.apply(&json!({"a.b": 2})).unwrap(), json!(2));
+    }
+
+    #[test]
+    fn test_obj_round_trip() {
+        let value = json!({"obj": [["a", {"var": ["x"]}], [{"var": ["k"]}, 1]]});
+        let rule = Rule::from_value(&value).unwrap();
+        assert_eq!(Value::from(rule.ast()), value);
+        assert_eq!(Value::from(rule), value);
+    }
+
+    #[test]
+    fn test_switch_round_trip() {
+        let value = json!({"switch": [{"var": ["x"]}, {"a": {"lit": {"b": 1}}}, 2]});
+        let rule = Rule::from_value(&value).unwrap();
+        assert_eq!(Value::from(rule.ast()), value);
+        assert_eq!(Value::from(rule), value);
+    }
+
+    #[test]
+    fn test_namespace() {
+        let mut ns = Namespace::new();
+        ns.define(&json!({"defn": ["double", ["n"], {"*": [{"param": "n"}, 2]}]}))
+            .unwrap();
+        let rule = ns.parse(&json!({"double": [{"var": []}]})).unwrap();
+        assert_eq!(
+            rule.ast(),
+            Ast::FunctionCall {
+                name: "double".into(),
+                args: vec![Ast::Var {
+                    path: None,
+                    default: None
+                }],
+            }
+        );
+        let defs = ns.definitions().unwrap();
+        assert_eq!(
+            defs,
+            vec![Ast::FunctionDef {
+                name: "double".into(),
+                params: vec!["n".into()],
+                body: Box::new(Ast::Operator {
+                    name: "*".into(),
+                    args: vec![Ast::Param("n".into()), Ast::Literal(json!(2))],
+                }),
+            }]
+        );
+        assert_eq!(
+            Value::from(defs[0].clone()),
+            json!({"defn": ["double", ["n"], {"*": [{"param": "n"}, 2]}]})
+        );
+    }
+}
@@ -0,0 +1,350 @@
+//! Asynchronous Operators
+//!
+//! Custom operators may be asynchronous, e.g. to look up a value from an
+//! external service. Rules using them are evaluated with
+//! `Rule::apply_async()`.
+//!
+//! Evaluation itself stays synchronous. When an evaluation reaches an async
+//! operation whose result isn't yet known, it records the call and stops.
+//! The call is then awaited, its result is cached for the rest of the
+//! evaluation, and the rule is evaluated again from the start. A rule with no
+//! async operations is therefore evaluated exactly once, as usual, and lazily
+//! evaluated branches only trigger the async calls they actually make, but a
+//! rule making `n` distinct async calls is evaluated `n + 1` times.
+//!
+//! Each async call is made once. On each evaluation after the first, the
+//! calls made so far are replayed in order, by the position at which the
+//! evaluation makes them, rather than made again. If a call's arguments
+//! differ from those of the call replayed at its position, e.g. because they
+//! depend on `random` or `now`, the evaluation fails rather than waiting on
+//! calls that would never be replayed.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use serde_json::Value;
+
+use crate::error::Error;
+use crate::lookup::AsyncLookupResolver;
+
+/// A boxed future, as returned by an `AsyncOperator`
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// The implementation of an asynchronous custom operator
+///
+/// Async operators receive their arguments already evaluated. Calls with the
+/// same arguments are made at most once per evaluation.
+///
+/// Any `Fn(Vec<Value>) -> impl Future<Output = Result<Value, Error>>` is an
+/// async operator.
+pub trait AsyncOperator: Send + Sync {
+    fn call(&self, args: Vec<Value>) -> BoxFuture<'static, Result<Value, Error>>;
+}
+impl<F, Fut> AsyncOperator for F
+where
+    F: Fn(Vec<Value>) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<Value, Error>> + Send + 'static,
+{
+    fn call(&self, args: Vec<Value>) -> BoxFuture<'static, Result<Value, Error>> {
+        Box::pin(self(args))
+    }
+}
+
+/// An async lookup resolver, as an operator taking a table and key
+pub(crate) struct LookupOperator(pub(crate) Arc<dyn AsyncLookupResolver>);
+impl AsyncOperator for LookupOperator {
+    fn call(&self, args: Vec<Value>) -> BoxFuture<'static, Result<Value, Error>> {
+        let mut args = args.into_iter();
+        let (table, key) = match (args.next(), args.next()) {
+            (Some(Value::String(table)), Some(key)) => (table, key),
+            _ => {
+                return Box::pin(async {
+                    Err(Error::UnexpectedError(
+                        "lookup called without a table and key".into(),
+                    ))
+                })
+            }
+        };
+        let lookup = self.0.lookup(table, key);
+        Box::pin(async move { lookup.await.map(|value| value.unwrap_or(Value::Null)) })
+    }
+}
+
+/// An async call that an evaluation is waiting on
+struct Deferred {
+    key: String,
+    operator: Arc<dyn AsyncOperator>,
+    args: Vec<Value>,
+}
+
+/// The results of the async calls made over the course of an evaluation
+#[derive(Default)]
+pub(crate) struct AsyncState {
+    /// The calls made so far, in the order they were made, with their results
+    calls: Mutex<Vec<(String, Value)>>,
+    /// The position of the next call made by the current evaluation
+    position: Mutex<usize>,
+    pending: Mutex<Option<Deferred>>,
+}
+impl AsyncState {
+    /// Return the result of an async call, if it is known.
+    ///
+    /// Otherwise, the call is deferred and an error is returned to stop the
+    /// evaluation, which will be retried once the call has completed.
+    pub(crate) fn call(
+        &self,
+        name: &str,
+        operator: &Arc<dyn AsyncOperator>,
+        args: &[&Value],
+    ) -> Result<Value, Error> {
+        let args: Vec<Value> = args.iter().map(|arg| (*arg).clone()).collect();
+        let key = format!("{}{}", name, Value::Array(args.clone()));
+        let position = {
+            let mut position = lock(&self.position);
+            *position += 1;
+            *position - 1
+        };
+        let mut calls = lock(&self.calls);
+        match calls.get(position) {
+            Some((replayed, result)) if *replayed == key => return Ok(result.clone()),
+            Some(_) => {
+                return Err(Error::InvalidOperation {
+                    key: name.into(),
+                    reason: "Async operators must be called with the same arguments \
+                             each time the rule is evaluated"
+                        .into(),
+                })
+            }
+            None => {}
+        }
+        // Calls with the same arguments as an earlier one aren't made again
+        let earlier = calls
+            .iter()
+            .find(|(earlier, _)| *earlier == key)
+            .map(|(_, result)| result.clone());
+        if let Some(result) = earlier {
+            if position == calls.len() {
+                calls.push((key, result.clone()));
+                return Ok(result);
+            }
+        }
+        lock(&self.pending).get_or_insert(Deferred {
+            key,
+            operator: operator.clone(),
+            args,
+        });
+        Err(Error::InvalidOperation {
+            key: name.into(),
+            reason: "Awaiting the result of an async operator".into(),
+        })
+    }
+
+    /// Await the call the evaluation stopped on, if any, so that the next
+    /// evaluation replays it.
+    ///
+    /// Returns false if the evaluation didn't need any async calls.
+    pub(crate) async fn resolve_pending(&self) -> Result<bool, Error> {
+        let deferred = match lock(&self.pending).take() {
+            Some(deferred) => deferred,
+            None => return Ok(false),
+        };
+        let result = deferred.operator.call(deferred.args).await?;
+        lock(&self.calls).push((deferred.key, result));
+        *lock(&self.position) = 0;
+        Ok(true)
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    // Nothing can panic while the lock is held.
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod test_async {
+    use super::*;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::{Context, Poll, Wake, Waker};
+    use std::thread::{self, Thread};
+
+    use crate::op::NumParams;
+    use crate::pack::{CustomOperator, OperatorPack, PACK_API_VERSION};
+    use crate::Namespace;
+
+    /// Run a future to completion on the current thread.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        struct ThreadWaker(Thread);
+        impl Wake for ThreadWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark()
+            }
+        }
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    /// A pack with an async lookup into a fixed table, counting its calls
+    struct LookupPack(Arc<AtomicUsize>);
+    impl OperatorPack for LookupPack {
+        fn name(&self) -> &str {
+            "lookup"
+        }
+        fn version(&self) -> &str {
+            "1.0.0"
+        }
+        fn api_version(&self) -> u32 {
+            PACK_API_VERSION
+        }
+        fn operators(&self) -> Vec<CustomOperator> {
+            let calls = self.0.clone();
+            vec![
+                CustomOperator::new_async(
+                    "risk",
+                    NumParams::Unary,
+                    move |args: Vec<Value>| {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        async move {
+                            match args[0].as_str() {
+                                Some("US") => Ok(json!(1)),
+                                Some("XX") => Ok(json!(9)),
+                                _ => Err(Error::invalid_argument(
+                                    args[0].clone(),
+                                    "risk",
+                                    "unknown country",
+                                )),
+                            }
+                        }
+                    },
+                ),
+                CustomOperator::new_async(
+                    "echo",
+                    NumParams::Unary,
+                    |args: Vec<Value>| async move { Ok(args[0].clone()) },
+                ),
+            ]
+        }
+    }
+
+    #[test]
+    fn test_apply_async() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut ns = Namespace::new();
+        ns.load_pack(&LookupPack(calls.clone())).unwrap();
+        let rule = ns
+            .parse(&json!({"if": [
+                {">": [{"risk": {"var": "country"}}, 5]},
+                "deny",
+                {"cat": ["allow:", {"risk": {"var": "country"}}, {"risk": "US"}]}
+            ]}))
+            .unwrap();
+
+        assert_eq!(
+            block_on(rule.apply_async(&json!({"country": "XX"}))).unwrap(),
+            json!("deny")
+        );
+        assert_eq!(calls.swap(0, Ordering::SeqCst), 1);
+
+        // Repeated calls with the same arguments are only made once
+        assert_eq!(
+            block_on(rule.apply_async(&json!({"country": "US"}))).unwrap(),
+            json!("allow:11")
+        );
+        assert_eq!(calls.swap(0, Ordering::SeqCst), 1);
+
+        // Errors from async operators are returned as-is
+        block_on(rule.apply_async(&json!({"country": "CA"}))).unwrap_err();
+
+        // Async operators can't be evaluated synchronously
+        rule.apply(&json!({"country": "US"})).unwrap_err();
+    }
+
+    #[test]
+    fn test_replayed_calls() {
+        let mut ns = Namespace::new();
+        ns.load_pack(&LookupPack(Arc::new(AtomicUsize::new(0))))
+            .unwrap();
+        let rule = ns
+            .parse(&json!({"cat": [{"echo": "a"}, {"echo": {"echo": "b"}}, {"echo": "a"}]}))
+            .unwrap();
+        assert_eq!(
+            block_on(rule.apply_async(&json!(null))).unwrap(),
+            json!("aba")
+        );
+
+        // Calls whose arguments differ each time the rule is evaluated would
+        // never be replayed
+        let rule = ns.parse(&json!({"echo": {"random": []}})).unwrap();
+        let err = block_on(rule.apply_async(&json!(null))).unwrap_err();
+        assert!(err.to_string().contains("same arguments"), "{}", err);
+    }
+
+    /// Tables that take a while to respond
+    #[derive(Debug)]
+    struct SlowTables;
+    impl AsyncLookupResolver for SlowTables {
+        fn lookup(
+            &self,
+            table: String,
+            key: Value,
+        ) -> BoxFuture<'static, Result<Option<Value>, Error>> {
+            Box::pin(async move {
+                match (table.as_str(), key.as_str()) {
+                    ("country_risk", Some("XX")) => Ok(Some(json!(9))),
+                    ("country_risk", _) => Ok(None),
+                    _ => {
+                        Err(Error::invalid_argument(json!(table), "lookup", "no table"))
+                    }
+                }
+            })
+        }
+    }
+
+    #[test]
+    fn test_async_lookup() {
+        let rule = crate::Rule::from_value(&json!({"lookup": [
+            "country_risk",
+            {"var": "country"}
+        ]}))
+        .unwrap();
+        let ctx = crate::Context {
+            async_lookup: Some(Arc::new(SlowTables)),
+            ..crate::Context::default()
+        };
+        assert_eq!(
+            block_on(rule.apply_async_with(&json!({"country": "XX"}), &ctx)).unwrap(),
+            json!(9)
+        );
+        assert_eq!(
+            block_on(rule.apply_async_with(&json!({"country": "US"}), &ctx)).unwrap(),
+            json!(null)
+        );
+        // Async lookups aren't available synchronously
+        rule.apply_with(&json!({"country": "XX"}), &ctx)
+            .unwrap_err();
+    }
+
+    #[test]
+    fn test_sync_rules() {
+        fn assert_send<T: Send>(value: T) -> T {
+            value
+        }
+        let rule = crate::Rule::from_value(&json!({"+": [1, {"var": "a"}]})).unwrap();
+        let data = json!({"a": 2});
+        // Evaluations may be run on multi-threaded executors
+        assert_eq!(
+            block_on(assert_send(rule.apply_async(&data))).unwrap(),
+            json!(3)
+        );
+    }
+}
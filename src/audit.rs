@@ -0,0 +1,264 @@
+//! Audit Records
+//!
+//! With an `Audit` set on the evaluation's `Context`, each evaluation of a
+//! rule produces an `AuditRecord` of which rule was evaluated (by its
+//! content hash), what it was evaluated against, and what it produced, so
+//! that a decision can later be traced to the exact version of the rule that
+//! made it.
+//!
+//! By default, the data is recorded as a SHA-256 hash of its JSON, so that
+//! records don't hold personal data but can still be matched against it.
+//! `Audit::with_snapshot()` records the data itself instead, with the values
+//! at the given paths redacted.
+//!
+//! Auditing a parsed `Rule` hashes the rule on each evaluation, whereas a
+//! `CompiledRule` is hashed once when compiled.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::error::Error;
+
+/// The value recorded in place of redacted data
+pub const REDACTED: &str = "[redacted]";
+
+/// A destination for audit records, e.g. an append-only log
+pub trait AuditSink: fmt::Debug + Send + Sync {
+    /// Record a finished evaluation.
+    fn record(&self, record: AuditRecord);
+}
+
+/// Records are collected in order, e.g. for tests.
+impl AuditSink for Mutex<Vec<AuditRecord>> {
+    fn record(&self, record: AuditRecord) {
+        self.lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(record);
+    }
+}
+
+/// How evaluations are audited
+#[derive(Clone, Debug)]
+pub struct Audit {
+    sink: Arc<dyn AuditSink>,
+    redact: Option<Vec<String>>,
+}
+impl Audit {
+    /// Send a record of each evaluation to the given sink, recording a hash
+    /// of the data.
+    pub fn new(sink: Arc<dyn AuditSink>) -> Self {
+        Self { sink, redact: None }
+    }
+
+    /// Record the data itself, rather than its hash, replacing the values at
+    /// the given dot-separated paths (e.g. `"applicant.ssn"`) with
+    /// `REDACTED`.
+    pub fn with_snapshot<I, S>(mut self, redact: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.redact = Some(redact.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Record the outcome of an evaluation that began at `started`.
+    pub(crate) fn record(
+        &self,
+        started: Started,
+        rule_id: Option<&str>,
+        rule_hash: String,
+        data: &Value,
+        result: &Result<Value, Error>,
+    ) {
+        self.sink.record(AuditRecord {
+            rule_id: rule_id.map(String::from),
+            rule_hash,
+            data: match &self.redact {
+                Some(paths) => AuditData::Snapshot(redacted(data, paths)),
+                None => AuditData::Hash(hash(data)),
+            },
+            result: result.as_ref().ok().cloned(),
+            error: result.as_ref().err().map(Error::to_json),
+            timestamp: started.timestamp,
+            duration: started.instant.elapsed(),
+        });
+    }
+}
+
+/// When an audited evaluation began
+pub(crate) struct Started {
+    timestamp: SystemTime,
+    instant: Instant,
+}
+impl Started {
+    pub(crate) fn now() -> Self {
+        Self {
+            timestamp: SystemTime::now(),
+            instant: Instant::now(),
+        }
+    }
+}
+
+/// The data a rule was evaluated against, as recorded
+#[derive(Clone, Debug, PartialEq)]
+pub enum AuditData {
+    /// The hex-encoded SHA-256 hash of the data's JSON
+    Hash(String),
+    /// The data, with any sensitive values redacted
+    Snapshot(Value),
+}
+
+/// A record of a single evaluation
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuditRecord {
+    /// The id of the rule, if it has one
+    pub rule_id: Option<String>,
+    /// The rule's `content_hash()`
+    pub rule_hash: String,
+    /// The data the rule was evaluated against
+    pub data: AuditData,
+    /// The result, if the evaluation succeeded
+    pub result: Option<Value>,
+    /// The error, as serialized by `Error::to_json()`, if it failed
+    pub error: Option<Value>,
+    /// When the evaluation started
+    pub timestamp: SystemTime,
+    /// How long the evaluation took
+    pub duration: Duration,
+}
+impl AuditRecord {
+    /// Serialize the record as JSON, e.g. to be written to a log.
+    ///
+    /// Times are in milliseconds since the Unix epoch, and durations in
+    /// microseconds.
+    pub fn to_json(&self) -> Value {
+        let timestamp = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis() as u64);
+        let data = match &self.data {
+            AuditData::Hash(hash) => json!({ "hash": hash }),
+            AuditData::Snapshot(snapshot) => json!({ "snapshot": snapshot }),
+        };
+        json!({
+            "rule_id": self.rule_id,
+            "rule_hash": self.rule_hash,
+            "data": data,
+            "result": self.result,
+            "error": self.error,
+            "timestamp": timestamp,
+            "duration_us": self.duration.as_micros() as u64,
+        })
+    }
+}
+
+/// The hex-encoded SHA-256 hash of a value's JSON
+fn hash(value: &Value) -> String {
+    Sha256::digest(value.to_string())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// A copy of the data, with the values at the given paths redacted
+fn redacted(data: &Value, paths: &[String]) -> Value {
+    let mut data = data.clone();
+    for path in paths {
+        redact(&mut data, &path.split('.').collect::<Vec<&str>>());
+    }
+    data
+}
+
+/// Redact the value at a path, if there is one.
+fn redact(value: &mut Value, path: &[&str]) {
+    let (key, rest) = match path.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+    let next = match value {
+        Value::Object(obj) => obj.get_mut(*key),
+        Value::Array(items) => key
+            .parse::<usize>()
+            .ok()
+            .and_then(move |idx| items.get_mut(idx)),
+        _ => None,
+    };
+    match next {
+        Some(value) if rest.is_empty() => *value = json!(REDACTED),
+        Some(value) => redact(value, rest),
+        None => {}
+    }
+}
+
+#[cfg(test)]
+mod test_audit {
+    use super::*;
+
+    use crate::{Context, Rule};
+
+    fn audited(
+        audit: impl FnOnce(Audit) -> Audit,
+    ) -> (Context, Arc<Mutex<Vec<AuditRecord>>>) {
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let ctx = Context {
+            audit: Some(audit(Audit::new(records.clone()))),
+            ..Context::default()
+        };
+        (ctx, records)
+    }
+
+    #[test]
+    fn test_audit() {
+        let (ctx, records) = audited(|audit| audit);
+        let rule =
+            Rule::from_value(&json!({"if": [{"var": "ok"}, "yes", {"/": [1, "x"]}]}))
+                .unwrap()
+                .with_id("check");
+        let data = json!({"ok": true});
+        assert_eq!(rule.apply_with(&data, &ctx).unwrap(), json!("yes"));
+        let compiled = rule.compile().unwrap();
+        compiled
+            .apply_with(&json!({"ok": false}), &ctx)
+            .unwrap_err();
+
+        let records = records.lock().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].rule_id.as_deref(), Some("check"));
+        assert_eq!(records[0].rule_hash, rule.content_hash());
+        assert_eq!(records[1].rule_hash, rule.content_hash());
+        assert_eq!(records[0].data, AuditData::Hash(hash(&data)));
+        assert_eq!(records[0].result, Some(json!("yes")));
+        assert_eq!(records[0].error, None);
+        assert_eq!(records[1].result, None);
+        assert!(records[1].error.as_ref().unwrap()["code"].is_string());
+
+        let record = records[0].to_json();
+        assert_eq!(record["data"]["hash"], json!(hash(&data)));
+        assert!(record["duration_us"].is_u64());
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let (ctx, records) = audited(|audit| {
+            audit.with_snapshot(["applicant.ssn", "scores.1", "missing.key"])
+        });
+        let rule = Rule::from_value(&json!({"var": "applicant.name"})).unwrap();
+        let data = json!({
+            "applicant": {"name": "Ada", "ssn": "123-45-6789"},
+            "scores": [1, 2]
+        });
+        rule.apply_with(&data, &ctx).unwrap();
+        assert_eq!(
+            records.lock().unwrap()[0].data,
+            AuditData::Snapshot(json!({
+                "applicant": {"name": "Ada", "ssn": REDACTED},
+                "scores": [1, REDACTED]
+            }))
+        );
+    }
+}
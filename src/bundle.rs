@@ -0,0 +1,302 @@
+//! Rule Bundles
+//!
+//! A bundle is a document of named rules, along with the functions they
+//! share, so that a complex decision may be split into rules that refer to
+//! one another rather than repeating them inline:
+//!
+//! ```json
+//! {
+//!   "functions": [{"defn": ["adult", ["age"], {">=": [{"param": "age"}, 18]}]}],
+//!   "rules": {
+//!     "eligible": {"adult": [{"var": "age"}]},
+//!     "approved": {"and": [{"$rule": "eligible"}, {"var": "verified"}]}
+//!   }
+//! }
+//! ```
+//!
+//! Besides `rules`, a bundle is a module document, as read by
+//! `Namespace::from_value()`, though its `functions` may be omitted.
+//!
+//! `{"$rule": "name"}` evaluates the named rule against the same data, and
+//! may be used anywhere in the bundle's rules and functions. Rules may not
+//! refer to one another in a cycle.
+//!
+//! A bundle may also hold test cases for its rules, keyed by rule name under
+//! `$tests`, as described in `TestCase`, which `Bundle::run_tests()` runs.
+
+use alloc::collections::BTreeMap;
+use core::convert::TryFrom;
+
+use serde_json::{Map, Value};
+
+use crate::collections::HashMap;
+use crate::error::Error;
+use crate::namespace::{find_cycles, Namespace, Scope};
+use crate::op::func::{Function, Identifier};
+use crate::prelude::*;
+use crate::rule::testing::{TestCase, TestResult, TESTS};
+use crate::rule::Rule;
+use crate::Parser;
+
+/// The operator referring to another rule in the same bundle
+pub(crate) const RULE_REF: &str = "$rule";
+
+/// The name of the function holding a bundle's rule
+pub(crate) fn rule_function(name: &str) -> String {
+    format!("{}:{}", RULE_REF, name)
+}
+
+/// The name of the rule a value refers to, if it is a rule reference
+pub(crate) fn rule_ref(value: &Value) -> Option<&str> {
+    match value {
+        Value::Object(obj) if obj.len() == 1 => obj.get(RULE_REF)?.as_str(),
+        _ => None,
+    }
+}
+
+/// Collect the names of the rules an expression refers to.
+fn rule_refs<'a>(value: &'a Value, refs: &mut Vec<&'a str>) {
+    if let Some(name) = rule_ref(value) {
+        return refs.push(name);
+    }
+    match value {
+        Value::Array(items) => items.iter().for_each(|item| rule_refs(item, refs)),
+        Value::Object(obj) => obj.values().for_each(|item| rule_refs(item, refs)),
+        _ => {}
+    }
+}
+
+/// A collection of named rules and the functions they share
+#[derive(Debug)]
+pub struct Bundle {
+    namespace: Namespace,
+    rules: BTreeMap<String, Rule>,
+}
+impl Bundle {
+    /// The key of a bundle's rules
+    pub const RULES: &'static str = "rules";
+
+    /// Parse a bundle document.
+    ///
+    /// Fails if any rule or function is invalid, or if a rule refers to one
+    /// that isn't in the bundle.
+    pub fn from_value(value: &Value) -> Result<Self, Error> {
+        let invalid = |value: &Value, reason: &str| Error::InvalidData {
+            value: value.clone(),
+            reason: reason.into(),
+        };
+        let doc = value
+            .as_object()
+            .ok_or_else(|| invalid(value, "Expected a bundle object"))?;
+        let rules =
+            doc.get(Self::RULES)
+                .and_then(Value::as_object)
+                .ok_or_else(|| {
+                    invalid(
+                        value,
+                        &format!(
+                            "Expected an object of named rules at '{}'",
+                            Self::RULES
+                        ),
+                    )
+                })?;
+
+        let mut module: Map<String, Value> = doc
+            .iter()
+            .filter(|(key, _)| !matches!(key.as_str(), Self::RULES | TESTS))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        module
+            .entry(Namespace::FUNCTIONS)
+            .or_insert_with(|| Value::Array(Vec::new()));
+        let mut namespace = Namespace::from_value(&Value::Object(module))?;
+
+        let mut refs = HashMap::new();
+        for (name, rule) in rules {
+            Identifier::try_from(&Value::from(name.as_str()))?;
+            let def = serde_json::json!({"defn": [rule_function(name), [], rule]});
+            let func =
+                Function::from_value(&def, &Scope::default())?.ok_or_else(|| {
+                    Error::UnexpectedError(format!("Rule '{}' is not a function", name))
+                })?;
+            namespace.insert(func);
+            let mut names = Vec::new();
+            rule_refs(rule, &mut names);
+            if let Some(unknown) = names.iter().find(|name| !rules.contains_key(**name))
+            {
+                return Err(Error::InvalidOperation {
+                    key: RULE_REF.into(),
+                    reason: format!(
+                        "rule '{}' refers to '{}', which is not in the bundle",
+                        name, unknown
+                    ),
+                });
+            }
+            refs.insert(name.as_str(), names);
+        }
+        if let Some(cycle) = find_cycles(&refs).first() {
+            return Err(Error::InvalidOperation {
+                key: RULE_REF.into(),
+                reason: format!(
+                    "rules refer to one another in a cycle: {}",
+                    cycle.join(" -> ")
+                ),
+            });
+        }
+
+        let mut tests = match doc.get(TESTS) {
+            Some(Value::Object(tests)) => tests.clone(),
+            Some(other) => {
+                return Err(invalid(
+                    other,
+                    &format!("Expected an object of test cases by rule at '{}'", TESTS),
+                ))
+            }
+            None => Map::new(),
+        };
+        if let Some(unknown) = tests.keys().find(|name| !rules.contains_key(*name)) {
+            return Err(invalid(
+                value,
+                &format!(
+                    "There are tests for '{}', which is not in the bundle",
+                    unknown
+                ),
+            ));
+        }
+
+        let rules = rules
+            .iter()
+            .map(|(name, rule)| {
+                let cases = match tests.remove(name) {
+                    Some(cases) => TestCase::from_values(&cases)?,
+                    None => Vec::new(),
+                };
+                namespace.parse(rule).map(|rule| {
+                    (name.clone(), rule.with_id(name.as_str()).with_tests(cases))
+                })
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok(Self { namespace, rules })
+    }
+
+    /// Run the test cases of every rule, sorted by rule name and then by
+    /// their order in the bundle.
+    pub fn run_tests(&self) -> Vec<TestResult> {
+        self.rules.values().flat_map(Rule::run_tests).collect()
+    }
+
+    /// Retrieve a rule by name.
+    pub fn rule(&self, name: &str) -> Option<&Rule> {
+        self.rules.get(name)
+    }
+
+    /// The names of the bundle's rules, in order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.rules.keys().map(String::as_str)
+    }
+
+    /// The namespace holding the bundle's functions, with which other rules
+    /// may be parsed that call them or refer to the bundle's rules
+    pub fn namespace(&self) -> &Namespace {
+        &self.namespace
+    }
+}
+
+#[cfg(test)]
+mod test_bundle {
+    use super::*;
+    use serde_json::json;
+
+    fn bundle() -> Value {
+        json!({
+            "functions": [{"defn": ["adult", ["age"], {">=": [{"param": "age"}, 18]}]}],
+            "rules": {
+                "eligible": {"adult": [{"var": "age"}]},
+                "approved": {"and": [{"$rule": "eligible"}, {"var": "verified"}]},
+                "label": {"if": [{"$rule": "approved"}, "approved", "declined"]}
+            }
+        })
+    }
+
+    #[test]
+    fn test_bundle() {
+        let bundle = Bundle::from_value(&bundle()).unwrap();
+        assert_eq!(
+            bundle.names().collect::<Vec<&str>>(),
+            vec!["approved", "eligible", "label"]
+        );
+        let label = bundle.rule("label").unwrap();
+        assert_eq!(label.id(), Some("label"));
+        let data = json!({"age": 20, "verified": true});
+        assert_eq!(label.apply(&data).unwrap(), json!("approved"));
+        assert_eq!(
+            label.compile().unwrap().apply(&data).unwrap(),
+            json!("approved")
+        );
+        let data = json!({"age": 17, "verified": true});
+        assert_eq!(label.apply(&data).unwrap(), json!("declined"));
+        assert!(bundle.rule("adult").is_none());
+
+        // Other rules may refer to the bundle's
+        let rule = bundle
+            .namespace()
+            .parse(&json!({"!": {"$rule": "eligible"}}))
+            .unwrap();
+        assert_eq!(rule.apply(&data).unwrap(), json!(true));
+        bundle.namespace().check().unwrap();
+
+        Bundle::from_value(&json!({"rules": {"a": 1}})).unwrap();
+    }
+
+    #[test]
+    fn test_bundle_tests() {
+        let with_tests = |tests: Value| {
+            let mut doc = bundle();
+            doc["$tests"] = tests;
+            Bundle::from_value(&doc)
+        };
+        let parsed = with_tests(json!({
+            "label": [
+                {"data": {"age": 20, "verified": true}, "expect": "approved"},
+                {"data": {"age": 20}, "expect": "approved"}
+            ],
+            "eligible": [{"name": "minor", "data": {"age": 17}, "expect": false}]
+        }))
+        .unwrap();
+        assert!(parsed.rule("approved").unwrap().tests().is_empty());
+        let results = parsed.run_tests();
+        assert_eq!(
+            results
+                .iter()
+                .map(|result| (result.rule.as_deref(), result.index, result.passed))
+                .collect::<Vec<_>>(),
+            vec![
+                (Some("eligible"), 0, true),
+                (Some("label"), 0, true),
+                (Some("label"), 1, false),
+            ]
+        );
+
+        let error = |tests: Value| with_tests(tests).unwrap_err().to_string();
+        assert!(error(json!([])).contains("Expected an object of test cases"));
+        assert!(error(json!({"nope": []})).contains("tests for 'nope'"));
+        assert!(error(json!({"label": [{"data": 1}]})).contains("'expect' or 'error'"));
+    }
+
+    #[test]
+    fn test_invalid_bundles() {
+        let error = |value: Value| Bundle::from_value(&value).unwrap_err().to_string();
+        assert!(error(json!({"functions": []}))
+            .contains("Expected an object of named rules"));
+        assert!(error(json!({"rules": {"a": {"$rule": "b"}}}))
+            .contains("rule 'a' refers to 'b', which is not in the bundle"));
+        assert!(error(json!({"rules": {
+            "a": {"$rule": "b"},
+            "b": {"!": {"$rule": "a"}}
+        }}))
+        .contains("rules refer to one another in a cycle: a -> b -> a"));
+        assert!(error(json!({"rules": {"a b": 1}})).contains("Invalid identifier"));
+        assert!(error(json!({"rules": {"a": {"nope": []}}}))
+            .contains("Unknown operator 'nope'"));
+    }
+}
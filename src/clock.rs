@@ -0,0 +1,58 @@
+//! Clocks
+//!
+//! The `within_last` operator compares dates with the current time of the
+//! `Clock` set on the evaluation's `Context`. Use `FixedClock` for
+//! reproducible results, e.g. in tests. Without a clock, the system's clock
+//! is used, which requires the `std` feature.
+//!
+//! Since its result differs between evaluations, `within_last` may not be
+//! used in rules parsed with `ParseOptions::deterministic`.
+
+use core::fmt;
+
+use crate::error::Error;
+#[cfg(feature = "std")]
+use crate::prelude::*;
+
+/// A source of the current time
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// The current time, in milliseconds since the Unix epoch
+    fn now(&self) -> Result<i64, Error>;
+}
+
+/// A clock that is always at the same time
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FixedClock {
+    millis: i64,
+}
+impl FixedClock {
+    /// Construct a clock stopped at the given number of milliseconds since
+    /// the Unix epoch.
+    pub fn new(millis: i64) -> Self {
+        Self { millis }
+    }
+}
+impl Clock for FixedClock {
+    fn now(&self) -> Result<i64, Error> {
+        Ok(self.millis)
+    }
+}
+
+/// The system's clock
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct SystemClock;
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+    fn now(&self) -> Result<i64, Error> {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as i64)
+            .map_err(|err| {
+                Error::UnexpectedError(format!(
+                    "The system clock is before 1970: {}",
+                    err
+                ))
+            })
+    }
+}
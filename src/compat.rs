@@ -0,0 +1,147 @@
+//! JsonLogic Compatibility Tests
+//!
+//! Run test suites in the format of the shared JsonLogic tests at
+//! jsonlogic.com/tests.json: an array of `[rule, data, expected]` cases,
+//! interspersed with strings naming the section of the cases that follow.
+//!
+//! Forks and custom builds of the engine can check their parity with the
+//! official operators via `compat::run(&compat::official_suite())`.
+
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::{Context, Error, Rule};
+
+/// The shared JsonLogic test suite, as of this release
+pub fn official_suite() -> Value {
+    serde_json::from_str(include_str!("../tests/data/tests.json"))
+        .expect("the bundled test suite is valid JSON")
+}
+
+/// A single test case
+#[derive(Clone, Debug, PartialEq)]
+pub struct Case {
+    /// The section of the suite the case is in, if any
+    pub section: Option<String>,
+    pub logic: Value,
+    pub data: Value,
+    pub expected: Value,
+}
+
+/// Load the cases from a test suite.
+pub fn load(suite: &Value) -> Result<Vec<Case>, Error> {
+    let entries = suite.as_array().ok_or_else(|| Error::InvalidData {
+        value: suite.clone(),
+        reason: "A test suite must be an array".into(),
+    })?;
+    let mut section = None;
+    let mut cases = Vec::new();
+    for entry in entries {
+        match entry {
+            Value::String(name) => {
+                section = Some(name.trim_start_matches('#').trim().to_string())
+            }
+            Value::Array(case) if case.len() == 3 => cases.push(Case {
+                section: section.clone(),
+                logic: case[0].clone(),
+                data: case[1].clone(),
+                expected: case[2].clone(),
+            }),
+            _ => {
+                return Err(Error::InvalidData {
+                    value: entry.clone(),
+                    reason: "Test cases must be [rule, data, expected] arrays".into(),
+                })
+            }
+        }
+    }
+    Ok(cases)
+}
+
+/// A case whose result wasn't as expected
+#[derive(Debug)]
+pub struct Failure {
+    pub case: Case,
+    /// The actual result of applying the case's rule to its data
+    pub result: Result<Value, Error>,
+}
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(section) = &self.case.section {
+            write!(f, "[{}] ", section)?;
+        }
+        write!(
+            f,
+            "{} with data {}: expected {}, ",
+            self.case.logic, self.case.data, self.case.expected
+        )?;
+        match &self.result {
+            Ok(value) => write!(f, "got {}", value),
+            Err(err) => write!(f, "got error: {}", err),
+        }
+    }
+}
+
+/// The results of running a test suite
+#[derive(Debug, Default)]
+pub struct Report {
+    /// The number of cases that passed
+    pub passed: usize,
+    pub failures: Vec<Failure>,
+}
+impl Report {
+    /// Whether every case passed
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Run every case in a test suite.
+pub fn run(suite: &Value) -> Result<Report, Error> {
+    run_with(suite, &Context::default())
+}
+
+/// Run every case in a test suite, with the given context.
+pub fn run_with(suite: &Value, ctx: &Context) -> Result<Report, Error> {
+    let mut report = Report::default();
+    for case in load(suite)? {
+        let result = Rule::from_value(&case.logic)
+            .and_then(|rule| rule.apply_with(&case.data, ctx));
+        match result {
+            Ok(ref value) if *value == case.expected => report.passed += 1,
+            _ => report.failures.push(Failure { case, result }),
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test_compat {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_run() {
+        let suite = json!([
+            "# Equality",
+            [{"==": [1, 1]}, {}, true],
+            [{"==": [1, 1]}, {}, false],
+            "# Data",
+            [{"var": "a"}, {"a": 2}, 2],
+            [{"missing_some": [1]}, {}, null]
+        ]);
+        let report = run(&suite).unwrap();
+        assert_eq!(report.passed, 2);
+        assert!(!report.is_success());
+        assert_eq!(
+            report.failures[0].to_string(),
+            "[Equality] {\"==\":[1,1]} with data {}: expected false, got true"
+        );
+        assert_eq!(report.failures[1].case.section.as_deref(), Some("Data"));
+        report.failures[1].result.as_ref().unwrap_err();
+
+        run(&json!({})).unwrap_err();
+        run(&json!([[1, 2]])).unwrap_err();
+    }
+}
@@ -0,0 +1,723 @@
+//! Evaluation Context
+//!
+//! The public `Context` holds options and hooks for evaluating rules. While a
+//! rule is being evaluated, the context is wrapped in an `Env`, which carries
+//! any state specific to that evaluation.
+
+use alloc::sync::Arc;
+#[cfg(any(feature = "tracing", not(feature = "std")))]
+use core::cell::RefCell;
+use core::fmt;
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::sync::Mutex;
+
+use serde_json::Value;
+
+#[cfg(feature = "async")]
+use crate::asynchronous::{AsyncOperator, AsyncState, LookupOperator};
+#[cfg(feature = "std")]
+use crate::audit::Audit;
+use crate::clock::Clock;
+#[cfg(feature = "std")]
+use crate::clock::SystemClock;
+use crate::collections::HashMap;
+use crate::error::Error;
+#[cfg(feature = "crypto")]
+use crate::keys::HmacKeys;
+use crate::locale::Locale;
+#[cfg(feature = "async")]
+use crate::lookup::AsyncLookupResolver;
+use crate::lookup::LookupResolver;
+use crate::money::ExchangeRates;
+use crate::numeric::{JsNumeric, NumericBackend};
+use crate::op::array::Reduced;
+use crate::op::func::{check_condition, tail, FunctionBody, Tail};
+use crate::op::NumParams;
+use crate::prelude::*;
+#[cfg(feature = "std")]
+use crate::profiler::Profiler;
+#[cfg(feature = "std")]
+use crate::random::OsRandom;
+use crate::random::RandomSource;
+#[cfg(feature = "std")]
+use crate::registry::{Evaluation, Registry};
+use crate::resolver::DataResolver;
+#[cfg(feature = "std")]
+use crate::rule::coverage::Branches;
+use crate::units::Units;
+use crate::value::Parsed;
+use crate::NULL;
+
+/// Options and hooks used when evaluating a rule
+#[derive(Clone, Debug, Default)]
+pub struct Context {
+    /// A registry in which to record each evaluation while it runs. Use
+    /// `Registry::global()` for the process-wide registry.
+    #[cfg(feature = "std")]
+    pub registry: Option<Arc<Registry>>,
+    /// The semantics with which to evaluate rules. Defaults to
+    /// `SemanticsVersion::V1`.
+    pub semantics: SemanticsVersion,
+    /// The implementation of numeric operations. Defaults to `JsNumeric`.
+    pub numeric: Option<Arc<dyn NumericBackend>>,
+    /// A profiler in which to record the execution of each operator
+    #[cfg(feature = "std")]
+    pub profiler: Option<Arc<Profiler>>,
+    /// The tables available to the `lookup` operator
+    pub lookup: Option<Arc<dyn LookupResolver>>,
+    /// The tables available to the `lookup` operator when evaluating with
+    /// `Rule::apply_async()`. Only used if `lookup` is not set.
+    #[cfg(feature = "async")]
+    pub async_lookup: Option<Arc<dyn AsyncLookupResolver>>,
+    /// The exchange rates available to the `money_convert` operator
+    pub exchange_rates: Option<Arc<dyn ExchangeRates>>,
+    /// The source of random numbers for the `random`, `random_int`, and
+    /// `uuid` operators. Defaults to the operating system's generator.
+    pub random: Option<Arc<dyn RandomSource>>,
+    /// The clock the `within_last` operator compares dates with. Defaults
+    /// to the system's clock.
+    pub clock: Option<Arc<dyn Clock>>,
+    /// The locale whose separators the `format` operator writes numbers
+    /// with. Defaults to US English conventions.
+    pub locale: Option<Arc<Locale>>,
+    /// The keys available to the `hmac_sha256` operator, by name
+    #[cfg(feature = "crypto")]
+    pub hmac_keys: Option<Arc<HmacKeys>>,
+    /// The units the `convert` operator converts between. Defaults to
+    /// `Units::default()`.
+    pub units: Option<Arc<Units>>,
+    /// Where to send a record of each evaluation
+    #[cfg(feature = "std")]
+    pub audit: Option<Audit>,
+    /// Whether to skip checking the `pre` and `post` conditions of
+    /// functions' contracts
+    pub skip_contracts: bool,
+    /// The maximum number of results of `defmemo` functions to cache during
+    /// each evaluation. Once the cache is full, further results aren't
+    /// cached. Defaults to no limit.
+    pub memo_cache_size: Option<usize>,
+    /// The maximum number of elements of an array generated by an operator
+    /// like `range`. Defaults to `DEFAULT_MAX_ARRAY_SIZE`.
+    pub max_array_size: Option<usize>,
+    /// The maximum depth to which calls to functions may be nested, past
+    /// which evaluation fails rather than overflowing the stack. Calls a
+    /// function makes to itself in tail position don't count towards it.
+    /// Defaults to `DEFAULT_MAX_CALL_DEPTH`.
+    pub max_call_depth: Option<usize>,
+}
+impl Context {
+    /// Construct a context with the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// A version of the crate's evaluation semantics
+///
+/// Any change to the result of evaluating an existing rule is introduced under
+/// a new semantics version, so that stored rules keep producing the same
+/// results across crate upgrades until they are opted into the new behavior.
+/// The snapshots under `tests/data/snapshots` record the results expected
+/// under each version.
+///
+/// A stored rule may be pinned to the version it was written for by wrapping
+/// it, as in `{"$version": "1", "rule": {...}}`, so that it's evaluated with
+/// that version whatever the context's `semantics`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum SemanticsVersion {
+    /// The semantics of the 0.x releases of the crate
+    #[default]
+    V1,
+    /// The semantics of `V1`, with these changes:
+    ///
+    /// - The segments `*` and `**` of dotted `var` and `missing` paths are
+    ///   wildcards rather than keys
+    /// - Their segments of the form `start:end`, where each bound is empty
+    ///   or an integer, are slices rather than keys
+    /// - Within the expression of an iterating operator, `{"var": "#index"}`
+    ///   is the index of the current element, rather than its `#index` key
+    /// - Variables starting with `../` refer to the data outside of the
+    ///   innermost iterating operator, rather than to keys named `""`
+    /// - A `var` default is evaluated once, so a default whose value is
+    ///   itself a rule is the result, rather than being evaluated again
+    V2,
+}
+impl SemanticsVersion {
+    /// The most recent semantics version
+    pub const LATEST: SemanticsVersion = SemanticsVersion::V2;
+}
+impl fmt::Display for SemanticsVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SemanticsVersion::V1 => write!(f, "1"),
+            SemanticsVersion::V2 => write!(f, "2"),
+        }
+    }
+}
+impl FromStr for SemanticsVersion {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1" => Ok(SemanticsVersion::V1),
+            "2" => Ok(SemanticsVersion::V2),
+            _ => Err(Error::UnknownSemanticsVersion(s.into())),
+        }
+    }
+}
+
+/// The number of elements an operator like `range` may generate if the
+/// context doesn't set `max_array_size`
+pub const DEFAULT_MAX_ARRAY_SIZE: usize = 1_000_000;
+
+/// The depth to which calls to functions may be nested if the context
+/// doesn't set `max_call_depth`, which leaves room for the calls within a
+/// thread's 2MiB stack even in unoptimized builds
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 64;
+
+/// The environment for a single evaluation of a rule
+#[derive(Clone, Default)]
+pub struct Env<'a> {
+    #[cfg(feature = "std")]
+    evaluation: Option<Arc<Evaluation>>,
+    #[cfg(feature = "std")]
+    profiler: Option<Arc<Profiler>>,
+    numeric: Option<Arc<dyn NumericBackend>>,
+    lookup: Option<Arc<dyn LookupResolver>>,
+    #[cfg(feature = "async")]
+    async_lookup: Option<Arc<dyn AsyncOperator>>,
+    exchange_rates: Option<Arc<dyn ExchangeRates>>,
+    random: Option<Arc<dyn RandomSource>>,
+    clock: Option<Arc<dyn Clock>>,
+    locale: Option<Arc<Locale>>,
+    #[cfg(feature = "crypto")]
+    hmac_keys: Option<Arc<HmacKeys>>,
+    units: Option<Arc<Units>>,
+    functions: Option<Arc<HashMap<String, FunctionBody>>>,
+    params: Arc<[Value]>,
+    /// The index of the element an iterating operator is evaluating an
+    /// expression against, for `{"var": "#index"}`
+    index: Option<usize>,
+    /// The data outside of the innermost iterating operator, and the
+    /// environment it was evaluated in, for `../` variables
+    outer: Option<(&'a dyn DataResolver, &'a Env<'a>)>,
+    /// Where `reduced` records the values it wraps, while a `reduce_while`
+    /// is evaluating its expression
+    reduced: Option<&'a Reduced>,
+    semantics: SemanticsVersion,
+    skip_contracts: bool,
+    max_array_size: Option<usize>,
+    max_call_depth: Option<usize>,
+    /// The number of function calls the current one is nested in
+    depth: usize,
+    memo: Arc<MemoCache>,
+    resolver: Option<&'a dyn DataResolver>,
+    #[cfg(feature = "async")]
+    asynchronous: Option<&'a AsyncState>,
+    #[cfg(feature = "std")]
+    branches: Option<&'a Branches>,
+    #[cfg(feature = "tracing")]
+    path: RefCell<String>,
+}
+impl<'a> Env<'a> {
+    /// Construct the environment for an evaluation of the given rule.
+    #[cfg_attr(
+        not(feature = "std"),
+        allow(unused_variables, clippy::arc_with_non_send_sync)
+    )]
+    pub(crate) fn new(ctx: &Context, rule_id: Option<&str>) -> Self {
+        Self {
+            #[cfg(feature = "std")]
+            evaluation: ctx
+                .registry
+                .as_ref()
+                .map(|registry| registry.register(rule_id)),
+            #[cfg(feature = "std")]
+            profiler: ctx.profiler.clone(),
+            numeric: ctx.numeric.clone(),
+            lookup: ctx.lookup.clone(),
+            #[cfg(feature = "async")]
+            async_lookup: ctx.async_lookup.clone().map(|resolver| {
+                Arc::new(LookupOperator(resolver)) as Arc<dyn AsyncOperator>
+            }),
+            exchange_rates: ctx.exchange_rates.clone(),
+            random: ctx.random.clone(),
+            clock: ctx.clock.clone(),
+            locale: ctx.locale.clone(),
+            #[cfg(feature = "crypto")]
+            hmac_keys: ctx.hmac_keys.clone(),
+            units: ctx.units.clone(),
+            semantics: ctx.semantics,
+            skip_contracts: ctx.skip_contracts,
+            max_array_size: ctx.max_array_size,
+            max_call_depth: ctx.max_call_depth,
+            memo: Arc::new(MemoCache {
+                limit: ctx.memo_cache_size,
+                ..MemoCache::default()
+            }),
+            ..Self::default()
+        }
+    }
+
+    /// Evaluate with the given semantics version, rather than the
+    /// context's, if there is one.
+    pub(crate) fn with_semantics(self, semantics: Option<SemanticsVersion>) -> Self {
+        Self {
+            semantics: semantics.unwrap_or(self.semantics),
+            ..self
+        }
+    }
+
+    /// The semantics version to evaluate with
+    pub(crate) fn semantics(&self) -> SemanticsVersion {
+        self.semantics
+    }
+
+    /// Make the given compiled function bodies available to call.
+    pub(crate) fn with_functions(
+        mut self,
+        functions: Option<Arc<HashMap<String, FunctionBody>>>,
+    ) -> Self {
+        self.functions = functions;
+        self
+    }
+
+    /// Look up data through the given resolver, rather than in the data
+    /// passed to the rule.
+    pub(crate) fn with_resolver(self, resolver: &'a dyn DataResolver) -> Self {
+        Self {
+            resolver: Some(resolver),
+            ..self
+        }
+    }
+
+    /// The resolver through which to look up data, if data isn't to be
+    /// looked up in the data passed to each operation
+    pub(crate) fn resolver(&self) -> Option<&'a dyn DataResolver> {
+        self.resolver
+    }
+
+    /// Record the results of async operations in the given state.
+    #[cfg(feature = "async")]
+    pub(crate) fn with_async(self, state: &'a AsyncState) -> Self {
+        Self {
+            asynchronous: Some(state),
+            ..self
+        }
+    }
+
+    /// The results of async operations, if evaluating asynchronously
+    #[cfg(feature = "async")]
+    pub(crate) fn asynchronous(&self) -> Option<&'a AsyncState> {
+        self.asynchronous
+    }
+
+    /// Record the branches taken in the given coverage.
+    #[cfg(feature = "std")]
+    pub(crate) fn with_branches(self, branches: &'a Branches) -> Self {
+        Self {
+            branches: Some(branches),
+            ..self
+        }
+    }
+
+    /// Record that the argument at `index` of a lazy operation became its
+    /// result, if branch coverage is being recorded.
+    #[cfg_attr(not(feature = "std"), allow(unused_variables))]
+    pub(crate) fn branch_taken(&self, args: &[Parsed], index: usize) {
+        #[cfg(feature = "std")]
+        if let Some(branches) = self.branches {
+            branches.taken(args, index)
+        }
+    }
+
+    /// The environment for evaluating an expression against new data, e.g.
+    /// an element of an array in `map`
+    pub(crate) fn rebind(&self) -> Self {
+        Self {
+            resolver: None,
+            ..self.clone()
+        }
+    }
+
+    /// The number of arguments a function that may be called takes, if there
+    /// is one with the given name
+    pub(crate) fn function_arity(&self, name: &str) -> Option<NumParams> {
+        self.functions
+            .as_ref()
+            .and_then(|functions| functions.get(name))
+            .map(|body| body.signature.num_params())
+    }
+
+    /// Call a function, binding the given arguments to its parameters, and
+    /// evaluating the defaults of any that were omitted.
+    pub(crate) fn call(
+        &self,
+        name: &str,
+        data: &Value,
+        arguments: Vec<Value>,
+    ) -> Result<Value, Error> {
+        let body = self
+            .functions
+            .as_ref()
+            .and_then(|functions| functions.get(name))
+            .ok_or_else(|| {
+                Error::UnexpectedError(format!(
+                    "Function '{}' was not compiled into the rule",
+                    name
+                ))
+            })?;
+        let bind = |env: &Self, arguments| {
+            body.signature.bind(arguments, |idx, bound| {
+                body.defaults[idx]
+                    .evaluate(data, &env.with_params(bound.to_vec()))
+                    .map(Value::from)
+            })
+        };
+        let mut env = self.calling(name, bind(self, arguments)?)?;
+        // Calls the function makes to itself in tail position are made in a
+        // loop, so that they don't use any more of the stack
+        if !body.memoize && !body.has_contract() {
+            loop {
+                match tail(&body.expression, name, data, &env)? {
+                    Tail::Value(result) => return Ok(result),
+                    Tail::Call(arguments) => {
+                        env = env.with_params(bind(&env, arguments)?)
+                    }
+                }
+            }
+        }
+        let (data, env) = env.for_body(body.memoize, data);
+        env.memoized(name, body.memoize, || {
+            let check = env.check_contracts();
+            if let (Some(pre), true) = (&body.pre, check) {
+                let outcome = pre.evaluate(data, &env)?;
+                check_condition(name, "pre", &outcome, &env, None)?;
+            }
+            let result = Value::from(body.expression.evaluate(data, &env)?);
+            if let (Some(post), true) = (&body.post, check) {
+                let outcome = post.evaluate(&result, &env.rebind())?;
+                check_condition(name, "post", &outcome, &env, Some(&result))?;
+            }
+            Ok(result)
+        })
+    }
+
+    /// Call a function whose arguments are bound in this environment,
+    /// returning its cached result if it is memoized and has already been
+    /// called with the same arguments.
+    pub(crate) fn memoized<F>(
+        &self,
+        name: &str,
+        memoize: bool,
+        call: F,
+    ) -> Result<Value, Error>
+    where
+        F: FnOnce() -> Result<Value, Error>,
+    {
+        if !memoize {
+            return call();
+        }
+        let key = (
+            name.to_string(),
+            Value::from(self.params.to_vec()).to_string(),
+        );
+        if let Some(result) = self.memo.get(&key) {
+            return Ok(result);
+        }
+        let result = call()?;
+        self.memo.insert(key, result.clone());
+        Ok(result)
+    }
+
+    /// The environment for the body of a function, with the given arguments
+    /// bound to its parameters
+    pub(crate) fn with_params(&self, arguments: Vec<Value>) -> Self {
+        Self {
+            params: arguments.into(),
+            ..self.clone()
+        }
+    }
+
+    /// The environment for the body of a function called from this one, with
+    /// the given arguments bound to its parameters, or an error if calls
+    /// would be nested deeper than the context allows
+    pub(crate) fn calling(
+        &self,
+        name: &str,
+        arguments: Vec<Value>,
+    ) -> Result<Self, Error> {
+        let limit = self.max_call_depth.unwrap_or(DEFAULT_MAX_CALL_DEPTH);
+        if self.depth >= limit {
+            return Err(Error::CallDepthExceeded {
+                function: name.to_string(),
+                limit,
+            });
+        }
+        Ok(Self {
+            params: arguments.into(),
+            depth: self.depth + 1,
+            ..self.clone()
+        })
+    }
+
+    /// The data and environment to evaluate the body of a function with.
+    /// Memoized functions are evaluated without the caller's data, so that
+    /// their results depend only on their arguments, which their cached
+    /// results are keyed by.
+    pub(crate) fn for_body(self, memoize: bool, data: &Value) -> (&Value, Self) {
+        match memoize {
+            true => (
+                &NULL,
+                Self {
+                    resolver: None,
+                    index: None,
+                    outer: None,
+                    ..self
+                },
+            ),
+            false => (data, self),
+        }
+    }
+
+    /// The environment for an iterating operator to evaluate an expression
+    /// against each element of an array, from which `../` variables refer to
+    /// the data the operator was evaluated against
+    pub(crate) fn within<'b>(&'b self, data: &'b Value) -> Env<'b> {
+        let env: Env<'b> = self.clone();
+        Env {
+            resolver: None,
+            index: None,
+            outer: Some((self.resolver().unwrap_or(data), self)),
+            ..env
+        }
+    }
+
+    /// Record the values `reduced` wraps in the given slot.
+    pub(crate) fn with_reduced(self, reduced: &'a Reduced) -> Self {
+        Self {
+            reduced: Some(reduced),
+            ..self
+        }
+    }
+
+    /// Where `reduced` records the values it wraps, if anywhere
+    pub(crate) fn reduced(&self) -> Option<&'a Reduced> {
+        self.reduced
+    }
+
+    /// The data `levels` iterating operators out, and the environment it was
+    /// evaluated in
+    pub(crate) fn outer(
+        &self,
+        levels: usize,
+    ) -> Option<(&'a dyn DataResolver, &'a Env<'a>)> {
+        let (data, env) = self.outer?;
+        match levels {
+            0 | 1 => Some((data, env)),
+            _ => env.outer(levels - 1),
+        }
+    }
+
+    /// The environment for evaluating an expression against the element at
+    /// `index` of an array, e.g. in `map`
+    pub(crate) fn at_index(&self, index: usize) -> Self {
+        Self {
+            index: Some(index),
+            ..self.clone()
+        }
+    }
+
+    /// The index of the element being iterated over, if any
+    pub(crate) fn index(&self) -> Option<usize> {
+        self.index
+    }
+
+    /// The argument bound to the parameter at `index` of the current function
+    pub(crate) fn param(&self, index: usize) -> Option<&Value> {
+        self.params.get(index)
+    }
+
+    /// The arguments bound to the parameters of the current function
+    pub(crate) fn params(&self) -> &[Value] {
+        &self.params
+    }
+
+    /// Whether to check the contracts of called functions
+    pub(crate) fn check_contracts(&self) -> bool {
+        !self.skip_contracts
+    }
+
+    /// The maximum number of elements of a generated array
+    pub(crate) fn max_array_size(&self) -> usize {
+        self.max_array_size.unwrap_or(DEFAULT_MAX_ARRAY_SIZE)
+    }
+
+    /// The tables available to the `lookup` operator
+    pub(crate) fn lookup(&self) -> Option<&dyn LookupResolver> {
+        self.lookup.as_deref()
+    }
+
+    /// The tables available to the `lookup` operator when evaluating
+    /// asynchronously, as an async operator
+    #[cfg(feature = "async")]
+    pub(crate) fn async_lookup(&self) -> Option<&Arc<dyn AsyncOperator>> {
+        self.async_lookup.as_ref()
+    }
+
+    /// The exchange rates for the `money_convert` operator
+    pub(crate) fn exchange_rates(&self) -> Option<&dyn ExchangeRates> {
+        self.exchange_rates.as_deref()
+    }
+
+    /// The source of random numbers for this evaluation, if there is one
+    pub(crate) fn random(&self) -> Option<&dyn RandomSource> {
+        #[cfg(feature = "std")]
+        return Some(self.random.as_deref().unwrap_or(&OsRandom));
+        #[cfg(not(feature = "std"))]
+        return self.random.as_deref();
+    }
+
+    /// The clock for this evaluation, if there is one
+    pub(crate) fn clock(&self) -> Option<&dyn Clock> {
+        #[cfg(feature = "std")]
+        return Some(self.clock.as_deref().unwrap_or(&SystemClock));
+        #[cfg(not(feature = "std"))]
+        return self.clock.as_deref();
+    }
+
+    /// The locale for this evaluation, if there is one
+    pub(crate) fn locale(&self) -> Option<&Locale> {
+        self.locale.as_deref()
+    }
+
+    /// The keys for the `hmac_sha256` operator, if there are any
+    #[cfg(feature = "crypto")]
+    pub(crate) fn hmac_keys(&self) -> Option<&HmacKeys> {
+        self.hmac_keys.as_deref()
+    }
+
+    /// The units for the `convert` operator, or None for the built-in units
+    pub(crate) fn units(&self) -> Option<&Units> {
+        self.units.as_deref()
+    }
+
+    /// The numeric backend for this evaluation
+    pub(crate) fn numeric(&self) -> &dyn NumericBackend {
+        self.numeric.as_deref().unwrap_or(&JsNumeric)
+    }
+
+    /// Execute an operation with the given operator and number of arguments.
+    ///
+    /// Returns an error without executing the operation if the evaluation
+    /// has been cancelled.
+    #[cfg_attr(
+        not(all(feature = "std", feature = "tracing")),
+        allow(unused_variables)
+    )]
+    pub(crate) fn execute<T, F>(
+        &self,
+        operator: &str,
+        arguments: usize,
+        execute: F,
+    ) -> Result<T, Error>
+    where
+        F: FnOnce() -> Result<T, Error>,
+    {
+        self.tick()?;
+        #[cfg(feature = "tracing")]
+        let _span = crate::trace::Operation::enter(&self.path, operator, arguments);
+        #[cfg(feature = "std")]
+        if let Some(profiler) = &self.profiler {
+            return profiler.time(operator, execute);
+        }
+        execute()
+    }
+
+    /// Record the execution of an operation.
+    ///
+    /// Returns an error if the evaluation has been cancelled.
+    fn tick(&self) -> Result<(), Error> {
+        #[cfg(feature = "std")]
+        if let Some(evaluation) = &self.evaluation {
+            return evaluation.tick();
+        }
+        Ok(())
+    }
+}
+
+/// The results of calls to memoized functions during an evaluation, keyed by
+/// the function's name and its serialized arguments
+///
+/// Without `std` the results are held in a `RefCell`, so an evaluation's
+/// environment can't be shared between threads.
+#[derive(Debug, Default)]
+struct MemoCache {
+    limit: Option<usize>,
+    #[cfg(feature = "std")]
+    results: Mutex<MemoResults>,
+    #[cfg(not(feature = "std"))]
+    results: RefCell<MemoResults>,
+}
+type MemoResults = HashMap<(String, String), Value>;
+impl MemoCache {
+    fn get(&self, key: &(String, String)) -> Option<Value> {
+        self.results()?.get(key).cloned()
+    }
+
+    fn insert(&self, key: (String, String), result: Value) {
+        if let Some(mut results) = self.results() {
+            if self.limit.map_or(true, |limit| results.len() < limit) {
+                results.insert(key, result);
+            }
+        }
+    }
+
+    /// The cached results, unless they're unavailable because another
+    /// thread panicked while holding them
+    #[cfg(feature = "std")]
+    fn results(&self) -> Option<std::sync::MutexGuard<'_, MemoResults>> {
+        self.results.lock().ok()
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn results(&self) -> Option<core::cell::RefMut<'_, MemoResults>> {
+        self.results.try_borrow_mut().ok()
+    }
+}
+
+impl fmt::Debug for Env<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut f = f.debug_struct("Env");
+        #[cfg(feature = "std")]
+        f.field("evaluation", &self.evaluation)
+            .field("profiler", &self.profiler);
+        f.field("numeric", &self.numeric)
+            .field("lookup", &self.lookup)
+            .field("random", &self.random)
+            .field("functions", &self.functions)
+            .field("params", &self.params)
+            .field("resolver", &self.resolver.map(|_| "<resolver>"))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test_context {
+    use super::*;
+
+    #[test]
+    fn test_semantics_version_round_trip() {
+        let version: SemanticsVersion =
+            SemanticsVersion::LATEST.to_string().parse().unwrap();
+        assert_eq!(version, SemanticsVersion::LATEST);
+        assert_eq!(Context::new().semantics, SemanticsVersion::V1);
+        assert_eq!(
+            "1".parse::<SemanticsVersion>().unwrap(),
+            SemanticsVersion::V1
+        );
+        "0".parse::<SemanticsVersion>().unwrap_err();
+    }
+}
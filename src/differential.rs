@@ -0,0 +1,330 @@
+//! Differential Testing
+//!
+//! Evaluate rules both with this crate and with json-logic-js, the reference
+//! JavaScript implementation of JsonLogic, running in an embedded QuickJS
+//! engine, and report the rules for which their results differ. This is how
+//! the crate's compatibility with JavaScript semantics is checked: rules are
+//! generated with `WellFormed`, using only the operators json-logic-js
+//! implements, and evaluated against data generated with `ArbitraryValue`.
+//!
+//! json-logic-js isn't bundled with the crate. Load its `logic.js` with
+//! `Engine::new()`, e.g.
+//!
+//! ```ignore
+//! let engine = Engine::new(&std::fs::read_to_string("logic.js")?)?;
+//! let report = differential::run(&engine, 0, 1000);
+//! for divergence in &report.divergences {
+//!     println!("{}", divergence);
+//! }
+//! ```
+
+use std::fmt;
+
+use arbitrary::Unstructured;
+use rquickjs::{CatchResultExt, Runtime};
+use serde_json::Value;
+
+use crate::{ArbitraryValue, Error, RandomSource, Rule, SeededRandom, WellFormed};
+
+/// The operators json-logic-js implements, which generated rules are limited
+/// to (along with `var`)
+pub const OPERATORS: &[&str] = &[
+    "==",
+    "===",
+    "!=",
+    "!==",
+    "!",
+    "!!",
+    "or",
+    "and",
+    "?:",
+    "if",
+    ">",
+    ">=",
+    "<",
+    "<=",
+    "max",
+    "min",
+    "+",
+    "-",
+    "*",
+    "/",
+    "%",
+    "map",
+    "reduce",
+    "filter",
+    "all",
+    "none",
+    "some",
+    "merge",
+    "in",
+    "cat",
+    "substr",
+    "missing",
+    "missing_some",
+];
+
+/// The number of random bytes each rule and piece of data is generated from
+const INPUT_LEN: usize = 512;
+
+/// A QuickJS engine with json-logic-js loaded
+pub struct Engine {
+    // The context must be dropped before its runtime
+    context: rquickjs::Context,
+    _runtime: Runtime,
+}
+impl Engine {
+    /// Load json-logic-js from its source, which must define `jsonLogic` as
+    /// a global, as `logic.js` does when loaded as a script.
+    pub fn new(source: &str) -> Result<Self, String> {
+        let runtime = Runtime::new().map_err(|err| err.to_string())?;
+        let context =
+            rquickjs::Context::full(&runtime).map_err(|err| err.to_string())?;
+        context.with(|ctx| {
+            ctx.eval::<(), _>(source)
+                .catch(&ctx)
+                .map_err(|err| format!("Could not load json-logic-js: {}", err))?;
+            match ctx.globals().contains_key("jsonLogic") {
+                Ok(true) => Ok(()),
+                _ => Err(String::from("json-logic-js did not define `jsonLogic`")),
+            }
+        })?;
+        Ok(Self {
+            context,
+            _runtime: runtime,
+        })
+    }
+
+    /// Apply a rule to data with json-logic-js, returning the message of
+    /// the exception it throws if it fails.
+    pub fn apply(&self, logic: &Value, data: &Value) -> Result<Value, String> {
+        // JSON documents are JavaScript expressions, so may be embedded
+        // as-is. An `undefined` result is omitted from the outer object.
+        let script = format!(
+            "(function () {{
+                try {{
+                    return JSON.stringify({{ ok: jsonLogic.apply({}, {}) }});
+                }} catch (e) {{
+                    return JSON.stringify({{ error: String(e) }});
+                }}
+            }})()",
+            logic, data
+        );
+        let output: String = self
+            .context
+            .with(|ctx| ctx.eval(script).catch(&ctx).map_err(|err| err.to_string()))?;
+        let mut output: Value =
+            serde_json::from_str(&output).map_err(|err| err.to_string())?;
+        match output.get("error") {
+            Some(Value::String(message)) => Err(message.clone()),
+            _ => Ok(output.get_mut("ok").map(Value::take).unwrap_or(Value::Null)),
+        }
+    }
+}
+impl fmt::Debug for Engine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Engine").finish_non_exhaustive()
+    }
+}
+
+/// A rule and data for which this crate and json-logic-js disagree
+#[derive(Debug)]
+pub struct Divergence {
+    pub logic: Value,
+    pub data: Value,
+    /// The result of applying the rule with this crate
+    pub result: Result<Value, Error>,
+    /// The result of applying the rule with json-logic-js
+    pub reference: Result<Value, String>,
+}
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} with data {}: ", self.logic, self.data)?;
+        match &self.result {
+            Ok(value) => write!(f, "got {}", value)?,
+            Err(err) => write!(f, "got error: {}", err)?,
+        }
+        match &self.reference {
+            Ok(value) => write!(f, ", json-logic-js got {}", value),
+            Err(err) => write!(f, ", json-logic-js threw: {}", err),
+        }
+    }
+}
+
+/// The results of a differential run
+#[derive(Debug, Default)]
+pub struct Report {
+    /// The number of rules evaluated
+    pub checked: usize,
+    pub divergences: Vec<Divergence>,
+}
+impl Report {
+    /// Whether every rule gave the same result with both implementations
+    pub fn is_success(&self) -> bool {
+        self.divergences.is_empty()
+    }
+}
+
+/// Apply a rule to data with both implementations, returning how their
+/// results differ, if they do.
+///
+/// Numbers are compared by value, since json-logic-js doesn't distinguish
+/// integers from floats, and any two errors are considered the same.
+pub fn compare(engine: &Engine, logic: &Value, data: &Value) -> Option<Divergence> {
+    let result = Rule::from_value(logic).and_then(|rule| rule.apply(data));
+    let reference = engine.apply(logic, data);
+    let agree = match (&result, &reference) {
+        (Ok(ours), Ok(theirs)) => same(ours, theirs),
+        (Err(_), Err(_)) => true,
+        _ => false,
+    };
+    match agree {
+        true => None,
+        false => Some(Divergence {
+            logic: logic.clone(),
+            data: data.clone(),
+            result,
+            reference,
+        }),
+    }
+}
+
+/// Compare both implementations on `count` rules and pieces of data,
+/// generated from the given seed.
+pub fn run(engine: &Engine, seed: u64, count: usize) -> Report {
+    let source = SeededRandom::new(seed);
+    let mut report = Report::default();
+    for _ in 0..count {
+        let bytes: Vec<u8> = (0..INPUT_LEN / 8)
+            .flat_map(|_| {
+                source
+                    .next_u64()
+                    .expect("seeded sources don't fail")
+                    .to_le_bytes()
+            })
+            .collect();
+        let mut u = Unstructured::new(&bytes);
+        let generated =
+            WellFormed::with_operators(&mut u, |name| OPERATORS.contains(&name))
+                .and_then(|logic| Ok((logic, u.arbitrary::<ArbitraryValue>()?)));
+        // Inputs too short to generate from are skipped
+        let (logic, ArbitraryValue(data)) = match generated {
+            Ok(generated) => generated,
+            Err(_) => continue,
+        };
+        report.checked += 1;
+        if let Some(divergence) = compare(engine, &Value::from(logic), &data) {
+            report.divergences.push(divergence);
+        }
+    }
+    report
+}
+
+/// Equality, with numbers compared by value
+fn same(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same(a, b))
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(key, a)| b.get(key).is_some_and(|b| same(a, b)))
+        }
+        (a, b) => a == b,
+    }
+}
+
+#[cfg(test)]
+mod test_differential {
+    use super::*;
+    use serde_json::json;
+
+    /// A stand-in for json-logic-js that implements a few operators
+    const STUB: &str = r#"
+        var jsonLogic = {
+            apply: function (logic, data) {
+                if (typeof logic !== "object" || logic === null) {
+                    return logic;
+                }
+                var op = Object.keys(logic)[0];
+                var args = logic[op];
+                if (op === "var") {
+                    return data[args];
+                }
+                if (op === "+") {
+                    return args[0] + args[1];
+                }
+                if (op === "cat") {
+                    return "stub";
+                }
+                throw new Error("Unrecognized operation " + op);
+            }
+        };
+    "#;
+
+    #[test]
+    fn test_apply() {
+        let engine = Engine::new(STUB).unwrap();
+        assert_eq!(
+            engine.apply(&json!({"+": [1, 2]}), &json!({})),
+            Ok(json!(3))
+        );
+        assert_eq!(
+            engine.apply(&json!({"var": "a"}), &json!({"a": [1, "b"]})),
+            Ok(json!([1, "b"]))
+        );
+        // undefined
+        assert_eq!(
+            engine.apply(&json!({"var": "a"}), &json!({})),
+            Ok(json!(null))
+        );
+        assert_eq!(
+            engine.apply(&json!({"nope": []}), &json!({})),
+            Err(String::from("Error: Unrecognized operation nope"))
+        );
+    }
+
+    #[test]
+    fn test_new() {
+        Engine::new("var x = 1;").unwrap_err();
+        Engine::new("syntax error (").unwrap_err();
+    }
+
+    #[test]
+    fn test_compare() {
+        let engine = Engine::new(STUB).unwrap();
+        assert!(compare(&engine, &json!({"+": [1, 2.0]}), &json!({})).is_none());
+        // Both fail
+        assert!(compare(&engine, &json!({"substr": []}), &json!({})).is_none());
+
+        let divergence =
+            compare(&engine, &json!({"cat": ["a", "b"]}), &json!({})).unwrap();
+        assert_eq!(divergence.result.unwrap(), json!("ab"));
+        assert_eq!(divergence.reference, Ok(json!("stub")));
+
+        let divergence =
+            compare(&engine, &json!({"if": [true, 1, 2]}), &json!({})).unwrap();
+        assert_eq!(
+            divergence.to_string(),
+            "{\"if\":[true,1,2]} with data {}: got 1, \
+             json-logic-js threw: Error: Unrecognized operation if"
+        );
+    }
+
+    #[test]
+    fn test_run() {
+        let engine = Engine::new(STUB).unwrap();
+        let report = run(&engine, 0, 20);
+        assert!(report.checked > 0);
+        assert!(!report.is_success());
+        // Generated rules only use json-logic-js's operators
+        for divergence in &report.divergences {
+            if let Some(op) = divergence.logic.as_object().and_then(|o| o.keys().next())
+            {
+                assert!(op == "var" || OPERATORS.contains(&op.as_str()), "{}", op);
+            }
+        }
+    }
+}
@@ -0,0 +1,882 @@
+//! Infix Expressions
+//!
+//! A text syntax for rules, for people writing them by hand:
+//!
+//! ```
+//! use jsonlogic_plus::{dsl, Rule};
+//! use serde_json::json;
+//!
+//! let ast = dsl::parse(r#"age >= 18 and country in ["US", "CA"]"#).unwrap();
+//! let rule = Rule::from_value(&json!({"and": [
+//!     {">=": [{"var": "age"}, 18]},
+//!     {"in": [{"var": "country"}, ["US", "CA"]]}
+//! ]}))
+//! .unwrap();
+//! assert_eq!(ast, rule.ast());
+//! assert_eq!(dsl::to_string(&ast), r#"age >= 18 and country in ["US", "CA"]"#);
+//! ```
+//!
+//! From loosest to tightest binding, expressions are made of:
+//!
+//!   - `cond ? then : else`, for `if`. Chains like `a ? b : c ? d : e` are
+//!     a single `if`.
+//!   - `or` (or `||`) and `and` (or `&&`)
+//!   - `not` (`!`)
+//!   - `==`, `!=`, `===`, `!==`, `<`, `<=`, `>`, `>=`, and `in`. `a < b < c`
+//!     is a single `<` with three arguments, as is `a <= b <= c` for `<=`.
+//!   - `+` and `-`, then `*`, `/`, and `%`
+//!   - unary `-`, `+`, `!`, and `!!`
+//!   - literals (JSON numbers, strings in single or double quotes, `true`,
+//!     `false`, `null`, `[arrays]`, and `{"objects": ...}`), parentheses,
+//!     and calls, e.g. `max(a, b)`
+//!   - `var` paths, e.g. `user.address.0`. Paths that aren't made of
+//!     identifiers, or that have a default, are written as calls, e.g.
+//!     `var("first name", "anonymous")`.
+//!
+//! Any operator or function may be called by name. Names that aren't
+//! identifiers are quoted with backticks, e.g. `` `?:`(a, b, c) ``.
+//! Parameters of functions are referred to with `param("name")`.
+//!
+//! Calls parse as `Ast::Operator`s, since whether a name refers to an
+//! operator or to a function in a namespace is only known when the rule is
+//! parsed, e.g. with `Namespace::parse(&Value::from(ast))`.
+
+use serde_json::{Map, Value};
+
+use crate::ast::Ast;
+use crate::error::Error;
+use crate::op::data::Variable;
+use crate::op::func::{Function, Param};
+use crate::prelude::*;
+use crate::rule::Rule;
+
+/// Parse an expression into an `Ast`.
+pub fn parse(text: &str) -> Result<Ast, Error> {
+    let mut parser = Parser {
+        text,
+        tokens: lex(text)?,
+        pos: 0,
+    };
+    let ast = parser.ternary()?;
+    match parser.peek() {
+        Token::End => Ok(ast),
+        _ => Err(parser.unexpected()),
+    }
+}
+
+/// Parse an expression into a `Rule`.
+pub fn parse_rule(text: &str) -> Result<Rule, Error> {
+    Rule::from_value(&Value::from(parse(text)?))
+}
+
+/// Render an `Ast` as an expression.
+///
+/// Parsing the expression again produces the same `Ast`.
+pub fn to_string(ast: &Ast) -> String {
+    expr(ast, TERNARY)
+}
+
+const KEYWORDS: &[&str] = &["and", "or", "not", "in", "true", "false", "null"];
+
+/// Punctuation, with longer tokens before their prefixes
+const PUNCTUATION: &[&str] = &[
+    "===", "!==", "==", "!=", "<=", ">=", "&&", "||", "!!", "<", ">", "!", "+", "-",
+    "*", "/", "%", "?", ":", "(", ")", "[", "]", "{", "}", ",",
+];
+
+const COMPARISONS: &[&str] = &["==", "!=", "===", "!==", "<", "<=", ">", ">="];
+
+// Precedence, from loosest to tightest binding
+const TERNARY: u8 = 1;
+const OR: u8 = 2;
+const AND: u8 = 3;
+const NOT: u8 = 4;
+const COMPARISON: u8 = 5;
+const ADDITIVE: u8 = 6;
+const MULTIPLICATIVE: u8 = 7;
+const UNARY: u8 = 8;
+const PRIMARY: u8 = 9;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(Value),
+    String(String),
+    /// An identifier, or a dotted path of them
+    Path(String),
+    /// A name quoted in backticks
+    Name(String),
+    Punct(&'static str),
+    End,
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || c == '$'
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '$'
+}
+
+fn syntax_error(text: &str, offset: usize, reason: String) -> Error {
+    let before = &text[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before[before.rfind('\n').map_or(0, |i| i + 1)..]
+        .chars()
+        .count()
+        + 1;
+    Error::InvalidSyntax {
+        reason,
+        line: Some(line),
+        column: Some(column),
+    }
+}
+
+/// Split an expression into tokens, along with their offsets in the text.
+fn lex(text: &str) -> Result<Vec<(Token, usize)>, Error> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        let rest = &text[start..];
+        let end_of = |pred: fn(char) -> bool| {
+            rest.find(|c: char| !pred(c))
+                .map_or(text.len(), |len| start + len)
+        };
+        let (token, end) = if c.is_whitespace() {
+            chars.next();
+            continue;
+        } else if c.is_ascii_digit() {
+            let mut end = end_of(|c| c.is_ascii_digit());
+            let digits_at = |i: usize| {
+                text[i..]
+                    .starts_with(|c: char| c.is_ascii_digit())
+                    .then(|| {
+                        text[i..]
+                            .find(|c: char| !c.is_ascii_digit())
+                            .map_or(text.len(), |len| i + len)
+                    })
+            };
+            if text[end..].starts_with('.') {
+                end = digits_at(end + 1).unwrap_or(end);
+            }
+            if text[end..].starts_with(['e', 'E']) {
+                let sign = text[end + 1..].starts_with(['+', '-']) as usize;
+                end = digits_at(end + 1 + sign).unwrap_or(end);
+            }
+            let number = serde_json::from_str(&text[start..end]).map_err(|err| {
+                syntax_error(text, start, format!("invalid number: {}", err))
+            })?;
+            (Token::Number(number), end)
+        } else if is_ident_start(c) {
+            let mut end = end_of(is_ident_char);
+            while text[end..].starts_with('.')
+                && text[end + 1..].starts_with(is_ident_char)
+            {
+                end = text[end + 1..]
+                    .find(|c: char| !is_ident_char(c))
+                    .map_or(text.len(), |len| end + 1 + len);
+            }
+            (Token::Path(text[start..end].into()), end)
+        } else if c == '"' || c == '\'' {
+            let mut escaped = false;
+            let len = rest[1..]
+                .find(|ch: char| {
+                    let found = ch == c && !escaped;
+                    escaped = ch == '\\' && !escaped;
+                    found
+                })
+                .ok_or_else(|| {
+                    syntax_error(text, start, "unterminated string".into())
+                })?;
+            let end = start + 1 + len + 1;
+            let quoted = if c == '"' {
+                text[start..end].to_string()
+            } else {
+                format!(
+                    "\"{}\"",
+                    text[start + 1..end - 1]
+                        .replace("\\'", "'")
+                        .replace('"', "\\\"")
+                )
+            };
+            let s = serde_json::from_str(&quoted).map_err(|err| {
+                syntax_error(text, start, format!("invalid string: {}", err))
+            })?;
+            (Token::String(s), end)
+        } else if c == '`' {
+            let len = rest[1..]
+                .find('`')
+                .ok_or_else(|| syntax_error(text, start, "unterminated name".into()))?;
+            let end = start + 1 + len + 1;
+            (Token::Name(text[start + 1..end - 1].into()), end)
+        } else {
+            match PUNCTUATION.iter().find(|p| rest.starts_with(*p)) {
+                Some(p) => (Token::Punct(p), start + p.len()),
+                None => {
+                    return Err(syntax_error(
+                        text,
+                        start,
+                        format!("unexpected character '{}'", c),
+                    ))
+                }
+            }
+        };
+        tokens.push((token, start));
+        while chars.peek().is_some_and(|&(i, _)| i < end) {
+            chars.next();
+        }
+    }
+    tokens.push((Token::End, text.len()));
+    Ok(tokens)
+}
+
+fn operator(name: &str, args: Vec<Ast>) -> Ast {
+    Ast::Operator {
+        name: name.into(),
+        args,
+    }
+}
+
+/// A node as an item of an array or object literal. Literals within
+/// literals aren't parsed, so they needn't be quoted.
+fn unquoted(ast: Ast) -> Value {
+    match ast {
+        Ast::Literal(value) => value,
+        ast => Value::from(ast),
+    }
+}
+
+/// A recursive descent parser, with a method for each level of precedence
+struct Parser<'a> {
+    text: &'a str,
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+impl Parser<'_> {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].0.clone();
+        if token != Token::End {
+            self.pos += 1;
+        }
+        token
+    }
+
+    /// Consume the next token if it is the given punctuation or keyword.
+    fn eat(&mut self, expected: &str) -> bool {
+        let found = match self.peek() {
+            Token::Punct(p) => *p == expected,
+            Token::Path(word) => word == expected && KEYWORDS.contains(&expected),
+            _ => false,
+        };
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), Error> {
+        match self.eat(expected) {
+            true => Ok(()),
+            false => Err(self.error(format!(
+                "expected '{}', found {}",
+                expected,
+                self.describe()
+            ))),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self.peek() {
+            Token::Number(n) => format!("number {}", n),
+            Token::String(s) => format!("string {:?}", s),
+            Token::Path(p) => format!("'{}'", p),
+            Token::Name(n) => format!("`{}`", n),
+            Token::Punct(p) => format!("'{}'", p),
+            Token::End => "end of input".into(),
+        }
+    }
+
+    fn error(&self, reason: String) -> Error {
+        syntax_error(self.text, self.tokens[self.pos].1, reason)
+    }
+
+    fn unexpected(&self) -> Error {
+        self.error(format!("unexpected {}", self.describe()))
+    }
+
+    fn ternary(&mut self) -> Result<Ast, Error> {
+        let cond = self.or()?;
+        if !self.eat("?") {
+            return Ok(cond);
+        }
+        let mut args = vec![cond, self.ternary()?];
+        loop {
+            self.expect(":")?;
+            let branch = self.or()?;
+            args.push(branch);
+            if !self.eat("?") {
+                break;
+            }
+            args.push(self.ternary()?);
+        }
+        Ok(operator("if", args))
+    }
+
+    /// Parse a chain of operands joined by either spelling of an operator.
+    fn chain<F>(&mut self, name: &str, alias: &str, operand: F) -> Result<Ast, Error>
+    where
+        F: Fn(&mut Self) -> Result<Ast, Error>,
+    {
+        let first = operand(self)?;
+        if !(self.eat(name) || self.eat(alias)) {
+            return Ok(first);
+        }
+        let mut args = vec![first, operand(self)?];
+        while self.eat(name) || self.eat(alias) {
+            args.push(operand(self)?);
+        }
+        Ok(operator(name, args))
+    }
+
+    fn or(&mut self) -> Result<Ast, Error> {
+        self.chain("or", "||", Self::and)
+    }
+
+    fn and(&mut self) -> Result<Ast, Error> {
+        self.chain("and", "&&", Self::not)
+    }
+
+    fn not(&mut self) -> Result<Ast, Error> {
+        if self.eat("not") {
+            return Ok(operator("!", vec![self.not()?]));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Ast, Error> {
+        let left = self.additive()?;
+        let op = match self.peek() {
+            Token::Punct(p) if COMPARISONS.contains(p) => *p,
+            Token::Path(word) if word == "in" => "in",
+            _ => return Ok(left),
+        };
+        self.next();
+        let mut args = vec![left, self.additive()?];
+        if (op == "<" || op == "<=") && self.eat(op) {
+            args.push(self.additive()?);
+        }
+        Ok(operator(op, args))
+    }
+
+    /// Parse left-associative operators at one level of precedence, where
+    /// `variadic` operators are collected into a single operation.
+    fn binary<F>(
+        &mut self,
+        variadic: &'static str,
+        others: &[&'static str],
+        operand: F,
+    ) -> Result<Ast, Error>
+    where
+        F: Fn(&mut Self) -> Result<Ast, Error>,
+    {
+        let mut acc = operand(self)?;
+        let mut collecting = false;
+        loop {
+            if self.eat(variadic) {
+                let rhs = operand(self)?;
+                match &mut acc {
+                    Ast::Operator { args, .. } if collecting => args.push(rhs),
+                    _ => acc = operator(variadic, vec![acc, rhs]),
+                }
+                collecting = true;
+            } else if let Some(op) = others.iter().find(|op| self.eat(op)) {
+                acc = operator(op, vec![acc, operand(self)?]);
+                collecting = false;
+            } else {
+                return Ok(acc);
+            }
+        }
+    }
+
+    fn additive(&mut self) -> Result<Ast, Error> {
+        self.binary("+", &["-"], Self::multiplicative)
+    }
+
+    fn multiplicative(&mut self) -> Result<Ast, Error> {
+        self.binary("*", &["/", "%"], Self::unary)
+    }
+
+    fn unary(&mut self) -> Result<Ast, Error> {
+        let (token, offset) = self.tokens[self.pos].clone();
+        let op = match token {
+            Token::Punct(op @ ("-" | "+" | "!" | "!!")) => op,
+            _ => return self.primary(),
+        };
+        self.next();
+        // A minus sign directly before a number is part of the number
+        if let ("-", (Token::Number(Value::Number(n)), next)) =
+            (op, &self.tokens[self.pos])
+        {
+            if *next == offset + 1 {
+                let negated = serde_json::from_str(&format!("-{}", n))
+                    .map_err(|err| self.error(format!("invalid number: {}", err)))?;
+                self.next();
+                return Ok(Ast::Literal(negated));
+            }
+        }
+        Ok(operator(op, vec![self.unary()?]))
+    }
+
+    fn primary(&mut self) -> Result<Ast, Error> {
+        let start = self.pos;
+        match self.next() {
+            Token::Number(n) => Ok(Ast::Literal(n)),
+            Token::String(s) => Ok(Ast::Literal(Value::String(s))),
+            Token::Path(word) => match word.as_str() {
+                "true" => Ok(Ast::Literal(Value::Bool(true))),
+                "false" => Ok(Ast::Literal(Value::Bool(false))),
+                "null" => Ok(Ast::Literal(Value::Null)),
+                _ if KEYWORDS.contains(&word.as_str()) => {
+                    self.pos = start;
+                    Err(self.unexpected())
+                }
+                _ if self.eat("(") => self.call(word, start),
+                _ => Ok(Ast::Var {
+                    path: Some(Box::new(Ast::Literal(Value::String(word)))),
+                    default: None,
+                }),
+            },
+            Token::Name(name) => {
+                self.expect("(")?;
+                self.call(name, start)
+            }
+            Token::Punct("(") => {
+                let ast = self.ternary()?;
+                self.expect(")")?;
+                Ok(ast)
+            }
+            Token::Punct("[") => {
+                let items = self.list("]")?;
+                Ok(Ast::Literal(Value::Array(
+                    items.into_iter().map(unquoted).collect(),
+                )))
+            }
+            Token::Punct("{") => self.object(),
+            _ => {
+                self.pos = start;
+                Err(self.unexpected())
+            }
+        }
+    }
+
+    /// Parse comma-separated expressions up to a closing delimiter, which
+    /// may be preceded by a trailing comma.
+    fn list(&mut self, close: &str) -> Result<Vec<Ast>, Error> {
+        let mut items = Vec::new();
+        while !self.eat(close) {
+            items.push(self.ternary()?);
+            if !self.eat(",") {
+                self.expect(close)?;
+                break;
+            }
+        }
+        Ok(items)
+    }
+
+    fn object(&mut self) -> Result<Ast, Error> {
+        let mut map = Map::new();
+        while !self.eat("}") {
+            let start = self.pos;
+            let key = match self.next() {
+                Token::String(key) | Token::Path(key) => key,
+                _ => {
+                    self.pos = start;
+                    return Err(self.unexpected());
+                }
+            };
+            self.expect(":")?;
+            map.insert(key, unquoted(self.ternary()?));
+            if !self.eat(",") {
+                self.expect("}")?;
+                break;
+            }
+        }
+        Ok(Ast::Literal(Value::Object(map)))
+    }
+
+    /// Parse the arguments of a call, whose name is the token at `start`.
+    fn call(&mut self, name: String, start: usize) -> Result<Ast, Error> {
+        let args = self.list(")")?;
+        let ast = match (name.as_str(), args.as_slice()) {
+            (Variable::OPERATOR, [_, _, _, ..]) => {
+                return Err(syntax_error(
+                    self.text,
+                    self.tokens[start].1,
+                    "'var' takes at most two arguments".into(),
+                ))
+            }
+            (Variable::OPERATOR, _) => {
+                let mut args = args.into_iter().map(Box::new);
+                Ast::Var {
+                    path: args.next(),
+                    default: args.next(),
+                }
+            }
+            (Param::OPERATOR, [Ast::Literal(Value::String(name))]) => {
+                Ast::Param(name.clone())
+            }
+            (
+                Function::OPERATOR,
+                [Ast::Literal(Value::String(func)), Ast::Literal(Value::Array(params)), body],
+            ) if params.iter().all(Value::is_string) => Ast::FunctionDef {
+                name: func.clone(),
+                params: params
+                    .iter()
+                    .filter_map(|p| p.as_str().map(String::from))
+                    .collect(),
+                body: Box::new(body.clone()),
+            },
+            _ => operator(&name, args),
+        };
+        Ok(ast)
+    }
+}
+
+/// Render a node, parenthesized if it binds more loosely than `min`.
+fn expr(ast: &Ast, min: u8) -> String {
+    let (prec, rendered) = node(ast);
+    if prec < min {
+        format!("({})", rendered)
+    } else {
+        rendered
+    }
+}
+
+/// Render a node, along with its precedence.
+fn node(ast: &Ast) -> (u8, String) {
+    match ast {
+        Ast::Literal(value) => (PRIMARY, literal(value)),
+        Ast::Var {
+            path: Some(path),
+            default: None,
+        } => match &**path {
+            Ast::Literal(Value::String(path)) if is_bare_path(path) => {
+                (PRIMARY, path.clone())
+            }
+            path => (PRIMARY, call(Variable::OPERATOR, [path])),
+        },
+        Ast::Var { path, default } => (
+            PRIMARY,
+            call(Variable::OPERATOR, path.iter().chain(default).map(|a| &**a)),
+        ),
+        Ast::Param(name) => (
+            PRIMARY,
+            format!(
+                "{}({})",
+                Param::OPERATOR,
+                literal(&Value::from(name.as_str()))
+            ),
+        ),
+        Ast::FunctionDef { name, params, body } => (
+            PRIMARY,
+            format!(
+                "{}({}, {}, {})",
+                Function::OPERATOR,
+                literal(&Value::from(name.as_str())),
+                literal(&Value::from(params.clone())),
+                expr(body, TERNARY)
+            ),
+        ),
+        Ast::FunctionCall { name, args } => (PRIMARY, call(name, args)),
+        Ast::Object(entries) => (
+            PRIMARY,
+            format!(
+                "{{{}}}",
+                entries
+                    .iter()
+                    .map(|(key, value)| format!(
+                        "{}: {}",
+                        Value::from(key.as_str()),
+                        expr(value, TERNARY)
+                    ))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+        ),
+        Ast::Operator { name, args } => operation(name, args),
+    }
+}
+
+fn operation(name: &str, args: &[Ast]) -> (u8, String) {
+    let join = |prec: u8, sep: &str| {
+        args.iter()
+            .map(|arg| expr(arg, prec + 1))
+            .collect::<Vec<String>>()
+            .join(sep)
+    };
+    match (name, args) {
+        ("or", [_, _, ..]) => (OR, join(OR, " or ")),
+        ("and", [_, _, ..]) => (AND, join(AND, " and ")),
+        ("!", [arg]) => (NOT, format!("not {}", expr(arg, NOT))),
+        (op, [_, _]) if COMPARISONS.contains(&op) || op == "in" => {
+            (COMPARISON, join(COMPARISON, &format!(" {} ", op)))
+        }
+        ("<" | "<=", [_, _, _]) => {
+            (COMPARISON, join(COMPARISON, &format!(" {} ", name)))
+        }
+        ("+", [_, _, ..]) => (ADDITIVE, join(ADDITIVE, " + ")),
+        ("*", [_, _, ..]) => (MULTIPLICATIVE, join(MULTIPLICATIVE, " * ")),
+        ("-", [left, right]) => (ADDITIVE, binary(left, "-", right, ADDITIVE)),
+        ("/" | "%", [left, right]) => {
+            (MULTIPLICATIVE, binary(left, name, right, MULTIPLICATIVE))
+        }
+        ("-", [Ast::Literal(Value::Number(n))]) if !n.to_string().starts_with('-') => {
+            (UNARY, format!("-({})", n))
+        }
+        ("-" | "+" | "!!", [arg]) => (UNARY, format!("{}{}", name, expr(arg, UNARY))),
+        ("if", [_, _, _, ..]) if args.len() % 2 == 1 => {
+            let mut rendered = String::new();
+            for pair in args.chunks(2) {
+                match pair {
+                    [cond, then] => rendered.push_str(&format!(
+                        "{} ? {} : ",
+                        expr(cond, OR),
+                        expr(then, OR)
+                    )),
+                    [otherwise] => rendered.push_str(&expr(otherwise, OR)),
+                    _ => unreachable!(),
+                }
+            }
+            (TERNARY, rendered)
+        }
+        _ => (PRIMARY, call(name, args)),
+    }
+}
+
+fn binary(left: &Ast, op: &str, right: &Ast, prec: u8) -> String {
+    format!("{} {} {}", expr(left, prec), op, expr(right, prec + 1))
+}
+
+fn call<'a, I: IntoIterator<Item = &'a Ast>>(name: &str, args: I) -> String {
+    let is_name = name.split('.').all(|part| {
+        part.starts_with(is_ident_start) && part.chars().all(is_ident_char)
+    }) && !KEYWORDS.contains(&name);
+    let args = args
+        .into_iter()
+        .map(|arg| expr(arg, TERNARY))
+        .collect::<Vec<String>>()
+        .join(", ");
+    match is_name {
+        true => format!("{}({})", name, args),
+        false => format!("`{}`({})", name, args),
+    }
+}
+
+/// Whether a `var` path may be written as-is, rather than as a call
+fn is_bare_path(path: &str) -> bool {
+    path.starts_with(is_ident_start)
+        && path
+            .split('.')
+            .all(|part| !part.is_empty() && part.chars().all(is_ident_char))
+        && !KEYWORDS.contains(&path)
+}
+
+fn literal(value: &Value) -> String {
+    match value {
+        Value::Array(items) => format!(
+            "[{}]",
+            items
+                .iter()
+                .map(literal)
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+        Value::Object(map) => format!(
+            "{{{}}}",
+            map.iter()
+                .map(|(key, value)| format!(
+                    "{}: {}",
+                    Value::from(key.as_str()),
+                    literal(value)
+                ))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test_dsl {
+    use super::*;
+    use serde_json::json;
+
+    use crate::Namespace;
+
+    #[test]
+    fn test_parse() {
+        let cases = vec![
+            ("a", json!({"var": "a"})),
+            (
+                "user.addresses.0.city",
+                json!({"var": "user.addresses.0.city"}),
+            ),
+            (
+                "var('first name', 'anon')",
+                json!({"var": ["first name", "anon"]}),
+            ),
+            ("1 + 2 * 3", json!({"+": [1, {"*": [2, 3]}]})),
+            ("(1 + 2) * 3", json!({"*": [{"+": [1, 2]}, 3]})),
+            (
+                "1 + 2 + 3 - 4 - 5",
+                json!({"-": [{"-": [{"+": [1, 2, 3]}, 4]}, 5]}),
+            ),
+            ("a - -1.5e2", json!({"-": [{"var": "a"}, -150.0]})),
+            ("-a", json!({"-": [{"var": "a"}]})),
+            ("- 1", json!({"-": [1]})),
+            ("!!a", json!({"!!": [{"var": "a"}]})),
+            (
+                "not a == b",
+                json!({"!": [{"==": [{"var": "a"}, {"var": "b"}]}]}),
+            ),
+            (
+                "!a == b",
+                json!({"==": [{"!": [{"var": "a"}]}, {"var": "b"}]}),
+            ),
+            (
+                "a or b and c",
+                json!({"or": [{"var": "a"}, {"and": [{"var": "b"}, {"var": "c"}]}]}),
+            ),
+            (
+                "a && b && c || d",
+                json!({"or": [{"and": [{"var": "a"}, {"var": "b"}, {"var": "c"}]}, {"var": "d"}]}),
+            ),
+            ("1 <= a <= 2", json!({"<=": [1, {"var": "a"}, 2]})),
+            ("'x' in tags", json!({"in": ["x", {"var": "tags"}]})),
+            (
+                "a ? 1 : b ? 2 : 3",
+                json!({"if": [{"var": "a"}, 1, {"var": "b"}, 2, 3]}),
+            ),
+            (
+                "a ? 1 : (b ? 2 : 3)",
+                json!({"if": [{"var": "a"}, 1, {"if": [{"var": "b"}, 2, 3]}]}),
+            ),
+            ("max(a, 2,)", json!({"max": [{"var": "a"}, 2]})),
+            (
+                "`?:`(a, b, c)",
+                json!({"?:": [{"var": "a"}, {"var": "b"}, {"var": "c"}]}),
+            ),
+            (
+                "[1, \"two\", [null], {\"k\": true}]",
+                json!([1, "two", [null], {"k": true}]),
+            ),
+            (
+                "map(xs, var(''))",
+                json!({"map": [{"var": "xs"}, {"var": ""}]}),
+            ),
+            ("var()", json!({"var": []})),
+        ];
+        for (text, expected) in cases {
+            let ast = parse(text).unwrap_or_else(|err| panic!("{}: {}", text, err));
+            assert_eq!(ast, Rule::from_value(&expected).unwrap().ast(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_same_ast_as_json() {
+        let text = "defn('double', ['n'], param('n') * 2)";
+        let ast = parse(text).unwrap();
+        let mut ns = Namespace::new();
+        ns.define(&Value::from(ast.clone())).unwrap();
+        assert_eq!(ns.definitions().unwrap(), vec![ast]);
+
+        let rule = parse_rule("if(a, 1) + var('b.c', 2)").unwrap();
+        assert_eq!(
+            parse("if(a, 1) + var('b.c', 2)").unwrap(),
+            Rule::from_value(&json!({"+": [
+                {"if": [{"var": "a"}, 1]},
+                {"var": ["b.c", 2]}
+            ]}))
+            .unwrap()
+            .ast()
+        );
+        assert_eq!(rule.apply(&json!({"a": true})).unwrap(), json!(3));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let cases = vec![
+            json!({"and": [{">=": [{"var": "age"}, 18]}, {"in": [{"var": "country"}, ["US", "CA"]]}]}),
+            json!({"or": [{"and": [{"var": "a"}, {"var": "b"}]}, {"!": [{"var": "c"}]}]}),
+            json!({"and": [{"and": [{"var": "a"}, {"var": "b"}]}, {"var": "c"}]}),
+            json!({"+": [{"+": [1, 2]}, {"-": [3, {"-": [4, 5]}]}]}),
+            json!({"*": [{"-": [1]}, {"-": [{"var": "a"}]}, -2, {"-": [-2]}]}),
+            json!({"/": [{"%": [{"var": "a"}, 2]}, {"*": [1, 2]}]}),
+            json!({"<": [1, {"var": "x"}, 3]}),
+            json!({"==": [{"==": [1, 1]}, true]}),
+            json!({"if": [{"var": "a"}, {"if": [{"var": "b"}, 1, 2]}, {"if": [{"var": "c"}, 3, 4]}]}),
+            json!({"if": [{"var": "a"}, 1, {"var": "b"}, 2, 3]}),
+            json!({"if": [{"var": "a"}, 1]}),
+            json!({"!!": [{"!": [{"var": "a"}]}]}),
+            json!({"var": ["user.first name", {"var": "and"}]}),
+            json!({"var": [{"cat": ["a", "b"]}]}),
+            json!({"var": "a.-1"}),
+            json!({"var": []}),
+            json!({"missing_some": [1, ["a", "b"]]}),
+            json!({"?:": [{"var": "a"}, "it's", "\"quoted\""]}),
+            json!({"reduce": [{"var": "xs"}, {"+": [{"var": "current"}, {"var": "accumulator"}]}, 0]}),
+            json!({"merge": [[1, {"var": "a"}], {}]}),
+            json!({"defn": ["f", ["x", "y"], {"+": [{"param": "x"}, {"param": "y"}]}]}),
+            json!({"+": [1.5, 1e100, 0]}),
+        ];
+        for rule in cases {
+            let ast = match rule.get("defn") {
+                Some(_) => {
+                    let mut ns = Namespace::new();
+                    ns.define(&rule).unwrap();
+                    ns.definitions().unwrap().remove(0)
+                }
+                None => Rule::from_value(&rule).unwrap().ast(),
+            };
+            let text = to_string(&ast);
+            assert_eq!(
+                parse(&text).unwrap_or_else(|err| panic!("{}: {}", text, err)),
+                ast,
+                "{}",
+                text
+            );
+        }
+        assert_eq!(
+            to_string(&parse("a ? b : c ? (d or e) : f * (g + h)").unwrap()),
+            "a ? b : c ? d or e : f * (g + h)"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let cases = vec![
+            ("a +", 1, 4),
+            ("a ==\n  b c", 2, 5),
+            ("(a", 1, 3),
+            ("'abc", 1, 1),
+            ("a # b", 1, 3),
+            ("f(a b)", 1, 5),
+            ("var(1, 2, 3)", 1, 1),
+            ("1 < a <= 2", 1, 7),
+            ("and", 1, 1),
+        ];
+        for (text, line, column) in cases {
+            match parse(text) {
+                Err(Error::InvalidSyntax {
+                    line: Some(l),
+                    column: Some(c),
+                    ..
+                }) => assert_eq!((l, c), (line, column), "{}", text),
+                other => panic!("{}: unexpected result {:?}", text, other),
+            }
+        }
+    }
+}
@@ -1,18 +1,43 @@
 //! Error handling
 //!
-use serde_json::Value;
+use serde_json::{json, Value};
 use thiserror;
 
+use crate::namespace::Issue;
 use crate::op::NumParams;
+use crate::prelude::*;
 
 /// Public error enumeration
 #[derive(thiserror::Error, Debug)]
 #[non_exhaustive]
 pub enum Error {
+    #[error("Calls to '{function}' were nested more than {limit} deep")]
+    CallDepthExceeded { function: String, limit: usize },
+
+    #[error("Evaluation {0} was cancelled")]
+    Cancelled(u64),
+
+    #[error("Contract violation - {condition}condition of '{function}' failed for arguments {}", Value::from(.arguments.clone()))]
+    ContractViolation {
+        function: String,
+        condition: &'static str,
+        arguments: Vec<Value>,
+        result: Option<Value>,
+    },
+
+    #[error("Could not deserialize result - value: {value:?}, reason: {reason}")]
+    DeserializeResult { value: Value, reason: String },
+
     #[error("Invalid data - value: {value:?}, reason: {reason:?}")]
     InvalidData { value: Value, reason: String },
 
-    #[error("Invalid identifier '{0}': identifiers must be valid utf-8 of 2 or more characters, containing no whitespace")]
+    #[error("Identifier '{identifier}' is not allowed: {reason}")]
+    DisallowedIdentifier { identifier: String, reason: String },
+
+    #[error("Could not load '{path}' - {reason}")]
+    InvalidFile { path: String, reason: String },
+
+    #[error("Invalid identifier '{0}': identifiers must be non-empty, and contain no whitespace")]
     InvalidIdentifier(String),
 
     #[error("Invalid rule - operator: '{key:?}', reason: {reason:?}")]
@@ -31,15 +56,62 @@ pub enum Error {
         reason: String,
     },
 
+    #[error("Invalid namespace - {}", display_issues(.0))]
+    InvalidNamespace(Vec<Issue>),
+
+    #[error("Invalid operator pack '{pack}' - {reason}")]
+    InvalidPack { pack: String, reason: String },
+
+    #[error("Invalid rule signature - {0}")]
+    InvalidSignature(String),
+
+    #[error("Invalid rule syntax{} - {reason}", display_location(*.line, *.column))]
+    InvalidSyntax {
+        reason: String,
+        line: Option<usize>,
+        column: Option<usize>,
+    },
+
     #[error("Invalid variable mapping - {0} is not an object.")]
     InvalidVarMap(Value),
 
+    #[error("No pattern matched the value {value}")]
+    NoMatch { value: Value },
+
+    #[error("Operator '{0}' is not deterministic, so may not be used in deterministic rules")]
+    NondeterministicOperator(String),
+
     #[error("Overflow error during operation: '{0}' on values '{1}' and '{2}'")]
     OverflowBinaryOp(&'static str, String, String),
 
+    #[error("Could not serialize data - {0}")]
+    SerializeData(String),
+
     #[error("Encountered an unexpected error. Please raise an issue on GitHub and include the following error message: {0}")]
     UnexpectedError(String),
 
+    #[error("Undefined function '{name}' called by '{caller}'{}", display_suggestions(.suggestions))]
+    UndefinedFunction {
+        name: String,
+        caller: String,
+        suggestions: Vec<String>,
+    },
+
+    #[error("Unknown operator '{name}'{}", display_suggestions(.suggestions))]
+    UnknownOperator {
+        name: String,
+        suggestions: Vec<String>,
+    },
+
+    #[error("Unknown semantics version '{0}'")]
+    UnknownSemanticsVersion(String),
+
+    #[error("Cannot translate rule to {target} - unsupported: {}", .unsupported.join(", "))]
+    Untranslatable {
+        target: &'static str,
+        unsupported: Vec<String>,
+    },
+
     #[error("Wrong argument count - expected: {expected:?}, actual: {actual:?}")]
     WrongArgumentCount { expected: NumParams, actual: usize },
 }
@@ -58,4 +130,230 @@ impl Error {
     pub(crate) fn wrong_argument_count(expected: NumParams, actual: usize) -> Self {
         Self::WrongArgumentCount { expected, actual }
     }
+
+    /// A stable, machine-readable code identifying the kind of error
+    ///
+    /// Codes will not change between releases, unlike the `Display` output.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::CallDepthExceeded { .. } => "call_depth_exceeded",
+            Self::Cancelled(_) => "cancelled",
+            Self::ContractViolation { .. } => "contract_violation",
+            Self::DeserializeResult { .. } => "deserialize_result",
+            Self::InvalidData { .. } => "invalid_data",
+            Self::DisallowedIdentifier { .. } => "disallowed_identifier",
+            Self::InvalidFile { .. } => "invalid_file",
+            Self::InvalidIdentifier(_) => "invalid_identifier",
+            Self::InvalidOperation { .. } => "invalid_operation",
+            Self::InvalidVariable { .. } => "invalid_variable",
+            Self::InvalidVariableKey { .. } => "invalid_variable_key",
+            Self::InvalidArgument { .. } => "invalid_argument",
+            Self::InvalidNamespace(_) => "invalid_namespace",
+            Self::InvalidPack { .. } => "invalid_pack",
+            Self::InvalidSignature(_) => "invalid_signature",
+            Self::InvalidSyntax { .. } => "invalid_syntax",
+            Self::InvalidVarMap(_) => "invalid_var_map",
+            Self::NoMatch { .. } => "no_match",
+            Self::NondeterministicOperator(_) => "nondeterministic_operator",
+            Self::OverflowBinaryOp(..) => "overflow",
+            Self::SerializeData(_) => "serialize_data",
+            Self::UnexpectedError(_) => "unexpected_error",
+            Self::UndefinedFunction { .. } => "undefined_function",
+            Self::UnknownOperator { .. } => "unknown_operator",
+            Self::UnknownSemanticsVersion(_) => "unknown_semantics_version",
+            Self::Untranslatable { .. } => "untranslatable",
+            Self::WrongArgumentCount { .. } => "wrong_argument_count",
+        }
+    }
+
+    /// Serialize the error as a structured JSON payload.
+    ///
+    /// The payload is an object with the error's `code`, a human-readable
+    /// `message`, and a `details` object holding the error's fields, e.g.:
+    ///
+    /// ```json
+    /// {
+    ///   "code": "wrong_argument_count",
+    ///   "message": "Wrong argument count - expected: Exactly(2), actual: 1",
+    ///   "details": {"expected": {"min": 2, "max": 2}, "actual": 1}
+    /// }
+    /// ```
+    pub fn to_json(&self) -> Value {
+        let details = match self {
+            Self::CallDepthExceeded { function, limit } => {
+                json!({ "function": function, "limit": limit })
+            }
+            Self::Cancelled(id) => json!({ "evaluation": id }),
+            Self::ContractViolation {
+                function,
+                condition,
+                arguments,
+                result,
+            } => json!({
+                "function": function,
+                "condition": condition,
+                "arguments": arguments,
+                "result": result,
+            }),
+            Self::DeserializeResult { value, reason } => {
+                json!({ "value": value, "reason": reason })
+            }
+            Self::InvalidData { value, reason } => {
+                json!({ "value": value, "reason": reason })
+            }
+            Self::DisallowedIdentifier { identifier, reason } => {
+                json!({ "identifier": identifier, "reason": reason })
+            }
+            Self::InvalidFile { path, reason } => {
+                json!({ "path": path, "reason": reason })
+            }
+            Self::InvalidIdentifier(identifier) => json!({ "identifier": identifier }),
+            Self::InvalidOperation { key, reason } => {
+                json!({ "operator": key, "reason": reason })
+            }
+            Self::InvalidVariable { value, reason } => {
+                json!({ "value": value, "reason": reason })
+            }
+            Self::InvalidVariableKey { value, reason } => {
+                json!({ "value": value, "reason": reason })
+            }
+            Self::InvalidArgument {
+                value,
+                operation,
+                reason,
+            } => json!({ "value": value, "operator": operation, "reason": reason }),
+            Self::InvalidNamespace(issues) => json!({
+                "issues": issues
+                    .iter()
+                    .map(|issue| json!({
+                        "code": issue.code(),
+                        "function": issue.function(),
+                        "message": issue.to_string(),
+                    }))
+                    .collect::<Vec<Value>>()
+            }),
+            Self::InvalidPack { pack, reason } => {
+                json!({ "pack": pack, "reason": reason })
+            }
+            Self::InvalidSignature(reason) => json!({ "reason": reason }),
+            Self::InvalidSyntax {
+                reason,
+                line,
+                column,
+            } => json!({ "reason": reason, "line": line, "column": column }),
+            Self::InvalidVarMap(value) => json!({ "value": value }),
+            Self::NoMatch { value } => json!({ "value": value }),
+            Self::NondeterministicOperator(name) => json!({ "operator": name }),
+            Self::OverflowBinaryOp(operation, first, second) => {
+                json!({ "operator": operation, "operands": [first, second] })
+            }
+            Self::SerializeData(reason) => json!({ "reason": reason }),
+            Self::UnexpectedError(reason) => json!({ "reason": reason }),
+            Self::UndefinedFunction {
+                name,
+                caller,
+                suggestions,
+            } => {
+                json!({ "function": name, "caller": caller, "suggestions": suggestions })
+            }
+            Self::UnknownOperator { name, suggestions } => {
+                json!({ "operator": name, "suggestions": suggestions })
+            }
+            Self::UnknownSemanticsVersion(version) => json!({ "version": version }),
+            Self::Untranslatable {
+                target,
+                unsupported,
+            } => json!({ "target": target, "unsupported": unsupported }),
+            Self::WrongArgumentCount { expected, actual } => json!({
+                "expected": num_params_json(expected),
+                "actual": actual,
+            }),
+        };
+        json!({
+            "code": self.code(),
+            "message": self.to_string(),
+            "details": details,
+        })
+    }
+}
+
+/// Represent a number of parameters as its inclusive bounds, with a null
+/// maximum for unbounded parameters.
+fn num_params_json(num_params: &NumParams) -> Value {
+    let (min, max) = match num_params {
+        NumParams::None => (0, Some(0)),
+        NumParams::Any => (0, None),
+        NumParams::Unary => (1, Some(1)),
+        NumParams::Exactly(num) => (*num, Some(*num)),
+        NumParams::AtLeast(num) => (*num, None),
+        NumParams::Variadic(range) => (range.start, Some(range.end.saturating_sub(1))),
+    };
+    json!({ "min": min, "max": max })
+}
+
+fn display_suggestions(suggestions: &[String]) -> String {
+    match suggestions {
+        [] => String::new(),
+        [only] => format!(" - did you mean '{}'?", only),
+        [init @ .., last] => format!(
+            " - did you mean {} or '{}'?",
+            init.iter()
+                .map(|s| format!("'{}'", s))
+                .collect::<Vec<String>>()
+                .join(", "),
+            last
+        ),
+    }
+}
+
+fn display_location(line: Option<usize>, column: Option<usize>) -> String {
+    match (line, column) {
+        (Some(line), Some(column)) => format!(" at line {}, column {}", line, column),
+        _ => String::new(),
+    }
+}
+
+fn display_issues(issues: &[Issue]) -> String {
+    issues
+        .iter()
+        .map(Issue::to_string)
+        .collect::<Vec<String>>()
+        .join("; ")
+}
+
+#[cfg(test)]
+mod test_error {
+    use super::*;
+
+    #[test]
+    fn test_to_json() {
+        let err = Error::wrong_argument_count(NumParams::Variadic(2..4), 1);
+        assert_eq!(
+            err.to_json(),
+            json!({
+                "code": "wrong_argument_count",
+                "message": err.to_string(),
+                "details": {"expected": {"min": 2, "max": 3}, "actual": 1},
+            })
+        );
+
+        let err = Error::invalid_argument(json!("a"), "add", "not a number");
+        let payload = err.to_json();
+        assert_eq!(payload["code"], json!("invalid_argument"));
+        assert_eq!(
+            payload["details"],
+            json!({"value": "a", "operator": "add", "reason": "not a number"})
+        );
+    }
+
+    #[test]
+    fn test_to_json_namespace_issues() {
+        let err = Error::InvalidNamespace(vec![Issue::Recursion {
+            cycle: vec!["a".into(), "b".into()],
+        }]);
+        let payload = err.to_json();
+        assert_eq!(payload["code"], json!("invalid_namespace"));
+        assert_eq!(payload["details"]["issues"][0]["code"], json!("recursion"));
+        assert_eq!(payload["details"]["issues"][0]["function"], json!("a"));
+    }
 }
@@ -0,0 +1,241 @@
+//! Arbitrary Rules
+//!
+//! With the `fuzz` feature, `Ast` implements `arbitrary::Arbitrary`, for
+//! property testing and fuzzing. An arbitrary `Ast` may be any tree of nodes,
+//! most of which won't parse as a rule, which is what's wanted to fuzz the
+//! parser. `WellFormed` generates only rules that parse, using the built-in
+//! operators with valid numbers of arguments, to fuzz the evaluator and the
+//! round-trip of a rule through its JSON form. `ArbitraryValue` generates
+//! JSON, e.g. data to evaluate rules against.
+//!
+//! Trees are limited in depth, so that generation always terminates.
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+use serde_json::{Map, Number, Value};
+
+use crate::ast::Ast;
+use crate::op::{self, NumParams};
+
+/// How deeply generated trees may nest
+const MAX_DEPTH: usize = 4;
+
+/// Operators that aren't generated for well-formed rules: those whose
+/// arguments must have a particular form (`var` is generated separately),
+/// and `log`, which prints
+const EXCLUDED: &[&str] = &["var", "lit", "match", "switch", "try", "obj", "log"];
+
+/// Keys that generated `var`s look up, so that some are found in
+/// `ArbitraryValue`s
+const KEYS: &[&str] = &["", "a", "b", "a.b", "0", "1"];
+
+impl<'a> Arbitrary<'a> for Ast {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        ast(u, MAX_DEPTH)
+    }
+}
+
+/// An AST that always parses as a rule
+#[derive(Clone, Debug, PartialEq)]
+pub struct WellFormed(pub Ast);
+impl WellFormed {
+    /// Generate a rule using only the built-in operators for which `include`
+    /// returns true.
+    pub(crate) fn with_operators(
+        u: &mut Unstructured,
+        include: impl Fn(&str) -> bool,
+    ) -> Result<Self> {
+        let operators: Vec<(&'static str, NumParams)> = op::builtin_names()
+            .filter(|name| !EXCLUDED.contains(name) && include(name))
+            .filter_map(|name| Some((name, op::builtin_param_info(name)?)))
+            .collect();
+        well_formed(u, &operators, MAX_DEPTH).map(Self)
+    }
+}
+impl<'a> Arbitrary<'a> for WellFormed {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Self::with_operators(u, |_| true)
+    }
+}
+impl From<WellFormed> for Value {
+    fn from(rule: WellFormed) -> Self {
+        Value::from(rule.0)
+    }
+}
+
+/// An arbitrary JSON value
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArbitraryValue(pub Value);
+impl<'a> Arbitrary<'a> for ArbitraryValue {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        value(u, MAX_DEPTH).map(Self)
+    }
+}
+
+/// Any node, with any names
+fn ast(u: &mut Unstructured, depth: usize) -> Result<Ast> {
+    if depth == 0 {
+        return value(u, 0).map(Ast::Literal);
+    }
+    let depth = depth - 1;
+    let boxed = |u: &mut Unstructured| -> Result<Option<Box<Ast>>> {
+        match bool::arbitrary(u)? {
+            true => Ok(Some(Box::new(ast(u, depth)?))),
+            false => Ok(None),
+        }
+    };
+    Ok(match u.int_in_range(0..=6)? {
+        0 => Ast::Literal(value(u, depth)?),
+        1 => Ast::Var {
+            path: boxed(u)?,
+            default: boxed(u)?,
+        },
+        2 => Ast::Operator {
+            name: String::arbitrary(u)?,
+            args: asts(u, depth)?,
+        },
+        3 => Ast::FunctionDef {
+            name: String::arbitrary(u)?,
+            params: Vec::arbitrary(u)?,
+            body: Box::new(ast(u, depth)?),
+        },
+        4 => Ast::FunctionCall {
+            name: String::arbitrary(u)?,
+            args: asts(u, depth)?,
+        },
+        5 => Ast::Param(String::arbitrary(u)?),
+        _ => Ast::Object(
+            (0..u.int_in_range(0..=3)?)
+                .map(|_| Ok((String::arbitrary(u)?, ast(u, depth)?)))
+                .collect::<Result<_>>()?,
+        ),
+    })
+}
+
+fn asts(u: &mut Unstructured, depth: usize) -> Result<Vec<Ast>> {
+    (0..u.int_in_range(0..=3)?).map(|_| ast(u, depth)).collect()
+}
+
+/// A literal, `var`, or built-in operation
+fn well_formed(
+    u: &mut Unstructured,
+    operators: &[(&'static str, NumParams)],
+    depth: usize,
+) -> Result<Ast> {
+    if depth == 0 {
+        return scalar(u).map(Ast::Literal);
+    }
+    let depth = depth - 1;
+    Ok(match u.int_in_range(0..=3)? {
+        0 => Ast::Literal(scalar(u)?),
+        1 => Ast::Var {
+            path: Some(Box::new(Ast::Literal(Value::from(*u.choose(KEYS)?)))),
+            default: match bool::arbitrary(u)? {
+                true => Some(Box::new(well_formed(u, operators, depth)?)),
+                false => None,
+            },
+        },
+        _ => {
+            let (name, num_params) = u.choose(operators)?;
+            let len = match num_params {
+                NumParams::None => 0,
+                NumParams::Any => u.int_in_range(0..=3)?,
+                NumParams::Unary => 1,
+                NumParams::Exactly(num) => *num,
+                NumParams::AtLeast(num) => u.int_in_range(*num..=num + 2)?,
+                NumParams::Variadic(range) => {
+                    u.int_in_range(range.start..=range.end - 1)?
+                }
+            };
+            Ast::Operator {
+                name: String::from(*name),
+                args: (0..len)
+                    .map(|_| well_formed(u, operators, depth))
+                    .collect::<Result<_>>()?,
+            }
+        }
+    })
+}
+
+/// A null, boolean, number, or string
+fn scalar(u: &mut Unstructured) -> Result<Value> {
+    Ok(match u.int_in_range(0..=4)? {
+        0 => Value::Null,
+        1 => Value::Bool(bool::arbitrary(u)?),
+        2 => Value::from(i64::arbitrary(u)?),
+        // Infinities and NaN aren't JSON
+        3 => Number::from_f64(f64::arbitrary(u)?).map_or(Value::Null, Value::Number),
+        _ => Value::String(String::arbitrary(u)?),
+    })
+}
+
+/// Any JSON value, nested at most `depth` deep
+fn value(u: &mut Unstructured, depth: usize) -> Result<Value> {
+    if depth == 0 {
+        return scalar(u);
+    }
+    Ok(match u.int_in_range(0..=2)? {
+        0 => Value::Array(
+            (0..u.int_in_range(0..=3)?)
+                .map(|_| value(u, depth - 1))
+                .collect::<Result<_>>()?,
+        ),
+        1 => Value::Object(
+            (0..u.int_in_range(0..=3)?)
+                .map(|_| {
+                    let key = match bool::arbitrary(u)? {
+                        true => String::from(*u.choose(KEYS)?),
+                        false => String::arbitrary(u)?,
+                    };
+                    Ok((key, value(u, depth - 1)?))
+                })
+                .collect::<Result<Map<_, _>>>()?,
+        ),
+        _ => scalar(u)?,
+    })
+}
+
+#[cfg(test)]
+mod test_fuzz {
+    use super::*;
+
+    use crate::{Context, RandomSource, Rule, SeededRandom};
+
+    /// Generate a value from pseudo-random bytes.
+    fn generate<T: for<'a> Arbitrary<'a>>(source: &SeededRandom) -> T {
+        let bytes: Vec<u8> = (0..64)
+            .flat_map(|_| source.next_u64().unwrap().to_le_bytes())
+            .collect();
+        T::arbitrary(&mut Unstructured::new(&bytes)).unwrap()
+    }
+
+    #[test]
+    fn test_well_formed() {
+        let source = SeededRandom::new(0);
+        let ctx = Context {
+            random: Some(alloc::sync::Arc::new(SeededRandom::new(0))),
+            ..Context::default()
+        };
+        for _ in 0..500 {
+            let ast = generate::<WellFormed>(&source);
+            let value = Value::from(ast.clone());
+            let rule = Rule::from_value(&value)
+                .unwrap_or_else(|err| panic!("{} failed to parse: {}", value, err));
+            let printed = Value::from(rule.clone());
+            assert_eq!(Value::from(Rule::from_value(&printed).unwrap()), printed);
+
+            let ArbitraryValue(data) = generate(&source);
+            let _ = rule.apply_with(&data, &ctx);
+        }
+    }
+
+    #[test]
+    fn test_any() {
+        let source = SeededRandom::new(0);
+        for _ in 0..500 {
+            let ast = generate::<Ast>(&source);
+            if let Ok(rule) = Rule::from_value(&Value::from(ast)) {
+                let _ = rule.apply(&generate::<ArbitraryValue>(&source).0);
+            }
+        }
+    }
+}
@@ -1,10 +1,12 @@
 //! Implementations of JavaScript operators for JSON Values
 
+use core::borrow::Borrow;
+use core::f64;
+use core::str::FromStr;
 use serde_json::{Number, Value};
-use std::f64;
-use std::str::FromStr;
 
 use crate::error::Error;
+use crate::prelude::*;
 
 // numeric characters according to parseFloat
 const NUMERICS: &'static [char] = &[
@@ -323,7 +325,7 @@ pub fn abstract_eq(first: &Value, second: &Value) -> bool {
 /// ```
 ///
 pub fn strict_eq(first: &Value, second: &Value) -> bool {
-    if std::ptr::eq(first, second) {
+    if core::ptr::eq(first, second) {
         return true;
     };
     match (first, second) {
@@ -430,12 +432,13 @@ pub fn abstract_gte(first: &Value, second: &Value) -> bool {
 }
 
 /// Get the max of an array of values, performing abstract type conversion
-pub fn abstract_max(items: &Vec<&Value>) -> Result<f64, Error> {
+pub fn abstract_max<V: Borrow<Value>>(items: &[V]) -> Result<f64, Error> {
     items
-        .into_iter()
+        .iter()
+        .map(Borrow::borrow)
         .map(|v| {
             to_number(v).ok_or(Error::InvalidArgument {
-                value: (*v).clone(),
+                value: v.clone(),
                 operation: "max",
                 reason: "Could not convert value to number".into(),
             })
@@ -456,12 +459,13 @@ pub fn abstract_max(items: &Vec<&Value>) -> Result<f64, Error> {
 }
 
 /// Get the max of an array of values, performing abstract type conversion
-pub fn abstract_min(items: &Vec<&Value>) -> Result<f64, Error> {
+pub fn abstract_min<V: Borrow<Value>>(items: &[V]) -> Result<f64, Error> {
     items
-        .into_iter()
+        .iter()
+        .map(Borrow::borrow)
         .map(|v| {
             to_number(v).ok_or(Error::InvalidArgument {
-                value: (*v).clone(),
+                value: v.clone(),
                 operation: "max",
                 reason: "Could not convert value to number".into(),
             })
@@ -515,9 +519,10 @@ pub fn abstract_plus(first: &Value, second: &Value) -> Value {
 /// the behavior for non-numeric inputs is not specified in the spec,
 /// and returning errors seems like a more reasonable course of action
 /// than returning null.
-pub fn parse_float_add(vals: &Vec<&Value>) -> Result<f64, Error> {
-    vals.into_iter()
-        .map(|&v| {
+pub fn parse_float_add<V: Borrow<Value>>(vals: &[V]) -> Result<f64, Error> {
+    vals.iter()
+        .map(Borrow::borrow)
+        .map(|v| {
             parse_float(v).ok_or(Error::InvalidArgument {
                 value: v.clone(),
                 operation: "+",
@@ -538,9 +543,10 @@ pub fn parse_float_add(vals: &Vec<&Value>) -> Result<f64, Error> {
 /// See notes for parse_float_add on how this differs from normal number
 /// conversion as is done for _other_ arithmetic operators in the reference
 /// implementation
-pub fn parse_float_mul(vals: &Vec<&Value>) -> Result<f64, Error> {
-    vals.into_iter()
-        .map(|&v| {
+pub fn parse_float_mul<V: Borrow<Value>>(vals: &[V]) -> Result<f64, Error> {
+    vals.iter()
+        .map(Borrow::borrow)
+        .map(|v| {
             parse_float(v).ok_or(Error::InvalidArgument {
                 value: v.clone(),
                 operation: "*",
@@ -1112,7 +1118,7 @@ mod test_abstract_max {
     fn test_abstract_max() {
         max_cases().into_iter().for_each(|(items, exp)| {
             println!("Max: {:?}", items);
-            let res = abstract_max(&items.iter().collect());
+            let res = abstract_max(&items);
             println!("Res: {:?}", res);
             match exp {
                 Ok(exp) => assert_eq!(res.unwrap(), exp),
@@ -1146,7 +1152,7 @@ mod test_abstract_min {
     fn test_abstract_min() {
         min_cases().into_iter().for_each(|(items, exp)| {
             println!("Min: {:?}", items);
-            let res = abstract_min(&items.iter().collect());
+            let res = abstract_min(&items);
             println!("Res: {:?}", res);
             match exp {
                 Ok(exp) => assert_eq!(res.unwrap(), exp),
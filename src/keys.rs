@@ -0,0 +1,47 @@
+//! Keys
+//!
+//! The `hmac_sha256` operator signs values with keys set on the evaluation's
+//! `Context`. Rules refer to keys by name, so that the keys themselves are
+//! never written into rules, and may be rotated without changing them.
+
+use core::fmt;
+
+use crate::collections::HashMap;
+use crate::prelude::*;
+
+/// Named secret keys for the `hmac_sha256` operator
+///
+/// Keys are omitted from the `Debug` representation, which lists only their
+/// names.
+#[derive(Clone, Default)]
+pub struct HmacKeys {
+    keys: HashMap<String, Vec<u8>>,
+}
+impl HmacKeys {
+    /// Construct an empty set of keys.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a key with the given name, replacing any key with the same name.
+    pub fn with_key<N: Into<String>, K: Into<Vec<u8>>>(
+        mut self,
+        name: N,
+        key: K,
+    ) -> Self {
+        self.keys.insert(name.into(), key.into());
+        self
+    }
+
+    /// The key with the given name, if there is one
+    pub fn get(&self, name: &str) -> Option<&[u8]> {
+        self.keys.get(name).map(Vec::as_slice)
+    }
+}
+impl fmt::Debug for HmacKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names: Vec<&String> = self.keys.keys().collect();
+        names.sort();
+        f.debug_struct("HmacKeys").field("names", &names).finish()
+    }
+}
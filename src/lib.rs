@@ -1,20 +1,138 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
 use serde_json;
 use serde_json::Value;
 
+mod arena;
+mod ast;
+#[cfg(feature = "async")]
+mod asynchronous;
+#[cfg(feature = "std")]
+mod audit;
+mod bundle;
+mod clock;
+#[cfg(feature = "compat-tests")]
+pub mod compat;
+mod context;
+#[cfg(feature = "differential")]
+pub mod differential;
+pub mod dsl;
 mod error;
+#[cfg(feature = "fuzz")]
+mod fuzz;
 // TODO consider whether this should be public; move doctests if so
 pub mod js_op;
+#[cfg(feature = "crypto")]
+mod keys;
+pub mod lint;
+mod locale;
+mod lookup;
+mod money;
+mod namespace;
+mod numeric;
 mod op;
+mod pack;
+#[cfg(feature = "std")]
+mod profiler;
+#[cfg(feature = "pyo3")]
+mod pyo3_iface;
+mod random;
+#[cfg(feature = "std")]
+mod registry;
+mod resolver;
+pub mod rule;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "signing")]
+mod signing;
+#[cfg(feature = "simd-json")]
+mod simd;
+mod stdlib;
+#[cfg(feature = "std")]
+pub mod store;
+pub mod to_sql;
+#[cfg(feature = "tracing")]
+mod trace;
+pub mod typecheck;
+mod units;
 mod value;
-
-use error::Error;
-use value::{Evaluated, Parsed};
+#[cfg(feature = "serde_yaml")]
+mod yaml;
+
+pub use ast::{Ast, AstVisitor, AstVisitorMut};
+#[cfg(feature = "async")]
+pub use asynchronous::{AsyncOperator, BoxFuture};
+#[cfg(feature = "std")]
+pub use audit::{Audit, AuditData, AuditRecord, AuditSink, REDACTED};
+pub use bundle::Bundle;
+#[cfg(feature = "std")]
+pub use clock::SystemClock;
+pub use clock::{Clock, FixedClock};
+use context::Env;
+pub use context::{
+    Context, SemanticsVersion, DEFAULT_MAX_ARRAY_SIZE, DEFAULT_MAX_CALL_DEPTH,
+};
+pub use error::Error;
+#[cfg(feature = "fuzz")]
+pub use fuzz::{ArbitraryValue, WellFormed};
+#[cfg(feature = "crypto")]
+pub use keys::HmacKeys;
+pub use locale::Locale;
+#[cfg(feature = "async")]
+pub use lookup::AsyncLookupResolver;
+pub use lookup::LookupResolver;
+pub use money::ExchangeRates;
+use namespace::Scope;
+pub use namespace::{CheckOptions, ExampleFailure, Issue, Namespace, Shadowed};
+pub use numeric::{JsNumeric, NumericBackend};
+pub use op::data::PathSegment;
+pub use op::{operators, NumParams, OperatorCategory, OperatorInfo};
+pub use pack::{
+    CustomOperator, CustomOperatorFn, OperatorPack, PackInfo, PACK_API_VERSION,
+};
+#[cfg(feature = "std")]
+pub use profiler::{OperatorStats, Profiler};
+#[cfg(feature = "std")]
+pub use random::OsRandom;
+pub use random::{RandomSource, SeededRandom};
+#[cfg(feature = "std")]
+pub use registry::{EvaluationInfo, Registry};
+pub use resolver::{DataResolver, JsonLike, ValueKind};
+pub use rule::{
+    CompiledRule, IdentifierCharacters, IdentifierPolicy, MultiKeyObjects,
+    ParseOptions, Rule,
+};
+#[cfg(feature = "signing")]
+pub use signing::{sign_rule, verify_rule};
+pub use stdlib::{stdlib, STDLIB_VERSION};
+pub use units::Units;
+use value::Evaluated;
 
 const NULL: Value = Value::Null;
 
-trait Parser<'a>: Sized + Into<Value> {
-    fn from_value(value: &'a Value) -> Result<Option<Self>, Error>;
-    fn evaluate(&self, data: &'a Value) -> Result<Evaluated, Error>;
+/// The parts of the std prelude provided by `alloc`, so that modules build
+/// the same with or without `std`
+mod prelude {
+    pub(crate) use alloc::boxed::Box;
+    pub(crate) use alloc::string::{String, ToString};
+    pub(crate) use alloc::vec::Vec;
+    pub(crate) use alloc::{format, vec};
+}
+
+/// Maps and sets, which without `std` are ordered rather than hashed, since
+/// there's no source of randomness to seed a hasher with
+mod collections {
+    #[cfg(not(feature = "std"))]
+    pub(crate) use alloc::collections::{BTreeMap as HashMap, BTreeSet as HashSet};
+    #[cfg(feature = "std")]
+    pub(crate) use std::collections::{HashMap, HashSet};
+}
+
+trait Parser: Sized + Into<Value> {
+    fn from_value(value: &Value, scope: &Scope) -> Result<Option<Self>, Error>;
+    fn evaluate(&self, data: &Value, env: &Env) -> Result<Evaluated<'_>, Error>;
 }
 
 #[cfg(feature = "wasm")]
@@ -82,11 +200,171 @@ pub mod python_iface {
     }
 }
 
+/// C bindings, declared in `include/jsonlogic_plus.h`
+///
+/// Rules, data, and results are passed as null-terminated JSON strings.
+/// Strings returned to the caller are owned by it, and must be released
+/// with `jlp_free()`.
+#[cfg(feature = "ffi")]
+pub mod c_iface {
+    use std::cell::RefCell;
+    use std::ffi::{CStr, CString};
+    use std::os::raw::c_char;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::ptr;
+
+    use serde_json::Value;
+
+    use crate::Error;
+
+    thread_local! {
+        static LAST_ERROR: RefCell<Option<Error>> = const { RefCell::new(None) };
+    }
+
+    /// Read a JSON argument.
+    ///
+    /// # Safety
+    ///
+    /// `json` must be null or point to a null-terminated string.
+    unsafe fn read_json(json: *const c_char, name: &str) -> Result<Value, Error> {
+        if json.is_null() {
+            return Err(Error::InvalidData {
+                value: Value::Null,
+                reason: format!("{} is a null pointer", name),
+            });
+        }
+        let text = CStr::from_ptr(json)
+            .to_str()
+            .map_err(|err| Error::InvalidData {
+                value: Value::Null,
+                reason: format!("{} is not valid UTF-8: {}", name, err),
+            })?;
+        serde_json::from_str(text).map_err(|err| Error::InvalidSyntax {
+            reason: format!("{} is not valid JSON: {}", name, err),
+            line: Some(err.line()),
+            column: Some(err.column()),
+        })
+    }
+
+    /// Return a string to the caller, who must free it with `jlp_free()`.
+    fn to_c_string(value: &Value) -> *mut c_char {
+        // Serialized JSON escapes any null characters within strings
+        CString::new(value.to_string())
+            .map(CString::into_raw)
+            .unwrap_or(ptr::null_mut())
+    }
+
+    /// Evaluate a rule against data, both given as JSON.
+    ///
+    /// Returns the result as JSON, or null if evaluation fails, in which case
+    /// the error may be retrieved with `jlp_last_error()`.
+    ///
+    /// # Safety
+    ///
+    /// `rule_json` and `data_json` must each be null or point to a
+    /// null-terminated string.
+    #[no_mangle]
+    pub unsafe extern "C" fn jlp_apply(
+        rule_json: *const c_char,
+        data_json: *const c_char,
+    ) -> *mut c_char {
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            let rule = read_json(rule_json, "rule_json")?;
+            let data = read_json(data_json, "data_json")?;
+            crate::apply(&rule, &data)
+        }))
+        .unwrap_or_else(|_| Err(Error::UnexpectedError("evaluation panicked".into())));
+        match result {
+            Ok(value) => {
+                LAST_ERROR.with(|last| last.borrow_mut().take());
+                to_c_string(&value)
+            }
+            Err(err) => {
+                LAST_ERROR.with(|last| *last.borrow_mut() = Some(err));
+                ptr::null_mut()
+            }
+        }
+    }
+
+    /// The error from the last call to `jlp_apply()` on this thread, as JSON
+    /// with its `code`, `message`, and `details`, or null if it succeeded.
+    #[no_mangle]
+    pub extern "C" fn jlp_last_error() -> *mut c_char {
+        LAST_ERROR.with(|last| {
+            last.borrow()
+                .as_ref()
+                .map_or(ptr::null_mut(), |err| to_c_string(&err.to_json()))
+        })
+    }
+
+    /// Free a string returned by this library. Null pointers are ignored.
+    ///
+    /// # Safety
+    ///
+    /// `s` must be null or a string returned by this library that hasn't
+    /// already been freed.
+    #[no_mangle]
+    pub unsafe extern "C" fn jlp_free(s: *mut c_char) {
+        if !s.is_null() {
+            drop(CString::from_raw(s));
+        }
+    }
+
+    #[cfg(test)]
+    mod test_c_iface {
+        use super::*;
+
+        unsafe fn take(s: *mut c_char) -> Option<Value> {
+            if s.is_null() {
+                return None;
+            }
+            let value =
+                serde_json::from_str(CStr::from_ptr(s).to_str().unwrap()).unwrap();
+            jlp_free(s);
+            Some(value)
+        }
+
+        #[test]
+        fn test_apply() {
+            let rule = CString::new(r#"{"+": [{"var": "x"}, 1]}"#).unwrap();
+            let data = CString::new(r#"{"x": 1}"#).unwrap();
+            unsafe {
+                let result = take(jlp_apply(rule.as_ptr(), data.as_ptr()));
+                assert_eq!(result, Some(serde_json::json!(2)));
+                assert_eq!(take(jlp_last_error()), None);
+
+                let bad = CString::new(r#"{"+": "#).unwrap();
+                assert_eq!(take(jlp_apply(bad.as_ptr(), data.as_ptr())), None);
+                let error = take(jlp_last_error()).unwrap();
+                assert_eq!(error["code"], "invalid_syntax");
+                assert_eq!(error["details"]["line"], 1);
+
+                assert!(jlp_apply(rule.as_ptr(), ptr::null()).is_null());
+                let error = take(jlp_last_error()).unwrap();
+                assert_eq!(error["code"], "invalid_data");
+
+                jlp_free(ptr::null_mut());
+            }
+        }
+    }
+}
+
 /// Run JSONLogic for the given operation and data.
 ///
+/// If the same rule will be applied to more than one piece of data, prefer
+/// parsing it once with [`Rule::from_value`](struct.Rule.html#method.from_value).
 pub fn apply(value: &Value, data: &Value) -> Result<Value, Error> {
-    let parsed = Parsed::from_value(&value)?;
-    parsed.evaluate(data).map(Value::from)
+    Rule::from_value(value)?.apply(data)
+}
+
+/// Run JSONLogic for the given operation against any serializable data.
+///
+/// See [`Rule::apply_serialize`](struct.Rule.html#method.apply_serialize).
+pub fn apply_serialize<T: serde::Serialize + ?Sized>(
+    value: &Value,
+    data: &T,
+) -> Result<Value, Error> {
+    Rule::from_value(value)?.apply_serialize(data)
 }
 
 #[cfg(test)]
@@ -251,6 +529,12 @@ mod jsonlogic_tests {
             (json!({"var": 1}), json!(["foo", "bar"]), Ok(json!("bar"))),
             // Absent variable
             (json!({"var": "foo"}), json!({}), Ok(json!(null))),
+            // Before V2, a default whose value is a rule is evaluated again
+            (
+                json!({"var": ["zz", {"lit": {"var": "n"}}]}),
+                json!({"n": 5}),
+                Ok(json!(5)),
+            ),
             (
                 json!({"==": [{"var": "first"}, true]}),
                 json!({"first": true}),
@@ -877,166 +1161,1379 @@ mod jsonlogic_tests {
         ]
     }
 
-    fn cat_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
-        vec![
-            (json!({"cat": []}), json!({}), Ok(json!(""))),
-            (json!({"cat": [1]}), json!({}), Ok(json!("1"))),
-            (json!({"cat": ["a"]}), json!({}), Ok(json!("a"))),
-            (json!({"cat": ["a", "b"]}), json!({}), Ok(json!("ab"))),
-            (json!({"cat": ["a", "b", "c"]}), json!({}), Ok(json!("abc"))),
-            (json!({"cat": ["a", "b", 1]}), json!({}), Ok(json!("ab1"))),
-        ]
-    }
-
-    fn substr_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
+    fn range_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
         vec![
-            // Wrong number of arguments
-            (json!({"substr": []}), json!({}), Err(())),
-            (json!({"substr": ["foo"]}), json!({}), Err(())),
-            (json!({"substr": ["foo", 1, 2, 3]}), json!({}), Err(())),
-            // Wrong argument types
-            (json!({"substr": [12, 1]}), json!({}), Err(())),
-            (json!({"substr": ["foo", "12"]}), json!({}), Err(())),
-            // Non-negative indices
-            (json!({"substr": ["foo", 0]}), json!({}), Ok(json!("foo"))),
-            (json!({"substr": ["foo", 1]}), json!({}), Ok(json!("oo"))),
-            (json!({"substr": ["foo", 2]}), json!({}), Ok(json!("o"))),
-            // Negative indices
-            (json!({"substr": ["foo", -1]}), json!({}), Ok(json!("o"))),
-            (json!({"substr": ["foo", -2]}), json!({}), Ok(json!("oo"))),
-            (json!({"substr": ["foo", -3]}), json!({}), Ok(json!("foo"))),
-            // Out-of-bounds indices
-            (json!({"substr": ["foo", 3]}), json!({}), Ok(json!(""))),
-            (json!({"substr": ["foo", 20]}), json!({}), Ok(json!(""))),
-            (json!({"substr": ["foo", -4]}), json!({}), Ok(json!("foo"))),
-            // Non-negative Limits
-            (json!({"substr": ["foo", 0, 1]}), json!({}), Ok(json!("f"))),
+            (json!({"range": [4]}), json!({}), Ok(json!([0, 1, 2, 3]))),
+            (json!({"range": [2, 5]}), json!({}), Ok(json!([2, 3, 4]))),
             (
-                json!({"substr": ["foo", 0, 3]}),
+                json!({"range": [0, 10, 3]}),
                 json!({}),
-                Ok(json!("foo")),
+                Ok(json!([0, 3, 6, 9])),
             ),
-            (json!({"substr": ["foo", 0, 0]}), json!({}), Ok(json!(""))),
-            (json!({"substr": ["foo", 1, 1]}), json!({}), Ok(json!("o"))),
-            // Negative Limits
             (
-                json!({"substr": ["foo", 0, -1]}),
+                json!({"range": [5, 0, -2]}),
                 json!({}),
-                Ok(json!("fo")),
+                Ok(json!([5, 3, 1])),
             ),
-            (json!({"substr": ["foo", 0, -2]}), json!({}), Ok(json!("f"))),
-            (json!({"substr": ["foo", 0, -3]}), json!({}), Ok(json!(""))),
-            // Out-of-bounds limits
             (
-                json!({"substr": ["foo", 0, 10]}),
+                json!({"range": [0, 1, 0.25]}),
                 json!({}),
-                Ok(json!("foo")),
+                Ok(json!([0, 0.25, 0.5, 0.75])),
             ),
-            (json!({"substr": ["foo", 0, -10]}), json!({}), Ok(json!(""))),
-            // Negative indices with negative limits
+            (json!({"range": [5, 2]}), json!({}), Ok(json!([]))),
+            (json!({"range": [0]}), json!({}), Ok(json!([]))),
+            // Integers are counted exactly, even where floats can't be
             (
-                json!({"substr": ["foo", -3, -2]}),
+                json!({"range": [9223372036854775806_i64, 9223372036854775807_i64]}),
                 json!({}),
-                Ok(json!("f")),
+                Ok(json!([9223372036854775806_i64])),
             ),
-            // Negative indices with positive limits
             (
-                json!({"substr": ["foo", -3, 2]}),
+                json!({"range": [-9223372036854775807_i64, -9223372036854775808_i64, -1]}),
                 json!({}),
-                Ok(json!("fo")),
+                Ok(json!([-9223372036854775807_i64])),
             ),
-            // Out-of-bounds indices with out-of-bounds limits
-            (json!({"substr": ["foo", 10, 10]}), json!({}), Ok(json!(""))),
             (
-                json!({"substr": ["foo", 10, -10]}),
+                json!({"range": [9007199254740993_i64, 9007199254740999_i64, 3]}),
                 json!({}),
-                Ok(json!("")),
+                Ok(json!([9007199254740993_i64, 9007199254740996_i64])),
             ),
             (
-                json!({"substr": ["foo", -10, 10]}),
+                json!({"range": [{"var": "from"}, {"var": "to"}]}),
+                json!({"from": 1, "to": 3}),
+                Ok(json!([1, 2])),
+            ),
+            (
+                json!({"reduce": [{"range": [1, 5]}, {"*": [{"var": "accumulator"}, {"var": "current"}]}, 1]}),
                 json!({}),
-                Ok(json!("foo")),
+                Ok(json!(24)),
             ),
+            (json!({"range": [0, 5, 0]}), json!({}), Err(())),
+            (json!({"range": ["0", 5]}), json!({}), Err(())),
+            (json!({"range": [0, null]}), json!({}), Err(())),
+            // More than the default maximum number of elements
+            (json!({"range": [0, 1e18]}), json!({}), Err(())),
             (
-                json!({"substr": ["foo", -10, -10]}),
+                json!({"range": [-9223372036854775808_i64, 9223372036854775807_i64]}),
                 json!({}),
-                Ok(json!("")),
+                Err(()),
             ),
+            (json!({"range": []}), json!({}), Err(())),
         ]
     }
 
-    fn log_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
+    fn zip_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
         vec![
-            // Invalid number of arguments
-            (json!({"log": []}), json!({}), Err(())),
-            (json!({"log": [1, 2]}), json!({}), Err(())),
-            // Correct number of arguments
-            (json!({"log": [1]}), json!({}), Ok(json!(1))),
-            (json!({"log": 1}), json!({}), Ok(json!(1))),
+            (
+                json!({"zip": [[1, 2, 3], ["a", "b"]]}),
+                json!({}),
+                Ok(json!([[1, "a"], [2, "b"]])),
+            ),
+            (
+                json!({"zip": [{"var": "xs"}, {"var": "ys"}, [true, false]]}),
+                json!({"xs": [1, 2], "ys": [3, 4]}),
+                Ok(json!([[1, 3, true], [2, 4, false]])),
+            ),
+            (json!({"zip": [[1, 2], null]}), json!({}), Ok(json!([]))),
+            (json!({"zip": [[1, 2], "ab"]}), json!({}), Err(())),
+            (json!({"zip": [[1, 2]]}), json!({}), Err(())),
+            (
+                json!({"zip_with": [
+                    {"var": "prices"},
+                    {"var": "quantities"},
+                    {"*": [{"var": "0"}, {"var": "1"}]}
+                ]}),
+                json!({"prices": [1.5, 2, 10], "quantities": [2, 3, 0]}),
+                Ok(json!([3, 6, 0])),
+            ),
+            (
+                json!({"zip_with": [[1, 2], [3], {"cat": [{"var": "0"}, "-", {"var": "1"}]}]}),
+                json!({}),
+                Ok(json!(["1-3"])),
+            ),
+            (json!({"zip_with": [[1], {"var": "0"}]}), json!({}), Err(())),
+            (
+                json!({"zip_with": [[1], 1, {"var": "0"}]}),
+                json!({}),
+                Err(()),
+            ),
+            (
+                json!({"cartesian": [[1, 2], ["a", "b"]]}),
+                json!({}),
+                Ok(json!([[1, "a"], [1, "b"], [2, "a"], [2, "b"]])),
+            ),
+            (
+                json!({"cartesian": [["s", "m"], ["red"], [true, false]]}),
+                json!({}),
+                Ok(json!([
+                    ["s", "red", true],
+                    ["s", "red", false],
+                    ["m", "red", true],
+                    ["m", "red", false]
+                ])),
+            ),
+            (json!({"cartesian": [[1, 2], []]}), json!({}), Ok(json!([]))),
+            (json!({"cartesian": [[1, 2], {}]}), json!({}), Err(())),
+            (
+                json!({"cartesian": [{"range": [1000]}, {"range": [1000]}, []]}),
+                json!({}),
+                Ok(json!([])),
+            ),
+            (
+                json!({"cartesian": [{"range": [1000]}, {"range": [1000]}, {"range": [1000]}]}),
+                json!({}),
+                Err(()),
+            ),
         ]
     }
 
-    fn lt_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
+    fn index_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
         vec![
-            (json!({"<": [1, 2]}), json!({}), Ok(json!(true))),
-            (json!({"<": [3, 2]}), json!({}), Ok(json!(false))),
+            // The first three line items
             (
-                json!({"<": [1, {"var": "foo"}]}),
-                json!({"foo": 5}),
+                json!({"filter": [{"var": "items"}, {"<": [{"var": "#index"}, 3]}]}),
+                json!({"items": ["a", "b", "c", "d"]}),
+                Ok(json!(["a", "b", "c"])),
+            ),
+            (
+                json!({"map": [["a", "b"], {"cat": [{"var": "#index"}, {"var": ""}]}]}),
+                json!({}),
+                Ok(json!(["0a", "1b"])),
+            ),
+            (
+                json!({"reduce": [
+                    [10, 20, 30],
+                    {"+": [{"var": "accumulator"}, {"*": [{"var": "current"}, {"var": "#index"}]}]},
+                    0
+                ]}),
+                json!({}),
+                Ok(json!(80)),
+            ),
+            (
+                json!({"all": [[0, 1, 2], {"==": [{"var": "#index"}, {"var": ""}]}]}),
+                json!({}),
                 Ok(json!(true)),
             ),
-            (json!({"<": [1, 2, 3]}), json!({}), Ok(json!(true))),
-            (json!({"<": [3, 2, 3]}), json!({}), Ok(json!(false))),
-            (json!({"<": [1, 2, 1]}), json!({}), Ok(json!(false))),
+            (
+                json!({"some": ["abc", {"==": [{"var": "#index"}, 3]}]}),
+                json!({}),
+                Ok(json!(false)),
+            ),
+            (
+                json!({"none": [[1, 1], {"==": [{"var": "#index"}, {"var": ""}]}]}),
+                json!({}),
+                Ok(json!(false)),
+            ),
+            // Nested iterations have their own indexes
+            (
+                json!({"map": [
+                    [["a", "b"], ["c"]],
+                    {"map": [{"var": ""}, {"var": "#index"}]}
+                ]}),
+                json!({}),
+                Ok(json!([[0, 1], [0]])),
+            ),
+            (
+                json!({"zip_with": [[1, 2], [3, 4], {"var": "#index"}]}),
+                json!({}),
+                Ok(json!([0, 1])),
+            ),
+            // Outside of an iteration, it's an ordinary variable
+            (json!({"var": "#index"}), json!({"#index": 5}), Ok(json!(5))),
+            (json!({"var": ["#index", 0]}), json!({}), Ok(json!(0))),
         ]
     }
 
-    fn gt_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
+    fn parent_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
         vec![
-            (json!({">": [1, 2]}), json!({}), Ok(json!(false))),
-            (json!({">": [3, 2]}), json!({}), Ok(json!(true))),
             (
-                json!({">": [1, {"var": "foo"}]}),
-                json!({"foo": 5}),
-                Ok(json!(false)),
+                json!({"filter": [
+                    {"var": "orders"},
+                    {">=": [{"var": "total"}, {"var": "../threshold"}]}
+                ]}),
+                json!({"threshold": 100, "orders": [{"total": 50}, {"total": 150}]}),
+                Ok(json!([{"total": 150}])),
+            ),
+            (
+                json!({"all": [{"var": "scores"}, {"<=": [{"var": ""}, {"var": "../max"}]}]}),
+                json!({"max": 10, "scores": [3, 10]}),
+                Ok(json!(true)),
+            ),
+            (
+                json!({"reduce": [
+                    [1, 2],
+                    {"+": [{"var": "accumulator"}, {"*": [{"var": "current"}, {"var": "../rate"}]}]},
+                    0
+                ]}),
+                json!({"rate": 3}),
+                Ok(json!(9)),
+            ),
+            // Each `../` is one iterating operator further out
+            (
+                json!({"map": [
+                    {"var": "teams"},
+                    {"map": [
+                        {"var": "members"},
+                        {"cat": [{"var": "../name"}, "/", {"var": ""}, "@", {"var": "../../org"}]}
+                    ]}
+                ]}),
+                json!({"org": "acme", "teams": [{"name": "x", "members": ["a", "b"]}]}),
+                Ok(json!([["x/a@acme", "x/b@acme"]])),
+            ),
+            (
+                json!({"map": [[["a"], ["b", "c"]], {"map": [{"var": ""}, {"var": "../#index"}]}]}),
+                json!({}),
+                Ok(json!([[0], [1, 1]])),
+            ),
+            (
+                json!({"map": [[1], {"var": "../"}]}),
+                json!({"k": 1}),
+                Ok(json!([{"k": 1}])),
+            ),
+            // Beyond the outermost operator, variables are missing
+            (
+                json!({"map": [[1], {"var": ["../../k", "none"]}]}),
+                json!({"k": 1}),
+                Ok(json!(["none"])),
+            ),
+            (json!({"var": "../k"}), json!({"k": 1}), Ok(json!(null))),
+            (
+                json!({"find": [[1, 2, 3], {">": [{"var": ""}, {"var": "../min"}]}]}),
+                json!({"min": 1}),
+                Ok(json!(2)),
+            ),
+            (
+                json!({"sort_with": [[1, 2], {"-": [{"var": "b"}, {"var": "a"}]}, {"var": "desc"}]}),
+                json!({"desc": true}),
+                Ok(json!([1, 2])),
             ),
-            (json!({">": [1, 2, 3]}), json!({}), Ok(json!(false))),
-            (json!({">": [3, 2, 3]}), json!({}), Ok(json!(false))),
-            (json!({">": [1, 2, 1]}), json!({}), Ok(json!(false))),
-            (json!({">": [3, 2, 1]}), json!({}), Ok(json!(true))),
         ]
     }
 
-    fn plus_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
+    fn selection_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
         vec![
-            (json!({"+": []}), json!({}), Ok(json!(0))),
-            (json!({"+": [1]}), json!({}), Ok(json!(1))),
-            (json!({"+": ["1"]}), json!({}), Ok(json!(1))),
-            (json!({"+": [1, 1]}), json!({}), Ok(json!(2))),
-            (json!({"+": [1, 1, 1]}), json!({}), Ok(json!(3))),
-            (json!({"+": [1, 1, false]}), json!({}), Err(())),
-            (json!({"+": [1, 1, "1"]}), json!({}), Ok(json!(3))),
             (
-                json!({"+": [1, 1, "123abc"]}), // WHY???
+                json!({"find": [{"var": "users"}, {">=": [{"var": "age"}, 18]}]}),
+                json!({"users": [{"name": "a", "age": 12}, {"name": "b", "age": 30}, {"name": "c", "age": 40}]}),
+                Ok(json!({"name": "b", "age": 30})),
+            ),
+            (
+                json!({"find": [[1, 2], {">": [{"var": ""}, 5]}]}),
                 json!({}),
-                Ok(json!(125)),
+                Ok(json!(null)),
+            ),
+            (json!({"find": [null, true]}), json!({}), Ok(json!(null))),
+            (json!({"find": ["abc", true]}), json!({}), Err(())),
+            (
+                json!({"position": [["a", "b", "c"], {"==": [{"var": ""}, "c"]}]}),
+                json!({}),
+                Ok(json!(2)),
+            ),
+            (
+                json!({"position": [["a"], {"==": [{"var": ""}, "z"]}]}),
+                json!({}),
+                Ok(json!(-1)),
+            ),
+            (
+                json!({"min_by": [{"var": "items"}, {"var": "price"}]}),
+                json!({"items": [{"id": 1, "price": 5}, {"id": 2, "price": 3}, {"id": 3, "price": 3}]}),
+                Ok(json!({"id": 2, "price": 3})),
+            ),
+            (
+                json!({"max_by": [{"var": "items"}, {"var": "price"}]}),
+                json!({"items": [{"id": 1, "price": 5}, {"id": 2, "price": 3}, {"id": 3, "price": 5}]}),
+                Ok(json!({"id": 1, "price": 5})),
+            ),
+            (
+                json!({"max_by": [[9, "10", 8], {"var": ""}]}),
+                json!({}),
+                Ok(json!("10")),
+            ),
+            (
+                json!({"min_by": [[], {"var": ""}]}),
+                json!({}),
+                Ok(json!(null)),
+            ),
+            (
+                json!({"sum_by": [{"var": "items"}, {"*": [{"var": "price"}, {"var": "qty"}]}]}),
+                json!({"items": [{"price": 2, "qty": 3}, {"price": 1.5, "qty": 2}]}),
+                Ok(json!(9)),
+            ),
+            (
+                json!({"sum_by": [[], {"var": "n"}]}),
+                json!({}),
+                Ok(json!(0)),
+            ),
+            (
+                json!({"sum_by": [[{"n": "abc"}], {"var": "n"}]}),
+                json!({}),
+                Err(()),
+            ),
+            (json!({"sum_by": [{}, 1]}), json!({}), Err(())),
+            // The expression is evaluated against each element
+            (
+                json!({"find": [{"var": "xs"}, {"var": "missing"}]}),
+                json!({"xs": [{"missing": 0}, {"missing": 1}], "missing": 1}),
+                Ok(json!({"missing": 1})),
             ),
         ]
     }
 
-    fn minus_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
+    fn distinct_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
         vec![
-            (json!({"-": "5"}), json!({}), Ok(json!(-5))),
-            (json!({"-": [2]}), json!({}), Ok(json!(-2))),
-            (json!({"-": [2, 2]}), json!({}), Ok(json!(0))),
-            (json!({"-": ["9", [3]]}), json!({}), Ok(json!(6))),
+            (
+                json!({"distinct_by": [{"var": "users"}, {"var": "email"}]}),
+                json!({"users": [
+                    {"id": 1, "email": "a@example.com"},
+                    {"id": 2, "email": "b@example.com"},
+                    {"id": 3, "email": "a@example.com"},
+                ]}),
+                Ok(json!([
+                    {"id": 1, "email": "a@example.com"},
+                    {"id": 2, "email": "b@example.com"},
+                ])),
+            ),
+            // Keys are compared by their contents, without converting types
+            (
+                json!({"distinct_by": [{"var": "xs"}, {"var": "k"}]}),
+                json!({"xs": [
+                    {"k": {"a": 1, "b": [1, 2]}},
+                    {"k": {"b": [1, 2], "a": 1}},
+                    {"k": "1"},
+                    {"k": 1},
+                    {"k": [1, 2]},
+                    {"k": [2, 1]},
+                ]}),
+                Ok(json!([
+                    {"k": {"a": 1, "b": [1, 2]}},
+                    {"k": "1"},
+                    {"k": 1},
+                    {"k": [1, 2]},
+                    {"k": [2, 1]},
+                ])),
+            ),
+            (
+                json!({"distinct_by": [[1, 2, 3, 4], {"%": [{"var": ""}, 2]}]}),
+                json!({}),
+                Ok(json!([1, 2])),
+            ),
+            (
+                json!({"distinct_by": [null, {"var": ""}]}),
+                json!({}),
+                Ok(json!([])),
+            ),
+            (
+                json!({"distinct_by": ["aab", {"var": ""}]}),
+                json!({}),
+                Err(()),
+            ),
         ]
     }
 
-    fn multiplication_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
+    fn sort_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
         vec![
-            (json!({"*": 1}), json!({}), Ok(json!(1))),
-            (json!({"*": [1]}), json!({}), Ok(json!(1))),
+            (
+                json!({"sort_by": [{"var": "users"}, {"var": "age"}]}),
+                json!({"users": [
+                    {"name": "a", "age": 30},
+                    {"name": "b", "age": 20},
+                    {"name": "c", "age": 30},
+                    {"name": "d", "age": 10},
+                ]}),
+                Ok(json!([
+                    {"name": "d", "age": 10},
+                    {"name": "b", "age": 20},
+                    {"name": "a", "age": 30},
+                    {"name": "c", "age": 30},
+                ])),
+            ),
+            // Descending sorts are stable too
+            (
+                json!({"sort_by": [{"var": "users"}, {"var": "age"}, true]}),
+                json!({"users": [
+                    {"name": "a", "age": 30},
+                    {"name": "b", "age": 20},
+                    {"name": "c", "age": 30},
+                ]}),
+                Ok(json!([
+                    {"name": "a", "age": 30},
+                    {"name": "c", "age": 30},
+                    {"name": "b", "age": 20},
+                ])),
+            ),
+            (
+                json!({"sort_by": [
+                    {"var": "users"},
+                    {"merge": [{"var": "team"}, {"var": "age"}]},
+                    [false, true]
+                ]}),
+                json!({"users": [
+                    {"name": "a", "team": "y", "age": 30},
+                    {"name": "b", "team": "x", "age": 20},
+                    {"name": "c", "team": "y", "age": 40},
+                    {"name": "d", "team": "x", "age": 25},
+                ]}),
+                Ok(json!([
+                    {"name": "d", "team": "x", "age": 25},
+                    {"name": "b", "team": "x", "age": 20},
+                    {"name": "c", "team": "y", "age": 40},
+                    {"name": "a", "team": "y", "age": 30},
+                ])),
+            ),
+            (
+                json!({"sort_by": [["b", 2, null, true, "a", 10, false], {"var": ""}]}),
+                json!({}),
+                Ok(json!([null, false, true, 2, 10, "a", "b"])),
+            ),
+            (
+                json!({"sort_by": [null, {"var": ""}]}),
+                json!({}),
+                Ok(json!([])),
+            ),
+            (
+                json!({"sort_by": [[1, 2], {"var": "o"}]}),
+                json!({}),
+                Ok(json!([1, 2])),
+            ),
+            (
+                json!({"sort_by": [[{"a": 1}, {"a": 2}], {"var": ""}]}),
+                json!({}),
+                Err(()),
+            ),
+            (
+                json!({"sort_by": [[1, 2], {"var": ""}, "desc"]}),
+                json!({}),
+                Err(()),
+            ),
+            (json!({"sort_by": ["ba", {"var": ""}]}), json!({}), Err(())),
+            (
+                json!({"sort_with": [
+                    {"var": "items"},
+                    {"-": [{"var": "a.price"}, {"var": "b.price"}]}
+                ]}),
+                json!({"items": [{"id": 1, "price": 5}, {"id": 2, "price": 2}, {"id": 3, "price": 5}]}),
+                Ok(
+                    json!([{"id": 2, "price": 2}, {"id": 1, "price": 5}, {"id": 3, "price": 5}]),
+                ),
+            ),
+            (
+                json!({"sort_with": [[1, 3, 2], {"-": [{"var": "a"}, {"var": "b"}]}, true]}),
+                json!({}),
+                Ok(json!([3, 2, 1])),
+            ),
+            (
+                json!({"sort_with": [[1, 3, 2], {"var": "a"}, [true]]}),
+                json!({}),
+                Err(()),
+            ),
+            (json!({"sort_with": [[1, 3, 2], "a"]}), json!({}), Err(())),
+        ]
+    }
+
+    fn reduce_while_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
+        vec![
+            // Stops at the first order that exceeds the budget
+            (
+                json!({"reduce_while": [
+                    {"var": "orders"},
+                    {"if": [
+                        {">": [{"+": [{"var": "accumulator"}, {"var": "current"}]}, 10]},
+                        {"reduced": {"var": "accumulator"}},
+                        {"+": [{"var": "accumulator"}, {"var": "current"}]}
+                    ]},
+                    0
+                ]}),
+                json!({"orders": [4, 5, 3, 1]}),
+                Ok(json!(9)),
+            ),
+            // Elements after the reduced one aren't evaluated
+            (
+                json!({"reduce_while": [
+                    [1, "x"],
+                    {"reduced": {"+": [{"var": "current"}, 1]}},
+                    0
+                ]}),
+                json!({}),
+                Ok(json!(2)),
+            ),
+            (
+                json!({"reduce_while": [
+                    [1, 2, 3],
+                    {"+": [{"var": "accumulator"}, {"var": "current"}]},
+                    0
+                ]}),
+                json!({}),
+                Ok(json!(6)),
+            ),
+            (
+                json!({"reduce_while": [
+                    [3, 4],
+                    {"reduced": {"var": "#index"}},
+                    null
+                ]}),
+                json!({}),
+                Ok(json!(0)),
+            ),
+            (
+                json!({"reduce_while": [null, 1, "init"]}),
+                json!({}),
+                Ok(json!("init")),
+            ),
+            (json!({"reduce_while": ["abc", 1, 0]}), json!({}), Err(())),
+            (
+                json!({"reduced": [1]}),
+                json!({}),
+                Ok(json!({"reduced": 1})),
+            ),
+            // Only objects with just the key are reduced
+            (
+                json!({"reduce_while": [[1, 2], {"var": "../o"}, 0]}),
+                json!({"o": {"reduced": 1, "x": 2}}),
+                Ok(json!({"reduced": 1, "x": 2})),
+            ),
+            // and only if they were wrapped by `reduced`, rather than the data
+            (
+                json!({"reduce_while": [[1, 2], {"var": "../o"}, 0]}),
+                json!({"o": {"reduced": 1}}),
+                Ok(json!({"reduced": 1})),
+            ),
+            (
+                json!({"reduce_while": [
+                    [1, 2],
+                    {"if": [{"reduced": 1}, {"var": "../o"}, 0]},
+                    0
+                ]}),
+                json!({"o": {"reduced": 2}}),
+                Ok(json!({"reduced": 2})),
+            ),
+            (
+                json!({"reduce_while": [
+                    [1, 2],
+                    {"map": [[1], {"reduced": {"var": ""}}]},
+                    0
+                ]}),
+                json!({}),
+                Ok(json!([{"reduced": 1}])),
+            ),
+        ]
+    }
+
+    fn object_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
+        vec![
+            (
+                json!({"obj": [
+                    ["name", {"var": "user.name"}],
+                    [{"cat": ["is_", {"var": "user.role"}]}, true]
+                ]}),
+                json!({"user": {"name": "Ann", "role": "admin"}}),
+                Ok(json!({"name": "Ann", "is_admin": true})),
+            ),
+            (json!({"obj": []}), json!({}), Ok(json!({}))),
+            (
+                json!({"obj": [["a", 1], ["a", 2]]}),
+                json!({}),
+                Ok(json!({"a": 2})),
+            ),
+            (json!({"obj": [[1, "a"]]}), json!({}), Err(())),
+            (json!({"obj": [[{"var": "k"}, 1]]}), json!({}), Err(())),
+            (
+                json!({"map_values": [{"var": "prices"}, {"*": [{"var": ""}, 2]}]}),
+                json!({"prices": {"apple": 1, "pear": 1.5}}),
+                Ok(json!({"apple": 2, "pear": 3})),
+            ),
+            (
+                json!({"map_values": [
+                    {"var": "limits"},
+                    {"min": [{"var": ""}, {"var": "../cap"}]}
+                ]}),
+                json!({"limits": {"a": 5, "b": 20}, "cap": 10}),
+                Ok(json!({"a": 5, "b": 10})),
+            ),
+            (json!({"map_values": [null, 1]}), json!({}), Ok(json!({}))),
+            (json!({"map_values": [[1, 2], 1]}), json!({}), Err(())),
+        ]
+    }
+
+    fn switch_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
+        let plan = || {
+            json!({"switch": [
+                {"var": "plan"},
+                {
+                    "free": 0,
+                    "pro": {"*": [{"var": "seats"}, 10]},
+                    "enterprise": {"var": "quote"}
+                },
+                {"var": "fallback"}
+            ]})
+        };
+        vec![
+            (plan(), json!({"plan": "free"}), Ok(json!(0))),
+            (plan(), json!({"plan": "pro", "seats": 3}), Ok(json!(30))),
+            (
+                plan(),
+                json!({"plan": "enterprise", "quote": 500}),
+                Ok(json!(500)),
+            ),
+            (
+                plan(),
+                json!({"plan": "other", "fallback": -1}),
+                Ok(json!(-1)),
+            ),
+            // Only the matching branch is evaluated
+            (
+                json!({"switch": ["a", {"a": 1, "b": {"/": [1, "x"]}}]}),
+                json!({}),
+                Ok(json!(1)),
+            ),
+            // A single case isn't parsed as an operation
+            (
+                json!({"switch": ["var", {"var": {"var": "x"}}]}),
+                json!({"x": 1}),
+                Ok(json!(1)),
+            ),
+            (
+                json!({"switch": ["c", {"a": 1}]}),
+                json!({}),
+                Ok(json!(null)),
+            ),
+            // Only strings select a case, and null selects none
+            (
+                json!({"switch": [{"var": "nope"}, {"null": 1}, "other"]}),
+                json!({}),
+                Ok(json!("other")),
+            ),
+            (
+                json!({"switch": [1, {"1": "one"}, "other"]}),
+                json!({}),
+                Err(()),
+            ),
+            (json!({"switch": [true, {"true": 1}]}), json!({}), Err(())),
+            (json!({"switch": [{"lit": {}}, {}, 1]}), json!({}), Err(())),
+            (
+                json!({"switch": ["a", {}, "other"]}),
+                json!({}),
+                Ok(json!("other")),
+            ),
+            (json!({"switch": ["a", ["a", 1]]}), json!({}), Err(())),
+            (json!({"switch": ["a"]}), json!({}), Err(())),
+        ]
+    }
+
+    fn partition_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
+        vec![
+            (
+                json!({"chunk": [[1, 2, 3, 4, 5], 2]}),
+                json!({}),
+                Ok(json!([[1, 2], [3, 4], [5]])),
+            ),
+            (json!({"chunk": [[], 3]}), json!({}), Ok(json!([]))),
+            (json!({"chunk": [[1], 0]}), json!({}), Err(())),
+            (json!({"chunk": ["abc", 1]}), json!({}), Err(())),
+            (
+                json!({"window": [[1, 2, 3, 4], 3]}),
+                json!({}),
+                Ok(json!([[1, 2, 3], [2, 3, 4]])),
+            ),
+            (
+                json!({"window": [[1, 2, 3, 4, 5, 6], 2, 3]}),
+                json!({}),
+                Ok(json!([[1, 2], [4, 5]])),
+            ),
+            (json!({"window": [[1, 2], 3]}), json!({}), Ok(json!([]))),
+            (json!({"window": [[1, 2], 1, 0]}), json!({}), Err(())),
+            // A moving average
+            (
+                json!({"map": [
+                    {"window": [{"var": "readings"}, 3]},
+                    {"/": [{"reduce": [{"var": ""}, {"+": [{"var": "current"}, {"var": "accumulator"}]}, 0]}, 3]}
+                ]}),
+                json!({"readings": [3, 6, 9, 0, 3]}),
+                Ok(json!([6, 5, 4])),
+            ),
+            (
+                json!({"take": [[1, 2, 3], 2]}),
+                json!({}),
+                Ok(json!([1, 2])),
+            ),
+            (
+                json!({"take": [[1, 2, 3], -2]}),
+                json!({}),
+                Ok(json!([2, 3])),
+            ),
+            (
+                json!({"take": [[1, 2, 3], 5]}),
+                json!({}),
+                Ok(json!([1, 2, 3])),
+            ),
+            (
+                json!({"take": [[1, 2, 3], -5]}),
+                json!({}),
+                Ok(json!([1, 2, 3])),
+            ),
+            (json!({"take": [null, 1]}), json!({}), Ok(json!([]))),
+            (json!({"take": [[1], 0.5]}), json!({}), Err(())),
+            (json!({"drop": [[1, 2, 3], 2]}), json!({}), Ok(json!([3]))),
+            (json!({"drop": [[1, 2, 3], -2]}), json!({}), Ok(json!([1]))),
+            (json!({"drop": [[1, 2, 3], 5]}), json!({}), Ok(json!([]))),
+            (json!({"drop": [[1, 2, 3], -5]}), json!({}), Ok(json!([]))),
+            (
+                json!({"drop": [[1, 2, 3], 0]}),
+                json!({}),
+                Ok(json!([1, 2, 3])),
+            ),
+            (json!({"drop": [{}, 1]}), json!({}), Err(())),
+        ]
+    }
+
+    fn cat_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
+        vec![
+            (json!({"cat": []}), json!({}), Ok(json!(""))),
+            (json!({"cat": [1]}), json!({}), Ok(json!("1"))),
+            (json!({"cat": ["a"]}), json!({}), Ok(json!("a"))),
+            (json!({"cat": ["a", "b"]}), json!({}), Ok(json!("ab"))),
+            (json!({"cat": ["a", "b", "c"]}), json!({}), Ok(json!("abc"))),
+            (json!({"cat": ["a", "b", 1]}), json!({}), Ok(json!("ab1"))),
+        ]
+    }
+
+    fn string_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
+        vec![
+            (
+                json!({"upper": ["straße"]}),
+                json!({}),
+                Ok(json!("STRASSE")),
+            ),
+            (json!({"lower": ["ÀÉÎ"]}), json!({}), Ok(json!("àéî"))),
+            (json!({"upper": [1]}), json!({}), Err(())),
+            (
+                json!({"trim": ["\u{3000} a b\n"]}),
+                json!({}),
+                Ok(json!("a b")),
+            ),
+            (json!({"trim_start": ["  a "]}), json!({}), Ok(json!("a "))),
+            (json!({"trim_end": ["  a "]}), json!({}), Ok(json!("  a"))),
+            (json!({"trim": [null]}), json!({}), Err(())),
+            // Lengths are in characters
+            (json!({"pad_start": ["é", 3]}), json!({}), Ok(json!("  é"))),
+            (
+                json!({"pad_start": ["7", 3, "0"]}),
+                json!({}),
+                Ok(json!("007")),
+            ),
+            (
+                json!({"pad_end": ["a", 6, "xyz"]}),
+                json!({}),
+                Ok(json!("axyzxy")),
+            ),
+            (json!({"pad_end": ["abc", 2]}), json!({}), Ok(json!("abc"))),
+            (json!({"pad_start": ["a", 3, ""]}), json!({}), Err(())),
+            (json!({"pad_start": ["a", -1]}), json!({}), Err(())),
+            (json!({"pad_start": ["a", 1.5]}), json!({}), Err(())),
+            (
+                json!({"pad_start": ["a", 1024, "x"]}),
+                json!({}),
+                Ok(json!(format!("{}a", "x".repeat(1023)))),
+            ),
+            (json!({"pad_end": ["a", 1025, "x"]}), json!({}), Err(())),
+            (
+                json!({"pad_start": ["a", 18446744073709551615_u64, "x"]}),
+                json!({}),
+                Err(()),
+            ),
+            (
+                json!({"starts_with": ["héllo", "hé"]}),
+                json!({}),
+                Ok(json!(true)),
+            ),
+            (
+                json!({"starts_with": ["héllo", "e"]}),
+                json!({}),
+                Ok(json!(false)),
+            ),
+            (
+                json!({"ends_with": ["héllo", "lo"]}),
+                json!({}),
+                Ok(json!(true)),
+            ),
+            (
+                json!({"ends_with": ["héllo", ""]}),
+                json!({}),
+                Ok(json!(true)),
+            ),
+            (json!({"ends_with": [1, "1"]}), json!({}), Err(())),
+            (
+                json!({"split": ["a,b,,c", ","]}),
+                json!({}),
+                Ok(json!(["a", "b", "", "c"])),
+            ),
+            (
+                json!({"split": ["hé", ""]}),
+                json!({}),
+                Ok(json!(["h", "é"])),
+            ),
+            (json!({"split": ["", ","]}), json!({}), Ok(json!([""]))),
+            (json!({"split": ["a", 1]}), json!({}), Err(())),
+            (
+                json!({"join": [{"var": "xs"}, ", "]}),
+                json!({"xs": ["a", 1, null, true]}),
+                Ok(json!("a, 1, null, true")),
+            ),
+            (json!({"join": [[], ","]}), json!({}), Ok(json!(""))),
+            (json!({"join": ["abc", ","]}), json!({}), Err(())),
+        ]
+    }
+
+    fn format_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
+        vec![
+            (
+                json!({"format": ["%s owes %.2f", {"var": "name"}, {"var": "owed"}]}),
+                json!({"name": "Ann", "owed": 1234.5}),
+                Ok(json!("Ann owes 1234.50")),
+            ),
+            (
+                json!({"format": ["%'.1f", 1234567.25]}),
+                json!({}),
+                Ok(json!("1,234,567.2")),
+            ),
+            (
+                json!({"format": ["%f", 0.1]}),
+                json!({}),
+                Ok(json!("0.100000")),
+            ),
+            (
+                json!({"format": ["%d|%i", 42.9, -42.9]}),
+                json!({}),
+                Ok(json!("42|-42")),
+            ),
+            (
+                json!({"format": ["%'d", -1234567]}),
+                json!({}),
+                Ok(json!("-1,234,567")),
+            ),
+            (
+                json!({"format": ["%e", 1500]}),
+                json!({}),
+                Ok(json!("1.500000e+03")),
+            ),
+            (
+                json!({"format": ["%.1e", -0.00015]}),
+                json!({}),
+                Ok(json!("-1.5e-04")),
+            ),
+            // Width and padding
+            (
+                json!({"format": ["[%5d]", 42]}),
+                json!({}),
+                Ok(json!("[   42]")),
+            ),
+            (
+                json!({"format": ["[%-5d]", 42]}),
+                json!({}),
+                Ok(json!("[42   ]")),
+            ),
+            (
+                json!({"format": ["[%05d]", -42]}),
+                json!({}),
+                Ok(json!("[-0042]")),
+            ),
+            (
+                json!({"format": ["[%+.1f]", 2]}),
+                json!({}),
+                Ok(json!("[+2.0]")),
+            ),
+            (
+                json!({"format": ["[%-6s]", "héllo"]}),
+                json!({}),
+                Ok(json!("[héllo ]")),
+            ),
+            (
+                json!({"format": ["[%06s]", "ab"]}),
+                json!({}),
+                Ok(json!("[    ab]")),
+            ),
+            (
+                json!({"format": ["%.2s", "héllo"]}),
+                json!({}),
+                Ok(json!("hé")),
+            ),
+            // Arguments are converted as by `cat` and `+`
+            (
+                json!({"format": ["%s %s", [1, 2], null]}),
+                json!({}),
+                Ok(json!("1,2 null")),
+            ),
+            (json!({"format": ["%d", "12"]}), json!({}), Ok(json!("12"))),
+            (json!({"format": ["100%%"]}), json!({}), Ok(json!("100%"))),
+            (json!({"format": ["plain"]}), json!({}), Ok(json!("plain"))),
+            // Invalid formats and arguments
+            (json!({"format": ["%d", "abc"]}), json!({}), Err(())),
+            (json!({"format": ["%d %d", 1]}), json!({}), Err(())),
+            (json!({"format": ["%d", 1, 2]}), json!({}), Err(())),
+            (json!({"format": ["%q", 1]}), json!({}), Err(())),
+            (json!({"format": ["%5", 1]}), json!({}), Err(())),
+            (json!({"format": ["%.99999999999f", 1]}), json!({}), Err(())),
+            (json!({"format": ["%99999999999d", 1]}), json!({}), Err(())),
+            (json!({"format": ["%1025s", "x"]}), json!({}), Err(())),
+            (json!({"format": [1]}), json!({}), Err(())),
+            (json!({"format": []}), json!({}), Err(())),
+        ]
+    }
+
+    fn semver_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
+        vec![
+            (
+                json!({"semver_gt": ["1.10.0", "1.9.2"]}),
+                json!({}),
+                Ok(json!(true)),
+            ),
+            (
+                json!({"semver_gt": ["1.9.2", "1.10.0"]}),
+                json!({}),
+                Ok(json!(false)),
+            ),
+            // Prereleases precede their release, and build metadata is
+            // ignored
+            (
+                json!({"semver_gt": ["1.0.0", "1.0.0-rc.1"]}),
+                json!({}),
+                Ok(json!(true)),
+            ),
+            (
+                json!({"semver_gt": ["1.0.0-rc.10", "1.0.0-rc.9"]}),
+                json!({}),
+                Ok(json!(true)),
+            ),
+            (
+                json!({"semver_gt": ["1.0.0-alpha.beta", "1.0.0-alpha.1"]}),
+                json!({}),
+                Ok(json!(true)),
+            ),
+            (
+                json!({"semver_gt": ["1.0.0+b", "1.0.0+a"]}),
+                json!({}),
+                Ok(json!(false)),
+            ),
+            (
+                json!({"semver_satisfies": [{"var": "version"}, "^1.4"]}),
+                json!({"version": "1.9.0"}),
+                Ok(json!(true)),
+            ),
+            (
+                json!({"semver_satisfies": ["2.0.0", "^1.4"]}),
+                json!({}),
+                Ok(json!(false)),
+            ),
+            (
+                json!({"semver_satisfies": ["0.3.5", "^0.3.1"]}),
+                json!({}),
+                Ok(json!(true)),
+            ),
+            (
+                json!({"semver_satisfies": ["0.4.0", "^0.3.1"]}),
+                json!({}),
+                Ok(json!(false)),
+            ),
+            (
+                json!({"semver_satisfies": ["1.4.9", "~1.4.2"]}),
+                json!({}),
+                Ok(json!(true)),
+            ),
+            (
+                json!({"semver_satisfies": ["1.5.0", ">=1.2, <1.5"]}),
+                json!({}),
+                Ok(json!(false)),
+            ),
+            (
+                json!({"semver_satisfies": ["3.2.0", ">=1.2, <2 || ^3.1"]}),
+                json!({}),
+                Ok(json!(true)),
+            ),
+            (
+                json!({"semver_satisfies": ["1.2.7", "1.2.*"]}),
+                json!({}),
+                Ok(json!(true)),
+            ),
+            (
+                json!({"semver_satisfies": ["7.0.0", "*"]}),
+                json!({}),
+                Ok(json!(true)),
+            ),
+            // Prereleases only satisfy ranges with a prerelease of the same
+            // version
+            (
+                json!({"semver_satisfies": ["1.5.0-beta", ">=1.4"]}),
+                json!({}),
+                Ok(json!(false)),
+            ),
+            (
+                json!({"semver_satisfies": ["1.5.0-beta.2", ">=1.5.0-beta.1"]}),
+                json!({}),
+                Ok(json!(true)),
+            ),
+            // Versions must be strict semantic versions
+            (json!({"semver_gt": ["1.2", "1.0.0"]}), json!({}), Err(())),
+            (
+                json!({"semver_gt": ["v1.2.0", "1.0.0"]}),
+                json!({}),
+                Err(()),
+            ),
+            (
+                json!({"semver_gt": ["1.02.0", "1.0.0"]}),
+                json!({}),
+                Err(()),
+            ),
+            (json!({"semver_gt": [1, "1.0.0"]}), json!({}), Err(())),
+            (
+                json!({"semver_satisfies": ["1.0.0", ">=1.2 <2"]}),
+                json!({}),
+                Err(()),
+            ),
+            (
+                json!({"semver_satisfies": ["1.0.0", "1.* || "]}),
+                json!({}),
+                Err(()),
+            ),
+        ]
+    }
+
+    fn money_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
+        vec![
+            (
+                json!({"money": "12.3 USD"}),
+                json!({}),
+                Ok(json!("12.30 USD")),
+            ),
+            (
+                json!({"money": "12.345 USD"}),
+                json!({}),
+                Ok(json!("12.345 USD")),
+            ),
+            (
+                json!({"money": [1500, "JPY"]}),
+                json!({}),
+                Ok(json!("1500 JPY")),
+            ),
+            (
+                json!({"money": [0.1, "KWD"]}),
+                json!({}),
+                Ok(json!("0.100 KWD")),
+            ),
+            (
+                json!({"money": ["-7.5", "EUR"]}),
+                json!({}),
+                Ok(json!("-7.50 EUR")),
+            ),
+            (json!({"money": "12.34"}), json!({}), Err(())),
+            (json!({"money": "12.34 usd"}), json!({}), Err(())),
+            (json!({"money": "1e3 USD"}), json!({}), Err(())),
+            (json!({"money": [true, "USD"]}), json!({}), Err(())),
+            // Arithmetic is exact, unlike with floats
+            (
+                json!({"money_add": ["0.10 USD", "0.20 USD"]}),
+                json!({}),
+                Ok(json!("0.30 USD")),
+            ),
+            (
+                json!({"money_add": [{"var": "price"}, {"var": "tax"}, "1 USD"]}),
+                json!({"price": "19.99 USD", "tax": "1.655 USD"}),
+                Ok(json!("22.645 USD")),
+            ),
+            (
+                json!({"money_sub": ["5 USD", "7.25 USD"]}),
+                json!({}),
+                Ok(json!("-2.25 USD")),
+            ),
+            (
+                json!({"money_cmp": ["0.3 USD", "0.30 USD"]}),
+                json!({}),
+                Ok(json!(0)),
+            ),
+            (
+                json!({"money_cmp": ["10 USD", "9.99 USD"]}),
+                json!({}),
+                Ok(json!(1)),
+            ),
+            (
+                json!({"money_cmp": ["-1 USD", "0 USD"]}),
+                json!({}),
+                Ok(json!(-1)),
+            ),
+            // Currencies can't be mixed
+            (json!({"money_add": ["1 USD", "1 EUR"]}), json!({}), Err(())),
+            (json!({"money_sub": ["1 USD", "1 EUR"]}), json!({}), Err(())),
+            (json!({"money_cmp": ["1 USD", "1 EUR"]}), json!({}), Err(())),
+            (json!({"money_add": ["1 USD", 1]}), json!({}), Err(())),
+        ]
+    }
+
+    fn date_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
+        vec![
+            (json!({"duration": "7d"}), json!({}), Ok(json!(604_800))),
+            (json!({"duration": "1h30m"}), json!({}), Ok(json!(5400))),
+            (json!({"duration": "P1DT2H"}), json!({}), Ok(json!(93_600))),
+            (json!({"duration": "PT0.25S"}), json!({}), Ok(json!(0.25))),
+            (json!({"duration": "-2w"}), json!({}), Ok(json!(-1_209_600))),
+            // Months have no fixed length
+            (json!({"duration": "P1M"}), json!({}), Err(())),
+            (json!({"duration": "7 days"}), json!({}), Err(())),
+            (json!({"duration": 7}), json!({}), Err(())),
+            (
+                json!({"date_add": ["2024-03-01", "P1D"]}),
+                json!({}),
+                Ok(json!("2024-03-02T00:00:00Z")),
+            ),
+            (
+                json!({"date_add": [{"var": "at"}, "90m"]}),
+                json!({"at": "2024-03-01T23:00:00.5-05:00"}),
+                Ok(json!("2024-03-02T05:30:00.500Z")),
+            ),
+            (
+                json!({"date_add": ["2024-01-31", "P1M"]}),
+                json!({}),
+                Ok(json!("2024-02-29T00:00:00Z")),
+            ),
+            (
+                json!({"date_add": ["2024-02-29", "P1Y"]}),
+                json!({}),
+                Ok(json!("2025-02-28T00:00:00Z")),
+            ),
+            (
+                json!({"date_add": ["2024-03-01T00:00:00Z", 3600]}),
+                json!({}),
+                Ok(json!("2024-03-01T01:00:00Z")),
+            ),
+            (
+                json!({"date_add": ["2024-03-01", {"duration": "2h"}]}),
+                json!({}),
+                Ok(json!("2024-03-01T02:00:00Z")),
+            ),
+            (
+                json!({"date_sub": ["2024-03-31T12:00:00Z", "P1M"]}),
+                json!({}),
+                Ok(json!("2024-02-29T12:00:00Z")),
+            ),
+            (
+                json!({"date_sub": ["1970-01-01", "1ms"]}),
+                json!({}),
+                Ok(json!("1969-12-31T23:59:59.999Z")),
+            ),
+            (
+                json!({"date_add": ["9999-12-31", "1d"]}),
+                json!({}),
+                Err(()),
+            ),
+            (
+                json!({"date_add": ["2024-02-30", "1d"]}),
+                json!({}),
+                Err(()),
+            ),
+            (
+                json!({"date_add": [1_709_251_200, "1d"]}),
+                json!({}),
+                Err(()),
+            ),
+            (
+                json!({"date_add": ["2024-03-01", true]}),
+                json!({}),
+                Err(()),
+            ),
+        ]
+    }
+
+    fn template_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
+        let data = json!({"user": {"name": "Ann", "owed": 1.5, "tags": ["a"]}});
+        vec![
+            (
+                json!({"template": ["Hello {name}, you owe {owed}", {"var": "user"}]}),
+                data.clone(),
+                Ok(json!("Hello Ann, you owe 1.5")),
+            ),
+            (
+                json!({"template": ["{user.name}: {user.tags.0}", {"var": ""}]}),
+                data.clone(),
+                Ok(json!("Ann: a")),
+            ),
+            (
+                json!({"template": ["{{literal}} {}!", "héllo"]}),
+                json!({}),
+                Ok(json!("{literal} héllo!")),
+            ),
+            (json!({"template": ["", {}]}), json!({}), Ok(json!(""))),
+            // Missing values are errors, unless placeholders are kept
+            (
+                json!({"template": ["Hi {nope}", {"var": "user"}]}),
+                data.clone(),
+                Err(()),
+            ),
+            (
+                json!({"template": ["Hi {nope} {name}", {"var": "user"}, "keep"]}),
+                data.clone(),
+                Ok(json!("Hi {nope} Ann")),
+            ),
+            (
+                json!({"template": ["Hi {nope}", {}, "error"]}),
+                json!({}),
+                Err(()),
+            ),
+            // Invalid templates and options
+            (json!({"template": ["Hi {name", {}]}), json!({}), Err(())),
+            (json!({"template": ["Hi }", {}]}), json!({}), Err(())),
+            (json!({"template": [1, {}]}), json!({}), Err(())),
+            (json!({"template": ["", {}, "skip"]}), json!({}), Err(())),
+            (json!({"template": ["", {}, true]}), json!({}), Err(())),
+            (json!({"template": ["Hi"]}), json!({}), Err(())),
+        ]
+    }
+
+    fn substr_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
+        vec![
+            // Wrong number of arguments
+            (json!({"substr": []}), json!({}), Err(())),
+            (json!({"substr": ["foo"]}), json!({}), Err(())),
+            (json!({"substr": ["foo", 1, 2, 3]}), json!({}), Err(())),
+            // Wrong argument types
+            (json!({"substr": [12, 1]}), json!({}), Err(())),
+            (json!({"substr": ["foo", "12"]}), json!({}), Err(())),
+            // Non-negative indices
+            (json!({"substr": ["foo", 0]}), json!({}), Ok(json!("foo"))),
+            (json!({"substr": ["foo", 1]}), json!({}), Ok(json!("oo"))),
+            (json!({"substr": ["foo", 2]}), json!({}), Ok(json!("o"))),
+            // Negative indices
+            (json!({"substr": ["foo", -1]}), json!({}), Ok(json!("o"))),
+            (json!({"substr": ["foo", -2]}), json!({}), Ok(json!("oo"))),
+            (json!({"substr": ["foo", -3]}), json!({}), Ok(json!("foo"))),
+            // Out-of-bounds indices
+            (json!({"substr": ["foo", 3]}), json!({}), Ok(json!(""))),
+            (json!({"substr": ["foo", 20]}), json!({}), Ok(json!(""))),
+            (json!({"substr": ["foo", -4]}), json!({}), Ok(json!("foo"))),
+            // Non-negative Limits
+            (json!({"substr": ["foo", 0, 1]}), json!({}), Ok(json!("f"))),
+            (
+                json!({"substr": ["foo", 0, 3]}),
+                json!({}),
+                Ok(json!("foo")),
+            ),
+            (json!({"substr": ["foo", 0, 0]}), json!({}), Ok(json!(""))),
+            (json!({"substr": ["foo", 1, 1]}), json!({}), Ok(json!("o"))),
+            // Negative Limits
+            (
+                json!({"substr": ["foo", 0, -1]}),
+                json!({}),
+                Ok(json!("fo")),
+            ),
+            (json!({"substr": ["foo", 0, -2]}), json!({}), Ok(json!("f"))),
+            (json!({"substr": ["foo", 0, -3]}), json!({}), Ok(json!(""))),
+            // Out-of-bounds limits
+            (
+                json!({"substr": ["foo", 0, 10]}),
+                json!({}),
+                Ok(json!("foo")),
+            ),
+            (json!({"substr": ["foo", 0, -10]}), json!({}), Ok(json!(""))),
+            // Negative indices with negative limits
+            (
+                json!({"substr": ["foo", -3, -2]}),
+                json!({}),
+                Ok(json!("f")),
+            ),
+            // Negative indices with positive limits
+            (
+                json!({"substr": ["foo", -3, 2]}),
+                json!({}),
+                Ok(json!("fo")),
+            ),
+            // Out-of-bounds indices with out-of-bounds limits
+            (json!({"substr": ["foo", 10, 10]}), json!({}), Ok(json!(""))),
+            (
+                json!({"substr": ["foo", 10, -10]}),
+                json!({}),
+                Ok(json!("")),
+            ),
+            (
+                json!({"substr": ["foo", -10, 10]}),
+                json!({}),
+                Ok(json!("foo")),
+            ),
+            (
+                json!({"substr": ["foo", -10, -10]}),
+                json!({}),
+                Ok(json!("")),
+            ),
+        ]
+    }
+
+    fn log_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
+        vec![
+            // Invalid number of arguments
+            (json!({"log": []}), json!({}), Err(())),
+            (json!({"log": [1, 2]}), json!({}), Err(())),
+            // Correct number of arguments
+            (json!({"log": [1]}), json!({}), Ok(json!(1))),
+            (json!({"log": 1}), json!({}), Ok(json!(1))),
+        ]
+    }
+
+    fn lt_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
+        vec![
+            (json!({"<": [1, 2]}), json!({}), Ok(json!(true))),
+            (json!({"<": [3, 2]}), json!({}), Ok(json!(false))),
+            (
+                json!({"<": [1, {"var": "foo"}]}),
+                json!({"foo": 5}),
+                Ok(json!(true)),
+            ),
+            (json!({"<": [1, 2, 3]}), json!({}), Ok(json!(true))),
+            (json!({"<": [3, 2, 3]}), json!({}), Ok(json!(false))),
+            (json!({"<": [1, 2, 1]}), json!({}), Ok(json!(false))),
+        ]
+    }
+
+    fn gt_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
+        vec![
+            (json!({">": [1, 2]}), json!({}), Ok(json!(false))),
+            (json!({">": [3, 2]}), json!({}), Ok(json!(true))),
+            (
+                json!({">": [1, {"var": "foo"}]}),
+                json!({"foo": 5}),
+                Ok(json!(false)),
+            ),
+            (json!({">": [1, 2, 3]}), json!({}), Ok(json!(false))),
+            (json!({">": [3, 2, 3]}), json!({}), Ok(json!(false))),
+            (json!({">": [1, 2, 1]}), json!({}), Ok(json!(false))),
+            (json!({">": [3, 2, 1]}), json!({}), Ok(json!(true))),
+        ]
+    }
+
+    fn plus_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
+        vec![
+            (json!({"+": []}), json!({}), Ok(json!(0))),
+            (json!({"+": [1]}), json!({}), Ok(json!(1))),
+            (json!({"+": ["1"]}), json!({}), Ok(json!(1))),
+            (json!({"+": [1, 1]}), json!({}), Ok(json!(2))),
+            (json!({"+": [1, 1, 1]}), json!({}), Ok(json!(3))),
+            (json!({"+": [1, 1, false]}), json!({}), Err(())),
+            (json!({"+": [1, 1, "1"]}), json!({}), Ok(json!(3))),
+            (
+                json!({"+": [1, 1, "123abc"]}), // WHY???
+                json!({}),
+                Ok(json!(125)),
+            ),
+        ]
+    }
+
+    fn minus_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
+        vec![
+            (json!({"-": "5"}), json!({}), Ok(json!(-5))),
+            (json!({"-": [2]}), json!({}), Ok(json!(-2))),
+            (json!({"-": [2, 2]}), json!({}), Ok(json!(0))),
+            (json!({"-": ["9", [3]]}), json!({}), Ok(json!(6))),
+        ]
+    }
+
+    fn multiplication_cases() -> Vec<(Value, Value, Result<Value, ()>)> {
+        vec![
+            (json!({"*": 1}), json!({}), Ok(json!(1))),
+            (json!({"*": [1]}), json!({}), Ok(json!(1))),
             (json!({"*": [1, 2]}), json!({}), Ok(json!(2))),
             (json!({"*": [0, 2]}), json!({}), Ok(json!(0))),
             (json!({"*": [1, 2, 3]}), json!({}), Ok(json!(6))),
@@ -1161,9 +2658,26 @@ mod jsonlogic_tests {
         ]
     }
 
-    fn assert_jsonlogic((op, data, exp): (Value, Value, Result<Value, ()>)) -> () {
+    fn assert_jsonlogic(case: (Value, Value, Result<Value, ()>)) -> () {
+        assert_jsonlogic_with(&Context::default(), case)
+    }
+
+    /// Check a case under the latest semantics version, rather than the
+    /// default one
+    fn assert_latest(case: (Value, Value, Result<Value, ()>)) {
+        let ctx = Context {
+            semantics: SemanticsVersion::LATEST,
+            ..Context::default()
+        };
+        assert_jsonlogic_with(&ctx, case)
+    }
+
+    fn assert_jsonlogic_with(
+        ctx: &Context,
+        (op, data, exp): (Value, Value, Result<Value, ()>),
+    ) {
         println!("Running rule: {:?} with data: {:?}", op, data);
-        let result = apply(&op, &data);
+        let result = Rule::from_value(&op).and_then(|rule| rule.apply_with(&data, ctx));
         println!("- Result: {:?}", result);
         println!("- Expected: {:?}", exp);
         if exp.is_ok() {
@@ -1266,7 +2780,13 @@ mod jsonlogic_tests {
 
     #[test]
     fn test_var_data_op() {
-        var_cases().into_iter().for_each(assert_jsonlogic)
+        var_cases().into_iter().for_each(assert_jsonlogic);
+        // Under V2, a default is evaluated once, so a quoted rule stays quoted
+        assert_latest((
+            json!({"var": ["zz", {"lit": {"var": "n"}}]}),
+            json!({"n": 5}),
+            Ok(json!({"var": "n"})),
+        ));
     }
 
     #[test]
@@ -1329,11 +2849,156 @@ mod jsonlogic_tests {
         merge_cases().into_iter().for_each(assert_jsonlogic)
     }
 
+    #[test]
+    fn test_zip_ops() {
+        zip_cases().into_iter().for_each(assert_jsonlogic);
+
+        let ctx = Context {
+            max_array_size: Some(4),
+            ..Context::default()
+        };
+        let cartesian = |args: Value| {
+            Rule::from_value(&json!({ "cartesian": args }))
+                .unwrap()
+                .apply_with(&json!({}), &ctx)
+        };
+        assert_eq!(
+            cartesian(json!([[1, 2], [3, 4]])).unwrap(),
+            json!([[1, 3], [1, 4], [2, 3], [2, 4]])
+        );
+        assert_eq!(
+            cartesian(json!([[1, 2], [3, 4, 5]])).unwrap_err().code(),
+            "invalid_argument"
+        );
+    }
+
+    #[test]
+    fn test_element_index() {
+        index_cases().into_iter().for_each(assert_latest);
+        // Before V2, `#index` is an ordinary variable
+        assert_jsonlogic((
+            json!({"map": [{"var": "xs"}, {"var": "#index"}]}),
+            json!({"xs": [{"#index": "k"}, {}]}),
+            Ok(json!(["k", null])),
+        ));
+    }
+
+    #[test]
+    fn test_parent_variables() {
+        parent_cases().into_iter().for_each(assert_latest);
+        // Before V2, `../` is part of a key
+        vec![
+            (
+                json!({"map": [[1], {"var": "../a"}]}),
+                json!({"a": 9}),
+                Ok(json!([null])),
+            ),
+            (
+                json!({"var": "../a"}),
+                json!({"": {"": {"/a": 1}}}),
+                Ok(json!(1)),
+            ),
+            (
+                json!({"var": ".."}),
+                json!({"": {"": {"": 2}}}),
+                Ok(json!(2)),
+            ),
+        ]
+        .into_iter()
+        .for_each(assert_jsonlogic)
+    }
+
+    #[test]
+    fn test_selection_ops() {
+        selection_cases().into_iter().for_each(assert_jsonlogic)
+    }
+
+    #[test]
+    fn test_distinct_by_op() {
+        distinct_cases().into_iter().for_each(assert_jsonlogic)
+    }
+
+    #[test]
+    fn test_sort_ops() {
+        sort_cases().into_iter().for_each(assert_jsonlogic)
+    }
+
+    #[test]
+    fn test_reduce_while_op() {
+        reduce_while_cases().into_iter().for_each(assert_latest)
+    }
+
+    #[test]
+    fn test_object_ops() {
+        object_cases().into_iter().for_each(assert_latest)
+    }
+
+    #[test]
+    fn test_switch_op() {
+        switch_cases().into_iter().for_each(assert_jsonlogic)
+    }
+
+    #[test]
+    fn test_partition_ops() {
+        partition_cases().into_iter().for_each(assert_jsonlogic)
+    }
+
+    #[test]
+    fn test_range_op() {
+        range_cases().into_iter().for_each(assert_jsonlogic);
+
+        let ctx = Context {
+            max_array_size: Some(3),
+            ..Context::default()
+        };
+        let range = |args: Value| {
+            Rule::from_value(&json!({ "range": args }))
+                .unwrap()
+                .apply_with(&json!({}), &ctx)
+        };
+        assert_eq!(range(json!([3])).unwrap(), json!([0, 1, 2]));
+        assert_eq!(range(json!([4])).unwrap_err().code(), "invalid_argument");
+        assert_eq!(
+            range(json!([0, 1e18])).unwrap_err().code(),
+            "invalid_argument"
+        );
+    }
+
     #[test]
     fn test_cat_op() {
         cat_cases().into_iter().for_each(assert_jsonlogic)
     }
 
+    #[test]
+    fn test_string_ops() {
+        string_cases().into_iter().for_each(assert_jsonlogic)
+    }
+
+    #[test]
+    fn test_format_op() {
+        format_cases().into_iter().for_each(assert_jsonlogic)
+    }
+
+    #[test]
+    fn test_semver_ops() {
+        semver_cases().into_iter().for_each(assert_jsonlogic)
+    }
+
+    #[test]
+    fn test_money_ops() {
+        money_cases().into_iter().for_each(assert_jsonlogic)
+    }
+
+    #[test]
+    fn test_date_ops() {
+        date_cases().into_iter().for_each(assert_jsonlogic)
+    }
+
+    #[test]
+    fn test_template_op() {
+        template_cases().into_iter().for_each(assert_jsonlogic)
+    }
+
     #[test]
     fn test_substr_op() {
         substr_cases().into_iter().for_each(assert_jsonlogic)
@@ -0,0 +1,591 @@
+//! Rule Linting
+//!
+//! Find likely mistakes in rules that are nonetheless valid, such as
+//! comparisons whose result never changes:
+//!
+//! ```
+//! use jsonlogic_plus::{lint, Rule};
+//! use serde_json::json;
+//!
+//! let rule = Rule::from_value(&json!({"if": [
+//!     {">": [18, 21]},
+//!     "adult",
+//!     "adult"
+//! ]}))
+//! .unwrap();
+//! let warnings = lint::lint(&rule, &lint::LintOptions::default());
+//! let codes: Vec<&str> = warnings.iter().map(|w| w.code()).collect();
+//! assert_eq!(codes, vec!["duplicate_branch", "constant_comparison"]);
+//! assert_eq!(warnings[0].path, "/if/2");
+//! ```
+//!
+//! Each warning has a stable code, and the location of the offending
+//! sub-expression as a JSON Pointer into the rule (in the form of
+//! `Value::from(rule.ast())`), e.g. `/and/1/==/0` for the first argument of
+//! the `==` that is the second argument of `and`.
+
+use core::fmt;
+
+use serde_json::Value;
+
+use crate::ast::{Ast, AstVisitor};
+use crate::collections::HashSet;
+use crate::error::Error;
+use crate::namespace::{Namespace, Shadowed};
+use crate::op;
+use crate::op::data::{self, key_path, Variable};
+use crate::op::func::Function;
+use crate::prelude::*;
+use crate::rule::Rule;
+use crate::value::{Evaluated, Raw};
+
+/// Options for linting rules
+#[derive(Clone, Debug, Default)]
+pub struct LintOptions {
+    /// Sample data of the shape rules are applied to. If set, `var` paths
+    /// that aren't present in the sample are reported.
+    pub sample: Option<Value>,
+    /// The codes of warnings not to report
+    pub allow: Vec<String>,
+}
+
+/// A likely mistake in a rule
+#[derive(Clone, Debug, PartialEq)]
+pub struct Warning {
+    pub kind: WarningKind,
+    /// A JSON Pointer to the sub-expression the warning is about
+    pub path: String,
+    /// The function in which the warning was found, if it was found in a
+    /// function definition
+    pub function: Option<String>,
+}
+impl Warning {
+    /// A stable, machine-readable code identifying the kind of warning
+    pub fn code(&self) -> &'static str {
+        self.kind.code()
+    }
+}
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(function) = &self.function {
+            write!(f, "in '{}' ", function)?;
+        }
+        write!(f, "at '{}': {}", self.path, self.kind)
+    }
+}
+
+/// The kind of a `Warning`
+#[derive(Clone, Debug, PartialEq)]
+pub enum WarningKind {
+    /// A comparison of literals, whose result is always the same.
+    ConstantComparison { operator: String, result: bool },
+    /// A branch of an `if` identical to an earlier branch, at `first`.
+    DuplicateBranch { first: String },
+    /// A `var` path that isn't present in the sample data.
+    UnknownVariable { variable: String },
+    /// A function parameter that is never referenced in its body.
+    UnusedParameter { name: String },
+    /// A function or parameter name that hides another definition.
+    Shadowing { name: String, shadowed: Shadowed },
+    /// A name the namespace has deprecated in favor of `replacement`.
+    Deprecated { name: String, replacement: String },
+}
+impl WarningKind {
+    /// A stable, machine-readable code identifying the kind of warning
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::ConstantComparison { .. } => "constant_comparison",
+            Self::DuplicateBranch { .. } => "duplicate_branch",
+            Self::UnknownVariable { .. } => "unknown_variable",
+            Self::UnusedParameter { .. } => "unused_parameter",
+            Self::Shadowing { .. } => "shadowing",
+            Self::Deprecated { .. } => "deprecated",
+        }
+    }
+}
+impl fmt::Display for WarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ConstantComparison { operator, result } => {
+                write!(f, "'{}' comparison is always {}", operator, result)
+            }
+            Self::DuplicateBranch { first } => {
+                write!(f, "branch is identical to the branch at '{}'", first)
+            }
+            Self::UnknownVariable { variable } => {
+                write!(f, "'{}' is not present in the sample data", variable)
+            }
+            Self::UnusedParameter { name } => {
+                write!(f, "parameter '{}' is never used", name)
+            }
+            Self::Shadowing {
+                name,
+                shadowed: Shadowed::Operator,
+            } => write!(f, "'{}' shadows an operator of the same name", name),
+            Self::Shadowing {
+                name,
+                shadowed: Shadowed::Parameter,
+            } => write!(f, "parameter '{}' is declared more than once", name),
+            Self::Deprecated { name, replacement } => {
+                write!(f, "'{}' is deprecated; use '{}' instead", name, replacement)
+            }
+        }
+    }
+}
+
+/// Lint a rule.
+pub fn lint(rule: &Rule, options: &LintOptions) -> Vec<Warning> {
+    let mut linter = Linter::new(options, None);
+    linter.expr(&rule.ast(), false);
+    linter.finish()
+}
+
+/// Lint a rule as parsed within a namespace.
+///
+/// Besides the warnings for any rule, this reports each use of a name the
+/// namespace has deprecated. As aliases are replaced when the rule is
+/// parsed, the paths of these warnings are into `value`, rather than the
+/// parsed rule.
+pub fn lint_value(
+    value: &Value,
+    namespace: &Namespace,
+    options: &LintOptions,
+) -> Result<Vec<Warning>, Error> {
+    let rule = namespace.parse(value)?;
+    let mut linter = Linter::new(options, None);
+    linter.expr(&rule.ast(), false);
+    linter.deprecations(namespace, value);
+    Ok(linter.finish())
+}
+
+/// Lint the functions of a namespace, sorted by name.
+///
+/// Besides the warnings for rules, this reports unused parameters, shadowed
+/// names, and the use of deprecated names. Paths are relative to each
+/// function's `defn`.
+pub fn lint_namespace(
+    namespace: &Namespace,
+    options: &LintOptions,
+) -> Result<Vec<Warning>, Error> {
+    let mut warnings = Vec::new();
+    for def in namespace.definitions()? {
+        if let Ast::FunctionDef { name, params, body } = &def {
+            let mut linter = Linter::new(options, Some(name));
+            linter.definition(namespace, name, params, body);
+            if let Some(func) = namespace.get(name) {
+                linter.nested(&[Function::OPERATOR, "2"], |linter| {
+                    linter.deprecations(namespace, func.expression())
+                });
+            }
+            warnings.extend(linter.finish());
+        }
+    }
+    Ok(warnings)
+}
+
+/// Append a segment to a JSON Pointer, escaping it as needed.
+pub(crate) fn push_segment(pointer: &mut String, segment: &str) {
+    pointer.push('/');
+    pointer.push_str(&segment.replace('~', "~0").replace('/', "~1"));
+}
+
+/// Comparisons, which are constant when their operands are literals.
+///
+/// Comparing an expression with itself isn't necessarily constant: objects
+/// and arrays are compared by reference, and operators like `random` give
+/// a different value each time.
+const COMPARISONS: &[&str] = &["==", "===", "<=", ">=", "!=", "!==", "<", ">"];
+
+/// Collect the names of the parameters referenced in an expression
+#[derive(Default)]
+struct Params(HashSet<String>);
+impl AstVisitor for Params {
+    fn visit_param(&mut self, name: &str) {
+        self.0.insert(name.into());
+    }
+}
+
+struct Linter<'a> {
+    options: &'a LintOptions,
+    function: Option<&'a str>,
+    path: String,
+    warnings: Vec<Warning>,
+}
+impl<'a> Linter<'a> {
+    fn new(options: &'a LintOptions, function: Option<&'a str>) -> Self {
+        Self {
+            options,
+            function,
+            path: String::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    fn finish(self) -> Vec<Warning> {
+        let allow = &self.options.allow;
+        self.warnings
+            .into_iter()
+            .filter(|warning| !allow.iter().any(|code| code == warning.code()))
+            .collect()
+    }
+
+    fn warn(&mut self, kind: WarningKind) {
+        self.warn_at(self.path.clone(), kind)
+    }
+
+    fn warn_at(&mut self, path: String, kind: WarningKind) {
+        self.warnings.push(Warning {
+            kind,
+            path,
+            function: self.function.map(String::from),
+        })
+    }
+
+    /// The current path extended by the given segments
+    fn child(&self, segments: &[&str]) -> String {
+        let mut path = self.path.clone();
+        for segment in segments {
+            push_segment(&mut path, segment);
+        }
+        path
+    }
+
+    /// Run `f` with the path extended by the given segments.
+    fn nested<F: FnOnce(&mut Self)>(&mut self, segments: &[&str], f: F) {
+        let child = self.child(segments);
+        let parent = core::mem::replace(&mut self.path, child);
+        f(self);
+        self.path = parent;
+    }
+
+    fn definition(
+        &mut self,
+        namespace: &Namespace,
+        name: &str,
+        params: &[String],
+        body: &Ast,
+    ) {
+        let defn = Function::OPERATOR;
+        if op::is_builtin(name) || namespace.operator(name).is_some() {
+            self.warn_at(
+                self.child(&[defn, "0"]),
+                WarningKind::Shadowing {
+                    name: name.into(),
+                    shadowed: Shadowed::Operator,
+                },
+            );
+        }
+        let mut used = Params::default();
+        used.visit(body);
+        for (i, param) in params.iter().enumerate() {
+            let path = self.child(&[defn, "1", &i.to_string()]);
+            if params[..i].contains(param) {
+                self.warn_at(
+                    path,
+                    WarningKind::Shadowing {
+                        name: param.clone(),
+                        shadowed: Shadowed::Parameter,
+                    },
+                );
+            } else if !used.0.contains(param) {
+                self.warn_at(
+                    path,
+                    WarningKind::UnusedParameter {
+                        name: param.clone(),
+                    },
+                );
+            }
+        }
+        self.nested(&[defn, "2"], |linter| linter.expr(body, false));
+    }
+
+    /// Lint an expression. Within `per_element` expressions, such as the
+    /// second argument of `map`, data refers to each element of an array.
+    fn expr(&mut self, ast: &Ast, per_element: bool) {
+        match ast {
+            Ast::Var { path, default } => {
+                if let (Some(Ast::Literal(key)), false) = (path.as_deref(), per_element)
+                {
+                    self.variable(key);
+                }
+                let args = path.iter().chain(default).enumerate();
+                for (i, arg) in args {
+                    self.nested(&[Variable::OPERATOR, &i.to_string()], |linter| {
+                        linter.expr(arg, per_element)
+                    });
+                }
+            }
+            Ast::Operator { name, args } => {
+                self.comparison(name, args);
+                if name == "if" || name == "?:" {
+                    self.branches(name, args);
+                }
+                for (i, arg) in args.iter().enumerate() {
+                    let per_element =
+                        per_element || op::per_element(name, i, args.len());
+                    self.nested(&[name, &i.to_string()], |linter| {
+                        linter.expr(arg, per_element)
+                    });
+                }
+            }
+            Ast::FunctionCall { name, args } => {
+                for (i, arg) in args.iter().enumerate() {
+                    self.nested(&[name, &i.to_string()], |linter| {
+                        linter.expr(arg, per_element)
+                    });
+                }
+            }
+            Ast::FunctionDef { body, .. } => {
+                self.nested(&[Function::OPERATOR, "2"], |linter| {
+                    linter.expr(body, per_element)
+                });
+            }
+            Ast::Object(entries) => {
+                for (key, value) in entries {
+                    self.nested(&[key], |linter| linter.expr(value, per_element));
+                }
+            }
+            Ast::Literal(_) | Ast::Param(_) => {}
+        }
+    }
+
+    /// Report each deprecated name used in an unparsed expression.
+    fn deprecations(&mut self, namespace: &Namespace, value: &Value) {
+        let obj = match value {
+            Value::Array(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    self.nested(&[&i.to_string()], |linter| {
+                        linter.deprecations(namespace, item)
+                    });
+                }
+                return;
+            }
+            Value::Object(obj) => obj,
+            _ => return,
+        };
+        if let (1, Some((key, _))) = (obj.len(), obj.iter().next()) {
+            // Quoted values are data, rather than expressions
+            if key == Raw::OPERATOR {
+                return;
+            }
+            if let Some(replacement) = namespace.replacement(key) {
+                self.warn(WarningKind::Deprecated {
+                    name: key.clone(),
+                    replacement: replacement.into(),
+                });
+            }
+        }
+        for (key, item) in obj {
+            self.nested(&[key], |linter| linter.deprecations(namespace, item));
+        }
+    }
+
+    fn variable(&mut self, key: &Value) {
+        let sample = match &self.options.sample {
+            Some(sample) => sample,
+            None => return,
+        };
+        let path = match key_path(Evaluated::Raw(key)) {
+            Ok(path) => path,
+            Err(_) => return,
+        };
+        if !path.is_empty() && matches!(data::resolve(sample, &path), Ok(None)) {
+            let variable = match key {
+                Value::String(key) => key.clone(),
+                key => key.to_string(),
+            };
+            self.warn(WarningKind::UnknownVariable { variable });
+        }
+    }
+
+    fn comparison(&mut self, name: &str, args: &[Ast]) {
+        if !COMPARISONS.contains(&name)
+            || !args.iter().all(|arg| matches!(arg, Ast::Literal(_)))
+        {
+            return;
+        }
+        let comparison = Value::from(Ast::Operator {
+            name: name.into(),
+            args: args.to_vec(),
+        });
+        let result = match Rule::from_value(&comparison)
+            .and_then(|rule| rule.apply(&Value::Null))
+        {
+            Ok(Value::Bool(result)) => result,
+            _ => return,
+        };
+        self.warn(WarningKind::ConstantComparison {
+            operator: name.into(),
+            result,
+        });
+    }
+
+    fn branches(&mut self, name: &str, args: &[Ast]) {
+        let mut branches: Vec<usize> = (1..args.len()).step_by(2).collect();
+        if args.len() > 2 && args.len() % 2 == 1 {
+            branches.push(args.len() - 1);
+        }
+        for (n, &i) in branches.iter().enumerate() {
+            if let Some(&first) = branches[..n].iter().find(|&&j| args[j] == args[i]) {
+                let first = self.child(&[name, &first.to_string()]);
+                self.warn_at(
+                    self.child(&[name, &i.to_string()]),
+                    WarningKind::DuplicateBranch { first },
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_lint {
+    use super::*;
+    use crate::rule::{IdentifierPolicy, ParseOptions};
+    use serde_json::json;
+
+    fn codes_and_paths(warnings: &[Warning]) -> Vec<(&str, &str)> {
+        warnings
+            .iter()
+            .map(|warning| (warning.code(), warning.path.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_lint_rule() {
+        let rule = Rule::from_value(&json!({"and": [
+            {"==": [1, 1]},
+            {"<": [1, 2, 3]},
+            {"!=": ["a", "a"]},
+            {"==": [{"var": "a.b"}, {"var": "a.c"}]},
+            {"==": [{"var": "a.b"}, {"var": "a.b"}]},
+            {">=": [{"random": []}, {"random": []}]},
+            {"if": [{"var": "x"}, "yes", {"var": "y"}, "no", "yes"]},
+            {"map": [{"var": "xs"}, {"var": "not_in_sample"}]},
+            {"var": ["missing.0", {"var": "xs.-1"}]}
+        ]}))
+        .unwrap();
+        let options = LintOptions {
+            sample: Some(json!({"a": {"b": 1}, "x": true, "y": false, "xs": [1]})),
+            allow: vec![],
+        };
+        let warnings = lint(&rule, &options);
+        assert_eq!(
+            codes_and_paths(&warnings),
+            vec![
+                ("constant_comparison", "/and/0"),
+                ("constant_comparison", "/and/1"),
+                ("constant_comparison", "/and/2"),
+                ("unknown_variable", "/and/3/==/1"),
+                ("duplicate_branch", "/and/6/if/4"),
+                ("unknown_variable", "/and/8"),
+            ]
+        );
+        assert_eq!(
+            warnings[2].kind,
+            WarningKind::ConstantComparison {
+                operator: "!=".into(),
+                result: false
+            }
+        );
+        assert_eq!(
+            warnings[4].to_string(),
+            "at '/and/6/if/4': branch is identical to the branch at '/and/6/if/1'"
+        );
+
+        let options = LintOptions {
+            allow: vec!["constant_comparison".into()],
+            ..LintOptions::default()
+        };
+        assert_eq!(
+            codes_and_paths(&lint(&rule, &options)),
+            vec![("duplicate_branch", "/and/6/if/4")]
+        );
+    }
+
+    #[test]
+    fn test_lint_namespace() {
+        let mut ns = Namespace::new();
+        ns.set_parse_options(ParseOptions {
+            identifiers: IdentifierPolicy {
+                allow_builtin_names: true,
+                ..IdentifierPolicy::default()
+            },
+            ..ParseOptions::default()
+        });
+        ns.define(&json!({"defn": ["max", ["a", "b", "a"], {"param": "a"}]}))
+            .unwrap();
+        ns.define(&json!({"defn": ["f", ["x", "y"], {"if": [{"param": "x"}, 1, 1]}]}))
+            .unwrap();
+        let warnings = lint_namespace(&ns, &LintOptions::default()).unwrap();
+        assert_eq!(
+            codes_and_paths(&warnings),
+            vec![
+                ("unused_parameter", "/defn/1/1"),
+                ("duplicate_branch", "/defn/2/if/2"),
+                ("shadowing", "/defn/0"),
+                ("unused_parameter", "/defn/1/1"),
+                ("shadowing", "/defn/1/2"),
+            ]
+        );
+        assert_eq!(
+            warnings[4].to_string(),
+            "in 'max' at '/defn/1/2': parameter 'a' is declared more than once"
+        );
+    }
+
+    #[test]
+    fn test_lint_deprecations() {
+        let mut ns = Namespace::new();
+        ns.alias("equals", "==").unwrap();
+        ns.deprecate("times", "*").unwrap();
+        ns.define(&json!({"defn": ["double", ["x"], {"times": [{"param": "x"}, 2]}]}))
+            .unwrap();
+        ns.deprecate("twice", "double").unwrap();
+
+        let value = json!({"and": [
+            {"equals": [{"twice": [2]}, 4]},
+            {"lit": {"times": [1]}},
+            {"!": {"times": [0, 1]}}
+        ]});
+        let rule = ns.parse(&value).unwrap();
+        assert_eq!(rule.apply(&json!(null)).unwrap(), json!(true));
+        let warnings = lint_value(&value, &ns, &LintOptions::default()).unwrap();
+        assert_eq!(
+            codes_and_paths(&warnings),
+            vec![
+                ("deprecated", "/and/0/equals/0"),
+                ("deprecated", "/and/2/!")
+            ]
+        );
+        assert_eq!(
+            warnings[0].to_string(),
+            "at '/and/0/equals/0': 'twice' is deprecated; use 'double' instead"
+        );
+        let warnings = lint_namespace(&ns, &LintOptions::default()).unwrap();
+        assert_eq!(codes_and_paths(&warnings), vec![("deprecated", "/defn/2")]);
+
+        // Aliases must stand for something, and can't hide anything
+        ns.alias("eq", "==").unwrap_err();
+        ns.alias("double", "==").unwrap_err();
+        ns.alias("is", "nope").unwrap_err();
+        ns.alias("is", "equals").unwrap();
+        ns.check().unwrap();
+
+        // Aliases are part of the namespace's module document
+        let module = Namespace::from_value(&ns.to_value()).unwrap();
+        assert_eq!(module.to_value(), ns.to_value());
+        assert_eq!(
+            module.to_value()["aliases"],
+            json!({"equals": "==", "is": "=="})
+        );
+        assert_eq!(module.replacement("times"), Some("*"));
+        assert_eq!(module.replacement("equals"), None);
+        assert_eq!(
+            ns.parse(&json!({"is": [1, 1]}))
+                .unwrap()
+                .apply(&json!(null))
+                .unwrap(),
+            json!(true)
+        );
+    }
+}
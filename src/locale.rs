@@ -0,0 +1,190 @@
+//! Locales
+//!
+//! The `format` operator writes numbers with the separators of the `Locale`
+//! set on the evaluation's `Context`. Without one, numbers are written with
+//! US English conventions, e.g. `1,234.5`.
+
+use crate::prelude::*;
+
+/// The decimal and group separators of locales, by language tag or
+/// language. Tags not listed use US English separators.
+const SEPARATORS: &[(&str, char, char)] = &[
+    ("cs", ',', '\u{a0}'),
+    ("da", ',', '.'),
+    ("de", ',', '.'),
+    ("de-ch", '.', '\u{2019}'),
+    ("es", ',', '.'),
+    ("es-mx", '.', ','),
+    ("fi", ',', '\u{a0}'),
+    ("fr", ',', '\u{202f}'),
+    ("fr-ch", ',', '\u{202f}'),
+    ("id", ',', '.'),
+    ("it", ',', '.'),
+    ("nb", ',', '\u{a0}'),
+    ("nl", ',', '.'),
+    ("pl", ',', '\u{a0}'),
+    ("pt", ',', '.'),
+    ("ru", ',', '\u{a0}'),
+    ("sv", ',', '\u{a0}'),
+    ("tr", ',', '.'),
+    ("uk", ',', '\u{a0}'),
+];
+
+/// Conventions for writing numbers in a language and region
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Locale {
+    tag: String,
+    decimal_separator: char,
+    group_separator: char,
+}
+impl Locale {
+    /// The locale for a BCP 47 language tag, e.g. `de-DE`.
+    ///
+    /// Tags the crate has no conventions for, either for their language and
+    /// region or for their language alone, use US English conventions. Use
+    /// `with_separators()` to set them explicitly.
+    pub fn new<S: Into<String>>(tag: S) -> Self {
+        let tag = tag.into();
+        let normalized = tag.replace('_', "-").to_lowercase();
+        let language = normalized.split('-').next().unwrap_or_default();
+        let (decimal_separator, group_separator) = SEPARATORS
+            .iter()
+            .find(|(known, _, _)| *known == normalized)
+            .or_else(|| SEPARATORS.iter().find(|(known, _, _)| *known == language))
+            .map_or(('.', ','), |(_, decimal, group)| (*decimal, *group));
+        Self {
+            tag,
+            decimal_separator,
+            group_separator,
+        }
+    }
+
+    /// Use the given separators rather than the language's.
+    pub fn with_separators(
+        self,
+        decimal_separator: char,
+        group_separator: char,
+    ) -> Self {
+        Self {
+            decimal_separator,
+            group_separator,
+            ..self
+        }
+    }
+
+    /// The locale's language tag
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    /// The separator between the integer and fractional parts of a number
+    pub fn decimal_separator(&self) -> char {
+        self.decimal_separator
+    }
+
+    /// The separator between groups of thousands in the integer part of a
+    /// number
+    pub fn group_separator(&self) -> char {
+        self.group_separator
+    }
+
+    /// Localize a number written with `.` as its decimal separator, e.g. by
+    /// `format!()`, optionally grouping its integer digits in thousands.
+    pub(crate) fn localize(&self, number: &str, group: bool) -> String {
+        let start = number
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(number.len());
+        let (sign, rest) = number.split_at(start);
+        let end = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (integer, fraction) = rest.split_at(end);
+
+        let mut localized = String::with_capacity(number.len() + integer.len() / 3);
+        localized.push_str(sign);
+        for (i, digit) in integer.chars().enumerate() {
+            if group && i > 0 && (integer.len() - i) % 3 == 0 {
+                localized.push(self.group_separator);
+            }
+            localized.push(digit);
+        }
+        match fraction.strip_prefix('.') {
+            Some(fraction) => {
+                localized.push(self.decimal_separator);
+                localized.push_str(fraction);
+            }
+            None => localized.push_str(fraction),
+        }
+        localized
+    }
+}
+impl Default for Locale {
+    fn default() -> Self {
+        Self::new("en-US")
+    }
+}
+
+#[cfg(test)]
+mod test_locale {
+    use super::*;
+    use alloc::sync::Arc;
+    use serde_json::json;
+
+    use crate::{Context, Rule};
+
+    #[test]
+    fn test_new() {
+        assert_eq!(Locale::new("de-DE").decimal_separator(), ',');
+        assert_eq!(Locale::new("de_AT").group_separator(), '.');
+        assert_eq!(Locale::new("de-CH").decimal_separator(), '.');
+        assert_eq!(Locale::new("es-MX").decimal_separator(), '.');
+        assert_eq!(Locale::new("ES").decimal_separator(), ',');
+        let unknown = Locale::new("xx-YY");
+        assert_eq!(unknown.decimal_separator(), '.');
+        assert_eq!(unknown.group_separator(), ',');
+        assert_eq!(Locale::new("fr").tag(), "fr");
+        let custom = Locale::new("en").with_separators('·', '\'');
+        assert_eq!(custom.localize("1234.5", true), "1'234·5");
+    }
+
+    #[test]
+    fn test_localize() {
+        let en = Locale::default();
+        let de = Locale::new("de");
+        assert_eq!(en.localize("1234567.891", true), "1,234,567.891");
+        assert_eq!(en.localize("1234567.891", false), "1234567.891");
+        assert_eq!(de.localize("-1234567.891", true), "-1.234.567,891");
+        assert_eq!(de.localize("+123", true), "+123");
+        assert_eq!(de.localize("1234", true), "1.234");
+        assert_eq!(de.localize("1.5e+03", true), "1,5e+03");
+        assert_eq!(de.localize("0", true), "0");
+    }
+
+    #[test]
+    fn test_format_with_locale() {
+        let rule = Rule::from_value(
+            &json!({"format": ["%'.2f|%e|%d", 1234567.891, 0.5, 1e4]}),
+        )
+        .unwrap();
+        let with_locale = |locale: Locale| Context {
+            locale: Some(Arc::new(locale)),
+            ..Context::default()
+        };
+        assert_eq!(
+            rule.apply(&json!({})).unwrap(),
+            json!("1,234,567.89|5.000000e-01|10000")
+        );
+        assert_eq!(
+            rule.apply_with(&json!({}), &with_locale(Locale::new("de-DE")))
+                .unwrap(),
+            json!("1.234.567,89|5,000000e-01|10000")
+        );
+        assert_eq!(
+            rule.apply_with(&json!({}), &with_locale(Locale::new("fr-FR")))
+                .unwrap(),
+            json!("1\u{202f}234\u{202f}567,89|5,000000e-01|10000")
+        );
+        // Translated rules have no context
+        assert_eq!(rule.to_javascript().unwrap_err().code(), "untranslatable");
+    }
+}
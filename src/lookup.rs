@@ -0,0 +1,103 @@
+//! External Lookups
+//!
+//! The `lookup` operator retrieves a value from a table provided by the host,
+//! e.g. `{"lookup": ["country_risk", {"var": "country"}]}`. The tables are
+//! backed by a `LookupResolver` set on the evaluation's `Context`, so rules
+//! can refer to reference data without it being copied into every piece of
+//! data they're applied to.
+
+use core::fmt;
+
+use serde_json::Value;
+
+#[cfg(feature = "async")]
+use crate::asynchronous::BoxFuture;
+use crate::collections::HashMap;
+use crate::error::Error;
+use crate::prelude::*;
+
+/// A source of tables for the `lookup` operator
+pub trait LookupResolver: fmt::Debug + Send + Sync {
+    /// Look up a key in a table, returning None if the key isn't present.
+    fn lookup(&self, table: &str, key: &Value) -> Result<Option<Value>, Error>;
+}
+
+/// Each entry of the map is a table, which should be an object. Keys that
+/// aren't strings are looked up by their JSON representation.
+impl LookupResolver for HashMap<String, Value> {
+    fn lookup(&self, table: &str, key: &Value) -> Result<Option<Value>, Error> {
+        let table = self.get(table).ok_or_else(|| {
+            Error::invalid_argument(table.into(), "lookup", "No such table")
+        })?;
+        Ok(match key {
+            Value::String(key) => table.get(key),
+            key => table.get(key.to_string()),
+        }
+        .cloned())
+    }
+}
+
+/// An asynchronous source of tables for the `lookup` operator
+///
+/// Async lookups are only performed by `Rule::apply_async()`.
+#[cfg(feature = "async")]
+pub trait AsyncLookupResolver: fmt::Debug + Send + Sync {
+    /// Look up a key in a table, returning None if the key isn't present.
+    fn lookup(
+        &self,
+        table: String,
+        key: Value,
+    ) -> BoxFuture<'static, Result<Option<Value>, Error>>;
+}
+
+#[cfg(test)]
+mod test_lookup {
+    use super::*;
+    use alloc::sync::Arc;
+    use serde_json::json;
+
+    use crate::{Context, Rule};
+
+    #[test]
+    fn test_lookup() {
+        let mut tables = HashMap::new();
+        tables.insert("country_risk".to_string(), json!({"US": 1, "XX": 9}));
+        tables.insert("ranks".to_string(), json!({"1": "first"}));
+        let ctx = Context {
+            lookup: Some(Arc::new(tables)),
+            ..Context::default()
+        };
+
+        let rule = Rule::from_value(&json!({"lookup": [
+            "country_risk",
+            {"var": "country"}
+        ]}))
+        .unwrap();
+        assert_eq!(
+            rule.apply_with(&json!({"country": "XX"}), &ctx).unwrap(),
+            json!(9)
+        );
+        assert_eq!(
+            rule.apply_with(&json!({"country": "CA"}), &ctx).unwrap(),
+            json!(null)
+        );
+        assert_eq!(
+            Rule::from_value(&json!({"lookup": ["ranks", 1]}))
+                .unwrap()
+                .apply_with(&json!(null), &ctx)
+                .unwrap(),
+            json!("first")
+        );
+
+        // Unknown tables and missing resolvers are errors
+        Rule::from_value(&json!({"lookup": ["nope", 1]}))
+            .unwrap()
+            .apply_with(&json!(null), &ctx)
+            .unwrap_err();
+        Rule::from_value(&json!({"lookup": [1, 1]}))
+            .unwrap()
+            .apply_with(&json!(null), &ctx)
+            .unwrap_err();
+        rule.apply(&json!({"country": "XX"})).unwrap_err();
+    }
+}
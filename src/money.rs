@@ -0,0 +1,29 @@
+//! Exchange Rates
+//!
+//! The `money_convert` operator converts amounts between currencies with
+//! the rates of an `ExchangeRates` set on the evaluation's `Context`, so that
+//! rates are maintained by the host rather than written into rules.
+
+use core::fmt;
+
+use serde_json::Value;
+
+use crate::collections::HashMap;
+use crate::error::Error;
+use crate::prelude::*;
+
+/// A source of exchange rates for the `money_convert` operator
+pub trait ExchangeRates: fmt::Debug + Send + Sync {
+    /// The amount of the `to` currency that one unit of the `from` currency
+    /// is worth, as a number or a decimal string, or None if there's no
+    /// rate between them.
+    fn rate(&self, from: &str, to: &str) -> Result<Option<Value>, Error>;
+}
+
+/// Rates are keyed by currency pairs, as `FROM/TO`, e.g. `USD/EUR` for the
+/// euros that a dollar is worth.
+impl ExchangeRates for HashMap<String, Value> {
+    fn rate(&self, from: &str, to: &str) -> Result<Option<Value>, Error> {
+        Ok(self.get(&format!("{}/{}", from, to)).cloned())
+    }
+}
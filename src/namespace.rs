@@ -0,0 +1,2131 @@
+//! Namespaces
+//!
+//! A namespace is a collection of user-defined functions, which may call one
+//! another, along with any custom operators loaded from operator packs.
+//! Namespaces are generally assembled from a library of `defn` expressions
+//! and deployed together, so they can be validated as a unit.
+//!
+//! Namespaces may be nested: mounting one namespace within another under a
+//! prefix makes its functions available by dotted names, e.g. the `clamp`
+//! function of a namespace mounted as `math` is called as `math.clamp`.
+//! Function names are resolved in order of precedence:
+//!
+//! 1. Built-in operators, and custom operators from loaded packs
+//! 2. Within the body of a function, functions of the same namespace as the
+//!    caller, then of each enclosing namespace in turn, so that from within
+//!    `math.stats.mean`, `sum` refers to `math.stats.sum` if it is defined,
+//!    then `math.sum`, then `sum`
+//! 3. Functions with exactly the given name
+//!
+//! Names are compared once normalized to Unicode NFC, so a name may be
+//! written with composed or decomposed characters.
+//!
+//! A namespace may also give operators and functions other names: aliases,
+//! e.g. `equals` for `==`, and deprecated names, which are parsed like
+//! aliases but reported by the linter, easing the migration of existing
+//! rules to new names.
+
+use alloc::borrow::Cow;
+use alloc::sync::Arc;
+use core::convert::TryFrom;
+use core::fmt;
+
+use serde_json::{Map, Value};
+
+use crate::arena::{Arena, ArenaSlice, ArenaStr};
+use crate::ast::{Ast, AstVisitor};
+use crate::bundle;
+use crate::collections::{HashMap, HashSet};
+use crate::error::Error;
+use crate::op::data::PathSegment;
+use crate::op::func::{
+    function_ref, normalize, Function, FunctionBody, Identifier, Param,
+    CLOSURE_SEPARATOR, FUNCTION_REF,
+};
+use crate::op::{self, CommonOperator, NumParams, OperatorCategory, OperatorInfo};
+use crate::op::{logic, pattern};
+use crate::pack::{CustomOperator, OperatorPack, PackInfo, PACK_API_VERSION};
+use crate::prelude::*;
+use crate::rule::{self, ParseOptions, Rule};
+use crate::value::{Parsed, Raw};
+use crate::Parser;
+
+/// A collection of named functions and custom operators
+#[derive(Clone, Debug, Default)]
+pub struct Namespace {
+    functions: HashMap<String, Function>,
+    operators: HashMap<String, Arc<CustomOperator>>,
+    packs: Vec<PackInfo>,
+    aliases: HashMap<String, Alias>,
+    options: ParseOptions,
+}
+impl Namespace {
+    /// The key of a module document's function definitions
+    pub(crate) const FUNCTIONS: &'static str = "functions";
+    /// The key of a module document's aliases
+    const ALIASES: &'static str = "aliases";
+    /// The key of a module document's deprecated names
+    const DEPRECATED: &'static str = "deprecated";
+
+    /// Construct an empty namespace.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse a function definition (a `defn` expression) and add it to the
+    /// namespace, replacing any existing function of the same name.
+    ///
+    /// The names of the function and its parameters must be allowed by the
+    /// namespace's `IdentifierPolicy`, set with
+    /// [`set_parse_options`](#method.set_parse_options).
+    pub fn define(&mut self, value: &Value) -> Result<(), Error> {
+        let func =
+            Function::from_value(value, &Scope::default())?.ok_or_else(|| {
+                Error::invalid_argument(
+                    value.clone(),
+                    Function::OPERATOR,
+                    "Value is not a function definition",
+                )
+            })?;
+        let policy = &self.options.identifiers;
+        policy.check_function_name(func.name().value())?;
+        for func in core::iter::once(&func).chain(func.closures()) {
+            // Closures are named for the functions enclosing them, so can't
+            // shadow built-ins, but their own names must still be allowed
+            let name = func.name().value();
+            if let Some((_, own)) = name.rsplit_once(CLOSURE_SEPARATOR) {
+                policy.check(own)?;
+            }
+            func.params()
+                .iter()
+                .try_for_each(|param| policy.check(param.value()))?;
+        }
+        self.insert(func);
+        Ok(())
+    }
+
+    /// Add a function to the namespace, along with the closures defined
+    /// within it, returning any function it replaced.
+    pub fn insert(&mut self, func: Function) -> Option<Function> {
+        for closure in func.closures() {
+            self.functions
+                .insert(closure.name().value().into(), closure.clone());
+        }
+        self.functions.insert(func.name().value().into(), func)
+    }
+
+    /// Retrieve a function by name.
+    pub fn get(&self, name: &str) -> Option<&Function> {
+        self.functions.get(normalize(name).as_ref())
+    }
+
+    /// Retrieve the function a name refers to when called from within the
+    /// body of `caller`, or at the top level of a rule if it is None.
+    ///
+    /// See the [module documentation](index.html) for the order in which
+    /// names are resolved.
+    pub fn resolve(&self, name: &str, caller: Option<&str>) -> Option<&Function> {
+        let name = normalize(name);
+        // Closures share the namespace of the function enclosing them
+        let caller = caller.and_then(|caller| caller.split(CLOSURE_SEPARATOR).next());
+        let mut prefix = caller.map_or("", parent);
+        while !prefix.is_empty() {
+            if let Some(func) = self.functions.get(&format!("{}.{}", prefix, name)) {
+                return Some(func);
+            }
+            prefix = parent(prefix);
+        }
+        self.functions.get(name.as_ref())
+    }
+
+    /// Mount the functions of another namespace within this one, prefixing
+    /// their names with `prefix` and a dot, and replacing any existing
+    /// functions of the same names.
+    ///
+    /// Custom operators aren't namespaced, so the other namespace's
+    /// operators are loaded alongside this one's, and those whose names are
+    /// already taken are skipped. Fails without mounting anything if the
+    /// prefix isn't a valid identifier.
+    pub fn mount(&mut self, prefix: &str, namespace: Namespace) -> Result<(), Error> {
+        Identifier::try_from(&Value::from(prefix))?;
+        let functions = namespace
+            .functions
+            .into_values()
+            .map(|func| func.prefixed(prefix))
+            .collect::<Result<Vec<_>, Error>>()?;
+        functions.into_iter().for_each(|func| {
+            self.functions.insert(func.name().value().into(), func);
+        });
+        for (name, operator) in namespace.operators {
+            self.operators.entry(name).or_insert(operator);
+        }
+        for pack in namespace.packs {
+            if !self.packs.contains(&pack) {
+                self.packs.push(pack);
+            }
+        }
+        Ok(())
+    }
+
+    /// Iterate over the functions in the namespace, in no particular order.
+    pub fn functions(&self) -> impl Iterator<Item = &Function> {
+        self.functions.values()
+    }
+
+    /// Give an operator or function another name, e.g. `equals` for `==`.
+    /// Rules using the alias are parsed as though they used the target's
+    /// name.
+    ///
+    /// Fails if the alias is already the name of an operator or function, or
+    /// if the target isn't one. Aliases aren't carried over when the
+    /// namespace is mounted within another.
+    pub fn alias(&mut self, alias: &str, target: &str) -> Result<(), Error> {
+        self.add_alias(alias, target, false)
+    }
+
+    /// Deprecate a name in favor of the name of an operator or function.
+    /// Rules using the deprecated name are parsed as though they used the
+    /// replacement, but each use is reported by `lint::lint_value()` and
+    /// `lint::lint_namespace()`, and, with the `tracing` feature, emits a
+    /// warning event when parsed.
+    ///
+    /// Fails under the same conditions as [`alias`](#method.alias).
+    pub fn deprecate(&mut self, name: &str, replacement: &str) -> Result<(), Error> {
+        self.add_alias(name, replacement, true)
+    }
+
+    fn add_alias(
+        &mut self,
+        alias: &str,
+        target: &str,
+        deprecated: bool,
+    ) -> Result<(), Error> {
+        let invalid = |reason: String| Error::InvalidOperation {
+            key: alias.into(),
+            reason,
+        };
+        let defined = |name: &str| {
+            op::is_builtin(name)
+                || self.functions.contains_key(name)
+                || self.operators.contains_key(name)
+        };
+        if defined(alias) {
+            return Err(invalid(format!("'{}' is already defined", alias)));
+        }
+        // Aliases of aliases stand for the same name
+        let target = self
+            .aliases
+            .get(target)
+            .map_or(target, |alias| alias.target.as_str());
+        if !defined(target) {
+            return Err(invalid(format!(
+                "'{}' is not an operator or function",
+                target
+            )));
+        }
+        let target = target.to_string();
+        self.aliases
+            .insert(alias.into(), Alias { target, deprecated });
+        Ok(())
+    }
+
+    /// The name that replaces a deprecated name, if `name` is deprecated
+    pub fn replacement(&self, name: &str) -> Option<&str> {
+        self.aliases
+            .get(name)
+            .filter(|alias| alias.deprecated)
+            .map(|alias| alias.target.as_str())
+    }
+
+    /// Construct a namespace from a module document, as produced by
+    /// [`to_value`](#method.to_value).
+    pub fn from_value(value: &Value) -> Result<Self, Error> {
+        let defs = value
+            .get(Self::FUNCTIONS)
+            .and_then(Value::as_array)
+            .ok_or_else(|| Error::InvalidData {
+                value: value.clone(),
+                reason: format!(
+                    "Expected a module with an array of definitions at '{}'",
+                    Self::FUNCTIONS
+                ),
+            })?;
+        let mut namespace = Self::new();
+        defs.iter().try_for_each(|def| namespace.define(def))?;
+        for (key, deprecated) in [(Self::ALIASES, false), (Self::DEPRECATED, true)] {
+            let aliases = match value.get(key) {
+                Some(Value::Object(aliases)) => aliases,
+                Some(other) => {
+                    return Err(Error::InvalidData {
+                        value: other.clone(),
+                        reason: format!("Expected an object of names at '{}'", key),
+                    })
+                }
+                None => continue,
+            };
+            aliases.iter().try_for_each(|(alias, target)| {
+                let target = target.as_str().ok_or_else(|| Error::InvalidData {
+                    value: target.clone(),
+                    reason: format!("Expected the name '{}' stands for", alias),
+                })?;
+                namespace.add_alias(alias, target, deprecated)
+            })?;
+        }
+        Ok(namespace)
+    }
+
+    /// The namespace as a module document, which may be stored and later
+    /// loaded with [`from_value`](#method.from_value):
+    ///
+    /// ```jsonc
+    /// {"functions": [{"defn": ["double", ["x"], {"*": [{"param": "x"}, 2]}]}]}
+    /// ```
+    ///
+    /// Definitions are sorted by name, and closures are defined alongside
+    /// the functions they were lifted from. Any aliases and deprecated
+    /// names are included as objects mapping each name to the one it stands
+    /// for, at `aliases` and `deprecated`. Custom operators aren't part of
+    /// the document, so their packs must be loaded again.
+    pub fn to_value(&self) -> Value {
+        let mut funcs: Vec<&Function> = self.functions.values().collect();
+        funcs.sort_unstable_by_key(|func| func.name().value());
+        let defs = funcs.into_iter().cloned().map(Value::from).collect();
+        let mut module = Map::new();
+        module.insert(Self::FUNCTIONS.into(), Value::Array(defs));
+        for (key, deprecated) in [(Self::ALIASES, false), (Self::DEPRECATED, true)] {
+            let mut aliases: Vec<(&String, &Alias)> = self
+                .aliases
+                .iter()
+                .filter(|(_, alias)| alias.deprecated == deprecated)
+                .collect();
+            if aliases.is_empty() {
+                continue;
+            }
+            aliases.sort_unstable_by_key(|(name, _)| *name);
+            let aliases = aliases
+                .into_iter()
+                .map(|(name, alias)| (name.clone(), alias.target.clone().into()))
+                .collect();
+            module.insert(key.into(), Value::Object(aliases));
+        }
+        Value::Object(module)
+    }
+
+    /// Load the custom operators from an operator pack.
+    ///
+    /// Fails without loading anything if the pack was built against an
+    /// incompatible version of the pack API, or if any of its operators
+    /// share a name with a built-in operator, a function, or an operator
+    /// that is already loaded.
+    pub fn load_pack(&mut self, pack: &dyn OperatorPack) -> Result<(), Error> {
+        let invalid = |reason: String| Error::InvalidPack {
+            pack: pack.name().into(),
+            reason,
+        };
+        if pack.api_version() != PACK_API_VERSION {
+            return Err(invalid(format!(
+                "pack requires API version {}, but version {} is supported",
+                pack.api_version(),
+                PACK_API_VERSION
+            )));
+        }
+        let operators = pack.operators();
+        let mut names = HashSet::new();
+        operators.iter().try_for_each(|operator| {
+            let name = operator.name();
+            if op::is_builtin(name)
+                || self.functions.contains_key(name)
+                || self.operators.contains_key(name)
+                || !names.insert(name)
+            {
+                Err(invalid(format!("operator '{}' is already defined", name)))
+            } else {
+                Ok(())
+            }
+        })?;
+        operators.into_iter().for_each(|operator| {
+            self.operators
+                .insert(operator.name().into(), Arc::new(operator));
+        });
+        self.packs.push(PackInfo {
+            name: pack.name().into(),
+            version: pack.version().into(),
+        });
+        Ok(())
+    }
+
+    /// Retrieve a custom operator by name.
+    pub fn operator(&self, name: &str) -> Option<&CustomOperator> {
+        self.operators.get(name).map(|operator| &**operator)
+    }
+
+    /// Metadata for every operator available within the namespace: the
+    /// built-in operators, the custom operators from loaded packs, and the
+    /// namespace's functions, sorted by name. Closures lifted out of
+    /// functions aren't included.
+    pub fn operators(&self) -> Vec<OperatorInfo> {
+        let mut operators = op::operators();
+        operators.extend(self.operators.values().map(|operator| {
+            OperatorInfo::new(
+                operator.name(),
+                operator.num_params().clone(),
+                OperatorCategory::Custom,
+                operator.description(),
+            )
+        }));
+        let functions = self
+            .functions
+            .values()
+            .filter(|func| !func.name().value().contains(CLOSURE_SEPARATOR));
+        operators.extend(functions.map(|func| {
+            OperatorInfo::new(
+                func.name().value(),
+                func.param_info().clone(),
+                OperatorCategory::Defined,
+                func.doc().unwrap_or_default(),
+            )
+        }));
+        operators.sort_by(|a, b| a.name().cmp(b.name()));
+        operators
+    }
+
+    /// Retrieve a custom operator by name, to be shared by the operations
+    /// that apply it.
+    pub(crate) fn shared_operator(&self, name: &str) -> Option<&Arc<CustomOperator>> {
+        self.operators.get(name)
+    }
+
+    /// The packs that have been loaded, in the order they were loaded
+    pub fn packs(&self) -> &[PackInfo] {
+        &self.packs
+    }
+
+    /// Parse a rule that may call the functions in the namespace.
+    ///
+    /// Within a namespace, every single-key object is an operation: a
+    /// built-in operator, a call to one of the namespace's functions, or (in
+    /// a function body) a `param` reference. Any other key is an
+    /// `UnknownOperator` error, or within a function body an
+    /// `UndefinedFunction` error, which suggest the closest known names.
+    ///
+    /// Functions may call one another regardless of the order in which they
+    /// were defined, including recursively. The bodies of the functions the
+    /// rule may call, directly or indirectly, are compiled into the returned
+    /// rule, so later changes to the namespace don't affect it. Functions it
+    /// never calls aren't parsed.
+    ///
+    /// `{"fn": "name"}` references a function as a value, which may be
+    /// called with `{"call": [reference, [arguments...]]}` (or its alias,
+    /// `apply`), composed with `{"comp": [f, g, ...]}`, and partially
+    /// applied to leading arguments with `{"partial": [f, [arguments...]]}`.
+    /// Functions that are referenced anywhere in the rule, including within
+    /// literal arrays and objects, may be called in this way.
+    pub fn parse(&self, value: &Value) -> Result<Rule, Error> {
+        #[cfg(feature = "tracing")]
+        let _span = crate::trace::parse();
+        let (semantics, value) = rule::unwrap_version(value)?;
+        let arena = Arena::default();
+        let parsed = Parsed::from_value(value, &self.scope().with_arena(&arena))?;
+        let mut bodies = HashMap::new();
+        let mut pending: Vec<String> = calls(&parsed)
+            .into_iter()
+            .filter(|name| self.functions.contains_key(name))
+            .collect();
+        while let Some(name) = pending.pop() {
+            if bodies.contains_key(&name) {
+                continue;
+            }
+            let func = self.functions.get(&name).ok_or_else(|| {
+                Error::UnexpectedError(format!("Function '{}' is not defined", name))
+            })?;
+            let body = self.body(func, &arena)?;
+            pending.extend(
+                core::iter::once(&body.expression)
+                    .chain(&body.pre)
+                    .chain(&body.post)
+                    .chain(&body.defaults)
+                    .flat_map(calls)
+                    .filter(|name| self.functions.contains_key(name)),
+            );
+            bodies.insert(name, body);
+        }
+        let arena = arena.freeze();
+        for body in bodies.values_mut() {
+            body.arena = Some(arena.clone());
+        }
+        let rule = Rule::with_functions(parsed, Arc::new(bodies), arena);
+        Ok(match semantics {
+            Some(semantics) => rule.with_semantics(semantics),
+            None => rule,
+        })
+    }
+
+    /// The typed syntax trees of all functions in the namespace, sorted by
+    /// name.
+    pub fn definitions(&self) -> Result<Vec<Ast>, Error> {
+        let arena = Arena::default();
+        self.bodies(&arena).map(|bodies| {
+            bodies
+                .iter()
+                .map(|(func, body)| Ast::function_def(func, &body.expression))
+                .collect()
+        })
+    }
+
+    /// Set the options used when parsing rules and function bodies in the
+    /// namespace.
+    pub fn set_parse_options(&mut self, options: ParseOptions) {
+        self.options = options;
+    }
+
+    /// The scope for parsing rules in this namespace
+    fn scope(&self) -> Scope<'_> {
+        Scope {
+            namespace: Some(self),
+            params: &[],
+            function: None,
+            arena: None,
+            options: &self.options,
+        }
+    }
+
+    /// Parse the body and contract of every function, sorted by name,
+    /// allocating their names, argument lists, and paths in the given arena.
+    fn bodies(&self, arena: &Arena) -> Result<Vec<(&Function, FunctionBody)>, Error> {
+        let mut funcs: Vec<&Function> = self.functions.values().collect();
+        funcs.sort_unstable_by_key(|func| func.name().value());
+        funcs
+            .into_iter()
+            .map(|func| self.body(func, arena).map(|body| (func, body)))
+            .collect()
+    }
+
+    /// Parse the body and contract of a function, allocating its names,
+    /// argument lists, and paths in the given arena.
+    fn body(&self, func: &Function, arena: &Arena) -> Result<FunctionBody, Error> {
+        let scope = Scope {
+            params: func.params(),
+            function: Some(func.name().value()),
+            ..self.scope().with_arena(arena)
+        };
+        FunctionBody::parse(func, &scope)
+    }
+
+    /// Validate all functions in the namespace with the default options.
+    ///
+    /// See [`check_with`](#method.check_with).
+    pub fn check(&self) -> Result<(), Error> {
+        self.check_with(&CheckOptions::default())
+    }
+
+    /// Validate all functions in the namespace against one another.
+    ///
+    /// Function bodies are walked to find every call site. Within a function
+    /// body, any single-key object whose key is not a built-in operator is
+    /// considered to be a call to a function in the namespace. Returns an
+    /// `InvalidNamespace` error containing every problem found, sorted by the
+    /// name of the function in which it occurred.
+    pub fn check_with(&self, options: &CheckOptions) -> Result<(), Error> {
+        let mut issues = Vec::new();
+        let mut calls: HashMap<&str, Vec<&str>> = HashMap::new();
+
+        for func in self.functions.values() {
+            let name = func.name().value();
+            let mut seen = HashSet::new();
+            func.params().iter().for_each(|param| {
+                if !seen.insert(param.value()) {
+                    issues.push(Issue::Shadowing {
+                        function: name.into(),
+                        name: param.value().into(),
+                        shadowed: Shadowed::Parameter,
+                    })
+                }
+            });
+            if op::is_builtin(name) || self.operators.contains_key(name) {
+                issues.push(Issue::Shadowing {
+                    function: name.into(),
+                    name: name.into(),
+                    shadowed: Shadowed::Operator,
+                })
+            }
+            let mut callees = Vec::new();
+            self.check_expression(func, func.expression(), &mut callees, &mut issues);
+            func.defaults().iter().for_each(|default| {
+                self.check_expression(func, default, &mut callees, &mut issues)
+            });
+            calls.insert(name, callees);
+        }
+
+        if !options.allow_recursion {
+            find_cycles(&calls)
+                .into_iter()
+                .for_each(|cycle| issues.push(Issue::Recursion { cycle }));
+        }
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            issues.sort_by(|a, b| a.function().cmp(b.function()));
+            Err(Error::InvalidNamespace(issues))
+        }
+    }
+
+    /// Run the examples of every function in the namespace, returning those
+    /// whose results differ from what was expected.
+    ///
+    /// Each example calls its function with the example's arguments, as
+    /// literal values. Results are compared as with `eq`, and an example
+    /// expecting a result fails if the call returns an error. Failures are
+    /// sorted by function name, then by the examples' order in the
+    /// definition. Fails if the namespace can't be parsed.
+    pub fn run_examples(&self) -> Result<Vec<ExampleFailure>, Error> {
+        let mut funcs: Vec<&Function> = self.functions.values().collect();
+        funcs.sort_unstable_by_key(|func| func.name().value());
+        let mut failures = Vec::new();
+        for func in funcs {
+            if func.examples().is_empty() {
+                continue;
+            }
+            let name = func.name().value();
+            for (index, (arguments, expected)) in func.examples().iter().enumerate() {
+                // Arguments are read from the data, so that they aren't
+                // parsed as expressions.
+                let args: Vec<Value> = (0..arguments.len())
+                    .map(|idx| serde_json::json!({ "var": idx }))
+                    .collect();
+                let mut call = serde_json::Map::new();
+                call.insert(name.into(), Value::Array(args));
+                let rule = self.parse(&Value::Object(call))?;
+                let actual = rule.apply(&Value::Array(arguments.clone()));
+                if actual.as_ref().ok() != Some(expected) {
+                    failures.push(ExampleFailure {
+                        function: name.into(),
+                        index,
+                        arguments: arguments.clone(),
+                        expected: expected.clone(),
+                        actual,
+                    });
+                }
+            }
+        }
+        Ok(failures)
+    }
+
+    /// Recursively check an expression in the body of `func`.
+    fn check_expression<'a>(
+        &'a self,
+        func: &Function,
+        value: &'a Value,
+        callees: &mut Vec<&'a str>,
+        issues: &mut Vec<Issue>,
+    ) {
+        let obj = match value {
+            Value::Array(items) => {
+                return items.iter().for_each(|item| {
+                    self.check_expression(func, item, callees, issues)
+                })
+            }
+            Value::Object(obj) => obj,
+            _ => return,
+        };
+        if obj.len() != 1 {
+            return obj
+                .values()
+                .for_each(|item| self.check_expression(func, item, callees, issues));
+        }
+        let (key, args) = match obj.iter().next() {
+            Some(entry) => entry,
+            None => return,
+        };
+        let key = match self.aliases.get(key) {
+            Some(alias) => &alias.target,
+            None => key,
+        };
+        let arg_count = match args {
+            Value::Array(items) => items.len(),
+            _ => 1,
+        };
+
+        let caller = Some(func.name().value());
+        if key == bundle::RULE_REF {
+            let rule = args.as_str().map(bundle::rule_function);
+            match rule.and_then(|rule| self.resolve(&rule, caller)) {
+                Some(callee) => callees.push(callee.name().value()),
+                None => issues.push(Issue::UnresolvedFunction {
+                    function: func.name().value().into(),
+                    name: args.as_str().map_or_else(|| args.to_string(), String::from),
+                }),
+            }
+            return;
+        }
+        if key == FUNCTION_REF {
+            match args.as_str().and_then(|name| self.resolve(name, caller)) {
+                Some(callee) => callees.push(callee.name().value()),
+                None => issues.push(Issue::UnresolvedFunction {
+                    function: func.name().value().into(),
+                    name: args.as_str().map_or_else(|| args.to_string(), String::from),
+                }),
+            }
+            return;
+        }
+
+        // Quoted values are data, rather than expressions
+        if key == Raw::OPERATOR {
+            return;
+        }
+
+        if key == Param::OPERATOR {
+            if let Value::String(name) = args {
+                let p = normalize(name);
+                if !func.params().iter().any(|i| i.value() == p) {
+                    issues.push(Issue::UnresolvedParameter {
+                        function: func.name().value().into(),
+                        name: name.clone(),
+                    })
+                }
+            }
+            return;
+        }
+
+        let expected = if let Some(num_params) = op::builtin_param_info(key) {
+            Some(num_params)
+        } else if let Some(operator) = self.operators.get(key) {
+            Some(operator.num_params().clone())
+        } else if let Some(callee) = self.resolve(key, caller) {
+            callees.push(callee.name().value());
+            Some(callee.param_info().clone())
+        } else {
+            issues.push(Issue::UnresolvedFunction {
+                function: func.name().value().into(),
+                name: key.clone(),
+            });
+            None
+        };
+        if let Some(expected) = expected {
+            if !expected.is_valid_len(&arg_count) {
+                issues.push(Issue::ArityMismatch {
+                    function: func.name().value().into(),
+                    name: key.clone(),
+                    expected,
+                    actual: arg_count,
+                })
+            }
+        }
+
+        // Patterns are literals, rather than expressions
+        if let (pattern::OPERATOR, Value::Array(items)) = (key.as_str(), args) {
+            return items
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| !pattern::is_pattern(*i, arg_count))
+                .for_each(|(_, item)| {
+                    self.check_expression(func, item, callees, issues)
+                });
+        }
+        // The cases are an object of branches, rather than an expression
+        if let (logic::SWITCH, Value::Array(items)) = (key.as_str(), args) {
+            return items
+                .iter()
+                .enumerate()
+                .for_each(|(i, item)| match (i, item) {
+                    (1, Value::Object(cases)) => cases.values().for_each(|branch| {
+                        self.check_expression(func, branch, callees, issues)
+                    }),
+                    _ => self.check_expression(func, item, callees, issues),
+                });
+        }
+        self.check_expression(func, args, callees, issues)
+    }
+}
+
+/// The names that may be referenced while parsing a rule
+///
+/// Outside of a namespace, only the built-in operators are known, and any
+/// other object is a raw value.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Scope<'a> {
+    namespace: Option<&'a Namespace>,
+    params: &'a [Identifier],
+    /// The name of the function whose body is being parsed, if any
+    function: Option<&'a str>,
+    arena: Option<&'a Arena>,
+    options: &'a ParseOptions,
+}
+impl Default for Scope<'_> {
+    fn default() -> Self {
+        static DEFAULT_OPTIONS: ParseOptions = ParseOptions::DEFAULT;
+        Self {
+            namespace: None,
+            params: &[],
+            function: None,
+            arena: None,
+            options: &DEFAULT_OPTIONS,
+        }
+    }
+}
+impl<'a> Scope<'a> {
+    /// Parse with the given options.
+    pub(crate) fn with_options(self, options: &'a ParseOptions) -> Self {
+        Self { options, ..self }
+    }
+
+    /// The options to parse with
+    pub(crate) fn options(&self) -> &'a ParseOptions {
+        self.options
+    }
+
+    /// Allocate the names, argument lists, and paths of the parsed nodes in
+    /// the given arena.
+    pub(crate) fn with_arena(self, arena: &'a Arena) -> Self {
+        Self {
+            arena: Some(arena),
+            ..self
+        }
+    }
+
+    /// Allocate a name, or share an equal one, in the scope's arena if it
+    /// has one.
+    pub(crate) fn name(&self, name: &str) -> ArenaStr {
+        match self.arena {
+            Some(arena) => arena.name(name),
+            None => name.into(),
+        }
+    }
+
+    /// Allocate the path for a `var` key, or share an equal one, in the
+    /// scope's arena if it has one.
+    pub(crate) fn path<F>(&self, key: &str, split: F) -> ArenaSlice<PathSegment>
+    where
+        F: FnOnce() -> Vec<PathSegment>,
+    {
+        match self.arena {
+            Some(arena) => arena.path(key, split),
+            None => split().into(),
+        }
+    }
+
+    /// Allocate a list of parsed values, stopping at the first that fails,
+    /// in the scope's arena if it has one.
+    pub(crate) fn slice<T, I>(&self, items: I) -> Result<ArenaSlice<T>, Error>
+    where
+        I: IntoIterator<Item = Result<T, Error>>,
+    {
+        match self.arena {
+            Some(arena) => arena.slice(items),
+            None => items
+                .into_iter()
+                .collect::<Result<Vec<T>, Error>>()
+                .map(Into::into),
+        }
+    }
+
+    /// Parse with only the given parameters of the function in scope.
+    pub(crate) fn with_params(self, params: &'a [Identifier]) -> Self {
+        Self { params, ..self }
+    }
+
+    /// The namespace whose functions may be called, if any
+    pub(crate) fn namespace(&self) -> Option<&'a Namespace> {
+        self.namespace
+    }
+
+    /// The parameters of the function whose body is being parsed
+    pub(crate) fn params(&self) -> &'a [Identifier] {
+        self.params
+    }
+
+    /// The function a name refers to when called within the scope
+    pub(crate) fn resolve(&self, name: &str) -> Option<&'a Function> {
+        self.namespace?.resolve(name, self.function)
+    }
+
+    /// The value, with the name in a function reference replaced by that
+    /// of the function it refers to, so that references made within a
+    /// mounted namespace can be followed from anywhere.
+    pub(crate) fn qualify(&self, value: &Value) -> Value {
+        if let Some(func) = function_ref(value).and_then(|name| self.resolve(name)) {
+            return serde_json::json!({ FUNCTION_REF: func.name().value() });
+        }
+        value.clone()
+    }
+
+    /// The value, with an alias for an operator or function replaced by the
+    /// name it stands for
+    pub(crate) fn unalias<'v>(&self, value: &'v Value) -> Cow<'v, Value> {
+        // References to a bundle's rules call the functions holding them
+        if let (Some(_), Some(name)) = (self.namespace, bundle::rule_ref(value)) {
+            let mut obj = Map::with_capacity(1);
+            obj.insert(bundle::rule_function(name), Value::Array(Vec::new()));
+            return Cow::Owned(Value::Object(obj));
+        }
+        let aliases = match self.namespace {
+            Some(namespace) if !namespace.aliases.is_empty() => &namespace.aliases,
+            _ => return Cow::Borrowed(value),
+        };
+        let (key, args) = match value {
+            Value::Object(obj) if obj.len() == 1 => match obj.iter().next() {
+                Some(entry) => entry,
+                None => return Cow::Borrowed(value),
+            },
+            _ => return Cow::Borrowed(value),
+        };
+        match aliases.get(key) {
+            Some(alias) => {
+                #[cfg(feature = "tracing")]
+                if alias.deprecated {
+                    crate::trace::deprecated(key, &alias.target);
+                }
+                let mut obj = Map::with_capacity(1);
+                obj.insert(alias.target.clone(), args.clone());
+                Cow::Owned(Value::Object(obj))
+            }
+            None => Cow::Borrowed(value),
+        }
+    }
+
+    /// Return an error if `value` is an operation unknown within the scope.
+    pub(crate) fn check_operator(&self, value: &Value) -> Result<(), Error> {
+        let namespace = match self.namespace {
+            Some(namespace) => namespace,
+            None => return Ok(()),
+        };
+        let (key, val) = match op::single_entry(value)? {
+            Some(entry) => entry,
+            None => return Ok(()),
+        };
+        if key == FUNCTION_REF {
+            return match val.as_str() {
+                Some(name) if self.resolve(name).is_some() => Ok(()),
+                _ => Err(Error::invalid_argument(
+                    val.clone(),
+                    FUNCTION_REF,
+                    "Expected the name of a function in the namespace",
+                )),
+            };
+        }
+        if op::is_builtin(key)
+            || key == Param::OPERATOR
+            || self.resolve(key).is_some()
+            || namespace.operator(key).is_some()
+        {
+            return Ok(());
+        }
+        let mut known: Vec<&str> = op::builtin_names().collect();
+        known.extend(namespace.functions.keys().map(String::as_str));
+        known.extend(namespace.operators.keys().map(String::as_str));
+        known.push(Param::OPERATOR);
+        let suggestions = op::suggest(key, known.into_iter());
+        Err(match self.function {
+            Some(caller) => Error::UndefinedFunction {
+                name: key.clone(),
+                caller: caller.into(),
+                suggestions,
+            },
+            None => Error::UnknownOperator {
+                name: key.clone(),
+                suggestions,
+            },
+        })
+    }
+}
+
+/// Another name for an operator or function
+#[derive(Clone, Debug)]
+struct Alias {
+    /// The name the alias stands for
+    target: String,
+    /// Whether uses of the alias should be reported
+    deprecated: bool,
+}
+
+/// The namespace enclosing a dotted name, e.g. `math` for `math.clamp`, or
+/// an empty string if it has none
+fn parent(name: &str) -> &str {
+    name.rfind('.').map_or("", |idx| &name[..idx])
+}
+
+/// The names of the functions called or referenced in a parsed expression
+fn calls(parsed: &Parsed) -> Vec<String> {
+    struct Calls(Vec<String>);
+    impl Calls {
+        fn references(&mut self, value: &Value) {
+            if let Some(name) = function_ref(value) {
+                return self.0.push(name.into());
+            }
+            match value {
+                Value::Array(items) => {
+                    items.iter().for_each(|item| self.references(item))
+                }
+                Value::Object(obj) => {
+                    obj.values().for_each(|item| self.references(item))
+                }
+                _ => {}
+            }
+        }
+    }
+    impl AstVisitor for Calls {
+        fn visit_literal(&mut self, value: &Value) {
+            self.references(value)
+        }
+
+        fn visit_function_call(&mut self, name: &str, args: &[Ast]) {
+            self.0.push(name.into());
+            args.iter().for_each(|arg| self.visit(arg))
+        }
+    }
+    let mut calls = Calls(Vec::new());
+    calls.visit(&Ast::from(parsed));
+    calls.0
+}
+
+/// Find all distinct call cycles in a call graph.
+///
+/// Each cycle is reported once, starting from its lexicographically smallest
+/// member, and ending with a repeat of that member.
+pub(crate) fn find_cycles(calls: &HashMap<&str, Vec<&str>>) -> Vec<Vec<String>> {
+    fn visit<'a>(
+        node: &'a str,
+        calls: &HashMap<&'a str, Vec<&'a str>>,
+        path: &mut Vec<&'a str>,
+        cycles: &mut Vec<Vec<String>>,
+    ) {
+        if let Some(idx) = path.iter().position(|n| *n == node) {
+            let cycle = &path[idx..];
+            // Only record a cycle when starting from its smallest member, so
+            // that each cycle is reported exactly once.
+            if cycle.iter().min() == Some(&node) {
+                let mut cycle: Vec<String> =
+                    cycle.iter().map(|n| n.to_string()).collect();
+                cycle.push(node.into());
+                if !cycles.contains(&cycle) {
+                    cycles.push(cycle);
+                }
+            }
+            return;
+        }
+        path.push(node);
+        calls
+            .get(node)
+            .into_iter()
+            .flatten()
+            .for_each(|callee| visit(callee, calls, path, cycles));
+        path.pop();
+    }
+
+    let mut names: Vec<&str> = calls.keys().copied().collect();
+    names.sort_unstable();
+    let mut cycles = Vec::new();
+    names
+        .into_iter()
+        .for_each(|name| visit(name, calls, &mut Vec::new(), &mut cycles));
+    cycles
+}
+
+/// Options for checking a namespace
+#[derive(Clone, Debug, Default)]
+pub struct CheckOptions {
+    /// Whether functions may call themselves, directly or indirectly.
+    pub allow_recursion: bool,
+}
+
+/// A problem found while checking a namespace
+#[derive(Clone, Debug, PartialEq)]
+pub enum Issue {
+    /// A function calls a function that is not defined.
+    UnresolvedFunction { function: String, name: String },
+    /// A function references a parameter it does not declare.
+    UnresolvedParameter { function: String, name: String },
+    /// A function calls an operator or function with the wrong number of
+    /// arguments.
+    ArityMismatch {
+        function: String,
+        name: String,
+        expected: NumParams,
+        actual: usize,
+    },
+    /// A set of functions call one another recursively. The cycle begins and
+    /// ends with the same function.
+    Recursion { cycle: Vec<String> },
+    /// A function or parameter name hides another definition.
+    Shadowing {
+        function: String,
+        name: String,
+        shadowed: Shadowed,
+    },
+}
+impl Issue {
+    /// A stable, machine-readable code identifying the kind of issue
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::UnresolvedFunction { .. } => "unresolved_function",
+            Self::UnresolvedParameter { .. } => "unresolved_parameter",
+            Self::ArityMismatch { .. } => "arity_mismatch",
+            Self::Recursion { .. } => "recursion",
+            Self::Shadowing { .. } => "shadowing",
+        }
+    }
+
+    /// The name of the function in which the issue was found.
+    pub fn function(&self) -> &str {
+        match self {
+            Self::UnresolvedFunction { function, .. } => function,
+            Self::UnresolvedParameter { function, .. } => function,
+            Self::ArityMismatch { function, .. } => function,
+            Self::Recursion { cycle } => {
+                cycle.first().map(String::as_str).unwrap_or("")
+            }
+            Self::Shadowing { function, .. } => function,
+        }
+    }
+}
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnresolvedFunction { function, name } => {
+                write!(f, "'{}' calls undefined function '{}'", function, name)
+            }
+            Self::UnresolvedParameter { function, name } => {
+                write!(
+                    f,
+                    "'{}' references undeclared parameter '{}'",
+                    function, name
+                )
+            }
+            Self::ArityMismatch {
+                function,
+                name,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "'{}' calls '{}' with {} argument(s), expected {:?}",
+                function, name, actual, expected
+            ),
+            Self::Recursion { cycle } => {
+                write!(f, "recursive call cycle: {}", cycle.join(" -> "))
+            }
+            Self::Shadowing {
+                function,
+                name,
+                shadowed: Shadowed::Operator,
+            } => write!(f, "'{}' shadows the built-in operator '{}'", function, name),
+            Self::Shadowing {
+                function,
+                name,
+                shadowed: Shadowed::Parameter,
+            } => write!(
+                f,
+                "'{}' declares parameter '{}' more than once",
+                function, name
+            ),
+        }
+    }
+}
+
+/// An example of a function whose result differs from what was expected
+#[derive(Debug)]
+pub struct ExampleFailure {
+    /// The name of the function
+    pub function: String,
+    /// The position of the example in the function's definition
+    pub index: usize,
+    /// The arguments the function was called with
+    pub arguments: Vec<Value>,
+    /// The result the example expected
+    pub expected: Value,
+    /// The result of the call
+    pub actual: Result<Value, Error>,
+}
+impl fmt::Display for ExampleFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "example {} of '{}' with arguments {}: expected {}, ",
+            self.index,
+            self.function,
+            Value::from(self.arguments.clone()),
+            self.expected
+        )?;
+        match &self.actual {
+            Ok(actual) => write!(f, "got {}", actual),
+            Err(e) => write!(f, "failed with error: {}", e),
+        }
+    }
+}
+
+/// The kind of definition hidden by a shadowing name
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shadowed {
+    /// A built-in operator
+    Operator,
+    /// An earlier parameter of the same function
+    Parameter,
+}
+
+#[cfg(test)]
+mod test_namespace {
+    use super::*;
+    use crate::rule::{IdentifierCharacters, IdentifierPolicy};
+    use crate::Context;
+    use serde_json::json;
+
+    fn namespace(defs: Vec<Value>) -> Namespace {
+        let mut ns = Namespace::new();
+        defs.iter().for_each(|def| ns.define(def).unwrap());
+        ns
+    }
+
+    fn issues(ns: &Namespace, options: &CheckOptions) -> Vec<Issue> {
+        match ns.check_with(options) {
+            Ok(()) => vec![],
+            Err(Error::InvalidNamespace(issues)) => issues,
+            Err(e) => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_define_non_function() {
+        Namespace::new().define(&json!({"+": [1, 2]})).unwrap_err();
+    }
+
+    #[test]
+    fn test_valid_namespace() {
+        let ns = namespace(vec![
+            json!({"defn": ["is_even", ["a"], {"===": [{"%": [{"param": "a"}, 2]}, 0]}]}),
+            json!({"defn": ["is_odd", ["a"], {"!": {"is_even": [{"param": "a"}]}}]}),
+        ]);
+        ns.check().unwrap();
+    }
+
+    #[test]
+    fn test_parse_calls() {
+        let ns = namespace(vec![
+            json!({"defn": ["is_even", ["a"], {"===": [{"%": [{"param": "a"}, 2]}, 0]}]}),
+            json!({"defn": ["is_odd", ["a"], {"!": {"is_even": [{"param": "a"}]}}]}),
+        ]);
+        let rule = ns
+            .parse(&json!({"filter": [{"var": "xs"}, {"is_odd": {"var": ""}}]}))
+            .unwrap();
+        assert_eq!(
+            rule.apply(&json!({"xs": [1, 2, 3]})).unwrap(),
+            json!([1, 3])
+        );
+        assert_eq!(
+            Value::from(rule),
+            json!({"filter": [{"var": ["xs"]}, {"is_odd": [{"var": [""]}]}]})
+        );
+        ns.parse(&json!({"is_odd": [1, 2]})).unwrap_err();
+        ns.parse(&json!({"param": "a"})).unwrap_err();
+    }
+
+    #[test]
+    fn test_parse_unknown_operator() {
+        let ns = namespace(vec![json!({"defn": ["is_even", ["a"], true]})]);
+        match ns.parse(&json!({"if": [{"is_eevn": [1]}, 1, 2]})) {
+            Err(Error::UnknownOperator { name, suggestions }) => {
+                assert_eq!(name, "is_eevn");
+                assert_eq!(suggestions, vec!["is_even".to_string()]);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match ns.parse(&json!({"mising": ["a"]})) {
+            Err(Error::UnknownOperator { suggestions, .. }) => {
+                assert_eq!(suggestions, vec!["missing".to_string()])
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        // Unknown operators in function bodies are errors too
+        let ns = namespace(vec![json!({"defn": ["f", [], {"sbustr": ["abc", 1]}]})]);
+        ns.parse(&json!({"f": []})).unwrap_err();
+        // Outside of a namespace, unknown operators are raw values
+        crate::Rule::from_value(&json!({"is_eevn": [1]})).unwrap();
+    }
+
+    #[test]
+    fn test_mutual_recursion() {
+        // `is_odd` is defined before the `is_even` it calls
+        let ns = namespace(vec![
+            json!({"defn": ["is_odd", ["n"], {"if": [
+                {"==": [{"param": "n"}, 0]},
+                false,
+                {"is_even": [{"-": [{"param": "n"}, 1]}]}
+            ]}]}),
+            json!({"defn": ["is_even", ["n"], {"if": [
+                {"==": [{"param": "n"}, 0]},
+                true,
+                {"is_odd": [{"-": [{"param": "n"}, 1]}]}
+            ]}]}),
+            json!({"defn": ["broken", [], {"is_evn": [1]}]}),
+        ]);
+        // Functions the rule doesn't call don't need to be valid
+        let rule = ns.parse(&json!({"is_odd": [{"var": "n"}]})).unwrap();
+        assert_eq!(rule.apply(&json!({"n": 7})).unwrap(), json!(true));
+        assert_eq!(
+            rule.compile().unwrap().apply(&json!({"n": 8})).unwrap(),
+            json!(false)
+        );
+        match ns.parse(&json!({"if": [true, 1, {"broken": []}]})) {
+            Err(e @ Error::UndefinedFunction { .. }) => {
+                assert_eq!(e.code(), "undefined_function");
+                assert_eq!(
+                    e.to_string(),
+                    "Undefined function 'is_evn' called by 'broken' - did you mean 'is_even'?"
+                );
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_function_references() {
+        let ns = namespace(vec![
+            json!({"defn": ["double", ["n"], {"*": [{"param": "n"}, 2]}]}),
+            json!({"defn": ["square", ["n"], {"*": [{"param": "n"}, {"param": "n"}]}]}),
+            json!({"defn": ["pick", ["big"], {"if": [
+                {"param": "big"},
+                {"fn": "square"},
+                {"fn": "double"}
+            ]}]}),
+        ]);
+        ns.check().unwrap();
+        let check = |rule: &Value, data: &Value, expected: Option<Value>| {
+            let rule = ns.parse(rule).unwrap();
+            let compiled = rule.compile().unwrap();
+            for result in [rule.apply(data), compiled.apply(data)].iter() {
+                assert_eq!(result.as_ref().ok(), expected.as_ref(), "{:?}", result);
+            }
+        };
+        let dispatch =
+            json!({"call": [{"pick": [{"var": "big"}]}, {"merge": [{"var": "x"}]}]});
+        check(&dispatch, &json!({"big": true, "x": 5}), Some(json!(25)));
+        check(&dispatch, &json!({"big": false, "x": 5}), Some(json!(10)));
+        check(
+            &json!({"map": [
+                [{"fn": "double"}, {"fn": "square"}],
+                {"call": [{"var": ""}, [3]]}
+            ]}),
+            &json!(null),
+            Some(json!([6, 9])),
+        );
+        // Wrong number of arguments
+        check(
+            &json!({"call": [{"fn": "double"}, [1, 2]]}),
+            &json!(null),
+            None,
+        );
+        // Not a function reference
+        check(&json!({"call": ["double", [1]]}), &json!(null), None);
+        // Functions the rule doesn't reference can't be called
+        check(
+            &json!({"call": [{"var": "f"}, [1]]}),
+            &json!({"f": {"fn": "square"}}),
+            None,
+        );
+
+        // Compositions apply the last function first
+        check(
+            &json!({"apply": [
+                {"comp": [{"fn": "double"}, {"fn": "square"}]},
+                {"merge": [{"var": "x"}]}
+            ]}),
+            &json!({"x": 3}),
+            Some(json!(18)),
+        );
+        check(
+            &json!({"call": [
+                {"comp": [{"comp": [{"fn": "square"}]}, {"fn": "double"}]},
+                [3]
+            ]}),
+            &json!(null),
+            Some(json!(36)),
+        );
+        check(
+            &json!({"comp": [{"fn": "double"}, "square"]}),
+            &json!(null),
+            None,
+        );
+
+        ns.parse(&json!({"fn": "triple"})).unwrap_err();
+        let ns = namespace(vec![json!({"defn": ["f", [], {"fn": "g"}]})]);
+        assert_eq!(
+            issues(&ns, &CheckOptions::default()),
+            vec![Issue::UnresolvedFunction {
+                function: "f".into(),
+                name: "g".into()
+            }]
+        );
+
+        // Partial application binds the leading arguments
+        let ns = namespace(vec![json!({"defn": [
+            "between",
+            ["low", "high", "x"],
+            {"<=": [{"param": "low"}, {"param": "x"}, {"param": "high"}]}
+        ]})]);
+        let check = |rule: &Value, data: &Value, expected: Option<Value>| {
+            let rule = ns.parse(rule).unwrap();
+            let compiled = rule.compile().unwrap();
+            for result in [rule.apply(data), compiled.apply(data)].iter() {
+                assert_eq!(result.as_ref().ok(), expected.as_ref(), "{:?}", result);
+            }
+        };
+        check(
+            &json!({"filter": [
+                {"var": "xs"},
+                {"call": [
+                    {"partial": [{"partial": [{"fn": "between"}, [2]]}, [4]]},
+                    {"merge": [{"var": ""}]}
+                ]}
+            ]}),
+            &json!({"xs": [1, 2, 3, 4, 5]}),
+            Some(json!([2, 3, 4])),
+        );
+        check(
+            &json!({"partial": [{"fn": "between"}, {"var": "bounds"}]}),
+            &json!({"bounds": [1, 2]}),
+            Some(json!({"closure": [{"fn": "between"}, [1, 2]]})),
+        );
+        check(
+            &json!({"call": [{"partial": [{"fn": "between"}, [1, 2]]}, [3, 4]]}),
+            &json!(null),
+            None,
+        );
+        check(
+            &json!({"partial": [{"fn": "between"}, 1]}),
+            &json!(null),
+            None,
+        );
+        check(&json!({"partial": ["between", [1]]}), &json!(null), None);
+    }
+
+    #[test]
+    fn test_default_and_rest_params() {
+        let ns = namespace(vec![
+            json!({"defn": [
+                "greet",
+                ["name", ["greeting", {"cat": [{"var": "salutation"}, ","]}], ["punct", "!"]],
+                {"cat": [{"param": "greeting"}, " ", {"param": "name"}, {"param": "punct"}]},
+                {"examples": [[["Bo", "Yo"], "Yo Bo!"], [["Bo", "Yo", "?"], "Yo Bo?"]]}
+            ]}),
+            json!({"defn": [
+                "total",
+                ["first", {"name": "second", "type": "number", "default": {"param": "first"}}, "...others"],
+                {"reduce": [
+                    {"param": "others"},
+                    {"+": [{"var": "accumulator"}, {"var": "current"}]},
+                    {"+": [{"param": "first"}, {"param": "second"}]}
+                ]}
+            ]}),
+            json!({"defn": ["count", ["...xs"], {"reduce": [
+                {"param": "xs"},
+                {"+": [{"var": "accumulator"}, 1]},
+                0
+            ]}]}),
+        ]);
+        ns.check().unwrap();
+        let data = json!({"salutation": "Hi"});
+        let check = |rule: &Value, expected: Option<Value>| {
+            let rule = ns.parse(rule).unwrap();
+            let compiled = rule.compile().unwrap();
+            for result in [rule.apply(&data), compiled.apply(&data)].iter() {
+                assert_eq!(result.as_ref().ok(), expected.as_ref(), "{:?}", result);
+            }
+        };
+        check(&json!({"greet": ["Al"]}), Some(json!("Hi, Al!")));
+        check(&json!({"greet": ["Al", "Hey"]}), Some(json!("Hey Al!")));
+        check(
+            &json!({"greet": ["Al", "Hey", "?"]}),
+            Some(json!("Hey Al?")),
+        );
+        check(&json!({"total": [2]}), Some(json!(4)));
+        check(&json!({"total": [2, 3, 4, 5]}), Some(json!(14)));
+        check(&json!({"count": []}), Some(json!(0)));
+        check(&json!({"count": [1, 2, 3]}), Some(json!(3)));
+        check(
+            &json!({"call": [{"fn": "greet"}, ["Al", "Hey"]]}),
+            Some(json!("Hey Al!")),
+        );
+        check(
+            &json!({"call": [{"fn": "total"}, [1, 1, 1]]}),
+            Some(json!(3)),
+        );
+        check(&json!({"call": [{"fn": "greet"}, []]}), None);
+        check(&json!({"call": [{"fn": "greet"}, [1, 2, 3, 4]]}), None);
+        let failures = ns.run_examples().unwrap();
+        assert!(failures.is_empty(), "{:?}", failures);
+
+        ns.parse(&json!({"greet": []})).unwrap_err();
+        ns.parse(&json!({"total": []})).unwrap_err();
+        // Defaults may only refer to the parameters before them
+        let ns = namespace(vec![json!({"defn": [
+            "f",
+            [["a", {"param": "b"}], ["b", 1]],
+            {"param": "a"}
+        ]})]);
+        ns.parse(&json!({"f": []})).unwrap_err();
+        let ns = namespace(vec![
+            json!({"defn": ["f", ["a", ["b", 1]], {"param": "a"}]}),
+            json!({"defn": ["g", [], {"f": []}]}),
+        ]);
+        assert_eq!(
+            issues(&ns, &CheckOptions::default()),
+            vec![Issue::ArityMismatch {
+                function: "g".into(),
+                name: "f".into(),
+                expected: NumParams::Variadic(1..3),
+                actual: 0,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_closures() {
+        let ns = namespace(vec![
+            json!({"defn": ["greater_than", ["threshold"], {"defn": [
+                "above",
+                ["x"],
+                {">": [{"param": "x"}, {"param": "threshold"}]}
+            ]}]}),
+            json!({"defn": ["adder", ["n"], {"defn": [
+                "add",
+                ["x", ["n", 0]],
+                {"+": [{"param": "x"}, {"param": "n"}]}
+            ]}]}),
+        ]);
+        ns.check().unwrap();
+        assert!(ns.get("greater_than/above").is_some());
+        let check = |rule: &Value, expected: Value| {
+            let rule = ns.parse(rule).unwrap();
+            let compiled = rule.compile().unwrap();
+            let data = json!({"xs": [1, 5, 10]});
+            assert_eq!(rule.apply(&data).unwrap(), expected);
+            assert_eq!(compiled.apply(&data).unwrap(), expected);
+        };
+        check(
+            &json!({"filter": [
+                {"var": "xs"},
+                {"call": [{"greater_than": [4]}, {"merge": [{"var": ""}]}]}
+            ]}),
+            json!([5, 10]),
+        );
+        check(
+            &json!({"greater_than": [3]}),
+            json!({"closure": [{"fn": "greater_than/above"}, [3]]}),
+        );
+        // Parameters the closure declares itself shadow those it would capture
+        check(&json!({"call": [{"adder": [5]}, [1]]}), json!(1));
+        check(&json!({"call": [{"adder": [5]}, [1, 2]]}), json!(3));
+        check(
+            &json!({"call": [
+                {"comp": [{"greater_than": [10]}, {"adder": [0]}]},
+                [4, 8]
+            ]}),
+            json!(true),
+        );
+    }
+
+    #[test]
+    fn test_unresolved_references() {
+        let ns = namespace(vec![json!({"defn": [
+            "foo",
+            ["a"],
+            {"+": [{"bar": [1]}, {"param": "b"}]}
+        ]})]);
+        assert_eq!(
+            issues(&ns, &CheckOptions::default()),
+            vec![
+                Issue::UnresolvedFunction {
+                    function: "foo".into(),
+                    name: "bar".into()
+                },
+                Issue::UnresolvedParameter {
+                    function: "foo".into(),
+                    name: "b".into()
+                },
+            ]
+        );
+        assert_eq!(
+            issues(&ns, &CheckOptions::default())[1].to_string(),
+            "'foo' references undeclared parameter 'b'"
+        );
+    }
+
+    #[test]
+    fn test_literals() {
+        // Quoted values aren't calls, and their functions aren't closures
+        let ns = namespace(vec![json!({"defn": ["wrap", ["a"], {"merge": [
+            {"lit": {"wrap": [{"param": "a"}]}},
+            {"lit": {"defn": ["inner", [], 1]}},
+            {"param": "a"}
+        ]}]})]);
+        assert_eq!(issues(&ns, &CheckOptions::default()), vec![]);
+        let rule = ns.parse(&json!({"wrap": [1]})).unwrap();
+        assert_eq!(
+            rule.apply(&json!(null)).unwrap(),
+            json!([{"wrap": [{"param": "a"}]}, {"defn": ["inner", [], 1]}, 1])
+        );
+        let definitions = Value::from(ns.definitions().unwrap().remove(0));
+        assert_eq!(
+            definitions["defn"][2]["merge"][0],
+            json!({"lit": {"wrap": [{"param": "a"}]}})
+        );
+    }
+
+    #[test]
+    fn test_match_patterns() {
+        // Patterns aren't references to operators or functions
+        let ns = namespace(vec![json!({"defn": ["area", ["s"], {"match": [
+            {"param": "s"},
+            {"shape": {"r": {"bind": "r"}}},
+            {"*": [3, {"var": "r"}, {"var": "r"}]},
+            {"nope": 1},
+            0,
+            -1
+        ]}]})]);
+        assert_eq!(issues(&ns, &CheckOptions::default()), vec![]);
+        let rule = ns.parse(&json!({"area": [{"var": ""}]})).unwrap();
+        assert_eq!(rule.apply(&json!({"r": 2})).unwrap(), json!(12));
+        assert_eq!(rule.apply(&json!({"nope": 1})).unwrap(), json!(0));
+        assert_eq!(rule.apply(&json!({})).unwrap(), json!(-1));
+    }
+
+    #[test]
+    fn test_switch_cases() {
+        // Case keys aren't references to operators or functions, but their
+        // branches are checked
+        let ns = namespace(vec![
+            json!({"defn": ["shout", ["s"], {"switch": [
+                {"param": "s"},
+                {"nope": {"upper": [{"param": "s"}]}},
+                {"param": "s"}
+            ]}]}),
+            json!({"defn": ["bad", ["s"], {"switch": [
+                {"param": "s"},
+                {"nope": {"param": "t"}}
+            ]}]}),
+        ]);
+        assert_eq!(
+            issues(&ns, &CheckOptions::default()),
+            vec![Issue::UnresolvedParameter {
+                function: "bad".into(),
+                name: "t".into(),
+            }]
+        );
+        let rule = ns.parse(&json!({"shout": [{"var": ""}]})).unwrap();
+        assert_eq!(rule.apply(&json!("nope")).unwrap(), json!("NOPE"));
+        assert_eq!(rule.apply(&json!("yes")).unwrap(), json!("yes"));
+    }
+
+    #[test]
+    fn test_module_document() {
+        let mut ns = namespace(vec![
+            json!({"defmemo": [
+                "clamp",
+                [{"name": "x", "type": "number"}, ["lo", 0], ["hi", 1]],
+                {"min": [{"max": [{"param": "x"}, {"param": "lo"}]}, {"param": "hi"}]},
+                {"pre": {"<=": [{"param": "lo"}, {"param": "hi"}]}, "examples": [[[5], 1]]}
+            ]}),
+            json!({"defn": ["sum", ["...xs"], {"reduce": [
+                {"param": "xs"},
+                {"+": [{"var": "current"}, {"var": "accumulator"}]},
+                0
+            ]}]}),
+            json!({"defn": ["greater_than", ["threshold"], {"defn": [
+                "above",
+                ["x"],
+                {">": [{"param": "x"}, {"param": "threshold"}]}
+            ]}]}),
+        ]);
+        ns.mount(
+            "math",
+            namespace(vec![
+                json!({"defn": ["double", ["x"], {"*": [{"param": "x"}, 2]}]}),
+            ]),
+        )
+        .unwrap();
+        let module = ns.to_value();
+        let names: Vec<&Value> = module["functions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|def| {
+                def.as_object()
+                    .unwrap()
+                    .values()
+                    .next()
+                    .unwrap()
+                    .get(0)
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "clamp",
+                "greater_than",
+                "greater_than/above",
+                "math.double",
+                "sum"
+            ]
+        );
+
+        let loaded = Namespace::from_value(&module).unwrap();
+        assert_eq!(loaded.to_value(), module);
+        loaded.check().unwrap();
+        assert!(loaded.run_examples().unwrap().is_empty());
+        let rule = loaded
+            .parse(&json!({"filter": [
+                {"merge": [1, 5, {"math.double": [{"clamp": [3, 0, 4]}]}]},
+                {"call": [{"greater_than": [{"sum": [1, 2]}]}, {"merge": [{"var": ""}]}]}
+            ]}))
+            .unwrap();
+        assert_eq!(rule.apply(&json!(null)).unwrap(), json!([5, 6]));
+
+        Namespace::from_value(&json!({"functions": {}})).unwrap_err();
+        Namespace::from_value(&json!({"functions": [{"+": [1, 2]}]})).unwrap_err();
+    }
+
+    #[test]
+    fn test_mount() {
+        let stats = namespace(vec![
+            json!({"defn": ["sum", ["xs"], {"reduce": [
+                {"param": "xs"},
+                {"+": [{"var": "current"}, {"var": "accumulator"}]},
+                0
+            ]}]}),
+            json!({"defn": ["mean", ["xs"], {"/": [
+                {"sum": [{"param": "xs"}]},
+                {"reduce": [{"param": "xs"}, {"+": [{"var": "accumulator"}, 1]}, 0]}
+            ]}]}),
+        ]);
+        let mut math = namespace(vec![
+            json!({"defn": ["clamp", ["x", "lo", "hi"], {"min": [
+                {"max": [{"param": "x"}, {"param": "lo"}]},
+                {"param": "hi"}
+            ]}]}),
+            // Resolves to `math.clamp`, rather than the root's `clamp`
+            json!({"defn": ["unit", ["x"], {"clamp": [{"param": "x"}, 0, 1]}]}),
+            json!({"defn": ["scaler", ["n"], {"defn": [
+                "scale",
+                ["x"],
+                {"*": [{"param": "x"}, {"param": "n"}]}
+            ]}]}),
+        ]);
+        math.mount("stats", stats).unwrap();
+        let mut ns = namespace(vec![
+            json!({"defn": ["clamp", ["x"], "root"]}),
+            json!({"defn": ["sum", ["xs"], "root"]}),
+        ]);
+        ns.mount("math", math).unwrap();
+        ns.check().unwrap();
+        assert!(ns.get("math.stats.mean").is_some());
+        assert!(ns.get("math.scaler/scale").is_some());
+        assert_eq!(
+            ns.resolve("sum", Some("math.stats.mean"))
+                .unwrap()
+                .name()
+                .value(),
+            "math.stats.sum"
+        );
+        assert_eq!(
+            ns.resolve("clamp", Some("math.stats.mean"))
+                .unwrap()
+                .name()
+                .value(),
+            "math.clamp"
+        );
+        assert_eq!(ns.resolve("clamp", None).unwrap().name().value(), "clamp");
+        assert!(ns.resolve("mean", Some("math.unit")).is_none());
+
+        let check = |rule: Value, expected: Value| {
+            let rule = ns.parse(&rule).unwrap();
+            let compiled = rule.compile().unwrap();
+            assert_eq!(rule.apply(&json!(null)).unwrap(), expected);
+            assert_eq!(compiled.apply(&json!(null)).unwrap(), expected);
+        };
+        check(json!({"math.stats.mean": [[1, 2, 6]]}), json!(3));
+        check(json!({"math.unit": [5]}), json!(1));
+        check(json!({"clamp": [5]}), json!("root"));
+        check(
+            json!({"map": [[1, 2], {"call": [{"math.scaler": [3]}, {"merge": [{"var": ""}]}]}]}),
+            json!([3, 6]),
+        );
+        check(
+            json!({"call": [{"fn": "math.stats.sum"}, [[1, 2]]]}),
+            json!(3),
+        );
+
+        Namespace::new().mount("", Namespace::new()).unwrap_err();
+        let unresolved = namespace(vec![json!({"defn": ["f", [], {"g": []}]})]);
+        let mut ns = Namespace::new();
+        ns.mount("a", unresolved).unwrap();
+        assert_eq!(
+            issues(&ns, &CheckOptions::default()),
+            vec![Issue::UnresolvedFunction {
+                function: "a.f".into(),
+                name: "g".into()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_arity_mismatch() {
+        let ns = namespace(vec![
+            json!({"defn": ["double", ["a"], {"*": [{"param": "a"}, 2]}]}),
+            json!({"defn": ["quad", ["a"], {"double": [{"double": [1, 2]}]}]}),
+            json!({"defn": ["neg", ["a"], {"==": [{"param": "a"}]}]}),
+        ]);
+        assert_eq!(
+            issues(&ns, &CheckOptions::default()),
+            vec![
+                Issue::ArityMismatch {
+                    function: "neg".into(),
+                    name: "==".into(),
+                    expected: NumParams::Exactly(2),
+                    actual: 1,
+                },
+                Issue::ArityMismatch {
+                    function: "quad".into(),
+                    name: "double".into(),
+                    expected: NumParams::Exactly(1),
+                    actual: 2,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_recursion() {
+        let ns = namespace(vec![
+            json!({"defn": ["ping", ["a"], {"pong": [{"param": "a"}]}]}),
+            json!({"defn": ["pong", ["a"], {"ping": [{"param": "a"}]}]}),
+            json!({"defn": ["loop", ["a"], {"loop": [{"param": "a"}]}]}),
+        ]);
+        assert_eq!(
+            issues(&ns, &CheckOptions::default()),
+            vec![
+                Issue::Recursion {
+                    cycle: vec!["loop".into(), "loop".into()]
+                },
+                Issue::Recursion {
+                    cycle: vec!["ping".into(), "pong".into(), "ping".into()]
+                },
+            ]
+        );
+        ns.check_with(&CheckOptions {
+            allow_recursion: true,
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_contracts() {
+        let ns = namespace(vec![json!({"defn": [
+            "dec",
+            ["x"],
+            {"-": [{"param": "x"}, 1]},
+            {
+                "pre": {">": [{"param": "x"}, 0]},
+                "post": {">": [{"var": ""}, 0]}
+            }
+        ]})]);
+        ns.check().unwrap();
+        let rule = ns.parse(&json!({"dec": [{"var": "x"}]})).unwrap();
+        let compiled = rule.compile().unwrap();
+        assert_eq!(rule.apply(&json!({"x": 3})).unwrap(), json!(2));
+        assert_eq!(compiled.apply(&json!({"x": 3})).unwrap(), json!(2));
+        for result in [
+            rule.apply(&json!({"x": -4})),
+            compiled.apply(&json!({"x": -4})),
+        ]
+        .iter()
+        {
+            match result {
+                Err(e @ Error::ContractViolation { .. }) => {
+                    assert_eq!(e.code(), "contract_violation");
+                    assert_eq!(
+                        e.to_string(),
+                        "Contract violation - precondition of 'dec' failed for arguments [-4]"
+                    );
+                }
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+        match rule.apply(&json!({"x": 1})) {
+            Err(Error::ContractViolation {
+                condition, result, ..
+            }) => {
+                assert_eq!(condition, "post");
+                assert_eq!(result, Some(json!(0)));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        let ctx = Context {
+            skip_contracts: true,
+            ..Context::default()
+        };
+        assert_eq!(rule.apply_with(&json!({"x": 1}), &ctx).unwrap(), json!(0));
+        assert_eq!(
+            compiled.apply_with(&json!({"x": 1}), &ctx).unwrap(),
+            json!(0)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_memoization() {
+        let ns = namespace(vec![json!({"defmemo": ["fib", ["n"], {"if": [
+            {"<": [{"param": "n"}, 2]},
+            {"param": "n"},
+            {"+": [
+                {"fib": [{"-": [{"param": "n"}, 1]}]},
+                {"fib": [{"-": [{"param": "n"}, 2]}]}
+            ]}
+        ]}]})]);
+        assert!(ns.get("fib").unwrap().memoized());
+        let rule = ns.parse(&json!({"fib": [{"var": "n"}]})).unwrap();
+        let compiled = rule.compile().unwrap();
+        let additions = |ctx: &Context, compile: bool| {
+            let profiler = Arc::new(crate::Profiler::new());
+            let ctx = Context {
+                profiler: Some(profiler.clone()),
+                ..ctx.clone()
+            };
+            let result = if compile {
+                compiled.apply_with(&json!({"n": 20}), &ctx)
+            } else {
+                rule.apply_with(&json!({"n": 20}), &ctx)
+            };
+            assert_eq!(result.unwrap(), json!(6765));
+            profiler
+                .stats()
+                .into_iter()
+                .find(|stats| stats.operator == "+")
+                .unwrap()
+                .calls
+        };
+        let ctx = Context::default();
+        assert_eq!(additions(&ctx, false), 19);
+        assert_eq!(additions(&ctx, true), 19);
+        // Each evaluation has its own cache
+        assert_eq!(additions(&ctx, false), 19);
+        // Without room in the cache, every call is evaluated
+        let ctx = Context {
+            memo_cache_size: Some(0),
+            ..Context::default()
+        };
+        assert_eq!(additions(&ctx, false), 10945);
+        assert_eq!(additions(&ctx, true), 10945);
+    }
+
+    #[test]
+    fn test_memoization_without_data() {
+        let mut ns = Namespace::new();
+        for body in [
+            json!({"+": [{"param": "x"}, {"var": "y"}]}),
+            json!({"missing": ["y"]}),
+            json!({"map": [{"param": "x"}, {"var": "../y"}]}),
+            json!({"defn": ["inner", [], {"var": "y"}]}),
+        ] {
+            let err = ns
+                .define(&json!({"defmemo": ["f", ["x"], body]}))
+                .unwrap_err();
+            assert_eq!(err.code(), "invalid_argument");
+        }
+
+        // Elements of arrays the function iterates over may be read
+        ns.define(&json!({"defmemo": ["total", ["xs"], {"reduce": [
+            {"map": [{"param": "xs"}, {"var": "n"}]},
+            {"+": [{"var": "current"}, {"var": "accumulator"}]},
+            0
+        ]}]}))
+        .unwrap();
+        let rule = ns.parse(&json!({"total": [[{"n": 1}, {"n": 2}]]})).unwrap();
+        assert_eq!(rule.apply(&json!({})).unwrap(), json!(3));
+
+        // Functions called by a memoized function don't see the data either
+        ns.define(&json!({"defn": ["y", [], {"var": ["y", 0]}]}))
+            .unwrap();
+        ns.define(
+            &json!({"defmemo": ["plus_y", ["x"], {"+": [{"param": "x"}, {"y": []}]}]}),
+        )
+        .unwrap();
+        let rule = ns
+            .parse(&json!({"map": [{"var": "ys"}, {"plus_y": [10]}]}))
+            .unwrap();
+        let data = json!({"ys": [{"y": 1}, {"y": 2}]});
+        assert_eq!(rule.apply(&data).unwrap(), json!([10, 10]));
+        assert_eq!(
+            rule.compile().unwrap().apply(&data).unwrap(),
+            json!([10, 10])
+        );
+    }
+
+    #[test]
+    fn test_run_examples() {
+        let ns = namespace(vec![
+            json!({"defn": [
+                "addition",
+                ["a", "b"],
+                {"+": [{"param": "a"}, {"param": "b"}]},
+                {"examples": [[[1, 2], 3], [[2, 2], 5]]}
+            ]}),
+            json!({"defn": [
+                "append",
+                ["xs", "x"],
+                {"merge": [{"param": "xs"}, {"param": "x"}]},
+                {"examples": [[[[{"a": 1}], 2], [{"a": 1}, 2]], [[[], 1], []]]}
+            ]}),
+            json!({"defn": [
+                "dec",
+                ["n"],
+                {"-": [{"param": "n"}, 1]},
+                {"pre": {">": [{"param": "n"}, 0]}, "examples": [[[0], -1]]}
+            ]}),
+        ]);
+        let failures = ns.run_examples().unwrap();
+        assert_eq!(failures.len(), 3, "{:?}", failures);
+        assert_eq!(
+            failures[0].to_string(),
+            "example 1 of 'addition' with arguments [2,2]: expected 5, got 4"
+        );
+        assert_eq!(
+            failures[1].to_string(),
+            "example 1 of 'append' with arguments [[],1]: expected [], got [1]"
+        );
+        assert_eq!(failures[2].function, "dec");
+        assert!(matches!(
+            failures[2].actual,
+            Err(Error::ContractViolation { .. })
+        ));
+        Namespace::new().run_examples().unwrap();
+    }
+
+    #[test]
+    fn test_identifier_policy() {
+        let define = |ns: &mut Namespace, def: Value| match ns.define(&def) {
+            Ok(()) => None,
+            Err(e) => Some(e.to_string()),
+        };
+
+        // Built-in names are rejected by default, but closures can't shadow
+        let mut ns = Namespace::new();
+        assert_eq!(
+            define(&mut ns, json!({"defn": ["max", ["a"], {"param": "a"}]})),
+            Some(
+                "Identifier 'max' is not allowed: it is the name of a built-in operator"
+                    .into()
+            )
+        );
+        assert_eq!(
+            define(
+                &mut ns,
+                json!({"defn": ["f", [], {"defn": ["max", ["a"], {"param": "a"}]}]})
+            ),
+            None
+        );
+
+        ns.set_parse_options(ParseOptions {
+            identifiers: IdentifierPolicy {
+                min_length: 2,
+                characters: IdentifierCharacters::Word,
+                reserved: vec!["let".into()],
+                allow_builtin_names: true,
+            },
+            ..ParseOptions::default()
+        });
+        assert_eq!(
+            define(&mut ns, json!({"defn": ["max", ["ab"], {"param": "ab"}]})),
+            None
+        );
+        assert_eq!(
+            define(&mut ns, json!({"defn": ["g", ["ab"], {"param": "ab"}]})),
+            Some("Identifier 'g' is not allowed: identifiers must have at least 2 characters".into())
+        );
+        assert_eq!(
+            define(&mut ns, json!({"defn": ["gg", ["a-b"], {"param": "a-b"}]})),
+            Some(
+                "Identifier 'a-b' is not allowed: '-' is not allowed in identifiers"
+                    .into()
+            )
+        );
+        assert_eq!(
+            define(
+                &mut ns,
+                json!({"defn": ["gg", ["ab"], {"defn": ["let", [], 1]}]})
+            ),
+            Some("Identifier 'let' is not allowed: it is a reserved word".into())
+        );
+        let err = ns.define(&json!({"defn": ["let", [], 1]})).unwrap_err();
+        assert_eq!(err.code(), "disallowed_identifier");
+        assert!(ns.get("gg").is_none());
+    }
+
+    #[test]
+    fn test_unicode_names() {
+        // Defined with composed characters, and used with decomposed ones
+        let mut ns = namespace(vec![json!({"defn": [
+            "d\u{e9}j\u{e0}",
+            ["\u{e9}t\u{e9}"],
+            {"+": [{"param": "e\u{301}te\u{301}"}, 1]}
+        ]})]);
+        ns.check().unwrap();
+        assert!(ns.get("de\u{301}ja\u{300}").is_some());
+        let rule = ns
+            .parse(&json!({"de\u{301}ja\u{300}": [{"var": "x"}]}))
+            .unwrap();
+        assert_eq!(rule.apply(&json!({"x": 1})).unwrap(), json!(2));
+        let rule = ns
+            .parse(&json!({"call": [{"fn": "de\u{301}ja\u{300}"}, [1]]}))
+            .unwrap();
+        assert_eq!(rule.apply(&json!({})).unwrap(), json!(2));
+
+        // Perceived characters are counted and checked as a whole
+        ns.set_parse_options(ParseOptions {
+            identifiers: IdentifierPolicy {
+                min_length: 2,
+                characters: IdentifierCharacters::Word,
+                ..IdentifierPolicy::default()
+            },
+            ..ParseOptions::default()
+        });
+        ns.define(&json!({"defn": ["q\u{303}x", ["y\u{308}\u{301}z"], 1]}))
+            .unwrap();
+        assert_eq!(
+            ns.define(&json!({"defn": ["q\u{303}", [], 1]}))
+                .unwrap_err()
+                .code(),
+            "disallowed_identifier"
+        );
+    }
+
+    #[test]
+    fn test_shadowing() {
+        let mut ns = Namespace::new();
+        ns.set_parse_options(ParseOptions {
+            identifiers: IdentifierPolicy {
+                allow_builtin_names: true,
+                ..IdentifierPolicy::default()
+            },
+            ..ParseOptions::default()
+        });
+        ns.define(&json!({"defn": ["max", ["a", "b"], {"param": "a"}]}))
+            .unwrap();
+        ns.define(&json!({"defn": ["first", ["a", "a"], {"param": "a"}]}))
+            .unwrap();
+        assert_eq!(
+            issues(&ns, &CheckOptions::default()),
+            vec![
+                Issue::Shadowing {
+                    function: "first".into(),
+                    name: "a".into(),
+                    shadowed: Shadowed::Parameter,
+                },
+                Issue::Shadowing {
+                    function: "max".into(),
+                    name: "max".into(),
+                    shadowed: Shadowed::Operator,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! Numeric Backends
+//!
+//! All arithmetic and numeric comparisons performed by operators are routed
+//! through a `NumericBackend`. The default backend, `JsNumeric`, follows the
+//! JavaScript semantics of the reference implementation over serde_json
+//! numbers. Other backends (e.g. decimal or big integer arithmetic) may be
+//! used by setting `Context::numeric`, overriding only the operations whose
+//! behavior they need to change.
+
+use core::fmt;
+
+use serde_json::Value;
+
+use crate::error::Error;
+use crate::js_op;
+use crate::op::arithmetic;
+use crate::value::to_number_value;
+
+/// The implementation of numeric operations used by operators
+///
+/// Every method has a default implementation matching `JsNumeric`, so a
+/// backend need only override the operations it wants to change.
+pub trait NumericBackend: fmt::Debug + Send + Sync {
+    /// Sum any number of values (`+`)
+    fn plus(&self, items: &[&Value]) -> Result<Value, Error> {
+        js_op::parse_float_add(items).and_then(to_number_value)
+    }
+
+    /// Multiply one or more values (`*`)
+    fn times(&self, items: &[&Value]) -> Result<Value, Error> {
+        js_op::parse_float_mul(items).and_then(to_number_value)
+    }
+
+    /// Subtract the second value from the first (binary `-`)
+    fn minus(&self, first: &Value, second: &Value) -> Result<Value, Error> {
+        js_op::abstract_minus(first, second).and_then(to_number_value)
+    }
+
+    /// Negate a value (unary `-`)
+    fn negate(&self, value: &Value) -> Result<Value, Error> {
+        js_op::to_negative(value).and_then(to_number_value)
+    }
+
+    /// Divide the first value by the second (`/`)
+    fn divide(&self, first: &Value, second: &Value) -> Result<Value, Error> {
+        js_op::abstract_div(first, second).and_then(to_number_value)
+    }
+
+    /// Get the remainder of dividing the first value by the second (`%`)
+    fn remainder(&self, first: &Value, second: &Value) -> Result<Value, Error> {
+        js_op::abstract_mod(first, second).and_then(to_number_value)
+    }
+
+    /// Get the largest of one or more values (`max`)
+    fn max(&self, items: &[&Value]) -> Result<Value, Error> {
+        js_op::abstract_max(items).and_then(to_number_value)
+    }
+
+    /// Get the smallest of one or more values (`min`)
+    fn min(&self, items: &[&Value]) -> Result<Value, Error> {
+        js_op::abstract_min(items).and_then(to_number_value)
+    }
+
+    /// Add two numbers without any type conversion (`add`)
+    fn add(&self, first: &Value, second: &Value) -> Result<Value, Error> {
+        arithmetic::checked_add(first, second)
+    }
+
+    /// Whether the first value is less than the second (`<`)
+    fn lt(&self, first: &Value, second: &Value) -> bool {
+        js_op::abstract_lt(first, second)
+    }
+
+    /// Whether the first value is less than or equal to the second (`<=`)
+    fn lte(&self, first: &Value, second: &Value) -> bool {
+        js_op::abstract_lte(first, second)
+    }
+
+    /// Whether the first value is greater than the second (`>`)
+    fn gt(&self, first: &Value, second: &Value) -> bool {
+        js_op::abstract_gt(first, second)
+    }
+
+    /// Whether the first value is greater than or equal to the second (`>=`)
+    fn gte(&self, first: &Value, second: &Value) -> bool {
+        js_op::abstract_gte(first, second)
+    }
+}
+
+/// The default numeric backend
+///
+/// Values are converted to numbers as in JavaScript, arithmetic is performed
+/// on 64-bit floats, and integral results are returned as integers. The
+/// typesafe `add` operator preserves integers where it can.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JsNumeric;
+impl NumericBackend for JsNumeric {}
+
+#[cfg(test)]
+mod test_numeric {
+    use super::*;
+    use serde_json::json;
+
+    use crate::{Context, Rule};
+    use alloc::sync::Arc;
+
+    /// A backend that refuses to work with anything but integers
+    #[derive(Debug)]
+    struct IntegersOnly;
+    impl NumericBackend for IntegersOnly {
+        fn plus(&self, items: &[&Value]) -> Result<Value, Error> {
+            items
+                .iter()
+                .try_fold(0i64, |total, item| {
+                    item.as_i64()
+                        .and_then(|i| total.checked_add(i))
+                        .ok_or_else(|| {
+                            Error::invalid_argument(
+                                (*item).clone(),
+                                "+",
+                                "not an integer",
+                            )
+                        })
+                })
+                .map(Value::from)
+        }
+    }
+
+    #[test]
+    fn test_custom_backend() {
+        let rule = Rule::from_value(&json!({"+": [1, {"var": "a"}]})).unwrap();
+        let ctx = Context {
+            numeric: Some(Arc::new(IntegersOnly)),
+            ..Context::default()
+        };
+        assert_eq!(rule.apply(&json!({"a": "2"})).unwrap(), json!(3));
+        rule.apply_with(&json!({"a": "2"}), &ctx).unwrap_err();
+        assert_eq!(rule.apply_with(&json!({"a": 2}), &ctx).unwrap(), json!(3));
+
+        // Operations the backend doesn't override use the defaults
+        let rule = Rule::from_value(&json!({"*": [2, "3"]})).unwrap();
+        assert_eq!(rule.apply_with(&json!(null), &ctx).unwrap(), json!(6));
+    }
+}
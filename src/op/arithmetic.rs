@@ -2,9 +2,12 @@
 
 use serde_json::{Number, Value};
 
+use crate::context::Env;
 use crate::error::Error;
-use crate::js_op;
-use crate::value::to_number_value;
+use crate::op::string::string_arg;
+use crate::prelude::*;
+use crate::units::Units;
+use crate::value::{to_number_value, Evaluated};
 
 enum JsonNumber {
     U64(u64),
@@ -116,55 +119,111 @@ impl CheckedAdd for Number {
     }
 }
 
-fn compare<F>(func: F, items: &Vec<&Value>) -> Result<Value, Error>
+/// The values of the evaluated arguments to an operator
+fn values<'a>(items: &'a [Evaluated]) -> Vec<&'a Value> {
+    items.iter().map(|item| &**item).collect()
+}
+
+fn compare<F>(func: F, items: &[Evaluated]) -> Result<Value, Error>
 where
     F: Fn(&Value, &Value) -> bool,
 {
     if items.len() == 2 {
-        Ok(Value::Bool(func(items[0], items[1])))
+        Ok(Value::Bool(func(&items[0], &items[1])))
     } else {
         Ok(Value::Bool(
-            func(items[0], items[1]) && func(items[1], items[2]),
+            func(&items[0], &items[1]) && func(&items[1], &items[2]),
         ))
     }
 }
 
 /// Do < for either 2 or 3 values
-pub fn lt(items: &Vec<&Value>) -> Result<Value, Error> {
-    compare(js_op::abstract_lt, items)
+pub fn lt(items: &[Evaluated], env: &Env) -> Result<Value, Error> {
+    compare(|a, b| env.numeric().lt(a, b), items)
 }
 
 /// Do <= for either 2 or 3 values
-pub fn lte(items: &Vec<&Value>) -> Result<Value, Error> {
-    compare(js_op::abstract_lte, items)
+pub fn lte(items: &[Evaluated], env: &Env) -> Result<Value, Error> {
+    compare(|a, b| env.numeric().lte(a, b), items)
 }
 
 /// Do > for either 2 or 3 values
-pub fn gt(items: &Vec<&Value>) -> Result<Value, Error> {
-    compare(js_op::abstract_gt, items)
+pub fn gt(items: &[Evaluated], env: &Env) -> Result<Value, Error> {
+    compare(|a, b| env.numeric().gt(a, b), items)
 }
 
 /// Do >= for either 2 or 3 values
-pub fn gte(items: &Vec<&Value>) -> Result<Value, Error> {
-    compare(js_op::abstract_gte, items)
+pub fn gte(items: &[Evaluated], env: &Env) -> Result<Value, Error> {
+    compare(|a, b| env.numeric().gte(a, b), items)
+}
+
+/// Sum any number of values
+pub fn plus(items: &[Evaluated], env: &Env) -> Result<Value, Error> {
+    env.numeric().plus(&values(items))
+}
+
+/// Multiply one or more values
+pub fn times(items: &[Evaluated], env: &Env) -> Result<Value, Error> {
+    env.numeric().times(&values(items))
 }
 
 /// Perform subtraction or convert a number to a negative
-pub fn minus(items: &Vec<&Value>) -> Result<Value, Error> {
-    let value = if items.len() == 1 {
-        js_op::to_negative(items[0])?
+pub fn minus(items: &[Evaluated], env: &Env) -> Result<Value, Error> {
+    if items.len() == 1 {
+        env.numeric().negate(&items[0])
     } else {
-        js_op::abstract_minus(items[0], items[1])?
+        env.numeric().minus(&items[0], &items[1])
+    }
+}
+
+/// Divide two values
+pub fn divide(items: &[Evaluated], env: &Env) -> Result<Value, Error> {
+    env.numeric().divide(&items[0], &items[1])
+}
+
+/// Get the remainder of dividing two values
+pub fn remainder(items: &[Evaluated], env: &Env) -> Result<Value, Error> {
+    env.numeric().remainder(&items[0], &items[1])
+}
+
+/// Get the largest of one or more values
+pub fn max(items: &[Evaluated], env: &Env) -> Result<Value, Error> {
+    env.numeric().max(&values(items))
+}
+
+/// Get the smallest of one or more values
+pub fn min(items: &[Evaluated], env: &Env) -> Result<Value, Error> {
+    env.numeric().min(&values(items))
+}
+
+/// Perform typesafe addition on two numbers
+pub fn add(items: &[Evaluated], env: &Env) -> Result<Value, Error> {
+    env.numeric().add(&items[0], &items[1])
+}
+
+/// Convert a number from one unit of measure to another
+pub fn convert(items: &[Evaluated], env: &Env) -> Result<Value, Error> {
+    let value = items[0].as_f64().ok_or_else(|| {
+        Error::invalid_argument(
+            items[0].clone(),
+            "convert",
+            "Only numbers may be converted",
+        )
+    })?;
+    let from = string_arg(items, 1, "convert")?;
+    let to = string_arg(items, 2, "convert")?;
+    let converted = match env.units() {
+        Some(units) => units.convert(value, from, to)?,
+        None => Units::default().convert(value, from, to)?,
     };
-    to_number_value(value)
+    to_number_value(converted)
 }
 
 /// Perform addition on two numbers.
 ///
 /// This is a non-JS-compliant operation, which is to say it does no implicit
 /// type conversion. The only acceptable arguments are numbers.
-pub fn add(items: &Vec<&Value>) -> Result<Value, Error> {
-    let (first, second) = (items[0], items[1]);
+pub(crate) fn checked_add(first: &Value, second: &Value) -> Result<Value, Error> {
     match (first, second) {
         (Value::Number(first), Value::Number(second)) => first
             .checked_add(second)
@@ -227,18 +286,21 @@ pub(crate) mod test_arithmetic {
             TestCase::ok(json!([1.0, 1]), json!(2.0)),
             // we can handle things over the maximum i64
             TestCase::ok(
-                json!([std::i64::MAX as u64, 1]),
-                json!(std::i64::MAX as u64 + 1),
+                json!([core::i64::MAX as u64, 1]),
+                json!(core::i64::MAX as u64 + 1),
             ),
             // going below the minimum i64 transitions us to floats
             TestCase::ok(
-                json!([std::i64::MIN, -1]),
-                json!(std::i64::MIN as f64 - 1.0),
+                json!([core::i64::MIN, -1]),
+                json!(core::i64::MIN as f64 - 1.0),
             ),
             // going over the maximum u64 transitions us to floats
-            TestCase::ok(json!([std::u64::MAX, 1]), json!(std::u64::MAX as f64 + 1.0)),
+            TestCase::ok(
+                json!([core::u64::MAX, 1]),
+                json!(core::u64::MAX as f64 + 1.0),
+            ),
             // float overflow is an error
-            TestCase::err(json!([std::f64::MAX, std::f64::MAX])),
+            TestCase::err(json!([core::f64::MAX, core::f64::MAX])),
             // Passing non-numbers is an error
             TestCase::err(json!([12, "foo"])),
             TestCase::err(json!(["bar", "foo"])),
@@ -254,7 +316,7 @@ pub(crate) mod test_arithmetic {
                 Value::Array(vals) => vals,
                 _ => panic!("Invalid case"),
             };
-            let res = add(&items.iter().collect());
+            let res = checked_add(&items[0], &items[1]);
 
             match case.exp_err {
                 true => {
@@ -3,18 +3,28 @@
 //! Note that some array operations also operate on strings as arrays
 //! of characters.
 
+use core::cell::RefCell;
+use core::cmp::Ordering;
+
 use serde_json::{Map, Value};
 
+use crate::context::Env;
 use crate::error::Error;
+use crate::namespace::Scope;
 use crate::op::logic;
-use crate::value::{Evaluated, Parsed};
+use crate::prelude::*;
+use crate::value::{to_number_value, Evaluated, Parsed};
 
 /// Map an operation onto values
-pub fn map(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
-    let (items, expression) = (args[0], args[1]);
+///
+/// Within the expression, as in those of the other iterating operators,
+/// `{"var": "#index"}` is the index of the current element, and variables
+/// starting with `../` refer to the data `map` was evaluated against, e.g.
+/// `{"var": "../threshold"}`.
+pub fn map(data: &Value, args: &[Parsed], env: &Env) -> Result<Value, Error> {
+    let (items, expression) = (&args[0], &args[1]);
 
-    let _parsed = Parsed::from_value(items)?;
-    let evaluated_items = _parsed.evaluate(data)?;
+    let evaluated_items = items.evaluate(data, env)?;
 
     let values: Vec<&Value> = match evaluated_items {
         Evaluated::New(Value::Array(ref vals)) => vals.iter().collect(),
@@ -24,32 +34,28 @@ pub fn map(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
         Evaluated::New(Value::Null) => vec![],
         Evaluated::Raw(Value::Null) => vec![],
         _ => {
-            return Err(Error::InvalidArgument {
-                value: args[0].clone(),
-                operation: "map",
-                reason: format!(
-                    "First argument to map must evaluate to an array. Got {:?}",
-                    evaluated_items
-                ),
-            })
+            return Err(invalid_items(
+                "map",
+                args[0].clone().into(),
+                &evaluated_items,
+            ))
         }
     };
 
-    let parsed_expression = Parsed::from_value(expression)?;
-
+    let env = env.within(data);
     values
         .iter()
-        .map(|v| parsed_expression.evaluate(v).map(Value::from))
+        .enumerate()
+        .map(|(idx, v)| expression.evaluate(v, &env.at_index(idx)).map(Value::from))
         .collect::<Result<Vec<Value>, Error>>()
         .map(Value::Array)
 }
 
 /// Filter values by some predicate
-pub fn filter(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
-    let (items, expression) = (args[0], args[1]);
+pub fn filter(data: &Value, args: &[Parsed], env: &Env) -> Result<Value, Error> {
+    let (items, expression) = (&args[0], &args[1]);
 
-    let _parsed = Parsed::from_value(items)?;
-    let evaluated_items = _parsed.evaluate(data)?;
+    let evaluated_items = items.evaluate(data, env)?;
 
     let values: Vec<Value> = match evaluated_items {
         Evaluated::New(Value::Array(vals)) => vals,
@@ -61,25 +67,22 @@ pub fn filter(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
         Evaluated::New(Value::Null) => vec![],
         Evaluated::Raw(Value::Null) => vec![],
         _ => {
-            return Err(Error::InvalidArgument {
-                value: args[0].clone(),
-                operation: "map",
-                reason: format!(
-                    "First argument to filter must evaluate to an array. Got {:?}",
-                    evaluated_items
-                ),
-            })
+            return Err(invalid_items(
+                "filter",
+                args[0].clone().into(),
+                &evaluated_items,
+            ))
         }
     };
 
-    let parsed_expression = Parsed::from_value(expression)?;
-
+    let env = env.within(data);
     let value_vec: Vec<Value> = Vec::with_capacity(values.len());
     values
         .into_iter()
-        .fold(Ok(value_vec), |acc, cur| {
+        .enumerate()
+        .fold(Ok(value_vec), |acc, (idx, cur)| {
             let mut filtered = acc?;
-            let predicate = parsed_expression.evaluate(&cur)?;
+            let predicate = expression.evaluate(&cur, &env.at_index(idx))?;
 
             match logic::truthy_from_evaluated(&predicate) {
                 true => {
@@ -97,14 +100,12 @@ pub fn filter(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
 /// Note this differs from the reference implementation of jsonlogic
 /// (but not the spec), in that it evaluates the initializer as a
 /// jsonlogic expression rather than a raw value.
-pub fn reduce(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
-    let (items, expression, initializer) = (args[0], args[1], args[2]);
+pub fn reduce(data: &Value, args: &[Parsed], env: &Env) -> Result<Value, Error> {
+    let (items, expression, initializer) = (&args[0], &args[1], &args[2]);
 
-    let _parsed_items = Parsed::from_value(items)?;
-    let evaluated_items = _parsed_items.evaluate(data)?;
+    let evaluated_items = items.evaluate(data, env)?;
 
-    let _parsed_initializer = Parsed::from_value(initializer)?;
-    let evaluated_initializer = _parsed_initializer.evaluate(data)?;
+    let evaluated_initializer = initializer.evaluate(data, env)?;
 
     let values: Vec<Value> = match evaluated_items {
         Evaluated::New(Value::Array(vals)) => vals,
@@ -114,31 +115,93 @@ pub fn reduce(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
         Evaluated::New(Value::Null) => vec![],
         Evaluated::Raw(Value::Null) => vec![],
         _ => {
-            return Err(Error::InvalidArgument {
-                value: args[0].clone(),
-                operation: "map",
-                reason: format!(
-                    "First argument to filter must evaluate to an array. Got {:?}",
-                    evaluated_items
-                ),
-            })
+            return Err(invalid_items(
+                "reduce",
+                args[0].clone().into(),
+                &evaluated_items,
+            ))
         }
     };
 
-    let parsed_expression = Parsed::from_value(expression)?;
-
-    values
-        .into_iter()
-        .fold(Ok(Value::from(evaluated_initializer)), |acc, cur| {
+    let item_env = env.within(data);
+    values.into_iter().enumerate().fold(
+        Ok(Value::from(evaluated_initializer)),
+        |acc, (idx, cur)| {
             let accumulator = acc?;
             let mut data = Map::with_capacity(2);
             data.insert("current".into(), cur);
             data.insert("accumulator".into(), accumulator);
 
-            parsed_expression
-                .evaluate(&Value::Object(data))
+            expression
+                .evaluate(&Value::Object(data), &item_env.at_index(idx))
                 .map(Value::from)
-        })
+        },
+    )
+}
+
+/// Reduce values into a single result, like `reduce`, stopping early if
+/// the expression returns a value wrapped by `reduced`, which is the result.
+pub fn reduce_while(data: &Value, args: &[Parsed], env: &Env) -> Result<Value, Error> {
+    let (items, expression, initializer) = (&args[0], &args[1], &args[2]);
+    let evaluated_items = items.evaluate(data, env)?;
+    let values = elements("reduce_while", items, &evaluated_items)?;
+    let initial = initializer.evaluate(data, env)?.into();
+    let reduced = Reduced::default();
+    let item_env = env.within(data).with_reduced(&reduced);
+    fold_while(values, initial, &reduced, |accumulator, idx, current| {
+        let mut data = Map::with_capacity(2);
+        data.insert("current".into(), current.clone());
+        data.insert("accumulator".into(), accumulator);
+        expression
+            .evaluate(&Value::Object(data), &item_env.at_index(idx))
+            .map(Value::from)
+    })
+}
+
+/// The key of the object `reduced` wraps its value in
+const REDUCED: &str = "reduced";
+
+/// The value `reduced` last wrapped while a `reduce_while` evaluated its
+/// expression, so that an object shaped like the wrapper that came from the
+/// data doesn't end the reduction
+#[derive(Default)]
+pub(crate) struct Reduced(RefCell<Option<Value>>);
+
+/// Wrap a value to signal that a `reduce_while` is complete.
+pub fn reduced(items: &[Evaluated], env: &Env) -> Result<Value, Error> {
+    let value = Value::clone(&items[0]);
+    if let Some(reduced) = env.reduced() {
+        *reduced.0.borrow_mut() = Some(value.clone());
+    }
+    let mut wrapper = Map::with_capacity(1);
+    wrapper.insert(REDUCED.into(), value);
+    Ok(Value::Object(wrapper))
+}
+
+/// Fold values with `step`, until it returns a value wrapped by `reduced`
+/// while evaluating with the given slot.
+pub(crate) fn fold_while<F>(
+    values: &[Value],
+    initial: Value,
+    reduced: &Reduced,
+    mut step: F,
+) -> Result<Value, Error>
+where
+    F: FnMut(Value, usize, &Value) -> Result<Value, Error>,
+{
+    let mut accumulator = initial;
+    for (idx, current) in values.iter().enumerate() {
+        let result = step(accumulator, idx, current)?;
+        accumulator = match (reduced.0.borrow_mut().take(), result) {
+            (Some(value), Value::Object(wrapper))
+                if wrapper.len() == 1 && wrapper.get(REDUCED) == Some(&value) =>
+            {
+                return Ok(value);
+            }
+            (_, result) => result,
+        };
+    }
+    Ok(accumulator)
 }
 
 /// Return whether all members of an array or string satisfy a predicate.
@@ -146,25 +209,17 @@ pub fn reduce(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
 /// The predicate does not need to return true or false explicitly. Its
 /// return is evaluated using the "truthy" definition specified in the
 /// jsonlogic spec.
-pub fn all(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
-    let (first_arg, second_arg) = (args[0], args[1]);
-
-    // The first argument must be an array of values or a string of chars
-    // We won't bother parsing yet if the value is anything other than
-    // an object, because we can short-circuit this function if any of
-    // the items fail to match the predicate. However, we will parse
-    // if it's an object, in case it evaluates to a string or array, which
-    // we will then pass on
-
-    let _new_item: Value;
-    let potentially_evaled_first_arg = match first_arg {
-        Value::Object(_) => {
-            let parsed = Parsed::from_value(first_arg)?;
-            let evaluated = parsed.evaluate(data)?;
-            _new_item = evaluated.into();
-            &_new_item
-        }
-        _ => first_arg,
+pub fn all(data: &Value, args: &[Parsed], env: &Env) -> Result<Value, Error> {
+    let (first_arg, predicate) = (&args[0], &args[1]);
+
+    // The first argument must be an array of values or a string of chars.
+    // If it is a raw array, its items are evaluated one at a time below,
+    // because we can short-circuit this function if any of the items fail
+    // to match the predicate.
+    let evaluated_first_arg = first_arg.evaluate(data, env)?;
+    let potentially_evaled_first_arg: &Value = match &evaluated_first_arg {
+        Evaluated::New(val) => val,
+        Evaluated::Raw(val) => val,
     };
 
     let _new_arr: Vec<Value>;
@@ -183,14 +238,11 @@ pub fn all(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
             &_new_arr
         }
         _ => {
-            return Err(Error::InvalidArgument {
-                value: first_arg.clone(),
-                operation: "all",
-                reason: format!(
-                "First argument to all must evaluate to an array, string, or null, got {}",
-                potentially_evaled_first_arg
-            ),
-            })
+            return Err(invalid_items(
+                "all",
+                first_arg.clone().into(),
+                &evaluated_first_arg,
+            ))
         }
     };
 
@@ -203,20 +255,20 @@ pub fn all(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
     // Note we _expect_ the predicate to be an operator, but it doesn't
     // necessarily have to be. all([1, 2, 3], 1) is a valid operation,
     // returning 1 for each of the items and thus evaluating to true.
-    let predicate = Parsed::from_value(second_arg)?;
-
-    let result = items.into_iter().fold(Ok(true), |acc, i| {
+    let item_env = env.within(data);
+    let result = items.iter().enumerate().fold(Ok(true), |acc, (idx, i)| {
         acc.and_then(|res| {
             // "Short-circuit": return false if the previous eval was false
             if !res {
                 return Ok(false);
             };
-            let _parsed_item = Parsed::from_value(i)?;
+            let _parsed_item = Parsed::from_value(i, &Scope::default())?;
             // Evaluate each item as we go, in case we can short-circuit
-            let evaluated_item = _parsed_item.evaluate(data)?;
-            Ok(logic::truthy_from_evaluated(
-                &predicate.evaluate(&evaluated_item.into())?,
-            ))
+            let evaluated_item = _parsed_item.evaluate(data, env)?;
+            Ok(logic::truthy_from_evaluated(&predicate.evaluate(
+                &evaluated_item.into(),
+                &item_env.at_index(idx),
+            )?))
         })
     })?;
 
@@ -228,25 +280,17 @@ pub fn all(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
 /// The predicate does not need to return true or false explicitly. Its
 /// return is evaluated using the "truthy" definition specified in the
 /// jsonlogic spec.
-pub fn some(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
-    let (first_arg, second_arg) = (args[0], args[1]);
-
-    // The first argument must be an array of values or a string of chars
-    // We won't bother parsing yet if the value is anything other than
-    // an object, because we can short-circuit this function if any of
-    // the items fail to match the predicate. However, we will parse
-    // if it's an object, in case it evaluates to a string or array, which
-    // we will then pass on
-
-    let _new_item: Value;
-    let potentially_evaled_first_arg = match first_arg {
-        Value::Object(_) => {
-            let parsed = Parsed::from_value(first_arg)?;
-            let evaluated = parsed.evaluate(data)?;
-            _new_item = evaluated.into();
-            &_new_item
-        }
-        _ => first_arg,
+pub fn some(data: &Value, args: &[Parsed], env: &Env) -> Result<Value, Error> {
+    let (first_arg, predicate) = (&args[0], &args[1]);
+
+    // The first argument must be an array of values or a string of chars.
+    // If it is a raw array, its items are evaluated one at a time below,
+    // because we can short-circuit this function if any of the items fail
+    // to match the predicate.
+    let evaluated_first_arg = first_arg.evaluate(data, env)?;
+    let potentially_evaled_first_arg: &Value = match &evaluated_first_arg {
+        Evaluated::New(val) => val,
+        Evaluated::Raw(val) => val,
     };
 
     let _new_arr: Vec<Value>;
@@ -265,14 +309,11 @@ pub fn some(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
             &_new_arr
         }
         _ => {
-            return Err(Error::InvalidArgument {
-                value: first_arg.clone(),
-                operation: "all",
-                reason: format!(
-                "First argument must evaluate to an array, a string, or null, got {}",
-                potentially_evaled_first_arg
-            ),
-            })
+            return Err(invalid_items(
+                "some",
+                first_arg.clone().into(),
+                &evaluated_first_arg,
+            ))
         }
     };
 
@@ -285,20 +326,20 @@ pub fn some(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
     // Note we _expect_ the predicate to be an operator, but it doesn't
     // necessarily have to be. all([1, 2, 3], 1) is a valid operation,
     // returning 1 for each of the items and thus evaluating to true.
-    let predicate = Parsed::from_value(second_arg)?;
-
-    let result = items.into_iter().fold(Ok(false), |acc, i| {
+    let item_env = env.within(data);
+    let result = items.iter().enumerate().fold(Ok(false), |acc, (idx, i)| {
         acc.and_then(|res| {
             // "Short-circuit": return false if the previous eval was false
             if res {
                 return Ok(true);
             };
-            let _parsed_item = Parsed::from_value(i)?;
+            let _parsed_item = Parsed::from_value(i, &Scope::default())?;
             // Evaluate each item as we go, in case we can short-circuit
-            let evaluated_item = _parsed_item.evaluate(data)?;
-            Ok(logic::truthy_from_evaluated(
-                &predicate.evaluate(&evaluated_item.into())?,
-            ))
+            let evaluated_item = _parsed_item.evaluate(data, env)?;
+            Ok(logic::truthy_from_evaluated(&predicate.evaluate(
+                &evaluated_item.into(),
+                &item_env.at_index(idx),
+            )?))
         })
     })?;
 
@@ -310,8 +351,8 @@ pub fn some(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
 /// The predicate does not need to return true or false explicitly. Its
 /// return is evaluated using the "truthy" definition specified in the
 /// jsonlogic spec.
-pub fn none(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
-    some(data, args).and_then(|had_some| match had_some {
+pub fn none(data: &Value, args: &[Parsed], env: &Env) -> Result<Value, Error> {
+    some(data, args, env).and_then(|had_some| match had_some {
         Value::Bool(res) => Ok(Value::Bool(!res)),
         _ => Err(Error::UnexpectedError(
             "Unexpected return type from op_some".into(),
@@ -319,32 +360,683 @@ pub fn none(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
     })
 }
 
+/// The first element of an array for which an expression is truthy, or
+/// null if there is none
+pub fn find(data: &Value, args: &[Parsed], env: &Env) -> Result<Value, Error> {
+    selection("find", data, args, env)
+}
+
+/// The index of the first element of an array for which an expression is
+/// truthy, or -1 if there is none
+pub fn position(data: &Value, args: &[Parsed], env: &Env) -> Result<Value, Error> {
+    selection("position", data, args, env)
+}
+
+/// The element of an array for which an expression is smallest, or null
+/// if the array is empty. Of equal elements, the first is chosen.
+pub fn min_by(data: &Value, args: &[Parsed], env: &Env) -> Result<Value, Error> {
+    selection("min_by", data, args, env)
+}
+
+/// The element of an array for which an expression is largest, or null
+/// if the array is empty. Of equal elements, the first is chosen.
+pub fn max_by(data: &Value, args: &[Parsed], env: &Env) -> Result<Value, Error> {
+    selection("max_by", data, args, env)
+}
+
+/// The sum of an expression's values for each element of an array
+pub fn sum_by(data: &Value, args: &[Parsed], env: &Env) -> Result<Value, Error> {
+    selection("sum_by", data, args, env)
+}
+
+/// Evaluate one of the selecting operators: `find`, `position`, `min_by`,
+/// `max_by`, or `sum_by`.
+fn selection(
+    operator: &'static str,
+    data: &Value,
+    args: &[Parsed],
+    env: &Env,
+) -> Result<Value, Error> {
+    let (items, expression) = (&args[0], &args[1]);
+    let evaluated_items = items.evaluate(data, env)?;
+    let values = elements(operator, items, &evaluated_items)?;
+    let item_env = env.within(data);
+    select(operator, values, env, |value| {
+        expression.evaluate(value, &item_env).map(Value::from)
+    })
+}
+
+/// The elements of the array an operator iterates over, treating null as
+/// an empty array
+fn elements<'a>(
+    operator: &'static str,
+    source: &Parsed,
+    items: &'a Evaluated,
+) -> Result<&'a [Value], Error> {
+    match &**items {
+        Value::Array(values) => Ok(values),
+        Value::Null => Ok(&[]),
+        _ => Err(invalid_items(operator, source.clone().into(), items)),
+    }
+}
+
+/// Select from values by the results of `evaluate` for each, as the named
+/// selecting operator does.
+pub(crate) fn select<F>(
+    operator: &'static str,
+    values: &[Value],
+    env: &Env,
+    mut evaluate: F,
+) -> Result<Value, Error>
+where
+    F: FnMut(&Value) -> Result<Value, Error>,
+{
+    match operator {
+        "find" | "position" => {
+            for (idx, value) in values.iter().enumerate() {
+                if logic::truthy(&evaluate(value)?) {
+                    return Ok(match operator {
+                        "find" => value.clone(),
+                        _ => Value::from(idx),
+                    });
+                }
+            }
+            Ok(match operator {
+                "find" => Value::Null,
+                _ => Value::from(-1),
+            })
+        }
+        "min_by" | "max_by" => {
+            let mut selected: Option<(&Value, Value)> = None;
+            for value in values {
+                let key = evaluate(value)?;
+                let better = match &selected {
+                    None => true,
+                    Some((_, best)) if operator == "min_by" => {
+                        env.numeric().lt(&key, best)
+                    }
+                    Some((_, best)) => env.numeric().gt(&key, best),
+                };
+                if better {
+                    selected = Some((value, key));
+                }
+            }
+            Ok(selected.map_or(Value::Null, |(value, _)| value.clone()))
+        }
+        _ => {
+            let keys = values
+                .iter()
+                .map(&mut evaluate)
+                .collect::<Result<Vec<Value>, Error>>()?;
+            env.numeric().plus(&keys.iter().collect::<Vec<&Value>>())
+        }
+    }
+}
+
+/// The elements of an array whose result for an expression differs from
+/// that of every element before it, i.e. the first element for each
+/// distinct result.
+///
+/// Results are compared like `eq` compares values, so arrays and objects
+/// are equal if their contents are.
+pub fn distinct_by(data: &Value, args: &[Parsed], env: &Env) -> Result<Value, Error> {
+    let (items, expression) = (&args[0], &args[1]);
+    let evaluated_items = items.evaluate(data, env)?;
+    let values = elements("distinct_by", items, &evaluated_items)?;
+    let item_env = env.within(data);
+    distinct(values, |value| {
+        expression.evaluate(value, &item_env).map(Value::from)
+    })
+}
+
+/// The first of the values for each distinct result of `evaluate`
+pub(crate) fn distinct<F>(values: &[Value], mut evaluate: F) -> Result<Value, Error>
+where
+    F: FnMut(&Value) -> Result<Value, Error>,
+{
+    let mut keys: Vec<Value> = Vec::new();
+    let mut distinct = Vec::new();
+    for value in values {
+        let key = evaluate(value)?;
+        if !keys.contains(&key) {
+            keys.push(key);
+            distinct.push(value.clone());
+        }
+    }
+    Ok(Value::Array(distinct))
+}
+
+/// Sort an array by the result of an expression for each element.
+///
+/// Keys are ordered null first, then booleans, numbers, and strings. A key
+/// that is an array sorts by its first element, then its second, and so
+/// on, to sort by several keys. The optional third argument sorts in
+/// descending order if true, or for a multi-key sort, may be an array of
+/// whether each key is descending. Elements with equal keys keep their
+/// order.
+pub fn sort_by(data: &Value, args: &[Parsed], env: &Env) -> Result<Value, Error> {
+    sorting("sort_by", data, args, env)
+}
+
+/// Sort an array by a comparison, which is evaluated against objects with
+/// two of its elements, `a` and `b`, and returns a negative number if `a`
+/// sorts first, a positive number if `b` does, or zero if they're equal,
+/// e.g. `{"compare": [{"var": "a.name"}, {"var": "b.name"}]}`.
+///
+/// The optional third argument sorts in descending order if true. Equal
+/// elements keep their order.
+pub fn sort_with(data: &Value, args: &[Parsed], env: &Env) -> Result<Value, Error> {
+    sorting("sort_with", data, args, env)
+}
+
+/// Evaluate one of the sorting operators: `sort_by` or `sort_with`.
+fn sorting(
+    operator: &'static str,
+    data: &Value,
+    args: &[Parsed],
+    env: &Env,
+) -> Result<Value, Error> {
+    let (items, expression) = (&args[0], &args[1]);
+    let evaluated_items = items.evaluate(data, env)?;
+    let values = elements(operator, items, &evaluated_items)?;
+    let descending = match args.get(2) {
+        Some(descending) => descending.evaluate(data, env)?.into(),
+        None => Value::Bool(false),
+    };
+    let item_env = env.within(data);
+    sort(operator, values, &descending, |value| {
+        expression.evaluate(value, &item_env).map(Value::from)
+    })
+}
+
+/// Sort values as the named sorting operator does, with `evaluate` giving
+/// the key of each value for `sort_by`, or the comparison of the values in
+/// an object of `a` and `b` for `sort_with`.
+pub(crate) fn sort<F>(
+    operator: &'static str,
+    values: &[Value],
+    descending: &Value,
+    mut evaluate: F,
+) -> Result<Value, Error>
+where
+    F: FnMut(&Value) -> Result<Value, Error>,
+{
+    let valid = match descending {
+        Value::Bool(_) => true,
+        Value::Array(flags) => {
+            operator == "sort_by" && flags.iter().all(Value::is_boolean)
+        }
+        _ => false,
+    };
+    if !valid {
+        return Err(Error::invalid_argument(
+            descending.clone(),
+            operator,
+            "Whether to sort in descending order must be a boolean, or for a multi-key sort_by, an array of them",
+        ));
+    }
+    let is_descending = |idx: usize| match descending {
+        Value::Array(flags) => flags.get(idx) == Some(&Value::Bool(true)),
+        descending => descending == &Value::Bool(true),
+    };
+
+    let mut sorted: Vec<&Value> = values.iter().collect();
+    if operator == "sort_by" {
+        let keys = values
+            .iter()
+            .map(|value| {
+                let key = evaluate(value)?;
+                match sort_key(&key) {
+                    true => Ok(key),
+                    false => Err(Error::invalid_argument(
+                        key,
+                        operator,
+                        "Sort keys must be null, booleans, numbers, strings, or arrays of them",
+                    )),
+                }
+            })
+            .collect::<Result<Vec<Value>, Error>>()?;
+        let mut order: Vec<usize> = (0..values.len()).collect();
+        order.sort_by(|a, b| match (&keys[*a], &keys[*b]) {
+            (Value::Array(a), Value::Array(b)) => a
+                .iter()
+                .zip(b)
+                .enumerate()
+                .map(|(idx, (a, b))| match is_descending(idx) {
+                    true => compare_keys(b, a),
+                    false => compare_keys(a, b),
+                })
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or_else(|| a.len().cmp(&b.len())),
+            (a, b) => match is_descending(0) {
+                true => compare_keys(b, a),
+                false => compare_keys(a, b),
+            },
+        });
+        sorted = order.into_iter().map(|idx| &values[idx]).collect();
+    } else {
+        let mut error = None;
+        sorted.sort_by(|a, b| {
+            if error.is_some() {
+                return Ordering::Equal;
+            }
+            let mut pair = Map::with_capacity(2);
+            pair.insert("a".into(), Value::clone(a));
+            pair.insert("b".into(), Value::clone(b));
+            let ordering = evaluate(&Value::Object(pair)).and_then(|comparison| {
+                comparison
+                    .as_f64()
+                    .and_then(|comparison| comparison.partial_cmp(&0.0))
+                    .ok_or_else(|| {
+                        Error::invalid_argument(
+                            comparison,
+                            operator,
+                            "Comparisons must evaluate to numbers",
+                        )
+                    })
+            });
+            match ordering {
+                Ok(ordering) if is_descending(0) => ordering.reverse(),
+                Ok(ordering) => ordering,
+                Err(err) => {
+                    error = Some(err);
+                    Ordering::Equal
+                }
+            }
+        });
+        if let Some(err) = error {
+            return Err(err);
+        }
+    }
+    Ok(Value::Array(sorted.into_iter().cloned().collect()))
+}
+
+/// Whether a value may be a key of `sort_by`
+fn sort_key(key: &Value) -> bool {
+    match key {
+        Value::Object(_) => false,
+        Value::Array(keys) => keys.iter().all(sort_key),
+        _ => true,
+    }
+}
+
+/// Order sort keys: null, then booleans, numbers, and strings, with arrays
+/// after all of them, ordered by their elements
+fn compare_keys(a: &Value, b: &Value) -> Ordering {
+    let rank = |key: &Value| match key {
+        Value::Null => 0,
+        Value::Bool(_) => 1,
+        Value::Number(_) => 2,
+        Value::String(_) => 3,
+        _ => 4,
+    };
+    match (a, b) {
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        (Value::Number(a), Value::Number(b)) => {
+            let (a, b) = (a.as_f64(), b.as_f64());
+            a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+        }
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Array(a), Value::Array(b)) => a
+            .iter()
+            .zip(b)
+            .map(|(a, b)| compare_keys(a, b))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len())),
+        (a, b) => rank(a).cmp(&rank(b)),
+    }
+}
+
+/// The error for an iterating operator whose first argument, `source`,
+/// evaluated to something that can't be iterated over
+pub(crate) fn invalid_items(
+    operator: &'static str,
+    source: Value,
+    items: &Evaluated,
+) -> Error {
+    let (operation, reason) = match operator {
+        "map" => (
+            "map",
+            format!(
+                "First argument to map must evaluate to an array. Got {:?}",
+                items
+            ),
+        ),
+        "filter" | "reduce" => (
+            "map",
+            format!(
+                "First argument to filter must evaluate to an array. Got {:?}",
+                items
+            ),
+        ),
+        "find" | "position" | "min_by" | "max_by" | "sum_by" | "sort_by"
+        | "sort_with" | "distinct_by" | "reduce_while" => (
+            operator,
+            format!(
+                "First argument to {} must evaluate to an array or null, got {}",
+                operator, &**items
+            ),
+        ),
+        "all" => (
+            "all",
+            format!(
+                "First argument to all must evaluate to an array, string, or null, got {}",
+                &**items
+            ),
+        ),
+        _ => (
+            "all",
+            format!(
+                "First argument must evaluate to an array, a string, or null, got {}",
+                &**items
+            ),
+        ),
+    };
+    Error::InvalidArgument {
+        value: source,
+        operation,
+        reason,
+    }
+}
+
 /// Merge one to n arrays, flattening them by one level.
 ///
 /// Values that are not arrays are (effectively) converted to arrays
 /// before flattening.
-pub fn merge(items: &Vec<&Value>) -> Result<Value, Error> {
+pub fn merge(items: &[Evaluated]) -> Result<Value, Error> {
     let rv_vec: Vec<Value> = Vec::new();
-    Ok(Value::Array(items.into_iter().fold(
-        rv_vec,
-        |mut acc, i| {
-            match i {
-                Value::Array(i_vals) => {
-                    i_vals.into_iter().for_each(|val| acc.push((*val).clone()));
-                }
-                _ => acc.push((**i).clone()),
-            };
-            acc
-        },
-    )))
+    Ok(Value::Array(items.iter().fold(rv_vec, |mut acc, i| {
+        match &**i {
+            Value::Array(i_vals) => {
+                i_vals.iter().for_each(|val| acc.push(val.clone()));
+            }
+            i => acc.push(i.clone()),
+        };
+        acc
+    })))
+}
+
+/// The elements of an array argument, treating null as an empty array
+fn array<'a>(value: &'a Value, operation: &'static str) -> Result<&'a [Value], Error> {
+    match value {
+        Value::Array(values) => Ok(values.as_slice()),
+        Value::Null => Ok(&[]),
+        value => Err(Error::invalid_argument(
+            value.clone(),
+            operation,
+            format!("Arguments to {} must be arrays", operation),
+        )),
+    }
+}
+
+/// The elements of the arrays an operator combines
+fn arrays<'a>(
+    items: &'a [Evaluated],
+    operation: &'static str,
+) -> Result<Vec<&'a [Value]>, Error> {
+    items.iter().map(|item| array(item, operation)).collect()
+}
+
+/// Arrays of the elements at each index of the given arrays, up to the
+/// length of the shortest
+pub(crate) fn zip_arrays(
+    items: &[Evaluated],
+    operation: &'static str,
+) -> Result<Vec<Value>, Error> {
+    let arrays = arrays(items, operation)?;
+    let len = arrays.iter().map(|array| array.len()).min().unwrap_or(0);
+    Ok((0..len)
+        .map(|idx| {
+            Value::Array(arrays.iter().map(|array| array[idx].clone()).collect())
+        })
+        .collect())
+}
+
+/// Combine arrays into an array of the arrays of their elements at each
+/// index, up to the length of the shortest.
+pub fn zip(items: &[Evaluated]) -> Result<Value, Error> {
+    zip_arrays(items, "zip").map(Value::Array)
+}
+
+/// Evaluate an expression against the array of the elements at each index
+/// of one or more arrays, up to the length of the shortest.
+///
+/// The expression is the last argument, and the arrays are those before it.
+pub fn zip_with(data: &Value, args: &[Parsed], env: &Env) -> Result<Value, Error> {
+    let (arrays, expression) = args.split_at(args.len() - 1);
+    let evaluated = arrays
+        .iter()
+        .map(|array| array.evaluate(data, env))
+        .collect::<Result<Vec<Evaluated>, Error>>()?;
+    let env = env.within(data);
+    zip_arrays(&evaluated, "zip_with")?
+        .iter()
+        .enumerate()
+        .map(|(idx, tuple)| {
+            expression[0]
+                .evaluate(tuple, &env.at_index(idx))
+                .map(Value::from)
+        })
+        .collect::<Result<Vec<Value>, Error>>()
+        .map(Value::Array)
+}
+
+/// Every combination of an element of each array, as arrays, in order of
+/// the first array's elements, then the second's, and so on.
+pub fn cartesian(items: &[Evaluated], env: &Env) -> Result<Value, Error> {
+    let arrays = arrays(items, "cartesian")?;
+    let count = arrays
+        .iter()
+        .try_fold(1usize, |count, array| count.checked_mul(array.len()));
+    let max = env.max_array_size();
+    if count.map_or(true, |count| count > max) {
+        return Err(Error::invalid_argument(
+            count.map_or(Value::Null, Value::from),
+            "cartesian",
+            format!(
+                "The product would have more than the maximum of {} elements",
+                max
+            ),
+        ));
+    }
+    // Partial products could otherwise be larger than the (empty) result
+    if count == Some(0) {
+        return Ok(Value::Array(Vec::new()));
+    }
+    let mut product: Vec<Vec<Value>> = vec![Vec::new()];
+    for array in arrays {
+        product = product
+            .iter()
+            .flat_map(|prefix| {
+                array.iter().map(move |value| {
+                    let mut tuple = prefix.clone();
+                    tuple.push(value.clone());
+                    tuple
+                })
+            })
+            .collect();
+    }
+    Ok(Value::Array(
+        product.into_iter().map(Value::Array).collect(),
+    ))
+}
+
+/// A size argument, which must be a positive integer
+fn size_arg(value: &Value, operation: &'static str) -> Result<usize, Error> {
+    value
+        .as_u64()
+        .filter(|size| *size > 0)
+        .map(|size| size as usize)
+        .ok_or_else(|| {
+            Error::invalid_argument(
+                value.clone(),
+                operation,
+                "Sizes and steps must be positive integers",
+            )
+        })
+}
+
+/// A count argument, which must be an integer
+fn count_arg(value: &Value, operation: &'static str) -> Result<i64, Error> {
+    value.as_i64().ok_or_else(|| {
+        Error::invalid_argument(value.clone(), operation, "Counts must be integers")
+    })
+}
+
+fn to_array(values: &[Value]) -> Value {
+    Value::Array(values.to_vec())
+}
+
+/// Split an array into arrays of a size, the last of which may be shorter.
+pub fn chunk(items: &[Evaluated]) -> Result<Value, Error> {
+    let values = array(&items[0], "chunk")?;
+    let size = size_arg(&items[1], "chunk")?;
+    Ok(Value::Array(values.chunks(size).map(to_array).collect()))
+}
+
+/// The arrays of a size starting at every element of an array, or every
+/// `step` elements, that fit within it.
+pub fn window(items: &[Evaluated]) -> Result<Value, Error> {
+    let values = array(&items[0], "window")?;
+    let size = size_arg(&items[1], "window")?;
+    let step = match items.get(2) {
+        Some(step) => size_arg(step, "window")?,
+        None => 1,
+    };
+    Ok(Value::Array(
+        values.windows(size).step_by(step).map(to_array).collect(),
+    ))
+}
+
+/// The first elements of an array, or with a negative count, the last.
+pub fn take(items: &[Evaluated]) -> Result<Value, Error> {
+    let values = array(&items[0], "take")?;
+    let count = count_arg(&items[1], "take")?;
+    let len = values.len();
+    let n = (count.unsigned_abs() as usize).min(len);
+    Ok(to_array(if count >= 0 {
+        &values[..n]
+    } else {
+        &values[len - n..]
+    }))
+}
+
+/// An array without its first elements, or with a negative count, its last.
+pub fn drop(items: &[Evaluated]) -> Result<Value, Error> {
+    let values = array(&items[0], "drop")?;
+    let count = count_arg(&items[1], "drop")?;
+    let len = values.len();
+    let n = (count.unsigned_abs() as usize).min(len);
+    Ok(to_array(if count >= 0 {
+        &values[n..]
+    } else {
+        &values[..len - n]
+    }))
+}
+
+/// The numbers from a start up to, but not including, an end, counting by
+/// a step, which defaults to 1. With only one argument, the numbers from 0
+/// up to it.
+pub fn range(items: &[Evaluated], env: &Env) -> Result<Value, Error> {
+    // Integers are counted exactly, since past 2^53 not every integer is
+    // a float
+    if let Some(integers) = items
+        .iter()
+        .map(|item| item.as_i64())
+        .collect::<Option<Vec<i64>>>()
+    {
+        return integer_range(items, &integers, env);
+    }
+    let number = |item: &Evaluated| {
+        item.as_f64().filter(|n| n.is_finite()).ok_or_else(|| {
+            Error::invalid_argument(
+                Value::clone(item),
+                "range",
+                "Bounds and steps must be finite numbers",
+            )
+        })
+    };
+    let (start, end) = match items {
+        [end] => (0.0, number(end)?),
+        _ => (number(&items[0])?, number(&items[1])?),
+    };
+    let step = match items.get(2) {
+        Some(step) => number(step)?,
+        None => 1.0,
+    };
+    if step == 0.0 {
+        return Err(zero_step(items));
+    }
+    let count = ((end - start) / step).ceil().max(0.0);
+    let max = env.max_array_size();
+    if count > max as f64 {
+        return Err(too_long(Value::from(count), max));
+    }
+    (0..count as usize)
+        .map(|idx| to_number_value(start + idx as f64 * step))
+        .collect::<Result<Vec<Value>, Error>>()
+        .map(Value::Array)
+}
+
+/// A `range` whose bounds and step are all integers
+fn integer_range(
+    items: &[Evaluated],
+    integers: &[i64],
+    env: &Env,
+) -> Result<Value, Error> {
+    let (start, end) = match *integers {
+        [end] => (0, i128::from(end)),
+        _ => (i128::from(integers[0]), i128::from(integers[1])),
+    };
+    let step = integers.get(2).map_or(1, |step| i128::from(*step));
+    if step == 0 {
+        return Err(zero_step(items));
+    }
+    // Count in the direction of the step
+    let (distance, stride) = match step > 0 {
+        true => (end - start, step),
+        false => (start - end, -step),
+    };
+    let count = match distance > 0 {
+        true => (distance + stride - 1) / stride,
+        false => 0,
+    };
+    let max = env.max_array_size();
+    if count > max as i128 {
+        return Err(too_long(Value::from(count as u64), max));
+    }
+    Ok(Value::Array(
+        (0..count)
+            .map(|idx| Value::from((start + idx * step) as i64))
+            .collect(),
+    ))
+}
+
+fn zero_step(items: &[Evaluated]) -> Error {
+    Error::invalid_argument(
+        Value::clone(&items[2]),
+        "range",
+        "The step must not be zero",
+    )
+}
+
+fn too_long(count: Value, max: usize) -> Error {
+    Error::invalid_argument(
+        count,
+        "range",
+        format!(
+            "The range would have more than the maximum of {} elements",
+            max
+        ),
+    )
 }
 
 /// Perform containment checks with "in"
 // TODO: make this a lazy operator, since we don't need to parse things
 // later on in the list if we find something that matches early.
-pub fn in_(items: &Vec<&Value>) -> Result<Value, Error> {
-    let needle = items[0];
-    let haystack = items[1];
+pub fn in_(items: &[Evaluated]) -> Result<Value, Error> {
+    let needle = &*items[0];
+    let haystack = &*items[1];
 
     match haystack {
         // Note: our containment check for array values is actually a bit
@@ -0,0 +1,150 @@
+//! Locale-Aware String Comparison
+
+use core::cmp::Ordering;
+use core::str::FromStr;
+
+use icu_collator::{CaseLevel, Collator, CollatorOptions, Strength};
+use serde_json::Value;
+
+use crate::context::Env;
+use crate::error::Error;
+use crate::value::Evaluated;
+
+/// Compare two strings by the collation rules of the context's locale,
+/// returning -1, 0, or 1 as the first sorts before, with, or after the
+/// second.
+///
+/// The optional third argument is the differences that are significant, as
+/// with the `sensitivity` of JavaScript's `Intl.Collator`:
+///
+/// - `"base"`: only differing letters, e.g. `a ≠ b`, `a = á`, `a = A`
+/// - `"accent"`: differing letters and accents, e.g. `a ≠ á`, `a = A`,
+///   i.e. a case-insensitive comparison
+/// - `"case"`: differing letters and case, e.g. `a = á`, `a ≠ A`
+/// - `"variant"` (the default): any difference
+pub fn compare(items: &[Evaluated], env: &Env) -> Result<Value, Error> {
+    let strings = items[..2]
+        .iter()
+        .map(|item| match &**item {
+            Value::String(string) => Ok(string.as_str()),
+            value => Err(Error::invalid_argument(
+                value.clone(),
+                "compare",
+                "Only strings may be compared",
+            )),
+        })
+        .collect::<Result<Vec<&str>, Error>>()?;
+
+    let mut options = CollatorOptions::new();
+    match items.get(2).map(|sensitivity| &**sensitivity) {
+        None => options.strength = Some(Strength::Tertiary),
+        Some(Value::String(sensitivity)) => match sensitivity.as_str() {
+            "base" => options.strength = Some(Strength::Primary),
+            "accent" => options.strength = Some(Strength::Secondary),
+            "case" => {
+                options.strength = Some(Strength::Primary);
+                options.case_level = Some(CaseLevel::On);
+            }
+            "variant" => options.strength = Some(Strength::Tertiary),
+            _ => return Err(invalid_sensitivity(&items[2])),
+        },
+        Some(sensitivity) => return Err(invalid_sensitivity(sensitivity)),
+    }
+
+    // Tags ICU can't parse are collated by the root locale's rules
+    let tag = env.locale().map(|locale| locale.tag().replace('_', "-"));
+    let locale = tag
+        .and_then(|tag| icu_locid::Locale::from_str(&tag).ok())
+        .unwrap_or_default();
+    let collator = Collator::try_new(&(&locale).into(), options).map_err(|err| {
+        Error::invalid_argument(
+            Value::String(locale.to_string()),
+            "compare",
+            err.to_string(),
+        )
+    })?;
+    Ok(Value::from(
+        match collator.compare(strings[0], strings[1]) {
+            Ordering::Less => -1,
+            Ordering::Equal => 0,
+            Ordering::Greater => 1,
+        },
+    ))
+}
+
+fn invalid_sensitivity(value: &Value) -> Error {
+    Error::invalid_argument(
+        value.clone(),
+        "compare",
+        "The sensitivity must be \"base\", \"accent\", \"case\", or \"variant\"",
+    )
+}
+
+#[cfg(test)]
+mod test_collation {
+    use std::sync::Arc;
+
+    use serde_json::{json, Value};
+
+    use crate::{Context, Error, Locale, Rule};
+
+    fn compare(args: Value, locale: Option<&str>) -> Result<Value, Error> {
+        let ctx = Context {
+            locale: locale.map(|tag| Arc::new(Locale::new(tag))),
+            ..Context::default()
+        };
+        Rule::from_value(&json!({ "compare": args }))?.apply_with(&json!({}), &ctx)
+    }
+
+    #[test]
+    fn test_compare() {
+        let cases = vec![
+            (json!(["a", "b"]), json!(-1)),
+            (json!(["b", "a"]), json!(1)),
+            (json!(["a", "a"]), json!(0)),
+            // Byte-wise, these would sort after "z"
+            (json!(["é", "f"]), json!(-1)),
+            (json!(["Zoë", "zoe"]), json!(1)),
+            (json!(["Zoë", "zoe", "variant"]), json!(1)),
+            (json!(["Zoë", "zoe", "accent"]), json!(1)),
+            (json!(["Zoë", "zoë", "accent"]), json!(0)),
+            (json!(["Zoë", "zoe", "case"]), json!(1)),
+            (json!(["zoë", "zoe", "case"]), json!(0)),
+            (json!(["Zoë", "zoe", "base"]), json!(0)),
+        ];
+        for (args, expected) in cases {
+            assert_eq!(compare(args.clone(), None).unwrap(), expected, "{}", args);
+        }
+    }
+
+    #[test]
+    fn test_locales() {
+        // In Swedish, "ä" sorts after "z", and in German, with "a"
+        assert_eq!(compare(json!(["ä", "z"]), Some("sv-SE")).unwrap(), json!(1));
+        assert_eq!(
+            compare(json!(["ä", "z"]), Some("de_DE")).unwrap(),
+            json!(-1)
+        );
+        assert_eq!(
+            compare(json!(["ä", "z"]), Some("not a tag")).unwrap(),
+            json!(-1)
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        for args in [
+            json!(["a", 1]),
+            json!(["a", "b", "loose"]),
+            json!(["a", "b", 1]),
+        ] {
+            assert_eq!(compare(args, None).unwrap_err().code(), "invalid_argument");
+        }
+    }
+
+    #[test]
+    fn test_untranslatable() {
+        let rule = Rule::from_value(&json!({"compare": ["a", "b"]})).unwrap();
+        assert_eq!(rule.to_javascript().unwrap_err().code(), "untranslatable");
+    }
+}
@@ -0,0 +1,149 @@
+//! Cryptographic Hashes
+
+use hmac::{Hmac, Mac};
+use serde_json::Value;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use crate::context::Env;
+use crate::error::Error;
+use crate::value::Evaluated;
+
+/// The bytes hashed for a value: a string's UTF-8, or any other value's JSON,
+/// e.g. `1` for the number 1.
+fn message(value: &Value) -> Vec<u8> {
+    match value {
+        Value::String(string) => string.as_bytes().to_vec(),
+        value => value.to_string().into_bytes(),
+    }
+}
+
+fn hex(bytes: &[u8]) -> Value {
+    Value::String(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// The hex-encoded SHA-256 hash of a value
+pub fn sha256(items: &[Evaluated]) -> Result<Value, Error> {
+    Ok(hex(&Sha256::digest(message(&items[0]))))
+}
+
+/// The hex-encoded SHA-1 hash of a value
+///
+/// SHA-1 is not collision-resistant, and is provided only for compatibility
+/// with existing identifiers, not for verifying untrusted data.
+pub fn sha1(items: &[Evaluated]) -> Result<Value, Error> {
+    Ok(hex(&Sha1::digest(message(&items[0]))))
+}
+
+/// The hex-encoded HMAC-SHA256 of a value, with the key of the given name
+/// from the context's `HmacKeys`
+pub fn hmac_sha256(items: &[Evaluated], env: &Env) -> Result<Value, Error> {
+    let name = match &*items[1] {
+        Value::String(name) => name,
+        value => {
+            return Err(Error::invalid_argument(
+                value.clone(),
+                "hmac_sha256",
+                "Key names must be strings",
+            ))
+        }
+    };
+    let key = env
+        .hmac_keys()
+        .ok_or_else(|| Error::InvalidOperation {
+            key: "hmac_sha256".into(),
+            reason: "No HMAC keys were provided in the evaluation context".into(),
+        })?
+        .get(name)
+        .ok_or_else(|| {
+            Error::invalid_argument(
+                Value::String(name.clone()),
+                "hmac_sha256",
+                "There is no key with this name in the evaluation context",
+            )
+        })?;
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(&message(&items[0]));
+    Ok(hex(&mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod test_crypto {
+    use std::sync::Arc;
+
+    use serde_json::{json, Value};
+
+    use crate::{Context, Error, HmacKeys, Rule};
+
+    fn apply(rule: Value, ctx: &Context) -> Result<Value, Error> {
+        Rule::from_value(&rule)?.apply_with(&json!({"id": 42, "name": "abc"}), ctx)
+    }
+
+    #[test]
+    fn test_hashes() {
+        let ctx = Context::default();
+        assert_eq!(
+            apply(json!({"sha256": ""}), &ctx).unwrap(),
+            json!("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")
+        );
+        assert_eq!(
+            apply(json!({"sha256": {"var": "name"}}), &ctx).unwrap(),
+            json!("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+        assert_eq!(
+            apply(json!({"sha1": "abc"}), &ctx).unwrap(),
+            json!("a9993e364706816aba3e25717850c26c9cd0d89d")
+        );
+        // Other values are hashed as JSON
+        assert_eq!(
+            apply(json!({"sha256": {"var": "id"}}), &ctx).unwrap(),
+            apply(json!({"sha256": "42"}), &ctx).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_hmac_sha256() {
+        let ctx = Context {
+            hmac_keys: Some(Arc::new(HmacKeys::new().with_key("signing", "key"))),
+            ..Context::default()
+        };
+        assert_eq!(
+            apply(
+                json!({"hmac_sha256": ["The quick brown fox jumps over the lazy dog", "signing"]}),
+                &ctx
+            )
+            .unwrap(),
+            json!("f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8")
+        );
+        assert_eq!(
+            apply(json!({"hmac_sha256": ["abc", "other"]}), &ctx)
+                .unwrap_err()
+                .code(),
+            "invalid_argument"
+        );
+        assert_eq!(
+            apply(json!({"hmac_sha256": ["abc", 1]}), &ctx)
+                .unwrap_err()
+                .code(),
+            "invalid_argument"
+        );
+        assert_eq!(
+            apply(
+                json!({"hmac_sha256": ["abc", "signing"]}),
+                &Context::default()
+            )
+            .unwrap_err()
+            .code(),
+            "invalid_operation"
+        );
+        // The keys aren't exposed by the context's debug representation
+        assert!(!format!("{:?}", ctx).contains("[107, 101, 121]"));
+    }
+
+    #[test]
+    fn test_untranslatable() {
+        let rule = Rule::from_value(&json!({"sha256": "a"})).unwrap();
+        assert_eq!(rule.to_javascript().unwrap_err().code(), "untranslatable");
+    }
+}
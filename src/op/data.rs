@@ -1,38 +1,50 @@
 //! Data Operators
 
-use std::borrow::Cow;
-use std::convert::TryFrom;
-use std::convert::TryInto;
+use alloc::borrow::Cow;
+use core::convert::TryFrom;
+use core::convert::TryInto;
+use core::ops::Deref;
 
-use serde_json::Value;
+use serde_json::{Map, Value};
 
+use crate::arena::ArenaSlice;
+use crate::context::{Env, SemanticsVersion};
 use crate::error::Error;
-use crate::value::{Evaluated, Parsed};
-use crate::NULL;
+use crate::namespace::Scope;
+use crate::op::{op_args, single_entry, NumParams};
+use crate::prelude::*;
+use crate::resolver::DataResolver;
+use crate::value::{Evaluated, Parsed, Raw};
+use crate::{Parser, NULL};
 
 /// Valid types of variable keys
+#[derive(Clone)]
 enum KeyType<'a> {
     Null,
     String(Cow<'a, str>),
     Number(i64),
+    /// A JSON Pointer (RFC 6901), given as `{"pointer": "/a/b"}`
+    Pointer(Cow<'a, str>),
+}
+impl KeyType<'_> {
+    /// The key as a string. Keys with equal strings have the same path,
+    /// except for pointers, which have no string form.
+    fn as_str(&self) -> Option<Cow<'_, str>> {
+        match self {
+            Self::Null => Some(Cow::Borrowed("")),
+            Self::String(s) => Some(Cow::Borrowed(s)),
+            Self::Number(i) => Some(Cow::Owned(i.to_string())),
+            Self::Pointer(_) => None,
+        }
+    }
 }
 impl<'a> TryFrom<Value> for KeyType<'a> {
     type Error = Error;
 
     fn try_from(value: Value) -> Result<Self, Self::Error> {
         match value {
-            Value::Null => Ok(Self::Null),
             Value::String(s) => Ok(Self::String(Cow::from(s))),
-            Value::Number(n) => Ok(Self::Number(n.as_i64().ok_or(
-                Error::InvalidVariableKey {
-                    value: Value::Number(n),
-                    reason: "Numeric keys must be valid integers".into(),
-                },
-            )?)),
-            _ => Err(Error::InvalidVariableKey {
-                value: value.clone(),
-                reason: "Variable keys must be strings, integers, or null".into(),
-            }),
+            value => KeyType::try_from(&value).map(KeyType::into_owned),
         }
     }
 }
@@ -49,10 +61,31 @@ impl<'a> TryFrom<&'a Value> for KeyType<'a> {
                     reason: "Numeric keys must be valid integers".into(),
                 },
             )?)),
-            _ => Err(Error::InvalidVariableKey {
-                value: value.clone(),
-                reason: "Variable keys must be strings, integers, or null".into(),
-            }),
+            _ => match pointer(value) {
+                Some(Value::String(p)) if p.is_empty() || p.starts_with('/') => {
+                    Ok(Self::Pointer(Cow::from(p)))
+                }
+                Some(_) => Err(Error::InvalidVariableKey {
+                    value: value.clone(),
+                    reason: "JSON Pointers must be strings that are empty or start with '/'"
+                        .into(),
+                }),
+                None => Err(Error::InvalidVariableKey {
+                    value: value.clone(),
+                    reason: "Variable keys must be strings, integers, null, or JSON Pointers"
+                        .into(),
+                }),
+            },
+        }
+    }
+}
+impl KeyType<'_> {
+    fn into_owned<'b>(self) -> KeyType<'b> {
+        match self {
+            Self::Null => KeyType::Null,
+            Self::String(s) => KeyType::String(Cow::Owned(s.into_owned())),
+            Self::Number(i) => KeyType::Number(i),
+            Self::Pointer(p) => KeyType::Pointer(Cow::Owned(p.into_owned())),
         }
     }
 }
@@ -67,69 +100,619 @@ impl<'a> TryFrom<Evaluated<'a>> for KeyType<'a> {
     }
 }
 
-/// A get operation that supports negative indexes
-fn get<T>(slice: &[T], idx: i64) -> Option<&T> {
-    let vec_len = slice.len();
-    let usize_idx: usize = idx.abs().try_into().ok()?;
+/// One segment of a path into the data
+///
+/// Segments are used as keys into objects. Segments that are valid integers
+/// may also be used as indices into arrays and strings, counting from the
+/// end if they're negative, e.g. `items.-1` for the last item.
+///
+/// In dotted paths, `*` and `**` are wildcards: `*` matches each element of
+/// an array or value of an object, and `**` matches a value and each of its
+/// descendants, recursively. `start:end` slices an array or string, from
+/// `start` up to but not including `end`, either of which may be omitted or
+/// negative, e.g. `items.1:3` or `items.-2:`. See `resolve()` for how paths
+/// with wildcards and slices are resolved.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathSegment {
+    key: String,
+    index: Option<i64>,
+    pattern: Option<Pattern>,
+}
+impl PathSegment {
+    /// Construct a segment from a single key, without splitting on dots.
+    ///
+    /// The segment is always a key, even if it is `*`, `**`, or a slice.
+    pub fn new<S: Into<String>>(key: S) -> Self {
+        let key = key.into();
+        let index = key.parse::<i64>().ok();
+        Self {
+            key,
+            index,
+            pattern: None,
+        }
+    }
+
+    /// A segment matching each child of a value, i.e. `*`
+    pub fn wildcard() -> Self {
+        Self {
+            key: WILDCARD.into(),
+            index: None,
+            pattern: Some(Pattern::Children),
+        }
+    }
+
+    /// A segment matching a value and each of its descendants, i.e. `**`
+    pub fn recursive() -> Self {
+        Self {
+            key: RECURSIVE.into(),
+            index: None,
+            pattern: Some(Pattern::Descendants),
+        }
+    }
+
+    /// A segment slicing an array or string, i.e. `start:end`
+    pub fn slice(start: Option<i64>, end: Option<i64>) -> Self {
+        let bound =
+            |bound: Option<i64>| bound.map(|b| b.to_string()).unwrap_or_default();
+        Self {
+            key: format!("{}:{}", bound(start), bound(end)),
+            index: None,
+            pattern: Some(Pattern::Slice { start, end }),
+        }
+    }
+
+    /// A segment referring to the data outside of the innermost iterating
+    /// operator, i.e. a leading `../`, or `..` at the end of a key, as
+    /// `written`
+    pub(crate) fn parent(written: &str) -> Self {
+        Self {
+            key: written.into(),
+            index: None,
+            pattern: Some(Pattern::Parent),
+        }
+    }
+
+    /// Whether the segment is a leading `../`, rather than a key
+    pub(crate) fn is_parent(&self) -> bool {
+        self.pattern == Some(Pattern::Parent)
+    }
+
+    /// Whether the segment is `*` or `**`, rather than a key
+    pub fn is_wildcard(&self) -> bool {
+        matches!(
+            self.pattern,
+            Some(Pattern::Children) | Some(Pattern::Descendants)
+        )
+    }
+
+    /// Whether the segment is `**`
+    pub fn is_recursive(&self) -> bool {
+        self.pattern == Some(Pattern::Descendants)
+    }
+
+    /// Whether the segment is a slice, rather than a key
+    pub fn is_slice(&self) -> bool {
+        matches!(self.pattern, Some(Pattern::Slice { .. }))
+    }
+
+    /// A segment of a dotted path, which may be a wildcard or slice
+    fn parse(key: &str) -> Self {
+        match key {
+            WILDCARD => Self::wildcard(),
+            RECURSIVE => Self::recursive(),
+            key => match key.split_once(':') {
+                Some((start, end)) => match (bound(start), bound(end)) {
+                    // Keep the key as written, for semantics versions under
+                    // which it's a key
+                    (Some(start), Some(end)) => Self {
+                        key: key.into(),
+                        index: None,
+                        pattern: Some(Pattern::Slice { start, end }),
+                    },
+                    _ => Self::new(key),
+                },
+                None => Self::new(key),
+            },
+        }
+    }
+
+    /// The segment as an object key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// The segment as an array or string index, if it is an integer
+    pub fn index(&self) -> Option<i64> {
+        self.index
+    }
+}
+impl From<i64> for PathSegment {
+    fn from(index: i64) -> Self {
+        Self {
+            key: index.to_string(),
+            index: Some(index),
+            pattern: None,
+        }
+    }
+}
+
+const WILDCARD: &str = "*";
+const RECURSIVE: &str = "**";
+
+/// A segment that isn't a key
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Pattern {
+    /// `*`
+    Children,
+    /// `**`
+    Descendants,
+    /// `start:end`
+    Slice {
+        start: Option<i64>,
+        end: Option<i64>,
+    },
+    /// A leading `../`
+    Parent,
+}
+
+/// Parse one bound of a slice, which is either empty or an integer.
+fn bound(bound: &str) -> Option<Option<i64>> {
+    match bound {
+        "" => Some(None),
+        bound => bound.parse().ok().map(Some),
+    }
+}
+
+/// The variable key for the index of the element that an iterating
+/// operator, such as `map`, is evaluating its expression against
+pub(crate) const INDEX: &str = "#index";
+
+/// The prefix of variable keys referring to the data outside of the
+/// innermost iterating operator, e.g. `../threshold` within `filter`
+const PARENT: &str = "..";
+
+/// Look up a variable's path in the data, or for `#index` within an
+/// iterating operator's expression under `SemanticsVersion::V2`, the index
+/// of the current element.
+///
+/// Paths starting with `../` are looked up in the data outside of the
+/// innermost iterating operator, and for each additional `../`, the one
+/// outside of that. They are missing if there are fewer operators.
+pub(crate) fn resolve_variable<'a>(
+    data: &'a dyn DataResolver,
+    path: &[PathSegment],
+    env: &Env,
+) -> Result<Option<Cow<'a, Value>>, Error> {
+    let path = versioned(path, env.semantics());
+    let levels = path
+        .iter()
+        .take_while(|segment| segment.is_parent())
+        .count();
+    if levels > 0 {
+        return match env.outer(levels) {
+            Some((data, env)) => Ok(resolve_variable(data, &path[levels..], env)?
+                .map(|value| Cow::Owned(value.into_owned()))),
+            None => Ok(None),
+        };
+    }
+    match (&*path, env.index()) {
+        ([segment], Some(index))
+            if segment.key() == INDEX && env.semantics() >= SemanticsVersion::V2 =>
+        {
+            Ok(Some(Cow::Owned(Value::from(index))))
+        }
+        _ => resolve(data, &path),
+    }
+}
 
-    let adjusted_idx = if idx >= 0 {
-        usize_idx
+/// The path to look up under a semantics version.
+///
+/// Before `SemanticsVersion::V2`, every segment of a dotted path was a key,
+/// so wildcards and slices are looked up as the keys they were written as,
+/// and a path with a leading `../` is split again as a whole, as though the
+/// `../` were part of its first key.
+pub(crate) fn versioned(
+    path: &[PathSegment],
+    semantics: SemanticsVersion,
+) -> Cow<'_, [PathSegment]> {
+    let is_key = |segment: &PathSegment| segment.pattern.is_some();
+    if semantics >= SemanticsVersion::V2 || !path.iter().any(is_key) {
+        return Cow::Borrowed(path);
+    }
+    let levels = path
+        .iter()
+        .take_while(|segment| segment.is_parent())
+        .count();
+    if levels > 0 {
+        let parents: String = path[..levels].iter().map(PathSegment::key).collect();
+        let rest: Vec<&str> = path[levels..].iter().map(PathSegment::key).collect();
+        return Cow::Owned(
+            (parents + &rest.join("."))
+                .split('.')
+                .map(PathSegment::new)
+                .collect(),
+        );
+    }
+    Cow::Owned(
+        path.iter()
+            .map(|segment| {
+                if is_key(segment) {
+                    PathSegment::new(segment.key())
+                } else {
+                    segment.clone()
+                }
+            })
+            .collect(),
+    )
+}
+
+/// The value of a variable's default under a semantics version.
+///
+/// Before `SemanticsVersion::V2`, a default whose value was an object was
+/// parsed and evaluated again as a rule, so `{"var": ["zz", {"var": "x"}]}`
+/// resolved to the value of `y` when `x` held `{"var": "y"}`.
+pub(crate) fn default_value<'a>(
+    default: Evaluated<'a>,
+    data: &Value,
+    env: &Env,
+) -> Result<Evaluated<'a>, Error> {
+    if env.semantics() >= SemanticsVersion::V2 || !default.is_object() {
+        return Ok(default);
+    }
+    let parsed = Parsed::from_value(&default, &Scope::default())?;
+    Ok(Evaluated::New(parsed.evaluate(data, env)?.into()))
+}
+
+/// Look up a path, which may contain wildcards and slices, in the data.
+///
+/// A path with wildcards resolves to an array of every value it matches:
+/// array elements are matched in order, object values in the order of
+/// their keys, and `**` matches a value before its descendants. Values that
+/// are missing the segments after a wildcard are skipped. The path is only
+/// missing if the part of it before its first wildcard is.
+///
+/// Slices of arrays are arrays, and of strings, strings. Slicing any other
+/// value is an error.
+pub(crate) fn resolve<'a>(
+    data: &'a dyn DataResolver,
+    path: &[PathSegment],
+) -> Result<Option<Cow<'a, Value>>, Error> {
+    let i = match path.iter().position(|segment| segment.pattern.is_some()) {
+        Some(i) => i,
+        None => return Ok(data.resolve(path)),
+    };
+    let base = match data.resolve(&path[..i]) {
+        Some(base) => base,
+        None => return Ok(None),
+    };
+    if path[i..].iter().any(PathSegment::is_wildcard) {
+        let mut matches = Vec::new();
+        expand(&base, &path[i..], path, &mut matches)?;
+        Ok(Some(Cow::Owned(Value::Array(matches))))
     } else {
-        vec_len.checked_sub(usize_idx)?
+        follow(&base, &path[i..], path).map(|value| value.map(Cow::Owned))
+    }
+}
+
+/// Look up a path without wildcards within a value. `full` is the whole
+/// path, for error messages.
+fn follow(
+    value: &Value,
+    path: &[PathSegment],
+    full: &[PathSegment],
+) -> Result<Option<Value>, Error> {
+    let end = path
+        .iter()
+        .position(PathSegment::is_slice)
+        .unwrap_or(path.len());
+    let found = match value.resolve(&path[..end]) {
+        Some(found) => found,
+        None => return Ok(None),
     };
+    match path.get(end) {
+        Some(segment) => follow(&slice(&found, segment, full)?, &path[end + 1..], full),
+        None => Ok(Some(found.into_owned())),
+    }
+}
 
-    slice.get(adjusted_idx)
+/// Collect the values matching a path within a value.
+fn expand(
+    value: &Value,
+    path: &[PathSegment],
+    full: &[PathSegment],
+    matches: &mut Vec<Value>,
+) -> Result<(), Error> {
+    let segment = match path.first() {
+        Some(segment) => segment,
+        None => {
+            matches.push(value.clone());
+            return Ok(());
+        }
+    };
+    match segment.pattern {
+        Some(Pattern::Children) => children(value)
+            .into_iter()
+            .try_for_each(|child| expand(child, &path[1..], full, matches)),
+        Some(Pattern::Descendants) => {
+            expand(value, &path[1..], full, matches)?;
+            children(value)
+                .into_iter()
+                .try_for_each(|child| expand(child, path, full, matches))
+        }
+        Some(Pattern::Slice { .. }) => {
+            expand(&slice(value, segment, full)?, &path[1..], full, matches)
+        }
+        // Only variables may refer to outer data
+        Some(Pattern::Parent) => Ok(()),
+        None => {
+            let end = path
+                .iter()
+                .position(|segment| segment.pattern.is_some())
+                .unwrap_or(path.len());
+            match value.resolve(&path[..end]) {
+                Some(found) => expand(&found, &path[end..], full, matches),
+                None => Ok(()),
+            }
+        }
+    }
+}
+
+/// The elements of an array, or the values of an object ordered by key
+fn children(value: &Value) -> Vec<&Value> {
+    match value {
+        Value::Array(elements) => elements.iter().collect(),
+        Value::Object(obj) => {
+            let mut entries: Vec<(&String, &Value)> = obj.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            entries.into_iter().map(|(_, value)| value).collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Slice an array or string with a slice segment.
+fn slice(
+    value: &Value,
+    segment: &PathSegment,
+    full: &[PathSegment],
+) -> Result<Value, Error> {
+    let (start, end) = match segment.pattern {
+        Some(Pattern::Slice { start, end }) => (start, end),
+        _ => return Ok(value.clone()),
+    };
+    // Bounds count from the end if they're negative, and are clamped to the
+    // length of the value
+    let range = |len: usize| {
+        let clamp = |bound: i64| {
+            let bound = if bound < 0 { len as i64 + bound } else { bound };
+            bound.clamp(0, len as i64) as usize
+        };
+        let start = start.map_or(0, clamp);
+        let end = end.map_or(len, clamp);
+        start..end.max(start)
+    };
+    match value {
+        Value::Array(elements) => {
+            Ok(Value::Array(elements[range(elements.len())].to_vec()))
+        }
+        Value::String(s) => {
+            let chars: Vec<char> = s.chars().collect();
+            Ok(Value::String(chars[range(chars.len())].iter().collect()))
+        }
+        value => Err(Error::InvalidVariable {
+            value: Value::String(
+                full.iter()
+                    .map(PathSegment::key)
+                    .collect::<Vec<&str>>()
+                    .join("."),
+            ),
+            reason: format!(
+                "Cannot slice {} with '{}': only arrays and strings may be sliced",
+                value,
+                segment.key()
+            ),
+        }),
+    }
+}
+
+/// The pointer of a `{"pointer": ...}` variable key, if the value is one
+pub(crate) fn pointer(value: &Value) -> Option<&Value> {
+    match value {
+        Value::Object(obj) if obj.len() == 1 => obj.get(POINTER),
+        _ => None,
+    }
+}
+
+/// The key of JSON Pointer variable keys
+const POINTER: &str = "pointer";
+
+/// Split a variable key into its path segments.
+///
+/// Null and empty keys refer to the data as a whole, and so have no segments.
+/// Each leading `../` of a string key is a parent segment. JSON Pointers are
+/// split on `/`, so that their segments may contain dots, with `~1` and `~0`
+/// unescaped to `/` and `~`.
+fn split_key(key: KeyType) -> Vec<PathSegment> {
+    match key {
+        KeyType::Null => vec![],
+        KeyType::String(k) if k.is_empty() => vec![],
+        KeyType::String(k) if k.starts_with(PARENT) => {
+            let mut segments = Vec::new();
+            let mut rest: &str = &k;
+            while let Some(inner) = rest.strip_prefix(PARENT) {
+                let next = match inner.strip_prefix('/') {
+                    Some(inner) => inner,
+                    None if inner.is_empty() => inner,
+                    None => break,
+                };
+                segments.push(PathSegment::parent(&rest[..rest.len() - next.len()]));
+                rest = next;
+            }
+            if !rest.is_empty() {
+                segments.extend(rest.split('.').map(PathSegment::parse));
+            }
+            segments
+        }
+        KeyType::String(k) => k.split('.').map(PathSegment::parse).collect(),
+        KeyType::Number(i) => vec![i.into()],
+        KeyType::Pointer(p) => p
+            .split('/')
+            .skip(1)
+            .map(|segment| {
+                PathSegment::new(segment.replace("~1", "/").replace("~0", "~"))
+            })
+            .collect(),
+    }
+}
+
+/// Split an evaluated variable key into its path segments.
+pub(crate) fn key_path(key: Evaluated) -> Result<Vec<PathSegment>, Error> {
+    key.try_into().map(split_key)
 }
 
 /// Retrieve a variable from the data
 ///
+/// Variables are parsed separately from the other data operators so that
+/// static keys may be split into their path segments only once.
+///
 /// Note that the reference implementation does not support negative
 /// indexing for numeric values, but we do.
-pub fn var(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
-    let arg_count = args.len();
-    if arg_count == 0 {
-        return Ok(data.clone());
-    };
+#[derive(Clone, Debug)]
+pub struct Variable {
+    arguments: ArenaSlice<Parsed>,
+    /// The pre-split path, if the key is a static value
+    path: Option<ArenaSlice<PathSegment>>,
+}
+impl Variable {
+    pub(crate) const OPERATOR: &'static str = "var";
+    pub(crate) const NUM_PARAMS: NumParams = NumParams::Variadic(0..3);
 
-    let key = args[0].try_into()?;
-    let val = get_key(data, key);
+    /// The key and default value expressions, if present
+    pub(crate) fn arguments(&self) -> &[Parsed] {
+        &self.arguments
+    }
 
-    Ok(val.unwrap_or(if arg_count < 2 {
-        NULL
-    } else {
-        let _parsed_default = Parsed::from_value(args[1])?;
-        _parsed_default.evaluate(&data)?.into()
-    }))
+    /// The pre-split path, if the key is a static value
+    pub(crate) fn path(&self) -> Option<&[PathSegment]> {
+        self.path.as_deref()
+    }
+
+    /// Look up the variable, ignoring any default, or return None if it
+    /// isn't present.
+    pub(crate) fn lookup(
+        &self,
+        data: &Value,
+        env: &Env,
+    ) -> Result<Option<Value>, Error> {
+        let resolver = env.resolver().unwrap_or(data);
+        let val = match &self.path {
+            Some(path) => resolve_variable(resolver, path, env)?,
+            None => resolve_variable(
+                resolver,
+                &key_path(self.arguments[0].evaluate(data, env)?)?,
+                env,
+            )?,
+        };
+        Ok(val.map(Cow::into_owned))
+    }
+}
+impl Parser for Variable {
+    fn from_value(value: &Value, scope: &Scope) -> Result<Option<Self>, Error> {
+        let args = match single_entry(value)? {
+            Some((key, val)) if key == Self::OPERATOR => {
+                op_args(key, val, &Self::NUM_PARAMS)?
+            }
+            _ => return Ok(None),
+        };
+        // Pointers would otherwise be parsed as operations
+        let arguments = match args.split_first() {
+            Some((key, rest)) if pointer(key).is_some() => {
+                KeyType::try_from(key)?;
+                scope.slice(
+                    core::iter::once(Ok(Parsed::Raw(Raw::new(key.clone()))))
+                        .chain(rest.iter().map(|arg| Parsed::from_value(arg, scope))),
+                )?
+            }
+            _ => Parsed::from_values(args, scope)?,
+        };
+        let path = match arguments.first() {
+            None => Some(scope.path("", Vec::new)),
+            Some(Parsed::Raw(raw)) => {
+                let key: KeyType = raw.value().try_into()?;
+                Some(match key.as_str() {
+                    Some(k) => scope.path(&k, || split_key(key.clone())),
+                    None => split_key(key).into(),
+                })
+            }
+            Some(_) => None,
+        };
+        Ok(Some(Self { arguments, path }))
+    }
+
+    fn evaluate(&self, data: &Value, env: &Env) -> Result<Evaluated<'_>, Error> {
+        match (self.lookup(data, env)?, self.arguments.get(1)) {
+            (Some(val), _) => Ok(Evaluated::New(val)),
+            (None, None) => Ok(Evaluated::New(NULL)),
+            (None, Some(default)) => {
+                default_value(default.evaluate(data, env)?, data, env)
+            }
+        }
+    }
+}
+impl From<Variable> for Value {
+    fn from(var: Variable) -> Self {
+        let mut rv = Map::with_capacity(1);
+        let values = var.arguments.into_iter().map(Value::from).collect();
+        rv.insert(Variable::OPERATOR.into(), Value::Array(values));
+        Value::Object(rv)
+    }
+}
+
+/// Look up a key in one of the tables provided by the context
+pub fn lookup(args: &[Evaluated], env: &Env) -> Result<Value, Error> {
+    let (table, key) = (&*args[0], &*args[1]);
+    let table_name = table.as_str().ok_or_else(|| {
+        Error::invalid_argument(table.clone(), "lookup", "Table names must be strings")
+    })?;
+    if let Some(resolver) = env.lookup() {
+        return resolver
+            .lookup(table_name, key)
+            .map(|value| value.unwrap_or(NULL));
+    }
+    #[cfg(feature = "async")]
+    if let (Some(operator), Some(state)) = (env.async_lookup(), env.asynchronous()) {
+        return state.call("lookup", operator, &[table, key]);
+    }
+    Err(Error::InvalidOperation {
+        key: "lookup".into(),
+        reason: "No lookup resolver was provided in the evaluation context".into(),
+    })
 }
 
 /// Check for keys that are missing from the data
-pub fn missing(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
+pub fn missing(
+    data: &dyn DataResolver,
+    args: &[Evaluated],
+    env: &Env,
+) -> Result<Value, Error> {
     let mut missing_keys: Vec<Value> = Vec::new();
 
     // This bit of insanity is because for some reason the reference
     // implementation is tested to do this, i.e. if missing is passed
     // multiple args and the first arg is an array, _that_ array is
     // treated as the only argument.
-    let inner_vec: Vec<&Value>;
-    let adjusted_args = if args.len() > 0 {
-        match args[0] {
-            Value::Array(vals) => {
-                inner_vec = vals.iter().collect();
-                &inner_vec
-            }
-            _ => args,
-        }
-    } else {
-        args
+    let adjusted_args: Vec<&Value> = match args.first().map(Deref::deref) {
+        Some(Value::Array(vals)) => vals.iter().collect(),
+        _ => args.iter().map(Deref::deref).collect(),
     };
 
     adjusted_args.into_iter().fold(Ok(()), |had_error, arg| {
         had_error?;
-        let key: KeyType = (*arg).try_into()?;
+        let key: KeyType = arg.try_into()?;
         match key {
             KeyType::Null => Ok(()),
             _ => {
-                let val = get_key(data, key);
+                let val = get_key(data, key, env)?;
                 if val.is_none() {
                     missing_keys.push((*arg).clone());
                 };
@@ -148,8 +731,12 @@ pub fn missing(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
 /// to or more than the threshold value _present_ in the data, an empty
 /// array is returned. Otherwise, an array containing all missing keys
 /// is returned.
-pub fn missing_some(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
-    let (threshold_arg, keys_arg) = (args[0], args[1]);
+pub fn missing_some(
+    data: &dyn DataResolver,
+    args: &[Evaluated],
+    env: &Env,
+) -> Result<Value, Error> {
+    let (threshold_arg, keys_arg) = (&*args[0], &*args[1]);
 
     let threshold = match threshold_arg {
         Value::Number(n) => n.as_u64(),
@@ -189,7 +776,9 @@ pub fn missing_some(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
             // since they aren't valid Object or Array keys in JSON.
             KeyType::Null => prev_present_count,
             _ => {
-                if get_key(data, parsed_key).is_none() && !missing_keys.contains(key) {
+                if get_key(data, parsed_key, env)?.is_none()
+                    && !missing_keys.contains(key)
+                {
                     missing_keys.push((*key).clone());
                     prev_present_count
                 } else {
@@ -209,56 +798,265 @@ pub fn missing_some(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
     }
 }
 
-fn get_key(data: &Value, key: KeyType) -> Option<Value> {
-    match key {
-        // If the key is null, we return the data, always, even if there
-        // is a default parameter.
-        KeyType::Null => return Some(data.clone()),
-        KeyType::String(k) => get_str_key(data, k),
-        KeyType::Number(i) => match data {
-            Value::Object(_) => get_str_key(data, i.to_string()),
-            Value::Array(arr) => get(arr, i).map(Value::clone),
-            Value::String(s) => {
-                let s_vec: Vec<char> = s.chars().collect();
-                get(&s_vec, i).map(|c| c.to_string()).map(Value::String)
-            }
-            _ => None,
-        },
-    }
+fn get_key<'a>(
+    data: &'a dyn DataResolver,
+    key: KeyType,
+    env: &Env,
+) -> Result<Option<Cow<'a, Value>>, Error> {
+    resolve(data, &versioned(&split_key(key), env.semantics()))
 }
 
-fn get_str_key<K: AsRef<str>>(data: &Value, key: K) -> Option<Value> {
-    let k = key.as_ref();
-    if k == "" {
-        return Some(data.clone());
-    };
-    match data {
-        Value::Object(_) | Value::Array(_) | Value::String(_) => {
-            // Exterior ref in case we need to make a new value in the match.
-            k.split(".").fold(Some(data.clone()), |acc, i| match acc? {
-                // If the current value is an object, try to get the value
-                Value::Object(map) => map.get(i).map(Value::clone),
-                // If the current value is an array, we need an integer
-                // index. If integer conversion fails, return None.
-                Value::Array(arr) => i
-                    .parse::<i64>()
-                    .ok()
-                    .and_then(|i| get(&arr, i))
-                    .map(Value::clone),
-                // Same deal if it's a string.
-                Value::String(s) => {
-                    let s_chars: Vec<char> = s.chars().collect();
-                    i.parse::<i64>()
-                        .ok()
-                        .and_then(|i| get(&s_chars, i))
-                        .map(|c| c.to_string())
-                        .map(Value::String)
-                }
-                // This handles cases where we've got an un-indexable
-                // type or similar.
-                _ => None,
-            })
+#[cfg(test)]
+mod test_variable {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_static_paths_are_split() {
+        let var = Variable::from_value(&json!({"var": "a.1.b"}), &Scope::default())
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            var.path(),
+            Some(
+                &[
+                    PathSegment::new("a"),
+                    PathSegment::from(1),
+                    PathSegment::new("b")
+                ][..]
+            )
+        );
+        assert_eq!(
+            Variable::from_value(&json!({"var": [null]}), &Scope::default())
+                .unwrap()
+                .unwrap()
+                .path(),
+            Some(&[][..])
+        );
+        assert_eq!(
+            Variable::from_value(&json!({"var": -2}), &Scope::default())
+                .unwrap()
+                .unwrap()
+                .path(),
+            Some(&[PathSegment::from(-2)][..])
+        );
+    }
+
+    #[test]
+    fn test_dynamic_paths_are_not_split() {
+        let var = Variable::from_value(
+            &json!({"var": {"cat": ["a", ".b"]}}),
+            &Scope::default(),
+        )
+        .unwrap()
+        .unwrap();
+        assert!(var.path.is_none());
+        assert_eq!(
+            var.evaluate(&json!({"a": {"b": 3}}), &Env::default())
+                .map(Value::from)
+                .unwrap(),
+            json!(3)
+        );
+    }
+
+    #[test]
+    fn test_invalid_static_key() {
+        Variable::from_value(&json!({"var": [true]}), &Scope::default()).unwrap_err();
+    }
+
+    #[test]
+    fn test_wildcards() {
+        let data = json!({
+            "items": [
+                {"id": 1, "price": 5, "tags": {"b": {"id": 4}, "a": {"id": 3}}},
+                {"id": 2},
+                {"id": 5, "price": 7},
+            ],
+            "empty": [],
+            "*": "star",
+        });
+        let env = Env::default().with_semantics(Some(SemanticsVersion::V2));
+        let apply = |var: Value| {
+            Variable::from_value(&var, &Scope::default())
+                .unwrap()
+                .unwrap()
+                .evaluate(&data, &env)
+                .map(Value::from)
+                .unwrap()
+        };
+        // Items without a price are skipped
+        assert_eq!(apply(json!({"var": "items.*.price"})), json!([5, 7]));
+        // Object values are matched in the order of their keys
+        assert_eq!(apply(json!({"var": "items.0.tags.*.id"})), json!([3, 4]));
+        // Values are matched before their descendants
+        assert_eq!(apply(json!({"var": "**.id"})), json!([1, 3, 4, 2, 5]));
+        assert_eq!(apply(json!({"var": "items.**.id"})), json!([1, 3, 4, 2, 5]));
+        assert_eq!(apply(json!({"var": "items.*.nope"})), json!([]));
+        assert_eq!(apply(json!({"var": ["empty.*", "default"]})), json!([]));
+        assert_eq!(apply(json!({"var": "*.1.id"})), json!([2]));
+        // Only the part of the path before a wildcard may be missing
+        assert_eq!(
+            apply(json!({"var": ["nope.*.id", "default"]})),
+            json!("default")
+        );
+        assert_eq!(apply(json!({"var": "nope.*.id"})), json!(null));
+        // Pointers have no wildcards
+        assert_eq!(apply(json!({"var": {"pointer": "/*"}})), json!("star"));
+
+        let var = Variable::from_value(&json!({"var": "a.*.**"}), &Scope::default())
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            var.path(),
+            Some(
+                &[
+                    PathSegment::new("a"),
+                    PathSegment::wildcard(),
+                    PathSegment::recursive()
+                ][..]
+            )
+        );
+        assert!(!PathSegment::new("*").is_wildcard());
+
+        let keys = [
+            Evaluated::Raw(&json!("items.*.nope")),
+            Evaluated::Raw(&json!("nope.*")),
+        ];
+        assert_eq!(missing(&data, &keys, &env).unwrap(), json!(["nope.*"]));
+
+        // Before V2, wildcards are keys
+        let v1 = Env::default();
+        let var = Variable::from_value(&json!({"var": "*"}), &Scope::default())
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            Value::from(var.evaluate(&data, &v1).unwrap()),
+            json!("star")
+        );
+        let var =
+            Variable::from_value(&json!({"var": "items.*.price"}), &Scope::default())
+                .unwrap()
+                .unwrap();
+        assert_eq!(Value::from(var.evaluate(&data, &v1).unwrap()), json!(null));
+        assert_eq!(
+            missing(&data, &keys, &v1).unwrap(),
+            json!(["items.*.nope", "nope.*"])
+        );
+    }
+
+    #[test]
+    fn test_slices() {
+        let data = json!({"items": [1, 2, 3, 4], "s": "héllo", "o": {"1:2": "key"}});
+        let env = Env::default().with_semantics(Some(SemanticsVersion::V2));
+        let apply = |var: Value| {
+            Variable::from_value(&var, &Scope::default())
+                .unwrap()
+                .unwrap()
+                .evaluate(&data, &env)
+                .map(Value::from)
+        };
+        assert_eq!(apply(json!({"var": "items.-1"})).unwrap(), json!(4));
+        assert_eq!(apply(json!({"var": "items.1:3"})).unwrap(), json!([2, 3]));
+        assert_eq!(apply(json!({"var": "items.-2:"})).unwrap(), json!([3, 4]));
+        assert_eq!(apply(json!({"var": "items.:-3"})).unwrap(), json!([1]));
+        assert_eq!(
+            apply(json!({"var": "items.:"})).unwrap(),
+            json!([1, 2, 3, 4])
+        );
+        assert_eq!(apply(json!({"var": "items.3:1"})).unwrap(), json!([]));
+        assert_eq!(apply(json!({"var": "items.1:9.0"})).unwrap(), json!(2));
+        assert_eq!(apply(json!({"var": "s.1:3"})).unwrap(), json!("él"));
+        // Keys that aren't slices are looked up as-is
+        assert_eq!(
+            apply(json!({"var": ["o.1:a", "default"]})).unwrap(),
+            json!("default")
+        );
+        assert_eq!(
+            apply(json!({"var": {"pointer": "/o/1:2"}})).unwrap(),
+            json!("key")
+        );
+        assert_eq!(apply(json!({"var": "nope.1:2"})).unwrap(), json!(null));
+
+        match apply(json!({"var": "o.1:2"})).unwrap_err() {
+            Error::InvalidVariable { value, reason } => {
+                assert_eq!(value, json!("o.1:2"));
+                assert_eq!(
+                    reason,
+                    "Cannot slice {\"1:2\":\"key\"} with '1:2': only arrays and strings \
+                     may be sliced"
+                );
+            }
+            err => panic!("unexpected error: {}", err),
         }
-        _ => None,
+        assert_eq!(PathSegment::slice(Some(-1), None).key(), "-1:");
+
+        // Before V2, slices are keys
+        let v1 = |var: Value| {
+            Variable::from_value(&var, &Scope::default())
+                .unwrap()
+                .unwrap()
+                .evaluate(&data, &Env::default())
+                .map(Value::from)
+                .unwrap()
+        };
+        assert_eq!(v1(json!({"var": "o.1:2"})), json!("key"));
+        assert_eq!(v1(json!({"var": "items.1:3"})), json!(null));
+        assert_eq!(v1(json!({"var": "items.-1"})), json!(4));
+    }
+
+    #[test]
+    fn test_pointer() {
+        let data = json!({"a.b": {"c/d": [1, {"~e": 2}]}, "": 3});
+        let apply = |var: Value| {
+            Variable::from_value(&var, &Scope::default())
+                .unwrap()
+                .unwrap()
+                .evaluate(&data, &Env::default())
+                .map(Value::from)
+                .unwrap()
+        };
+        assert_eq!(apply(json!({"var": {"pointer": "/a.b/c~1d/0"}})), json!(1));
+        assert_eq!(
+            apply(json!({"var": {"pointer": "/a.b/c~1d/1/~0e"}})),
+            json!(2)
+        );
+        assert_eq!(apply(json!({"var": {"pointer": "/"}})), json!(3));
+        assert_eq!(apply(json!({"var": {"pointer": ""}})), data);
+        assert_eq!(apply(json!({"var": [{"pointer": "/a.b/x"}, 4]})), json!(4));
+        assert_eq!(apply(json!({"var": "a.b"})), json!(null));
+        // Pointers may be computed, too
+        assert_eq!(
+            apply(json!({"var": {"lit": {"pointer": "/a.b/c~1d/0"}}})),
+            json!(1)
+        );
+
+        let var = Variable::from_value(
+            &json!({"var": {"pointer": "/a.b/0"}}),
+            &Scope::default(),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            var.path(),
+            Some(&[PathSegment::new("a.b"), PathSegment::from(0)][..])
+        );
+        assert_eq!(Value::from(var), json!({"var": [{"pointer": "/a.b/0"}]}));
+
+        Variable::from_value(&json!({"var": {"pointer": "a"}}), &Scope::default())
+            .unwrap_err();
+        Variable::from_value(&json!({"var": {"pointer": 1}}), &Scope::default())
+            .unwrap_err();
+        assert_eq!(
+            missing(
+                &data,
+                &[
+                    Evaluated::Raw(&json!({"pointer": "/a.b"})),
+                    Evaluated::Raw(&json!({"pointer": "/x"}))
+                ],
+                &Env::default()
+            )
+            .unwrap(),
+            json!([{"pointer": "/x"}])
+        );
     }
 }
@@ -0,0 +1,553 @@
+//! Dates and Durations
+//!
+//! Dates are RFC 3339 strings, either a date, e.g. `2024-03-01`, meaning
+//! its midnight in UTC, or a date and time with an offset, e.g.
+//! `2024-03-01T09:30:00Z` or `2024-03-01T09:30:00.25+01:00`. Resulting dates
+//! are in UTC, to the millisecond, e.g. `2024-03-01T08:30:00.250Z`, leaving
+//! out milliseconds if they're zero.
+//!
+//! Durations are ISO 8601 durations, e.g. `P1Y2M10DT2H30M` or `PT0.5S`, or
+//! shorthand whole numbers of weeks (`w`), days (`d`), hours (`h`), minutes
+//! (`m`), seconds (`s`), or milliseconds (`ms`), e.g. `7d` or `1h30m`, either
+//! of which may be negated with a leading `-`. Numbers are durations in
+//! seconds. Years and months are added to dates on the calendar, clamping
+//! the day to the end of shorter months, so a month after `2024-01-31` is
+//! `2024-02-29`. Since dates are in UTC, days are always 24 hours.
+
+use serde_json::Value;
+
+use crate::context::Env;
+use crate::error::Error;
+use crate::op::string::string_arg;
+use crate::prelude::*;
+use crate::value::{to_number_value, Evaluated};
+
+const MILLIS_PER_DAY: i64 = 86_400_000;
+
+/// A length of time, as calendar months and a fixed number of milliseconds
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Duration {
+    months: i64,
+    millis: i64,
+}
+impl Duration {
+    fn add_months(self, months: i64) -> Option<Self> {
+        Some(Self {
+            months: self.months.checked_add(months)?,
+            ..self
+        })
+    }
+
+    fn add_millis(self, millis: i64) -> Option<Self> {
+        Some(Self {
+            millis: self.millis.checked_add(millis)?,
+            ..self
+        })
+    }
+
+    fn negate(self) -> Self {
+        Self {
+            months: -self.months,
+            millis: -self.millis,
+        }
+    }
+}
+
+/// Split the leading ASCII digits from a string.
+fn leading_digits(text: &str) -> (&str, &str) {
+    let end = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    text.split_at(end)
+}
+
+/// Take exactly `count` ASCII digits from the start of a string.
+fn take_digits(text: &str, count: usize) -> Option<(i64, &str)> {
+    let digits = text.get(..count)?;
+    if !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    Some((digits.parse().ok()?, &text[count..]))
+}
+
+/// The milliseconds of the digits of a decimal fraction, truncating any
+/// beyond the third
+fn fraction_millis(digits: &str) -> i64 {
+    digits
+        .bytes()
+        .chain(core::iter::repeat(b'0'))
+        .take(3)
+        .fold(0, |millis, digit| millis * 10 + i64::from(digit - b'0'))
+}
+
+fn parse_duration(text: &str) -> Option<Duration> {
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let duration = match text.strip_prefix('P') {
+        Some(rest) => iso_duration(rest)?,
+        None => shorthand_duration(text)?,
+    };
+    Some(if negative {
+        duration.negate()
+    } else {
+        duration
+    })
+}
+
+/// An ISO 8601 duration, after its `P`
+fn iso_duration(text: &str) -> Option<Duration> {
+    let (date, time) = match text.split_once('T') {
+        Some((date, time)) if !time.is_empty() => (date, time),
+        Some(_) => return None,
+        None if !text.is_empty() => (text, ""),
+        None => return None,
+    };
+    let mut duration = Duration::default();
+    // Each designator may appear once, in order
+    let mut designators = "YMWD";
+    let mut rest = date;
+    while !rest.is_empty() {
+        let (digits, after) = leading_digits(rest);
+        let designator = after.chars().next()?;
+        designators = &designators[designators.find(designator)? + 1..];
+        let count: i64 = digits.parse().ok()?;
+        duration = match designator {
+            'Y' => duration.add_months(count.checked_mul(12)?)?,
+            'M' => duration.add_months(count)?,
+            'W' => duration.add_millis(count.checked_mul(7 * MILLIS_PER_DAY)?)?,
+            _ => duration.add_millis(count.checked_mul(MILLIS_PER_DAY)?)?,
+        };
+        rest = &after[1..];
+    }
+    let mut designators = "HMS";
+    let mut rest = time;
+    while !rest.is_empty() {
+        let (digits, after) = leading_digits(rest);
+        // Only seconds may have a fraction
+        let (fraction, after) = match after.strip_prefix(|c| c == '.' || c == ',') {
+            Some(fraction) => match leading_digits(fraction) {
+                ("", _) => return None,
+                (fraction, after) if after.starts_with('S') => {
+                    (fraction_millis(fraction), after)
+                }
+                _ => return None,
+            },
+            None => (0, after),
+        };
+        let designator = after.chars().next()?;
+        designators = &designators[designators.find(designator)? + 1..];
+        let scale = match designator {
+            'H' => 3_600_000,
+            'M' => 60_000,
+            _ => 1_000,
+        };
+        let count: i64 = digits.parse().ok()?;
+        duration =
+            duration.add_millis(count.checked_mul(scale)?.checked_add(fraction)?)?;
+        rest = &after[1..];
+    }
+    Some(duration)
+}
+
+/// A duration of whole numbers of units, e.g. `1h30m`
+fn shorthand_duration(text: &str) -> Option<Duration> {
+    if text.is_empty() {
+        return None;
+    }
+    let mut duration = Duration::default();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let (digits, after) = leading_digits(rest);
+        let end = after
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(after.len());
+        let (unit, after) = after.split_at(end);
+        let scale = match unit {
+            "w" => 7 * MILLIS_PER_DAY,
+            "d" => MILLIS_PER_DAY,
+            "h" => 3_600_000,
+            "m" => 60_000,
+            "s" => 1_000,
+            "ms" => 1,
+            _ => return None,
+        };
+        let count: i64 = digits.parse().ok()?;
+        duration = duration.add_millis(count.checked_mul(scale)?)?;
+        rest = after;
+    }
+    Some(duration)
+}
+
+fn duration_arg(value: &Value, operation: &'static str) -> Result<Duration, Error> {
+    match value {
+        Value::String(text) => parse_duration(text).ok_or_else(|| {
+            Error::invalid_argument(
+                value.clone(),
+                operation,
+                format!("'{}' is not a duration, e.g. \"P1DT2H\" or \"7d\"", text),
+            )
+        }),
+        Value::Number(number) => number
+            .as_f64()
+            .map(|seconds| seconds * 1000.0)
+            .filter(|millis| millis.abs() < i64::MAX as f64)
+            .map(|millis| Duration {
+                months: 0,
+                millis: millis as i64,
+            })
+            .ok_or_else(|| {
+                Error::invalid_argument(
+                    value.clone(),
+                    operation,
+                    "The duration is too long",
+                )
+            }),
+        _ => Err(Error::invalid_argument(
+            value.clone(),
+            operation,
+            "Durations must be strings or numbers of seconds",
+        )),
+    }
+}
+
+fn is_leap_year(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// The days since the Unix epoch of a date on the proleptic Gregorian
+/// calendar, by Howard Hinnant's algorithm
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era =
+        year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The year, month, and day of a number of days since the Unix epoch
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524
+        - day_of_era / 146_096)
+        / 365;
+    let day_of_year =
+        day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// A date, as milliseconds since the Unix epoch
+fn parse_date(text: &str) -> Option<i64> {
+    let (year, rest) = take_digits(text, 4)?;
+    let (month, rest) = take_digits(rest.strip_prefix('-')?, 2)?;
+    let (day, rest) = take_digits(rest.strip_prefix('-')?, 2)?;
+    if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+        return None;
+    }
+    let date = days_from_civil(year, month, day) * MILLIS_PER_DAY;
+    if rest.is_empty() {
+        return Some(date);
+    }
+    let rest = rest.strip_prefix(|c| matches!(c, 'T' | 't' | ' '))?;
+    let (hour, rest) = take_digits(rest, 2)?;
+    let (minute, rest) = take_digits(rest.strip_prefix(':')?, 2)?;
+    let (second, rest) = take_digits(rest.strip_prefix(':')?, 2)?;
+    let (millis, rest) = match rest.strip_prefix('.') {
+        Some(fraction) => match leading_digits(fraction) {
+            ("", _) => return None,
+            (digits, rest) => (fraction_millis(digits), rest),
+        },
+        None => (0, rest),
+    };
+    let offset = match rest {
+        "Z" | "z" => 0,
+        _ => {
+            let (sign, rest) = match rest.strip_prefix('+') {
+                Some(rest) => (1, rest),
+                None => (-1, rest.strip_prefix('-')?),
+            };
+            let (hours, rest) = take_digits(rest, 2)?;
+            let (minutes, rest) = take_digits(rest.strip_prefix(':')?, 2)?;
+            if !rest.is_empty() || hours > 23 || minutes > 59 {
+                return None;
+            }
+            sign * (hours * 60 + minutes) * 60_000
+        }
+    };
+    if hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+    Some(date + ((hour * 60 + minute) * 60 + second) * 1000 + millis - offset)
+}
+
+/// A date in UTC, if it's between the years 0 and 9999
+fn format_date(millis: i64) -> Option<String> {
+    let (days, time) = (
+        millis.div_euclid(MILLIS_PER_DAY),
+        millis.rem_euclid(MILLIS_PER_DAY),
+    );
+    let (year, month, day) = civil_from_days(days);
+    if !(0..=9999).contains(&year) {
+        return None;
+    }
+    let seconds = time / 1000;
+    let mut date = format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    );
+    if time % 1000 != 0 {
+        date.push_str(&format!(".{:03}", time % 1000));
+    }
+    date.push('Z');
+    Some(date)
+}
+
+fn date_arg(value: &Value, operation: &'static str) -> Result<i64, Error> {
+    value.as_str().and_then(parse_date).ok_or_else(|| {
+        Error::invalid_argument(
+            value.clone(),
+            operation,
+            "Dates must be RFC 3339 strings, e.g. \"2024-03-01\" or \"2024-03-01T09:30:00Z\"",
+        )
+    })
+}
+
+/// A date plus a duration, if it's between the years 0 and 9999
+fn add(date: i64, duration: Duration) -> Option<i64> {
+    let mut date = date;
+    if duration.months != 0 {
+        let (days, time) = (
+            date.div_euclid(MILLIS_PER_DAY),
+            date.rem_euclid(MILLIS_PER_DAY),
+        );
+        let (year, month, day) = civil_from_days(days);
+        let months = (year * 12 + month - 1).checked_add(duration.months)?;
+        let (year, month) = (months.div_euclid(12), months.rem_euclid(12) + 1);
+        if !(0..=9999).contains(&year) {
+            return None;
+        }
+        let day = day.min(days_in_month(year, month));
+        date = days_from_civil(year, month, day) * MILLIS_PER_DAY + time;
+    }
+    date.checked_add(duration.millis)
+}
+
+/// The number of seconds in a duration, which may not have years or months
+pub fn duration(items: &[Evaluated]) -> Result<Value, Error> {
+    string_arg(items, 0, "duration")?;
+    let duration = duration_arg(&items[0], "duration")?;
+    if duration.months != 0 {
+        return Err(Error::invalid_argument(
+            items[0].clone(),
+            "duration",
+            "Years and months have no fixed number of seconds, but may be added to dates with date_add",
+        ));
+    }
+    to_number_value(duration.millis as f64 / 1000.0)
+}
+
+fn shift(
+    items: &[Evaluated],
+    operation: &'static str,
+    negate: bool,
+) -> Result<Value, Error> {
+    let date = date_arg(&items[0], operation)?;
+    let duration = duration_arg(&items[1], operation)?;
+    let duration = if negate { duration.negate() } else { duration };
+    add(date, duration)
+        .and_then(format_date)
+        .map(Value::String)
+        .ok_or_else(|| {
+            Error::invalid_argument(
+                items[0].clone(),
+                operation,
+                "The result is outside the years 0 to 9999",
+            )
+        })
+}
+
+/// A date plus a duration
+pub fn date_add(items: &[Evaluated]) -> Result<Value, Error> {
+    shift(items, "date_add", false)
+}
+
+/// A date minus a duration
+pub fn date_sub(items: &[Evaluated]) -> Result<Value, Error> {
+    shift(items, "date_sub", true)
+}
+
+/// Whether a date is no later than the context's clock's current time, and
+/// no more than a duration earlier
+pub fn within_last(items: &[Evaluated], env: &Env) -> Result<Value, Error> {
+    let date = date_arg(&items[0], "within_last")?;
+    let duration = duration_arg(&items[1], "within_last")?;
+    let clock = env.clock().ok_or_else(|| Error::InvalidOperation {
+        key: "within_last".into(),
+        reason: "No clock was provided in the evaluation context".into(),
+    })?;
+    let now = clock.now()?;
+    let since = add(now, duration.negate()).ok_or_else(|| {
+        Error::invalid_argument(
+            items[1].clone(),
+            "within_last",
+            "The duration reaches outside the years 0 to 9999",
+        )
+    })?;
+    Ok(Value::Bool(since <= date && date <= now))
+}
+
+#[cfg(test)]
+mod test_date {
+    use super::*;
+    use alloc::sync::Arc;
+    use serde_json::json;
+
+    use crate::clock::FixedClock;
+    use crate::rule::ParseOptions;
+    use crate::{Context, Rule};
+
+    #[test]
+    fn test_parse_duration() {
+        let duration = |months, millis| Some(Duration { months, millis });
+        assert_eq!(parse_duration("P1Y2M"), duration(14, 0));
+        assert_eq!(parse_duration("P2W"), duration(0, 14 * MILLIS_PER_DAY));
+        assert_eq!(
+            parse_duration("P1DT2H30M"),
+            duration(0, MILLIS_PER_DAY + 9_000_000)
+        );
+        assert_eq!(parse_duration("PT0.5S"), duration(0, 500));
+        assert_eq!(parse_duration("PT1,2345S"), duration(0, 1_234));
+        assert_eq!(parse_duration("-P1M"), duration(-1, 0));
+        assert_eq!(parse_duration("7d"), duration(0, 7 * MILLIS_PER_DAY));
+        assert_eq!(parse_duration("1h30m"), duration(0, 5_400_000));
+        assert_eq!(parse_duration("250ms"), duration(0, 250));
+        assert_eq!(parse_duration("-1w"), duration(0, -7 * MILLIS_PER_DAY));
+        for invalid in [
+            "",
+            "P",
+            "PT",
+            "P1DT",
+            "P1H",
+            "PT1D",
+            "P1M1Y",
+            "PT1.5M",
+            "P1.5D",
+            "1.5h",
+            "7",
+            "d",
+            "7 d",
+            "1y",
+            "--1d",
+            "99999999999999w",
+        ] {
+            assert_eq!(parse_duration(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("1970-01-01"), Some(0));
+        assert_eq!(
+            parse_date("1970-01-02T00:00:01.5Z"),
+            Some(MILLIS_PER_DAY + 1_500)
+        );
+        assert_eq!(parse_date("1970-01-01T01:00:00+01:00"), Some(0));
+        assert_eq!(parse_date("1969-12-31t23:00:00-01:00"), Some(0));
+        assert_eq!(parse_date("2000-02-29 00:00:00Z"), Some(951_782_400_000));
+        for invalid in [
+            "2001-02-29",
+            "2024-13-01",
+            "2024-1-01",
+            "2024-01-01T10:00Z",
+            "2024-01-01T10:00:00",
+            "2024-01-01T24:00:00Z",
+            "2024-01-01T10:00:00.Z",
+            "2024-01-01T10:00:00+0100",
+            "20240101",
+        ] {
+            assert_eq!(parse_date(invalid), None, "{}", invalid);
+        }
+        for days in [-719_528, -1, 0, 11_016, 19_782, 2_932_896] {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+
+    #[test]
+    fn test_within_last() {
+        // 2024-03-01T12:00:00Z
+        let ctx = Context {
+            clock: Some(Arc::new(FixedClock::new(1_709_294_400_000))),
+            ..Context::default()
+        };
+        let within = |date: &str, duration: Value| {
+            Rule::from_value(&json!({"within_last": [date, duration]}))?
+                .apply_with(&json!({}), &ctx)
+        };
+        assert_eq!(within("2024-02-01", json!("30d")).unwrap(), json!(true));
+        assert_eq!(within("2024-01-31", json!("30d")).unwrap(), json!(false));
+        assert_eq!(
+            within("2024-02-01T12:00:00Z", json!("P1M")).unwrap(),
+            json!(true)
+        );
+        assert_eq!(
+            within("2024-02-01T11:59:59Z", json!("P1M")).unwrap(),
+            json!(false)
+        );
+        assert_eq!(
+            within("2024-03-01T11:00:00Z", json!(3600)).unwrap(),
+            json!(true)
+        );
+        assert_eq!(within("2024-03-02", json!("30d")).unwrap(), json!(false));
+        assert_eq!(
+            within("yesterday", json!("30d")).unwrap_err().code(),
+            "invalid_argument"
+        );
+
+        #[cfg(feature = "std")]
+        assert_eq!(
+            Rule::from_value(&json!({"within_last": ["2024-01-01", "1h"]}))
+                .unwrap()
+                .apply(&json!({}))
+                .unwrap(),
+            json!(false)
+        );
+        let options = ParseOptions {
+            deterministic: true,
+            ..ParseOptions::default()
+        };
+        let err = Rule::from_value_with(
+            &json!({"within_last": ["2024-01-01", "1h"]}),
+            &options,
+        )
+        .unwrap_err();
+        assert_eq!(err.code(), "nondeterministic_operator");
+    }
+}
@@ -1,15 +1,16 @@
 //! Equality operators
 
 use crate::error::Error;
+use crate::value::Evaluated;
 use serde_json::Value;
 
 /// Compare two values for equality.
 ///
 /// Returns true if the item are equal.
-pub fn equal(items: &Vec<&Value>) -> Result<Value, Error> {
+pub fn equal(items: &[Evaluated]) -> Result<Value, Error> {
     // We assume the number of parameters has already been checked.
-    let first = items[0];
-    let second = items[1];
+    let first = &*items[0];
+    let second = &*items[1];
 
     Ok(Value::Bool(first == second))
 }
@@ -17,10 +18,10 @@ pub fn equal(items: &Vec<&Value>) -> Result<Value, Error> {
 /// Compare any number of items for inequality.
 ///
 /// Returns false if the items are equal
-pub fn not_equal(items: &Vec<&Value>) -> Result<Value, Error> {
+pub fn not_equal(items: &[Evaluated]) -> Result<Value, Error> {
     // We assume the number of parameters has already been checked.
-    let first = items[0];
-    let second = items[1];
+    let first = &*items[0];
+    let second = &*items[1];
 
     Ok(Value::Bool(first != second))
 }
@@ -63,7 +64,7 @@ pub mod test_equality_operators {
     fn test_equal() {
         eq_cases().iter().for_each(|(first, second, exp)| {
             assert_eq!(
-                &equal(&vec![first, second]).unwrap(),
+                &equal(&[Evaluated::Raw(first), Evaluated::Raw(second)]).unwrap(),
                 exp,
                 "Comparing {:?} to {:?} failed",
                 first,
@@ -77,7 +78,8 @@ pub mod test_equality_operators {
         eq_cases().iter().for_each(|(first, second, exp)| {
             if let Value::Bool(exp) = exp {
                 assert_eq!(
-                    not_equal(&vec![first, second]).unwrap(),
+                    not_equal(&[Evaluated::Raw(first), Evaluated::Raw(second)])
+                        .unwrap(),
                     Value::Bool(!exp),
                     "Comparing {:?} to {:?} failed",
                     first,
@@ -1,10 +1,23 @@
 //! FUNctions
 
-use std::convert::{TryFrom, TryInto};
+use alloc::borrow::Cow;
+use alloc::sync::Arc;
+use core::convert::{TryFrom, TryInto};
+use core::mem;
 
-use serde_json::{Map, Value};
+use serde_json::{json, Map, Value};
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
 
-use crate::{error::Error, op::CommonOperator, op::NumParams, Parser};
+use crate::arena::{ArenaSlice, ArenaStr, FrozenArena};
+use crate::namespace::Scope;
+use crate::op::data::Variable;
+use crate::op::logic::truthy;
+use crate::op::{op_args, single_entry};
+use crate::prelude::*;
+use crate::resolver::ValueKind;
+use crate::value::{Evaluated, Parsed, Raw};
+use crate::NULL;
+use crate::{context::Env, error::Error, op::CommonOperator, op::NumParams, Parser};
 
 /// A (potentially user-defined) function
 ///
@@ -36,15 +49,101 @@ use crate::{error::Error, op::CommonOperator, op::NumParams, Parser};
 /// Function expressions may use any of the standard operators or any
 /// previously defined functions.
 ///
+/// An optional fourth element may give the function a contract, with `pre`
+/// and `post` conditions checked around each call:
+///
+/// ```jsonc
+/// {"defn": [
+///     "sqrt",
+///     ["a"],
+///     {"sqrt_impl": [{"param": "a"}]},
+///     {
+///         // evaluated against the call's data, with the params bound
+///         "pre": {">=": [{"param": "a"}, 0]},
+///         // evaluated against the function's result
+///         "post": {">=": [{"var": ""}, 0]}
+///     }
+/// ]}
+/// ```
+///
+/// A condition that isn't truthy fails the call with an
+/// `Error::ContractViolation`. Contracts may be skipped by setting
+/// `Context::skip_contracts`.
+///
+/// Functions defined with `defmemo` rather than `defn` are memoized: the
+/// result of each call is cached for the rest of the evaluation, keyed by the
+/// arguments. A memoized function may depend only on its parameters: its body
+/// may not read the data, other than the elements of arrays it iterates over,
+/// and is evaluated against null, as are any functions it calls. The size of
+/// the cache may be limited with `Context::memo_cache_size`.
+///
+/// The options may also hold `examples`, each a list of arguments and the
+/// expected result, which are run by `Namespace::run_examples()`, and a `doc`
+/// string describing the function:
+///
+/// ```jsonc
+/// {"defn": [
+///     "add_two",
+///     ["a", "b"],
+///     {"+": [{"param": "a"}, {"param": "b"}]},
+///     {"doc": "The sum of two values", "examples": [[[1, 2], 3], [[-1, 1], 0]]}
+/// ]}
+/// ```
+///
+/// Trailing parameters may be given defaults, as `[name, default]` (or with a
+/// `default` key in the object form), which are evaluated when the argument
+/// is omitted. A default may refer to the parameters before it. The last
+/// parameter may instead be a rest parameter, `"...name"`, which binds any
+/// remaining arguments as an array:
+///
+/// ```jsonc
+/// {"defn": [
+///     "total",
+///     ["first", ["second", 0], "...others"],
+///     {"reduce": [
+///         {"param": "others"},
+///         {"+": [{"var": "accumulator"}, {"var": "current"}]},
+///         {"+": [{"param": "first"}, {"param": "second"}]}
+///     ]}
+/// ]}
+/// ```
+///
+/// A function may define others within its body, which are closures: each
+/// evaluates to a function value that captures the values of the enclosing
+/// function's parameters, and may be called with `call`:
+///
+/// ```jsonc
+/// {"defn": [
+///     "greater_than",
+///     ["threshold"],
+///     {"defn": ["above", ["x"], {">": [{"param": "x"}, {"param": "threshold"}]}]}
+/// ]}
+/// ```
+///
+/// Closures are lifted into functions of their own, named for the enclosing
+/// function and the closure, e.g. `greater_than/above`, whose leading
+/// parameters are the captured ones, and the definition is replaced by a
+/// `closure` operation binding them.
 #[derive(Clone, Debug, PartialEq)]
-pub struct Function<'a> {
+pub struct Function {
     name: Identifier,
     params: Vec<Identifier>,
-    expression: &'a Value,
+    param_types: Vec<Option<ValueKind>>,
+    expression: Value,
+    pre: Option<Value>,
+    post: Option<Value>,
+    examples: Vec<(Vec<Value>, Value)>,
+    memoize: bool,
+    doc: Option<String>,
+    defaults: Vec<Value>,
+    rest: bool,
+    closures: Vec<Function>,
     num_params: NumParams,
 }
-impl Function<'_> {
-    const OPERATOR: &'static str = "defn";
+impl Function {
+    pub(crate) const OPERATOR: &'static str = "defn";
+    pub(crate) const MEMO_OPERATOR: &'static str = "defmemo";
+    pub(crate) const NUM_PARAMS: NumParams = NumParams::Variadic(3..5);
 
     /// Return Some with the passed value if it is a function, or None otherwise.
     pub fn filter_value(value: &Value) -> Option<&Value> {
@@ -63,71 +162,519 @@ impl Function<'_> {
             1 => Some(obj),
             _ => None,
         })
-        .and_then(|o| o.get(Self::OPERATOR))
+        .and_then(|o| o.get(Self::OPERATOR).or_else(|| o.get(Self::MEMO_OPERATOR)))
     }
 
     /// Convert a value into a parameter list
-    fn to_parameters(value: &Value) -> Result<Vec<Identifier>, Error> {
+    ///
+    /// Each parameter is either a name, or an object with a `name` and an
+    /// optional `type`, e.g. `{"name": "a", "type": "number"}`. Parameters
+    /// with defaults, given as `[name, default]` or with a `default` key,
+    /// must follow those without, and may only be followed by a rest
+    /// parameter, `"...name"`, which must be last.
+    fn to_parameters(value: &Value) -> Result<Parameters, Error> {
+        let params = match value {
+            Value::Array(params) => params,
+            _ => {
+                return Err(Error::InvalidArgument {
+                    value: value.clone(),
+                    operation: Self::OPERATOR,
+                    reason: "Parameter list must be an array".into(),
+                })
+            }
+        };
+        let invalid = |reason: String| Error::InvalidArgument {
+            value: value.clone(),
+            operation: Self::OPERATOR,
+            reason,
+        };
+        let mut parameters = Parameters::default();
+        for param in params {
+            if parameters.rest {
+                return Err(invalid(
+                    "A rest parameter must be the last parameter".into(),
+                ));
+            }
+            let (name, kind, form) = Self::to_parameter(param).map_err(|e| {
+                invalid(format!("Could not parse parameter {} due to: {}", param, e))
+            })?;
+            match form {
+                ParamForm::Required if !parameters.defaults.is_empty() => {
+                    return Err(invalid(format!(
+                        "Parameter {} must have a default, as it follows one that does",
+                        param
+                    )))
+                }
+                ParamForm::Required => {}
+                ParamForm::Default(default) => parameters.defaults.push(default),
+                ParamForm::Rest => parameters.rest = true,
+            }
+            parameters.names.push(name);
+            parameters.types.push(kind);
+        }
+        Ok(parameters)
+    }
+
+    /// Convert a value into a parameter, its declared type, and whether it
+    /// has a default or is a rest parameter
+    fn to_parameter(
+        param: &Value,
+    ) -> Result<(Identifier, Option<ValueKind>, ParamForm), Error> {
+        let obj = match param {
+            Value::Object(obj) => obj,
+            Value::String(name) => {
+                return match name.strip_prefix(REST_PREFIX) {
+                    Some(rest) => Identifier::new(rest).map(|ident| (ident, None, ParamForm::Rest)),
+                    None => Identifier::new(name.as_str()).map(|ident| (ident, None, ParamForm::Required)),
+                }
+            }
+            Value::Array(param) => {
+                return match param.as_slice() {
+                    [name, default] => Ok((
+                        name.try_into()?,
+                        None,
+                        ParamForm::Default(Self::to_default(default)?),
+                    )),
+                    _ => Err(Error::invalid_argument(
+                        Value::Array(param.clone()),
+                        Self::OPERATOR,
+                        "A parameter with a default must be an array of its name and default",
+                    )),
+                }
+            }
+            _ => return param.try_into().map(|ident| (ident, None, ParamForm::Required)),
+        };
+        let name = obj
+            .get("name")
+            .ok_or_else(|| Error::InvalidIdentifier(param.to_string()))?
+            .try_into()?;
+        if let Some(key) = obj
+            .keys()
+            .find(|key| !["name", "type", "default"].contains(&key.as_str()))
+        {
+            return Err(Error::invalid_argument(
+                param.clone(),
+                Self::OPERATOR,
+                format!("Unexpected key '{}' in parameter", key),
+            ));
+        }
+        let kind = match obj.get("type") {
+            None => None,
+            Some(Value::String(ty)) => Some(ValueKind::from_name(ty).ok_or_else(|| {
+                Error::invalid_argument(
+                    param.clone(),
+                    Self::OPERATOR,
+                    format!(
+                        "Unknown type '{}'. Expected one of null, boolean, number, string, array, or object",
+                        ty
+                    ),
+                )
+            })?),
+            Some(ty) => {
+                return Err(Error::invalid_argument(
+                    ty.clone(),
+                    Self::OPERATOR,
+                    "Parameter types must be strings",
+                ))
+            }
+        };
+        let form = match obj.get("default") {
+            Some(default) => ParamForm::Default(Self::to_default(default)?),
+            None => ParamForm::Required,
+        };
+        Ok((name, kind, form))
+    }
+
+    /// Check a parameter's default expression, which may not be a function
+    fn to_default(default: &Value) -> Result<Value, Error> {
+        match Self::filter_value(default) {
+            Some(default) => Err(Error::invalid_argument(
+                default.clone(),
+                Self::OPERATOR,
+                "A parameter's default may not be a function",
+            )),
+            None => Ok(default.clone()),
+        }
+    }
+
+    /// Check that a memoized function's body and precondition don't read the
+    /// data, on which its results would otherwise depend without it being
+    /// part of the key they're cached by.
+    fn check_memoizable(self) -> Result<Self, Error> {
+        if !self.memoize {
+            return Ok(self);
+        }
+        let access = core::iter::once(&self.expression)
+            .chain(&self.pre)
+            .find_map(|expr| data_access(expr, false));
+        match access {
+            Some(access) => Err(Error::invalid_argument(
+                access.clone(),
+                Self::MEMO_OPERATOR,
+                "Memoized functions may only depend on their parameters, not the data",
+            )),
+            None => Ok(self),
+        }
+    }
+
+    /// Lift the functions defined within the function's body into closures,
+    /// replacing each definition with a `closure` operation that captures
+    /// the function's parameters.
+    fn lift_closures(mut self) -> Result<Self, Error> {
+        let mut expression = mem::take(&mut self.expression);
+        self.lift(&mut expression)?;
+        self.expression = expression;
+        Ok(self)
+    }
+
+    fn lift(&mut self, value: &mut Value) -> Result<(), Error> {
+        if let Some(inner) = Self::parse_definition(value)? {
+            *value = self.capture(inner)?;
+            return Ok(());
+        }
+        // Quoted values are data, rather than definitions
+        if matches!(single_entry(value)?, Some((key, _)) if key == Raw::OPERATOR) {
+            return Ok(());
+        }
         match value {
-            Value::Array(params) => Ok(params),
-            _ => Err(Error::InvalidArgument {
-                value: value.clone(),
-                operation: Self::OPERATOR,
-                reason: "Parameter list must be an array".into(),
-            }),
-        }
-        .and_then(|params| {
-            params
+            Value::Array(items) => {
+                items.iter_mut().try_for_each(|item| self.lift(item))
+            }
+            Value::Object(obj) => obj.values_mut().try_for_each(|item| self.lift(item)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Lift a function defined within this one's body into a closure, whose
+    /// leading parameters are those of this function that it doesn't
+    /// shadow, and return the `closure` operation capturing them.
+    fn capture(&mut self, inner: Function) -> Result<Value, Error> {
+        if !inner.examples.is_empty() {
+            return Err(Error::invalid_argument(
+                Value::from(inner),
+                Self::OPERATOR,
+                "A function defined within another may not have examples",
+            ));
+        }
+        let (captured, types): (Vec<Identifier>, Vec<Option<ValueKind>>) = self
+            .params
+            .iter()
+            .zip(&self.param_types)
+            .filter(|(param, _)| !inner.params.contains(param))
+            .map(|(param, kind)| (param.clone(), *kind))
+            .unzip();
+        let name = Identifier::new(format!(
+            "{}{}{}",
+            self.name.value(),
+            CLOSURE_SEPARATOR,
+            inner.name.value()
+        ))?;
+        let mut args = vec![json!({ FUNCTION_REF: name.value() })];
+        args.extend(
+            captured
                 .iter()
-                // Construct an iterable of Result<Identifier, Error>
-                .map(|param| {
-                    param.try_into().map_err(|e| Error::InvalidArgument {
-                        value: value.clone(),
-                        operation: Self::OPERATOR,
-                        reason: format!(
-                            "Could not parse parameter {} due to: {}",
-                            param, e
-                        ),
-                    })
-                })
-                // Collect it into a Result<Vec<Identifier>, Error>
-                .collect()
-        })
+                .map(|param| json!({ Param::OPERATOR: param.value() })),
+        );
+        let rest = inner.rest;
+        let lifted = Function {
+            name,
+            params: captured.into_iter().chain(inner.params).collect(),
+            param_types: types.into_iter().chain(inner.param_types).collect(),
+            ..inner
+        };
+        let lifted = lifted.with_rest_param(rest).lift_closures()?;
+        self.closures.extend(lifted.closures.iter().cloned());
+        self.closures.push(lifted);
+        Ok(json!({ CLOSURE: args }))
     }
-}
-impl<'a> Function<'a> {
+
+    /// The function, and the closures defined within it, with their names
+    /// qualified by a namespace prefix, e.g. `math.clamp`.
+    pub(crate) fn prefixed(mut self, prefix: &str) -> Result<Self, Error> {
+        self.name = Identifier::new(format!("{}.{}", prefix, self.name.value()))?;
+        self.closures = self
+            .closures
+            .into_iter()
+            .map(|closure| closure.prefixed(prefix))
+            .collect::<Result<_, _>>()?;
+        Ok(self)
+    }
+
     pub fn new(
         name: Identifier,
         params: Vec<Identifier>,
-        expression: &'a Value,
+        expression: Value,
         num_params: NumParams,
     ) -> Self {
+        let param_types = vec![None; params.len()];
         Self {
             name,
             params,
+            param_types,
             expression,
+            pre: None,
+            post: None,
+            examples: Vec::new(),
+            memoize: false,
+            doc: None,
+            defaults: Vec::new(),
+            rest: false,
+            closures: Vec::new(),
             num_params,
         }
     }
+
+    /// Give the function's trailing parameters default expressions, which
+    /// are evaluated when their arguments are omitted. If the function has a
+    /// rest parameter, the defaults are for the parameters before it.
+    pub fn with_defaults(mut self, defaults: Vec<Value>) -> Self {
+        self.defaults = defaults;
+        self.num_params = self.signature().num_params();
+        self
+    }
+
+    /// Make the function's last parameter a rest parameter, which binds any
+    /// remaining arguments as an array.
+    pub fn with_rest_param(mut self, rest: bool) -> Self {
+        self.rest = rest;
+        self.num_params = self.signature().num_params();
+        self
+    }
+
+    /// Give the function a contract, whose `pre` condition is checked before
+    /// each call, and whose `post` condition is checked against the result.
+    pub fn with_contract(mut self, pre: Option<Value>, post: Option<Value>) -> Self {
+        self.pre = pre;
+        self.post = post;
+        self
+    }
+
+    /// Give the function examples, each a list of arguments and the result
+    /// the function is expected to return for them.
+    pub fn with_examples(mut self, examples: Vec<(Vec<Value>, Value)>) -> Self {
+        self.examples = examples;
+        self
+    }
+
+    /// Describe the function, e.g. for documentation or editor tooling.
+    pub fn with_doc<S: Into<String>>(mut self, doc: S) -> Self {
+        self.doc = Some(doc.into());
+        self
+    }
+
+    /// Cache the results of calls to the function, as with `defmemo`.
+    pub fn with_memoization(mut self, memoize: bool) -> Self {
+        self.memoize = memoize;
+        self
+    }
+
+    /// Apply the options given in the fourth element of a `defn`.
+    fn with_options(self, options: &Value) -> Result<Self, Error> {
+        let options = match options {
+            Value::Object(options) => options,
+            _ => {
+                return Err(Error::invalid_argument(
+                    options.clone(),
+                    Self::OPERATOR,
+                    "Function options must be an object",
+                ))
+            }
+        };
+        if let Some(key) = options
+            .keys()
+            .find(|key| !["pre", "post", "examples", "doc"].contains(&key.as_str()))
+        {
+            return Err(Error::invalid_argument(
+                Value::Object(options.clone()),
+                Self::OPERATOR,
+                format!("Unexpected function option '{}'", key),
+            ));
+        }
+        let condition = |key: &str| -> Result<Option<Value>, Error> {
+            match options.get(key) {
+                Some(value) if Self::filter_value(value).is_some() => {
+                    Err(Error::invalid_argument(
+                        value.clone(),
+                        Self::OPERATOR,
+                        format!("A function's {} condition may not be a function", key),
+                    ))
+                }
+                value => Ok(value.cloned()),
+            }
+        };
+        let (pre, post) = (condition("pre")?, condition("post")?);
+        let examples = match options.get("examples") {
+            Some(examples) => self.to_examples(examples)?,
+            None => Vec::new(),
+        };
+        let func = self.with_contract(pre, post).with_examples(examples);
+        match options.get("doc") {
+            Some(Value::String(doc)) => Ok(func.with_doc(doc.as_str())),
+            Some(doc) => Err(Error::invalid_argument(
+                doc.clone(),
+                Self::OPERATOR,
+                "A function's doc must be a string",
+            )),
+            None => Ok(func),
+        }
+    }
+
+    /// Convert a value into a list of examples, each of which must be an
+    /// array of the arguments and the expected result.
+    fn to_examples(&self, value: &Value) -> Result<Vec<(Vec<Value>, Value)>, Error> {
+        let invalid = |value: &Value, reason: String| {
+            Error::invalid_argument(value.clone(), Self::OPERATOR, reason)
+        };
+        let examples = match value {
+            Value::Array(examples) => examples,
+            _ => return Err(invalid(value, "Examples must be an array".into())),
+        };
+        examples
+            .iter()
+            .map(|example| match example.as_array().map(Vec::as_slice) {
+                Some([Value::Array(args), expected])
+                    if self.num_params.is_valid_len(&args.len()) =>
+                {
+                    Ok((args.clone(), expected.clone()))
+                }
+                Some([Value::Array(args), _]) => Err(invalid(
+                    example,
+                    format!(
+                        "Example has {} argument(s), but '{}' takes {}",
+                        args.len(),
+                        self.name.value(),
+                        match &self.num_params {
+                            NumParams::AtLeast(min) => format!("at least {}", min),
+                            NumParams::Variadic(range) =>
+                                format!("{} to {}", range.start, range.end - 1),
+                            _ => self.params.len().to_string(),
+                        }
+                    ),
+                )),
+                _ => Err(invalid(
+                    example,
+                    "An example must be an array of arguments and the expected result"
+                        .into(),
+                )),
+            })
+            .collect()
+    }
+
+    /// Declare the types of the function's parameters, in order.
+    pub fn with_param_types(mut self, param_types: Vec<Option<ValueKind>>) -> Self {
+        self.param_types = param_types;
+        self
+    }
+
+    /// The function's name
+    pub fn name(&self) -> &Identifier {
+        &self.name
+    }
+
+    /// The function's parameters, in order
+    pub fn params(&self) -> &[Identifier] {
+        &self.params
+    }
+
+    /// The declared types of the function's parameters, in order. Parameters
+    /// declared by name alone have no type.
+    pub fn param_types(&self) -> &[Option<ValueKind>] {
+        &self.param_types
+    }
+
+    /// The (unparsed) default expressions of the function's trailing
+    /// parameters, in order
+    pub fn defaults(&self) -> &[Value] {
+        &self.defaults
+    }
+
+    /// The functions defined within the function's body, lifted into
+    /// functions of their own
+    pub fn closures(&self) -> &[Function] {
+        &self.closures
+    }
+
+    /// Whether the function's last parameter is a rest parameter
+    pub fn has_rest_param(&self) -> bool {
+        self.rest
+    }
+
+    /// The shape of the function's parameter list
+    pub(crate) fn signature(&self) -> Signature {
+        let optional = self.defaults.len();
+        let required = self.params.len() - optional - usize::from(self.rest);
+        Signature {
+            required,
+            optional,
+            rest: self.rest,
+        }
+    }
+
+    /// The (unparsed) expression making up the function's body
+    pub fn expression(&self) -> &Value {
+        &self.expression
+    }
+
+    /// The (unparsed) condition checked before each call, if any
+    pub fn precondition(&self) -> Option<&Value> {
+        self.pre.as_ref()
+    }
+
+    /// The (unparsed) condition checked against the result of each call, if
+    /// any
+    pub fn postcondition(&self) -> Option<&Value> {
+        self.post.as_ref()
+    }
+
+    /// Whether the results of calls to the function are cached
+    pub fn memoized(&self) -> bool {
+        self.memoize
+    }
+
+    /// The function's examples: lists of arguments, and the result expected
+    /// for each
+    pub fn examples(&self) -> &[(Vec<Value>, Value)] {
+        &self.examples
+    }
+
+    /// A description of the function, if it has one
+    pub fn doc(&self) -> Option<&str> {
+        self.doc.as_deref()
+    }
 }
-impl CommonOperator for Function<'_> {
+impl CommonOperator for Function {
     fn param_info(&self) -> &NumParams {
         &self.num_params
     }
 }
-impl<'a> Parser<'a> for Function<'a> {
+impl Parser for Function {
     /// Attempt to parse a function from a Value.
     ///
     /// If the Value cannot be interpreted as a function, return Ok(None). If the
     /// Value can be interpreted as a function but is an invalid function expression,
     /// return an error. Otherwise, return Ok(Some(func)).
-    fn from_value(value: &'a Value) -> Result<Option<Self>, Error> {
+    fn from_value(value: &Value, _scope: &Scope) -> Result<Option<Self>, Error> {
+        Self::parse_definition(value)?
+            .map(Self::lift_closures)
+            .transpose()
+    }
+
+    fn evaluate(&self, data: &Value, _env: &Env) -> Result<Evaluated<'_>, Error> {
+        todo!()
+    }
+}
+impl Function {
+    /// Parse a function definition, without lifting any functions defined
+    /// within its body.
+    fn parse_definition(value: &Value) -> Result<Option<Self>, Error> {
         struct InParams<'b> {
             name: &'b Value,
             params: &'b Value,
             expr: &'b Value,
+            options: Option<&'b Value>,
         }
 
+        let memoize = value.get(Self::MEMO_OPERATOR).is_some();
         Self::get_in_params(value)
             .map(|vals| {
                 match vals {
@@ -138,10 +685,10 @@ impl<'a> Parser<'a> for Function<'a> {
                         reason: "Argument to 'defn' must be an array".into(),
                     }),
                 }
-                .and_then(|vals| match vals.len() {
-                    3 => Ok(vals),
-                    _ => Err(Error::WrongArgumentCount {
-                        expected: NumParams::Exactly(3),
+                .and_then(|vals| match Self::NUM_PARAMS.check_len(&vals.len()) {
+                    Ok(_) => Ok(vals),
+                    Err(_) => Err(Error::WrongArgumentCount {
+                        expected: Self::NUM_PARAMS,
                         actual: vals.len(),
                     }),
                 })
@@ -149,21 +696,18 @@ impl<'a> Parser<'a> for Function<'a> {
                     name: &vals[0],
                     params: &vals[1],
                     expr: &vals[2],
+                    options: vals.get(3),
                 })
                 .and_then(|in_params| {
-                    let params = Function::to_parameters(in_params.params)?;
-                    let num_params = params.len();
-                    // For now prevent functions returning functions.
-                    let expr = Function::filter_value(in_params.expr)
-                        .map(|expr| {
-                            Err(Error::InvalidArgument {
-                                value: expr.clone(),
-                                operation: Function::OPERATOR,
-                                reason: "A function's body may not be a expression"
-                                    .into(),
-                            })
-                        })
-                        .unwrap_or(Ok(&in_params.expr))?;
+                    let Parameters {
+                        names,
+                        types,
+                        defaults,
+                        rest,
+                    } = Function::to_parameters(in_params.params)?;
+                    let options = in_params.options;
+                    let num_params = names.len();
+                    let expr = in_params.expr;
                     Ok(Function::new(
                         in_params.name.try_into().map_err(|e: Error| {
                             Error::invalid_argument(
@@ -172,38 +716,718 @@ impl<'a> Parser<'a> for Function<'a> {
                                 e.to_string(),
                             )
                         })?,
-                        params,
-                        expr,
+                        names,
+                        expr.clone(),
                         NumParams::Exactly(num_params),
-                    ))
+                    )
+                    .with_param_types(types)
+                    .with_defaults(defaults)
+                    .with_rest_param(rest)
+                    .with_memoization(memoize))
+                    .and_then(|func| match options {
+                        Some(options) => func.with_options(options),
+                        None => Ok(func),
+                    })
+                    .and_then(Function::check_memoizable)
                 })
             })
             .transpose()
     }
-
-    fn evaluate(&self, data: &'a Value) -> Result<crate::value::Evaluated, Error> {
-        todo!()
-    }
 }
-impl From<Function<'_>> for Value {
-    fn from(func: Function<'_>) -> Self {
-        let params =
-            Value::Array(func.params.iter().map(|param| param.into()).collect());
-        let expression = func.expression.clone();
-        let values = Value::Array(vec![params, expression]);
+impl From<Function> for Value {
+    fn from(func: Function) -> Self {
+        let required = func.signature().required;
+        let rest = func.rest.then(|| func.params.len() - 1);
+        let params = func
+            .params
+            .iter()
+            .zip(&func.param_types)
+            .enumerate()
+            .map(|(i, (param, kind))| {
+                let default =
+                    i.checked_sub(required).and_then(|i| func.defaults.get(i));
+                match (kind, default) {
+                    _ if Some(i) == rest => {
+                        format!("{}{}", REST_PREFIX, param.value()).into()
+                    }
+                    (Some(kind), Some(default)) => json!({
+                        "name": param.value(),
+                        "type": kind.name(),
+                        "default": default
+                    }),
+                    (Some(kind), None) => {
+                        json!({"name": param.value(), "type": kind.name()})
+                    }
+                    (None, Some(default)) => json!([param.value(), default]),
+                    (None, None) => param.into(),
+                }
+            })
+            .collect();
+        let mut values = vec![func.name.into(), Value::Array(params), func.expression];
+        let mut options = Map::new();
+        options.extend(func.doc.map(|doc| ("doc".to_string(), doc.into())));
+        options.extend(func.pre.map(|pre| ("pre".to_string(), pre)));
+        options.extend(func.post.map(|post| ("post".to_string(), post)));
+        if !func.examples.is_empty() {
+            let examples = func
+                .examples
+                .into_iter()
+                .map(|(args, expected)| json!([args, expected]))
+                .collect();
+            options.insert("examples".into(), Value::Array(examples));
+        }
+        if !options.is_empty() {
+            values.push(Value::Object(options));
+        }
+        let values = Value::Array(values);
+        let operator = if func.memoize {
+            Function::MEMO_OPERATOR
+        } else {
+            Function::OPERATOR
+        };
         let mut val = Map::new();
-        val.insert(Function::OPERATOR.into(), values);
+        val.insert(operator.into(), values);
         Value::Object(val)
     }
 }
 
+/// The prefix marking a rest parameter, e.g. `"...rest"`
+const REST_PREFIX: &str = "...";
+
+/// A parameter list, as parsed from a `defn`
+#[derive(Default)]
+struct Parameters {
+    names: Vec<Identifier>,
+    types: Vec<Option<ValueKind>>,
+    defaults: Vec<Value>,
+    rest: bool,
+}
+
+/// How a parameter is bound
+enum ParamForm {
+    Required,
+    Default(Value),
+    Rest,
+}
+
+/// The shape of a function's parameter list: its required parameters,
+/// followed by those with defaults, and then perhaps a rest parameter
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct Signature {
+    pub(crate) required: usize,
+    pub(crate) optional: usize,
+    pub(crate) rest: bool,
+}
+impl Signature {
+    /// The number of arguments the function may be called with
+    pub(crate) fn num_params(&self) -> NumParams {
+        match (self.optional, self.rest) {
+            (_, true) => NumParams::AtLeast(self.required),
+            (0, false) => NumParams::Exactly(self.required),
+            (optional, false) => {
+                NumParams::Variadic(self.required..self.required + optional + 1)
+            }
+        }
+    }
+
+    /// Whether arguments are bound to parameters as they are, with no
+    /// defaults or rest parameter
+    pub(crate) fn is_positional(&self) -> bool {
+        self.optional == 0 && !self.rest
+    }
+
+    /// Bind a call's arguments to the parameters, evaluating the defaults of
+    /// any that were omitted with `default`, which is given the index of the
+    /// default and the parameters bound so far, and collecting any remaining
+    /// arguments for the rest parameter.
+    pub(crate) fn bind<F>(
+        &self,
+        mut arguments: Vec<Value>,
+        mut default: F,
+    ) -> Result<Vec<Value>, Error>
+    where
+        F: FnMut(usize, &[Value]) -> Result<Value, Error>,
+    {
+        let positional = self.required + self.optional;
+        let rest = if self.rest && arguments.len() > positional {
+            arguments.split_off(positional)
+        } else {
+            Vec::new()
+        };
+        while arguments.len() < positional {
+            let value = default(arguments.len() - self.required, &arguments)?;
+            arguments.push(value);
+        }
+        if self.rest {
+            arguments.push(Value::Array(rest));
+        }
+        Ok(arguments)
+    }
+}
+
+/// A function's parsed body, and its parsed contract and defaults
+#[derive(Clone, Debug)]
+pub(crate) struct FunctionBody {
+    pub(crate) expression: Parsed,
+    pub(crate) pre: Option<Parsed>,
+    pub(crate) post: Option<Parsed>,
+    pub(crate) memoize: bool,
+    /// The default expressions of the function's trailing parameters
+    pub(crate) defaults: Vec<Parsed>,
+    pub(crate) signature: Signature,
+    /// The arena the function was parsed into, along with the rule that
+    /// calls it, dropped after everything in it
+    pub(crate) arena: Option<Arc<FrozenArena>>,
+}
+impl FunctionBody {
+    /// Parse the body, contract, and defaults of a function. Each default is
+    /// parsed with only the parameters before it in scope.
+    pub(crate) fn parse(func: &Function, scope: &Scope) -> Result<Self, Error> {
+        let parse = |value: Option<&Value>| {
+            value
+                .map(|value| Parsed::from_value(value, scope))
+                .transpose()
+        };
+        let signature = func.signature();
+        let defaults = func
+            .defaults()
+            .iter()
+            .enumerate()
+            .map(|(i, default)| {
+                let params = &func.params()[..signature.required + i];
+                Parsed::from_value(default, &scope.with_params(params))
+            })
+            .collect::<Result<Vec<Parsed>, Error>>()?;
+        Ok(Self {
+            expression: Parsed::from_value(func.expression(), scope)?,
+            pre: parse(func.precondition())?,
+            post: parse(func.postcondition())?,
+            memoize: func.memoized(),
+            defaults,
+            signature,
+            arena: None,
+        })
+    }
+
+    /// Whether the function has a contract
+    pub(crate) fn has_contract(&self) -> bool {
+        self.pre.is_some() || self.post.is_some()
+    }
+}
+
+/// Check the outcome of one of a function's `pre` or `post` conditions,
+/// evaluated with the call's arguments bound in `env`.
+pub(crate) fn check_condition(
+    function: &str,
+    condition: &'static str,
+    outcome: &Value,
+    env: &Env,
+    result: Option<&Value>,
+) -> Result<(), Error> {
+    if truthy(outcome) {
+        return Ok(());
+    }
+    Err(Error::ContractViolation {
+        function: function.into(),
+        condition,
+        arguments: env.params().to_vec(),
+        result: result.cloned(),
+    })
+}
+
+/// The operator referencing a function as a value, e.g. `{"fn": "double"}`
+pub(crate) const FUNCTION_REF: &str = "fn";
+
+/// The operator composing functions into a function value, e.g.
+/// `{"comp": [{"fn": "f"}, {"fn": "g"}]}`
+pub(crate) const COMPOSITION: &str = "comp";
+
+/// The operator capturing the arguments of a closure, e.g.
+/// `{"closure": [{"fn": "outer/inner"}, {"param": "a"}]}`
+pub(crate) const CLOSURE: &str = "closure";
+
+/// The operator partially applying a function value, e.g.
+/// `{"partial": [{"fn": "add"}, [1]]}`
+const PARTIAL: &str = "partial";
+
+/// The separator between the name of a function and those of the closures
+/// defined within it
+pub(crate) const CLOSURE_SEPARATOR: &str = "/";
+
+/// The name of the function a value refers to, if it is a function reference
+pub(crate) fn function_ref(value: &Value) -> Option<&str> {
+    match value {
+        Value::Object(obj) if obj.len() == 1 => obj.get(FUNCTION_REF)?.as_str(),
+        _ => None,
+    }
+}
+
+/// The function values a value composes, if it is a composition
+fn composition(value: &Value) -> Option<&[Value]> {
+    match value {
+        Value::Object(obj) if obj.len() == 1 => {
+            obj.get(COMPOSITION)?.as_array().map(Vec::as_slice)
+        }
+        _ => None,
+    }
+}
+
+/// The function value a closure calls, and the arguments it captured, if
+/// the value is a closure
+fn closure_parts(value: &Value) -> Option<(&Value, &[Value])> {
+    match value {
+        Value::Object(obj) if obj.len() == 1 => {
+            match obj.get(CLOSURE)?.as_array()?.as_slice() {
+                [function, Value::Array(captured)] => Some((function, captured)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Whether a value is a function value: a function reference, a closure,
+/// or a composition of function values
+fn is_function_value(value: &Value) -> bool {
+    function_ref(value).is_some()
+        || closure_parts(value).is_some_and(|(function, _)| is_function_value(function))
+        || composition(value)
+            .is_some_and(|functions| functions.iter().all(is_function_value))
+}
+
+/// Capture the arguments given after a function value, which are passed
+/// ahead of any others when the resulting closure is called.
+pub fn closure(items: &[Evaluated]) -> Result<Value, Error> {
+    let function = Value::clone(&items[0]);
+    if !is_function_value(&function) {
+        return Err(Error::invalid_argument(
+            function,
+            CLOSURE,
+            "Expected a function reference, e.g. {\"fn\": \"name\"}",
+        ));
+    }
+    let captured: Vec<Value> =
+        items[1..].iter().map(|item| Value::clone(item)).collect();
+    Ok(json!({ CLOSURE: [function, captured] }))
+}
+
+/// Compose function values into one that applies the last to its arguments,
+/// then each of the others in turn to the result, e.g.
+/// `{"comp": [{"fn": "f"}, {"fn": "g"}]}` is `f(g(...))`.
+pub fn comp(items: &[Evaluated]) -> Result<Value, Error> {
+    let functions = items
+        .iter()
+        .map(|item| {
+            if is_function_value(item) {
+                Ok(Value::clone(item))
+            } else {
+                Err(Error::invalid_argument(
+                    Value::clone(item),
+                    COMPOSITION,
+                    "Expected a function reference, e.g. {\"fn\": \"name\"}",
+                ))
+            }
+        })
+        .collect::<Result<Vec<Value>, Error>>()?;
+    Ok(json!({ COMPOSITION: functions }))
+}
+
+/// Partially apply the function value given by the first argument to the
+/// array of arguments given by the second, returning a closure that passes
+/// them ahead of any others, e.g. `{"partial": [{"fn": "add"}, [1]]}`.
+pub fn partial(items: &[Evaluated]) -> Result<Value, Error> {
+    let arguments = match &*items[1] {
+        Value::Array(arguments) => arguments.clone(),
+        other => {
+            return Err(Error::invalid_argument(
+                other.clone(),
+                PARTIAL,
+                "Arguments must be an array",
+            ))
+        }
+    };
+    let function = Value::clone(&items[0]);
+    if !is_function_value(&function) {
+        return Err(Error::invalid_argument(
+            function,
+            PARTIAL,
+            "Expected a function reference, e.g. {\"fn\": \"name\"}",
+        ));
+    }
+    Ok(json!({ CLOSURE: [function, arguments] }))
+}
+
+/// Call the function value given by the first argument with the array of
+/// arguments given by the second, e.g. `{"call": [{"fn": "double"}, [2]]}`.
+///
+/// Only the functions referenced somewhere in the rule, or in the functions
+/// it calls, may be called.
+pub fn call(data: &Value, args: &[Parsed], env: &Env) -> Result<Value, Error> {
+    let function = args[0].evaluate(data, env)?;
+    let arguments = args[1].evaluate(data, env)?;
+    call_value(
+        &function,
+        call_arguments(&arguments)?,
+        &|name| env.function_arity(name),
+        &mut |name, arguments| env.call(name, data, arguments),
+    )
+}
+
+/// The arguments given to `call`, which must be an array
+pub(crate) fn call_arguments(arguments: &Value) -> Result<Vec<Value>, Error> {
+    match arguments {
+        Value::Array(arguments) => Ok(arguments.clone()),
+        _ => Err(Error::invalid_argument(
+            arguments.clone(),
+            "call",
+            "Arguments must be an array",
+        )),
+    }
+}
+
+/// Call a function value with the given arguments. `arity` gives the number
+/// of arguments each function that may be called takes, and `call` calls one
+/// of them by name.
+pub(crate) fn call_value<A, C>(
+    function: &Value,
+    arguments: Vec<Value>,
+    arity: &A,
+    call: &mut C,
+) -> Result<Value, Error>
+where
+    A: Fn(&str) -> Option<NumParams>,
+    C: FnMut(&str, Vec<Value>) -> Result<Value, Error>,
+{
+    if let Some(functions) = composition(function) {
+        let (last, rest) = functions.split_last().ok_or_else(|| {
+            Error::invalid_argument(
+                function.clone(),
+                "call",
+                "A composition must have at least one function",
+            )
+        })?;
+        let mut result = call_value(last, arguments, arity, call)?;
+        for function in rest.iter().rev() {
+            result = call_value(function, vec![result], arity, call)?;
+        }
+        return Ok(result);
+    }
+    if let Some((function, captured)) = closure_parts(function) {
+        let arguments = captured.iter().cloned().chain(arguments).collect();
+        return call_value(function, arguments, arity, call);
+    }
+    let name = function_ref(function).ok_or_else(|| {
+        Error::invalid_argument(
+            function.clone(),
+            "call",
+            "Expected a function reference, e.g. {\"fn\": \"name\"}",
+        )
+    })?;
+    let expected = arity(name).ok_or_else(|| {
+        Error::invalid_argument(
+            function.clone(),
+            "call",
+            format!("'{}' is not a function referenced by the rule", name),
+        )
+    })?;
+    if !expected.is_valid_len(&arguments.len()) {
+        return Err(Error::WrongArgumentCount {
+            expected,
+            actual: arguments.len(),
+        });
+    }
+    call(name, arguments)
+}
+
+/// A call to a function in a namespace
+///
+/// Calls are only parsed within a namespace, where the called function's
+/// body is compiled alongside the rule. Arguments are evaluated eagerly and
+/// bound to the function's parameters, in order.
+#[derive(Clone, Debug)]
+pub struct Call {
+    name: ArenaStr,
+    arguments: ArenaSlice<Parsed>,
+}
+impl Call {
+    /// The name of the called function
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The (unevaluated) arguments to the function
+    pub(crate) fn arguments(&self) -> &[Parsed] {
+        &self.arguments
+    }
+
+    /// The arguments, to be replaced when generating mutants
+    pub(crate) fn arguments_mut(&mut self) -> &mut Vec<Parsed> {
+        self.arguments.to_mut()
+    }
+}
+impl Parser for Call {
+    fn from_value(value: &Value, scope: &Scope) -> Result<Option<Self>, Error> {
+        let (key, val) = match single_entry(value)? {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let func = match scope.resolve(key) {
+            Some(func) => func,
+            None => return Ok(None),
+        };
+        let args = op_args(key, val, func.param_info())?;
+        Ok(Some(Self {
+            name: scope.name(func.name().value()),
+            arguments: Parsed::from_values(args, scope)?,
+        }))
+    }
+
+    fn evaluate(&self, data: &Value, env: &Env) -> Result<Evaluated<'_>, Error> {
+        let arguments = self
+            .arguments
+            .iter()
+            .map(|arg| arg.evaluate(data, env).map(Value::from))
+            .collect::<Result<Vec<Value>, Error>>()?;
+        env.call(&self.name, data, arguments).map(Evaluated::New)
+    }
+}
+
+/// The first part of an (unparsed) expression that reads the data it's
+/// evaluated against, if any. Within `per_element` expressions, such as the
+/// second argument of `map`, only `../` variables reach that data.
+fn data_access(expr: &Value, per_element: bool) -> Option<&Value> {
+    let (key, args) = match expr {
+        Value::Array(items) => {
+            return items.iter().find_map(|item| data_access(item, per_element))
+        }
+        Value::Object(obj) if obj.len() == 1 => obj.iter().next()?,
+        Value::Object(obj) => {
+            return obj
+                .values()
+                .find_map(|value| data_access(value, per_element))
+        }
+        _ => return None,
+    };
+    let args = match args {
+        Value::Array(args) => args.as_slice(),
+        arg => core::slice::from_ref(arg),
+    };
+    match key.as_str() {
+        Raw::OPERATOR | Param::OPERATOR => None,
+        Variable::OPERATOR if !per_element => Some(expr),
+        Variable::OPERATOR
+            if args
+                .first()
+                .and_then(Value::as_str)
+                .is_some_and(|path| path.starts_with("../")) =>
+        {
+            Some(expr)
+        }
+        key if !per_element && super::data_operator(key).is_some() => Some(expr),
+        Function::OPERATOR | Function::MEMO_OPERATOR => {
+            args.get(2).and_then(|body| data_access(body, per_element))
+        }
+        key => args.iter().enumerate().find_map(|(i, arg)| {
+            data_access(arg, per_element || super::per_element(key, i, args.len()))
+        }),
+    }
+}
+
+/// The result of an expression in tail position of a function, or the
+/// arguments of a call the function makes to itself there, which is made in
+/// a loop rather than by recursing
+pub(crate) enum Tail {
+    Value(Value),
+    Call(Vec<Value>),
+}
+
+/// Evaluate an expression in tail position of the function `name`, i.e. its
+/// body, or a branch of an `if`, `and`, `or`, or `default` that is.
+pub(crate) fn tail(
+    expr: &Parsed,
+    name: &str,
+    data: &Value,
+    env: &Env,
+) -> Result<Tail, Error> {
+    let op = match expr {
+        Parsed::Call(call) if call.name() == name => {
+            let arguments = call
+                .arguments()
+                .iter()
+                .map(|arg| arg.evaluate(data, env).map(Value::from))
+                .collect::<Result<Vec<Value>, Error>>()?;
+            env.execute(name, arguments.len(), || Ok(()))?;
+            return Ok(Tail::Call(arguments));
+        }
+        Parsed::LazyOperation(op)
+            if matches!(op.symbol(), "if" | "?:" | "and" | "or" | "default") =>
+        {
+            op
+        }
+        _ => {
+            return expr
+                .evaluate(data, env)
+                .map(|value| Tail::Value(value.into()))
+        }
+    };
+    let args = op.arguments();
+    let evaluate = |arg: &Parsed| arg.evaluate(data, env).map(Value::from);
+    env.execute(op.symbol(), args.len(), || match (op.symbol(), args) {
+        ("and" | "or", [init @ .., last]) => {
+            // Stop at the first falsy value for `and`, or truthy one for `or`
+            let stop = op.symbol() == "or";
+            for (i, arg) in init.iter().enumerate() {
+                let value = evaluate(arg)?;
+                if truthy(&value) == stop {
+                    env.branch_taken(args, i);
+                    return Ok(Tail::Value(value));
+                }
+            }
+            env.branch_taken(args, init.len());
+            tail(last, name, data, env)
+        }
+        ("and" | "or", []) => Err(Error::UnexpectedError(format!(
+            "{} operation had no values to operate on",
+            op.symbol()
+        ))),
+        ("default", _) => {
+            for arg in args {
+                match arg {
+                    Parsed::Variable(var) if var.arguments().len() < 2 => {
+                        let value = env.execute(
+                            Variable::OPERATOR,
+                            var.arguments().len(),
+                            || var.lookup(data, env),
+                        )?;
+                        if let Some(value) = value {
+                            return Ok(Tail::Value(value));
+                        }
+                    }
+                    _ => return tail(arg, name, data, env),
+                }
+            }
+            Ok(Tail::Value(NULL))
+        }
+        (_, []) => Ok(Tail::Value(NULL)),
+        (_, [arg]) => tail(arg, name, data, env),
+        (_, _) => {
+            for (i, pair) in args.chunks(2).enumerate() {
+                match pair {
+                    [condition, then] if truthy(&evaluate(condition)?) => {
+                        env.branch_taken(args, 2 * i + 1);
+                        return tail(then, name, data, env);
+                    }
+                    [otherwise] => {
+                        env.branch_taken(args, 2 * i);
+                        return tail(otherwise, name, data, env);
+                    }
+                    _ => {}
+                }
+            }
+            Ok(Tail::Value(NULL))
+        }
+    })
+}
+
+impl From<Call> for Value {
+    fn from(call: Call) -> Self {
+        let mut rv = Map::with_capacity(1);
+        let values = call.arguments.into_iter().map(Value::from).collect();
+        rv.insert(call.name.to_string(), Value::Array(values));
+        Value::Object(rv)
+    }
+}
+
+/// A reference to a parameter of the enclosing function
+///
+/// Parameters are resolved to their position when the function body is
+/// parsed, so referencing a parameter the function doesn't declare is an
+/// error.
+#[derive(Clone, Debug)]
+pub struct Param {
+    name: ArenaStr,
+    index: usize,
+}
+impl Param {
+    pub(crate) const OPERATOR: &'static str = "param";
+
+    /// The name of the referenced parameter
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The position of the referenced parameter
+    pub(crate) fn index(&self) -> usize {
+        self.index
+    }
+}
+impl Parser for Param {
+    fn from_value(value: &Value, scope: &Scope) -> Result<Option<Self>, Error> {
+        if scope.namespace().is_none() {
+            return Ok(None);
+        }
+        let val = match single_entry(value)? {
+            Some((key, val)) if key == Self::OPERATOR => val,
+            _ => return Ok(None),
+        };
+        let arg = &op_args(Self::OPERATOR, val, &NumParams::Unary)?[0];
+        let name = match arg {
+            Value::String(name) => name,
+            _ => {
+                return Err(Error::invalid_argument(
+                    arg.clone(),
+                    Self::OPERATOR,
+                    "Parameter names must be strings",
+                ))
+            }
+        };
+        let normalized = normalize(name);
+        let index = scope
+            .params()
+            .iter()
+            .position(|param| param.value() == normalized)
+            .ok_or_else(|| {
+                Error::invalid_argument(
+                    arg.clone(),
+                    Self::OPERATOR,
+                    format!("'{}' is not a parameter of the enclosing function", name),
+                )
+            })?;
+        Ok(Some(Self {
+            name: scope.name(name),
+            index,
+        }))
+    }
+
+    fn evaluate(&self, _data: &Value, env: &Env) -> Result<Evaluated<'_>, Error> {
+        env.param(self.index)
+            .cloned()
+            .map(Evaluated::New)
+            .ok_or_else(|| {
+                Error::UnexpectedError(format!(
+                    "Parameter '{}' was referenced outside of a function call",
+                    self.name
+                ))
+            })
+    }
+}
+impl From<Param> for Value {
+    fn from(param: Param) -> Self {
+        let mut rv = Map::with_capacity(1);
+        rv.insert(
+            Param::OPERATOR.into(),
+            Value::String(param.name.to_string()),
+        );
+        Value::Object(rv)
+    }
+}
+
 /// An Identifier for a function or variable.
 ///
-/// Identifiers contain one or more non-whitespace unicode characters. Note that
-/// these are characters, not code points, so you can get away with silly things
-/// like é being a valid identifier (since it is two characters but one code
-/// point). However, this should not be considered part of the specification,
-/// which may eventually evolve to use code points if needed.
+/// Identifiers contain one or more unicode scalar values, none of which may
+/// be whitespace. They are normalized to NFC when constructed, so that
+/// canonically equivalent names, like `é` written as one code point or as
+/// `e` followed by a combining accent, are the same identifier.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Identifier {
     value: String,
@@ -212,7 +1436,10 @@ pub struct Identifier {
 impl Identifier {
     /// Construct a new identifier.
     fn new<S: Into<String>>(value: S) -> Result<Self, Error> {
-        let value: String = value.into();
+        let mut value: String = value.into();
+        if let Cow::Owned(normalized) = normalize(&value) {
+            value = normalized;
+        }
         if value.len() < 1 {
             return Err(Error::InvalidIdentifier(value));
         }
@@ -224,10 +1451,20 @@ impl Identifier {
     }
 
     /// Return a reference to the identifier as a string slice.
-    fn value(&self) -> &str {
+    pub fn value(&self) -> &str {
         &self.value
     }
 }
+
+/// A name in Unicode Normalization Form C, the form identifiers are stored
+/// in, so that it may be compared with them.
+pub(crate) fn normalize(name: &str) -> Cow<'_, str> {
+    match is_nfc_quick(name.chars()) {
+        IsNormalized::Yes => Cow::Borrowed(name),
+        _ => Cow::Owned(name.nfc().collect()),
+    }
+}
+
 impl TryFrom<&Value> for Identifier {
     type Error = Error;
 
@@ -252,7 +1489,7 @@ impl From<&Identifier> for Value {
 
 #[cfg(test)]
 mod test_function {
-    use std::mem;
+    use core::mem;
 
     use super::*;
     use serde_json::json;
@@ -260,25 +1497,21 @@ mod test_function {
     use crate::test_common::json_exp;
 
     #[derive(Debug)]
-    struct FunctionParsingCase<'a> {
+    struct FunctionParsingCase {
         value: Value,
-        function: Option<Function<'a>>,
+        function: Option<Function>,
         err: Option<Error>,
     }
 
-    impl<'a> FunctionParsingCase<'a> {
-        fn new(
-            value: Value,
-            function: Option<Function<'a>>,
-            err: Option<Error>,
-        ) -> Self {
+    impl FunctionParsingCase {
+        fn new(value: Value, function: Option<Function>, err: Option<Error>) -> Self {
             Self {
                 value,
                 function,
                 err,
             }
         }
-        fn ok(value: Value, function: Function<'a>) -> Self {
+        fn ok(value: Value, function: Function) -> Self {
             Self::new(value, Some(function), None)
         }
         fn err(value: Value, error: Error) -> Self {
@@ -300,7 +1533,7 @@ mod test_function {
         }
     }
 
-    fn function_parsing_cases<'a>() -> Vec<FunctionParsingCase<'a>> {
+    fn function_parsing_cases() -> Vec<FunctionParsingCase> {
         vec![
             FunctionParsingCase::not_a_func(json!(12)),
             FunctionParsingCase::not_a_func(json!({"+": [1, 2]})),
@@ -311,10 +1544,140 @@ mod test_function {
                 Function::new(
                     Identifier::new("foo").unwrap(),
                     vec![Identifier::new("a").unwrap(), Identifier::new("b").unwrap()],
-                    &*json_exp::ADD_TWO,
+                    json_exp::ADD_TWO.clone(),
                     NumParams::Exactly(2),
                 ),
             ),
+            FunctionParsingCase::ok(
+                json!({"defn": [
+                    "foo",
+                    [{"name": "a", "type": "number"}, {"name": "b"}],
+                    &*json_exp::ADD_TWO
+                ]}),
+                Function::new(
+                    Identifier::new("foo").unwrap(),
+                    vec![Identifier::new("a").unwrap(), Identifier::new("b").unwrap()],
+                    json_exp::ADD_TWO.clone(),
+                    NumParams::Exactly(2),
+                )
+                .with_param_types(vec![Some(ValueKind::Number), None]),
+            ),
+            FunctionParsingCase::ok(
+                json!({"defn": [
+                    "foo",
+                    ["a", "b"],
+                    &*json_exp::ADD_TWO,
+                    {"pre": {">": [{"param": "a"}, 0]}}
+                ]}),
+                Function::new(
+                    Identifier::new("foo").unwrap(),
+                    vec![Identifier::new("a").unwrap(), Identifier::new("b").unwrap()],
+                    json_exp::ADD_TWO.clone(),
+                    NumParams::Exactly(2),
+                )
+                .with_contract(Some(json!({">": [{"param": "a"}, 0]})), None),
+            ),
+            FunctionParsingCase::ok(
+                json!({"defn": [
+                    "foo",
+                    ["a", "b"],
+                    &*json_exp::ADD_TWO,
+                    {"examples": [[[1, 2], 3]]}
+                ]}),
+                Function::new(
+                    Identifier::new("foo").unwrap(),
+                    vec![Identifier::new("a").unwrap(), Identifier::new("b").unwrap()],
+                    json_exp::ADD_TWO.clone(),
+                    NumParams::Exactly(2),
+                )
+                .with_examples(vec![(vec![json!(1), json!(2)], json!(3))]),
+            ),
+            FunctionParsingCase::ok(
+                json!({"defmemo": ["foo", ["a", "b"], &*json_exp::ADD_TWO]}),
+                Function::new(
+                    Identifier::new("foo").unwrap(),
+                    vec![Identifier::new("a").unwrap(), Identifier::new("b").unwrap()],
+                    json_exp::ADD_TWO.clone(),
+                    NumParams::Exactly(2),
+                )
+                .with_memoization(true),
+            ),
+            FunctionParsingCase::ok(
+                json!({"defn": [
+                    "foo",
+                    ["a", ["b", 1], {"name": "c", "type": "number", "default": 2}, "...d"],
+                    &*json_exp::ADD_TWO
+                ]}),
+                Function::new(
+                    Identifier::new("foo").unwrap(),
+                    vec![
+                        Identifier::new("a").unwrap(),
+                        Identifier::new("b").unwrap(),
+                        Identifier::new("c").unwrap(),
+                        Identifier::new("d").unwrap(),
+                    ],
+                    json_exp::ADD_TWO.clone(),
+                    NumParams::AtLeast(1),
+                )
+                .with_param_types(vec![None, None, Some(ValueKind::Number), None])
+                .with_defaults(vec![json!(1), json!(2)])
+                .with_rest_param(true),
+            ),
+            FunctionParsingCase::ok(
+                json!({"defn": ["foo", ["a", ["b", 1]], &*json_exp::ADD_TWO]}),
+                Function::new(
+                    Identifier::new("foo").unwrap(),
+                    vec![Identifier::new("a").unwrap(), Identifier::new("b").unwrap()],
+                    json_exp::ADD_TWO.clone(),
+                    NumParams::Variadic(1..3),
+                )
+                .with_defaults(vec![json!(1)]),
+            ),
+            // Invalid b/c a parameter without a default follows one with
+            FunctionParsingCase::err(
+                json!({"defn": ["a", [["a", 1], "b"], 15]}),
+                Error::default_invalid_argument(),
+            ),
+            // Invalid b/c the rest parameter isn't last
+            FunctionParsingCase::err(
+                json!({"defn": ["a", ["...a", "b"], 15]}),
+                Error::default_invalid_argument(),
+            ),
+            // Invalid b/c a default is a function definition
+            FunctionParsingCase::err(
+                json!({"defn": ["a", [["a", {"defn": ["b", [], 1]}]], 15]}),
+                Error::default_invalid_argument(),
+            ),
+            // Invalid b/c an example has the wrong number of arguments
+            FunctionParsingCase::err(
+                json!({"defn": ["a", ["a"], 15, {"examples": [[[1, 2], 15]]}]}),
+                Error::default_invalid_argument(),
+            ),
+            // Invalid b/c an example has no expected result
+            FunctionParsingCase::err(
+                json!({"defn": ["a", ["a"], 15, {"examples": [[[1]]]}]}),
+                Error::default_invalid_argument(),
+            ),
+            // Invalid b/c the options have an unknown key
+            FunctionParsingCase::err(
+                json!({"defn": ["a", ["a"], 15, {"invariant": true}]}),
+                Error::default_invalid_argument(),
+            ),
+            // Invalid b/c the options are not an object
+            FunctionParsingCase::err(
+                json!({"defn": ["a", ["a"], 15, [true]]}),
+                Error::default_invalid_argument(),
+            ),
+            // Invalid b/c the param type is unknown
+            FunctionParsingCase::err(
+                json!({"defn": ["a", [{"name": "a", "type": "int"}], 15]}),
+                Error::default_invalid_argument(),
+            ),
+            // Invalid b/c the param has no name
+            FunctionParsingCase::err(
+                json!({"defn": ["a", [{"type": "number"}], 15]}),
+                Error::default_invalid_argument(),
+            ),
             // Invalid b/c wrong argument type
             FunctionParsingCase::err(
                 json!({"defn": "foo"}),
@@ -340,18 +1703,82 @@ mod test_function {
                 json!({"defn": ["", ["a"], 15]}),
                 Error::default_invalid_argument(),
             ),
-            // Invalid b/c it returns a function definition
+            // Invalid b/c a closure has examples
             FunctionParsingCase::err(
-                json!({"defn": ["a", ["a"], {"defn": ["a", [], 12]}]}),
+                json!({"defn": ["a", ["a"], {"defn": ["b", [], 12, {"examples": [[[], 12]]}]}]}),
                 Error::default_invalid_argument(),
             ),
         ]
     }
 
+    #[test]
+    fn lift_closures() {
+        let value = json!({"defn": ["outer", ["a", "b"], {"if": [
+            {"param": "a"},
+            {"defn": ["inner", ["b", "c"], {"defn": ["innermost", [], {"param": "c"}]}]},
+            null
+        ]}]});
+        let func = Function::from_value(&value, &Scope::default())
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            func.expression(),
+            &json!({"if": [
+                {"param": "a"},
+                {"closure": [{"fn": "outer/inner"}, {"param": "a"}]},
+                null
+            ]})
+        );
+        let closures: Vec<(&str, Vec<&str>, &Value)> = func
+            .closures()
+            .iter()
+            .map(|closure| {
+                (
+                    closure.name().value(),
+                    closure.params().iter().map(Identifier::value).collect(),
+                    closure.expression(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            closures,
+            vec![
+                (
+                    "outer/inner/innermost",
+                    vec!["a", "b", "c"],
+                    &json!({"param": "c"})
+                ),
+                (
+                    "outer/inner",
+                    vec!["a", "b", "c"],
+                    &json!({"closure": [
+                        {"fn": "outer/inner/innermost"},
+                        {"param": "a"},
+                        {"param": "b"},
+                        {"param": "c"}
+                    ]})
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn function_to_value() {
+        let value = json!({"defn": [
+            "foo",
+            ["a", ["b", 1], {"name": "c", "type": "number", "default": 2}, "...d"],
+            {"param": "a"}
+        ]});
+        let func = Function::from_value(&value, &Scope::default())
+            .unwrap()
+            .unwrap();
+        assert_eq!(Value::from(func), value);
+    }
+
     #[test]
     fn parse_function() {
         function_parsing_cases().iter().for_each(|case| {
-            let parsed = Function::from_value(&case.value);
+            let parsed = Function::from_value(&case.value, &Scope::default());
 
             let debug_msg = format!("case: {:?}, res: {:?}", &case, &parsed);
             if let Some(exp_err) = &case.err {
@@ -424,6 +1851,18 @@ mod test_identifier {
         ]
     }
 
+    #[test]
+    fn test_identifier_normalization() {
+        let composed = Identifier::new("caf\u{e9}").unwrap();
+        let decomposed = Identifier::new("cafe\u{301}").unwrap();
+        assert_eq!(composed, decomposed);
+        assert_eq!(decomposed.value(), "caf\u{e9}");
+        assert_eq!(normalize("cafe\u{301}"), "caf\u{e9}");
+        assert!(matches!(normalize("caf\u{e9}"), Cow::Borrowed(_)));
+        // Whitespace is disallowed in any form
+        Identifier::new("a\u{300}\u{2003}").unwrap_err();
+    }
+
     #[test]
     fn test_identifier() {
         identifier_cases().iter().for_each(|case| {
@@ -0,0 +1,214 @@
+//! Geospatial Operators
+//!
+//! Points are `[latitude, longitude]` arrays, in degrees. Polygons are
+//! GeoJSON (RFC 7946) `Polygon` or `MultiPolygon` geometries, or `Feature`s
+//! with one, whose positions are `[longitude, latitude]` arrays, as GeoJSON
+//! requires.
+
+use serde_json::{Map, Value};
+
+use crate::error::Error;
+use crate::value::Evaluated;
+
+/// The mean radius of the Earth, in meters
+const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// A point as `(latitude, longitude)`, in degrees
+type Point = (f64, f64);
+
+fn invalid(value: &Value, operation: &'static str, reason: &str) -> Error {
+    Error::invalid_argument(value.clone(), operation, reason)
+}
+
+/// Parse a `[latitude, longitude]` point.
+fn point(value: &Value, operation: &'static str) -> Result<Point, Error> {
+    let reason = "Points must be [latitude, longitude] arrays of numbers, in degrees";
+    match value.as_array().map(Vec::as_slice) {
+        Some([lat, lon]) => match (lat.as_f64(), lon.as_f64()) {
+            (Some(lat), Some(lon))
+                if (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon) =>
+            {
+                Ok((lat, lon))
+            }
+            _ => Err(invalid(value, operation, reason)),
+        },
+        _ => Err(invalid(value, operation, reason)),
+    }
+}
+
+/// The great-circle distance between two points, in meters, by the
+/// haversine formula
+pub fn geo_distance(items: &[Evaluated]) -> Result<Value, Error> {
+    let (lat1, lon1) = point(&items[0], "geo_distance")?;
+    let (lat2, lon2) = point(&items[1], "geo_distance")?;
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let d_phi = (lat2 - lat1).to_radians();
+    let d_lambda = (lon2 - lon1).to_radians();
+    let a = (d_phi / 2.0).sin().powi(2)
+        + phi1.cos() * phi2.cos() * (d_lambda / 2.0).sin().powi(2);
+    let distance = 2.0 * EARTH_RADIUS * a.sqrt().min(1.0).asin();
+    Ok(Value::from(distance))
+}
+
+/// Whether a point is within a polygon, i.e. within its exterior ring and
+/// outside any holes. Points exactly on a boundary may be either.
+pub fn geo_within(items: &[Evaluated]) -> Result<Value, Error> {
+    let (lat, lon) = point(&items[0], "geo_within")?;
+    let polygons = polygons(&items[1])?;
+    Ok(Value::Bool(polygons.iter().any(|rings| {
+        let mut rings = rings.iter();
+        rings
+            .next()
+            .is_some_and(|exterior| contains(exterior, lat, lon))
+            && !rings.any(|hole| contains(hole, lat, lon))
+    })))
+}
+
+/// The rings of each polygon of a GeoJSON geometry or feature
+fn polygons(value: &Value) -> Result<Vec<Vec<Vec<Point>>>, Error> {
+    let invalid = |reason| invalid(value, "geo_within", reason);
+    let object: &Map<String, Value> = value
+        .as_object()
+        .ok_or_else(|| invalid("Polygons must be GeoJSON objects"))?;
+    let coordinates = object.get("coordinates").unwrap_or(&Value::Null);
+    match object.get("type").and_then(Value::as_str) {
+        Some("Feature") => polygons(object.get("geometry").unwrap_or(&Value::Null)),
+        Some("Polygon") => Ok(vec![rings(coordinates)
+            .ok_or_else(|| invalid("Polygon coordinates must be arrays of rings"))?]),
+        Some("MultiPolygon") => coordinates
+            .as_array()
+            .and_then(|polygons| polygons.iter().map(rings).collect())
+            .ok_or_else(|| invalid("MultiPolygon coordinates must be arrays of polygons")),
+        _ => Err(invalid(
+            "Only GeoJSON Polygons, MultiPolygons, and Features with them are supported",
+        )),
+    }
+}
+
+/// The rings of a polygon's coordinates, as `(latitude, longitude)` points
+fn rings(coordinates: &Value) -> Option<Vec<Vec<Point>>> {
+    coordinates
+        .as_array()?
+        .iter()
+        .map(|ring| {
+            ring.as_array()?
+                .iter()
+                .map(|position| match position.as_array().map(Vec::as_slice) {
+                    Some([lon, lat, ..]) => Some((lat.as_f64()?, lon.as_f64()?)),
+                    _ => None,
+                })
+                .collect::<Option<Vec<Point>>>()
+                .filter(|ring| ring.len() >= 4)
+        })
+        .collect()
+}
+
+/// Whether a ring contains a point, by casting a ray from the point and
+/// counting the edges it crosses
+fn contains(ring: &[Point], lat: f64, lon: f64) -> bool {
+    let mut inside = false;
+    for edge in ring.windows(2) {
+        let ((lat1, lon1), (lat2, lon2)) = (edge[0], edge[1]);
+        if (lat1 > lat) != (lat2 > lat)
+            && lon < lon1 + (lat - lat1) * (lon2 - lon1) / (lat2 - lat1)
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+#[cfg(test)]
+mod test_geo {
+    use serde_json::{json, Value};
+
+    use crate::{Error, Rule};
+
+    fn apply(rule: Value, data: Value) -> Result<Value, Error> {
+        Rule::from_value(&rule)?.apply(&data)
+    }
+
+    #[test]
+    fn test_geo_distance() {
+        let distance = |a: Value, b: Value| {
+            apply(
+                json!({"geo_distance": [{"var": "a"}, {"var": "b"}]}),
+                json!({"a": a, "b": b}),
+            )
+            .unwrap()
+            .as_f64()
+            .unwrap()
+        };
+        // London to Paris
+        let london_paris =
+            distance(json!([51.5074, -0.1278]), json!([48.8566, 2.3522]));
+        assert!(
+            (london_paris - 343_900.0).abs() < 1_000.0,
+            "{}",
+            london_paris
+        );
+        assert_eq!(distance(json!([10, 20]), json!([10, 20])), 0.0);
+        // Antipodes are half the Earth's circumference apart
+        let antipodes = distance(json!([0, 0]), json!([0, 180]));
+        assert!((antipodes - 20_015_115.0).abs() < 1.0, "{}", antipodes);
+
+        for point in [json!([91, 0]), json!([0, 181]), json!([1]), json!("0,0")] {
+            assert_eq!(
+                apply(
+                    json!({"geo_distance": [{"var": "p"}, [0, 0]]}),
+                    json!({"p": point})
+                )
+                .unwrap_err()
+                .code(),
+                "invalid_argument"
+            );
+        }
+    }
+
+    #[test]
+    fn test_geo_within() {
+        // A square with a square hole, as [longitude, latitude] positions
+        let area = json!({
+            "type": "Polygon",
+            "coordinates": [
+                [[0, 0], [10, 0], [10, 10], [0, 10], [0, 0]],
+                [[4, 4], [6, 4], [6, 6], [4, 6], [4, 4]],
+            ]
+        });
+        let within = |point: Value, area: &Value| {
+            apply(
+                json!({"geo_within": [{"var": "point"}, {"var": "area"}]}),
+                json!({"point": point, "area": area}),
+            )
+        };
+        assert_eq!(within(json!([2, 8]), &area).unwrap(), json!(true));
+        assert_eq!(within(json!([5, 5]), &area).unwrap(), json!(false));
+        assert_eq!(within(json!([2, 11]), &area).unwrap(), json!(false));
+        assert_eq!(within(json!([-1, 5]), &area).unwrap(), json!(false));
+
+        let feature = json!({
+            "type": "Feature",
+            "properties": {},
+            "geometry": {
+                "type": "MultiPolygon",
+                "coordinates": [
+                    [[[0, 0], [1, 0], [1, 1], [0, 0]]],
+                    [[[20, 20], [30, 20], [30, 30], [20, 30], [20, 20]]],
+                ]
+            }
+        });
+        assert_eq!(within(json!([25, 25]), &feature).unwrap(), json!(true));
+        assert_eq!(within(json!([15, 15]), &feature).unwrap(), json!(false));
+
+        for area in [
+            json!({"type": "Point", "coordinates": [0, 0]}),
+            json!({"type": "Polygon", "coordinates": [[[0, 0], [1, 1]]]}),
+            json!([[0, 0], [1, 0], [1, 1], [0, 0]]),
+        ] {
+            assert_eq!(
+                within(json!([0, 0]), &area).unwrap_err().code(),
+                "invalid_argument"
+            );
+        }
+    }
+}
@@ -2,14 +2,43 @@
 
 use serde_json::Value;
 
+use crate::context::Env;
 use crate::error::Error;
+use crate::random::{self, RandomSource};
+use crate::value::Evaluated;
 
 /// Log the Operation's Value(s)
 ///
 /// The reference implementation ignores any arguments beyond the first,
 /// and the specification seems to indicate that the first argument is
 /// the only one considered, so we're doing the same.
-pub fn log(items: &Vec<&Value>) -> Result<Value, Error> {
-    println!("{}", items[0]);
-    Ok(items[0].clone())
+pub fn log(items: &[Evaluated]) -> Result<Value, Error> {
+    let value: &Value = &items[0];
+    // Without std, there's nowhere to log to
+    #[cfg(feature = "std")]
+    println!("{}", value);
+    Ok(value.clone())
+}
+
+/// The context's source of random numbers
+fn source<'a>(env: &'a Env, operation: &str) -> Result<&'a dyn RandomSource, Error> {
+    env.random().ok_or_else(|| Error::InvalidOperation {
+        key: operation.into(),
+        reason: "No random source was provided in the evaluation context".into(),
+    })
+}
+
+/// A random float in `[0, 1)`
+pub fn random(env: &Env) -> Result<Value, Error> {
+    random::random(source(env, "random")?)
+}
+
+/// A random integer between the first and second arguments, inclusive
+pub fn random_int(items: &[Evaluated], env: &Env) -> Result<Value, Error> {
+    random::random_int(source(env, "random_int")?, &items[0], &items[1])
+}
+
+/// A random (version 4) UUID
+pub fn uuid(env: &Env) -> Result<Value, Error> {
+    random::uuid(source(env, "uuid")?)
 }
@@ -0,0 +1,92 @@
+//! JSONPath Queries
+
+use serde_json::Value;
+use serde_json_path::JsonPath;
+
+use crate::error::Error;
+use crate::resolver::DataResolver;
+use crate::value::Evaluated;
+
+/// Select values from the data with a JSONPath (RFC 9535) query, returning
+/// an array of the values selected, in document order.
+pub fn jsonpath(data: &dyn DataResolver, args: &[Evaluated]) -> Result<Value, Error> {
+    let query = match &*args[0] {
+        Value::String(query) => query,
+        value => {
+            return Err(Error::InvalidArgument {
+                value: value.clone(),
+                operation: "jsonpath",
+                reason: "JSONPath queries must be strings".into(),
+            })
+        }
+    };
+    let path = JsonPath::parse(query).map_err(|err| Error::InvalidArgument {
+        value: Value::String(query.clone()),
+        operation: "jsonpath",
+        reason: err.to_string(),
+    })?;
+    let document = data.resolve(&[]).unwrap_or_default();
+    Ok(Value::Array(
+        path.query(&document).all().into_iter().cloned().collect(),
+    ))
+}
+
+#[cfg(test)]
+mod test_jsonpath {
+    use serde_json::{json, Value};
+
+    use crate::{Error, Rule};
+
+    fn apply(query: Value, data: Value) -> Result<Value, Error> {
+        Rule::from_value(&json!({ "jsonpath": query }))?.apply(&data)
+    }
+
+    #[test]
+    fn test_jsonpath() {
+        let data = json!({
+            "store": {
+                "items": [
+                    {"name": "a", "price": 5},
+                    {"name": "b", "price": 15},
+                ],
+                "archive": {"items": [{"name": "c", "price": 20}]}
+            }
+        });
+        assert_eq!(
+            apply(json!("$..items[?(@.price > 10)].name"), data.clone()).unwrap(),
+            json!(["b", "c"])
+        );
+        assert_eq!(
+            apply(json!("$.store.items[0]"), data.clone()).unwrap(),
+            json!([{"name": "a", "price": 5}])
+        );
+        assert_eq!(apply(json!("$.nope"), data.clone()).unwrap(), json!([]));
+        assert_eq!(apply(json!("$"), json!(null)).unwrap(), json!([null]));
+        // The query may be computed
+        assert_eq!(
+            Rule::from_value(&json!({"jsonpath": {"cat": ["$.", {"var": "key"}]}}))
+                .unwrap()
+                .apply(&json!({"key": "x", "x": 1}))
+                .unwrap(),
+            json!([1])
+        );
+    }
+
+    #[test]
+    fn test_invalid_query() {
+        assert_eq!(
+            apply(json!("store"), json!({})).unwrap_err().code(),
+            "invalid_argument"
+        );
+        assert_eq!(
+            apply(json!(1), json!({})).unwrap_err().code(),
+            "invalid_argument"
+        );
+    }
+
+    #[test]
+    fn test_untranslatable() {
+        let rule = Rule::from_value(&json!({"jsonpath": "$.a"})).unwrap();
+        assert_eq!(rule.to_javascript().unwrap_err().code(), "untranslatable");
+    }
+}
@@ -1,9 +1,14 @@
 //! Boolean Logic Operations
 
-use serde_json::Value;
+use serde_json::{Map, Value};
 
+use crate::context::Env;
 use crate::error::Error;
-use crate::value::{Evaluated, Parsed};
+use crate::namespace::Scope;
+use crate::op::data::Variable;
+use crate::op::pattern;
+use crate::prelude::*;
+use crate::value::{Evaluated, Object, Parsed};
 use crate::NULL;
 
 /// Implement the "if" operator
@@ -12,7 +17,7 @@ use crate::NULL;
 /// However, it can lso work like:
 ///     [condition, true, condition2, true2, false2]
 ///     for an if/elseif/else type of operation
-pub fn if_(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
+pub fn if_(data: &Value, args: &[Parsed], env: &Env) -> Result<Value, Error> {
     // Special case incorrect arguments. These are not defined in the
     // specification, but they are defined in the test cases.
     match args.len() {
@@ -24,14 +29,13 @@ pub fn if_(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
         // evaluates, but this is I can gather is the expected behavior
         // from the tests.
         1 => {
-            let parsed = Parsed::from_value(args[0])?;
-            let evaluated = parsed.evaluate(&data)?;
+            let evaluated = args[0].evaluate(data, env)?;
             return Ok(evaluated.into());
         }
         _ => {}
     }
 
-    args.into_iter()
+    args.iter()
         .enumerate()
         // Our accumulator is:
         //  - last conditional evaluation value,
@@ -45,8 +49,11 @@ pub fn if_(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
             }
             // Potential false-value, initial evaluation, or else-if clause
             else if i % 2 == 0 {
-                let parsed = Parsed::from_value(val)?;
-                let eval = parsed.evaluate(data)?;
+                let eval = val.evaluate(data, env)?;
+                // The else clause
+                if i == args.len() - 1 {
+                    env.branch_taken(args, i);
+                }
                 let is_truthy = match eval {
                     Evaluated::New(ref v) => truthy(v),
                     Evaluated::Raw(v) => truthy(v),
@@ -60,8 +67,8 @@ pub fn if_(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
                 // If there was a previous evaluation and it was truthy,
                 // return, and indicate we're a final value.
                 if was_truthy {
-                    let parsed = Parsed::from_value(val)?;
-                    let t_eval = parsed.evaluate(data)?;
+                    env.branch_taken(args, i);
+                    let t_eval = val.evaluate(data, env)?;
                     Ok((Value::from(t_eval), true, true))
                 } else {
                     // Return a null for the last eval to handle cases
@@ -74,36 +81,40 @@ pub fn if_(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
 }
 
 /// Perform short-circuiting or evaluation
-pub fn or(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
+pub fn or(data: &Value, args: &[Parsed], env: &Env) -> Result<Value, Error> {
     enum OrResult {
         Uninitialized,
         Truthy(Value),
         Current(Value),
     }
 
-    let eval =
-        args.into_iter()
-            .fold(Ok(OrResult::Uninitialized), |last_res, current| {
-                let last_eval = last_res?;
+    let eval = args.iter().enumerate().fold(
+        Ok(OrResult::Uninitialized),
+        |last_res, (i, current)| {
+            let last_eval = last_res?;
 
-                // if we've found a truthy value, don't evaluate anything else
-                if let OrResult::Truthy(_) = last_eval {
-                    return Ok(last_eval);
-                }
+            // if we've found a truthy value, don't evaluate anything else
+            if let OrResult::Truthy(_) = last_eval {
+                return Ok(last_eval);
+            }
 
-                let parsed = Parsed::from_value(current)?;
-                let evaluated = parsed.evaluate(data)?;
+            let evaluated = current.evaluate(data, env)?;
 
-                if truthy_from_evaluated(&evaluated) {
-                    return Ok(OrResult::Truthy(evaluated.into()));
-                }
+            if truthy_from_evaluated(&evaluated) {
+                env.branch_taken(args, i);
+                return Ok(OrResult::Truthy(evaluated.into()));
+            }
 
-                Ok(OrResult::Current(evaluated.into()))
-            })?;
+            Ok(OrResult::Current(evaluated.into()))
+        },
+    )?;
 
     match eval {
         OrResult::Truthy(v) => Ok(v),
-        OrResult::Current(v) => Ok(v),
+        OrResult::Current(v) => {
+            env.branch_taken(args, args.len() - 1);
+            Ok(v)
+        }
         _ => Err(Error::UnexpectedError(
             "Or operation had no values to operate on".into(),
         )),
@@ -111,41 +122,180 @@ pub fn or(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
 }
 
 /// Perform short-circuiting and evaluation
-pub fn and(data: &Value, args: &Vec<&Value>) -> Result<Value, Error> {
+pub fn and(data: &Value, args: &[Parsed], env: &Env) -> Result<Value, Error> {
     enum AndResult {
         Uninitialized,
         Falsey(Value),
         Current(Value),
     }
 
-    let eval =
-        args.into_iter()
-            .fold(Ok(AndResult::Uninitialized), |last_res, current| {
-                let last_eval = last_res?;
+    let eval = args.iter().enumerate().fold(
+        Ok(AndResult::Uninitialized),
+        |last_res, (i, current)| {
+            let last_eval = last_res?;
 
-                if let AndResult::Falsey(_) = last_eval {
-                    return Ok(last_eval);
-                }
+            if let AndResult::Falsey(_) = last_eval {
+                return Ok(last_eval);
+            }
 
-                let parsed = Parsed::from_value(current)?;
-                let evaluated = parsed.evaluate(data)?;
+            let evaluated = current.evaluate(data, env)?;
 
-                if !truthy_from_evaluated(&evaluated) {
-                    return Ok(AndResult::Falsey(evaluated.into()));
-                }
+            if !truthy_from_evaluated(&evaluated) {
+                env.branch_taken(args, i);
+                return Ok(AndResult::Falsey(evaluated.into()));
+            }
 
-                Ok(AndResult::Current(evaluated.into()))
-            })?;
+            Ok(AndResult::Current(evaluated.into()))
+        },
+    )?;
 
     match eval {
         AndResult::Falsey(v) => Ok(v),
-        AndResult::Current(v) => Ok(v),
+        AndResult::Current(v) => {
+            env.branch_taken(args, args.len() - 1);
+            Ok(v)
+        }
         _ => Err(Error::UnexpectedError(
             "And operation had no values to operate on".into(),
         )),
     }
 }
 
+/// Return the first argument that isn't null, evaluating no more of them than
+/// necessary, or null if they all are.
+pub fn coalesce(data: &Value, args: &[Parsed], env: &Env) -> Result<Value, Error> {
+    for arg in args {
+        let value = Value::from(arg.evaluate(data, env)?);
+        if !value.is_null() {
+            return Ok(value);
+        }
+    }
+    Ok(NULL)
+}
+
+/// Return the first argument that isn't missing, evaluating no more of them
+/// than necessary, or null if they all are.
+///
+/// Only `var` lookups (without defaults of their own) may be missing, so
+/// unlike `coalesce`, a variable that is present but null is returned.
+pub fn default(data: &Value, args: &[Parsed], env: &Env) -> Result<Value, Error> {
+    for arg in args {
+        match arg {
+            Parsed::Variable(var) if var.arguments().len() < 2 => {
+                let value =
+                    env.execute(Variable::OPERATOR, var.arguments().len(), || {
+                        var.lookup(data, env)
+                    })?;
+                if let Some(value) = value {
+                    return Ok(value);
+                }
+            }
+            _ => return arg.evaluate(data, env).map(Value::from),
+        }
+    }
+    Ok(NULL)
+}
+
+/// Thread a value through a series of steps. The first argument is
+/// evaluated against the data, and each of the others against the result of
+/// the one before, so that within a step `{"var": ""}` is the previous result.
+pub fn pipe(data: &Value, args: &[Parsed], env: &Env) -> Result<Value, Error> {
+    let mut result = Value::from(args[0].evaluate(data, env)?);
+    let env = env.rebind();
+    for step in &args[1..] {
+        let next = Value::from(step.evaluate(&result, &env)?);
+        result = next;
+    }
+    Ok(result)
+}
+
+/// Evaluate the first argument, or if that fails, the fallback in the
+/// second. With a third argument, a name, the fallback is evaluated against
+/// the data with the error's message under that name.
+///
+/// Cancellation isn't an error in the rule, so it isn't caught.
+pub fn try_(data: &Value, args: &[Parsed], env: &Env) -> Result<Value, Error> {
+    match args[0].evaluate(data, env) {
+        Ok(result) => Ok(result.into()),
+        Err(err @ Error::Cancelled(_)) => Err(err),
+        Err(err) => match args.get(2) {
+            Some(name) => {
+                let name = Value::from(name.clone());
+                let mut captures = Map::new();
+                captures.insert(error_name(&name)?.into(), err.to_string().into());
+                let data = pattern::branch_data(data, captures);
+                args[1].evaluate(&data, &env.rebind()).map(Value::from)
+            }
+            None => args[1].evaluate(data, env).map(Value::from),
+        },
+    }
+}
+
+pub(crate) const TRY: &str = "try";
+
+/// The name a `try` gives its error
+pub(crate) fn error_name(name: &Value) -> Result<&str, Error> {
+    name.as_str().ok_or_else(|| {
+        Error::invalid_argument(name.clone(), TRY, "The error's name must be a string")
+    })
+}
+
+/// Evaluate the branch of the case whose key is the value of the first
+/// argument, a string, or otherwise the default branch, or null if there is
+/// none. The cases are an object of keys and branches.
+pub fn switch(data: &Value, args: &[Parsed], env: &Env) -> Result<Value, Error> {
+    let value = Value::from(args[0].evaluate(data, env)?);
+    let cases = match &args[1] {
+        Parsed::Object(cases) => cases.entries(),
+        _ => {
+            return Err(Error::UnexpectedError(
+                "The cases of a switch were parsed as an expression".into(),
+            ))
+        }
+    };
+    let branch = switch_key(&value)?
+        .and_then(|value| cases.iter().find(|(key, _)| key == value))
+        .map(|(_, branch)| branch);
+    match branch.or(args.get(2)) {
+        Some(branch) => branch.evaluate(data, env).map(Value::from),
+        None => Ok(NULL),
+    }
+}
+
+pub(crate) const SWITCH: &str = "switch";
+
+/// The key of the case a `switch` value selects, which is the value itself
+/// if it's a string. Null, as of a missing value, selects no case, and any
+/// other value is an error, rather than silently selecting the default.
+pub(crate) fn switch_key(value: &Value) -> Result<Option<&str>, Error> {
+    match value {
+        Value::String(key) => Ok(Some(key)),
+        Value::Null => Ok(None),
+        value => Err(Error::invalid_argument(
+            value.clone(),
+            SWITCH,
+            "The value must be a string, the key of a case",
+        )),
+    }
+}
+
+/// The cases of a `switch`, whose branches are parsed as expressions even if
+/// there's only one
+pub(crate) fn switch_cases(cases: &Value, scope: &Scope) -> Result<Parsed, Error> {
+    let cases = cases.as_object().ok_or_else(|| {
+        Error::invalid_argument(
+            cases.clone(),
+            SWITCH,
+            "The cases must be an object of keys and branches",
+        )
+    })?;
+    cases
+        .iter()
+        .map(|(key, branch)| Ok((key.clone(), Parsed::from_value(branch, scope)?)))
+        .collect::<Result<Vec<(String, Parsed)>, Error>>()
+        .map(|entries| Parsed::Object(Object::new(entries)))
+}
+
 pub fn truthy_from_evaluated(evaluated: &Evaluated) -> bool {
     match evaluated {
         Evaluated::New(ref v) => truthy(v),
@@ -189,6 +339,157 @@ pub fn truthy(val: &Value) -> bool {
     }
 }
 
+#[cfg(test)]
+mod test_pipe {
+    use serde_json::json;
+
+    use crate::Rule;
+
+    #[test]
+    fn test_pipe() {
+        let apply = |rule: serde_json::Value, data: serde_json::Value| {
+            Rule::from_value(&rule).unwrap().apply(&data).unwrap()
+        };
+        assert_eq!(
+            apply(
+                json!({"pipe": [
+                    {"var": "xs"},
+                    {"filter": [{"var": ""}, {">": [{"var": ""}, 1]}]},
+                    {"map": [{"var": ""}, {"*": [{"var": ""}, 10]}]},
+                    {"reduce": [{"var": ""}, {"+": [{"var": "accumulator"}, {"var": "current"}]}, 0]}
+                ]}),
+                json!({"xs": [1, 2, 3]})
+            ),
+            json!(50)
+        );
+        // Steps see the previous result, not the data
+        assert_eq!(
+            apply(
+                json!({"pipe": [{"var": "a"}, {"var": "b"}, {"var": ["a", "none"]}]}),
+                json!({"a": {"b": {"c": 1}}})
+            ),
+            json!("none")
+        );
+        assert_eq!(apply(json!({"pipe": [3]}), json!(null)), json!(3));
+    }
+}
+
+#[cfg(test)]
+mod test_coalesce {
+    use serde_json::json;
+
+    use crate::Rule;
+
+    #[test]
+    fn test_coalesce_and_default() {
+        let data = json!({"a": null, "b": 0, "c": "c"});
+        let apply = |rule: serde_json::Value| {
+            let rule = Rule::from_value(&rule).unwrap();
+            let compiled = rule.compile().unwrap();
+            let result = rule.apply(&data).unwrap();
+            assert_eq!(compiled.apply(&data).unwrap(), result);
+            result
+        };
+        assert_eq!(
+            apply(json!({"coalesce": [{"var": "a"}, {"var": "b"}]})),
+            json!(0)
+        );
+        assert_eq!(
+            apply(json!({"coalesce": [{"var": "x"}, null]})),
+            json!(null)
+        );
+        assert_eq!(apply(json!({"coalesce": []})), json!(null));
+        // Later arguments aren't evaluated
+        assert_eq!(
+            apply(json!({"coalesce": [{"var": "c"}, {"/": [1, "x"]}]})),
+            json!("c")
+        );
+
+        // Nulls that are present aren't missing
+        assert_eq!(apply(json!({"default": [{"var": "a"}, 1]})), json!(null));
+        assert_eq!(
+            apply(json!({"default": [{"var": "x"}, {"var": "y"}, 1]})),
+            json!(1)
+        );
+        assert_eq!(
+            apply(json!({"default": [{"var": "x"}, {"var": "b"}, 1]})),
+            json!(0)
+        );
+        assert_eq!(apply(json!({"default": [{"var": "x"}]})), json!(null));
+        assert_eq!(apply(json!({"default": [{"var": ["x", 2]}, 1]})), json!(2));
+        assert_eq!(
+            apply(json!({"default": [null, {"/": [1, "x"]}]})),
+            json!(null)
+        );
+        assert_eq!(
+            apply(
+                json!({"default": [{"var": {"cat": ["x", "y"]}}, {"var": {"cat": ["", "c"]}}]})
+            ),
+            json!("c")
+        );
+    }
+}
+
+#[cfg(test)]
+mod test_try {
+    use serde_json::json;
+
+    use crate::error::Error;
+    use crate::Rule;
+
+    #[test]
+    fn test_try() {
+        let apply = |rule: serde_json::Value, data: serde_json::Value| {
+            let rule = Rule::from_value(&rule).unwrap();
+            let compiled = rule.compile().unwrap();
+            let result = rule.apply(&data).unwrap();
+            assert_eq!(compiled.apply(&data).unwrap(), result);
+            result
+        };
+        assert_eq!(
+            apply(
+                json!({"try": [{"+": [{"var": "a"}, 1]}, 0]}),
+                json!({"a": 1})
+            ),
+            json!(2)
+        );
+        assert_eq!(
+            apply(
+                json!({"try": [{"+": [{"var": "a"}, 1]}, 0]}),
+                json!({"a": "x"})
+            ),
+            json!(0)
+        );
+        // The fallback may see the error, alongside the data
+        assert_eq!(
+            apply(
+                json!({"try": [
+                    {"substr": [{"var": "s"}, "x"]},
+                    {"cat": [{"var": "s"}, ": ", {"var": "e"}]},
+                    "e"
+                ]}),
+                json!({"s": "abc"})
+            ),
+            json!(format!(
+                "abc: {}",
+                Rule::from_value(&json!({"substr": ["abc", "x"]}))
+                    .unwrap()
+                    .apply(&json!(null))
+                    .unwrap_err()
+            ))
+        );
+        // Errors in the fallback aren't caught
+        match Rule::from_value(&json!({"try": [{"/": [1, "x"]}, {"/": [1, "y"]}]}))
+            .unwrap()
+            .apply(&json!(null))
+        {
+            Err(Error::InvalidArgument { .. }) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        Rule::from_value(&json!({"try": [1, 2, {"var": "e"}]})).unwrap_err();
+    }
+}
+
 #[cfg(test)]
 mod test_truthy {
     use super::*;
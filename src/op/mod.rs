@@ -9,51 +9,87 @@
 // as operators. They were originally done differently because there wasn't
 // yet a LazyOperator concept.
 
+use core::{cmp, fmt, iter};
 use phf::phf_map;
 use serde_json::{Map, Value};
-use std::fmt;
 
+use crate::arena::ArenaSlice;
+use crate::context::Env;
 use crate::error::Error;
-use crate::value::to_number_value;
-use crate::value::{Evaluated, Parsed};
+use crate::namespace::Scope;
+use crate::prelude::*;
+use crate::resolver::DataResolver;
+use crate::value::{Evaluated, Parsed, Raw};
 use crate::{js_op, Parser};
 
 pub(crate) mod arithmetic;
-mod array;
-mod data;
+pub(crate) mod array;
+#[cfg(feature = "collation")]
+mod collation;
+#[cfg(feature = "crypto")]
+mod crypto;
+pub(crate) mod data;
+mod date;
 pub(crate) mod equality;
 pub(crate) mod func;
+#[cfg(feature = "geo")]
+mod geo;
 mod impure;
-mod logic;
+#[cfg(feature = "jsonpath")]
+mod jsonpath;
+pub(crate) mod logic;
+mod money;
+pub(crate) mod object;
+mod patch;
+pub(crate) mod pattern;
+mod semver;
 mod string;
+#[cfg(feature = "url")]
+mod url;
 
 pub const OPERATOR_MAP: phf::Map<&'static str, Operator> = phf_map! {
     "eq" => Operator {
         symbol: "eq",
-        operator: equality::equal,
+        operator: |items, _| equality::equal(items),
         num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Comparison,
+        doc: "Whether two values are equal, without converting their types. Arrays and objects are compared by their contents.",
     },
     "ne" => Operator {
         symbol: "ne",
-        operator: equality::not_equal,
+        operator: |items, _| equality::not_equal(items),
         num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Comparison,
+        doc: "Whether two values differ, without converting their types. Arrays and objects are compared by their contents.",
     },
     "==" => Operator {
         symbol: "==",
-        operator: |items| Ok(Value::Bool(js_op::abstract_eq(items[0], items[1]))),
-        num_params: NumParams::Exactly(2)},
+        operator: |items, _| Ok(Value::Bool(js_op::abstract_eq(&items[0], &items[1]))),
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Comparison,
+        doc: "Whether two values are equal, converting their types as JavaScript's == does.",
+    },
     "!=" => Operator {
         symbol: "!=",
-        operator: |items| Ok(Value::Bool(js_op::abstract_ne(items[0], items[1]))),
-        num_params: NumParams::Exactly(2)},
+        operator: |items, _| Ok(Value::Bool(js_op::abstract_ne(&items[0], &items[1]))),
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Comparison,
+        doc: "Whether two values differ, converting their types as JavaScript's != does.",
+    },
     "===" => Operator {
         symbol: "===",
-        operator: |items| Ok(Value::Bool(js_op::strict_eq(items[0], items[1]))),
-        num_params: NumParams::Exactly(2)},
+        operator: |items, _| Ok(Value::Bool(js_op::strict_eq(&items[0], &items[1]))),
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Comparison,
+        doc: "Whether two values are equal and of the same type.",
+    },
     "!==" => Operator {
         symbol: "!==",
-        operator: |items| Ok(Value::Bool(js_op::strict_ne(items[0], items[1]))),
-        num_params: NumParams::Exactly(2)},
+        operator: |items, _| Ok(Value::Bool(js_op::strict_ne(&items[0], &items[1]))),
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Comparison,
+        doc: "Whether two values differ in value or type.",
+    },
     // Note: the ! and !! behavior conforms to the specification, but not the
     // reference implementation. The specification states: "Note: unary
     // operators can also take a single, non array argument." However,
@@ -66,23 +102,31 @@ pub const OPERATOR_MAP: phf::Map<&'static str, Operator> = phf_map! {
     // is that it is "Consistent. `{"operator" : ["values" ... ]}` Always"
     "!" => Operator {
         symbol: "!",
-        operator: |items| Ok(Value::Bool(!logic::truthy(items[0]))),
+        operator: |items, _| Ok(Value::Bool(!logic::truthy(&items[0]))),
         num_params: NumParams::Unary,
+        category: OperatorCategory::Logic,
+        doc: "Whether the value is falsy.",
     },
     "!!" => Operator {
         symbol: "!!",
-        operator: |items| Ok(Value::Bool(logic::truthy(items[0]))),
+        operator: |items, _| Ok(Value::Bool(logic::truthy(&items[0]))),
         num_params: NumParams::Unary,
+        category: OperatorCategory::Logic,
+        doc: "Whether the value is truthy.",
     },
     "<" => Operator {
         symbol: "<",
         operator: arithmetic::lt,
         num_params: NumParams::Variadic(2..4),
+        category: OperatorCategory::Comparison,
+        doc: "Whether each value is less than the next, e.g. for a range check with three values.",
     },
     "<=" => Operator {
         symbol: "<=",
         operator: arithmetic::lte,
         num_params: NumParams::Variadic(2..4),
+        category: OperatorCategory::Comparison,
+        doc: "Whether each value is less than or equal to the next, e.g. for a range check with three values.",
     },
     // Note: this is actually an _expansion_ on the specification and the
     // reference implementation. The spec states that < and <= can be used
@@ -95,107 +139,548 @@ pub const OPERATOR_MAP: phf::Map<&'static str, Operator> = phf_map! {
         symbol: ">",
         operator: arithmetic::gt,
         num_params: NumParams::Variadic(2..4),
+        category: OperatorCategory::Comparison,
+        doc: "Whether each value is greater than the next.",
     },
     ">=" => Operator {
         symbol: ">=",
         operator: arithmetic::gte,
         num_params: NumParams::Variadic(2..4),
+        category: OperatorCategory::Comparison,
+        doc: "Whether each value is greater than or equal to the next.",
+    },
+    "semver_gt" => Operator {
+        symbol: "semver_gt",
+        operator: |items, _| semver::semver_gt(items),
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Comparison,
+        doc: "Whether the first semantic version is greater than the second, e.g. \"1.10.0\" than \"1.9.2\".",
+    },
+    "semver_satisfies" => Operator {
+        symbol: "semver_satisfies",
+        operator: |items, _| semver::semver_satisfies(items),
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Comparison,
+        doc: "Whether a semantic version is in a range written as a Cargo version requirement, with || between alternatives, e.g. \">=1.2, <2 || ^3.1\".",
+    },
+    "money" => Operator {
+        symbol: "money",
+        operator: |items, _| money::money(items),
+        num_params: NumParams::Variadic(1..3),
+        category: OperatorCategory::Arithmetic,
+        doc: "A money value, e.g. \"12.30 USD\", from a string like \"12.3 USD\" or from an amount and a currency code.",
+    },
+    "money_add" => Operator {
+        symbol: "money_add",
+        operator: |items, _| money::money_add(items),
+        num_params: NumParams::AtLeast(1),
+        category: OperatorCategory::Arithmetic,
+        doc: "The exact sum of money values of one currency.",
+    },
+    "money_sub" => Operator {
+        symbol: "money_sub",
+        operator: |items, _| money::money_sub(items),
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Arithmetic,
+        doc: "The exact difference of two money values of one currency.",
+    },
+    "money_cmp" => Operator {
+        symbol: "money_cmp",
+        operator: |items, _| money::money_cmp(items),
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Comparison,
+        doc: "-1, 0, or 1 as the first money value is less than, equal to, or greater than the second, of the same currency.",
+    },
+    "money_convert" => Operator {
+        symbol: "money_convert",
+        operator: money::money_convert,
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Arithmetic,
+        doc: "A money value converted to another currency with the context's exchange rates, rounded to the currency's minor unit.",
+    },
+    "duration" => Operator {
+        symbol: "duration",
+        operator: |items, _| date::duration(items),
+        num_params: NumParams::Unary,
+        category: OperatorCategory::Arithmetic,
+        doc: "The number of seconds in an ISO 8601 duration, e.g. \"PT1H30M\", or shorthand, e.g. \"7d\" or \"1h30m\".",
+    },
+    "date_add" => Operator {
+        symbol: "date_add",
+        operator: |items, _| date::date_add(items),
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Arithmetic,
+        doc: "An RFC 3339 date plus a duration, as a string or a number of seconds, in UTC.",
+    },
+    "date_sub" => Operator {
+        symbol: "date_sub",
+        operator: |items, _| date::date_sub(items),
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Arithmetic,
+        doc: "An RFC 3339 date minus a duration, as a string or a number of seconds, in UTC.",
+    },
+    "within_last" => Operator {
+        symbol: "within_last",
+        operator: date::within_last,
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Comparison,
+        doc: "Whether an RFC 3339 date is within a duration before the current time, e.g. {\"within_last\": [{\"var\": \"last_login\"}, \"30d\"]}.",
     },
     "+" => Operator {
         symbol: "+",
-        operator: |items| js_op::parse_float_add(items).and_then(to_number_value),
+        operator: arithmetic::plus,
         num_params: NumParams::Any,
+        category: OperatorCategory::Arithmetic,
+        doc: "The sum of the values, converted to numbers.",
     },
     "add" => Operator {
         symbol: "add",
         operator: arithmetic::add,
         num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Arithmetic,
+        doc: "The sum of two numbers, which must already be numbers.",
+    },
+    "convert" => Operator {
+        symbol: "convert",
+        operator: arithmetic::convert,
+        num_params: NumParams::Exactly(3),
+        category: OperatorCategory::Arithmetic,
+        doc: "A number converted from one unit of measure to another of the same dimension, e.g. from \"km\" to \"mi\", with the context's units.",
     },
     "-" => Operator {
         symbol: "-",
         operator: arithmetic::minus,
         num_params: NumParams::Variadic(1..3),
+        category: OperatorCategory::Arithmetic,
+        doc: "The difference of two numbers, or the negation of one.",
     },
     "*" => Operator {
         symbol: "*",
-        operator: |items| js_op::parse_float_mul(items).and_then(to_number_value),
+        operator: arithmetic::times,
         num_params: NumParams::AtLeast(1),
+        category: OperatorCategory::Arithmetic,
+        doc: "The product of the values.",
     },
     "/" => Operator {
         symbol: "/",
-        operator: |items| js_op::abstract_div(items[0], items[1])
-            .and_then(to_number_value),
+        operator: arithmetic::divide,
         num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Arithmetic,
+        doc: "The quotient of two numbers.",
     },
     "%" => Operator {
         symbol: "%",
-        operator: |items| js_op::abstract_mod(items[0], items[1])
-            .and_then(to_number_value),
+        operator: arithmetic::remainder,
         num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Arithmetic,
+        doc: "The remainder of dividing the first number by the second.",
     },
     "max" => Operator {
         symbol: "max",
-        operator: |items| js_op::abstract_max(items)
-            .and_then(to_number_value),
+        operator: arithmetic::max,
         num_params: NumParams::AtLeast(1),
+        category: OperatorCategory::Arithmetic,
+        doc: "The largest of the values.",
     },
     "min" => Operator {
         symbol: "min",
-        operator: |items| js_op::abstract_min(items)
-            .and_then(to_number_value),
+        operator: arithmetic::min,
+        num_params: NumParams::AtLeast(1),
+        category: OperatorCategory::Arithmetic,
+        doc: "The smallest of the values.",
+    },
+    "comp" => Operator {
+        symbol: "comp",
+        operator: |items, _| func::comp(items),
+        num_params: NumParams::AtLeast(1),
+        category: OperatorCategory::Function,
+        doc: "The composition of function values, applying the last first.",
+    },
+    "closure" => Operator {
+        symbol: "closure",
+        operator: |items, _| func::closure(items),
         num_params: NumParams::AtLeast(1),
+        category: OperatorCategory::Function,
+        doc: "A function value, capturing arguments to pass ahead of any others.",
+    },
+    "partial" => Operator {
+        symbol: "partial",
+        operator: |items, _| func::partial(items),
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Function,
+        doc: "A function value, partially applied to an array of arguments.",
     },
     "merge" => Operator {
         symbol: "merge",
-        operator: array::merge,
+        operator: |items, _| array::merge(items),
         num_params: NumParams::Any,
+        category: OperatorCategory::Array,
+        doc: "The values in one array, with arrays flattened one level.",
+    },
+    "reduced" => Operator {
+        symbol: "reduced",
+        operator: array::reduced,
+        num_params: NumParams::Unary,
+        category: OperatorCategory::Array,
+        doc: "A value wrapped to signal that a reduce_while is complete, with the value as its result.",
+    },
+    "zip" => Operator {
+        symbol: "zip",
+        operator: |items, _| array::zip(items),
+        num_params: NumParams::AtLeast(2),
+        category: OperatorCategory::Array,
+        doc: "The arrays of the elements at each index of two or more arrays, up to the length of the shortest, e.g. [[1, \"a\"], [2, \"b\"]] for [1, 2] and [\"a\", \"b\"].",
+    },
+    "cartesian" => Operator {
+        symbol: "cartesian",
+        operator: array::cartesian,
+        num_params: NumParams::AtLeast(2),
+        category: OperatorCategory::Array,
+        doc: "Every combination of an element of each of two or more arrays, as arrays, up to the context's maximum array size.",
+    },
+    "chunk" => Operator {
+        symbol: "chunk",
+        operator: |items, _| array::chunk(items),
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Array,
+        doc: "An array split into arrays of a size, the last of which may be shorter, e.g. [[1, 2], [3]] for [1, 2, 3] and 2.",
+    },
+    "window" => Operator {
+        symbol: "window",
+        operator: |items, _| array::window(items),
+        num_params: NumParams::Variadic(2..4),
+        category: OperatorCategory::Array,
+        doc: "The sliding windows of a size over an array, moving by a step that defaults to 1, e.g. [[1, 2], [2, 3]] for [1, 2, 3] and 2.",
+    },
+    "take" => Operator {
+        symbol: "take",
+        operator: |items, _| array::take(items),
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Array,
+        doc: "The first elements of an array, or the last for a negative count.",
+    },
+    "drop" => Operator {
+        symbol: "drop",
+        operator: |items, _| array::drop(items),
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Array,
+        doc: "An array without its first elements, or without its last for a negative count.",
+    },
+    "range" => Operator {
+        symbol: "range",
+        operator: array::range,
+        num_params: NumParams::Variadic(1..4),
+        category: OperatorCategory::Array,
+        doc: "The numbers from a start up to, but not including, an end, by a step, e.g. [0, 2, 4] for [0, 6, 2], or from 0 if only an end is given.",
     },
     "in" => Operator {
         symbol: "in",
-        operator: array::in_,
+        operator: |items, _| array::in_(items),
         num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Array,
+        doc: "Whether the first value is in the array, or a substring of the string, given second.",
     },
     "cat" => Operator {
         symbol: "cat",
-        operator: string::cat,
+        operator: |items, _| string::cat(items),
         num_params: NumParams::Any,
+        category: OperatorCategory::String,
+        doc: "The strings, concatenated.",
     },
     "substr" => Operator {
         symbol: "substr",
-        operator: string::substr,
+        operator: |items, _| string::substr(items),
+        num_params: NumParams::Variadic(2..4),
+        category: OperatorCategory::String,
+        doc: "The portion of a string from a start index, optionally limited to a length. Negative values count from the end.",
+    },
+    "obj" => Operator {
+        symbol: "obj",
+        operator: |items, _| object::obj(items),
+        num_params: NumParams::Any,
+        category: OperatorCategory::Data,
+        doc: "An object built from [key, value] pairs of expressions, with later values replacing earlier ones of the same key.",
+    },
+    "patch" => Operator {
+        symbol: "patch",
+        operator: |items, _| patch::patch(items),
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Data,
+        doc: "The document with a JSON Patch (RFC 6902), an array of operations, applied.",
+    },
+    "merge_patch" => Operator {
+        symbol: "merge_patch",
+        operator: |items, _| patch::merge_patch(items),
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Data,
+        doc: "The document with a JSON Merge Patch (RFC 7386) applied.",
+    },
+    "diff" => Operator {
+        symbol: "diff",
+        operator: |items, _| patch::diff(items),
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Data,
+        doc: "A JSON Patch (RFC 6902) that changes the first value into the second.",
+    },
+    "upper" => Operator {
+        symbol: "upper",
+        operator: |items, _| string::upper(items),
+        num_params: NumParams::Unary,
+        category: OperatorCategory::String,
+        doc: "The string in upper case.",
+    },
+    "lower" => Operator {
+        symbol: "lower",
+        operator: |items, _| string::lower(items),
+        num_params: NumParams::Unary,
+        category: OperatorCategory::String,
+        doc: "The string in lower case.",
+    },
+    "trim" => Operator {
+        symbol: "trim",
+        operator: |items, _| string::trim(items),
+        num_params: NumParams::Unary,
+        category: OperatorCategory::String,
+        doc: "The string without leading or trailing whitespace.",
+    },
+    "trim_start" => Operator {
+        symbol: "trim_start",
+        operator: |items, _| string::trim_start(items),
+        num_params: NumParams::Unary,
+        category: OperatorCategory::String,
+        doc: "The string without leading whitespace.",
+    },
+    "trim_end" => Operator {
+        symbol: "trim_end",
+        operator: |items, _| string::trim_end(items),
+        num_params: NumParams::Unary,
+        category: OperatorCategory::String,
+        doc: "The string without trailing whitespace.",
+    },
+    "pad_start" => Operator {
+        symbol: "pad_start",
+        operator: |items, _| string::pad_start(items),
         num_params: NumParams::Variadic(2..4),
+        category: OperatorCategory::String,
+        doc: "The string padded at its start to a length, with a fill string or spaces.",
+    },
+    "pad_end" => Operator {
+        symbol: "pad_end",
+        operator: |items, _| string::pad_end(items),
+        num_params: NumParams::Variadic(2..4),
+        category: OperatorCategory::String,
+        doc: "The string padded at its end to a length, with a fill string or spaces.",
+    },
+    "starts_with" => Operator {
+        symbol: "starts_with",
+        operator: |items, _| string::starts_with(items),
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::String,
+        doc: "Whether the first string starts with the second.",
+    },
+    "ends_with" => Operator {
+        symbol: "ends_with",
+        operator: |items, _| string::ends_with(items),
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::String,
+        doc: "Whether the first string ends with the second.",
+    },
+    "split" => Operator {
+        symbol: "split",
+        operator: |items, _| string::split(items),
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::String,
+        doc: "The parts of a string between occurrences of a separator, or its characters if the separator is empty.",
+    },
+    "join" => Operator {
+        symbol: "join",
+        operator: |items, _| string::join(items),
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Array,
+        doc: "The elements of an array, as strings, joined with a separator.",
+    },
+    "format" => Operator {
+        symbol: "format",
+        operator: string::format,
+        num_params: NumParams::AtLeast(1),
+        category: OperatorCategory::String,
+        doc: "A printf-style format string, with each conversion replaced by the next argument. Numbers use the separators of the context's locale.",
+    },
+    "template" => Operator {
+        symbol: "template",
+        operator: |items, _| string::template(items),
+        num_params: NumParams::Variadic(2..4),
+        category: OperatorCategory::String,
+        doc: "A template string with each `{path}` replaced by the value at the path in a context, optionally keeping placeholders for missing values.",
+    },
+    "lookup" => Operator {
+        symbol: "lookup",
+        operator: data::lookup,
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Data,
+        doc: "The value of a key in a table provided by the host.",
     },
     "log" => Operator {
         symbol: "log",
-        operator: impure::log,
+        operator: |items, _| impure::log(items),
         num_params: NumParams::Unary,
+        category: OperatorCategory::Misc,
+        doc: "The value, which is also logged.",
+    },
+    "random" => Operator {
+        symbol: "random",
+        operator: |_, env| impure::random(env),
+        num_params: NumParams::None,
+        category: OperatorCategory::Misc,
+        doc: "A random number of at least 0 and less than 1.",
+    },
+    "random_int" => Operator {
+        symbol: "random_int",
+        operator: impure::random_int,
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Misc,
+        doc: "A random integer between a minimum and maximum, inclusive.",
+    },
+    "uuid" => Operator {
+        symbol: "uuid",
+        operator: |_, env| impure::uuid(env),
+        num_params: NumParams::None,
+        category: OperatorCategory::Misc,
+        doc: "A random (version 4) UUID.",
     },
 };
 
 pub const DATA_OPERATOR_MAP: phf::Map<&'static str, DataOperator> = phf_map! {
-    "var" => DataOperator {
-        symbol: "var",
-        operator: data::var,
-        num_params: NumParams::Variadic(0..3)
-    },
     "missing" => DataOperator {
         symbol: "missing",
         operator: data::missing,
         num_params: NumParams::Any,
+        category: OperatorCategory::Data,
+        doc: "The keys that are missing from the data.",
     },
     "missing_some" => DataOperator {
         symbol: "missing_some",
         operator: data::missing_some,
         num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Data,
+        doc: "The keys that are missing from the data, or none if at least the given number are present.",
     },
 };
 
+/// Operators that are only built with a feature enabled. `phf_map!` can't
+/// include entries conditionally, so these are listed separately.
+pub(crate) const FEATURE_OPERATORS: &[Operator] = &[
+    #[cfg(feature = "collation")]
+    Operator {
+        symbol: "compare",
+        operator: collation::compare,
+        num_params: NumParams::Variadic(2..4),
+        category: OperatorCategory::String,
+        doc: "-1, 0, or 1 as the first string sorts before, with, or after the second by the rules of the context's locale, optionally ignoring case or accents, e.g. \"accent\" for a case-insensitive comparison.",
+    },
+    #[cfg(feature = "crypto")]
+    Operator {
+        symbol: "sha256",
+        operator: |items, _| crypto::sha256(items),
+        num_params: NumParams::Unary,
+        category: OperatorCategory::String,
+        doc: "The hex-encoded SHA-256 hash of a string, or of any other value's JSON.",
+    },
+    #[cfg(feature = "crypto")]
+    Operator {
+        symbol: "sha1",
+        operator: |items, _| crypto::sha1(items),
+        num_params: NumParams::Unary,
+        category: OperatorCategory::String,
+        doc: "The hex-encoded SHA-1 hash of a string, or of any other value's JSON. Not collision-resistant.",
+    },
+    #[cfg(feature = "crypto")]
+    Operator {
+        symbol: "hmac_sha256",
+        operator: crypto::hmac_sha256,
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::String,
+        doc: "The hex-encoded HMAC-SHA256 of a value, with the named key from the evaluation context, e.g. {\"hmac_sha256\": [{\"var\": \"id\"}, \"signing\"]}.",
+    },
+    #[cfg(feature = "url")]
+    Operator {
+        symbol: "url_parse",
+        operator: |items, _| url::url_parse(items),
+        num_params: NumParams::Unary,
+        category: OperatorCategory::String,
+        doc: "The components of a URL, as an object with its scheme, username, password, host, port, path, query, fragment, and query params, or null if it isn't a URL.",
+    },
+    #[cfg(feature = "url")]
+    Operator {
+        symbol: "url_host",
+        operator: |items, _| url::url_host(items),
+        num_params: NumParams::Unary,
+        category: OperatorCategory::String,
+        doc: "The lowercased host of a URL, or null if it has none.",
+    },
+    #[cfg(feature = "url")]
+    Operator {
+        symbol: "url_matches_pattern",
+        operator: |items, _| url::url_matches_pattern(items),
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::String,
+        doc: "Whether a URL matches a pattern like \"https://*.example.com/blog/*\", with wildcards for the scheme, subdomains, port, and within the path.",
+    },
+    #[cfg(feature = "geo")]
+    Operator {
+        symbol: "geo_distance",
+        operator: |items, _| geo::geo_distance(items),
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Arithmetic,
+        doc: "The great-circle distance in meters between two [latitude, longitude] points.",
+    },
+    #[cfg(feature = "geo")]
+    Operator {
+        symbol: "geo_within",
+        operator: |items, _| geo::geo_within(items),
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Comparison,
+        doc: "Whether a [latitude, longitude] point is within a GeoJSON Polygon or MultiPolygon, or a Feature with one.",
+    },
+];
+
+/// The operator with the given symbol, if there is one
+fn operator(key: &str) -> Option<&'static Operator> {
+    let operators: &'static phf::Map<&'static str, Operator> = &OPERATOR_MAP;
+    operators
+        .get(key)
+        .or_else(|| FEATURE_OPERATORS.iter().find(|op| op.symbol == key))
+}
+
+/// Data operators that are only built with a feature enabled. `phf_map!`
+/// can't include entries conditionally, so these are listed separately.
+pub(crate) const FEATURE_DATA_OPERATORS: &[DataOperator] = &[
+    #[cfg(feature = "jsonpath")]
+    DataOperator {
+        symbol: "jsonpath",
+        operator: |data, items, _| jsonpath::jsonpath(data, items),
+        num_params: NumParams::Unary,
+        category: OperatorCategory::Data,
+        doc: "The values in the data selected by a JSONPath query, e.g. \"$..items[?(@.price > 10)]\".",
+    },
+];
+
+/// The data operator with the given symbol, if there is one
+fn data_operator(key: &str) -> Option<&'static DataOperator> {
+    let operators: &'static phf::Map<&'static str, DataOperator> = &DATA_OPERATOR_MAP;
+    operators
+        .get(key)
+        .or_else(|| FEATURE_DATA_OPERATORS.iter().find(|op| op.symbol == key))
+}
+
 pub const LAZY_OPERATOR_MAP: phf::Map<&'static str, LazyOperator> = phf_map! {
     // Logical operators
     "if" => LazyOperator {
         symbol: "if",
         operator: logic::if_,
         num_params: NumParams::Any,
+        category: OperatorCategory::Logic,
+        doc: "The branch following the first truthy condition, or the final branch if none is truthy.",
     },
     // Note this operator isn't defined in the specification, but is
     // present in the tests as what looks like an alias for "if".
@@ -203,49 +688,453 @@ pub const LAZY_OPERATOR_MAP: phf::Map<&'static str, LazyOperator> = phf_map! {
         symbol: "?:",
         operator: logic::if_,
         num_params: NumParams::Any,
+        category: OperatorCategory::Logic,
+        doc: "An alias for if.",
     },
     "or" => LazyOperator {
         symbol: "or",
         operator: logic::or,
         num_params: NumParams::AtLeast(1),
+        category: OperatorCategory::Logic,
+        doc: "The first truthy value, or the last value.",
     },
     "and" => LazyOperator {
         symbol: "and",
         operator: logic::and,
         num_params: NumParams::AtLeast(1),
+        category: OperatorCategory::Logic,
+        doc: "The first falsy value, or the last value.",
+    },
+    "coalesce" => LazyOperator {
+        symbol: "coalesce",
+        operator: logic::coalesce,
+        num_params: NumParams::Any,
+        category: OperatorCategory::Logic,
+        doc: "The first value that isn't null.",
+    },
+    "default" => LazyOperator {
+        symbol: "default",
+        operator: logic::default,
+        num_params: NumParams::Any,
+        category: OperatorCategory::Logic,
+        doc: "The first value that isn't missing from the data.",
+    },
+    "pipe" => LazyOperator {
+        symbol: "pipe",
+        operator: logic::pipe,
+        num_params: NumParams::AtLeast(1),
+        category: OperatorCategory::Logic,
+        doc: "The result of evaluating each step against the result of the one before.",
+    },
+    "try" => LazyOperator {
+        symbol: "try",
+        operator: logic::try_,
+        num_params: NumParams::Variadic(2..4),
+        category: OperatorCategory::Logic,
+        doc: "The value of the expression, or of the fallback if it fails.",
+    },
+    "match" => LazyOperator {
+        symbol: "match",
+        operator: pattern::match_,
+        num_params: NumParams::AtLeast(2),
+        category: OperatorCategory::Logic,
+        doc: "The branch of the first pattern matching the value.",
+    },
+    "switch" => LazyOperator {
+        symbol: "switch",
+        operator: logic::switch,
+        num_params: NumParams::Variadic(2..4),
+        category: OperatorCategory::Logic,
+        doc: "The branch of the case whose key is the string value, or the default.",
     },
     "map" => LazyOperator {
         symbol: "map",
         operator: array::map,
         num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Array,
+        doc: "The result of an expression for each element of an array.",
+    },
+    "zip_with" => LazyOperator {
+        symbol: "zip_with",
+        operator: array::zip_with,
+        num_params: NumParams::AtLeast(3),
+        category: OperatorCategory::Array,
+        doc: "The result of an expression, the last argument, for the array of the elements at each index of the arrays before it.",
     },
     "filter" => LazyOperator {
         symbol: "filter",
         operator: array::filter,
         num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Array,
+        doc: "The elements of an array for which an expression is truthy.",
     },
     "reduce" => LazyOperator {
         symbol: "reduce",
         operator: array::reduce,
         num_params: NumParams::Exactly(3),
+        category: OperatorCategory::Array,
+        doc: "An array reduced to a single value, with the accumulator and current element as data.",
+    },
+    "reduce_while" => LazyOperator {
+        symbol: "reduce_while",
+        operator: array::reduce_while,
+        num_params: NumParams::Exactly(3),
+        category: OperatorCategory::Array,
+        doc: "An array reduced to a single value like reduce, stopping early with the value the expression wraps in reduced.",
+    },
+    "map_values" => LazyOperator {
+        symbol: "map_values",
+        operator: object::map_values,
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Data,
+        doc: "An object with each of its values transformed by an expression, with the value as data.",
+    },
+    "call" => LazyOperator {
+        symbol: "call",
+        operator: func::call,
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Function,
+        doc: "The result of calling a function value with an array of arguments.",
+    },
+    // An alias for "call", for those used to `apply` in other languages
+    "apply" => LazyOperator {
+        symbol: "apply",
+        operator: func::call,
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Function,
+        doc: "An alias for call.",
     },
     "all" => LazyOperator {
         symbol: "all",
         operator: array::all,
         num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Array,
+        doc: "Whether an expression is truthy for every element of a non-empty array.",
     },
     "some" => LazyOperator {
         symbol: "some",
         operator: array::some,
         num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Array,
+        doc: "Whether an expression is truthy for any element of an array.",
     },
     "none" => LazyOperator {
         symbol: "none",
         operator: array::none,
         num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Array,
+        doc: "Whether an expression is falsy for every element of an array.",
+    },
+    "find" => LazyOperator {
+        symbol: "find",
+        operator: array::find,
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Array,
+        doc: "The first element of an array for which an expression is truthy.",
+    },
+    "position" => LazyOperator {
+        symbol: "position",
+        operator: array::position,
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Array,
+        doc: "The index of the first element for which an expression is truthy.",
+    },
+    "min_by" => LazyOperator {
+        symbol: "min_by",
+        operator: array::min_by,
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Array,
+        doc: "The element of an array for which an expression is smallest.",
+    },
+    "max_by" => LazyOperator {
+        symbol: "max_by",
+        operator: array::max_by,
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Array,
+        doc: "The element of an array for which an expression is largest.",
+    },
+    "sum_by" => LazyOperator {
+        symbol: "sum_by",
+        operator: array::sum_by,
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Array,
+        doc: "The sum of an expression for each element of an array.",
+    },
+    "distinct_by" => LazyOperator {
+        symbol: "distinct_by",
+        operator: array::distinct_by,
+        num_params: NumParams::Exactly(2),
+        category: OperatorCategory::Array,
+        doc: "The first element of an array for each distinct result of an expression, with arrays and objects compared by their contents.",
+    },
+    "sort_by" => LazyOperator {
+        symbol: "sort_by",
+        operator: array::sort_by,
+        num_params: NumParams::Variadic(2..4),
+        category: OperatorCategory::Array,
+        doc: "An array stably sorted by an expression for each element, or an array of them for a multi-key sort, in descending order if the third argument is true, or an array of whether each key is descending.",
+    },
+    "sort_with" => LazyOperator {
+        symbol: "sort_with",
+        operator: array::sort_with,
+        num_params: NumParams::Variadic(2..4),
+        category: OperatorCategory::Array,
+        doc: "An array stably sorted by a comparison of elements \"a\" and \"b\", negative if a sorts first, in descending order if the third argument is true.",
     },
 };
 
+/// Operators whose second argument is evaluated against each element of
+/// their first
+pub(crate) const ITERATORS: &[&str] = &[
+    "all",
+    "filter",
+    "map",
+    "none",
+    "reduce",
+    "reduce_while",
+    "some",
+    "find",
+    "position",
+    "min_by",
+    "max_by",
+    "sum_by",
+    "sort_by",
+    "sort_with",
+];
+
+/// Whether the argument at `index` of an operation with `argc` arguments is
+/// evaluated against something other than the operation's data, such as each
+/// element of an array, as the second argument of `map` is
+pub(crate) fn per_element(operator: &str, index: usize, argc: usize) -> bool {
+    (index == 1 && (ITERATORS.contains(&operator) || operator == "map_values"))
+        || (index > 0 && (operator == "pipe" || operator == pattern::OPERATOR))
+        || (index + 1 == argc && operator == "zip_with")
+        || (index == 1 && operator == logic::TRY && argc > 2)
+}
+
+/// Built-in operators whose results may differ between evaluations of the
+/// same rule against the same data, which deterministic rules may not use
+pub(crate) const NONDETERMINISTIC: &[&str] =
+    &["lookup", "random", "random_int", "uuid", "within_last"];
+
+/// Fail if the scope only allows deterministic operators, and the named
+/// operator isn't one.
+pub(crate) fn check_deterministic(
+    name: &str,
+    deterministic: bool,
+    scope: &Scope,
+) -> Result<(), Error> {
+    if scope.options().deterministic && !deterministic {
+        Err(Error::NondeterministicOperator(name.into()))
+    } else {
+        Ok(())
+    }
+}
+
+/// Operators whose arguments are all converted to numbers
+pub(crate) const NUMERIC: &[&str] = &["+", "add", "-", "*", "/", "%", "max", "min"];
+
+/// Return whether the given key is one of the built-in operators.
+pub(crate) fn is_builtin(key: &str) -> bool {
+    builtin_param_info(key).is_some()
+}
+
+/// Return the parameter information for a built-in operator, if `key` is one.
+pub(crate) fn builtin_param_info(key: &str) -> Option<NumParams> {
+    operator(key)
+        .map(|op| op.param_info().clone())
+        .or_else(|| LAZY_OPERATOR_MAP.get(key).map(|op| op.param_info().clone()))
+        .or_else(|| data_operator(key).map(|op| op.param_info().clone()))
+        .or(match key {
+            data::Variable::OPERATOR => Some(data::Variable::NUM_PARAMS),
+            Raw::OPERATOR => Some(NumParams::Any),
+            _ => None,
+        })
+}
+
+/// Iterate over the names of all of the built-in operators.
+pub(crate) fn builtin_names() -> impl Iterator<Item = &'static str> {
+    let operators: &'static phf::Map<&'static str, Operator> = &OPERATOR_MAP;
+    let lazy_operators: &'static phf::Map<&'static str, LazyOperator> =
+        &LAZY_OPERATOR_MAP;
+    let data_operators: &'static phf::Map<&'static str, DataOperator> =
+        &DATA_OPERATOR_MAP;
+    operators
+        .keys()
+        .chain(lazy_operators.keys())
+        .chain(data_operators.keys())
+        .copied()
+        .chain(FEATURE_OPERATORS.iter().map(|op| op.symbol))
+        .chain(FEATURE_DATA_OPERATORS.iter().map(|op| op.symbol))
+        .chain(iter::once(data::Variable::OPERATOR))
+        .chain(iter::once(Raw::OPERATOR))
+}
+
+/// The category and description of a built-in operator, if `key` is one
+fn builtin_description(key: &str) -> Option<(OperatorCategory, &'static str)> {
+    operator(key)
+        .map(|op| (op.category, op.doc))
+        .or_else(|| LAZY_OPERATOR_MAP.get(key).map(|op| (op.category, op.doc)))
+        .or_else(|| data_operator(key).map(|op| (op.category, op.doc)))
+        .or(match key {
+            data::Variable::OPERATOR => Some((
+                OperatorCategory::Data,
+                "The value at a path in the data, or a default if it is missing.",
+            )),
+            Raw::OPERATOR => Some((
+                OperatorCategory::Misc,
+                "The value, as a literal rather than an operation.",
+            )),
+            _ => None,
+        })
+}
+
+/// Metadata for each of the built-in operators, sorted by name.
+///
+/// See `Namespace::operators()` for the operators available within a
+/// namespace, including its functions and custom operators.
+pub fn operators() -> Vec<OperatorInfo> {
+    let mut operators: Vec<OperatorInfo> = builtin_names()
+        .filter_map(|name| {
+            let num_params = builtin_param_info(name)?;
+            let (category, doc) = builtin_description(name)?;
+            Some(OperatorInfo::new(name, num_params, category, doc))
+        })
+        .collect();
+    operators.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    operators
+}
+
+/// The kind of operation an operator performs, e.g. for grouping operators
+/// in a rule builder
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum OperatorCategory {
+    Arithmetic,
+    Array,
+    Comparison,
+    Data,
+    Function,
+    Logic,
+    Misc,
+    String,
+    /// A custom operator, loaded from an operator pack
+    Custom,
+    /// A function defined in a namespace
+    Defined,
+}
+impl OperatorCategory {
+    /// The category's name, e.g. `"arithmetic"`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Arithmetic => "arithmetic",
+            Self::Array => "array",
+            Self::Comparison => "comparison",
+            Self::Data => "data",
+            Self::Function => "function",
+            Self::Logic => "logic",
+            Self::Misc => "misc",
+            Self::String => "string",
+            Self::Custom => "custom",
+            Self::Defined => "defined",
+        }
+    }
+}
+impl fmt::Display for OperatorCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Metadata describing an operator
+#[derive(Clone, Debug, PartialEq)]
+pub struct OperatorInfo {
+    name: String,
+    num_params: NumParams,
+    category: OperatorCategory,
+    doc: String,
+}
+impl OperatorInfo {
+    pub(crate) fn new<N, D>(
+        name: N,
+        num_params: NumParams,
+        category: OperatorCategory,
+        doc: D,
+    ) -> Self
+    where
+        N: Into<String>,
+        D: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            num_params,
+            category,
+            doc: doc.into(),
+        }
+    }
+
+    /// The operator's name, i.e. its key in a rule
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The number of arguments the operator accepts
+    pub fn num_params(&self) -> &NumParams {
+        &self.num_params
+    }
+
+    /// The kind of operation the operator performs
+    pub fn category(&self) -> OperatorCategory {
+        self.category
+    }
+
+    /// A description of the operator, which may be empty
+    pub fn doc(&self) -> &str {
+        &self.doc
+    }
+}
+
+/// The most suggestions to offer for a misspelled name
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Return the known names closest to `name` by edit distance, closest first.
+///
+/// Names further than a third of the length of `name` (or 1, for short
+/// names) are not considered close enough to suggest.
+pub(crate) fn suggest<'a, I>(name: &str, known: I) -> Vec<String>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let max_distance = cmp::max(1, name.chars().count() / 3);
+    let mut candidates: Vec<(usize, &str)> = known
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    candidates.sort_unstable();
+    candidates.dedup();
+    candidates
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate.into())
+        .collect()
+}
+
+/// The Levenshtein distance between two strings, counted in characters
+fn edit_distance(first: &str, second: &str) -> usize {
+    let second: Vec<char> = second.chars().collect();
+    let mut row: Vec<usize> = (0..=second.len()).collect();
+    first.chars().enumerate().for_each(|(i, first_char)| {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        second.iter().enumerate().for_each(|(j, second_char)| {
+            let substitution = diagonal + (first_char != *second_char) as usize;
+            diagonal = row[j + 1];
+            row[j + 1] = cmp::min(substitution, cmp::min(row[j], row[j + 1]) + 1);
+        })
+    });
+    row[second.len()]
+}
+
 /// The number of parameters an operator takes
 #[derive(Debug, Clone, PartialEq)]
 pub enum NumParams {
@@ -254,12 +1143,12 @@ pub enum NumParams {
     Unary,
     Exactly(usize),
     AtLeast(usize),
-    Variadic(std::ops::Range<usize>), // [inclusive, exclusive)
+    Variadic(core::ops::Range<usize>), // [inclusive, exclusive)
 }
 impl NumParams {
     /// Return whether a given length matches the number of parameters specified
     /// by this NumParams.
-    fn is_valid_len(&self, len: &usize) -> bool {
+    pub(crate) fn is_valid_len(&self, len: &usize) -> bool {
         match self {
             Self::None => len == &0,
             Self::Any => true,
@@ -271,7 +1160,7 @@ impl NumParams {
     }
     /// Check whether a length matches the number of parameters specified by this
     /// NuMParams and return an error if it does not match.
-    fn check_len<'a>(&self, len: &'a usize) -> Result<&'a usize, Error> {
+    pub(crate) fn check_len<'a>(&self, len: &'a usize) -> Result<&'a usize, Error> {
         match self.is_valid_len(len) {
             true => Ok(len),
             false => Err(Error::WrongArgumentCount {
@@ -284,7 +1173,7 @@ impl NumParams {
     ///
     /// Useful because of the JsonLogic shortcut of treating a single value
     /// associated with a key as being equivalent to a single-item array.
-    fn can_accept_unary(&self) -> bool {
+    pub(crate) fn can_accept_unary(&self) -> bool {
         match self {
             Self::None => false,
             Self::Any => true,
@@ -299,6 +1188,14 @@ impl NumParams {
 /// Common requirements for an operator
 pub(crate) trait CommonOperator {
     fn param_info(&self) -> &NumParams;
+
+    /// Check that the operator accepts the given number of arguments.
+    ///
+    /// Operators call this before executing, so operator functions may
+    /// index into their arguments without further checks.
+    fn check_arity(&self, len: usize) -> Result<(), Error> {
+        self.param_info().check_len(&len).map(|_| ())
+    }
 }
 
 /// A JsonLogic operator
@@ -306,10 +1203,18 @@ pub struct Operator {
     symbol: &'static str,
     operator: OperatorFn,
     num_params: NumParams,
+    category: OperatorCategory,
+    doc: &'static str,
 }
 impl Operator {
-    pub fn execute(&self, items: &Vec<&Value>) -> Result<Value, Error> {
-        (self.operator)(items)
+    /// The operator's symbol
+    pub(crate) fn symbol(&self) -> &'static str {
+        self.symbol
+    }
+
+    pub fn execute(&self, items: &[Evaluated], env: &Env) -> Result<Value, Error> {
+        self.check_arity(items.len())?;
+        (self.operator)(items, env)
     }
 }
 impl CommonOperator for Operator {
@@ -331,10 +1236,18 @@ pub struct LazyOperator {
     symbol: &'static str,
     operator: LazyOperatorFn,
     num_params: NumParams,
+    category: OperatorCategory,
+    doc: &'static str,
 }
 impl LazyOperator {
-    pub fn execute(&self, data: &Value, items: &Vec<&Value>) -> Result<Value, Error> {
-        (self.operator)(data, items)
+    pub fn execute(
+        &self,
+        data: &Value,
+        items: &[Parsed],
+        env: &Env,
+    ) -> Result<Value, Error> {
+        self.check_arity(items.len())?;
+        (self.operator)(data, items, env)
     }
 }
 impl CommonOperator for LazyOperator {
@@ -360,10 +1273,23 @@ pub struct DataOperator {
     symbol: &'static str,
     operator: DataOperatorFn,
     num_params: NumParams,
+    category: OperatorCategory,
+    doc: &'static str,
 }
 impl DataOperator {
-    pub fn execute(&self, data: &Value, items: &Vec<&Value>) -> Result<Value, Error> {
-        (self.operator)(data, items)
+    /// The operator's symbol
+    pub(crate) fn symbol(&self) -> &'static str {
+        self.symbol
+    }
+
+    pub fn execute(
+        &self,
+        data: &dyn DataResolver,
+        items: &[Evaluated],
+        env: &Env,
+    ) -> Result<Value, Error> {
+        self.check_arity(items.len())?;
+        (self.operator)(data, items, env)
     }
 }
 impl CommonOperator for DataOperator {
@@ -380,61 +1306,163 @@ impl fmt::Debug for DataOperator {
     }
 }
 
-type OperatorFn = fn(&Vec<&Value>) -> Result<Value, Error>;
-type LazyOperatorFn = fn(&Value, &Vec<&Value>) -> Result<Value, Error>;
-type DataOperatorFn = fn(&Value, &Vec<&Value>) -> Result<Value, Error>;
+type OperatorFn = fn(&[Evaluated], &Env) -> Result<Value, Error>;
+type LazyOperatorFn = fn(&Value, &[Parsed], &Env) -> Result<Value, Error>;
+type DataOperatorFn = fn(&dyn DataResolver, &[Evaluated], &Env) -> Result<Value, Error>;
 
 /// An operation that doesn't do any recursive parsing or evaluation.
 ///
-/// Any operator functions used must handle parsing of values themselves.
-#[derive(Debug)]
-pub struct LazyOperation<'a> {
-    operator: &'a LazyOperator,
-    arguments: Vec<Value>,
-}
-impl<'a> Parser<'a> for LazyOperation<'a> {
-    fn from_value(value: &'a Value) -> Result<Option<Self>, Error> {
+/// Arguments are parsed up front, but operator functions decide when (and
+/// whether) to evaluate them.
+#[derive(Clone, Debug)]
+pub struct LazyOperation {
+    operator: &'static LazyOperator,
+    arguments: ArenaSlice<Parsed>,
+}
+impl LazyOperation {
+    /// The operator's symbol
+    pub(crate) fn symbol(&self) -> &'static str {
+        self.operator.symbol
+    }
+
+    /// The (unevaluated) arguments to the operator
+    pub(crate) fn arguments(&self) -> &[Parsed] {
+        &self.arguments
+    }
+
+    /// The arguments, to be replaced when generating mutants
+    pub(crate) fn arguments_mut(&mut self) -> &mut Vec<Parsed> {
+        self.arguments.to_mut()
+    }
+
+    /// The same operation with another lazy operator, if there is one with
+    /// the given symbol
+    pub(crate) fn with_symbol(&self, symbol: &str) -> Option<Self> {
+        let operators: &'static phf::Map<&'static str, LazyOperator> =
+            &LAZY_OPERATOR_MAP;
+        operators.get(symbol).map(|operator| Self {
+            operator,
+            arguments: self.arguments.clone(),
+        })
+    }
+}
+impl Parser for LazyOperation {
+    fn from_value(value: &Value, scope: &Scope) -> Result<Option<Self>, Error> {
         op_from_map(&LAZY_OPERATOR_MAP, value).and_then(|opt| {
             opt.map(|op| {
+                let arguments = match op.op.symbol {
+                    // Patterns are literals, rather than expressions
+                    pattern::OPERATOR => {
+                        scope.slice(op.args.iter().enumerate().map(|(i, arg)| {
+                            if pattern::is_pattern(i, op.args.len()) {
+                                pattern::check(arg)
+                                    .map(|_| Parsed::Raw(Raw::new(arg.clone())))
+                            } else {
+                                Parsed::from_value(arg, scope)
+                            }
+                        }))?
+                    }
+                    logic::SWITCH => scope.slice(op.args.iter().enumerate().map(
+                        |(i, arg)| match i {
+                            1 => logic::switch_cases(arg, scope),
+                            _ => Parsed::from_value(arg, scope),
+                        },
+                    ))?,
+                    logic::TRY => {
+                        if let Some(name) = op.args.get(2) {
+                            logic::error_name(name)?;
+                        }
+                        Parsed::from_values(op.args, scope)?
+                    }
+                    _ => Parsed::from_values(op.args, scope)?,
+                };
                 Ok(LazyOperation {
                     operator: op.op,
-                    arguments: op.args.into_iter().map(|v| v.clone()).collect(),
+                    arguments,
                 })
             })
             .transpose()
         })
     }
 
-    fn evaluate(&self, data: &'a Value) -> Result<Evaluated, Error> {
+    fn evaluate(&self, data: &Value, env: &Env) -> Result<Evaluated<'_>, Error> {
         self.operator
-            .execute(data, &self.arguments.iter().collect())
+            .execute(data, &self.arguments, env)
             .map(Evaluated::New)
     }
 }
 
-impl From<LazyOperation<'_>> for Value {
+impl From<LazyOperation> for Value {
     fn from(op: LazyOperation) -> Value {
         let mut rv = Map::with_capacity(1);
-        rv.insert(
-            op.operator.symbol.into(),
-            Value::Array(op.arguments.clone()),
-        );
+        let values = op
+            .arguments
+            .into_iter()
+            .map(Value::from)
+            .collect::<Vec<Value>>();
+        rv.insert(op.operator.symbol.into(), Value::Array(values));
         Value::Object(rv)
     }
 }
 
-#[derive(Debug)]
-pub struct Operation<'a> {
-    operator: &'a Operator,
-    arguments: Vec<Parsed<'a>>,
+#[derive(Clone, Debug)]
+pub struct Operation {
+    operator: &'static Operator,
+    arguments: ArenaSlice<Parsed>,
 }
-impl<'a> Parser<'a> for Operation<'a> {
-    fn from_value(value: &'a Value) -> Result<Option<Self>, Error> {
-        op_from_map(&OPERATOR_MAP, value).and_then(|opt| {
+impl Operation {
+    /// The operator's symbol
+    pub(crate) fn symbol(&self) -> &'static str {
+        self.operator.symbol
+    }
+
+    /// The operator
+    pub(crate) fn operator(&self) -> &'static Operator {
+        self.operator
+    }
+
+    /// The (unevaluated) arguments to the operator
+    pub(crate) fn arguments(&self) -> &[Parsed] {
+        &self.arguments
+    }
+
+    /// The arguments, to be replaced when generating mutants
+    pub(crate) fn arguments_mut(&mut self) -> &mut Vec<Parsed> {
+        self.arguments.to_mut()
+    }
+
+    /// The same operation with another operator, if there is one with the
+    /// given symbol
+    pub(crate) fn with_symbol(&self, symbol: &str) -> Option<Self> {
+        operator(symbol).map(|operator| Self {
+            operator,
+            arguments: self.arguments.clone(),
+        })
+    }
+}
+impl Parser for Operation {
+    fn from_value(value: &Value, scope: &Scope) -> Result<Option<Self>, Error> {
+        op_from_lookup(operator, value).and_then(|opt| {
             opt.map(|op| {
+                let symbol = op.op.symbol;
+                check_deterministic(
+                    symbol,
+                    !NONDETERMINISTIC.contains(&symbol),
+                    scope,
+                )?;
+                let arguments = match symbol {
+                    // Each argument is a pair of expressions, rather than an
+                    // expression
+                    object::OBJ => scope.slice(
+                        object::flatten_pairs(op.args)?
+                            .into_iter()
+                            .map(|arg| Parsed::from_value(arg, scope)),
+                    )?,
+                    _ => Parsed::from_values(op.args, scope)?,
+                };
                 Ok(Operation {
                     operator: op.op,
-                    arguments: Parsed::from_values(op.args)?,
+                    arguments,
                 })
             })
             .transpose()
@@ -442,19 +1470,17 @@ impl<'a> Parser<'a> for Operation<'a> {
     }
 
     /// Evaluate the operation after recursively evaluating any nested operations
-    fn evaluate(&self, data: &'a Value) -> Result<Evaluated, Error> {
+    fn evaluate(&self, data: &Value, env: &Env) -> Result<Evaluated<'_>, Error> {
         let arguments = self
             .arguments
             .iter()
-            .map(|value| value.evaluate(data).map(Value::from))
-            .collect::<Result<Vec<Value>, Error>>()?;
-        self.operator
-            .execute(&arguments.iter().collect())
-            .map(Evaluated::New)
+            .map(|value| value.evaluate(data, env))
+            .collect::<Result<Vec<Evaluated>, Error>>()?;
+        self.operator.execute(&arguments, env).map(Evaluated::New)
     }
 }
 
-impl From<Operation<'_>> for Value {
+impl From<Operation> for Value {
     fn from(op: Operation) -> Value {
         let mut rv = Map::with_capacity(1);
         let values = op
@@ -462,23 +1488,48 @@ impl From<Operation<'_>> for Value {
             .into_iter()
             .map(Value::from)
             .collect::<Vec<Value>>();
+        let values = match op.operator.symbol {
+            object::OBJ => object::pairs(values),
+            _ => values,
+        };
         rv.insert(op.operator.symbol.into(), Value::Array(values));
         Value::Object(rv)
     }
 }
 
-#[derive(Debug)]
-pub struct DataOperation<'a> {
-    operator: &'a DataOperator,
-    arguments: Vec<Parsed<'a>>,
+#[derive(Clone, Debug)]
+pub struct DataOperation {
+    operator: &'static DataOperator,
+    arguments: ArenaSlice<Parsed>,
 }
-impl<'a> Parser<'a> for DataOperation<'a> {
-    fn from_value(value: &'a Value) -> Result<Option<Self>, Error> {
-        op_from_map(&DATA_OPERATOR_MAP, value).and_then(|opt| {
+impl DataOperation {
+    /// The operator's symbol
+    pub(crate) fn symbol(&self) -> &'static str {
+        self.operator.symbol
+    }
+
+    /// The operator
+    pub(crate) fn operator(&self) -> &'static DataOperator {
+        self.operator
+    }
+
+    /// The (unevaluated) arguments to the operator
+    pub(crate) fn arguments(&self) -> &[Parsed] {
+        &self.arguments
+    }
+
+    /// The arguments, to be replaced when generating mutants
+    pub(crate) fn arguments_mut(&mut self) -> &mut Vec<Parsed> {
+        self.arguments.to_mut()
+    }
+}
+impl Parser for DataOperation {
+    fn from_value(value: &Value, scope: &Scope) -> Result<Option<Self>, Error> {
+        op_from_lookup(data_operator, value).and_then(|opt| {
             opt.map(|op| {
                 Ok(DataOperation {
                     operator: op.op,
-                    arguments: Parsed::from_values(op.args)?,
+                    arguments: Parsed::from_values(op.args, scope)?,
                 })
             })
             .transpose()
@@ -486,18 +1537,19 @@ impl<'a> Parser<'a> for DataOperation<'a> {
     }
 
     /// Evaluate the operation after recursively evaluating any nested operations
-    fn evaluate(&self, data: &'a Value) -> Result<Evaluated, Error> {
+    fn evaluate(&self, data: &Value, env: &Env) -> Result<Evaluated<'_>, Error> {
         let arguments = self
             .arguments
             .iter()
-            .map(|value| value.evaluate(data).map(Value::from))
-            .collect::<Result<Vec<Value>, Error>>()?;
+            .map(|value| value.evaluate(data, env))
+            .collect::<Result<Vec<Evaluated>, Error>>()?;
+        let resolver = env.resolver().unwrap_or(data);
         self.operator
-            .execute(data, &arguments.iter().collect())
+            .execute(resolver, &arguments, env)
             .map(Evaluated::New)
     }
 }
-impl From<DataOperation<'_>> for Value {
+impl From<DataOperation> for Value {
     fn from(op: DataOperation) -> Value {
         let mut rv = Map::with_capacity(1);
         let values = op
@@ -510,15 +1562,41 @@ impl From<DataOperation<'_>> for Value {
     }
 }
 
-struct OpArgs<'a, 'b, T> {
-    op: &'a T,
-    args: Vec<&'b Value>,
+struct OpArgs<'b, T: 'static> {
+    op: &'static T,
+    args: &'b [Value],
 }
 
-fn op_from_map<'a, 'b, T: CommonOperator>(
-    map: &'a phf::Map<&'static str, T>,
+fn op_from_map<'b, T: CommonOperator>(
+    map: &'static phf::Map<&'static str, T>,
     value: &'b Value,
-) -> Result<Option<OpArgs<'a, 'b, T>>, Error> {
+) -> Result<Option<OpArgs<'b, T>>, Error> {
+    op_from_lookup(|key| map.get(key), value)
+}
+
+fn op_from_lookup<'b, T: CommonOperator>(
+    lookup: impl Fn(&str) -> Option<&'static T>,
+    value: &'b Value,
+) -> Result<Option<OpArgs<'b, T>>, Error> {
+    let (key, val) = match single_entry(value)? {
+        Some(entry) => entry,
+        None => return Ok(None),
+    };
+
+    // See if the key is an operator. If it's not, return None.
+    let op = match lookup(key.as_str()) {
+        Some(op) => op,
+        _ => return Ok(None),
+    };
+
+    let args = op_args(key, val, op.param_info())?;
+
+    Ok(Some(OpArgs { op, args }))
+}
+
+/// Return the key and value of an object with exactly one key, or None
+/// if the value is not such an object.
+pub(crate) fn single_entry(value: &Value) -> Result<Option<(&String, &Value)>, Error> {
     let obj = match value {
         Value::Object(obj) => obj,
         _ => return Ok(None),
@@ -539,33 +1617,35 @@ fn op_from_map<'a, 'b, T: CommonOperator>(
         key, obj
     )))?;
 
-    // See if the key is an operator. If it's not, return None.
-    let op = match map.get(key.as_str()) {
-        Some(op) => op,
-        _ => return Ok(None),
-    };
+    Ok(Some((key, val)))
+}
 
+/// Get the list of arguments for an operator, validating their number.
+pub(crate) fn op_args<'b>(
+    key: &str,
+    val: &'b Value,
+    param_info: &NumParams,
+) -> Result<&'b [Value], Error> {
     let err_for_non_unary = || {
         Err(Error::InvalidOperation {
-            key: key.clone(),
+            key: key.into(),
             reason: "Arguments to non-unary operations must be arrays".into(),
         })
     };
 
-    let param_info = op.param_info();
     // If args value is not an array, and the operator is unary,
     // the value is treated as a unary argument array.
     let args = match val {
-        Value::Array(args) => args.iter().collect::<Vec<&Value>>(),
+        Value::Array(args) => args.as_slice(),
         _ => match param_info.can_accept_unary() {
-            true => vec![val],
+            true => core::slice::from_ref(val),
             false => return err_for_non_unary(),
         },
     };
 
     param_info.check_len(&args.len())?;
 
-    Ok(Some(OpArgs { op, args }))
+    Ok(args)
 }
 
 #[cfg(test)]
@@ -587,4 +1667,50 @@ mod test_operators {
             .into_iter()
             .for_each(|(k, op)| assert_eq!(*k, op.symbol))
     }
+
+    /// Every built-in operator is described
+    #[test]
+    fn test_operators() {
+        let operators = operators();
+        assert_eq!(operators.len(), builtin_names().count());
+        assert!(operators.windows(2).all(|w| w[0].name() < w[1].name()));
+        assert!(operators.iter().all(|op| !op.doc().is_empty()));
+        let var = operators.iter().find(|op| op.name() == "var").unwrap();
+        assert_eq!(var.num_params(), &data::Variable::NUM_PARAMS);
+        assert_eq!(var.category(), OperatorCategory::Data);
+        let map = operators.iter().find(|op| op.name() == "map").unwrap();
+        assert_eq!(map.num_params(), &NumParams::Exactly(2));
+        assert_eq!(map.category().name(), "array");
+    }
+
+    #[test]
+    fn test_suggest() {
+        assert_eq!(suggest("iif", builtin_names()), vec!["if".to_string()]);
+        assert_eq!(suggest("substring", builtin_names()), vec!["substr"]);
+        assert_eq!(suggest("xyzzy", builtin_names()), Vec::<String>::new());
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("héllo", "hello"), 1);
+    }
+
+    /// Operators check their arity before executing
+    #[test]
+    fn test_execute_checks_arity() {
+        let one = serde_json::json!(1);
+        let eq = OPERATOR_MAP.get("==").unwrap();
+        eq.execute(&[Evaluated::Raw(&one)], &Env::default())
+            .unwrap_err();
+        assert_eq!(
+            eq.execute(
+                &[Evaluated::Raw(&one), Evaluated::Raw(&one)],
+                &Env::default()
+            )
+            .unwrap(),
+            Value::Bool(true)
+        );
+        let missing_some = DATA_OPERATOR_MAP.get("missing_some").unwrap();
+        missing_some
+            .execute(&Value::Null, &[Evaluated::Raw(&one)], &Env::default())
+            .unwrap_err();
+    }
 }
@@ -0,0 +1,479 @@
+//! Money
+//!
+//! Money values are strings of a decimal amount and an ISO 4217 currency
+//! code, e.g. `"12.30 USD"`. Amounts are exact decimals, never floats, and
+//! have at least as many decimal places as their currency's minor unit, e.g.
+//! two for US dollars and none for yen. Combining amounts of different
+//! currencies is an error, rather than silently producing a meaningless
+//! amount: convert them to one currency first, with `money_convert`.
+
+use core::cmp::Ordering;
+use core::convert::TryFrom;
+use core::fmt;
+
+use serde_json::Value;
+
+use crate::context::Env;
+use crate::error::Error;
+use crate::js_op;
+use crate::prelude::*;
+use crate::value::Evaluated;
+
+/// Currencies whose minor unit isn't a hundredth, and its decimal places.
+/// All others have two.
+const MINOR_UNITS: &[(&str, u32)] = &[
+    ("BHD", 3),
+    ("BIF", 0),
+    ("CLF", 4),
+    ("CLP", 0),
+    ("DJF", 0),
+    ("GNF", 0),
+    ("IQD", 3),
+    ("ISK", 0),
+    ("JOD", 3),
+    ("JPY", 0),
+    ("KMF", 0),
+    ("KRW", 0),
+    ("KWD", 3),
+    ("LYD", 3),
+    ("OMR", 3),
+    ("PYG", 0),
+    ("RWF", 0),
+    ("TND", 3),
+    ("UGX", 0),
+    ("UYI", 0),
+    ("UYW", 4),
+    ("VND", 0),
+    ("VUV", 0),
+    ("XAF", 0),
+    ("XOF", 0),
+    ("XPF", 0),
+];
+
+fn minor_unit(currency: &str) -> u32 {
+    MINOR_UNITS
+        .iter()
+        .find(|(code, _)| *code == currency)
+        .map_or(2, |(_, places)| *places)
+}
+
+/// An exact decimal, `units` × 10^-`scale`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Decimal {
+    units: i128,
+    scale: u32,
+}
+impl Decimal {
+    /// Parse an amount like `-12.34`.
+    fn parse(text: &str) -> Option<Self> {
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+        let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        if integer.is_empty()
+            || (digits.contains('.') && fraction.is_empty())
+            || !integer
+                .bytes()
+                .chain(fraction.bytes())
+                .all(|b| b.is_ascii_digit())
+        {
+            return None;
+        }
+        let mut units: i128 = 0;
+        for digit in integer.bytes().chain(fraction.bytes()) {
+            units = units
+                .checked_mul(10)?
+                .checked_add(i128::from(digit - b'0'))?;
+        }
+        Some(Self {
+            units: if negative { -units } else { units },
+            scale: u32::try_from(fraction.len()).ok()?,
+        })
+    }
+
+    /// The same amount with at least the given number of decimal places
+    fn with_scale(self, scale: u32) -> Option<Self> {
+        if scale <= self.scale {
+            return Some(self);
+        }
+        Some(Self {
+            units: self
+                .units
+                .checked_mul(10_i128.checked_pow(scale - self.scale)?)?,
+            scale,
+        })
+    }
+
+    /// Both amounts with the same number of decimal places
+    fn aligned(self, other: Self) -> Option<(Self, Self)> {
+        let scale = self.scale.max(other.scale);
+        Some((self.with_scale(scale)?, other.with_scale(scale)?))
+    }
+
+    fn checked_add(self, other: Self) -> Option<Self> {
+        let (a, b) = self.aligned(other)?;
+        Some(Self {
+            units: a.units.checked_add(b.units)?,
+            scale: a.scale,
+        })
+    }
+
+    fn checked_mul(self, other: Self) -> Option<Self> {
+        Some(Self {
+            units: self.units.checked_mul(other.units)?,
+            scale: self.scale.checked_add(other.scale)?,
+        })
+    }
+
+    fn negate(self) -> Self {
+        Self {
+            units: -self.units,
+            ..self
+        }
+    }
+
+    /// Round to the given number of decimal places, with ties to even.
+    fn round(self, scale: u32) -> Option<Self> {
+        if scale >= self.scale {
+            return Some(self);
+        }
+        let divisor = 10_i128.checked_pow(self.scale - scale)?;
+        let (quotient, remainder) = (self.units / divisor, self.units % divisor);
+        let twice = remainder.abs() * 2;
+        let away = twice > divisor || (twice == divisor && quotient % 2 != 0);
+        Some(Self {
+            units: if away {
+                quotient + self.units.signum()
+            } else {
+                quotient
+            },
+            scale,
+        })
+    }
+}
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = self.units.unsigned_abs().to_string();
+        let scale = self.scale as usize;
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (integer, fraction) = digits.split_at(digits.len() - scale);
+        if self.units < 0 {
+            f.write_str("-")?;
+        }
+        match fraction {
+            "" => f.write_str(integer),
+            fraction => write!(f, "{}.{}", integer, fraction),
+        }
+    }
+}
+
+/// An amount of a currency
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Money {
+    amount: Decimal,
+    currency: String,
+}
+impl Money {
+    fn new(
+        amount: Decimal,
+        currency: &str,
+        value: &Value,
+        operation: &'static str,
+    ) -> Result<Self, Error> {
+        check_currency(currency, value, operation)?;
+        let amount = amount
+            .with_scale(minor_unit(currency))
+            .ok_or_else(|| overflow(value, operation))?;
+        Ok(Self {
+            amount,
+            currency: currency.into(),
+        })
+    }
+
+    /// Parse a money value like `"12.34 USD"`.
+    fn parse(value: &Value, operation: &'static str) -> Result<Self, Error> {
+        let invalid = || {
+            Error::invalid_argument(
+                value.clone(),
+                operation,
+                "Money values must be strings of an amount and a currency code, e.g. \"12.34 USD\"",
+            )
+        };
+        let (amount, currency) = value
+            .as_str()
+            .and_then(|text| text.split_once(' '))
+            .ok_or_else(invalid)?;
+        Self::new(
+            Decimal::parse(amount).ok_or_else(invalid)?,
+            currency,
+            value,
+            operation,
+        )
+    }
+
+    /// The other amount, if it's of the same currency
+    fn same_currency<'a>(
+        &self,
+        other: &'a Self,
+        operation: &'static str,
+    ) -> Result<&'a Self, Error> {
+        if self.currency == other.currency {
+            Ok(other)
+        } else {
+            Err(Error::invalid_argument(
+                Value::from(other),
+                operation,
+                format!(
+                    "Amounts of {} and {} can't be combined without converting them to one currency",
+                    self.currency, other.currency
+                ),
+            ))
+        }
+    }
+}
+impl From<&Money> for Value {
+    fn from(money: &Money) -> Self {
+        Value::String(format!("{} {}", money.amount, money.currency))
+    }
+}
+
+/// Check that a currency is a code of three capital letters.
+fn check_currency(
+    currency: &str,
+    value: &Value,
+    operation: &'static str,
+) -> Result<(), Error> {
+    if currency.len() == 3 && currency.bytes().all(|b| b.is_ascii_uppercase()) {
+        Ok(())
+    } else {
+        Err(Error::invalid_argument(
+            value.clone(),
+            operation,
+            format!("'{}' is not a currency code, e.g. USD", currency),
+        ))
+    }
+}
+
+/// A currency code argument
+fn currency_arg<'a>(
+    value: &'a Value,
+    operation: &'static str,
+) -> Result<&'a str, Error> {
+    let currency = value.as_str().ok_or_else(|| {
+        Error::invalid_argument(
+            value.clone(),
+            operation,
+            "Currency codes must be strings",
+        )
+    })?;
+    check_currency(currency, value, operation)?;
+    Ok(currency)
+}
+
+fn overflow(value: &Value, operation: &'static str) -> Error {
+    Error::invalid_argument(value.clone(), operation, "The amount is too large")
+}
+
+/// A money value, from a string like `"12.34 USD"`, or from an amount and a
+/// currency code. Amounts may be numbers or decimal strings.
+pub fn money(items: &[Evaluated]) -> Result<Value, Error> {
+    let money = match items {
+        [value] => Money::parse(value, "money")?,
+        [amount, currency, ..] => {
+            let amount = match &**amount {
+                Value::Number(_) | Value::String(_) => {
+                    Decimal::parse(&js_op::to_string(amount))
+                }
+                _ => None,
+            }
+            .ok_or_else(|| {
+                Error::invalid_argument(
+                    Value::clone(amount),
+                    "money",
+                    "Amounts must be numbers or decimal strings",
+                )
+            })?;
+            Money::new(amount, currency_arg(currency, "money")?, currency, "money")?
+        }
+        [] => unreachable!("money has at least one argument"),
+    };
+    Ok(Value::from(&money))
+}
+
+/// The sum of amounts of one currency
+pub fn money_add(items: &[Evaluated]) -> Result<Value, Error> {
+    let first = Money::parse(&items[0], "money_add")?;
+    let total = items[1..].iter().try_fold(first, |total, item| {
+        let money = Money::parse(item, "money_add")?;
+        let amount = total
+            .same_currency(&money, "money_add")?
+            .amount
+            .checked_add(total.amount)
+            .ok_or_else(|| overflow(item, "money_add"))?;
+        Ok(Money { amount, ..total })
+    })?;
+    Ok(Value::from(&total))
+}
+
+/// The difference of two amounts of one currency
+pub fn money_sub(items: &[Evaluated]) -> Result<Value, Error> {
+    let a = Money::parse(&items[0], "money_sub")?;
+    let b = Money::parse(&items[1], "money_sub")?;
+    let amount = a
+        .amount
+        .checked_add(a.same_currency(&b, "money_sub")?.amount.negate())
+        .ok_or_else(|| overflow(&items[1], "money_sub"))?;
+    Ok(Value::from(&Money { amount, ..a }))
+}
+
+/// -1, 0, or 1 as the first amount is less than, equal to, or greater than
+/// the second, of the same currency
+pub fn money_cmp(items: &[Evaluated]) -> Result<Value, Error> {
+    let a = Money::parse(&items[0], "money_cmp")?;
+    let b = Money::parse(&items[1], "money_cmp")?;
+    let (a, b) = a
+        .amount
+        .aligned(a.same_currency(&b, "money_cmp")?.amount)
+        .ok_or_else(|| overflow(&items[1], "money_cmp"))?;
+    Ok(Value::from(match a.units.cmp(&b.units) {
+        Ordering::Less => -1,
+        Ordering::Equal => 0,
+        Ordering::Greater => 1,
+    }))
+}
+
+/// An amount converted to another currency, with the rate from the
+/// context's `ExchangeRates`, rounded to the currency's minor unit with ties
+/// to even
+pub fn money_convert(items: &[Evaluated], env: &Env) -> Result<Value, Error> {
+    let money = Money::parse(&items[0], "money_convert")?;
+    let to = currency_arg(&items[1], "money_convert")?;
+    if money.currency == to {
+        return Ok(Value::from(&money));
+    }
+    let rates = env
+        .exchange_rates()
+        .ok_or_else(|| Error::InvalidOperation {
+            key: "money_convert".into(),
+            reason: "No exchange rates were provided in the evaluation context".into(),
+        })?;
+    let rate = rates.rate(&money.currency, to)?.ok_or_else(|| {
+        Error::invalid_argument(
+            Value::String(format!("{}/{}", money.currency, to)),
+            "money_convert",
+            "There is no exchange rate between these currencies",
+        )
+    })?;
+    let rate = match &rate {
+        Value::Number(_) | Value::String(_) => Decimal::parse(&js_op::to_string(&rate)),
+        _ => None,
+    }
+    .filter(|rate| rate.units > 0)
+    .ok_or_else(|| {
+        Error::invalid_argument(
+            rate.clone(),
+            "money_convert",
+            "Exchange rates must be positive numbers or decimal strings",
+        )
+    })?;
+    let amount = money
+        .amount
+        .checked_mul(rate)
+        .and_then(|amount| amount.round(minor_unit(to)))
+        .ok_or_else(|| overflow(&items[0], "money_convert"))?;
+    Ok(Value::from(&Money::new(
+        amount,
+        to,
+        &items[1],
+        "money_convert",
+    )?))
+}
+
+#[cfg(test)]
+mod test_money {
+    use super::*;
+    use alloc::sync::Arc;
+    use serde_json::json;
+
+    use crate::collections::HashMap;
+    use crate::{Context, Rule};
+
+    #[test]
+    fn test_decimal() {
+        let parse = |text| Decimal::parse(text).unwrap();
+        assert_eq!(parse("12.340").to_string(), "12.340");
+        assert_eq!(parse("-0.05").to_string(), "-0.05");
+        assert_eq!(parse("+7").to_string(), "7");
+        for invalid in [
+            "",
+            ".5",
+            "5.",
+            "1e3",
+            "1.2.3",
+            "--1",
+            "99999999999999999999999999999999999999999",
+        ] {
+            assert_eq!(Decimal::parse(invalid), None, "{}", invalid);
+        }
+        assert_eq!(parse("2.345").round(2).unwrap().to_string(), "2.34");
+        assert_eq!(parse("2.355").round(2).unwrap().to_string(), "2.36");
+        assert_eq!(parse("2.3451").round(2).unwrap().to_string(), "2.35");
+        assert_eq!(parse("-2.355").round(2).unwrap().to_string(), "-2.36");
+        assert_eq!(parse("0.5").round(0).unwrap().to_string(), "0");
+    }
+
+    #[test]
+    fn test_money_convert() {
+        let rates: HashMap<String, Value> = vec![
+            ("USD/EUR".to_string(), json!(0.92)),
+            ("USD/JPY".to_string(), json!("151.235")),
+            ("EUR/USD".to_string(), json!("nope")),
+        ]
+        .into_iter()
+        .collect();
+        let ctx = Context {
+            exchange_rates: Some(Arc::new(rates)),
+            ..Context::default()
+        };
+        let convert = |money: &str, to: &str, ctx: &Context| {
+            Rule::from_value(&json!({"money_convert": [money, to]}))
+                .unwrap()
+                .apply_with(&json!({}), ctx)
+        };
+        assert_eq!(
+            convert("10.05 USD", "EUR", &ctx).unwrap(),
+            json!("9.25 EUR")
+        );
+        assert_eq!(
+            convert("10.05 USD", "JPY", &ctx).unwrap(),
+            json!("1520 JPY")
+        );
+        assert_eq!(
+            convert("10.05 USD", "USD", &ctx).unwrap(),
+            json!("10.05 USD")
+        );
+        assert_eq!(
+            convert("1 EUR", "GBP", &ctx).unwrap_err().code(),
+            "invalid_argument"
+        );
+        assert_eq!(
+            convert("1 EUR", "USD", &ctx).unwrap_err().code(),
+            "invalid_argument"
+        );
+        assert_eq!(
+            convert("1 USD", "usd", &ctx).unwrap_err().code(),
+            "invalid_argument"
+        );
+        assert_eq!(
+            convert("1 USD", "EUR", &Context::default())
+                .unwrap_err()
+                .code(),
+            "invalid_operation"
+        );
+        // Translated rules have no context
+        let rule =
+            Rule::from_value(&json!({"money_convert": ["1 USD", "EUR"]})).unwrap();
+        assert_eq!(rule.to_javascript().unwrap_err().code(), "untranslatable");
+    }
+}
@@ -0,0 +1,96 @@
+//! Object Operations
+
+use serde_json::{Map, Value};
+
+use crate::context::Env;
+use crate::error::Error;
+use crate::prelude::*;
+use crate::value::{Evaluated, Parsed};
+
+pub(crate) const OBJ: &str = "obj";
+
+/// The expressions of the `[key, value]` pairs passed to `obj`, flattened
+/// into a list of alternating keys and values, which are its arguments.
+pub(crate) fn flatten_pairs(args: &[Value]) -> Result<Vec<&Value>, Error> {
+    args.iter()
+        .map(|pair| match pair {
+            Value::Array(pair) if pair.len() == 2 => Ok(pair.iter()),
+            _ => Err(Error::invalid_argument(
+                pair.clone(),
+                OBJ,
+                "Arguments must be [key, value] pairs",
+            )),
+        })
+        .collect::<Result<Vec<_>, Error>>()
+        .map(|pairs| pairs.into_iter().flatten().collect())
+}
+
+/// Regroup the flattened arguments of `obj` into `[key, value]` pairs.
+pub(crate) fn pairs(args: Vec<Value>) -> Vec<Value> {
+    let mut args = args.into_iter();
+    let mut pairs = Vec::with_capacity(args.len() / 2);
+    while let (Some(key), Some(value)) = (args.next(), args.next()) {
+        pairs.push(Value::Array(vec![key, value]));
+    }
+    pairs
+}
+
+/// Build an object from alternating keys and values, with later values
+/// replacing earlier ones of the same key.
+pub fn obj(items: &[Evaluated]) -> Result<Value, Error> {
+    let mut object = Map::with_capacity(items.len() / 2);
+    for pair in items.chunks(2) {
+        match (&*pair[0], pair.get(1)) {
+            (Value::String(key), Some(value)) => {
+                object.insert(key.clone(), Value::clone(value));
+            }
+            (key, _) => {
+                return Err(Error::invalid_argument(
+                    key.clone(),
+                    OBJ,
+                    "Object keys must be strings",
+                ))
+            }
+        }
+    }
+    Ok(Value::Object(object))
+}
+
+/// Transform each value of an object by an expression.
+pub fn map_values(data: &Value, args: &[Parsed], env: &Env) -> Result<Value, Error> {
+    let (object, expression) = (&args[0], &args[1]);
+    let evaluated = object.evaluate(data, env)?;
+    let item_env = env.within(data);
+    transform_values(&object.clone().into(), &evaluated, |value| {
+        expression.evaluate(value, &item_env).map(Value::from)
+    })
+}
+
+/// Transform each value of an object (or null, as an empty object) with
+/// `transform`, as `map_values` does. `source` is the object's expression,
+/// for errors.
+pub(crate) fn transform_values<F>(
+    source: &Value,
+    object: &Value,
+    mut transform: F,
+) -> Result<Value, Error>
+where
+    F: FnMut(&Value) -> Result<Value, Error>,
+{
+    match object {
+        Value::Object(object) => object
+            .iter()
+            .map(|(key, value)| Ok((key.clone(), transform(value)?)))
+            .collect::<Result<Map<String, Value>, Error>>()
+            .map(Value::Object),
+        Value::Null => Ok(Value::Object(Map::new())),
+        _ => Err(Error::invalid_argument(
+            source.clone(),
+            "map_values",
+            format!(
+                "First argument to map_values must evaluate to an object or null, got {}",
+                object
+            ),
+        )),
+    }
+}
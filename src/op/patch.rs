@@ -0,0 +1,519 @@
+//! JSON Patch and JSON Merge Patch
+
+use serde_json::{Map, Value};
+
+use crate::error::Error;
+use crate::prelude::*;
+use crate::value::Evaluated;
+
+/// Apply a JSON Patch (RFC 6902), an array of operations, to a document.
+///
+/// The operations are applied in order. If any of them fails, including a
+/// `test` operation whose value differs, so does the whole patch.
+pub fn patch(items: &[Evaluated]) -> Result<Value, Error> {
+    let operations = match &*items[1] {
+        Value::Array(operations) => operations,
+        value => {
+            return Err(Error::invalid_argument(
+                value.clone(),
+                "patch",
+                "A JSON Patch must be an array of operations",
+            ))
+        }
+    };
+    let mut document = (*items[0]).clone();
+    for operation in operations {
+        apply(&mut document, operation).map_err(|reason| {
+            Error::invalid_argument(operation.clone(), "patch", reason)
+        })?;
+    }
+    Ok(document)
+}
+
+/// Apply a JSON Merge Patch (RFC 7386) to a document.
+///
+/// Each member of an object patch replaces the document's, merging objects
+/// recursively, and `null` members are removed. Any other patch replaces
+/// the document entirely.
+pub fn merge_patch(items: &[Evaluated]) -> Result<Value, Error> {
+    let mut document = (*items[0]).clone();
+    merge(&mut document, &items[1]);
+    Ok(document)
+}
+
+/// Compute a JSON Patch (RFC 6902) that changes one value into another.
+///
+/// Objects and arrays are compared recursively, so the patch only touches
+/// the members and elements that differ: object members are removed, then
+/// added, in the order of their keys, and array elements past the end of
+/// the shorter array are removed from the end, or appended. Any other
+/// differing value is replaced.
+pub fn diff(items: &[Evaluated]) -> Result<Value, Error> {
+    let mut operations = Vec::new();
+    changes(&items[0], &items[1], &mut String::new(), &mut operations);
+    Ok(Value::Array(operations))
+}
+
+/// Collect the operations changing `from` into `to`, at the given pointer.
+fn changes(from: &Value, to: &Value, path: &mut String, operations: &mut Vec<Value>) {
+    if from == to {
+        return;
+    }
+    let len = path.len();
+    match (from, to) {
+        (Value::Object(from), Value::Object(to)) => {
+            let mut keys: Vec<&String> = from.keys().chain(to.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in &keys {
+                push_key(path, key);
+                match (from.get(*key), to.get(*key)) {
+                    (Some(a), Some(b)) => changes(a, b, path, operations),
+                    (Some(_), None) => operations.push(operation("remove", path, None)),
+                    _ => (),
+                }
+                path.truncate(len);
+            }
+            for key in keys.iter().filter(|key| !from.contains_key(**key)) {
+                push_key(path, key);
+                operations.push(operation("add", path, Some(&to[key.as_str()])));
+                path.truncate(len);
+            }
+        }
+        (Value::Array(from), Value::Array(to)) => {
+            for (i, (a, b)) in from.iter().zip(to).enumerate() {
+                push_key(path, &i.to_string());
+                changes(a, b, path, operations);
+                path.truncate(len);
+            }
+            for i in (to.len()..from.len()).rev() {
+                push_key(path, &i.to_string());
+                operations.push(operation("remove", path, None));
+                path.truncate(len);
+            }
+            for value in to.iter().skip(from.len()) {
+                push_key(path, "-");
+                operations.push(operation("add", path, Some(value)));
+                path.truncate(len);
+            }
+        }
+        (_, to) => operations.push(operation("replace", path, Some(to))),
+    }
+}
+
+/// Append an escaped key to a pointer.
+fn push_key(path: &mut String, key: &str) {
+    path.push('/');
+    path.push_str(&key.replace('~', "~0").replace('/', "~1"));
+}
+
+fn operation(op: &str, path: &str, value: Option<&Value>) -> Value {
+    let mut operation = Map::new();
+    operation.insert("op".into(), Value::String(op.into()));
+    operation.insert("path".into(), Value::String(path.into()));
+    if let Some(value) = value {
+        operation.insert("value".into(), value.clone());
+    }
+    Value::Object(operation)
+}
+
+fn merge(target: &mut Value, patch: &Value) {
+    let patch = match patch {
+        Value::Object(patch) => patch,
+        patch => {
+            *target = patch.clone();
+            return;
+        }
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    if let Value::Object(target) = target {
+        for (key, value) in patch {
+            match value {
+                Value::Null => {
+                    target.remove(key);
+                }
+                value => merge(target.entry(key.clone()).or_insert(Value::Null), value),
+            }
+        }
+    }
+}
+
+/// Apply one JSON Patch operation, returning why it failed if it does.
+fn apply(document: &mut Value, operation: &Value) -> Result<(), String> {
+    let path = pointer(operation, "path")?;
+    match member(operation, "op")? {
+        "add" => add(document, path, value(operation)?),
+        "remove" => remove(document, path).map(drop),
+        "replace" => {
+            *target(document, path)? = value(operation)?;
+            Ok(())
+        }
+        "move" => {
+            let from = pointer(operation, "from")?;
+            if path
+                .strip_prefix(from)
+                .is_some_and(|rest| rest.starts_with('/'))
+            {
+                return Err(format!("Cannot move '{}' into itself", from));
+            }
+            let value = remove(document, from)?;
+            add(document, path, value)
+        }
+        "copy" => {
+            let value = target(document, pointer(operation, "from")?)?.clone();
+            add(document, path, value)
+        }
+        "test" => {
+            let expected = value(operation)?;
+            match equal(target(document, path)?, &expected) {
+                true => Ok(()),
+                false => Err(format!("The value at '{}' is not {}", path, expected)),
+            }
+        }
+        op => Err(format!("Unknown JSON Patch operation '{}'", op)),
+    }
+}
+
+/// Whether two values are equal as JSON Patch compares them, with numbers
+/// compared by value, so that e.g. `1.0` is equal to `1`
+fn equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => match a.is_f64() || b.is_f64() {
+            true => a.as_f64() == b.as_f64(),
+            false => a == b,
+        },
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| equal(a, b))
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(key, a)| b.get(key).is_some_and(|b| equal(a, b)))
+        }
+        (a, b) => a == b,
+    }
+}
+
+/// A string member of an operation
+fn member<'a>(operation: &'a Value, name: &str) -> Result<&'a str, String> {
+    operation
+        .get(name)
+        .and_then(Value::as_str)
+        .ok_or_else(|| format!("JSON Patch operations must have a string '{}'", name))
+}
+
+/// A JSON Pointer member of an operation
+fn pointer<'a>(operation: &'a Value, name: &str) -> Result<&'a str, String> {
+    let pointer = member(operation, name)?;
+    match pointer.is_empty() || pointer.starts_with('/') {
+        true => Ok(pointer),
+        false => Err(format!("'{}' is not a JSON Pointer", pointer)),
+    }
+}
+
+/// The `value` member of an operation
+fn value(operation: &Value) -> Result<Value, String> {
+    operation
+        .get("value")
+        .cloned()
+        .ok_or_else(|| String::from("JSON Patch operations must have a 'value'"))
+}
+
+/// The value at a pointer, which must exist
+fn target<'a>(document: &'a mut Value, path: &str) -> Result<&'a mut Value, String> {
+    document
+        .pointer_mut(path)
+        .ok_or_else(|| format!("There is no value at '{}'", path))
+}
+
+/// Split a non-empty pointer into the pointer to its parent and its last key.
+fn split(path: &str) -> (&str, String) {
+    let (parent, key) = path.rsplit_once('/').unwrap_or(("", path));
+    (parent, key.replace("~1", "/").replace("~0", "~"))
+}
+
+/// Parse an array index, which must be less than `len`.
+fn index(key: &str, len: usize) -> Result<usize, String> {
+    // Indices are digits without leading zeros
+    let canonical = key == "0"
+        || (key.bytes().all(|b| b.is_ascii_digit()) && !key.starts_with('0'));
+    match key.parse::<usize>() {
+        Ok(index) if canonical && index < len => Ok(index),
+        _ => Err(format!("'{}' is not an index into the array", key)),
+    }
+}
+
+fn add(document: &mut Value, path: &str, value: Value) -> Result<(), String> {
+    if path.is_empty() {
+        *document = value;
+        return Ok(());
+    }
+    let (parent, key) = split(path);
+    match target(document, parent)? {
+        Value::Object(obj) => {
+            obj.insert(key, value);
+            Ok(())
+        }
+        Value::Array(elements) => {
+            let i = match key.as_str() {
+                "-" => elements.len(),
+                key => index(key, elements.len() + 1)?,
+            };
+            elements.insert(i, value);
+            Ok(())
+        }
+        _ => Err(format!(
+            "Cannot add '{}' to a value that isn't a container",
+            path
+        )),
+    }
+}
+
+fn remove(document: &mut Value, path: &str) -> Result<Value, String> {
+    if path.is_empty() {
+        return Err(String::from("Cannot remove the whole document"));
+    }
+    let (parent, key) = split(path);
+    match target(document, parent)? {
+        Value::Object(obj) => obj
+            .remove(&key)
+            .ok_or_else(|| format!("There is no value at '{}'", path)),
+        Value::Array(elements) => {
+            let i = index(&key, elements.len())?;
+            Ok(elements.remove(i))
+        }
+        _ => Err(format!("There is no value at '{}'", path)),
+    }
+}
+
+#[cfg(test)]
+mod test_patch {
+    use serde_json::{json, Value};
+
+    use crate::{Error, Rule};
+
+    fn apply(op: &str, document: Value, patch: Value) -> Result<Value, Error> {
+        Rule::from_value(&json!({ op: [{"lit": document}, {"lit": patch}] }))?
+            .apply(&json!({}))
+    }
+
+    #[test]
+    fn test_patch() {
+        let document = json!({"a": {"b": [1, 2]}, "c": "d"});
+        let cases = vec![
+            (
+                json!([{"op": "add", "path": "/a/b/1", "value": 9}]),
+                json!({"a": {"b": [1, 9, 2]}, "c": "d"}),
+            ),
+            (
+                json!([{"op": "add", "path": "/a/b/-", "value": 3}]),
+                json!({"a": {"b": [1, 2, 3]}, "c": "d"}),
+            ),
+            (
+                json!([{"op": "add", "path": "/e~1f", "value": null}]),
+                json!({"a": {"b": [1, 2]}, "c": "d", "e/f": null}),
+            ),
+            (
+                json!([{"op": "remove", "path": "/a/b/0"}]),
+                json!({"a": {"b": [2]}, "c": "d"}),
+            ),
+            (
+                json!([{"op": "replace", "path": "/c", "value": [1]}]),
+                json!({"a": {"b": [1, 2]}, "c": [1]}),
+            ),
+            (
+                json!([{"op": "move", "from": "/a/b", "path": "/b"}]),
+                json!({"a": {}, "b": [1, 2], "c": "d"}),
+            ),
+            (
+                json!([{"op": "copy", "from": "/c", "path": "/a/b/0"}]),
+                json!({"a": {"b": ["d", 1, 2]}, "c": "d"}),
+            ),
+            (
+                json!([
+                    {"op": "test", "path": "/a/b", "value": [1, 2]},
+                    {"op": "replace", "path": "", "value": 1}
+                ]),
+                json!(1),
+            ),
+            // Numbers are compared by value
+            (
+                json!([
+                    {"op": "test", "path": "/a", "value": {"b": [1.0, 2.0]}},
+                    {"op": "replace", "path": "/c", "value": 1}
+                ]),
+                json!({"a": {"b": [1, 2]}, "c": 1}),
+            ),
+            (json!([]), document.clone()),
+        ];
+        for (patch, expected) in cases {
+            assert_eq!(
+                apply("patch", document.clone(), patch.clone()).unwrap(),
+                expected,
+                "{}",
+                patch
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_patch() {
+        let document = json!({"a": [1], "b": 2});
+        let invalid = vec![
+            json!({"op": "remove", "path": "/a"}),
+            json!([{"op": "test", "path": "/b", "value": 3}]),
+            json!([{"op": "test", "path": "/b", "value": 2.5}]),
+            json!([{"op": "test", "path": "/a", "value": [1, 2]}]),
+            json!([{"op": "remove", "path": "/nope"}]),
+            json!([{"op": "remove", "path": ""}]),
+            json!([{"op": "add", "path": "/a/2", "value": 3}]),
+            json!([{"op": "add", "path": "/a/01", "value": 3}]),
+            json!([{"op": "add", "path": "/b/c", "value": 3}]),
+            json!([{"op": "add", "path": "a", "value": 3}]),
+            json!([{"op": "add", "path": "/c"}]),
+            json!([{"op": "move", "from": "/a", "path": "/a/0"}]),
+            json!([{"op": "nope", "path": "/a"}]),
+            json!([{"path": "/a"}]),
+            json!([1]),
+        ];
+        for patch in invalid {
+            assert_eq!(
+                apply("patch", document.clone(), patch.clone())
+                    .unwrap_err()
+                    .code(),
+                "invalid_argument",
+                "{}",
+                patch
+            );
+        }
+        // A failed operation fails the whole patch, even if earlier ones
+        // succeeded
+        let rule = Rule::from_value(&json!({"patch": [
+            {"var": ""},
+            [
+                {"op": "add", "path": "/c", "value": 3},
+                {"op": "test", "path": "/c", "value": 4}
+            ]
+        ]}))
+        .unwrap();
+        assert!(rule.apply(&document).is_err());
+    }
+
+    #[test]
+    fn test_diff() {
+        let diff = |from: Value, to: Value| {
+            Rule::from_value(&json!({"diff": [{"lit": from}, {"lit": to}]}))
+                .unwrap()
+                .apply(&json!({}))
+                .unwrap()
+        };
+        assert_eq!(diff(json!({"a": [1]}), json!({"a": [1]})), json!([]));
+        assert_eq!(
+            diff(
+                json!({"a": 1, "b": 2, "c/~": 3}),
+                json!({"z": 0, "b": 4, "a": 1})
+            ),
+            json!([
+                {"op": "replace", "path": "/b", "value": 4},
+                {"op": "remove", "path": "/c~1~0"},
+                {"op": "add", "path": "/z", "value": 0}
+            ])
+        );
+        assert_eq!(
+            diff(json!([1, 2, 3, 4]), json!([1, 5])),
+            json!([
+                {"op": "replace", "path": "/1", "value": 5},
+                {"op": "remove", "path": "/3"},
+                {"op": "remove", "path": "/2"}
+            ])
+        );
+        assert_eq!(
+            diff(json!([1]), json!([1, {"a": 2}])),
+            json!([{"op": "add", "path": "/-", "value": {"a": 2}}])
+        );
+        assert_eq!(
+            diff(json!({"a": [1]}), json!(null)),
+            json!([{"op": "replace", "path": "", "value": null}])
+        );
+
+        // Applying the diff gives the second value
+        let pairs = vec![
+            (
+                json!({"a": {"b": [1, 2, {"c": 3}]}, "d": "e"}),
+                json!({"a": {"b": [2, {"c": 4}]}, "f": []}),
+            ),
+            (json!([[1, 2], {"a": 1}, 3]), json!([[1], {}, "3", 4, 5])),
+            (json!({"": {"/": 1}}), json!({"": {"~": 1}})),
+            (json!("a"), json!({"a": 1})),
+        ];
+        for (from, to) in pairs {
+            let patch = diff(from.clone(), to.clone());
+            assert_eq!(
+                apply("patch", from, patch.clone()).unwrap(),
+                to,
+                "{}",
+                patch
+            );
+        }
+    }
+
+    #[test]
+    fn test_merge_patch() {
+        // The examples from RFC 7386
+        let cases = vec![
+            (json!({"a": "b"}), json!({"a": "c"}), json!({"a": "c"})),
+            (
+                json!({"a": "b"}),
+                json!({"b": "c"}),
+                json!({"a": "b", "b": "c"}),
+            ),
+            (json!({"a": "b"}), json!({"a": null}), json!({})),
+            (
+                json!({"a": "b", "b": "c"}),
+                json!({"a": null}),
+                json!({"b": "c"}),
+            ),
+            (json!({"a": ["b"]}), json!({"a": "c"}), json!({"a": "c"})),
+            (json!({"a": "c"}), json!({"a": ["b"]}), json!({"a": ["b"]})),
+            (
+                json!({"a": {"b": "c"}}),
+                json!({"a": {"b": "d", "c": null}}),
+                json!({"a": {"b": "d"}}),
+            ),
+            (
+                json!({"a": [{"b": "c"}]}),
+                json!({"a": [1]}),
+                json!({"a": [1]}),
+            ),
+            (json!(["a", "b"]), json!(["c", "d"]), json!(["c", "d"])),
+            (json!({"a": "b"}), json!(["c"]), json!(["c"])),
+            (json!({"a": "foo"}), json!(null), json!(null)),
+            (json!({"a": "foo"}), json!("bar"), json!("bar")),
+            (
+                json!({"e": null}),
+                json!({"a": 1}),
+                json!({"e": null, "a": 1}),
+            ),
+            (
+                json!([1, 2]),
+                json!({"a": "b", "c": null}),
+                json!({"a": "b"}),
+            ),
+            (
+                json!({}),
+                json!({"a": {"bb": {"ccc": null}}}),
+                json!({"a": {"bb": {}}}),
+            ),
+        ];
+        for (document, patch, expected) in cases {
+            assert_eq!(
+                apply("merge_patch", document, patch.clone()).unwrap(),
+                expected,
+                "{}",
+                patch
+            );
+        }
+    }
+}
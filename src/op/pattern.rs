@@ -0,0 +1,250 @@
+//! Pattern Matching
+//!
+//! `match` compares a value against a series of patterns, and evaluates the
+//! branch of the first that matches:
+//!
+//! ```jsonc
+//! {"match": [
+//!     {"var": "shape"},
+//!     {"shape": {"kind": "circle", "r": {"bind": "r"}}},
+//!     {"*": [3.14, {"var": "r"}, {"var": "r"}]},
+//!     {"type": "number"},
+//!     {"var": "shape"},
+//!     0
+//! ]}
+//! ```
+//!
+//! A pattern is one of:
+//!
+//! - `{"type": kind}`, matching any value of the kind: `null`, `boolean`,
+//!   `number`, `string`, `array`, or `object`
+//! - `{"bind": name}`, matching any value, which is captured as `name`
+//! - `{"shape": {key: pattern, ...}}`, matching an object with each of the
+//!   keys, whose values match the keys' patterns. Other keys are ignored.
+//! - `{"lit": value}`, matching values equal to `value`, for values that
+//!   would otherwise be patterns
+//! - any other value, matching values equal to it
+//!
+//! Patterns are literals, and aren't evaluated. A branch whose pattern
+//! captured values is evaluated against the data with the captures in place
+//! of any keys they share, so that `{"var": name}` is the captured value.
+//! A final argument without a pattern is the default branch. If no pattern
+//! matches and there is no default, the result is an `Error::NoMatch`.
+
+use alloc::borrow::Cow;
+
+use serde_json::{Map, Value};
+
+use crate::context::Env;
+use crate::error::Error;
+use crate::prelude::*;
+use crate::resolver::{JsonLike, ValueKind};
+use crate::value::{Parsed, Raw};
+
+pub(crate) const OPERATOR: &str = "match";
+
+/// Whether the argument at `index` of a `match` with `len` arguments is a
+/// pattern: the scrutinee is followed by pairs of patterns and branches, and
+/// then perhaps the default branch.
+pub(crate) fn is_pattern(index: usize, len: usize) -> bool {
+    index % 2 == 1 && index + 1 < len
+}
+
+/// Check that a value is a valid pattern.
+pub(crate) fn check(pattern: &Value) -> Result<(), Error> {
+    let invalid =
+        |reason: &str| Error::invalid_argument(pattern.clone(), OPERATOR, reason);
+    match tag(pattern) {
+        Some(("type", Value::String(kind))) => match ValueKind::from_name(kind) {
+            Some(_) => Ok(()),
+            None => Err(invalid(
+                "Unknown type. Expected one of null, boolean, number, string, array, or object",
+            )),
+        },
+        Some(("type", _)) => Err(invalid("Types must be strings")),
+        Some(("bind", Value::String(_))) => Ok(()),
+        Some(("bind", _)) => Err(invalid("Capture names must be strings")),
+        Some(("shape", Value::Object(shape))) => shape.values().try_for_each(check),
+        Some(("shape", _)) => Err(invalid("Shapes must be objects")),
+        _ => Ok(()),
+    }
+}
+
+/// The key and value of a pattern that is a single-key object
+fn tag(pattern: &Value) -> Option<(&str, &Value)> {
+    match pattern {
+        Value::Object(obj) if obj.len() == 1 => {
+            obj.iter().next().map(|(key, value)| (key.as_str(), value))
+        }
+        _ => None,
+    }
+}
+
+/// Whether a value matches a pattern, adding any values the pattern
+/// captures to `captures`
+pub(crate) fn matches(
+    pattern: &Value,
+    value: &Value,
+    captures: &mut Map<String, Value>,
+) -> bool {
+    match tag(pattern) {
+        Some(("type", Value::String(kind))) => {
+            ValueKind::from_name(kind) == Some(JsonLike::kind(value))
+        }
+        Some(("bind", Value::String(name))) => {
+            captures.insert(name.clone(), value.clone());
+            true
+        }
+        Some((Raw::OPERATOR, literal)) => literal == value,
+        Some(("shape", Value::Object(shape))) => match value {
+            Value::Object(obj) => shape.iter().all(|(key, pattern)| {
+                obj.get(key)
+                    .is_some_and(|value| matches(pattern, value, captures))
+            }),
+            _ => false,
+        },
+        _ => pattern == value,
+    }
+}
+
+/// The data a branch is evaluated against: the data, with any captures in
+/// place of the keys they share
+pub(crate) fn branch_data(
+    data: &Value,
+    captures: Map<String, Value>,
+) -> Cow<'_, Value> {
+    if captures.is_empty() {
+        return Cow::Borrowed(data);
+    }
+    let mut merged = match data {
+        Value::Object(obj) => obj.clone(),
+        _ => Map::new(),
+    };
+    merged.extend(captures);
+    Cow::Owned(Value::Object(merged))
+}
+
+/// Evaluate the branch of the first pattern matching the first argument.
+pub fn match_(data: &Value, args: &[Parsed], env: &Env) -> Result<Value, Error> {
+    let value = Value::from(args[0].evaluate(data, env)?);
+    for (idx, arm) in args[1..].chunks(2).enumerate() {
+        let (pattern, branch) = match arm {
+            [pattern, branch] => (pattern, branch),
+            [default] => {
+                env.branch_taken(args, args.len() - 1);
+                return default.evaluate(data, env).map(Value::from);
+            }
+            _ => break,
+        };
+        let pattern = match pattern {
+            Parsed::Raw(pattern) => pattern.value(),
+            _ => {
+                return Err(Error::UnexpectedError(
+                    "A pattern was parsed as an expression".into(),
+                ))
+            }
+        };
+        let mut captures = Map::new();
+        if matches(pattern, &value, &mut captures) {
+            let env = if captures.is_empty() {
+                env.clone()
+            } else {
+                env.rebind()
+            };
+            env.branch_taken(args, 2 * idx + 2);
+            let data = branch_data(data, captures);
+            return branch.evaluate(&data, &env).map(Value::from);
+        }
+    }
+    Err(Error::NoMatch { value })
+}
+
+#[cfg(test)]
+mod test_pattern {
+    use super::*;
+    use serde_json::json;
+
+    use crate::Rule;
+
+    #[test]
+    fn test_matches() {
+        let check = |pattern: Value, value: Value| {
+            let mut captures = Map::new();
+            matches(&pattern, &value, &mut captures).then_some(Value::Object(captures))
+        };
+        assert_eq!(check(json!(1), json!(1)), Some(json!({})));
+        assert_eq!(check(json!("a"), json!("b")), None);
+        assert_eq!(check(json!([1, 2]), json!([1, 2])), Some(json!({})));
+        assert_eq!(check(json!({"type": "array"}), json!([])), Some(json!({})));
+        assert_eq!(check(json!({"type": "string"}), json!(1)), None);
+        assert_eq!(check(json!({"bind": "x"}), json!(1)), Some(json!({"x": 1})));
+        assert_eq!(
+            check(
+                json!({"shape": {"a": {"bind": "a"}, "b": {"type": "number"}}}),
+                json!({"a": [1], "b": 2, "c": 3})
+            ),
+            Some(json!({"a": [1]}))
+        );
+        assert_eq!(
+            check(
+                json!({"shape": {"a": 1, "b": {"bind": "b"}}}),
+                json!({"a": 1})
+            ),
+            None
+        );
+        assert_eq!(check(json!({"a": 1}), json!({"a": 1})), Some(json!({})));
+        assert_eq!(
+            check(json!({"lit": {"bind": "x"}}), json!({"bind": "x"})),
+            Some(json!({}))
+        );
+    }
+
+    #[test]
+    fn test_check() {
+        check(&json!({"shape": {"a": {"type": "null"}, "b": {"bind": "b"}}})).unwrap();
+        check(&json!({"type": "int"})).unwrap_err();
+        check(&json!({"bind": 1})).unwrap_err();
+        check(&json!({"shape": {"a": {"type": 1}}})).unwrap_err();
+        check(&json!({"shape": []})).unwrap_err();
+    }
+
+    #[test]
+    fn test_match() {
+        let rule = Rule::from_value(&json!({"match": [
+            {"var": "value"},
+            {"shape": {"kind": "circle", "r": {"bind": "r"}}},
+            {"*": [3, {"var": "r"}, {"var": "r"}]},
+            {"shape": {"kind": "square", "side": {"bind": "side"}}},
+            {"*": [{"var": "side"}, {"var": "side"}]},
+            {"type": "number"},
+            {"var": "value"},
+            "none",
+            {"var": "fallback"},
+            null
+        ]}))
+        .unwrap();
+        let compiled = rule.compile().unwrap();
+        let check = |data: Value, expected: Value| {
+            assert_eq!(rule.apply(&data).unwrap(), expected);
+            assert_eq!(compiled.apply(&data).unwrap(), expected);
+        };
+        check(json!({"value": {"kind": "circle", "r": 2}}), json!(12));
+        check(json!({"value": {"kind": "square", "side": 3}}), json!(9));
+        check(json!({"value": 5}), json!(5));
+        check(json!({"value": "none", "fallback": "x"}), json!("x"));
+        check(json!({"value": "some"}), json!(null));
+
+        let rule =
+            Rule::from_value(&json!({"match": [{"var": ""}, 1, "one", 2, "two"]}))
+                .unwrap();
+        let compiled = rule.compile().unwrap();
+        assert_eq!(rule.apply(&json!(2)).unwrap(), json!("two"));
+        for result in [rule.apply(&json!(3)), compiled.apply(&json!(3))].iter() {
+            match result {
+                Err(err @ Error::NoMatch { .. }) => assert_eq!(err.code(), "no_match"),
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+        Rule::from_value(&json!({"match": [1, {"type": "int"}, 1]})).unwrap_err();
+    }
+}
@@ -0,0 +1,64 @@
+//! Semantic Versions
+//!
+//! Versions are strict semantic versions (https://semver.org), e.g. `1.2.3`
+//! or `2.0.0-rc.1+build.5`, and are compared by precedence, ignoring build
+//! metadata. Ranges use Cargo's syntax for version requirements, e.g.
+//! `^1.4`, `~1.4.2`, or `>=1.2, <2`, with `||` between alternatives, e.g.
+//! `^1.4 || >=2.1`. As with Cargo and npm, a prerelease version only
+//! satisfies a range with a prerelease of the same version, so `1.5.0-beta`
+//! doesn't satisfy `>=1.4`.
+
+use ::semver::{Version, VersionReq};
+use serde_json::Value;
+
+use crate::error::Error;
+use crate::prelude::*;
+use crate::value::Evaluated;
+
+fn version(value: &Value, operation: &'static str) -> Result<Version, Error> {
+    let text = value.as_str().ok_or_else(|| {
+        Error::invalid_argument(value.clone(), operation, "Versions must be strings")
+    })?;
+    Version::parse(text).map_err(|err| {
+        Error::invalid_argument(
+            value.clone(),
+            operation,
+            format!("'{}' is not a semantic version: {}", text, err),
+        )
+    })
+}
+
+fn range(value: &Value, operation: &'static str) -> Result<Vec<VersionReq>, Error> {
+    let text = value.as_str().ok_or_else(|| {
+        Error::invalid_argument(
+            value.clone(),
+            operation,
+            "Version ranges must be strings",
+        )
+    })?;
+    text.split("||")
+        .map(|alternative| {
+            VersionReq::parse(alternative).map_err(|err| {
+                Error::invalid_argument(
+                    value.clone(),
+                    operation,
+                    format!("'{}' is not a version range: {}", text, err),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Whether the first version is greater than the second
+pub fn semver_gt(items: &[Evaluated]) -> Result<Value, Error> {
+    let a = version(&items[0], "semver_gt")?;
+    let b = version(&items[1], "semver_gt")?;
+    Ok(Value::Bool(a.cmp_precedence(&b).is_gt()))
+}
+
+/// Whether a version satisfies any alternative of a range
+pub fn semver_satisfies(items: &[Evaluated]) -> Result<Value, Error> {
+    let version = version(&items[0], "semver_satisfies")?;
+    let range = range(&items[1], "semver_satisfies")?;
+    Ok(Value::Bool(range.iter().any(|req| req.matches(&version))))
+}
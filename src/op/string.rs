@@ -1,11 +1,16 @@
 //! String Operations
 
+use core::cmp;
+use core::convert::TryInto;
 use serde_json::Value;
-use std::cmp;
-use std::convert::TryInto;
 
+use crate::context::Env;
 use crate::error::Error;
 use crate::js_op;
+use crate::locale::Locale;
+use crate::op::data;
+use crate::prelude::*;
+use crate::value::Evaluated;
 use crate::NULL;
 
 /// Concatenate strings.
@@ -15,11 +20,11 @@ use crate::NULL;
 /// evaluates to `"foo[object Object]". Here we explicitly require all
 /// arguments to be strings, because the specification explicitly defines
 /// `cat` as a string operation.
-pub fn cat(items: &Vec<&Value>) -> Result<Value, Error> {
+pub fn cat(items: &[Evaluated]) -> Result<Value, Error> {
     let mut rv = String::from("");
     items
-        .into_iter()
-        .map(|i| match i {
+        .iter()
+        .map(|i| match &**i {
             Value::String(i_string) => Ok(i_string.clone()),
             _ => Ok(js_op::to_string(i)),
         })
@@ -36,12 +41,12 @@ pub fn cat(items: &Vec<&Value>) -> Result<Value, Error> {
 /// Note: the reference implementation casts the first argument to a string,
 /// but since the specification explicitly defines this as a string operation,
 /// the argument types are enforced here to avoid unpredictable behavior.
-pub fn substr(items: &Vec<&Value>) -> Result<Value, Error> {
+pub fn substr(items: &[Evaluated]) -> Result<Value, Error> {
     // We can only have 2 or 3 arguments. Number of arguments is validated elsewhere.
-    let (string_arg, idx_arg) = (items[0], items[1]);
+    let (string_arg, idx_arg) = (&*items[0], &*items[1]);
     let limit_opt: Option<&Value>;
     if items.len() > 2 {
-        limit_opt = Some(items[2]);
+        limit_opt = Some(&items[2]);
     } else {
         limit_opt = None;
     }
@@ -158,3 +163,420 @@ pub fn substr(items: &Vec<&Value>) -> Result<Value, Error> {
             .collect(),
     ))
 }
+
+/// The string argument at `index`, failing if it isn't one
+pub(crate) fn string_arg<'a>(
+    items: &'a [Evaluated],
+    index: usize,
+    operation: &'static str,
+) -> Result<&'a str, Error> {
+    match &*items[index] {
+        Value::String(string) => Ok(string),
+        value => Err(Error::invalid_argument(
+            value.clone(),
+            operation,
+            format!("Argument {} to {} must be a string", index + 1, operation),
+        )),
+    }
+}
+
+/// Convert a string to upper case, by Unicode's case mappings, e.g. `ß`
+/// becomes `SS`.
+pub fn upper(items: &[Evaluated]) -> Result<Value, Error> {
+    Ok(Value::String(string_arg(items, 0, "upper")?.to_uppercase()))
+}
+
+/// Convert a string to lower case, by Unicode's case mappings.
+pub fn lower(items: &[Evaluated]) -> Result<Value, Error> {
+    Ok(Value::String(string_arg(items, 0, "lower")?.to_lowercase()))
+}
+
+/// Remove leading and trailing Unicode whitespace from a string.
+pub fn trim(items: &[Evaluated]) -> Result<Value, Error> {
+    Ok(Value::String(string_arg(items, 0, "trim")?.trim().into()))
+}
+
+/// Remove leading Unicode whitespace from a string.
+pub fn trim_start(items: &[Evaluated]) -> Result<Value, Error> {
+    Ok(Value::String(
+        string_arg(items, 0, "trim_start")?.trim_start().into(),
+    ))
+}
+
+/// Remove trailing Unicode whitespace from a string.
+pub fn trim_end(items: &[Evaluated]) -> Result<Value, Error> {
+    Ok(Value::String(
+        string_arg(items, 0, "trim_end")?.trim_end().into(),
+    ))
+}
+
+/// Pad the start of a string to a length, in characters, by repeating a fill
+/// string (by default a space).
+pub fn pad_start(items: &[Evaluated]) -> Result<Value, Error> {
+    pad(items, "pad_start")
+}
+
+/// Pad the end of a string to a length, in characters, by repeating a fill
+/// string (by default a space).
+pub fn pad_end(items: &[Evaluated]) -> Result<Value, Error> {
+    pad(items, "pad_end")
+}
+
+fn pad(items: &[Evaluated], operation: &'static str) -> Result<Value, Error> {
+    let string = string_arg(items, 0, operation)?;
+    let length = match items[1].as_u64() {
+        Some(length) if length <= MAX_PAD_LENGTH as u64 => length as usize,
+        Some(_) => {
+            return Err(Error::invalid_argument(
+                (*items[1]).clone(),
+                operation,
+                format!(
+                    "Strings may be padded to at most {} characters",
+                    MAX_PAD_LENGTH
+                ),
+            ))
+        }
+        None => {
+            return Err(Error::invalid_argument(
+                (*items[1]).clone(),
+                operation,
+                format!("Argument 2 to {} must be a non-negative integer", operation),
+            ))
+        }
+    };
+    let fill = match items.get(2) {
+        Some(_) => string_arg(items, 2, operation)?,
+        None => " ",
+    };
+    if fill.is_empty() {
+        return Err(Error::invalid_argument(
+            Value::String(fill.into()),
+            operation,
+            "The fill string must not be empty",
+        ));
+    }
+    let padding: String = fill
+        .chars()
+        .cycle()
+        .take(length.saturating_sub(string.chars().count()))
+        .collect();
+    Ok(Value::String(match operation {
+        "pad_start" => padding + string,
+        _ => String::from(string) + &padding,
+    }))
+}
+
+/// The largest length `pad_start` and `pad_end` may pad a string to
+const MAX_PAD_LENGTH: usize = 1024;
+
+/// Whether a string starts with another.
+pub fn starts_with(items: &[Evaluated]) -> Result<Value, Error> {
+    let (string, prefix) = (
+        string_arg(items, 0, "starts_with")?,
+        string_arg(items, 1, "starts_with")?,
+    );
+    Ok(Value::Bool(string.starts_with(prefix)))
+}
+
+/// Whether a string ends with another.
+pub fn ends_with(items: &[Evaluated]) -> Result<Value, Error> {
+    let (string, suffix) = (
+        string_arg(items, 0, "ends_with")?,
+        string_arg(items, 1, "ends_with")?,
+    );
+    Ok(Value::Bool(string.ends_with(suffix)))
+}
+
+/// Split a string on a separator into an array of strings. An empty
+/// separator splits the string into its characters.
+pub fn split(items: &[Evaluated]) -> Result<Value, Error> {
+    let (string, separator) = (
+        string_arg(items, 0, "split")?,
+        string_arg(items, 1, "split")?,
+    );
+    let parts = match separator {
+        "" => string.chars().map(|c| Value::String(c.into())).collect(),
+        separator => string
+            .split(separator)
+            .map(|part| Value::String(part.into()))
+            .collect(),
+    };
+    Ok(Value::Array(parts))
+}
+
+/// Join the elements of an array, converted to strings as by `cat`, with a
+/// separator.
+pub fn join(items: &[Evaluated]) -> Result<Value, Error> {
+    let elements = match &*items[0] {
+        Value::Array(elements) => elements,
+        value => {
+            return Err(Error::invalid_argument(
+                value.clone(),
+                "join",
+                "Argument 1 to join must be an array",
+            ))
+        }
+    };
+    let separator = string_arg(items, 1, "join")?;
+    Ok(Value::String(
+        elements
+            .iter()
+            .map(js_op::to_string)
+            .collect::<Vec<String>>()
+            .join(separator),
+    ))
+}
+
+/// Interpolate values into a template string.
+///
+/// Each `{path}` in the template is replaced by the value at that path in
+/// the context given second, converted to a string as by `cat`, and `{{` and
+/// `}}` are literal braces. A placeholder whose path is missing from the
+/// context is an error, unless the optional third argument is `"keep"`
+/// rather than `"error"`, in which case the placeholder is left as-is.
+pub fn template(items: &[Evaluated]) -> Result<Value, Error> {
+    let template = match &*items[0] {
+        Value::String(template) => template,
+        value => {
+            return Err(Error::invalid_argument(
+                value.clone(),
+                "template",
+                "First argument to template must be a string",
+            ))
+        }
+    };
+    let context = &*items[1];
+    let keep_missing = match items.get(2).map(|missing| &**missing) {
+        None => false,
+        Some(Value::String(missing)) if missing == "error" => false,
+        Some(Value::String(missing)) if missing == "keep" => true,
+        Some(missing) => {
+            return Err(Error::invalid_argument(
+                missing.clone(),
+                "template",
+                "Third argument to template must be \"error\" or \"keep\"",
+            ))
+        }
+    };
+    let invalid = |reason: &str| {
+        Error::invalid_argument(Value::String(template.clone()), "template", reason)
+    };
+
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template.as_str();
+    while let Some(i) = rest.find(['{', '}']) {
+        rendered.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            rendered.push_str(&rest[..1]);
+            rest = &rest[2..];
+            continue;
+        }
+        if rest.starts_with('}') {
+            return Err(invalid("Unmatched '}' in template, which must be '}}'"));
+        }
+        let end = rest
+            .find('}')
+            .ok_or_else(|| invalid("Unclosed '{' in template"))?;
+        let path = &rest[1..end];
+        let key = Value::String(path.into());
+        match data::resolve(context, &data::key_path(Evaluated::Raw(&key))?)? {
+            Some(value) => rendered.push_str(&js_op::to_string(&value)),
+            None if keep_missing => rendered.push_str(&rest[..=end]),
+            None => {
+                return Err(Error::invalid_argument(
+                    key,
+                    "template",
+                    format!("The template's context has no value for '{}'", path),
+                ))
+            }
+        }
+        rest = &rest[end + 1..];
+    }
+    rendered.push_str(rest);
+    Ok(Value::String(rendered))
+}
+
+/// Format values with a printf-style format string.
+///
+/// Each conversion in the format, `%[flags][width][.precision]type`, is
+/// replaced by the next of the remaining arguments, formatted by type:
+///
+/// - `s`: the value as a string, as by `cat`, truncated to the precision
+/// - `d` or `i`: the number, truncated to an integer
+/// - `f`: the number, with `precision` (by default 6) fractional digits
+/// - `e`: the number in scientific notation, e.g. `1.5e+03`, with
+///   `precision` (by default 6) fractional digits
+///
+/// and padded with spaces to at least `width` characters. The flags are `-`
+/// to pad on the right rather than the left, `0` to pad numbers with zeros,
+/// `+` to sign positive numbers, and `'` to group the digits of numbers in
+/// thousands. `%%` is a literal `%`. Numbers are written with the separators
+/// of the context's locale. Widths and precisions may be at most 1024.
+pub fn format(items: &[Evaluated], env: &Env) -> Result<Value, Error> {
+    let format = match &*items[0] {
+        Value::String(format) => format,
+        value => {
+            return Err(Error::invalid_argument(
+                value.clone(),
+                "format",
+                "First argument to format must be a string",
+            ))
+        }
+    };
+    let invalid = |reason: String| {
+        Error::invalid_argument(Value::String(format.clone()), "format", reason)
+    };
+    let default_locale;
+    let locale = match env.locale() {
+        Some(locale) => locale,
+        None => {
+            default_locale = Locale::default();
+            &default_locale
+        }
+    };
+
+    let mut args = items[1..].iter();
+    let mut formatted = String::with_capacity(format.len());
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            formatted.push(c);
+            continue;
+        }
+        if chars.next_if_eq(&'%').is_some() {
+            formatted.push('%');
+            continue;
+        }
+        let mut spec = Spec::default();
+        while let Some(flag) = chars.next_if(|c| matches!(c, '-' | '0' | '+' | '\'')) {
+            match flag {
+                '-' => spec.left = true,
+                '0' => spec.zero = true,
+                '+' => spec.plus = true,
+                _ => spec.group = true,
+            }
+        }
+        spec.width = digits(&mut chars).unwrap_or(0);
+        if chars.next_if_eq(&'.').is_some() {
+            spec.precision = Some(digits(&mut chars).unwrap_or(0));
+        }
+        if spec.width.max(spec.precision.unwrap_or(0)) > MAX_FORMAT_FIELD {
+            return Err(invalid(format!(
+                "Widths and precisions may be at most {}",
+                MAX_FORMAT_FIELD
+            )));
+        }
+        let conversion = chars
+            .next()
+            .ok_or_else(|| invalid("The format ends within a conversion".into()))?;
+        let arg = args.next().ok_or_else(|| {
+            invalid(format!("There is no argument for '%{}'", conversion))
+        })?;
+        let (sign, text) = match conversion {
+            's' => {
+                let string = js_op::to_string(arg);
+                let text = match spec.precision {
+                    Some(precision) => string.chars().take(precision).collect(),
+                    None => string,
+                };
+                (String::new(), text)
+            }
+            'd' | 'i' | 'f' | 'e' => {
+                let number = js_op::to_number(arg)
+                    .filter(|number| number.is_finite())
+                    .ok_or_else(|| {
+                        Error::invalid_argument(
+                            (**arg).clone(),
+                            "format",
+                            format!("'%{}' requires a number", conversion),
+                        )
+                    })?;
+                number_parts(number, conversion, &spec, locale)
+            }
+            conversion => {
+                return Err(invalid(format!("Unknown conversion '%{}'", conversion)))
+            }
+        };
+
+        let len = sign.chars().count() + text.chars().count();
+        let padding = spec.width.saturating_sub(len);
+        let numeric = conversion != 's';
+        if spec.left {
+            formatted.push_str(&sign);
+            formatted.push_str(&text);
+            formatted.extend(core::iter::repeat(' ').take(padding));
+        } else if spec.zero && numeric {
+            formatted.push_str(&sign);
+            formatted.extend(core::iter::repeat('0').take(padding));
+            formatted.push_str(&text);
+        } else {
+            formatted.extend(core::iter::repeat(' ').take(padding));
+            formatted.push_str(&sign);
+            formatted.push_str(&text);
+        }
+    }
+    if args.next().is_some() {
+        return Err(invalid(String::from(
+            "There are more arguments than conversions in the format",
+        )));
+    }
+    Ok(Value::String(formatted))
+}
+
+/// The largest width or precision of a `format` conversion
+const MAX_FORMAT_FIELD: usize = 1024;
+
+/// The flags, width, and precision of a `format` conversion
+#[derive(Default)]
+struct Spec {
+    left: bool,
+    zero: bool,
+    plus: bool,
+    group: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+/// Consume a run of digits, returning their value.
+fn digits(chars: &mut core::iter::Peekable<core::str::Chars>) -> Option<usize> {
+    let mut value: Option<usize> = None;
+    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+        let digit = digit.to_digit(10).unwrap_or_default() as usize;
+        value = Some(value.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+    }
+    value
+}
+
+/// The sign and localized digits of a number formatted with a conversion
+fn number_parts(
+    number: f64,
+    conversion: char,
+    spec: &Spec,
+    locale: &Locale,
+) -> (String, String) {
+    let number = match conversion {
+        'd' | 'i' => number.trunc(),
+        _ => number,
+    };
+    let precision = spec.precision.unwrap_or(6);
+    let digits = match conversion {
+        'd' | 'i' => format!("{:.0}", number.abs()),
+        'f' => format!("{:.*}", precision, number.abs()),
+        _ => {
+            // Rust writes e.g. `1.5e3`, rather than C's `1.5e+03`
+            let written = format!("{:.*e}", precision, number.abs());
+            let (mantissa, exponent) =
+                written.split_once('e').unwrap_or((&written, "0"));
+            let exponent: i32 = exponent.parse().unwrap_or_default();
+            let sign = if exponent < 0 { '-' } else { '+' };
+            format!("{}e{}{:02}", mantissa, sign, exponent.abs())
+        }
+    };
+    let sign = match number < 0.0 {
+        true => "-",
+        false if spec.plus => "+",
+        false => "",
+    };
+    (sign.into(), locale.localize(&digits, spec.group))
+}
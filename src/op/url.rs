@@ -0,0 +1,330 @@
+//! URLs
+//!
+//! Strings that aren't absolute URLs have no components, so that rules over
+//! untrusted input, e.g. a referrer, don't fail on malformed values: their
+//! `url_parse` and `url_host` are `null`, and they match no pattern.
+//!
+//! Patterns for `url_matches_pattern` are written like URLs, as
+//! `scheme://host[:port][path]`, where:
+//!
+//! - the scheme is `*`, matching any scheme, or a scheme
+//! - the host is `*`, matching any host, `*.` followed by a domain,
+//!   matching the domain and any of its subdomains, or a host
+//! - the port, if any, is `*` or a port. Without one, any port matches.
+//! - the path, if any, starts with `/`, and is matched against the URL's
+//!   path and query, with `*` matching any characters. Without one, any path
+//!   matches.
+//!
+//! For example, `https://*.example.com/blog/*` matches
+//! `https://www.example.com/blog/post?id=1` and
+//! `https://example.com:8443/blog/`, but not `http://example.com/blog/` or
+//! `https://example.com/about`. Schemes and hosts are case-insensitive.
+
+use ::url::Url;
+use serde_json::{Map, Value};
+
+use super::string::string_arg;
+use crate::error::Error;
+use crate::prelude::*;
+use crate::value::Evaluated;
+
+fn optional(value: Option<&str>) -> Value {
+    value.map_or(Value::Null, Value::from)
+}
+
+/// The components of a URL, as an object with its `scheme`, `username`,
+/// `password`, `host`, `port`, `path`, `query`, and `fragment`, and its
+/// query's `params`. The port is the scheme's default if the URL has none.
+/// Params are decoded, and repeated params have their last value.
+pub fn url_parse(items: &[Evaluated]) -> Result<Value, Error> {
+    let url = match Url::parse(string_arg(items, 0, "url_parse")?) {
+        Ok(url) => url,
+        Err(_) => return Ok(Value::Null),
+    };
+    let mut components = Map::new();
+    components.insert("scheme".into(), url.scheme().into());
+    components.insert("username".into(), url.username().into());
+    components.insert("password".into(), optional(url.password()));
+    components.insert("host".into(), optional(url.host_str()));
+    components.insert(
+        "port".into(),
+        url.port_or_known_default().map_or(Value::Null, Value::from),
+    );
+    components.insert("path".into(), url.path().into());
+    components.insert("query".into(), optional(url.query()));
+    components.insert("fragment".into(), optional(url.fragment()));
+    components.insert(
+        "params".into(),
+        Value::Object(
+            url.query_pairs()
+                .map(|(key, value)| (key.into_owned(), Value::from(value.into_owned())))
+                .collect(),
+        ),
+    );
+    Ok(Value::Object(components))
+}
+
+/// The host of a URL, lowercased, or `null` if it has none
+pub fn url_host(items: &[Evaluated]) -> Result<Value, Error> {
+    Ok(Url::parse(string_arg(items, 0, "url_host")?)
+        .ok()
+        .and_then(|url| url.host_str().map(Value::from))
+        .unwrap_or(Value::Null))
+}
+
+/// Whether a URL matches a pattern. See the module documentation for the
+/// pattern syntax.
+pub fn url_matches_pattern(items: &[Evaluated]) -> Result<Value, Error> {
+    let url = string_arg(items, 0, "url_matches_pattern")?;
+    let pattern = UrlPattern::parse(string_arg(items, 1, "url_matches_pattern")?)?;
+    Ok(Value::Bool(
+        Url::parse(url).is_ok_and(|url| pattern.matches(&url)),
+    ))
+}
+
+/// A parsed `url_matches_pattern` pattern
+struct UrlPattern<'a> {
+    scheme: Option<String>,
+    host: Host,
+    port: Option<u16>,
+    path: Option<&'a str>,
+}
+
+/// A pattern's host
+enum Host {
+    Any,
+    Domain(String),
+    Exact(String),
+}
+
+impl<'a> UrlPattern<'a> {
+    fn parse(pattern: &'a str) -> Result<Self, Error> {
+        let invalid = |reason: &str| {
+            Error::invalid_argument(
+                Value::String(pattern.into()),
+                "url_matches_pattern",
+                reason,
+            )
+        };
+        let (scheme, rest) = pattern
+            .split_once("://")
+            .ok_or_else(|| invalid("URL patterns must start with a scheme and ://"))?;
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], Some(&rest[i..])),
+            None => (rest, None),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            // The colons of an IPv6 address are within its brackets
+            Some((host, port)) if !port.contains(']') => (host, Some(port)),
+            _ => (authority, None),
+        };
+        if host.is_empty() {
+            return Err(invalid("URL patterns must have a host, or *"));
+        }
+        let host = host.to_lowercase();
+        Ok(Self {
+            scheme: (scheme != "*").then(|| scheme.to_lowercase()),
+            host: match host.strip_prefix("*.") {
+                _ if host == "*" => Host::Any,
+                Some(domain) => Host::Domain(domain.into()),
+                None => Host::Exact(host),
+            },
+            port: match port {
+                None | Some("*") => None,
+                Some(port) => {
+                    Some(port.parse().map_err(|_| {
+                        invalid("URL pattern ports must be numbers, or *")
+                    })?)
+                }
+            },
+            path,
+        })
+    }
+
+    fn matches(&self, url: &Url) -> bool {
+        let host = url.host_str().unwrap_or_default();
+        self.scheme
+            .as_ref()
+            .map_or(true, |scheme| scheme == url.scheme())
+            && match &self.host {
+                Host::Any => true,
+                Host::Domain(domain) => {
+                    host == domain
+                        || host
+                            .strip_suffix(domain.as_str())
+                            .is_some_and(|sub| sub.ends_with('.'))
+                }
+                Host::Exact(exact) => host == exact,
+            }
+            && self
+                .port
+                .map_or(true, |port| url.port_or_known_default() == Some(port))
+            && self.path.map_or(true, |path| {
+                let target = match url.query() {
+                    Some(query) => format!("{}?{}", url.path(), query),
+                    None => url.path().into(),
+                };
+                glob(path.as_bytes(), target.as_bytes())
+            })
+    }
+}
+
+/// Whether a string matches a pattern in which `*` matches any characters
+fn glob(pattern: &[u8], target: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // The position of the last `*` in the pattern, and of the target when it
+    // was reached, to backtrack to on a mismatch
+    let mut star: Option<(usize, usize)> = None;
+    while t < target.len() {
+        if p < pattern.len() && pattern[p] == b'*' {
+            star = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == target[t] {
+            p += 1;
+            t += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+#[cfg(test)]
+mod test_url {
+    use serde_json::{json, Value};
+
+    use crate::{Error, Rule};
+
+    fn apply(rule: Value) -> Result<Value, Error> {
+        Rule::from_value(&rule)?.apply(&json!({}))
+    }
+
+    #[test]
+    fn test_url_parse() {
+        assert_eq!(
+            apply(json!({"url_parse": "HTTPS://user:pw@Example.com/a%20b/c?x=1&y=%C3%A9&x=2#top"}))
+                .unwrap(),
+            json!({
+                "scheme": "https",
+                "username": "user",
+                "password": "pw",
+                "host": "example.com",
+                "port": 443,
+                "path": "/a%20b/c",
+                "query": "x=1&y=%C3%A9&x=2",
+                "fragment": "top",
+                "params": {"x": "2", "y": "é"},
+            })
+        );
+        assert_eq!(
+            apply(json!({"url_parse": "mailto:someone@example.com"})).unwrap(),
+            json!({
+                "scheme": "mailto",
+                "username": "",
+                "password": null,
+                "host": null,
+                "port": null,
+                "path": "someone@example.com",
+                "query": null,
+                "fragment": null,
+                "params": {},
+            })
+        );
+        assert_eq!(
+            apply(json!({"url_parse": "/relative"})).unwrap(),
+            json!(null)
+        );
+        assert_eq!(
+            apply(json!({"url_parse": 1})).unwrap_err().code(),
+            "invalid_argument"
+        );
+    }
+
+    #[test]
+    fn test_url_host() {
+        assert_eq!(
+            apply(json!({"url_host": "https://WWW.example.com:8080/x"})).unwrap(),
+            json!("www.example.com")
+        );
+        assert_eq!(
+            apply(json!({"url_host": "http://[::1]/"})).unwrap(),
+            json!("[::1]")
+        );
+        assert_eq!(apply(json!({"url_host": "nope"})).unwrap(), json!(null));
+    }
+
+    #[test]
+    fn test_url_matches_pattern() {
+        let cases = [
+            (
+                "https://www.example.com/blog/post?id=1",
+                "https://*.example.com/blog/*",
+                true,
+            ),
+            (
+                "https://example.com:8443/blog/",
+                "https://*.example.com/blog/*",
+                true,
+            ),
+            (
+                "http://example.com/blog/",
+                "https://*.example.com/blog/*",
+                false,
+            ),
+            (
+                "https://example.com/about",
+                "https://*.example.com/blog/*",
+                false,
+            ),
+            (
+                "https://badexample.com/blog/",
+                "https://*.example.com/blog/*",
+                false,
+            ),
+            ("https://EXAMPLE.com/", "*://example.com", true),
+            ("ftp://example.com/x", "*://*", true),
+            ("https://example.com/", "https://example.com:443/", true),
+            (
+                "https://example.com:8443/",
+                "https://example.com:443/",
+                false,
+            ),
+            ("https://example.com:8443/", "https://example.com:*/", true),
+            (
+                "https://example.com/a/b/c.png",
+                "https://example.com/*/*.png",
+                true,
+            ),
+            (
+                "https://example.com/a/b/c.jpg",
+                "https://example.com/*/*.png",
+                false,
+            ),
+            ("http://[::1]:8080/", "http://[::1]:8080/*", true),
+            ("not a url", "*://*", false),
+        ];
+        for (url, pattern, expected) in cases {
+            assert_eq!(
+                apply(json!({"url_matches_pattern": [url, pattern]})).unwrap(),
+                json!(expected),
+                "{} {}",
+                url,
+                pattern
+            );
+        }
+        for pattern in ["example.com", "https://", "https://example.com:http/"] {
+            assert_eq!(
+                apply(json!({"url_matches_pattern": ["https://example.com", pattern]}))
+                    .unwrap_err()
+                    .code(),
+                "invalid_argument",
+                "{}",
+                pattern
+            );
+        }
+    }
+}
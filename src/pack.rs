@@ -0,0 +1,408 @@
+//! Operator Packs
+//!
+//! An operator pack is a collection of custom operators, generally published
+//! as its own crate, which may be loaded into a `Namespace` with
+//! `Namespace::load_pack()`. Packs declare the version of the pack API they
+//! were built against, so that a pack built for an incompatible version of
+//! this crate is rejected when loaded rather than misbehaving.
+
+use alloc::sync::Arc;
+use core::fmt;
+
+use serde_json::{Map, Value};
+
+use crate::arena::ArenaSlice;
+#[cfg(feature = "async")]
+use crate::asynchronous::AsyncOperator;
+use crate::context::Env;
+use crate::error::Error;
+use crate::namespace::Scope;
+use crate::op::{check_deterministic, op_args, single_entry, NumParams};
+use crate::prelude::*;
+use crate::value::{Evaluated, Parsed};
+use crate::Parser;
+
+/// The version of the operator pack API implemented by this crate
+///
+/// This only changes when `OperatorPack` or `CustomOperator` change in a way
+/// that would break existing packs.
+pub const PACK_API_VERSION: u32 = 1;
+
+/// A collection of custom operators
+pub trait OperatorPack {
+    /// The name of the pack, e.g. "geo"
+    fn name(&self) -> &str;
+
+    /// The version of the pack itself
+    fn version(&self) -> &str;
+
+    /// The version of the pack API the pack was built against, which should
+    /// be the `PACK_API_VERSION` of the version of this crate it depends on.
+    ///
+    /// This has no default, so that a pack always reports the version it
+    /// was built against, rather than that of the crate it's loaded into.
+    fn api_version(&self) -> u32;
+
+    /// The operators provided by the pack
+    fn operators(&self) -> Vec<CustomOperator>;
+}
+
+/// The signature of a custom operator's implementation
+///
+/// Custom operators receive their arguments already evaluated, after they
+/// have been checked against the operator's `NumParams`.
+pub type CustomOperatorFn = dyn Fn(&[&Value]) -> Result<Value, Error> + Send + Sync;
+
+/// The implementation of a custom operator
+#[derive(Clone)]
+enum Implementation {
+    Sync(Arc<CustomOperatorFn>),
+    #[cfg(feature = "async")]
+    Async(Arc<dyn AsyncOperator>),
+}
+
+/// An operator provided by an `OperatorPack`
+#[derive(Clone)]
+pub struct CustomOperator {
+    name: String,
+    num_params: NumParams,
+    description: String,
+    deterministic: bool,
+    operator: Implementation,
+}
+impl CustomOperator {
+    /// Construct a custom operator.
+    pub fn new<S, F>(name: S, num_params: NumParams, operator: F) -> Self
+    where
+        S: Into<String>,
+        F: Fn(&[&Value]) -> Result<Value, Error> + Send + Sync + 'static,
+    {
+        Self {
+            name: name.into(),
+            num_params,
+            description: String::new(),
+            deterministic: false,
+            operator: Implementation::Sync(Arc::new(operator)),
+        }
+    }
+
+    /// Construct an asynchronous custom operator.
+    ///
+    /// Rules using async operators must be evaluated with
+    /// `Rule::apply_async()`.
+    #[cfg(feature = "async")]
+    pub fn new_async<S, O>(name: S, num_params: NumParams, operator: O) -> Self
+    where
+        S: Into<String>,
+        O: AsyncOperator + 'static,
+    {
+        Self {
+            name: name.into(),
+            num_params,
+            description: String::new(),
+            deterministic: false,
+            operator: Implementation::Async(Arc::new(operator)),
+        }
+    }
+
+    /// Describe the operator, e.g. for documentation or editor tooling.
+    pub fn with_description<S: Into<String>>(mut self, description: S) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    /// Declare that the operator's result depends only on its arguments, so
+    /// that it may be used in rules parsed with `ParseOptions::deterministic`.
+    pub fn deterministic(mut self) -> Self {
+        self.deterministic = true;
+        self
+    }
+
+    /// The operator's name, i.e. its key in a rule
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The number of arguments the operator accepts
+    pub fn num_params(&self) -> &NumParams {
+        &self.num_params
+    }
+
+    /// Whether the operator has been declared deterministic
+    pub fn is_deterministic(&self) -> bool {
+        self.deterministic
+    }
+
+    /// A description of the operator
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Whether the operator is asynchronous
+    pub fn is_async(&self) -> bool {
+        match self.operator {
+            Implementation::Sync(_) => false,
+            #[cfg(feature = "async")]
+            Implementation::Async(_) => true,
+        }
+    }
+
+    /// Execute the operator on already-evaluated arguments.
+    ///
+    /// Async operators can't be executed directly, and return an error.
+    pub fn execute(&self, items: &[&Value]) -> Result<Value, Error> {
+        self.execute_in(items, &Env::default())
+    }
+
+    /// Execute the operator within an evaluation.
+    #[cfg_attr(not(feature = "async"), allow(unused_variables))]
+    pub(crate) fn execute_in(
+        &self,
+        items: &[&Value],
+        env: &Env,
+    ) -> Result<Value, Error> {
+        self.num_params.check_len(&items.len())?;
+        match &self.operator {
+            Implementation::Sync(operator) => operator(items),
+            #[cfg(feature = "async")]
+            Implementation::Async(operator) => match env.asynchronous() {
+                Some(state) => state.call(&self.name, operator, items),
+                None => Err(Error::InvalidOperation {
+                    key: self.name.clone(),
+                    reason: "Async operators must be evaluated with apply_async()"
+                        .into(),
+                }),
+            },
+        }
+    }
+}
+impl fmt::Debug for CustomOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CustomOperator")
+            .field("name", &self.name)
+            .field("num_params", &self.num_params)
+            .field("operator", &"<operator fn>")
+            .finish()
+    }
+}
+
+/// The name and version of a loaded pack
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PackInfo {
+    pub name: String,
+    pub version: String,
+}
+
+/// An application of a custom operator
+///
+/// Custom operations are only parsed within a namespace into which the
+/// operator's pack has been loaded.
+#[derive(Clone, Debug)]
+pub struct CustomOperation {
+    operator: Arc<CustomOperator>,
+    arguments: ArenaSlice<Parsed>,
+}
+impl CustomOperation {
+    /// The custom operator being applied
+    pub(crate) fn operator(&self) -> &CustomOperator {
+        &self.operator
+    }
+
+    /// The (unevaluated) arguments to the operator
+    pub(crate) fn arguments(&self) -> &[Parsed] {
+        &self.arguments
+    }
+
+    /// The arguments, to be replaced when generating mutants
+    pub(crate) fn arguments_mut(&mut self) -> &mut Vec<Parsed> {
+        self.arguments.to_mut()
+    }
+}
+impl Parser for CustomOperation {
+    fn from_value(value: &Value, scope: &Scope) -> Result<Option<Self>, Error> {
+        let namespace = match scope.namespace() {
+            Some(namespace) => namespace,
+            None => return Ok(None),
+        };
+        let (key, val) = match single_entry(value)? {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let operator = match namespace.shared_operator(key) {
+            Some(operator) => operator,
+            None => return Ok(None),
+        };
+        check_deterministic(key, operator.is_deterministic(), scope)?;
+        let args = op_args(key, val, operator.num_params())?;
+        Ok(Some(Self {
+            operator: operator.clone(),
+            arguments: Parsed::from_values(args, scope)?,
+        }))
+    }
+
+    fn evaluate(&self, data: &Value, env: &Env) -> Result<Evaluated<'_>, Error> {
+        let arguments = self
+            .arguments
+            .iter()
+            .map(|arg| arg.evaluate(data, env))
+            .collect::<Result<Vec<Evaluated>, Error>>()?;
+        let values: Vec<&Value> = arguments.iter().map(|arg| &**arg).collect();
+        self.operator.execute_in(&values, env).map(Evaluated::New)
+    }
+}
+impl From<CustomOperation> for Value {
+    fn from(op: CustomOperation) -> Self {
+        let mut rv = Map::with_capacity(1);
+        let values = op.arguments.into_iter().map(Value::from).collect();
+        rv.insert(op.operator.name.clone(), Value::Array(values));
+        Value::Object(rv)
+    }
+}
+
+#[cfg(test)]
+mod test_pack {
+    use super::*;
+    use serde_json::json;
+
+    use crate::rule::ParseOptions;
+    use crate::{Namespace, OperatorCategory};
+
+    struct StatsPack;
+    impl OperatorPack for StatsPack {
+        fn name(&self) -> &str {
+            "stats"
+        }
+        fn version(&self) -> &str {
+            "1.0.0"
+        }
+        fn api_version(&self) -> u32 {
+            PACK_API_VERSION
+        }
+        fn operators(&self) -> Vec<CustomOperator> {
+            vec![CustomOperator::new("mean", NumParams::AtLeast(1), |items| {
+                let total = items.iter().try_fold(0.0, |total, item| {
+                    item.as_f64().map(|n| total + n).ok_or_else(|| {
+                        Error::invalid_argument((*item).clone(), "mean", "not a number")
+                    })
+                })?;
+                Ok(json!(total / items.len() as f64))
+            })
+            .with_description("The arithmetic mean of its arguments")
+            .deterministic()]
+        }
+    }
+
+    struct ClockPack;
+    impl OperatorPack for ClockPack {
+        fn name(&self) -> &str {
+            "clock"
+        }
+        fn version(&self) -> &str {
+            "1.0.0"
+        }
+        fn api_version(&self) -> u32 {
+            PACK_API_VERSION
+        }
+        fn operators(&self) -> Vec<CustomOperator> {
+            vec![CustomOperator::new("ticks", NumParams::None, |_| {
+                Ok(json!(0))
+            })]
+        }
+    }
+
+    struct FuturePack;
+    impl OperatorPack for FuturePack {
+        fn name(&self) -> &str {
+            "future"
+        }
+        fn version(&self) -> &str {
+            "0.1.0"
+        }
+        fn api_version(&self) -> u32 {
+            PACK_API_VERSION + 1
+        }
+        fn operators(&self) -> Vec<CustomOperator> {
+            vec![]
+        }
+    }
+
+    #[test]
+    fn test_load_pack() {
+        let mut ns = Namespace::new();
+        ns.load_pack(&StatsPack).unwrap();
+        assert_eq!(
+            ns.packs(),
+            &[PackInfo {
+                name: "stats".into(),
+                version: "1.0.0".into()
+            }]
+        );
+        let rule = ns
+            .parse(&json!({"mean": [1, {"var": "a"}, {"+": [1, 2]}]}))
+            .unwrap();
+        assert_eq!(rule.apply(&json!({"a": 2})).unwrap(), json!(2.0));
+        ns.parse(&json!({"mean": []})).unwrap_err();
+        match ns.parse(&json!({"meen": [1]})) {
+            Err(Error::UnknownOperator { suggestions, .. }) => {
+                assert!(suggestions.contains(&"mean".to_string()))
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // Loading the same operators twice is an error
+        ns.load_pack(&StatsPack).unwrap_err();
+    }
+
+    #[test]
+    fn test_namespace_operators() {
+        let mut ns = Namespace::new();
+        ns.load_pack(&StatsPack).unwrap();
+        ns.define(&json!({"defn": [
+            "double",
+            ["x"],
+            {"defn": ["inner", [], {"param": "x"}]},
+            {"doc": "Twice the value"}
+        ]}))
+        .unwrap();
+        let operators = ns.operators();
+        assert_eq!(operators.len(), crate::operators().len() + 2);
+        let find = |name: &str| operators.iter().find(|op| op.name() == name);
+        let mean = find("mean").unwrap();
+        assert_eq!(mean.category(), OperatorCategory::Custom);
+        assert_eq!(mean.num_params(), &NumParams::AtLeast(1));
+        assert_eq!(mean.doc(), "The arithmetic mean of its arguments");
+        let double = find("double").unwrap();
+        assert_eq!(double.category(), OperatorCategory::Defined);
+        assert_eq!(double.num_params(), &NumParams::Exactly(1));
+        assert_eq!(double.doc(), "Twice the value");
+        assert!(find("double/inner").is_none());
+        assert_eq!(find("if").unwrap().category(), OperatorCategory::Logic);
+    }
+
+    #[test]
+    fn test_deterministic_operators() {
+        let mut ns = Namespace::new();
+        ns.load_pack(&StatsPack).unwrap();
+        ns.load_pack(&ClockPack).unwrap();
+        ns.define(&json!({"defn": ["now", [], {"ticks": []}]}))
+            .unwrap();
+        ns.set_parse_options(ParseOptions {
+            deterministic: true,
+            ..ParseOptions::default()
+        });
+        let rule = ns.parse(&json!({"mean": [1, 3]})).unwrap();
+        assert_eq!(rule.apply(&json!({})).unwrap(), json!(2.0));
+        // Including when called from functions
+        for rule in [json!({"ticks": []}), json!({"mean": [{"now": []}]})] {
+            match ns.parse(&rule) {
+                Err(Error::NondeterministicOperator(name)) => assert_eq!(name, "ticks"),
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_load_incompatible_pack() {
+        Namespace::new().load_pack(&FuturePack).unwrap_err();
+    }
+}
@@ -0,0 +1,129 @@
+//! Profiling
+//!
+//! A profiler records how many times each operator is executed, and how long
+//! those executions take, across every evaluation it is attached to via
+//! `Context::profiler`. Use it to find the expensive parts of large rules.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Per-operator execution statistics
+#[derive(Debug, Default)]
+pub struct Profiler {
+    stats: Mutex<HashMap<String, OperatorStats>>,
+}
+impl Profiler {
+    /// Construct a profiler with no recorded executions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Time the execution of an operator.
+    pub(crate) fn time<T, F: FnOnce() -> T>(&self, operator: &str, execute: F) -> T {
+        let started = Instant::now();
+        let result = execute();
+        self.record(operator, started.elapsed());
+        result
+    }
+
+    fn record(&self, operator: &str, elapsed: Duration) {
+        let mut stats = self.lock();
+        match stats.get_mut(operator) {
+            Some(entry) => {
+                entry.calls += 1;
+                entry.total += elapsed;
+            }
+            None => {
+                stats.insert(
+                    operator.into(),
+                    OperatorStats {
+                        operator: operator.into(),
+                        calls: 1,
+                        total: elapsed,
+                    },
+                );
+            }
+        }
+    }
+
+    /// The statistics for every operator executed so far, most expensive
+    /// first
+    pub fn stats(&self) -> Vec<OperatorStats> {
+        let mut stats: Vec<OperatorStats> = self.lock().values().cloned().collect();
+        stats.sort_by(|a, b| {
+            b.total
+                .cmp(&a.total)
+                .then_with(|| a.operator.cmp(&b.operator))
+        });
+        stats
+    }
+
+    /// Discard all recorded statistics.
+    pub fn reset(&self) {
+        self.lock().clear()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, OperatorStats>> {
+        // Nothing can panic while the lock is held.
+        self.stats
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// The executions of a single operator
+#[derive(Clone, Debug, PartialEq)]
+pub struct OperatorStats {
+    /// The operator, or the name of the called function
+    pub operator: String,
+    /// The number of times the operator was executed
+    pub calls: u64,
+    /// The total time spent executing the operator. This includes the time
+    /// spent evaluating its arguments, so nested operations are counted
+    /// towards every operator that encloses them.
+    pub total: Duration,
+}
+
+#[cfg(test)]
+mod test_profiler {
+    use super::*;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    use crate::{Context, Rule};
+
+    #[test]
+    fn test_profile() {
+        let profiler = Arc::new(Profiler::new());
+        let ctx = Context {
+            profiler: Some(profiler.clone()),
+            ..Context::default()
+        };
+        let rule = Rule::from_value(&json!({"map": [
+            {"var": "xs"},
+            {"*": [{"var": ""}, 2]}
+        ]}))
+        .unwrap();
+        rule.apply_with(&json!({"xs": [1, 2, 3]}), &ctx).unwrap();
+        rule.apply_with(&json!({"xs": [4]}), &ctx).unwrap();
+
+        let calls = |operator: &str| {
+            profiler
+                .stats()
+                .into_iter()
+                .find(|stats| stats.operator == operator)
+                .map(|stats| stats.calls)
+        };
+        assert_eq!(calls("map"), Some(2));
+        assert_eq!(calls("*"), Some(4));
+        assert_eq!(calls("var"), Some(6));
+        // `map` encloses everything else, so it is the most expensive
+        let stats = profiler.stats();
+        assert_eq!(stats[0].operator, "map");
+        assert!(stats[0].total >= stats[1].total);
+
+        profiler.reset();
+        assert!(profiler.stats().is_empty());
+    }
+}
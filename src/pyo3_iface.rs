@@ -0,0 +1,283 @@
+//! Python Bindings (pyo3)
+//!
+//! With the `pyo3` feature, the crate builds a `_jsonlogic` extension
+//! module that works with Python objects directly, rather than the JSON
+//! strings passed to the `python` feature's module:
+//!
+//! ```python
+//! from _jsonlogic import Namespace, Rule, apply
+//!
+//! apply({"+": [{"var": "x"}, 1]}, {"x": 1})  # 2
+//!
+//! rule = Rule({">=": [{"var": "age"}, 18]}).compile()
+//! [rule.apply(person) for person in people]
+//!
+//! namespace = Namespace({"functions": [
+//!     {"defn": ["adult", ["age"], {">=": [{"param": "age"}, 18]}]}
+//! ]})
+//! namespace.parse({"adult": [{"var": "age"}]}).apply({"age": 20})  # True
+//! ```
+//!
+//! Values may be `None`, `bool`, `int`, `float`, `str`, lists, tuples, and
+//! dicts with string keys. Failures raise `JsonLogicError`, a `ValueError`
+//! whose arguments are the error's message and its payload as serialized by
+//! `Error::to_json()`. Rules are evaluated without holding the GIL.
+
+use pyo3::create_exception;
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyFloat, PyInt, PyList, PyString, PyTuple};
+use pyo3::IntoPyObjectExt;
+use serde_json::{Map, Number, Value};
+
+use crate::error::Error;
+use crate::namespace::Namespace;
+use crate::rule::{CompiledRule, Rule};
+
+create_exception!(
+    _jsonlogic,
+    JsonLogicError,
+    PyValueError,
+    "An error parsing or evaluating a rule"
+);
+
+/// Convert a Python object to JSON.
+fn to_value(obj: &Bound<'_, PyAny>) -> PyResult<Value> {
+    if obj.is_none() {
+        Ok(Value::Null)
+    } else if let Ok(b) = obj.downcast::<PyBool>() {
+        // bool is a subclass of int, so it must be checked first
+        Ok(Value::Bool(b.is_true()))
+    } else if obj.is_instance_of::<PyInt>() {
+        if let Ok(i) = obj.extract::<i64>() {
+            Ok(Value::from(i))
+        } else if let Ok(u) = obj.extract::<u64>() {
+            Ok(Value::from(u))
+        } else {
+            to_number(obj.extract::<f64>()?)
+        }
+    } else if let Ok(f) = obj.downcast::<PyFloat>() {
+        to_number(f.value())
+    } else if let Ok(s) = obj.downcast::<PyString>() {
+        Ok(Value::String(s.to_str()?.to_owned()))
+    } else if let Ok(list) = obj.downcast::<PyList>() {
+        list.iter().map(|item| to_value(&item)).collect()
+    } else if let Ok(tuple) = obj.downcast::<PyTuple>() {
+        tuple.iter().map(|item| to_value(&item)).collect()
+    } else if let Ok(dict) = obj.downcast::<PyDict>() {
+        let mut map = Map::with_capacity(dict.len());
+        for (key, value) in dict.iter() {
+            let key = key.downcast::<PyString>().map_err(|_| {
+                PyTypeError::new_err(format!("dict keys must be strings, not {}", key))
+            })?;
+            map.insert(key.to_str()?.to_owned(), to_value(&value)?);
+        }
+        Ok(Value::Object(map))
+    } else {
+        Err(PyTypeError::new_err(format!(
+            "cannot convert {} to JSON",
+            obj.get_type().name()?
+        )))
+    }
+}
+
+fn to_number(f: f64) -> PyResult<Value> {
+    Number::from_f64(f)
+        .map(Value::Number)
+        .ok_or_else(|| PyValueError::new_err(format!("cannot convert {} to JSON", f)))
+}
+
+/// Convert JSON to a Python object.
+fn to_py(py: Python<'_>, value: &Value) -> PyResult<PyObject> {
+    match value {
+        Value::Null => Ok(py.None()),
+        Value::Bool(b) => b.into_py_any(py),
+        Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => i.into_py_any(py),
+            (None, Some(u)) => u.into_py_any(py),
+            _ => n.as_f64().unwrap_or(f64::NAN).into_py_any(py),
+        },
+        Value::String(s) => s.into_py_any(py),
+        Value::Array(items) => {
+            let items = items
+                .iter()
+                .map(|item| to_py(py, item))
+                .collect::<PyResult<Vec<PyObject>>>()?;
+            PyList::new(py, items)?.into_py_any(py)
+        }
+        Value::Object(obj) => {
+            let dict = PyDict::new(py);
+            for (key, value) in obj {
+                dict.set_item(key, to_py(py, value)?)?;
+            }
+            dict.into_py_any(py)
+        }
+    }
+}
+
+/// Raise an error as a `JsonLogicError`.
+fn to_py_err(py: Python<'_>, err: Error) -> PyErr {
+    match to_py(py, &err.to_json()) {
+        Ok(payload) => JsonLogicError::new_err((err.to_string(), payload)),
+        Err(e) => e,
+    }
+}
+
+/// Convert optional data, where `None` is JSON `null`.
+fn to_data(data: Option<&Bound<'_, PyAny>>) -> PyResult<Value> {
+    data.map_or(Ok(Value::Null), to_value)
+}
+
+/// Run JSONLogic for the given rule and data.
+#[pyfunction]
+#[pyo3(signature = (rule, data=None))]
+fn apply(
+    py: Python<'_>,
+    rule: &Bound<'_, PyAny>,
+    data: Option<&Bound<'_, PyAny>>,
+) -> PyResult<PyObject> {
+    let rule = to_value(rule)?;
+    let data = to_data(data)?;
+    py.allow_threads(|| crate::apply(&rule, &data))
+        .map_err(|err| to_py_err(py, err))
+        .and_then(|result| to_py(py, &result))
+}
+
+/// A parsed rule, which may be applied to any number of pieces of data
+#[pyclass(name = "Rule", module = "_jsonlogic", frozen)]
+struct PyRule(Rule);
+
+#[pymethods]
+impl PyRule {
+    #[new]
+    fn new(py: Python<'_>, rule: &Bound<'_, PyAny>) -> PyResult<Self> {
+        Rule::from_value(&to_value(rule)?)
+            .map(Self)
+            .map_err(|err| to_py_err(py, err))
+    }
+
+    /// Evaluate the rule against the given data.
+    #[pyo3(signature = (data=None))]
+    fn apply(
+        &self,
+        py: Python<'_>,
+        data: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<PyObject> {
+        let data = to_data(data)?;
+        py.allow_threads(|| self.0.apply(&data))
+            .map_err(|err| to_py_err(py, err))
+            .and_then(|result| to_py(py, &result))
+    }
+
+    /// Compile the rule to bytecode, for rules that are applied many times.
+    fn compile(&self, py: Python<'_>) -> PyResult<PyCompiledRule> {
+        self.0
+            .compile()
+            .map(PyCompiledRule)
+            .map_err(|err| to_py_err(py, err))
+    }
+
+    /// The rule, as JSON
+    fn to_json(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_py(py, &Value::from(self.0.clone()))
+    }
+}
+
+/// A rule compiled with `Rule.compile()`
+#[pyclass(name = "CompiledRule", module = "_jsonlogic", frozen)]
+struct PyCompiledRule(CompiledRule);
+
+#[pymethods]
+impl PyCompiledRule {
+    /// Evaluate the rule against the given data.
+    #[pyo3(signature = (data=None))]
+    fn apply(
+        &self,
+        py: Python<'_>,
+        data: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<PyObject> {
+        let data = to_data(data)?;
+        py.allow_threads(|| self.0.apply(&data))
+            .map_err(|err| to_py_err(py, err))
+            .and_then(|result| to_py(py, &result))
+    }
+}
+
+/// A collection of functions, with which rules calling them may be parsed
+#[pyclass(name = "Namespace", module = "_jsonlogic")]
+struct PyNamespace(Namespace);
+
+#[pymethods]
+impl PyNamespace {
+    /// Load a module document, or create an empty namespace.
+    #[new]
+    #[pyo3(signature = (module=None))]
+    fn new(py: Python<'_>, module: Option<&Bound<'_, PyAny>>) -> PyResult<Self> {
+        match module {
+            Some(module) => Namespace::from_value(&to_value(module)?)
+                .map(Self)
+                .map_err(|err| to_py_err(py, err)),
+            None => Ok(Self(Namespace::new())),
+        }
+    }
+
+    /// Add a `defn` function definition.
+    fn define(
+        &mut self,
+        py: Python<'_>,
+        definition: &Bound<'_, PyAny>,
+    ) -> PyResult<()> {
+        self.0
+            .define(&to_value(definition)?)
+            .map_err(|err| to_py_err(py, err))
+    }
+
+    /// Make another namespace's functions available under a prefix.
+    fn mount(
+        &mut self,
+        py: Python<'_>,
+        prefix: &str,
+        namespace: &Self,
+    ) -> PyResult<()> {
+        self.0
+            .mount(prefix, namespace.0.clone())
+            .map_err(|err| to_py_err(py, err))
+    }
+
+    /// Validate the namespace's functions.
+    fn check(&self, py: Python<'_>) -> PyResult<()> {
+        self.0.check().map_err(|err| to_py_err(py, err))
+    }
+
+    /// Parse a rule that may call the namespace's functions.
+    fn parse(&self, py: Python<'_>, rule: &Bound<'_, PyAny>) -> PyResult<PyRule> {
+        self.0
+            .parse(&to_value(rule)?)
+            .map(PyRule)
+            .map_err(|err| to_py_err(py, err))
+    }
+
+    /// The names of the namespace's functions
+    fn names(&self) -> Vec<String> {
+        self.0
+            .functions()
+            .map(|func| func.name().value().to_owned())
+            .collect()
+    }
+
+    /// The namespace, as a module document
+    fn to_json(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_py(py, &self.0.to_value())
+    }
+}
+
+#[pymodule]
+fn _jsonlogic(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("__doc__", "Python bindings for json-logic-plus")?;
+    m.add("JsonLogicError", m.py().get_type::<JsonLogicError>())?;
+    m.add_function(wrap_pyfunction!(apply, m)?)?;
+    m.add_class::<PyRule>()?;
+    m.add_class::<PyCompiledRule>()?;
+    m.add_class::<PyNamespace>()?;
+    Ok(())
+}
@@ -0,0 +1,220 @@
+//! Randomness
+//!
+//! The `random`, `random_int`, and `uuid` operators draw from a
+//! `RandomSource` set on the evaluation's `Context`. Use `SeededRandom` for
+//! reproducible results, e.g. in tests. Without a source, they draw from the
+//! operating system's random number generator, which requires the `std`
+//! feature.
+//!
+//! Since their results differ between evaluations, these operators may not
+//! be used in rules parsed with `ParseOptions::deterministic`.
+
+use core::fmt;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use serde_json::Value;
+
+use crate::error::Error;
+use crate::prelude::*;
+
+/// A source of random numbers
+pub trait RandomSource: fmt::Debug + Send + Sync {
+    /// Return the next random 64-bit value.
+    fn next_u64(&self) -> Result<u64, Error>;
+}
+
+/// A generator that produces the same sequence for the same seed
+///
+/// This is not cryptographically secure, and is intended for tests and
+/// simulations whose results must be reproducible.
+#[derive(Debug)]
+pub struct SeededRandom {
+    state: AtomicU64,
+}
+impl SeededRandom {
+    /// Construct a generator from a seed.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: AtomicU64::new(seed),
+        }
+    }
+}
+impl RandomSource for SeededRandom {
+    /// SplitMix64, which needs only an atomic counter for its state
+    fn next_u64(&self) -> Result<u64, Error> {
+        const GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut z = self
+            .state
+            .fetch_add(GAMMA, Ordering::Relaxed)
+            .wrapping_add(GAMMA);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        Ok(z ^ (z >> 31))
+    }
+}
+
+/// The operating system's random number generator
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct OsRandom;
+#[cfg(feature = "std")]
+impl RandomSource for OsRandom {
+    fn next_u64(&self) -> Result<u64, Error> {
+        let mut bytes = [0; 8];
+        getrandom::getrandom(&mut bytes).map_err(|err| {
+            Error::UnexpectedError(format!(
+                "Could not generate a random number: {}",
+                err
+            ))
+        })?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+}
+
+/// A float in `[0, 1)`
+pub(crate) fn random(source: &dyn RandomSource) -> Result<Value, Error> {
+    // The top 53 bits fill an f64's mantissa exactly
+    let float = (source.next_u64()? >> 11) as f64 / (1u64 << 53) as f64;
+    Ok(Value::from(float))
+}
+
+/// An integer between `min` and `max`, inclusive
+pub(crate) fn random_int(
+    source: &dyn RandomSource,
+    min: &Value,
+    max: &Value,
+) -> Result<Value, Error> {
+    let int = |value: &Value| {
+        value.as_i64().ok_or_else(|| {
+            Error::invalid_argument(
+                value.clone(),
+                "random_int",
+                "Bounds must be integers",
+            )
+        })
+    };
+    let (low, high) = (int(min)?, int(max)?);
+    if low > high {
+        return Err(Error::invalid_argument(
+            max.clone(),
+            "random_int",
+            "The maximum must not be less than the minimum",
+        ));
+    }
+    // The number of possible values, less one, so that the full range of
+    // i64 doesn't overflow
+    let span = high.wrapping_sub(low) as u64;
+    let offset = if span == u64::MAX {
+        source.next_u64()?
+    } else {
+        // Reject values from the incomplete final block, which would bias
+        // the result towards low offsets
+        let count = span + 1;
+        let limit = u64::MAX - u64::MAX % count;
+        loop {
+            let candidate = source.next_u64()?;
+            if candidate < limit {
+                break candidate % count;
+            }
+        }
+    };
+    Ok(Value::from(low.wrapping_add(offset as i64)))
+}
+
+/// A random (version 4) UUID, as a hyphenated lowercase string
+pub(crate) fn uuid(source: &dyn RandomSource) -> Result<Value, Error> {
+    let mut bytes = [0u8; 16];
+    bytes[..8].copy_from_slice(&source.next_u64()?.to_be_bytes());
+    bytes[8..].copy_from_slice(&source.next_u64()?.to_be_bytes());
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let mut uuid = String::with_capacity(36);
+    for (idx, byte) in bytes.iter().enumerate() {
+        if matches!(idx, 4 | 6 | 8 | 10) {
+            uuid.push('-');
+        }
+        uuid.push_str(&format!("{:02x}", byte));
+    }
+    Ok(Value::String(uuid))
+}
+
+#[cfg(test)]
+mod test_random {
+    use super::*;
+    use alloc::sync::Arc;
+    use serde_json::json;
+
+    use crate::rule::ParseOptions;
+    use crate::{Context, Rule};
+
+    fn seeded(seed: u64) -> Context {
+        Context {
+            random: Some(Arc::new(SeededRandom::new(seed))),
+            ..Context::default()
+        }
+    }
+
+    #[test]
+    fn test_seeded() {
+        let rule = Rule::from_value(&json!({"merge": [
+            {"random": []},
+            {"random_int": [1, 6]},
+            {"uuid": []}
+        ]}))
+        .unwrap();
+        let first = rule.apply_with(&json!({}), &seeded(42)).unwrap();
+        assert_eq!(rule.apply_with(&json!({}), &seeded(42)).unwrap(), first);
+        assert_ne!(rule.apply_with(&json!({}), &seeded(43)).unwrap(), first);
+
+        let float = first[0].as_f64().unwrap();
+        assert!((0.0..1.0).contains(&float));
+        assert!((1..=6).contains(&first[1].as_i64().unwrap()));
+        let uuid = first[2].as_str().unwrap();
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(&uuid[14..15], "4");
+        assert!(matches!(&uuid[19..20], "8" | "9" | "a" | "b"));
+    }
+
+    #[test]
+    fn test_random_int() {
+        let source = SeededRandom::new(0);
+        let mut seen = [false; 3];
+        for _ in 0..100 {
+            let value = random_int(&source, &json!(-1), &json!(1)).unwrap();
+            seen[(value.as_i64().unwrap() + 1) as usize] = true;
+        }
+        assert_eq!(seen, [true; 3]);
+        assert_eq!(random_int(&source, &json!(5), &json!(5)).unwrap(), json!(5));
+        random_int(&source, &json!(i64::MIN), &json!(i64::MAX)).unwrap();
+        random_int(&source, &json!(2), &json!(1)).unwrap_err();
+        random_int(&source, &json!(1.5), &json!(2)).unwrap_err();
+    }
+
+    #[test]
+    fn test_unseeded() {
+        let rule = Rule::from_value(&json!({"uuid": []})).unwrap();
+        #[cfg(feature = "std")]
+        assert_ne!(
+            rule.apply(&json!({})).unwrap(),
+            rule.apply(&json!({})).unwrap()
+        );
+        #[cfg(not(feature = "std"))]
+        rule.apply(&json!({})).unwrap_err();
+    }
+
+    #[test]
+    fn test_nondeterministic() {
+        let options = ParseOptions {
+            deterministic: true,
+            ..ParseOptions::default()
+        };
+        for rule in [
+            json!({"random": []}),
+            json!({"random_int": [1, 2]}),
+            json!({"uuid": []}),
+        ] {
+            let err = Rule::from_value_with(&rule, &options).unwrap_err();
+            assert_eq!(err.code(), "nondeterministic_operator");
+        }
+    }
+}
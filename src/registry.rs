@@ -0,0 +1,164 @@
+//! Registry of Active Evaluations
+//!
+//! A registry keeps track of every evaluation running against it, so that
+//! long-running or runaway evaluations can be found and cancelled while they
+//! run. The registry only holds weak references to evaluations, so finished
+//! evaluations drop out of it on their own.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::time::{Duration, Instant};
+
+use crate::error::Error;
+
+/// A registry of the evaluations that are currently running
+#[derive(Debug, Default)]
+pub struct Registry {
+    next_id: AtomicU64,
+    evaluations: Mutex<HashMap<u64, Weak<Evaluation>>>,
+}
+impl Registry {
+    /// Construct an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The process-wide registry
+    pub fn global() -> Arc<Registry> {
+        static GLOBAL: OnceLock<Arc<Registry>> = OnceLock::new();
+        GLOBAL.get_or_init(|| Arc::new(Registry::new())).clone()
+    }
+
+    /// Record the start of an evaluation.
+    ///
+    /// The evaluation remains in the registry until the returned handle is
+    /// dropped.
+    pub(crate) fn register(&self, rule_id: Option<&str>) -> Arc<Evaluation> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let evaluation = Arc::new(Evaluation {
+            id,
+            rule_id: rule_id.map(String::from),
+            started: Instant::now(),
+            ops: AtomicU64::new(0),
+            cancelled: AtomicBool::new(false),
+        });
+        let mut evaluations = self.lock();
+        // Take the opportunity to drop any finished evaluations.
+        evaluations.retain(|_, evaluation| evaluation.strong_count() > 0);
+        evaluations.insert(id, Arc::downgrade(&evaluation));
+        evaluation
+    }
+
+    /// Return a snapshot of all currently running evaluations, ordered by id.
+    pub fn active(&self) -> Vec<EvaluationInfo> {
+        let mut active: Vec<EvaluationInfo> = self
+            .lock()
+            .values()
+            .filter_map(Weak::upgrade)
+            .map(|evaluation| evaluation.info())
+            .collect();
+        active.sort_by_key(|info| info.id);
+        active
+    }
+
+    /// Cancel a running evaluation.
+    ///
+    /// The evaluation will fail with a `Cancelled` error the next time it
+    /// attempts to execute an operation. Returns false if no evaluation with
+    /// the given id is running.
+    pub fn cancel(&self, id: u64) -> bool {
+        match self.lock().get(&id).and_then(Weak::upgrade) {
+            Some(evaluation) => {
+                evaluation.cancelled.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<u64, Weak<Evaluation>>> {
+        // A panic while holding the lock can't leave the map in an invalid
+        // state, so a poisoned lock is safe to use.
+        self.evaluations
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// A running evaluation
+#[derive(Debug)]
+pub(crate) struct Evaluation {
+    id: u64,
+    rule_id: Option<String>,
+    started: Instant,
+    ops: AtomicU64,
+    cancelled: AtomicBool,
+}
+impl Evaluation {
+    /// Record the execution of an operation, failing if cancelled.
+    pub(crate) fn tick(&self) -> Result<(), Error> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            return Err(Error::Cancelled(self.id));
+        }
+        self.ops.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn info(&self) -> EvaluationInfo {
+        EvaluationInfo {
+            id: self.id,
+            rule_id: self.rule_id.clone(),
+            elapsed: self.started.elapsed(),
+            ops: self.ops.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A snapshot of a running evaluation
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvaluationInfo {
+    /// The registry-assigned id of the evaluation
+    pub id: u64,
+    /// The id of the rule being evaluated, if it has one
+    pub rule_id: Option<String>,
+    /// How long the evaluation has been running
+    pub elapsed: Duration,
+    /// The number of operations executed so far
+    pub ops: u64,
+}
+
+#[cfg(test)]
+mod test_registry {
+    use super::*;
+
+    #[test]
+    fn test_finished_evaluations_are_dropped() {
+        let registry = Registry::new();
+        let first = registry.register(Some("first"));
+        let second = registry.register(None);
+        first.tick().unwrap();
+        first.tick().unwrap();
+
+        let active = registry.active();
+        assert_eq!(active.len(), 2);
+        assert_eq!(active[0].rule_id, Some("first".into()));
+        assert_eq!(active[0].ops, 2);
+        assert_eq!(active[1].rule_id, None);
+
+        drop(first);
+        assert_eq!(registry.active().len(), 1);
+        drop(second);
+        assert!(registry.active().is_empty());
+    }
+
+    #[test]
+    fn test_cancel() {
+        let registry = Registry::new();
+        let evaluation = registry.register(None);
+        evaluation.tick().unwrap();
+        assert!(registry.cancel(evaluation.id));
+        evaluation.tick().unwrap_err();
+        assert!(!registry.cancel(evaluation.id + 1));
+    }
+}
@@ -0,0 +1,285 @@
+//! Data Resolvers
+//!
+//! `var`, `missing`, and `missing_some` look up data through a
+//! `DataResolver`. Usually the data is a `Value`, but rules may also be
+//! evaluated against a resolver of your own via `Rule::apply_resolver()`, so
+//! that data can be looked up lazily (e.g. from a database row or a config
+//! store) rather than being assembled into a `Value` up front.
+
+use alloc::borrow::Cow;
+use core::convert::TryInto;
+use core::fmt;
+
+use serde_json::{Number, Value};
+
+use crate::op::data::PathSegment;
+use crate::prelude::*;
+
+/// A source of data for a rule
+pub trait DataResolver {
+    /// Look up the value at a path, or return None if it isn't present.
+    ///
+    /// An empty path refers to the data as a whole.
+    fn resolve(&self, path: &[PathSegment]) -> Option<Cow<'_, Value>>;
+}
+
+/// The kind of a JSON-like value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValueKind {
+    Null,
+    Bool,
+    Number,
+    String,
+    Array,
+    Object,
+}
+impl ValueKind {
+    /// The kind's name, as used for JSON Schema types (e.g. `"boolean"`)
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Null => "null",
+            Self::Bool => "boolean",
+            Self::Number => "number",
+            Self::String => "string",
+            Self::Array => "array",
+            Self::Object => "object",
+        }
+    }
+
+    /// The kind with the given name, if there is one
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            Self::Null,
+            Self::Bool,
+            Self::Number,
+            Self::String,
+            Self::Array,
+            Self::Object,
+        ]
+        .iter()
+        .copied()
+        .find(|kind| kind.name() == name)
+    }
+}
+impl fmt::Display for ValueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A JSON-like document
+///
+/// Every `JsonLike` type is a `DataResolver`, so documents in other formats
+/// (e.g. YAML, with the `serde_yaml` feature) may be passed to
+/// `Rule::apply_resolver()` without first being converted into a `Value`.
+/// Only the values that a rule looks up are converted.
+pub trait JsonLike: Sized {
+    /// The kind of the value
+    fn kind(&self) -> ValueKind;
+
+    /// The value as a boolean, if it is one
+    fn as_bool(&self) -> Option<bool>;
+
+    /// The value as a number, if it is one
+    fn as_number(&self) -> Option<Number>;
+
+    /// The value as a string, if it is one
+    fn as_str(&self) -> Option<&str>;
+
+    /// The elements of the value, if it is an array
+    fn as_array(&self) -> Option<&[Self]>;
+
+    /// The value of the given key, if this is an object containing it
+    fn get(&self, key: &str) -> Option<&Self>;
+
+    /// The keys and values of the value, if it is an object
+    fn entries(&self) -> Box<dyn Iterator<Item = (Cow<'_, str>, &Self)> + '_>;
+
+    /// The value as a `Value`
+    fn to_json(&self) -> Cow<'_, Value> {
+        Cow::Owned(match self.kind() {
+            ValueKind::Null => Value::Null,
+            ValueKind::Bool => self.as_bool().map_or(Value::Null, Value::Bool),
+            ValueKind::Number => self.as_number().map_or(Value::Null, Value::Number),
+            ValueKind::String => self
+                .as_str()
+                .map_or(Value::Null, |s| Value::String(s.to_string())),
+            ValueKind::Array => Value::Array(
+                self.as_array()
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|value| value.to_json().into_owned())
+                    .collect(),
+            ),
+            ValueKind::Object => Value::Object(
+                self.entries()
+                    .map(|(key, value)| {
+                        (key.into_owned(), value.to_json().into_owned())
+                    })
+                    .collect(),
+            ),
+        })
+    }
+}
+
+impl<T: JsonLike> DataResolver for T {
+    fn resolve(&self, path: &[PathSegment]) -> Option<Cow<'_, Value>> {
+        let mut current = self;
+        for (i, seg) in path.iter().enumerate() {
+            current = match current.kind() {
+                // If the current value is an object, try to get the value
+                ValueKind::Object => current.get(seg.key())?,
+                // If the current value is an array, we need an integer
+                // index. If the segment is not an integer, return None.
+                ValueKind::Array => get(current.as_array()?, seg.index()?)?,
+                // Same deal if it's a string, whose characters are then
+                // indexed as strings themselves.
+                ValueKind::String => {
+                    let chars: Vec<char> = current.as_str()?.chars().collect();
+                    let c = Value::String(get(&chars, seg.index()?)?.to_string());
+                    return c
+                        .resolve(&path[i + 1..])
+                        .map(|value| Cow::Owned(value.into_owned()));
+                }
+                // This handles cases where we've got an un-indexable
+                // type or similar.
+                _ => return None,
+            };
+        }
+        Some(current.to_json())
+    }
+}
+
+impl JsonLike for Value {
+    fn kind(&self) -> ValueKind {
+        match self {
+            Value::Null => ValueKind::Null,
+            Value::Bool(_) => ValueKind::Bool,
+            Value::Number(_) => ValueKind::Number,
+            Value::String(_) => ValueKind::String,
+            Value::Array(_) => ValueKind::Array,
+            Value::Object(_) => ValueKind::Object,
+        }
+    }
+
+    fn as_bool(&self) -> Option<bool> {
+        self.as_bool()
+    }
+
+    fn as_number(&self) -> Option<Number> {
+        match self {
+            Value::Number(n) => Some(n.clone()),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        self.as_str()
+    }
+
+    fn as_array(&self) -> Option<&[Self]> {
+        self.as_array().map(Vec::as_slice)
+    }
+
+    fn get(&self, key: &str) -> Option<&Self> {
+        self.as_object().and_then(|map| map.get(key))
+    }
+
+    fn entries(&self) -> Box<dyn Iterator<Item = (Cow<'_, str>, &Self)> + '_> {
+        match self {
+            Value::Object(map) => Box::new(
+                map.iter()
+                    .map(|(key, value)| (Cow::Borrowed(key.as_str()), value)),
+            ),
+            _ => Box::new(core::iter::empty()),
+        }
+    }
+
+    fn to_json(&self) -> Cow<'_, Value> {
+        Cow::Borrowed(self)
+    }
+}
+
+/// A get operation that supports negative indexes
+pub(crate) fn get<T>(slice: &[T], idx: i64) -> Option<&T> {
+    let vec_len = slice.len();
+    let usize_idx: usize = idx.abs().try_into().ok()?;
+
+    let adjusted_idx = if idx >= 0 {
+        usize_idx
+    } else {
+        vec_len.checked_sub(usize_idx)?
+    };
+
+    slice.get(adjusted_idx)
+}
+
+#[cfg(test)]
+mod test_resolver {
+    use super::*;
+    use crate::collections::HashMap;
+    use serde_json::json;
+
+    use crate::Rule;
+
+    /// A flat table of columns, as might be read from a database row
+    struct Row(HashMap<&'static str, Value>);
+    impl DataResolver for Row {
+        fn resolve(&self, path: &[PathSegment]) -> Option<Cow<'_, Value>> {
+            match path {
+                [column] => self.0.get(column.key()).map(Cow::Borrowed),
+                [column, rest @ ..] => self
+                    .0
+                    .get(column.key())
+                    .and_then(|value| value.resolve(rest))
+                    .map(|value| Cow::Owned(value.into_owned())),
+                [] => None,
+            }
+        }
+    }
+
+    #[test]
+    fn test_value_resolver() {
+        let data = json!({"a": [{"b": "xyz"}]});
+        let path = |p: &str| p.split('.').map(PathSegment::new).collect::<Vec<_>>();
+        assert_eq!(data.resolve(&[]).unwrap().as_ref(), &data);
+        assert_eq!(
+            data.resolve(&path("a.0.b")).unwrap().as_ref(),
+            &json!("xyz")
+        );
+        assert_eq!(
+            data.resolve(&path("a.-1.b.1")).unwrap().as_ref(),
+            &json!("y")
+        );
+        assert_eq!(data.resolve(&path("a.1")), None);
+        assert_eq!(data.resolve(&path("a.b")), None);
+    }
+
+    #[test]
+    fn test_custom_resolver() {
+        let mut columns = HashMap::new();
+        columns.insert("age", json!(30));
+        columns.insert("address", json!({"country": "CA"}));
+        columns.insert("tags", json!([{"name": "x"}, {"name": "y"}]));
+        let row = Row(columns);
+
+        let rule = Rule::from_value(&json!({"and": [
+            {">=": [{"var": "age"}, 18]},
+            {"==": [{"var": "address.country"}, "CA"]},
+            {"==": [{"var": "name"}, null]},
+            {"missing": ["age", "email"]},
+            // Within iteration, `var` refers to the current element
+            {"==": [{"map": [{"var": "tags"}, {"var": "name"}]}, "x,y"]},
+            {"some": [[1], {"==": [{"var": "age"}, null]}]}
+        ]}))
+        .unwrap();
+        assert_eq!(rule.apply_resolver(&row).unwrap(), json!(true));
+        assert_eq!(
+            Rule::from_value(&json!({"missing_some": [1, ["email", "phone"]]}))
+                .unwrap()
+                .apply_resolver(&row)
+                .unwrap(),
+            json!(["email", "phone"])
+        );
+    }
+}
@@ -0,0 +1,768 @@
+//! Rules
+//!
+//! Besides `Rule` itself, this module provides a builder for constructing
+//! rules in Rust, e.g. `rule::var("age").gte(18)`.
+
+use alloc::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::arena::{Arena, FrozenArena};
+use crate::ast::Ast;
+#[cfg(feature = "async")]
+use crate::asynchronous::AsyncState;
+#[cfg(feature = "std")]
+use crate::audit::Started;
+use crate::collections::HashMap;
+use crate::context::{Context, Env, SemanticsVersion};
+use crate::error::Error;
+use crate::namespace::Scope;
+use crate::op;
+use crate::op::func::FunctionBody;
+use crate::prelude::*;
+use crate::resolver::DataResolver;
+use crate::value::Parsed;
+use crate::NULL;
+
+mod builder;
+mod canonical;
+mod compiled;
+#[cfg(feature = "std")]
+pub(crate) mod coverage;
+mod diff;
+mod format;
+mod javascript;
+mod mutate;
+mod schema;
+pub(crate) mod testing;
+pub use builder::{if_else, op, val, var, var_or, Expr};
+pub use compiled::CompiledRule;
+#[cfg(feature = "std")]
+pub use coverage::{BranchCoverage, CoverageReport};
+pub use diff::Change;
+pub use format::FormatStyle;
+pub use mutate::{Mutant, MutantResult, Mutation, MutationReport};
+pub use testing::{Expectation, TestCase, TestResult};
+
+/// Options used when parsing a rule
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// How to parse objects with more than one key, which can't be
+    /// operations. Defaults to `MultiKeyObjects::Literal`.
+    pub multi_key_objects: MultiKeyObjects,
+    /// The names functions and their parameters may have, checked when
+    /// functions are defined with `Namespace::define()`
+    pub identifiers: IdentifierPolicy,
+    /// Whether to reject operators whose results may differ between
+    /// evaluations of the same rule against the same data, e.g. `lookup`,
+    /// so that rules are pure functions of their data. Defaults to `false`.
+    pub deterministic: bool,
+}
+
+/// The names that functions and their parameters may have
+///
+/// Identifiers are always non-empty and free of whitespace. By default, any
+/// such name is allowed, except that functions may not be named for
+/// built-in operators, which they would otherwise shadow.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IdentifierPolicy {
+    /// The fewest characters an identifier may have, counted as they're
+    /// perceived (extended grapheme clusters), so that `é` is one character
+    /// whether or not it's written with a combining accent
+    pub min_length: usize,
+    /// The characters identifiers may contain. Each perceived character is
+    /// checked by its first unicode scalar value, so that letters keep any
+    /// combining marks that follow them
+    pub characters: IdentifierCharacters,
+    /// Names that may not be used, e.g. words reserved for future operators
+    pub reserved: Vec<String>,
+    /// Whether functions may be named for built-in operators
+    pub allow_builtin_names: bool,
+}
+impl ParseOptions {
+    /// The default options, which may be used in constants
+    pub(crate) const DEFAULT: Self = Self {
+        multi_key_objects: MultiKeyObjects::Literal,
+        identifiers: IdentifierPolicy::DEFAULT,
+        deterministic: false,
+    };
+}
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl Default for IdentifierPolicy {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+impl IdentifierPolicy {
+    /// The default policy, which may be used in constants
+    const DEFAULT: Self = Self {
+        min_length: 1,
+        characters: IdentifierCharacters::Any,
+        reserved: Vec::new(),
+        allow_builtin_names: false,
+    };
+
+    /// Check the name of a function against the policy.
+    pub fn check_function_name(&self, name: &str) -> Result<(), Error> {
+        if !self.allow_builtin_names && op::is_builtin(name) {
+            return Err(Error::DisallowedIdentifier {
+                identifier: name.into(),
+                reason: "it is the name of a built-in operator".into(),
+            });
+        }
+        self.check(name)
+    }
+
+    /// Check a name against the policy.
+    pub fn check(&self, name: &str) -> Result<(), Error> {
+        let disallowed = |reason: String| {
+            Err(Error::DisallowedIdentifier {
+                identifier: name.into(),
+                reason,
+            })
+        };
+        if name.graphemes(true).count() < self.min_length {
+            return disallowed(format!(
+                "identifiers must have at least {} characters",
+                self.min_length
+            ));
+        }
+        let allowed = |grapheme: &str| {
+            let mut chars = grapheme.chars();
+            chars.next().is_some_and(|c| self.characters.allows(c))
+                && chars.all(|c| !c.is_whitespace())
+        };
+        if let Some(grapheme) = name.graphemes(true).find(|g| !allowed(g)) {
+            return disallowed(format!("'{}' is not allowed in identifiers", grapheme));
+        }
+        if self.reserved.iter().any(|word| word == name) {
+            return disallowed("it is a reserved word".into());
+        }
+        Ok(())
+    }
+}
+
+/// The characters identifiers may contain, besides which whitespace is
+/// never allowed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum IdentifierCharacters {
+    /// Any character other than whitespace
+    #[default]
+    Any,
+    /// Letters, digits, underscores, and the dots separating the names of
+    /// namespaces
+    Word,
+}
+impl IdentifierCharacters {
+    /// Whether identifiers may contain the character
+    pub fn allows(&self, c: char) -> bool {
+        match self {
+            Self::Any => !c.is_whitespace(),
+            Self::Word => c.is_alphanumeric() || c == '_' || c == '.',
+        }
+    }
+}
+
+/// How to parse objects with more than one key
+///
+/// The reference implementation treats them as literals, but other engines
+/// differ, so rules ported from them may expect one of the other behaviors.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MultiKeyObjects {
+    /// Treat the object as a literal value, whose values aren't evaluated
+    #[default]
+    Literal,
+    /// Fail to parse the rule
+    Error,
+    /// Evaluate each of the object's values, producing an object with the
+    /// same keys
+    Evaluate,
+}
+
+/// A parsed JsonLogic rule
+///
+/// Parsing validates the rule and does as much work as possible up front
+/// (e.g. splitting `var` paths), so a rule that will be applied to many
+/// pieces of data should be parsed once and reused.
+#[derive(Clone, Debug)]
+pub struct Rule {
+    id: Option<String>,
+    semantics: Option<SemanticsVersion>,
+    parsed: Arc<Parsed>,
+    functions: Option<Arc<HashMap<String, FunctionBody>>>,
+    tests: Arc<[TestCase]>,
+    /// The arena the rule was parsed into, dropped after everything in it
+    _arena: Option<Arc<FrozenArena>>,
+}
+impl Rule {
+    /// Parse a rule from a JSON value.
+    ///
+    /// A rule may be wrapped with the semantics version it was written for,
+    /// as in `{"$version": "1", "rule": {...}}`, in which case it's always
+    /// evaluated with that version, whatever the context's `semantics`.
+    ///
+    /// To parse a rule that calls user-defined functions, use
+    /// `Namespace::parse()`.
+    pub fn from_value(value: &Value) -> Result<Self, Error> {
+        Self::from_value_with(value, &ParseOptions::default())
+    }
+
+    /// Parse a rule from a JSON value with the given options.
+    pub fn from_value_with(
+        value: &Value,
+        options: &ParseOptions,
+    ) -> Result<Self, Error> {
+        #[cfg(feature = "tracing")]
+        let _span = crate::trace::parse();
+        let (semantics, value) = unwrap_version(value)?;
+        let arena = Arena::default();
+        let scope = Scope::default().with_options(options).with_arena(&arena);
+        let parsed = Parsed::from_value(value, &scope)?;
+        Ok(Self {
+            id: None,
+            semantics,
+            parsed: Arc::new(parsed),
+            functions: None,
+            tests: Vec::new().into(),
+            _arena: Some(arena.freeze()),
+        })
+    }
+
+    /// Parse a rule from JSON5 text, which may contain comments, trailing
+    /// commas, unquoted keys, and so on. Since JSON5 is a superset of JSON
+    /// with comments (JSONC), this accepts JSONC rules too.
+    ///
+    /// Only the rule is parsed leniently: data is still parsed as usual.
+    #[cfg(feature = "json5")]
+    pub fn parse_str(text: &str) -> Result<Self, Error> {
+        let value: Value = json5::from_str(text).map_err(|err| match err {
+            json5::Error::Message { msg, location } => Error::InvalidSyntax {
+                reason: msg,
+                line: location.as_ref().map(|loc| loc.line),
+                column: location.as_ref().map(|loc| loc.column),
+            },
+        })?;
+        Self::from_value(&value)
+    }
+
+    /// Construct a rule that may call the given compiled functions, which
+    /// were parsed into the given arena along with it.
+    pub(crate) fn with_functions(
+        parsed: Parsed,
+        functions: Arc<HashMap<String, FunctionBody>>,
+        arena: Arc<FrozenArena>,
+    ) -> Self {
+        Self {
+            id: None,
+            semantics: None,
+            parsed: Arc::new(parsed),
+            functions: Some(functions),
+            tests: Vec::new().into(),
+            _arena: Some(arena),
+        }
+    }
+
+    /// Give the rule an id, used to identify it in e.g. a `Registry`.
+    pub fn with_id<S: Into<String>>(mut self, id: S) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// The rule's id, if it has one
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// Pin the semantics version the rule is evaluated with, overriding the
+    /// context's.
+    pub fn with_semantics(mut self, semantics: SemanticsVersion) -> Self {
+        self.semantics = Some(semantics);
+        self
+    }
+
+    /// The semantics version the rule is pinned to, if any
+    pub fn semantics(&self) -> Option<SemanticsVersion> {
+        self.semantics
+    }
+
+    /// The rule's typed syntax tree
+    pub fn ast(&self) -> Ast {
+        Ast::from(&*self.parsed)
+    }
+
+    /// Evaluate the rule against the given data.
+    pub fn apply(&self, data: &Value) -> Result<Value, Error> {
+        self.apply_with(data, &Context::default())
+    }
+
+    /// Evaluate the rule against the given data, with the given context.
+    pub fn apply_with(&self, data: &Value, ctx: &Context) -> Result<Value, Error> {
+        #[cfg(feature = "std")]
+        if let Some(audit) = &ctx.audit {
+            let started = Started::now();
+            let result = self.evaluate(data, ctx);
+            audit.record(started, self.id(), self.content_hash(), data, &result);
+            return result;
+        }
+        self.evaluate(data, ctx)
+    }
+
+    fn evaluate(&self, data: &Value, ctx: &Context) -> Result<Value, Error> {
+        #[cfg(feature = "tracing")]
+        let _span = crate::trace::apply(self.id());
+        let env = Env::new(ctx, self.id())
+            .with_semantics(self.semantics)
+            .with_functions(self.functions.clone());
+        self.parsed.evaluate(data, &env).map(Value::from)
+    }
+
+    /// Evaluate a rule that may use async operators.
+    #[cfg(feature = "async")]
+    pub async fn apply_async(&self, data: &Value) -> Result<Value, Error> {
+        self.apply_async_with(data, &Context::default()).await
+    }
+
+    /// Evaluate a rule that may use async operators, with the given context.
+    ///
+    /// The rule is evaluated again from the start each time an async
+    /// operator's result becomes available, replaying the results of the
+    /// async calls made so far, so it's evaluated once more per distinct
+    /// async call. Any custom operators it uses should be pure, and the
+    /// arguments of its async calls mustn't depend on impure operators like
+    /// `random`, or the evaluation fails.
+    #[cfg(feature = "async")]
+    pub async fn apply_async_with(
+        &self,
+        data: &Value,
+        ctx: &Context,
+    ) -> Result<Value, Error> {
+        if let Some(audit) = &ctx.audit {
+            let started = Started::now();
+            let result = self.evaluate_async(data, ctx).await;
+            audit.record(started, self.id(), self.content_hash(), data, &result);
+            return result;
+        }
+        self.evaluate_async(data, ctx).await
+    }
+
+    #[cfg(feature = "async")]
+    async fn evaluate_async(
+        &self,
+        data: &Value,
+        ctx: &Context,
+    ) -> Result<Value, Error> {
+        let state = AsyncState::default();
+        loop {
+            let result = {
+                #[cfg(feature = "tracing")]
+                let _span = crate::trace::apply(self.id());
+                let env = Env::new(ctx, self.id())
+                    .with_semantics(self.semantics)
+                    .with_functions(self.functions.clone())
+                    .with_async(&state);
+                self.parsed.evaluate(data, &env).map(Value::from)
+            };
+            if !state.resolve_pending().await? {
+                return result;
+            }
+        }
+    }
+
+    /// Evaluate the rule, looking up data through the given resolver.
+    pub fn apply_resolver(&self, resolver: &dyn DataResolver) -> Result<Value, Error> {
+        self.apply_resolver_with(resolver, &Context::default())
+    }
+
+    /// Evaluate the rule, looking up data through the given resolver, with
+    /// the given context.
+    ///
+    /// Within the sub-rules of iterating operators like `map`, data is
+    /// looked up in the current element as usual, rather than through the
+    /// resolver.
+    pub fn apply_resolver_with(
+        &self,
+        resolver: &dyn DataResolver,
+        ctx: &Context,
+    ) -> Result<Value, Error> {
+        #[cfg(feature = "tracing")]
+        let _span = crate::trace::apply(self.id());
+        let env = Env::new(ctx, self.id())
+            .with_semantics(self.semantics)
+            .with_functions(self.functions.clone())
+            .with_resolver(resolver);
+        self.parsed.evaluate(&NULL, &env).map(Value::from)
+    }
+
+    /// Evaluate the rule against the given data, and deserialize the result.
+    ///
+    /// Evaluation failures are returned as usual. If evaluation succeeds but
+    /// the result can't be deserialized into `T`, a `DeserializeResult` error
+    /// containing the result is returned.
+    pub fn evaluate_as<T: DeserializeOwned>(&self, data: &Value) -> Result<T, Error> {
+        let result = self.apply(data)?;
+        T::deserialize(&result).map_err(move |err| Error::DeserializeResult {
+            value: result,
+            reason: err.to_string(),
+        })
+    }
+
+    /// Evaluate the rule against any serializable data.
+    ///
+    /// The data is serialized into a `Value` once, before evaluation, so
+    /// callers with their own types don't need to convert them first.
+    pub fn apply_serialize<T: Serialize + ?Sized>(
+        &self,
+        data: &T,
+    ) -> Result<Value, Error> {
+        serde_json::to_value(data)
+            .map_err(|err| Error::SerializeData(err.to_string()))
+            .and_then(|data| self.apply(&data))
+    }
+}
+impl From<Rule> for Value {
+    fn from(rule: Rule) -> Self {
+        let value = Value::from(Parsed::clone(&rule.parsed));
+        match rule.semantics {
+            Some(semantics) => serde_json::json!({
+                VERSION: semantics.to_string(),
+                VERSIONED_RULE: value,
+            }),
+            None => value,
+        }
+    }
+}
+
+/// The key of the semantics version of a versioned rule
+const VERSION: &str = "$version";
+/// The key of the rule itself in a versioned rule
+const VERSIONED_RULE: &str = "rule";
+
+/// Split a rule into the semantics version it's pinned to, if it's wrapped
+/// as a versioned rule, and the rule itself.
+pub(crate) fn unwrap_version(
+    value: &Value,
+) -> Result<(Option<SemanticsVersion>, &Value), Error> {
+    let obj = match value {
+        Value::Object(obj) if obj.len() == 2 => obj,
+        _ => return Ok((None, value)),
+    };
+    let (version, rule) = match (obj.get(VERSION), obj.get(VERSIONED_RULE)) {
+        (Some(version), Some(rule)) => (version, rule),
+        _ => return Ok((None, value)),
+    };
+    let semantics = match version {
+        Value::String(version) => version.parse()?,
+        Value::Number(version) => version.to_string().parse()?,
+        _ => {
+            return Err(Error::InvalidData {
+                value: version.clone(),
+                reason: format!("Expected a semantics version at '{}'", VERSION),
+            })
+        }
+    };
+    Ok((Some(semantics), rule))
+}
+
+#[cfg(test)]
+mod test_rule {
+    use super::*;
+    #[cfg(feature = "std")]
+    use crate::registry::Registry;
+    use crate::Namespace;
+    #[cfg(feature = "std")]
+    use alloc::sync::Arc;
+    use serde_json::json;
+
+    #[test]
+    fn test_reuse() {
+        let rule = Rule::from_value(&json!({"map": [
+            {"var": "items"},
+            {"*": [{"var": "price"}, {"var": "qty"}]}
+        ]}))
+        .unwrap();
+        assert_eq!(
+            rule.apply(&json!({"items": [{"price": 2, "qty": 3}]}))
+                .unwrap(),
+            json!([6])
+        );
+        assert_eq!(
+            rule.apply(
+                &json!({"items": [{"price": 1, "qty": 1}, {"price": 5, "qty": 2}]})
+            )
+            .unwrap(),
+            json!([1, 10])
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_registry() {
+        let registry = Arc::new(Registry::new());
+        let ctx = Context {
+            registry: Some(registry.clone()),
+            ..Context::default()
+        };
+        let rule = Rule::from_value(&json!({"+": [1, {"var": "a"}]}))
+            .unwrap()
+            .with_id("plus");
+        assert_eq!(rule.apply_with(&json!({"a": 1}), &ctx).unwrap(), json!(2));
+        // The evaluation is dropped from the registry once complete
+        assert!(registry.active().is_empty());
+    }
+
+    #[test]
+    fn test_apply_serialize() {
+        #[derive(Serialize)]
+        struct Item<'a> {
+            name: &'a str,
+            price: f64,
+        }
+        #[derive(Serialize)]
+        struct Order<'a> {
+            items: Vec<Item<'a>>,
+            country: Option<&'a str>,
+        }
+
+        let rule = Rule::from_value(&json!({"and": [
+            {"==": [{"var": "country"}, null]},
+            {"reduce": [
+                {"var": "items"},
+                {"+": [{"var": "accumulator"}, {"var": "current.price"}]},
+                0
+            ]}
+        ]}))
+        .unwrap();
+        let order = Order {
+            items: vec![
+                Item {
+                    name: "widget",
+                    price: 1.5,
+                },
+                Item {
+                    name: "gadget",
+                    price: 2.0,
+                },
+            ],
+            country: None,
+        };
+        assert_eq!(rule.apply_serialize(&order).unwrap(), json!(3.5));
+
+        // Maps with non-string keys can't be serialized into a `Value`
+        let mut data = alloc::collections::BTreeMap::new();
+        data.insert(vec![1], 1);
+        match rule.apply_serialize(&data) {
+            Err(Error::SerializeData(_)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_evaluate_as() {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Totals {
+            subtotal: f64,
+            discounted: bool,
+        }
+
+        let rule = Rule::from_value(&json!({"map": [
+            {"var": "orders"},
+            {"merge": [
+                {"*": [{"var": "price"}, {"var": "qty"}]},
+                {">": [{"var": "qty"}, 10]}
+            ]}
+        ]}))
+        .unwrap();
+        let data = json!({"orders": [{"price": 2, "qty": 3}, {"price": 1, "qty": 20}]});
+        assert_eq!(
+            rule.evaluate_as::<Vec<(f64, bool)>>(&data).unwrap(),
+            vec![(6.0, false), (20.0, true)]
+        );
+
+        let rule = Rule::from_value(&json!({"var": "totals"})).unwrap();
+        assert_eq!(
+            rule.evaluate_as::<Totals>(
+                &json!({"totals": {"subtotal": 1.5, "discounted": false}})
+            )
+            .unwrap(),
+            Totals {
+                subtotal: 1.5,
+                discounted: false
+            }
+        );
+        match rule.evaluate_as::<Totals>(&json!({"totals": {"subtotal": 1.5}})) {
+            Err(Error::DeserializeResult { value, .. }) => {
+                assert_eq!(value, json!({"subtotal": 1.5}))
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // Evaluation errors are reported as such
+        match Rule::from_value(&json!({"/": [1, 0]}))
+            .unwrap()
+            .evaluate_as::<f64>(&json!(null))
+        {
+            Err(Error::DeserializeResult { .. }) => panic!("expected evaluation error"),
+            Err(_) => {}
+            Ok(res) => panic!("unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let value = json!({"if": [{"var": ["a.b", 1]}, {"missing": ["c"]}, "d"]});
+        assert_eq!(Value::from(Rule::from_value(&value).unwrap()), value);
+    }
+
+    #[test]
+    fn test_versioned_rules() {
+        let value = json!({"$version": "1", "rule": {"+": [{"var": "x"}, 1]}});
+        let rule = Rule::from_value(&value).unwrap();
+        assert_eq!(rule.semantics(), Some(SemanticsVersion::V1));
+        assert_eq!(rule.apply(&json!({"x": 1})).unwrap(), json!(2));
+        assert_eq!(
+            rule.compile().unwrap().apply(&json!({"x": 1})).unwrap(),
+            json!(2)
+        );
+        assert_eq!(
+            Value::from(rule.clone()),
+            json!({"$version": "1", "rule": {"+": [{"var": ["x"]}, 1]}})
+        );
+
+        let unpinned = Rule::from_value(&value["rule"]).unwrap();
+        assert_eq!(unpinned.semantics(), None);
+        assert!(!rule.semantically_equal(&unpinned));
+        assert_ne!(rule.content_hash(), unpinned.content_hash());
+        assert!(rule.semantically_equal(&unpinned.with_semantics(SemanticsVersion::V1)));
+
+        // Versions may be numbers, but must be known
+        let rule = Rule::from_value(&json!({"$version": 1, "rule": true})).unwrap();
+        assert_eq!(rule.semantics(), Some(SemanticsVersion::V1));
+        let err =
+            Rule::from_value(&json!({"$version": "99", "rule": true})).unwrap_err();
+        assert_eq!(err.code(), "unknown_semantics_version");
+        Rule::from_value(&json!({"$version": [], "rule": true})).unwrap_err();
+
+        // Anything else with those keys is an ordinary object
+        let value = json!({"$version": "1", "rule": 1, "other": 2});
+        let rule = Rule::from_value(&value).unwrap();
+        assert_eq!(rule.semantics(), None);
+        assert_eq!(rule.apply(&json!({})).unwrap(), value);
+
+        let rule = Namespace::new()
+            .parse(&json!({"$version": "1", "rule": {"var": "x"}}))
+            .unwrap();
+        assert_eq!(rule.semantics(), Some(SemanticsVersion::V1));
+    }
+
+    #[test]
+    fn test_multi_key_objects() {
+        let value = json!({"a": {"var": ["x"]}, "b": {"c": {"+": [1, 2]}, "d": [{"var": "x"}]}});
+        let parse = |multi_key_objects| {
+            Rule::from_value_with(
+                &value,
+                &ParseOptions {
+                    multi_key_objects,
+                    ..ParseOptions::default()
+                },
+            )
+        };
+        let data = json!({"x": 1});
+
+        let rule = parse(MultiKeyObjects::Literal).unwrap();
+        assert_eq!(rule.apply(&data).unwrap(), value);
+
+        match parse(MultiKeyObjects::Error) {
+            Err(Error::InvalidOperation { key, .. }) => assert_eq!(key, "a, b"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let rule = parse(MultiKeyObjects::Evaluate).unwrap();
+        let expected = json!({"a": 1, "b": {"c": 3, "d": [{"var": "x"}]}});
+        assert_eq!(rule.apply(&data).unwrap(), expected);
+        assert_eq!(rule.compile().unwrap().apply(&data).unwrap(), expected);
+        assert_eq!(Value::from(rule.ast()), value);
+        // Empty objects are still literals
+        let options = ParseOptions {
+            multi_key_objects: MultiKeyObjects::Error,
+            ..ParseOptions::default()
+        };
+        let rule = Rule::from_value_with(&json!({"if": [true, {}]}), &options).unwrap();
+        assert_eq!(rule.apply(&data).unwrap(), json!({}));
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let options = ParseOptions {
+            deterministic: true,
+            ..ParseOptions::default()
+        };
+        let rule = json!({"if": [{"var": "x"}, {"lookup": ["rates", "usd"]}, 1]});
+        Rule::from_value(&rule).unwrap();
+        match Rule::from_value_with(&rule, &options) {
+            Err(err) => {
+                assert_eq!(err.code(), "nondeterministic_operator");
+                assert_eq!(err.to_json()["details"], json!({"operator": "lookup"}));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        let rule = Rule::from_value_with(&json!({"+": [{"var": "x"}, 1]}), &options);
+        assert_eq!(rule.unwrap().apply(&json!({"x": 1})).unwrap(), json!(2));
+    }
+
+    #[test]
+    fn test_literals() {
+        let value = json!({"merge": [{"lit": {"var": "x"}}, {"lit": [1, 2]}, {"lit": {"lit": 1}}]});
+        let rule = Rule::from_value(&value).unwrap();
+        assert_eq!(
+            rule.apply(&json!({"x": 0})).unwrap(),
+            json!([{"var": "x"}, 1, 2, {"lit": 1}])
+        );
+        assert_eq!(
+            rule.compile().unwrap().apply(&json!({"x": 0})).unwrap(),
+            json!([{"var": "x"}, 1, 2, {"lit": 1}])
+        );
+        assert_eq!(Value::from(rule.clone()), value);
+        // Arrays aren't parsed, so needn't be quoted
+        assert_eq!(
+            Value::from(rule.ast()),
+            json!({"merge": [{"lit": {"var": "x"}}, [1, 2], {"lit": {"lit": 1}}]})
+        );
+    }
+
+    #[cfg(feature = "json5")]
+    #[test]
+    fn test_parse_str() {
+        let rule = Rule::parse_str(
+            r#"
+            // Adults in supported countries
+            {
+              and: [
+                {">=": [{var: "age"}, 18]},
+                /* trailing commas are fine */
+                {in: [{var: 'country'}, ["CA", "US",]]},
+              ],
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            rule.apply(&json!({"age": 30, "country": "CA"})).unwrap(),
+            json!(true)
+        );
+
+        match Rule::parse_str("{\"var\": \"a\" \"b\"}") {
+            Err(Error::InvalidSyntax { line: Some(1), .. }) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,263 @@
+//! Rule Builder
+//!
+//! A fluent interface for constructing rules in Rust:
+//!
+//! ```
+//! use jsonlogic_plus::rule;
+//! use serde_json::json;
+//!
+//! let expr = rule::var("age").gte(18).and(rule::var("country").eq("US"));
+//! assert_eq!(
+//!     serde_json::Value::from(expr.clone()),
+//!     json!({"and": [
+//!         {">=": [{"var": "age"}, 18]},
+//!         {"==": [{"var": "country"}, "US"]}
+//!     ]})
+//! );
+//! let rule = expr.build().unwrap();
+//! assert_eq!(rule.apply(&json!({"age": 21, "country": "US"})).unwrap(), json!(true));
+//! ```
+
+use core::ops;
+
+use serde_json::{Map, Value};
+
+use crate::error::Error;
+use crate::prelude::*;
+use crate::rule::Rule;
+
+/// An expression under construction
+///
+/// Plain values convert into literal expressions wherever an expression is
+/// expected. Note that, as in any rule, a literal object with a single key is
+/// parsed as an operation.
+#[derive(Clone, Debug)]
+pub struct Expr(Value);
+impl Expr {
+    /// Parse the expression into a `Rule`.
+    pub fn build(self) -> Result<Rule, Error> {
+        Rule::from_value(&self.0)
+    }
+
+    /// Loose equality (`==`)
+    pub fn eq<E: Into<Expr>>(self, other: E) -> Self {
+        op("==", vec![self, other.into()])
+    }
+
+    /// Loose inequality (`!=`)
+    pub fn ne<E: Into<Expr>>(self, other: E) -> Self {
+        op("!=", vec![self, other.into()])
+    }
+
+    /// Strict equality (`===`)
+    pub fn strict_eq<E: Into<Expr>>(self, other: E) -> Self {
+        op("===", vec![self, other.into()])
+    }
+
+    /// Strict inequality (`!==`)
+    pub fn strict_ne<E: Into<Expr>>(self, other: E) -> Self {
+        op("!==", vec![self, other.into()])
+    }
+
+    /// Less than (`<`)
+    pub fn lt<E: Into<Expr>>(self, other: E) -> Self {
+        op("<", vec![self, other.into()])
+    }
+
+    /// Less than or equal to (`<=`)
+    pub fn lte<E: Into<Expr>>(self, other: E) -> Self {
+        op("<=", vec![self, other.into()])
+    }
+
+    /// Greater than (`>`)
+    pub fn gt<E: Into<Expr>>(self, other: E) -> Self {
+        op(">", vec![self, other.into()])
+    }
+
+    /// Greater than or equal to (`>=`)
+    pub fn gte<E: Into<Expr>>(self, other: E) -> Self {
+        op(">=", vec![self, other.into()])
+    }
+
+    /// Whether the expression is in an array, or a substring of a string
+    /// (`in`)
+    pub fn is_in<E: Into<Expr>>(self, other: E) -> Self {
+        op("in", vec![self, other.into()])
+    }
+
+    /// Logical and (`and`)
+    ///
+    /// Chained calls are collected into a single `and` operation.
+    pub fn and<E: Into<Expr>>(self, other: E) -> Self {
+        self.chain("and", other.into())
+    }
+
+    /// Logical or (`or`)
+    ///
+    /// Chained calls are collected into a single `or` operation.
+    pub fn or<E: Into<Expr>>(self, other: E) -> Self {
+        self.chain("or", other.into())
+    }
+
+    /// Append an argument to an operation with the given operator, or
+    /// construct a new one if this expression isn't one.
+    fn chain(self, operator: &str, other: Expr) -> Self {
+        match self.0 {
+            Value::Object(mut obj) if obj.len() == 1 => {
+                if let Some(Value::Array(args)) = obj.get_mut(operator) {
+                    args.push(other.0);
+                    return Self(Value::Object(obj));
+                }
+                op(operator, vec![Self(Value::Object(obj)), other])
+            }
+            value => op(operator, vec![Self(value), other]),
+        }
+    }
+}
+impl From<Expr> for Value {
+    fn from(expr: Expr) -> Self {
+        expr.0
+    }
+}
+impl From<Value> for Expr {
+    fn from(value: Value) -> Self {
+        Self(value)
+    }
+}
+
+macro_rules! literal_from {
+    ($($t:ty),*) => {
+        $(
+            impl From<$t> for Expr {
+                fn from(value: $t) -> Self {
+                    Self(Value::from(value))
+                }
+            }
+        )*
+    };
+}
+literal_from!(bool, i32, i64, u32, u64, f64, &str, String);
+
+impl ops::Not for Expr {
+    type Output = Self;
+
+    /// Logical negation (`!`)
+    fn not(self) -> Self {
+        op("!", vec![self])
+    }
+}
+
+macro_rules! binary_op {
+    ($($trait:ident, $method:ident, $symbol:expr);*) => {
+        $(
+            impl<E: Into<Expr>> ops::$trait<E> for Expr {
+                type Output = Self;
+
+                fn $method(self, other: E) -> Self {
+                    op($symbol, vec![self, other.into()])
+                }
+            }
+        )*
+    };
+}
+binary_op!(Add, add, "+"; Sub, sub, "-"; Mul, mul, "*"; Div, div, "/"; Rem, rem, "%");
+
+/// Look up a value in the data by its path (`var`)
+pub fn var<S: Into<String>>(path: S) -> Expr {
+    let mut rv = Map::with_capacity(1);
+    rv.insert("var".into(), Value::String(path.into()));
+    Expr(Value::Object(rv))
+}
+
+/// Look up a value in the data by its path, falling back to a default if
+/// it's not present (`var`)
+pub fn var_or<S: Into<String>, E: Into<Expr>>(path: S, default: E) -> Expr {
+    op(
+        "var",
+        vec![Expr(Value::String(path.into())), default.into()],
+    )
+}
+
+/// A literal value
+pub fn val<E: Into<Expr>>(value: E) -> Expr {
+    value.into()
+}
+
+/// Choose between two expressions based on a condition (`if`)
+pub fn if_else<C, T, F>(condition: C, then: T, otherwise: F) -> Expr
+where
+    C: Into<Expr>,
+    T: Into<Expr>,
+    F: Into<Expr>,
+{
+    op("if", vec![condition.into(), then.into(), otherwise.into()])
+}
+
+/// An operation with any operator, e.g. a custom operator or a function in a
+/// `Namespace`
+pub fn op<S: Into<String>>(operator: S, args: Vec<Expr>) -> Expr {
+    let mut rv = Map::with_capacity(1);
+    rv.insert(
+        operator.into(),
+        Value::Array(args.into_iter().map(Value::from).collect()),
+    );
+    Expr(Value::Object(rv))
+}
+
+#[cfg(test)]
+mod test_builder {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_build() {
+        let cases = vec![
+            (
+                var("a").gt(1).and(var("b")).and(!var("c")),
+                json!({"and": [
+                    {">": [{"var": "a"}, 1]},
+                    {"var": "b"},
+                    {"!": [{"var": "c"}]}
+                ]}),
+            ),
+            (
+                var("a").or(var("b").and(true)),
+                json!({"or": [{"var": "a"}, {"and": [{"var": "b"}, true]}]}),
+            ),
+            (
+                (var("a") + 1) * var_or("b", 2),
+                json!({"*": [{"+": [{"var": "a"}, 1]}, {"var": ["b", 2]}]}),
+            ),
+            (
+                if_else(val("x").is_in(var("xs")), "yes", "no"),
+                json!({"if": [{"in": ["x", {"var": "xs"}]}, "yes", "no"]}),
+            ),
+            (
+                op("mean", vec![var("a"), val(json!([1, 2]))]),
+                json!({"mean": [{"var": "a"}, [1, 2]]}),
+            ),
+        ];
+        cases.into_iter().for_each(|(expr, exp)| {
+            assert_eq!(Value::from(expr), exp);
+        });
+    }
+
+    #[test]
+    fn test_apply() {
+        let rule = (var("price") * var("qty"))
+            .gte(10)
+            .and(var("country").strict_ne("US"))
+            .build()
+            .unwrap();
+        assert_eq!(
+            rule.apply(&json!({"price": 5, "qty": 2, "country": "CA"}))
+                .unwrap(),
+            json!(true)
+        );
+        assert_eq!(
+            rule.apply(&json!({"price": 5, "qty": 1, "country": "CA"}))
+                .unwrap(),
+            json!(false)
+        );
+    }
+}
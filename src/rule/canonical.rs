@@ -0,0 +1,249 @@
+//! Canonical Forms
+//!
+//! Rules that differ only in presentation (key order, `{"var": "a"}` vs
+//! `{"var": ["a", null]}`, etc.) have the same canonical form, and so the
+//! same content hash.
+//!
+//! Numbers are left as written: `1.0` and `1` give different results for
+//! some operators (e.g. `cat` and `in`), so they aren't the same rule.
+
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+
+use crate::ast::{Ast, AstVisitorMut};
+use crate::op::func::FunctionBody;
+use crate::prelude::*;
+use crate::rule::Rule;
+use crate::value::Parsed;
+
+impl Rule {
+    /// The rule in canonical form.
+    ///
+    /// In the canonical form:
+    ///   - object keys are sorted
+    ///   - every operator's arguments are written as an array
+    ///   - aliases are replaced by the operators they alias (e.g. `?:` by
+    ///     `if`)
+    ///   - wrappers with no effect are removed, e.g. `and` or `or` with a
+    ///     single argument, `if` with a single argument, double negations,
+    ///     and `null` defaults for `var`
+    pub fn canonicalize(&self) -> Value {
+        canonical(self.ast())
+    }
+
+    /// A stable digest of the rule's canonical form, as a hex-encoded
+    /// SHA-256 hash.
+    ///
+    /// For a rule parsed from a `Namespace`, the hash also covers the
+    /// functions the rule may call, and for a rule pinned to a semantics
+    /// version, the version.
+    pub fn content_hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.canonicalize().to_string());
+        if let Some(semantics) = self.semantics {
+            hasher.update([0]);
+            hasher.update(semantics.to_string());
+        }
+        for (name, body) in self.canonical_functions() {
+            hasher.update([0]);
+            hasher.update(name);
+            hasher.update([0]);
+            hasher.update(body.to_string());
+        }
+        hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// Whether two rules are the same once canonicalized, including the
+    /// functions they may call and the semantics versions they're pinned to.
+    pub fn semantically_equal(&self, other: &Rule) -> bool {
+        self.canonicalize() == other.canonicalize()
+            && self.semantics == other.semantics
+            && self.canonical_functions() == other.canonical_functions()
+    }
+
+    /// The canonical bodies of the functions the rule may call, sorted by
+    /// name
+    fn canonical_functions(&self) -> Vec<(&str, Value)> {
+        let mut functions: Vec<(&str, Value)> = self
+            .functions
+            .iter()
+            .flat_map(|functions| functions.iter())
+            .map(|(name, body)| (name.as_str(), canonical_function(body)))
+            .collect();
+        functions.sort_by(|a, b| a.0.cmp(b.0));
+        functions
+    }
+}
+
+/// Convert a function's body, and its contract if it has one, into
+/// canonical JSON.
+fn canonical_function(body: &FunctionBody) -> Value {
+    let expression = canonical(Ast::from(&body.expression));
+    if !body.has_contract() {
+        return expression;
+    }
+    let condition =
+        |cond: &Option<Parsed>| cond.as_ref().map(|c| canonical(Ast::from(c)));
+    Value::Array(vec![
+        expression,
+        condition(&body.pre).into(),
+        condition(&body.post).into(),
+    ])
+}
+
+/// Convert an AST into canonical JSON.
+fn canonical(mut ast: Ast) -> Value {
+    Canonicalize.visit_mut(&mut ast);
+    sort_keys(Value::from(ast))
+}
+
+/// Remove aliases and no-op wrappers from an AST
+struct Canonicalize;
+impl AstVisitorMut for Canonicalize {
+    fn visit_mut(&mut self, ast: &mut Ast) {
+        ast.walk_mut(self);
+        match ast {
+            Ast::Operator { name, args } => {
+                if name == "?:" {
+                    *name = "if".into();
+                }
+                match (name.as_str(), args.len()) {
+                    ("and", 1) | ("or", 1) | ("if", 1) => *ast = args.remove(0),
+                    ("if", 0) => *ast = Ast::Literal(Value::Null),
+                    ("!", 1) | ("!!", 1) => match &mut args[0] {
+                        Ast::Operator {
+                            name: inner,
+                            args: inner_args,
+                        } if inner == "!" && inner_args.len() == 1 => {
+                            // !!x is x, so !(!x) is !!x and !!(!x) is !x
+                            let x = inner_args.remove(0);
+                            let negations = if name == "!" { "!!" } else { "!" };
+                            *ast = Ast::Operator {
+                                name: negations.into(),
+                                args: vec![x],
+                            };
+                        }
+                        Ast::Operator { name: inner, .. }
+                            if inner == "!!" && name == "!!" =>
+                        {
+                            *ast = args.remove(0);
+                        }
+                        _ => {}
+                    },
+                    _ => {}
+                }
+            }
+            Ast::Var { path, default } => {
+                if let Some(Ast::Literal(Value::Null)) = default.as_deref() {
+                    *default = None;
+                }
+                match path.as_deref() {
+                    Some(Ast::Literal(Value::String(s)))
+                        if s.is_empty() && default.is_none() =>
+                    {
+                        *path = None
+                    }
+                    Some(Ast::Literal(Value::Number(n))) => {
+                        *path =
+                            Some(Box::new(Ast::Literal(Value::String(n.to_string()))))
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Sort object keys throughout a value.
+fn sort_keys(value: Value) -> Value {
+    match value {
+        Value::Array(items) => Value::Array(items.into_iter().map(sort_keys).collect()),
+        Value::Object(map) => {
+            let mut entries: Vec<(String, Value)> = map.into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, sort_keys(value)))
+                    .collect::<Map<String, Value>>(),
+            )
+        }
+        value => value,
+    }
+}
+
+#[cfg(test)]
+mod test_canonical {
+    use super::*;
+    use serde_json::json;
+
+    use crate::Namespace;
+
+    fn canonicalize(value: Value) -> Value {
+        Rule::from_value(&value).unwrap().canonicalize()
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        assert_eq!(
+            canonicalize(json!({"==": [{"var": ["a", null]}, 1.0]})),
+            json!({"==": [{"var": ["a"]}, 1.0]})
+        );
+        assert_eq!(
+            canonicalize(json!({"and": {"?:": [{"var": 0}, -0.0, 2.5]}})),
+            json!({"if": [{"var": ["0"]}, -0.0, 2.5]})
+        );
+        assert_eq!(
+            canonicalize(json!({"!": {"!": {"var": ""}}})),
+            json!({"!!": [{"var": []}]})
+        );
+        assert_eq!(
+            canonicalize(json!({"!!": [{"!": [{"!!": [true]}]}]})),
+            json!({"!": [{"!!": [true]}]})
+        );
+        assert_eq!(
+            canonicalize(json!({"in": ["a", {"or": [{"if": [["b", "a"]]}]}]})),
+            json!({"in": ["a", ["b", "a"]]})
+        );
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let hash = |value: Value| Rule::from_value(&value).unwrap().content_hash();
+        let a = hash(json!({"and": [{">": [{"var": "x"}, 1]}]}));
+        let b = hash(json!({">": [{"var": ["x", null]}, 1]}));
+        assert_eq!(a, b);
+        assert_eq!(a.len(), 64);
+        assert_ne!(a, hash(json!({">": [{"var": "x"}, 2]})));
+        // `1.0` and `1` give different results for some operators
+        assert_ne!(
+            hash(json!({"cat": ["x", 1.0]})),
+            hash(json!({"cat": ["x", 1]}))
+        );
+        assert_ne!(
+            hash(json!({"in": [{"var": "a"}, [1.0]]})),
+            hash(json!({"in": [{"var": "a"}, [1]]}))
+        );
+        // The hash is stable across releases
+        assert_eq!(
+            hash(json!(true)),
+            "b5bea41b6c623f7c09f1bf24dcae58ebab3c0cdd90ad966bc43a45b44867e12b"
+        );
+
+        // Functions called by the rule are part of its content
+        let mut ns = Namespace::new();
+        ns.define(&json!({"defn": ["f", [], 1]})).unwrap();
+        let rule = ns.parse(&json!({"f": []})).unwrap();
+        let mut other = Namespace::new();
+        other.define(&json!({"defn": ["f", [], 2]})).unwrap();
+        assert_ne!(
+            rule.content_hash(),
+            other.parse(&json!({"f": []})).unwrap().content_hash()
+        );
+    }
+}
@@ -0,0 +1,1488 @@
+//! Compiled Rules
+//!
+//! `Rule::compile()` flattens a parsed rule into bytecode for a small stack
+//! machine: literals are collected into a constant pool, operators are
+//! resolved up front, static `var` paths are split once, and lazily
+//! evaluated operators like `if` and `and` become jumps. A compiled rule gives
+//! the same results as the rule it was compiled from, without walking the
+//! tree of parsed values on each evaluation.
+//!
+//! Operators are still counted, traced, and profiled as they execute, but
+//! their recorded times exclude the evaluation of their arguments.
+//!
+//! Calls a function makes to itself in tail position, i.e. as the result of
+//! the function or of a branch of an `if`, `and`, or `or` that is, become
+//! jumps back to the start of its body, so that recursion to any depth runs
+//! within a fixed amount of stack, as they are when the rule is interpreted.
+//! Functions with contracts, and memoized functions, are always called
+//! normally.
+
+use alloc::borrow::Cow;
+use alloc::sync::Arc;
+
+use serde_json::{Map, Value};
+
+#[cfg(feature = "std")]
+use crate::audit::Started;
+use crate::collections::HashMap;
+use crate::context::{Context, Env, SemanticsVersion};
+use crate::error::Error;
+use crate::namespace::Scope;
+use crate::op::array::{
+    distinct, fold_while, invalid_items, select, sort, zip_arrays, Reduced,
+};
+use crate::op::data::{self, key_path, PathSegment, Variable};
+use crate::op::func::{call_arguments, call_value, check_condition, Signature};
+use crate::op::logic::{error_name, switch_key, truthy};
+use crate::op::object::transform_values;
+use crate::op::pattern;
+use crate::op::{DataOperator, Operator};
+use crate::pack::CustomOperator;
+use crate::prelude::*;
+use crate::rule::Rule;
+use crate::value::{Evaluated, Parsed};
+use crate::NULL;
+
+/// A rule compiled to bytecode with `Rule::compile()`
+///
+/// Compiled rules are cheap to clone and may be shared between threads.
+#[derive(Clone, Debug)]
+pub struct CompiledRule {
+    id: Option<String>,
+    content_hash: Arc<str>,
+    semantics: Option<SemanticsVersion>,
+    program: Arc<Program>,
+}
+impl CompiledRule {
+    /// The id of the rule this was compiled from, if it has one
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// The `content_hash()` of the rule this was compiled from
+    pub fn content_hash(&self) -> &str {
+        &self.content_hash
+    }
+
+    /// Evaluate the rule against the given data.
+    pub fn apply(&self, data: &Value) -> Result<Value, Error> {
+        self.apply_with(data, &Context::default())
+    }
+
+    /// Evaluate the rule against the given data, with the given context.
+    pub fn apply_with(&self, data: &Value, ctx: &Context) -> Result<Value, Error> {
+        #[cfg(feature = "std")]
+        if let Some(audit) = &ctx.audit {
+            let started = Started::now();
+            let result = self.evaluate(data, ctx);
+            let hash = self.content_hash().into();
+            audit.record(started, self.id(), hash, data, &result);
+            return result;
+        }
+        self.evaluate(data, ctx)
+    }
+
+    fn evaluate(&self, data: &Value, ctx: &Context) -> Result<Value, Error> {
+        #[cfg(feature = "tracing")]
+        let _span = crate::trace::apply(self.id());
+        let env = Env::new(ctx, self.id()).with_semantics(self.semantics);
+        self.program.run(0, data, &env)
+    }
+}
+
+impl Rule {
+    /// Compile the rule, along with any functions it may call, to bytecode.
+    ///
+    /// Compiling takes longer than parsing, so it's worthwhile for rules that
+    /// are applied many times.
+    pub fn compile(&self) -> Result<CompiledRule, Error> {
+        let mut names: Vec<&String> = self
+            .functions
+            .iter()
+            .flat_map(|functions| functions.keys())
+            .collect();
+        names.sort();
+        let mut compiler = Compiler {
+            tail: None,
+            program: Program {
+                functions: names
+                    .iter()
+                    .map(|name| Function {
+                        name: name.to_string(),
+                        entry: 0,
+                        pre: None,
+                        post: None,
+                        memoize: false,
+                        defaults: Vec::new(),
+                        signature: Signature::default(),
+                    })
+                    .collect(),
+                ..Program::default()
+            },
+        };
+        compiler.expr(&self.parsed)?;
+        compiler.emit(Instruction::Return);
+        for (i, name) in names.into_iter().enumerate() {
+            let body = &self.functions.as_ref().unwrap()[name];
+            if !body.has_contract() && !body.memoize {
+                compiler.tail = Some(i);
+            }
+            compiler.program.functions[i].entry = compiler.body(&body.expression)?;
+            compiler.program.functions[i].memoize = body.memoize;
+            compiler.program.functions[i].signature = body.signature;
+            for default in &body.defaults {
+                let entry = compiler.body(default)?;
+                compiler.program.functions[i].defaults.push(entry);
+            }
+            if let Some(pre) = &body.pre {
+                compiler.program.functions[i].pre = Some(compiler.body(pre)?);
+            }
+            if let Some(post) = &body.post {
+                compiler.program.functions[i].post = Some(compiler.body(post)?);
+            }
+        }
+        Ok(CompiledRule {
+            id: self.id.clone(),
+            content_hash: self.content_hash().into(),
+            semantics: self.semantics,
+            program: Arc::new(compiler.program),
+        })
+    }
+}
+
+/// A single bytecode instruction
+///
+/// Jump targets are indices into the program's code.
+#[derive(Debug)]
+enum Instruction {
+    /// Push a constant from the pool
+    Const(usize),
+    /// Push the argument bound to a parameter of the current function
+    Param(usize),
+    /// Look up a variable, at a path from the pool or at the key popped from
+    /// the stack. If the variable has a default, its instructions follow,
+    /// and are skipped if the variable is present.
+    Var {
+        path: Option<usize>,
+        argc: usize,
+        default: Option<usize>,
+    },
+    /// Evaluate a `var` default that is itself a rule, before
+    /// `SemanticsVersion::V2`
+    Default,
+    /// Build an object from the values at the top of the stack, whose keys
+    /// are the array in the pool
+    Object(usize),
+    /// Apply an operator to the arguments at the top of the stack
+    Op {
+        operator: &'static Operator,
+        argc: usize,
+    },
+    /// Apply a data operator to the arguments at the top of the stack
+    DataOp {
+        operator: &'static DataOperator,
+        argc: usize,
+    },
+    /// Apply a custom operator to the arguments at the top of the stack
+    Custom {
+        operator: usize,
+        argc: usize,
+    },
+    /// Call a function with the arguments at the top of the stack
+    Call {
+        function: usize,
+        argc: usize,
+    },
+    /// Call the current function again with the arguments at the top of the
+    /// stack, reusing the current call's frame
+    TailCall {
+        function: usize,
+        argc: usize,
+    },
+    /// Pop an array of arguments and a function value, and call the function
+    DynamicCall,
+    /// Record the execution of a lazily evaluated operator
+    Enter {
+        symbol: &'static str,
+        argc: usize,
+    },
+    Jump(usize),
+    /// Pop a value, and jump if it's falsy
+    JumpIfFalsy(usize),
+    /// Jump if the value on top of the stack is truthy, leaving it in place,
+    /// or pop it otherwise
+    JumpIfTruthyOrPop(usize),
+    /// Jump if the value on top of the stack is falsy, leaving it in place,
+    /// or pop it otherwise
+    JumpIfFalsyOrPop(usize),
+    /// Jump if the value on top of the stack isn't null, leaving it in place,
+    /// or pop it otherwise
+    JumpIfNotNullOrPop(usize),
+    /// Pop the items (and for the reductions, the initial value, or for the sorts,
+    /// whether they're descending), and evaluate the body that follows
+    /// against each item. `source` is the pool index of the items'
+    /// expression, for errors. The body ends at `end`.
+    Iterate {
+        iteration: Iteration,
+        source: usize,
+        end: usize,
+    },
+    /// Pop the given number of arrays, and push the arrays of their elements
+    /// at each index, for `zip_with`
+    Zip(usize),
+    /// Pop a value, and evaluate the body that follows against it, as a
+    /// step of a `pipe`. The body ends at `end`.
+    Pipe {
+        end: usize,
+    },
+    /// Evaluate the body that follows, or if that fails, the fallback body
+    /// at `fallback`, with the error's message under the name (a pool
+    /// index), if any. The fallback ends at `end`.
+    Try {
+        fallback: usize,
+        name: Option<usize>,
+        end: usize,
+    },
+    /// Pop a value, and evaluate the body of the first arm whose pattern (a
+    /// pool index) it matches, or else the default body. The bodies follow,
+    /// each ending in a `Return`, and the last ends at `end`.
+    Match {
+        arms: Vec<(usize, usize)>,
+        default: Option<usize>,
+        end: usize,
+    },
+    /// Pop a value, which must be a string or null, and evaluate the body of
+    /// the case with it as key, or else the default body, or push null if
+    /// there is none. The bodies follow, each ending in a `Return`, and the last ends
+    /// at `end`.
+    Switch {
+        cases: HashMap<String, usize>,
+        default: Option<usize>,
+        end: usize,
+    },
+    /// Pop and return the result of the current body
+    Return,
+}
+
+/// The iterating operators
+#[derive(Clone, Copy, Debug)]
+enum Iteration {
+    Map,
+    Filter,
+    Reduce,
+    ReduceWhile,
+    All,
+    Some,
+    None,
+    Find,
+    Position,
+    MinBy,
+    MaxBy,
+    SumBy,
+    DistinctBy,
+    SortBy,
+    SortWith,
+    MapValues,
+}
+impl Iteration {
+    fn from_symbol(symbol: &str) -> Option<Self> {
+        match symbol {
+            "map" => Some(Self::Map),
+            "filter" => Some(Self::Filter),
+            "reduce" => Some(Self::Reduce),
+            "reduce_while" => Some(Self::ReduceWhile),
+            "all" => Some(Self::All),
+            "some" => Some(Self::Some),
+            "none" => Some(Self::None),
+            "find" => Some(Self::Find),
+            "position" => Some(Self::Position),
+            "min_by" => Some(Self::MinBy),
+            "max_by" => Some(Self::MaxBy),
+            "sum_by" => Some(Self::SumBy),
+            "distinct_by" => Some(Self::DistinctBy),
+            "sort_by" => Some(Self::SortBy),
+            "sort_with" => Some(Self::SortWith),
+            "map_values" => Some(Self::MapValues),
+            _ => None,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Self::Map => "map",
+            Self::Filter => "filter",
+            Self::Reduce => "reduce",
+            Self::ReduceWhile => "reduce_while",
+            Self::All => "all",
+            Self::Some => "some",
+            Self::None => "none",
+            Self::Find => "find",
+            Self::Position => "position",
+            Self::MinBy => "min_by",
+            Self::MaxBy => "max_by",
+            Self::SumBy => "sum_by",
+            Self::DistinctBy => "distinct_by",
+            Self::SortBy => "sort_by",
+            Self::SortWith => "sort_with",
+            Self::MapValues => "map_values",
+        }
+    }
+}
+
+/// A function's entry points in the program's code
+#[derive(Debug)]
+struct Function {
+    name: String,
+    entry: usize,
+    /// The entry points of the function's `pre` and `post` conditions
+    pre: Option<usize>,
+    post: Option<usize>,
+    memoize: bool,
+    /// The entry points of the defaults of the function's trailing
+    /// parameters
+    defaults: Vec<usize>,
+    signature: Signature,
+}
+
+#[derive(Debug, Default)]
+struct Program {
+    /// The rule's instructions, followed by those of each function
+    code: Vec<Instruction>,
+    constants: Vec<Value>,
+    paths: Vec<Vec<PathSegment>>,
+    operators: Vec<CustomOperator>,
+    functions: Vec<Function>,
+}
+impl Program {
+    /// Call a function, whose arguments are bound in `env`, checking its
+    /// contract.
+    fn call(
+        &self,
+        function: &Function,
+        data: &Value,
+        env: &Env,
+    ) -> Result<Value, Error> {
+        let check = env.check_contracts();
+        if let (Some(pre), true) = (function.pre, check) {
+            let outcome = self.run(pre, data, env)?;
+            check_condition(&function.name, "pre", &outcome, env, None)?;
+        }
+        let result = self.run(function.entry, data, env)?;
+        if let (Some(post), true) = (function.post, check) {
+            let outcome = self.run(post, &result, &env.rebind())?;
+            check_condition(&function.name, "post", &outcome, env, Some(&result))?;
+        }
+        Ok(result)
+    }
+
+    /// Bind a call's arguments to the parameters of a function, running the
+    /// defaults of any that were omitted.
+    fn bind(
+        &self,
+        function: &Function,
+        arguments: Vec<Value>,
+        data: &Value,
+        env: &Env,
+    ) -> Result<Vec<Value>, Error> {
+        if function.signature.is_positional() {
+            return Ok(arguments);
+        }
+        function.signature.bind(arguments, |idx, bound| {
+            self.run(
+                function.defaults[idx],
+                data,
+                &env.with_params(bound.to_vec()),
+            )
+        })
+    }
+
+    /// The function with the given name
+    fn function(&self, name: &str) -> Option<&Function> {
+        self.functions.iter().find(|function| function.name == name)
+    }
+
+    /// Run the body starting at `pc` against the given data.
+    fn run(&self, mut pc: usize, data: &Value, env: &Env) -> Result<Value, Error> {
+        let mut stack: Vec<Evaluated> = Vec::new();
+        // The environment of the latest tail call, if any
+        let mut tail_env: Option<Env> = None;
+        loop {
+            let env = tail_env.as_ref().unwrap_or(env);
+            match &self.code[pc] {
+                Instruction::Const(i) => {
+                    stack.push(Evaluated::Raw(&self.constants[*i]))
+                }
+                Instruction::Param(i) => {
+                    let value = env.param(*i).cloned().ok_or_else(|| {
+                        Error::UnexpectedError(format!(
+                            "Parameter {} was referenced outside of a function call",
+                            i
+                        ))
+                    })?;
+                    stack.push(Evaluated::New(value));
+                }
+                Instruction::Var {
+                    path,
+                    argc,
+                    default,
+                } => {
+                    let key_path;
+                    let path = match path {
+                        Some(path) => &self.paths[*path],
+                        None => {
+                            key_path = self::key_path(pop(&mut stack)?)?;
+                            &key_path
+                        }
+                    };
+                    let resolver = env.resolver().unwrap_or(data);
+                    let value = env.execute(Variable::OPERATOR, *argc, || {
+                        Ok(data::resolve_variable(resolver, path, env)?
+                            .map(Cow::into_owned))
+                    })?;
+                    match (value, default) {
+                        (Some(value), Some(end)) => {
+                            stack.push(Evaluated::New(value));
+                            pc = *end;
+                            continue;
+                        }
+                        (Some(value), None) => stack.push(Evaluated::New(value)),
+                        (None, Some(_)) => {}
+                        (None, None) => stack.push(Evaluated::New(NULL)),
+                    }
+                }
+                Instruction::Default => {
+                    let default = pop(&mut stack)?;
+                    stack.push(data::default_value(default, data, env)?);
+                }
+                Instruction::Object(keys) => {
+                    let keys = self.constants[*keys]
+                        .as_array()
+                        .map_or(&[][..], Vec::as_slice);
+                    let values = stack.len() - keys.len();
+                    let obj = keys
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .map(String::from)
+                        .zip(stack.drain(values..).map(Value::from))
+                        .collect();
+                    stack.push(Evaluated::New(Value::Object(obj)));
+                }
+                Instruction::Op { operator, argc } => {
+                    let args = stack.len() - argc;
+                    let value = env.execute(operator.symbol(), *argc, || {
+                        operator.execute(&stack[args..], env)
+                    })?;
+                    stack.truncate(args);
+                    stack.push(Evaluated::New(value));
+                }
+                Instruction::DataOp { operator, argc } => {
+                    let args = stack.len() - argc;
+                    let resolver = env.resolver().unwrap_or(data);
+                    let value = env.execute(operator.symbol(), *argc, || {
+                        operator.execute(resolver, &stack[args..], env)
+                    })?;
+                    stack.truncate(args);
+                    stack.push(Evaluated::New(value));
+                }
+                Instruction::Custom { operator, argc } => {
+                    let operator = &self.operators[*operator];
+                    let args = stack.len() - argc;
+                    let value = env.execute(operator.name(), *argc, || {
+                        let values: Vec<&Value> =
+                            stack[args..].iter().map(|arg| &**arg).collect();
+                        operator.execute_in(&values, env)
+                    })?;
+                    stack.truncate(args);
+                    stack.push(Evaluated::New(value));
+                }
+                Instruction::Call { function, argc } => {
+                    let function = &self.functions[*function];
+                    let arguments: Vec<Value> =
+                        stack.drain(stack.len() - argc..).map(Value::from).collect();
+                    let value = env.execute(&function.name, *argc, || {
+                        let env = env.calling(
+                            &function.name,
+                            self.bind(function, arguments, data, env)?,
+                        )?;
+                        let (data, env) = env.for_body(function.memoize, data);
+                        env.memoized(&function.name, function.memoize, || {
+                            self.call(function, data, &env)
+                        })
+                    })?;
+                    stack.push(Evaluated::New(value));
+                }
+                Instruction::TailCall { function, argc } => {
+                    let function = &self.functions[*function];
+                    let arguments: Vec<Value> =
+                        stack.drain(stack.len() - argc..).map(Value::from).collect();
+                    env.execute(&function.name, *argc, || Ok(()))?;
+                    let arguments = self.bind(function, arguments, data, env)?;
+                    tail_env = Some(env.with_params(arguments));
+                    stack.clear();
+                    pc = function.entry;
+                    continue;
+                }
+                Instruction::DynamicCall => {
+                    let arguments = call_arguments(&*pop(&mut stack)?)?;
+                    let reference = pop(&mut stack)?;
+                    let value = call_value(
+                        &reference,
+                        arguments,
+                        &|name| {
+                            self.function(name)
+                                .map(|function| function.signature.num_params())
+                        },
+                        &mut |name, arguments| {
+                            let function = self.function(name).ok_or_else(|| {
+                                Error::UnexpectedError(format!(
+                                    "Function '{}' was not compiled into the rule",
+                                    name
+                                ))
+                            })?;
+                            let arguments =
+                                self.bind(function, arguments, data, env)?;
+                            let env = env.calling(&function.name, arguments)?;
+                            let (data, env) = env.for_body(function.memoize, data);
+                            env.memoized(&function.name, function.memoize, || {
+                                self.call(function, data, &env)
+                            })
+                        },
+                    )?;
+                    stack.push(Evaluated::New(value));
+                }
+                Instruction::Enter { symbol, argc } => {
+                    env.execute(symbol, *argc, || Ok(()))?
+                }
+                Instruction::Jump(target) => {
+                    pc = *target;
+                    continue;
+                }
+                Instruction::JumpIfFalsy(target) => {
+                    if !truthy(&*pop(&mut stack)?) {
+                        pc = *target;
+                        continue;
+                    }
+                }
+                Instruction::JumpIfTruthyOrPop(target) => {
+                    if stack.last().is_some_and(|value| truthy(value)) {
+                        pc = *target;
+                        continue;
+                    }
+                    pop(&mut stack)?;
+                }
+                Instruction::JumpIfFalsyOrPop(target) => {
+                    if stack.last().is_some_and(|value| !truthy(value)) {
+                        pc = *target;
+                        continue;
+                    }
+                    pop(&mut stack)?;
+                }
+                Instruction::JumpIfNotNullOrPop(target) => {
+                    if stack.last().is_some_and(|value| !value.is_null()) {
+                        pc = *target;
+                        continue;
+                    }
+                    pop(&mut stack)?;
+                }
+                Instruction::Iterate {
+                    iteration,
+                    source,
+                    end,
+                } => {
+                    let initial = match iteration {
+                        Iteration::Reduce
+                        | Iteration::ReduceWhile
+                        | Iteration::SortBy
+                        | Iteration::SortWith => Some(pop(&mut stack)?),
+                        _ => None,
+                    };
+                    let items = pop(&mut stack)?;
+                    let value = self.iterate(
+                        *iteration,
+                        &self.constants[*source],
+                        items,
+                        initial,
+                        pc + 1,
+                        data,
+                        env,
+                    )?;
+                    stack.push(Evaluated::New(value));
+                    pc = *end;
+                    continue;
+                }
+                Instruction::Zip(argc) => {
+                    let arrays = stack.len() - argc;
+                    let tuples = zip_arrays(&stack[arrays..], "zip_with")?;
+                    stack.truncate(arrays);
+                    stack.push(Evaluated::New(Value::Array(tuples)));
+                }
+                Instruction::Pipe { end } => {
+                    let value = Value::from(pop(&mut stack)?);
+                    let result = self.run(pc + 1, &value, &env.rebind())?;
+                    stack.push(Evaluated::New(result));
+                    pc = *end;
+                    continue;
+                }
+                Instruction::Try {
+                    fallback,
+                    name,
+                    end,
+                } => {
+                    let result = match self.run(pc + 1, data, env) {
+                        Ok(result) => result,
+                        Err(err @ Error::Cancelled(_)) => return Err(err),
+                        Err(err) => match name {
+                            Some(name) => {
+                                let mut captures = Map::new();
+                                captures.insert(
+                                    error_name(&self.constants[*name])?.into(),
+                                    err.to_string().into(),
+                                );
+                                let data = pattern::branch_data(data, captures);
+                                self.run(*fallback, &data, &env.rebind())?
+                            }
+                            None => self.run(*fallback, data, env)?,
+                        },
+                    };
+                    stack.push(Evaluated::New(result));
+                    pc = *end;
+                    continue;
+                }
+                Instruction::Match { arms, default, end } => {
+                    let value = Value::from(pop(&mut stack)?);
+                    let mut result = None;
+                    for (pattern, body) in arms {
+                        let mut captures = Map::new();
+                        if pattern::matches(
+                            &self.constants[*pattern],
+                            &value,
+                            &mut captures,
+                        ) {
+                            let env = if captures.is_empty() {
+                                env.clone()
+                            } else {
+                                env.rebind()
+                            };
+                            let data = pattern::branch_data(data, captures);
+                            result = Some(self.run(*body, &data, &env)?);
+                            break;
+                        }
+                    }
+                    let result = match (result, default) {
+                        (Some(result), _) => result,
+                        (None, Some(body)) => self.run(*body, data, env)?,
+                        (None, None) => return Err(Error::NoMatch { value }),
+                    };
+                    stack.push(Evaluated::New(result));
+                    pc = *end;
+                    continue;
+                }
+                Instruction::Switch {
+                    cases,
+                    default,
+                    end,
+                } => {
+                    let value = pop(&mut stack)?;
+                    let body = switch_key(&value)?.and_then(|key| cases.get(key));
+                    let result = match body.or(default.as_ref()) {
+                        Some(body) => self.run(*body, data, env)?,
+                        None => NULL,
+                    };
+                    stack.push(Evaluated::New(result));
+                    pc = *end;
+                    continue;
+                }
+                Instruction::Return => return pop(&mut stack).map(Value::from),
+            }
+            pc += 1;
+        }
+    }
+
+    /// Evaluate the body starting at `body` against each of the items.
+    #[allow(clippy::too_many_arguments)]
+    fn iterate(
+        &self,
+        iteration: Iteration,
+        source: &Value,
+        items: Evaluated,
+        initial: Option<Evaluated>,
+        body: usize,
+        data: &Value,
+        env: &Env,
+    ) -> Result<Value, Error> {
+        let item_env = env.within(data);
+        let chars: Vec<Value>;
+        let values: &[Value] = match (iteration, &*items) {
+            // The items are an object, whose values are transformed below
+            (Iteration::MapValues, _) => &[],
+            (_, Value::Array(values)) => values,
+            (_, Value::Null) => &[],
+            (Iteration::All | Iteration::Some | Iteration::None, Value::String(s)) => {
+                chars = s.chars().map(|c| Value::String(c.to_string())).collect();
+                &chars
+            }
+            _ => return Err(invalid_items(iteration.symbol(), source.clone(), &items)),
+        };
+
+        match iteration {
+            Iteration::Map => values
+                .iter()
+                .enumerate()
+                .map(|(idx, value)| self.run(body, value, &item_env.at_index(idx)))
+                .collect::<Result<Vec<Value>, Error>>()
+                .map(Value::Array),
+            Iteration::Filter => {
+                let mut filtered = Vec::with_capacity(values.len());
+                for (idx, value) in values.iter().enumerate() {
+                    if truthy(&self.run(body, value, &item_env.at_index(idx))?) {
+                        filtered.push(value.clone());
+                    }
+                }
+                Ok(Value::Array(filtered))
+            }
+            Iteration::Reduce => values.iter().enumerate().try_fold(
+                initial.map(Value::from).unwrap_or(NULL),
+                |accumulator, (idx, current)| {
+                    let mut data = Map::with_capacity(2);
+                    data.insert("current".into(), current.clone());
+                    data.insert("accumulator".into(), accumulator);
+                    self.run(body, &Value::Object(data), &item_env.at_index(idx))
+                },
+            ),
+            Iteration::ReduceWhile => {
+                let reduced = Reduced::default();
+                let item_env = item_env.with_reduced(&reduced);
+                fold_while(
+                    values,
+                    initial.map(Value::from).unwrap_or(NULL),
+                    &reduced,
+                    |accumulator, idx, current| {
+                        let mut data = Map::with_capacity(2);
+                        data.insert("current".into(), current.clone());
+                        data.insert("accumulator".into(), accumulator);
+                        self.run(body, &Value::Object(data), &item_env.at_index(idx))
+                    },
+                )
+            }
+            Iteration::All | Iteration::Some | Iteration::None => {
+                // Stop at the first item for which the predicate is false
+                // (for `all`) or true (otherwise)
+                let stop = matches!(iteration, Iteration::Some | Iteration::None);
+                let mut stopped = false;
+                for (idx, value) in values.iter().enumerate() {
+                    // Items that are rules are evaluated against the data
+                    // before being passed to the predicate
+                    let item: Cow<Value> = match value {
+                        Value::Object(_) => Cow::Owned(
+                            Parsed::from_value(value, &Scope::default())?
+                                .evaluate(data, env)?
+                                .into(),
+                        ),
+                        value => Cow::Borrowed(value),
+                    };
+                    if truthy(&self.run(body, &item, &item_env.at_index(idx))?) == stop
+                    {
+                        stopped = true;
+                        break;
+                    }
+                }
+                Ok(Value::Bool(match iteration {
+                    Iteration::All => !values.is_empty() && !stopped,
+                    Iteration::Some => stopped,
+                    _ => !stopped,
+                }))
+            }
+            Iteration::Find
+            | Iteration::Position
+            | Iteration::MinBy
+            | Iteration::MaxBy
+            | Iteration::SumBy => select(iteration.symbol(), values, env, |value| {
+                self.run(body, value, &item_env)
+            }),
+            Iteration::DistinctBy => {
+                distinct(values, |value| self.run(body, value, &item_env))
+            }
+            Iteration::SortBy | Iteration::SortWith => sort(
+                iteration.symbol(),
+                values,
+                &initial.map(Value::from).unwrap_or(Value::Bool(false)),
+                |value| self.run(body, value, &item_env),
+            ),
+            Iteration::MapValues => transform_values(source, &items, |value| {
+                self.run(body, value, &item_env)
+            }),
+        }
+    }
+}
+
+fn pop<'a>(stack: &mut Vec<Evaluated<'a>>) -> Result<Evaluated<'a>, Error> {
+    stack
+        .pop()
+        .ok_or_else(|| Error::UnexpectedError("Bytecode stack underflow".into()))
+}
+
+struct Compiler {
+    program: Program,
+    /// The function whose body is being compiled, while compiling an
+    /// expression whose value it returns, if calls to the function may be
+    /// compiled as tail calls
+    tail: Option<usize>,
+}
+impl Compiler {
+    /// Compile a function body, or one of its conditions, returning its
+    /// entry point.
+    fn body(&mut self, parsed: &Parsed) -> Result<usize, Error> {
+        let entry = self.program.code.len();
+        self.expr(parsed)?;
+        self.emit(Instruction::Return);
+        self.tail = None;
+        Ok(entry)
+    }
+
+    fn expr(&mut self, parsed: &Parsed) -> Result<(), Error> {
+        // Only this expression is in tail position, not its arguments
+        let tail = self.tail.take();
+        match parsed {
+            Parsed::Raw(raw) => {
+                let constant = self.constant(raw.value().clone());
+                self.emit(Instruction::Const(constant));
+            }
+            Parsed::Operation(op) => {
+                self.args(op.arguments())?;
+                self.emit(Instruction::Op {
+                    operator: op.operator(),
+                    argc: op.arguments().len(),
+                });
+            }
+            Parsed::DataOperation(op) => {
+                self.args(op.arguments())?;
+                self.emit(Instruction::DataOp {
+                    operator: op.operator(),
+                    argc: op.arguments().len(),
+                });
+            }
+            Parsed::CustomOperation(op) => {
+                self.args(op.arguments())?;
+                self.program.operators.push(op.operator().clone());
+                self.emit(Instruction::Custom {
+                    operator: self.program.operators.len() - 1,
+                    argc: op.arguments().len(),
+                });
+            }
+            Parsed::Call(call) => {
+                let function = self
+                    .program
+                    .functions
+                    .iter()
+                    .position(|function| function.name == call.name())
+                    .ok_or_else(|| {
+                        Error::UnexpectedError(format!(
+                            "Function '{}' was not compiled into the rule",
+                            call.name()
+                        ))
+                    })?;
+                self.args(call.arguments())?;
+                let argc = call.arguments().len();
+                self.emit(match tail {
+                    Some(current) if current == function => {
+                        Instruction::TailCall { function, argc }
+                    }
+                    _ => Instruction::Call { function, argc },
+                });
+            }
+            Parsed::Param(param) => {
+                self.emit(Instruction::Param(param.index()));
+            }
+            Parsed::Object(obj) => {
+                let mut keys = Vec::new();
+                for (key, value) in obj.entries() {
+                    self.expr(value)?;
+                    keys.push(Value::from(key.as_str()));
+                }
+                let keys = self.constant(Value::Array(keys));
+                self.emit(Instruction::Object(keys));
+            }
+            Parsed::Variable(var) => {
+                let path = self.path(var)?;
+                let argc = var.arguments().len();
+                match var.arguments().get(1) {
+                    None => {
+                        self.emit(Instruction::Var {
+                            path,
+                            argc,
+                            default: None,
+                        });
+                    }
+                    Some(default) => {
+                        let var = self.emit(Instruction::Var {
+                            path,
+                            argc,
+                            default: Some(0),
+                        });
+                        self.expr(default)?;
+                        self.emit(Instruction::Default);
+                        self.patch(var);
+                    }
+                }
+            }
+            Parsed::LazyOperation(op) => {
+                self.lazy(op.symbol(), op.arguments(), tail)?
+            }
+        }
+        Ok(())
+    }
+
+    /// Compile a lazily evaluated operator. `tail` is the function whose
+    /// result the operator's value is, if any.
+    fn lazy(
+        &mut self,
+        symbol: &'static str,
+        args: &[Parsed],
+        tail: Option<usize>,
+    ) -> Result<(), Error> {
+        self.emit(Instruction::Enter {
+            symbol,
+            argc: args.len(),
+        });
+        match (symbol, args) {
+            ("if" | "?:", []) => {
+                let null = self.constant(NULL);
+                self.emit(Instruction::Const(null));
+            }
+            ("if" | "?:", [arg]) => {
+                self.tail = tail;
+                self.expr(arg)?
+            }
+            ("if" | "?:", _) => {
+                let mut ends = Vec::new();
+                let mut has_else = false;
+                for pair in args.chunks(2) {
+                    match pair {
+                        [condition, then] => {
+                            self.expr(condition)?;
+                            let otherwise = self.emit(Instruction::JumpIfFalsy(0));
+                            self.tail = tail;
+                            self.expr(then)?;
+                            ends.push(self.emit(Instruction::Jump(0)));
+                            self.patch(otherwise);
+                        }
+                        [otherwise] => {
+                            self.tail = tail;
+                            self.expr(otherwise)?;
+                            has_else = true;
+                        }
+                        _ => {}
+                    }
+                }
+                if !has_else {
+                    let null = self.constant(NULL);
+                    self.emit(Instruction::Const(null));
+                }
+                ends.into_iter().for_each(|end| self.patch(end));
+            }
+            ("coalesce", args) => {
+                let mut ends = Vec::new();
+                for arg in args {
+                    self.expr(arg)?;
+                    ends.push(self.emit(Instruction::JumpIfNotNullOrPop(0)));
+                }
+                let null = self.constant(NULL);
+                self.emit(Instruction::Const(null));
+                ends.into_iter().for_each(|end| self.patch(end));
+            }
+            // A variable that is present jumps to the end, and one that
+            // isn't falls through to the next argument.
+            ("default", args) => {
+                let mut ends = Vec::new();
+                let mut found = false;
+                for arg in args {
+                    match arg {
+                        Parsed::Variable(var) if var.arguments().len() < 2 => {
+                            let path = self.path(var)?;
+                            ends.push(self.emit(Instruction::Var {
+                                path,
+                                argc: var.arguments().len(),
+                                default: Some(0),
+                            }));
+                        }
+                        _ => {
+                            self.tail = tail;
+                            self.expr(arg)?;
+                            found = true;
+                            break;
+                        }
+                    }
+                }
+                if !found {
+                    let null = self.constant(NULL);
+                    self.emit(Instruction::Const(null));
+                }
+                ends.into_iter().for_each(|end| self.patch(end));
+            }
+            ("pipe", [initial, steps @ ..]) => {
+                self.expr(initial)?;
+                for step in steps {
+                    let pipe = self.emit(Instruction::Pipe { end: 0 });
+                    self.expr(step)?;
+                    self.emit(Instruction::Return);
+                    self.patch(pipe);
+                }
+            }
+            ("try", [expr, fallback, name @ ..]) => {
+                let name = name.first().map(|name| self.constant(name.clone().into()));
+                let at = self.emit(Instruction::Try {
+                    fallback: 0,
+                    name,
+                    end: 0,
+                });
+                self.expr(expr)?;
+                self.emit(Instruction::Return);
+                let start = self.program.code.len();
+                if let Instruction::Try { fallback, .. } = &mut self.program.code[at] {
+                    *fallback = start;
+                }
+                self.expr(fallback)?;
+                self.emit(Instruction::Return);
+                self.patch(at);
+            }
+            ("match", [scrutinee, arms @ ..]) => {
+                self.expr(scrutinee)?;
+                let at = self.emit(Instruction::Match {
+                    arms: Vec::new(),
+                    default: None,
+                    end: 0,
+                });
+                let (mut patterns, mut default) = (Vec::new(), None);
+                for arm in arms.chunks(2) {
+                    let body = self.program.code.len();
+                    match arm {
+                        [pattern, branch] => {
+                            patterns
+                                .push((self.constant(pattern.clone().into()), body));
+                            self.expr(branch)?;
+                        }
+                        [branch] => {
+                            default = Some(body);
+                            self.expr(branch)?;
+                        }
+                        _ => continue,
+                    }
+                    self.emit(Instruction::Return);
+                }
+                if let Instruction::Match {
+                    arms, default: d, ..
+                } = &mut self.program.code[at]
+                {
+                    *arms = patterns;
+                    *d = default;
+                }
+                self.patch(at);
+            }
+            ("switch", [scrutinee, Parsed::Object(cases), default @ ..]) => {
+                self.expr(scrutinee)?;
+                let at = self.emit(Instruction::Switch {
+                    cases: HashMap::new(),
+                    default: None,
+                    end: 0,
+                });
+                let mut bodies = HashMap::new();
+                for (key, branch) in cases.entries() {
+                    bodies.insert(key.clone(), self.program.code.len());
+                    self.expr(branch)?;
+                    self.emit(Instruction::Return);
+                }
+                let default = match default {
+                    [branch] => {
+                        let body = self.program.code.len();
+                        self.expr(branch)?;
+                        self.emit(Instruction::Return);
+                        Some(body)
+                    }
+                    _ => None,
+                };
+                if let Instruction::Switch {
+                    cases, default: d, ..
+                } = &mut self.program.code[at]
+                {
+                    *cases = bodies;
+                    *d = default;
+                }
+                self.patch(at);
+            }
+            // The arrays' tuples are mapped over like the items of `map`
+            ("zip_with", [arrays @ .., expression]) => {
+                self.args(arrays)?;
+                self.emit(Instruction::Zip(arrays.len()));
+                let source = self.constant(NULL);
+                let iterate = self.emit(Instruction::Iterate {
+                    iteration: Iteration::Map,
+                    source,
+                    end: 0,
+                });
+                self.expr(expression)?;
+                self.emit(Instruction::Return);
+                self.patch(iterate);
+            }
+            ("call" | "apply", [function, arguments]) => {
+                self.expr(function)?;
+                self.expr(arguments)?;
+                self.emit(Instruction::DynamicCall);
+            }
+            ("or" | "and", [args @ .., last]) => {
+                let mut ends = Vec::new();
+                for arg in args {
+                    self.expr(arg)?;
+                    ends.push(self.emit(match symbol {
+                        "or" => Instruction::JumpIfTruthyOrPop(0),
+                        _ => Instruction::JumpIfFalsyOrPop(0),
+                    }));
+                }
+                self.tail = tail;
+                self.expr(last)?;
+                ends.into_iter().for_each(|end| self.patch(end));
+            }
+            _ => {
+                let iteration = Iteration::from_symbol(symbol).ok_or_else(|| {
+                    Error::UnexpectedError(format!(
+                        "Lazy operator '{}' can't be compiled",
+                        symbol
+                    ))
+                })?;
+                self.expr(&args[0])?;
+                match (iteration, args.get(2)) {
+                    (Iteration::Reduce | Iteration::ReduceWhile, Some(initial)) => {
+                        self.expr(initial)?
+                    }
+                    (Iteration::SortBy | Iteration::SortWith, Some(descending)) => {
+                        self.expr(descending)?
+                    }
+                    (Iteration::SortBy | Iteration::SortWith, None) => {
+                        let ascending = self.constant(Value::Bool(false));
+                        self.emit(Instruction::Const(ascending));
+                    }
+                    _ => {}
+                }
+                let source = self.constant(args[0].clone().into());
+                let iterate = self.emit(Instruction::Iterate {
+                    iteration,
+                    source,
+                    end: 0,
+                });
+                self.expr(&args[1])?;
+                self.emit(Instruction::Return);
+                self.patch(iterate);
+            }
+        }
+        Ok(())
+    }
+
+    /// Add a variable's path to the pool, or if it isn't static, compile the
+    /// expression for its key.
+    fn path(&mut self, var: &Variable) -> Result<Option<usize>, Error> {
+        match var.path() {
+            Some(path) => {
+                self.program.paths.push(path.to_vec());
+                Ok(Some(self.program.paths.len() - 1))
+            }
+            None => {
+                self.expr(&var.arguments()[0])?;
+                Ok(None)
+            }
+        }
+    }
+
+    fn args(&mut self, args: &[Parsed]) -> Result<(), Error> {
+        args.iter().try_for_each(|arg| self.expr(arg))
+    }
+
+    fn constant(&mut self, value: Value) -> usize {
+        self.program.constants.push(value);
+        self.program.constants.len() - 1
+    }
+
+    /// Append an instruction, returning its index.
+    fn emit(&mut self, instruction: Instruction) -> usize {
+        self.program.code.push(instruction);
+        self.program.code.len() - 1
+    }
+
+    /// Point the jump at `at` to the next instruction to be emitted.
+    fn patch(&mut self, at: usize) {
+        let next = self.program.code.len();
+        match &mut self.program.code[at] {
+            Instruction::Jump(target)
+            | Instruction::JumpIfFalsy(target)
+            | Instruction::JumpIfTruthyOrPop(target)
+            | Instruction::JumpIfFalsyOrPop(target)
+            | Instruction::JumpIfNotNullOrPop(target)
+            | Instruction::Iterate { end: target, .. }
+            | Instruction::Pipe { end: target }
+            | Instruction::Try { end: target, .. }
+            | Instruction::Match { end: target, .. }
+            | Instruction::Switch { end: target, .. } => *target = next,
+            Instruction::Var {
+                default: Some(target),
+                ..
+            } => *target = next,
+            instruction => unreachable!("{:?} is not a jump", instruction),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_compiled {
+    use super::*;
+    use serde_json::json;
+
+    #[cfg(feature = "std")]
+    use crate::Profiler;
+    use crate::{Namespace, DEFAULT_MAX_CALL_DEPTH};
+
+    fn check(rule: &Rule, data: &Value) {
+        [SemanticsVersion::V1, SemanticsVersion::LATEST]
+            .iter()
+            .for_each(|&semantics| {
+                let ctx = Context {
+                    semantics,
+                    ..Context::default()
+                };
+                let compiled = rule.compile().unwrap();
+                match (rule.apply_with(data, &ctx), compiled.apply_with(data, &ctx)) {
+                    (Ok(expected), Ok(actual)) => assert_eq!(actual, expected),
+                    (Err(expected), Err(actual)) => {
+                        assert_eq!(actual.to_string(), expected.to_string())
+                    }
+                    (expected, actual) => {
+                        panic!("expected {:?}, got {:?}", expected, actual)
+                    }
+                }
+            })
+    }
+
+    #[test]
+    fn test_compile() {
+        let data = json!({"xs": [1, 2, 3], "s": "abc", "k": "xs.1", "o": {"a": null}});
+        [
+            json!({"if": []}),
+            json!({"if": [{"var": "xs"}]}),
+            json!({"if": [false, 1, {"var": "nope"}, 2]}),
+            json!({"?:": [0, 1, "", 2, {"cat": ["x", {"var": "s"}]}]}),
+            json!({"or": [0, "", {"var": "xs.2"}, {"/": [1, 0]}]}),
+            json!({"and": [1, [], {"/": [1, 0]}]}),
+            json!({"var": {"var": "k"}}),
+            json!({"var": [{"var": "k"}, 9]}),
+            json!({"var": ["o.a", 1]}),
+            json!({"var": ["nope", {"var": "s"}]}),
+            json!({"var": ["nope", {"if": [true, {"+": [1, 2]}]}]}),
+            json!({"var": ["nope", {"lit": {"var": "s"}}]}),
+            json!({"var": [{"merge": ["not", "a", "key"]}]}),
+            json!({"map": [{"var": "xs"}, {"*": [{"var": ""}, 2]}]}),
+            json!({"map": [{"var": "s"}, 1]}),
+            json!({"filter": [{"var": "xs"}, {"%": [{"var": ""}, 2]}]}),
+            json!({"reduce": [
+                {"var": "xs"},
+                {"+": [{"var": "current"}, {"var": "accumulator"}]},
+                {"var": "xs.0"}
+            ]}),
+            json!({"reduce": [1, 1, 1]}),
+            json!({"reduce_while": [
+                {"var": "xs"},
+                {"if": [
+                    {">": [{"var": "accumulator"}, 2]},
+                    {"reduced": {"var": "accumulator"}},
+                    {"+": [{"var": "current"}, {"var": "accumulator"}]}
+                ]},
+                0
+            ]}),
+            json!({"reduce_while": [{"var": "xs"}, {"reduced": {"var": "#index"}}, null]}),
+            json!({"reduce_while": [1, 1, 1]}),
+            json!({"obj": [["a", {"var": "xs.0"}], [{"var": "s"}, {"var": "o"}], ["a", 2]]}),
+            json!({"obj": [[1, 2]]}),
+            json!({"map_values": [{"var": "o"}, {"cat": [{"var": ""}, {"var": "../s"}]}]}),
+            json!({"map_values": [{"obj": [["x", 1], ["y", 2]]}, {"*": [{"var": ""}, 2]}]}),
+            json!({"map_values": [{"var": "xs"}, 1]}),
+            json!({"switch": [{"var": "s"}, {"abc": {"var": "xs"}, "d": 1}, 2]}),
+            json!({"switch": [{"var": "s"}, {"d": 1}, {"var": "o"}]}),
+            json!({"switch": [{"var": "xs"}, {"a": 1}]}),
+            json!({"switch": ["a", {"a": {"var": "nope"}}]}),
+            json!({"all": [{"var": "s"}, {"!=": [{"var": ""}, "d"]}]}),
+            json!({"all": [[], true]}),
+            json!({"some": [[{"var": "s"}, "d"], {"==": [{"var": ""}, "abc"]}]}),
+            json!({"none": [null, true]}),
+            json!({"none": [1, true]}),
+            json!({"missing_some": [1, ["nope", "xs"]]}),
+            json!({"missing": [{"merge": ["xs", "nope"]}]}),
+            json!({"in": ["b", {"substr": [{"var": "s"}, 1]}]}),
+            json!({"zip_with": [{"var": "xs"}, [10, 20], {"+": [{"var": "0"}, {"var": "1"}]}]}),
+            json!({"zip_with": [{"var": "xs"}, {"var": "s"}, 1]}),
+            json!({"find": [{"var": "xs"}, {">": [{"var": ""}, 1]}]}),
+            json!({"position": [{"var": "xs"}, {"==": [{"var": ""}, 3]}]}),
+            json!({"min_by": [[{"n": 2}, {"n": 1}], {"var": "n"}]}),
+            json!({"max_by": [{"var": "xs"}, {"-": [0, {"var": ""}]}]}),
+            json!({"sum_by": [{"var": "xs"}, {"*": [{"var": ""}, 2]}]}),
+            json!({"sum_by": [{"var": "s"}, 1]}),
+            json!({"map": [{"var": "xs"}, {"*": [{"var": "#index"}, {"var": ""}]}]}),
+            json!({"filter": [{"var": "s"}, {"!=": [{"var": "#index"}, 1]}]}),
+            json!({"reduce": [{"var": "xs"}, {"var": "#index"}, null]}),
+            json!({"some": [{"var": "xs"}, {"==": [{"var": "#index"}, 2]}]}),
+            json!({"zip_with": [{"var": "xs"}, {"var": "xs"}, {"var": "#index"}]}),
+            json!({"var": "#index"}),
+            json!({"filter": [{"var": "xs"}, {">": [{"var": ""}, {"var": "../xs.0"}]}]}),
+            json!({"map": [
+                [[1, 2], [3]],
+                {"map": [{"var": ""}, {"cat": [{"var": "../../s"}, {"var": "../#index"}]}]}
+            ]}),
+            json!({"map": [{"var": "xs"}, {"var": ["../../s", {"var": "../s"}]}]}),
+            json!({"distinct_by": [{"var": "xs"}, {"%": [{"var": ""}, 2]}]}),
+            json!({"distinct_by": [[{"a": [1]}, {"a": [1]}, {"a": 1}], {"var": "a"}]}),
+            json!({"sort_by": [[{"n": 2}, {"n": 1}], {"var": "n"}]}),
+            json!({"sort_by": [{"var": "xs"}, {"var": ""}, {"var": "o.a"}]}),
+            json!({"sort_by": [[[1, "b"], [1, "a"]], {"var": ""}, [false, true]]}),
+            json!({"sort_with": [{"var": "xs"}, {"-": [{"var": "b"}, {"var": "a"}]}]}),
+            json!({"sort_with": [{"var": "xs"}, {"-": [{"var": "b"}, {"var": "a"}]}, true]}),
+            json!({"sort_with": [{"var": "xs"}, "x"]}),
+            json!({"pipe": [{"var": "xs"}, {"map": [{"var": ""}, 1]}, {"var": "1"}]}),
+            json!({"pipe": [{"var": "o"}, {"var": "a"}, {"if": [{"var": ""}, 1, 2]}]}),
+            json!({"try": [{"var": "xs"}, 1]}),
+            json!({"try": [{"+": [{"var": "s"}, 1]}, {"var": "xs.1"}]}),
+            json!({"try": [{"+": [{"var": "s"}, 1]}, {"var": "e"}, "e"]}),
+            json!({"match": [{"var": "o"}, {"shape": {"a": {"bind": "a"}}}, {"var": "a"}, 0]}),
+            json!({"match": [{"var": "xs"}, [1, 2], "pair", {"type": "array"}, {"var": "o"}]}),
+        ]
+        .iter()
+        .for_each(|rule| check(&Rule::from_value(rule).unwrap(), &data));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_compile_functions() {
+        let mut ns = Namespace::new();
+        ns.define(&json!({"defn": ["fact", ["n"], {"if": [
+            {"<=": [{"param": "n"}, 1]},
+            1,
+            {"*": [{"param": "n"}, {"fact": [{"-": [{"param": "n"}, 1]}]}]}
+        ]}]}))
+        .unwrap();
+        ns.define(&json!({"defn": ["scale", ["xs", "by"], {"map": [
+            {"param": "xs"},
+            {"*": [{"var": ""}, {"param": "by"}]}
+        ]}]}))
+        .unwrap();
+        let rule = ns
+            .parse(&json!({"scale": [{"var": "xs"}, {"fact": [4]}]}))
+            .unwrap()
+            .with_id("scaled");
+        check(&rule, &json!({"xs": [1, 2]}));
+
+        let compiled = rule.compile().unwrap();
+        assert_eq!(compiled.id(), Some("scaled"));
+        let profiler = Arc::new(Profiler::new());
+        let ctx = Context {
+            profiler: Some(profiler.clone()),
+            ..Context::default()
+        };
+        compiled.apply_with(&json!({"xs": [1, 2]}), &ctx).unwrap();
+        let calls = |operator: &str| {
+            profiler
+                .stats()
+                .into_iter()
+                .find(|stats| stats.operator == operator)
+                .map(|stats| stats.calls)
+        };
+        assert_eq!(calls("fact"), Some(4));
+        assert_eq!(calls("map"), Some(1));
+        assert_eq!(calls("*"), Some(5));
+    }
+
+    #[test]
+    fn test_tail_calls() {
+        let mut ns = Namespace::new();
+        // Tail calls through `if` and `or`
+        ns.define(&json!({"defn": ["sum", ["n", "total"], {"if": [
+            {"<=": [{"param": "n"}, 0]},
+            {"param": "total"},
+            {"or": [
+                {"==": [{"param": "n"}, -1]},
+                {"sum": [
+                    {"-": [{"param": "n"}, 1]},
+                    {"+": [{"param": "total"}, {"param": "n"}]}
+                ]}
+            ]}
+        ]}]}))
+        .unwrap();
+        // Not a tail call, as its result is an argument to `+`
+        ns.define(&json!({"defn": ["count", ["n"], {"if": [
+            {"<=": [{"param": "n"}, 0]},
+            0,
+            {"+": [1, {"count": [{"-": [{"param": "n"}, 1]}]}]}
+        ]}]}))
+        .unwrap();
+        let rule = ns
+            .parse(&json!({"+": [{"sum": [{"var": "n"}, 0]}, {"count": [3]}]}))
+            .unwrap();
+        check(&rule, &json!({"n": 10}));
+
+        let compiled = rule.compile().unwrap();
+        let tail_calls = |name: &str| {
+            let function = compiled
+                .program
+                .functions
+                .iter()
+                .position(|function| function.name == name)
+                .unwrap();
+            compiled
+                .program
+                .code
+                .iter()
+                .filter(|instruction| {
+                    matches!(instruction, Instruction::TailCall { function: f, .. } if *f == function)
+                })
+                .count()
+        };
+        assert_eq!(tail_calls("sum"), 1);
+        assert_eq!(tail_calls("count"), 0);
+        // Deep enough to overflow the stack without tail calls
+        assert_eq!(
+            compiled.apply(&json!({"n": 100_000})).unwrap(),
+            json!(5_000_050_003_u64)
+        );
+        check(&rule, &json!({"n": 100_000}));
+    }
+
+    #[test]
+    fn test_call_depth() {
+        let mut ns = Namespace::new();
+        ns.define(&json!({"defn": ["count", ["n"], {"if": [
+            {"<=": [{"param": "n"}, 0]},
+            0,
+            {"+": [1, {"count": [{"-": [{"param": "n"}, 1]}]}]}
+        ]}]}))
+        .unwrap();
+        let rule = ns.parse(&json!({"count": [{"var": "n"}]})).unwrap();
+        let compiled = rule.compile().unwrap();
+        let depth = DEFAULT_MAX_CALL_DEPTH;
+        for n in [depth - 1, depth, 100_000] {
+            check(&rule, &json!({ "n": n }));
+        }
+        assert_eq!(
+            rule.apply(&json!({"n": depth - 1})).unwrap(),
+            json!(depth - 1)
+        );
+        assert!(matches!(
+            compiled.apply(&json!({"n": depth})),
+            Err(Error::CallDepthExceeded { limit, .. }) if limit == depth
+        ));
+
+        let ctx = Context {
+            max_call_depth: Some(10),
+            ..Context::default()
+        };
+        assert_eq!(rule.apply_with(&json!({"n": 9}), &ctx).unwrap(), json!(9));
+        for result in [
+            rule.apply_with(&json!({"n": 10}), &ctx),
+            compiled.apply_with(&json!({"n": 10}), &ctx),
+        ] {
+            assert_eq!(result.unwrap_err().code(), "call_depth_exceeded");
+        }
+    }
+}
@@ -0,0 +1,315 @@
+//! Branch Coverage
+//!
+//! `Rule::coverage()` evaluates a rule against a corpus of data, recording
+//! which branches of its `if`, `and`, `or`, and `match` operations were
+//! taken, to find logic that no input ever reaches.
+//!
+//! The branches of an operation are the arguments that may become its
+//! result:
+//!
+//!   - for `if`, each branch following a condition, and the final `else`
+//!   - for `and` and `or`, each argument, since evaluation stops at the
+//!     first that is falsy (for `and`) or truthy (for `or`)
+//!   - for `match`, each arm's branch, and the default
+//!
+//! Branches are identified by their JSON pointer within the rule, e.g.
+//! `/and/1/if/2`, or within the body of the function that contains them.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde_json::Value;
+
+use crate::context::{Context, Env};
+use crate::op::pattern;
+use crate::rule::Rule;
+use crate::value::Parsed;
+
+/// The number of times each branch was taken, keyed by the address of the
+/// arguments of the operation and the index of the branch among them
+///
+/// Addresses identify operations since the rule is borrowed, and so can't
+/// move, for as long as branches are recorded.
+#[derive(Debug, Default)]
+pub(crate) struct Branches(Mutex<HashMap<(usize, usize), u64>>);
+impl Branches {
+    /// Record that the argument at `index` became the operation's result.
+    pub(crate) fn taken(&self, args: &[Parsed], index: usize) {
+        *self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .entry((args.as_ptr() as usize, index))
+            .or_default() += 1;
+    }
+
+    fn count(&self, args: &[Parsed], index: usize) -> u64 {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(&(args.as_ptr() as usize, index))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+/// The branches of a rule taken across a corpus of data
+#[derive(Clone, Debug, PartialEq)]
+pub struct CoverageReport {
+    /// The number of pieces of data the rule was evaluated against
+    pub evaluations: usize,
+    /// The number of those evaluations that failed. Branches taken before
+    /// an evaluation failed are still counted.
+    pub errors: usize,
+    /// Every branch in the rule and the functions it may call, in order
+    pub branches: Vec<BranchCoverage>,
+}
+impl CoverageReport {
+    /// The branches that were never taken
+    pub fn untaken(&self) -> impl Iterator<Item = &BranchCoverage> {
+        self.branches.iter().filter(|branch| branch.taken == 0)
+    }
+
+    /// The fraction of branches that were taken, which is 1 for rules
+    /// without branches
+    pub fn ratio(&self) -> f64 {
+        if self.branches.is_empty() {
+            return 1.0;
+        }
+        let taken = self.branches.iter().filter(|branch| branch.taken > 0);
+        taken.count() as f64 / self.branches.len() as f64
+    }
+}
+
+/// A single branch, and how often it was taken
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BranchCoverage {
+    /// The function containing the branch, or None if it's in the rule
+    pub function: Option<String>,
+    /// The JSON pointer to the branch within the rule or function body
+    pub path: String,
+    /// The operator the branch belongs to, e.g. `if`
+    pub operator: String,
+    /// The number of times the branch was taken
+    pub taken: u64,
+}
+
+impl Rule {
+    /// Evaluate the rule against each piece of data, reporting which
+    /// branches were taken.
+    ///
+    /// The branches of an operation are the arguments that may become its
+    /// result: for `if`, those following each condition and the final else;
+    /// for `and` and `or`, every argument, since evaluation stops at the
+    /// first that decides the result; and for `match`, each arm's branch
+    /// and the default. Each is identified by its JSON pointer within the
+    /// rule, or within the body of the function containing it.
+    pub fn coverage<'d, I>(&self, data: I) -> CoverageReport
+    where
+        I: IntoIterator<Item = &'d Value>,
+    {
+        self.coverage_with(data, &Context::default())
+    }
+
+    /// Evaluate the rule against each piece of data with the given context,
+    /// reporting which branches were taken.
+    pub fn coverage_with<'d, I>(&self, data: I, ctx: &Context) -> CoverageReport
+    where
+        I: IntoIterator<Item = &'d Value>,
+    {
+        let branches = Branches::default();
+        let (mut evaluations, mut errors) = (0, 0);
+        for data in data {
+            let env = Env::new(ctx, self.id())
+                .with_semantics(self.semantics)
+                .with_functions(self.functions.clone())
+                .with_branches(&branches);
+            evaluations += 1;
+            if self.parsed.evaluate(data, &env).is_err() {
+                errors += 1;
+            }
+        }
+
+        let mut report = Vec::new();
+        collect(
+            &self.parsed,
+            &mut String::new(),
+            None,
+            &branches,
+            &mut report,
+        );
+        let mut functions: Vec<_> =
+            self.functions.iter().flat_map(|f| f.iter()).collect();
+        functions.sort_by(|a, b| a.0.cmp(b.0));
+        for (name, body) in functions {
+            let expression = &body.expression;
+            collect(
+                expression,
+                &mut String::new(),
+                Some(name),
+                &branches,
+                &mut report,
+            );
+        }
+        CoverageReport {
+            evaluations,
+            errors,
+            branches: report,
+        }
+    }
+}
+
+/// The indices of the arguments of an operation that are its branches
+fn branch_indices(operator: &str, len: usize) -> Vec<usize> {
+    match operator {
+        "if" | "?:" if len > 1 => (1..len)
+            .filter(|idx| idx % 2 == 1 || *idx == len - 1)
+            .collect(),
+        "and" | "or" => (0..len).collect(),
+        pattern::OPERATOR if len > 1 => (2..len)
+            .step_by(2)
+            .chain(Some(len - 1).filter(|idx| idx % 2 == 1))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Collect the coverage of the branches in an expression at `path`.
+fn collect(
+    parsed: &Parsed,
+    path: &mut String,
+    function: Option<&str>,
+    branches: &Branches,
+    report: &mut Vec<BranchCoverage>,
+) {
+    let (key, args): (&str, &[Parsed]) = match parsed {
+        Parsed::LazyOperation(op) => {
+            for idx in branch_indices(op.symbol(), op.arguments().len()) {
+                report.push(BranchCoverage {
+                    function: function.map(String::from),
+                    path: format!("{}/{}/{}", path, escape(op.symbol()), idx),
+                    operator: op.symbol().into(),
+                    taken: branches.count(op.arguments(), idx),
+                });
+            }
+            (op.symbol(), op.arguments())
+        }
+        Parsed::Operation(op) => (op.symbol(), op.arguments()),
+        Parsed::DataOperation(op) => (op.symbol(), op.arguments()),
+        Parsed::CustomOperation(op) => (op.operator().name(), op.arguments()),
+        Parsed::Variable(var) => ("var", var.arguments()),
+        Parsed::Call(call) => (call.name(), call.arguments()),
+        Parsed::Object(obj) => {
+            for (key, value) in obj.entries() {
+                let len = path.len();
+                path.push('/');
+                path.push_str(&escape(key));
+                collect(value, path, function, branches, report);
+                path.truncate(len);
+            }
+            return;
+        }
+        Parsed::Param(_) | Parsed::Raw(_) => return,
+    };
+    for (idx, arg) in args.iter().enumerate() {
+        let len = path.len();
+        path.push_str(&format!("/{}/{}", escape(key), idx));
+        collect(arg, path, function, branches, report);
+        path.truncate(len);
+    }
+}
+
+/// Escape a key for use in a JSON pointer.
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod test_coverage {
+    use super::*;
+    use serde_json::json;
+
+    use crate::Namespace;
+
+    fn taken(report: &CoverageReport) -> Vec<(Option<&str>, &str, u64)> {
+        report
+            .branches
+            .iter()
+            .map(|branch| {
+                (
+                    branch.function.as_deref(),
+                    branch.path.as_str(),
+                    branch.taken,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_coverage() {
+        let rule = Rule::from_value(&json!({"if": [
+            {"and": [{"var": "a"}, {"var": "b"}]},
+            "both",
+            {"<": [{"var": "a"}, 0]},
+            "negative",
+            {"or": [{"var": "c"}, "neither"]}
+        ]}))
+        .unwrap();
+        let data = [json!({"a": 1, "b": 1}), json!({"a": 1, "b": 0})];
+        let report = rule.coverage(&data);
+        assert_eq!(report.evaluations, 2);
+        assert_eq!(report.errors, 0);
+        assert_eq!(
+            taken(&report),
+            vec![
+                (None, "/if/1", 1),
+                (None, "/if/3", 0),
+                (None, "/if/4", 1),
+                (None, "/if/0/and/0", 0),
+                (None, "/if/0/and/1", 2),
+                (None, "/if/4/or/0", 0),
+                (None, "/if/4/or/1", 1),
+            ]
+        );
+        assert_eq!(
+            report
+                .untaken()
+                .map(|branch| branch.path.as_str())
+                .collect::<Vec<_>>(),
+            vec!["/if/3", "/if/0/and/0", "/if/4/or/0"]
+        );
+        assert!((report.ratio() - 4.0 / 7.0).abs() < f64::EPSILON);
+        assert_eq!(
+            Rule::from_value(&json!(1)).unwrap().coverage(&data).ratio(),
+            1.0
+        );
+    }
+
+    #[test]
+    fn test_match_and_functions() {
+        let mut ns = Namespace::new();
+        ns.define(&json!({"defn": ["sign", ["x"], {"match": [
+            {"param": "x"},
+            0, "zero",
+            {"type": "number"}, "number",
+            "other"
+        ]}]}))
+        .unwrap();
+        let rule = ns
+            .parse(&json!({"cat": [{"sign": [{"var": "x"}]}, "!"]}))
+            .unwrap();
+        let report =
+            rule.coverage(&[json!({"x": 0}), json!({"x": 2}), json!({"x": 2})]);
+        assert_eq!(
+            taken(&report),
+            vec![
+                (Some("sign"), "/match/2", 1),
+                (Some("sign"), "/match/4", 2),
+                (Some("sign"), "/match/5", 0),
+            ]
+        );
+
+        let report = rule.coverage(&[json!({"x": null})]);
+        assert_eq!(report.branches[2].taken, 1);
+    }
+}
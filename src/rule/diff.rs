@@ -0,0 +1,177 @@
+//! Rule Diffs
+//!
+//! `Rule::diff()` compares the canonical forms of two rules, so changes in
+//! presentation alone (key order, aliases, etc.) aren't reported.
+
+use serde_json::Value;
+
+use crate::prelude::*;
+use crate::rule::Rule;
+
+/// A changed sub-expression between two rules
+///
+/// Paths are JSON Pointers into the canonical form of the rules, e.g.
+/// `/and/1/==/0` for the first argument of the `==` that is the second
+/// argument of `and`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    /// A sub-expression present only in the new rule
+    Added { path: String, value: Value },
+    /// A sub-expression present only in the old rule
+    Removed { path: String, value: Value },
+    /// A sub-expression that differs between the rules
+    Changed {
+        path: String,
+        old: Value,
+        new: Value,
+    },
+}
+impl Change {
+    /// The path of the changed sub-expression
+    pub fn path(&self) -> &str {
+        match self {
+            Self::Added { path, .. } | Self::Removed { path, .. } => path,
+            Self::Changed { path, .. } => path,
+        }
+    }
+}
+
+impl Rule {
+    /// The sub-expressions that differ between this rule and another.
+    ///
+    /// Changes are reported at the deepest point at which the rules still
+    /// share a structure: if the operators at some path differ, the whole
+    /// sub-expression is reported as changed. Functions the rules may call
+    /// are not compared; use `semantically_equal()` for that.
+    pub fn diff(&self, other: &Rule) -> Vec<Change> {
+        let mut changes = Vec::new();
+        diff_values(
+            &mut changes,
+            &mut String::new(),
+            &self.canonicalize(),
+            &other.canonicalize(),
+        );
+        changes
+    }
+}
+
+fn diff_values(changes: &mut Vec<Change>, path: &mut String, old: &Value, new: &Value) {
+    match (old, new) {
+        (Value::Array(old), Value::Array(new)) => {
+            for i in 0..old.len().max(new.len()) {
+                let parent_len = path.len();
+                path.push('/');
+                path.push_str(&i.to_string());
+                match (old.get(i), new.get(i)) {
+                    (Some(old), Some(new)) => diff_values(changes, path, old, new),
+                    (Some(old), None) => changes.push(Change::Removed {
+                        path: path.clone(),
+                        value: old.clone(),
+                    }),
+                    (None, Some(new)) => changes.push(Change::Added {
+                        path: path.clone(),
+                        value: new.clone(),
+                    }),
+                    (None, None) => {}
+                }
+                path.truncate(parent_len);
+            }
+        }
+        (Value::Object(old), Value::Object(new))
+            if old.len() == new.len() && old.keys().eq(new.keys()) =>
+        {
+            for (key, old) in old {
+                let parent_len = path.len();
+                path.push('/');
+                path.push_str(&key.replace('~', "~0").replace('/', "~1"));
+                diff_values(changes, path, old, &new[key]);
+                path.truncate(parent_len);
+            }
+        }
+        (old, new) if old != new => changes.push(Change::Changed {
+            path: path.clone(),
+            old: old.clone(),
+            new: new.clone(),
+        }),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test_diff {
+    use super::*;
+    use serde_json::json;
+
+    use crate::Namespace;
+
+    fn rule(value: Value) -> Rule {
+        Rule::from_value(&value).unwrap()
+    }
+
+    #[test]
+    fn test_semantically_equal() {
+        let a = rule(json!({"and": [{">": [{"var": "x"}, 1]}]}));
+        assert!(a.semantically_equal(&rule(json!({">": [{"var": ["x", null]}, 1]}))));
+        assert!(!a.semantically_equal(&rule(json!({">": [{"var": "x"}, 2]}))));
+        // `1.0` and `1` give different results for some operators
+        assert!(!rule(json!({"cat": ["x", 1.0]}))
+            .semantically_equal(&rule(json!({"cat": ["x", 1]}))));
+        assert!(!rule(json!({"in": [{"var": "a"}, [1.0]]}))
+            .semantically_equal(&rule(json!({"in": [{"var": "a"}, [1]]}))));
+
+        let mut ns = Namespace::new();
+        ns.define(&json!({"defn": ["f", [], 1]})).unwrap();
+        let mut other = Namespace::new();
+        other
+            .define(&json!({"defn": ["f", [], {"and": [1]}]}))
+            .unwrap();
+        let call = json!({"f": []});
+        assert!(ns
+            .parse(&call)
+            .unwrap()
+            .semantically_equal(&other.parse(&call).unwrap()));
+        other.define(&json!({"defn": ["f", [], 2]})).unwrap();
+        assert!(!ns
+            .parse(&call)
+            .unwrap()
+            .semantically_equal(&other.parse(&call).unwrap()));
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = rule(json!({"and": [
+            {">": [{"var": "age"}, 18]},
+            {"in": [{"var": "country"}, ["US", "CA"]]}
+        ]}));
+        let new = rule(json!({"and": [
+            {">=": [{"var": "age"}, 18.0]},
+            {"in": [{"var": "country"}, ["US", "MX", "CA"]]},
+            {"var": "active"}
+        ]}));
+        assert_eq!(
+            old.diff(&new),
+            vec![
+                Change::Changed {
+                    path: "/and/0".into(),
+                    old: json!({">": [{"var": ["age"]}, 18]}),
+                    new: json!({">=": [{"var": ["age"]}, 18.0]}),
+                },
+                Change::Changed {
+                    path: "/and/1/in/1/1".into(),
+                    old: json!("CA"),
+                    new: json!("MX"),
+                },
+                Change::Added {
+                    path: "/and/1/in/1/2".into(),
+                    value: json!("CA"),
+                },
+                Change::Added {
+                    path: "/and/2".into(),
+                    value: json!({"var": ["active"]}),
+                },
+            ]
+        );
+        assert_eq!(new.diff(&old)[3].path(), "/and/2");
+        assert!(old.diff(&old).is_empty());
+    }
+}
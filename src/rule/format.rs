@@ -0,0 +1,291 @@
+//! Formatting
+//!
+//! Render rules as JSON laid out for people to read, e.g. in code review.
+
+use serde_json::{json, Value};
+
+use crate::ast::Ast;
+use crate::op::data::Variable;
+use crate::op::object::OBJ;
+use crate::prelude::*;
+use crate::rule::Rule;
+
+/// How to lay out a formatted rule
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FormatStyle {
+    /// The number of spaces per level of indentation
+    pub indent: usize,
+    /// The column beyond which expressions are split across lines
+    pub width: usize,
+}
+impl Default for FormatStyle {
+    fn default() -> Self {
+        Self {
+            indent: 2,
+            width: 80,
+        }
+    }
+}
+
+impl Rule {
+    /// Render the rule as JSON, laid out according to the given style.
+    ///
+    /// Expressions that fit within the style's width are rendered on one
+    /// line, and others have one argument per line. The operands of `and`
+    /// and `or` are always rendered one per line. For example:
+    ///
+    /// ```json
+    /// {"and": [
+    ///   {">=": [{"var": "age"}, 18]},
+    ///   {"in": [{"var": "country"}, ["US", "CA"]]}
+    /// ]}
+    /// ```
+    pub fn format(&self, style: FormatStyle) -> String {
+        Formatter { style }.render(&normalize(&self.ast()), 0, 0)
+    }
+}
+
+/// Convert an AST into JSON, writing `var`s with only a path in the
+/// conventional short form, e.g. `{"var": "a"}` rather than `{"var": ["a"]}`.
+fn normalize(ast: &Ast) -> Value {
+    let mut value = Value::from(ast.clone());
+    shorten_vars(ast, &mut value);
+    value
+}
+
+/// Shorten the `var`s in the JSON an AST was converted into, walking the
+/// two alongside each other so that literals are left as they were written.
+fn shorten_vars(ast: &Ast, value: &mut Value) {
+    // The arguments of the operation the value is
+    fn operands(value: &mut Value) -> &mut [Value] {
+        match value
+            .as_object_mut()
+            .and_then(|map| map.values_mut().next())
+        {
+            Some(Value::Array(args)) => args,
+            _ => &mut [],
+        }
+    }
+    match ast {
+        Ast::Literal(_) | Ast::Param(_) => {}
+        Ast::Var { path, default } => {
+            let args = operands(value);
+            path.iter()
+                .chain(default)
+                .zip(args.iter_mut())
+                .for_each(|(ast, arg)| shorten_vars(ast, arg));
+            if default.is_none() && args.len() == 1 && !args[0].is_object() {
+                *value = json!({ Variable::OPERATOR: args[0].take() });
+            }
+        }
+        // Arguments are grouped into [key, value] pairs
+        Ast::Operator { name, args } if name == OBJ => args
+            .chunks(2)
+            .zip(operands(value))
+            .for_each(|(asts, pair)| {
+                if let Value::Array(pair) = pair {
+                    asts.iter()
+                        .zip(pair)
+                        .for_each(|(ast, item)| shorten_vars(ast, item))
+                }
+            }),
+        Ast::Operator { args, .. } | Ast::FunctionCall { args, .. } => args
+            .iter()
+            .zip(operands(value))
+            .for_each(|(ast, arg)| shorten_vars(ast, arg)),
+        Ast::FunctionDef { body, .. } => {
+            if let Some(arg) = operands(value).get_mut(2) {
+                shorten_vars(body, arg)
+            }
+        }
+        Ast::Object(entries) => {
+            if let Value::Object(map) = value {
+                entries.iter().for_each(|(key, ast)| {
+                    if let Some(value) = map.get_mut(key) {
+                        shorten_vars(ast, value)
+                    }
+                })
+            }
+        }
+    }
+}
+
+/// Whether a value is an `and` or `or` with more than one operand
+fn is_boolean_chain(value: &Value) -> bool {
+    match value.as_object() {
+        Some(map) if map.len() == 1 => map.iter().any(|(key, args)| {
+            (key == "and" || key == "or")
+                && args.as_array().is_some_and(|args| args.len() > 1)
+        }),
+        _ => false,
+    }
+}
+
+/// Render a value on a single line.
+fn inline(value: &Value) -> String {
+    match value {
+        Value::Array(items) => format!(
+            "[{}]",
+            items.iter().map(inline).collect::<Vec<String>>().join(", ")
+        ),
+        Value::Object(map) => format!(
+            "{{{}}}",
+            map.iter()
+                .map(|(key, value)| format!(
+                    "{}: {}",
+                    Value::from(key.as_str()),
+                    inline(value)
+                ))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+        value => value.to_string(),
+    }
+}
+
+struct Formatter {
+    style: FormatStyle,
+}
+impl Formatter {
+    /// Render a value nested `depth` levels deep, starting at `column`.
+    fn render(&self, value: &Value, depth: usize, column: usize) -> String {
+        let rendered = inline(value);
+        if !is_boolean_chain(value)
+            && column + rendered.chars().count() < self.style.width
+        {
+            return rendered;
+        }
+        match value {
+            // An operation, whose arguments are indented beneath it
+            Value::Object(map) if map.len() == 1 => {
+                let (key, args) = map.iter().next().unwrap();
+                let key = Value::from(key.as_str()).to_string();
+                match args {
+                    Value::Array(items) if !items.is_empty() => {
+                        format!("{{{}: {}}}", key, self.lines(items, depth))
+                    }
+                    arg => format!(
+                        "{{{}: {}}}",
+                        key,
+                        self.render(arg, depth, column + key.len() + 3)
+                    ),
+                }
+            }
+            Value::Object(map) if !map.is_empty() => {
+                let pad = self.pad(depth + 1);
+                let entries = map
+                    .iter()
+                    .map(|(key, value)| {
+                        let key = Value::from(key.as_str()).to_string();
+                        let column = pad.len() + key.len() + 2;
+                        format!(
+                            "{}{}: {}",
+                            pad,
+                            key,
+                            self.render(value, depth + 1, column)
+                        )
+                    })
+                    .collect::<Vec<String>>();
+                format!("{{\n{}\n{}}}", entries.join(",\n"), self.pad(depth))
+            }
+            Value::Array(items) if !items.is_empty() => self.lines(items, depth),
+            _ => rendered,
+        }
+    }
+
+    /// Render items one per line in an array.
+    fn lines(&self, items: &[Value], depth: usize) -> String {
+        let pad = self.pad(depth + 1);
+        let lines = items
+            .iter()
+            .map(|item| {
+                format!("{}{}", pad, self.render(item, depth + 1, pad.len() + 1))
+            })
+            .collect::<Vec<String>>();
+        format!("[\n{}\n{}]", lines.join(",\n"), self.pad(depth))
+    }
+
+    fn pad(&self, depth: usize) -> String {
+        " ".repeat(depth * self.style.indent)
+    }
+}
+
+#[cfg(test)]
+mod test_format {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_format() {
+        let rule = Rule::from_value(&json!({"if": [
+            {"and": [
+                {">=": [{"var": "age"}, 18]},
+                {"in": [{"var": "country"}, ["US", "CA"]]},
+                {"or": [{"var": "verified"}, {"!": {"var": "flagged"}}]}
+            ]},
+            {"cat": ["Welcome, ", {"var": ["name", "friend"]}, "! Your account is ready to use."]},
+            {"missing_some": [1, ["email", "phone"]]}
+        ]}))
+        .unwrap();
+        let expected = r#"{"if": [
+  {"and": [
+    {">=": [{"var": "age"}, 18]},
+    {"in": [{"var": "country"}, ["US", "CA"]]},
+    {"or": [
+      {"var": "verified"},
+      {"!": [{"var": "flagged"}]}
+    ]}
+  ]},
+  {"cat": [
+    "Welcome, ",
+    {"var": ["name", "friend"]},
+    "! Your account is ready to use."
+  ]},
+  {"missing_some": [1, ["email", "phone"]]}
+]}"#;
+        let formatted = rule.format(FormatStyle::default());
+        assert_eq!(formatted, expected, "\n{}", formatted);
+        assert_eq!(
+            serde_json::from_str::<Value>(&formatted).unwrap(),
+            normalize(&rule.ast())
+        );
+
+        let narrow = FormatStyle {
+            indent: 4,
+            width: 30,
+        };
+        assert_eq!(
+            Rule::from_value(&json!({"+": [{"var": "a"}, 1]}))
+                .unwrap()
+                .format(narrow),
+            r#"{"+": [{"var": "a"}, 1]}"#
+        );
+        assert_eq!(
+            Rule::from_value(&json!({"merge": [[1, 2, 3], {"var": "longer.path"}]}))
+                .unwrap()
+                .format(narrow),
+            "{\"merge\": [\n    [1, 2, 3],\n    {\"var\": \"longer.path\"}\n]}"
+        );
+    }
+
+    #[test]
+    fn test_format_leaves_literals() {
+        let format = |value: Value| {
+            Rule::from_value(&value)
+                .unwrap()
+                .format(FormatStyle::default())
+        };
+        assert_eq!(
+            format(json!({"lit": {"var": ["a"]}})),
+            r#"{"lit": {"var": ["a"]}}"#
+        );
+        assert_eq!(
+            format(json!({"==": [{"var": ["a"]}, {"a": {"var": ["b"]}, "c": 1}]})),
+            r#"{"==": [{"var": "a"}, {"a": {"var": ["b"]}, "c": 1}]}"#
+        );
+        assert_eq!(
+            format(json!({"obj": [["k", {"var": ["a"]}]]})),
+            r#"{"obj": [["k", {"var": "a"}]]}"#
+        );
+    }
+}
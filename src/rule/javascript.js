@@ -0,0 +1,1266 @@
+// The runtime for rules translated by `Rule::to_javascript()`.
+//
+// Each operator mirrors the crate's own implementation, so that translated
+// rules give the same results, and fail in the same cases. `semantics` is the
+// number of the semantics version to evaluate with.
+const $ = (() => {
+  const isObject = (value) => value !== null && typeof value === "object";
+  const has = (object, key) => Object.prototype.hasOwnProperty.call(object, key);
+
+  const fail = (operator, reason) => {
+    throw new Error(`Invalid argument for '${operator}': ${reason}`);
+  };
+
+  const truthy = (value) =>
+    Array.isArray(value) ? value.length > 0 : Boolean(value);
+
+  const deepEqual = (a, b) => {
+    if (Array.isArray(a) && Array.isArray(b)) {
+      return a.length === b.length && a.every((item, i) => deepEqual(item, b[i]));
+    }
+    if (isObject(a) && isObject(b) && !Array.isArray(a) && !Array.isArray(b)) {
+      const keys = Object.keys(a);
+      return (
+        keys.length === Object.keys(b).length &&
+        keys.every((key) => has(b, key) && deepEqual(a[key], b[key]))
+      );
+    }
+    return a === b;
+  };
+
+  // Number(), failing rather than returning NaN
+  const toNumber = (operator, value) => {
+    const number = Number(value);
+    if (Number.isNaN(number)) fail(operator, `${JSON.stringify(value)} is not a number`);
+    return number;
+  };
+
+  // parseFloat(), failing rather than returning NaN
+  const parseNumber = (operator, value) => {
+    const number = parseFloat(value);
+    if (Number.isNaN(number)) fail(operator, `${JSON.stringify(value)} is not a number`);
+    return number;
+  };
+
+  // Results that can't be represented in JSON are errors
+  const finite = (operator, number) => {
+    if (!Number.isFinite(number)) fail(operator, "the result is not a finite number");
+    return number;
+  };
+
+  const compare = (test) => (a, b, ...rest) =>
+    rest.length === 0 ? test(a, b) : test(a, b) && test(b, rest[0]);
+
+  // While an iterating operator evaluates its expression against an
+  // element, the data outside of it, for "../" variables, the index of the
+  // element, for {"var": "#index"}, and the scope outside of it
+  let scope = null;
+  const within = (data, index, evaluate) => {
+    const outer = scope;
+    scope = { data, index, outer };
+    try {
+      return evaluate();
+    } finally {
+      scope = outer;
+    }
+  };
+
+  // The items of an array argument to an iterating operator
+  const items = (operator, value, strings) => {
+    if (value === null) return [];
+    if (Array.isArray(value)) return value;
+    if (strings && typeof value === "string") return Array.from(value);
+    return fail(operator, `${JSON.stringify(value)} is not an array`);
+  };
+
+  const positiveInteger = (operator, value) => {
+    if (!Number.isInteger(value) || value < 1) fail(operator, "sizes and steps must be positive integers");
+  };
+
+  // The arrays of the elements at each index of arrays, up to the length of
+  // the shortest
+  const zipArrays = (operator, arrays) => {
+    const lists = arrays.map((array) => items(operator, array, false));
+    const length = Math.min(...lists.map((list) => list.length));
+    return Array.from({ length }, (_, i) => lists.map((list) => list[i]));
+  };
+
+  // Marks the objects `reduced` wraps values in, which the data can't, so
+  // that an object shaped like the wrapper in the data doesn't end a
+  // `reduce_while`
+  const REDUCED = Symbol("reduced");
+
+  // Whether a value is wrapped by `reduced`, to end a `reduce_while`
+  const isReduced = (value) => isObject(value) && value[REDUCED] === true;
+
+  // The first of the values whose key is better than every other's
+  const selectBy = (values, key, better) => {
+    let selected = null;
+    for (const value of values) {
+      const candidate = key(value);
+      if (selected === null || better(candidate, selected.key)) selected = { value, key: candidate };
+    }
+    return selected === null ? null : selected.value;
+  };
+
+  // Whether to sort in descending order: a boolean, or for sort_by, an array
+  // of them for each key
+  const descendingFlags = (operator, flags) => {
+    const valid =
+      typeof flags === "boolean" ||
+      (operator === "sort_by" && Array.isArray(flags) && flags.every((flag) => typeof flag === "boolean"));
+    if (!valid) fail(operator, "whether to sort in descending order must be a boolean");
+    return (i) => (Array.isArray(flags) ? flags[i] === true : flags);
+  };
+
+  const sortKey = (key) => !isObject(key) || (Array.isArray(key) && key.every(sortKey));
+
+  // Sort keys are ordered null first, then booleans, numbers, strings, and
+  // arrays, which are ordered by their elements
+  const compareKeys = (a, b) => {
+    const rank = (key) =>
+      key === null ? 0 : ["boolean", "number", "string"].indexOf(typeof key) + 1 || 4;
+    if (rank(a) !== rank(b)) return rank(a) - rank(b);
+    if (Array.isArray(a)) {
+      for (let i = 0; i < Math.min(a.length, b.length); i++) {
+        const order = compareKeys(a[i], b[i]);
+        if (order !== 0) return order;
+      }
+      return a.length - b.length;
+    }
+    return a < b ? -1 : a > b ? 1 : 0;
+  };
+
+  // Wildcard segments of dotted paths: `*` and `**`
+  const WILDCARD = { wildcard: "*" };
+  const RECURSIVE = { wildcard: "**" };
+  // A leading "../" of a variable key
+  const PARENT = { parent: true };
+  const isWildcard = (segment) => segment === WILDCARD || segment === RECURSIVE;
+  const isPattern = (segment) => typeof segment === "object";
+
+  // Dotted path segments may also be slices: `start:end`
+  const parseSegment = (segment) => {
+    if (segment === "*") return WILDCARD;
+    if (segment === "**") return RECURSIVE;
+    const bounds = /^([+-]?\d+)?:([+-]?\d+)?$/.exec(segment);
+    if (bounds === null) return segment;
+    const bound = (b) => (b === undefined ? null : Number(b));
+    return { slice: [bound(bounds[1]), bound(bounds[2])], key: segment };
+  };
+
+  const splitKey = (key) => {
+    if (key === null || key === "") return [];
+    // Before semantics version 2, every segment of a dotted path is a key
+    if (typeof key === "string" && semantics < 2) return key.split(".");
+    if (typeof key === "string") {
+      const parents = [];
+      let rest = key;
+      while (rest.startsWith("..") && (rest.length === 2 || rest[2] === "/")) {
+        parents.push(PARENT);
+        rest = rest.slice(3);
+      }
+      return rest === "" ? parents : [...parents, ...rest.split(".").map(parseSegment)];
+    }
+    if (typeof key === "number" && Number.isInteger(key)) return [String(key)];
+    if (isObject(key) && !Array.isArray(key) && Object.keys(key).length === 1 && has(key, "pointer")) {
+      const pointer = key.pointer;
+      if (typeof pointer !== "string" || !(pointer === "" || pointer.startsWith("/"))) {
+        fail("var", "JSON Pointers must be strings that are empty or start with '/'");
+      }
+      return pointer
+        .split("/")
+        .slice(1)
+        .map((segment) => segment.replace(/~1/g, "/").replace(/~0/g, "~"));
+    }
+    return fail("var", "variable keys must be strings, integers, null, or JSON Pointers");
+  };
+
+  // The elements of an array, or the values of an object ordered by key
+  const children = (value) => {
+    if (Array.isArray(value)) return value;
+    if (isObject(value)) return Object.keys(value).sort().map((key) => value[key]);
+    return [];
+  };
+
+  // Slice an array or string, with bounds counting from the end if they're
+  // negative, and clamped to its length
+  const sliceValue = (value, segment) => {
+    const isString = typeof value === "string";
+    if (!Array.isArray(value) && !isString) {
+      fail("var", `cannot slice ${JSON.stringify(value)} with '${segment.key}': only arrays and strings may be sliced`);
+    }
+    const elements = isString ? Array.from(value) : value;
+    const clamp = (bound, fallback) => {
+      if (bound === null) return fallback;
+      const position = bound < 0 ? elements.length + bound : bound;
+      return Math.min(Math.max(position, 0), elements.length);
+    };
+    const start = clamp(segment.slice[0], 0);
+    const end = Math.max(clamp(segment.slice[1], elements.length), start);
+    const sliced = elements.slice(start, end);
+    return isString ? sliced.join("") : sliced;
+  };
+
+  // Collect the values matching a path with wildcards within a value
+  const expand = (value, path, matches) => {
+    if (path.length === 0) {
+      matches.push(value);
+    } else if (path[0] === WILDCARD) {
+      children(value).forEach((child) => expand(child, path.slice(1), matches));
+    } else if (path[0] === RECURSIVE) {
+      expand(value, path.slice(1), matches);
+      children(value).forEach((child) => expand(child, path, matches));
+    } else if (isPattern(path[0])) {
+      expand(sliceValue(value, path[0]), path.slice(1), matches);
+    } else {
+      const pattern = path.findIndex(isPattern);
+      const end = pattern === -1 ? path.length : pattern;
+      const found = resolveKeys(value, path.slice(0, end));
+      if (found !== undefined) expand(found, path.slice(end), matches);
+    }
+  };
+
+  // Look up a path with slices but no wildcards within a value
+  const follow = (value, path) => {
+    let current = value;
+    let rest = path;
+    while (rest.length > 0) {
+      const slice = rest.findIndex(isPattern);
+      const end = slice === -1 ? rest.length : slice;
+      current = resolveKeys(current, rest.slice(0, end));
+      if (current === undefined || slice === -1) return current;
+      current = sliceValue(current, rest[slice]);
+      rest = rest.slice(slice + 1);
+    }
+    return current;
+  };
+
+  // Look up a path in the data, returning undefined if it isn't present.
+  // Paths with wildcards resolve to an array of the values they match.
+  const resolve = (data, path) => {
+    const pattern = path.findIndex(isPattern);
+    if (pattern === -1) return resolveKeys(data, path);
+    const base = resolveKeys(data, path.slice(0, pattern));
+    if (base === undefined) return undefined;
+    if (!path.some(isWildcard)) return follow(base, path.slice(pattern));
+    const matches = [];
+    expand(base, path.slice(pattern), matches);
+    return matches;
+  };
+
+  // Look up a path of keys
+  const resolveKeys = (data, path) => {
+    let current = data;
+    for (const segment of path) {
+      if (Array.isArray(current) || typeof current === "string") {
+        const elements = typeof current === "string" ? Array.from(current) : current;
+        if (!/^[+-]?\d+$/.test(segment)) return undefined;
+        const index = Number(segment);
+        const position = index < 0 ? elements.length + index : index;
+        if (position < 0 || position >= elements.length) return undefined;
+        current = elements[position];
+      } else if (isObject(current) && has(current, segment)) {
+        current = current[segment];
+      } else {
+        return undefined;
+      }
+    }
+    return current;
+  };
+
+  // JSON Patch (RFC 6902), which fails if any of its operations does
+  const copy = (value) => JSON.parse(JSON.stringify(value));
+  const patchPointer = (operation, member) => {
+    const pointer = operation[member];
+    if (typeof pointer !== "string" || !(pointer === "" || pointer.startsWith("/"))) {
+      fail("patch", `'${member}' must be a JSON Pointer`);
+    }
+    return pointer;
+  };
+  const patchIndex = (key, length) => {
+    if (!/^(0|[1-9]\d*)$/.test(key) || Number(key) >= length) {
+      fail("patch", `'${key}' is not an index into the array`);
+    }
+    return Number(key);
+  };
+  const patchTarget = (document, pointer) => {
+    let current = document;
+    for (const key of splitKey({ pointer })) {
+      if (Array.isArray(current)) {
+        current = current[patchIndex(key, current.length)];
+      } else if (isObject(current) && has(current, key)) {
+        current = current[key];
+      } else {
+        fail("patch", `there is no value at '${pointer}'`);
+      }
+    }
+    return current;
+  };
+  // Split a non-empty pointer into its parent and its last key
+  const patchParent = (document, pointer) => {
+    const slash = pointer.lastIndexOf("/");
+    const parent = patchTarget(document, pointer.slice(0, slash));
+    return [parent, pointer.slice(slash + 1).replace(/~1/g, "/").replace(/~0/g, "~")];
+  };
+  const patchAdd = (document, pointer, value) => {
+    if (pointer === "") return value;
+    const [parent, key] = patchParent(document, pointer);
+    if (Array.isArray(parent)) {
+      parent.splice(key === "-" ? parent.length : patchIndex(key, parent.length + 1), 0, value);
+    } else if (isObject(parent)) {
+      parent[key] = value;
+    } else {
+      fail("patch", `cannot add '${pointer}' to a value that isn't a container`);
+    }
+    return document;
+  };
+  const patchRemove = (document, pointer) => {
+    if (pointer === "") fail("patch", "cannot remove the whole document");
+    const [parent, key] = patchParent(document, pointer);
+    if (Array.isArray(parent)) return parent.splice(patchIndex(key, parent.length), 1)[0];
+    if (!isObject(parent) || !has(parent, key)) fail("patch", `there is no value at '${pointer}'`);
+    const value = parent[key];
+    delete parent[key];
+    return value;
+  };
+  const patchOperation = (document, operation) => {
+    if (!isObject(operation) || typeof operation.op !== "string") {
+      fail("patch", "JSON Patch operations must have a string 'op'");
+    }
+    const path = patchPointer(operation, "path");
+    const value = () => {
+      if (!has(operation, "value")) fail("patch", "JSON Patch operations must have a 'value'");
+      return copy(operation.value);
+    };
+    switch (operation.op) {
+      case "add":
+        return patchAdd(document, path, value());
+      case "remove":
+        patchRemove(document, path);
+        return document;
+      case "replace": {
+        const replacement = value();
+        if (path === "") return replacement;
+        patchRemove(document, path);
+        return patchAdd(document, path, replacement);
+      }
+      case "move": {
+        const from = patchPointer(operation, "from");
+        if (path.startsWith(`${from}/`)) fail("patch", `cannot move '${from}' into itself`);
+        return patchAdd(document, path, patchRemove(document, from));
+      }
+      case "copy":
+        return patchAdd(document, path, copy(patchTarget(document, patchPointer(operation, "from"))));
+      case "test":
+        if (!deepEqual(patchTarget(document, path), value())) {
+          fail("patch", `the value at '${path}' differs`);
+        }
+        return document;
+      default:
+        return fail("patch", `unknown JSON Patch operation '${operation.op}'`);
+    }
+  };
+
+  // The JSON Patch changing one value into another, at the given pointer
+  const diff = (from, to, path, operations) => {
+    if (deepEqual(from, to)) return operations;
+    const at = (key) => `${path}/${String(key).replace(/~/g, "~0").replace(/\//g, "~1")}`;
+    const isMap = (value) => isObject(value) && !Array.isArray(value);
+    if (isMap(from) && isMap(to)) {
+      const keys = [...new Set([...Object.keys(from), ...Object.keys(to)])].sort();
+      for (const key of keys) {
+        if (has(from, key) && has(to, key)) diff(from[key], to[key], at(key), operations);
+        else if (has(from, key)) operations.push({ op: "remove", path: at(key) });
+      }
+      for (const key of keys.filter((key) => !has(from, key))) {
+        operations.push({ op: "add", path: at(key), value: to[key] });
+      }
+    } else if (Array.isArray(from) && Array.isArray(to)) {
+      const common = Math.min(from.length, to.length);
+      for (let i = 0; i < common; i++) diff(from[i], to[i], at(i), operations);
+      for (let i = from.length - 1; i >= to.length; i--) operations.push({ op: "remove", path: at(i) });
+      for (const value of to.slice(from.length)) operations.push({ op: "add", path: at("-"), value });
+    } else {
+      operations.push({ op: "replace", path, value: to });
+    }
+    return operations;
+  };
+
+  // JSON Merge Patch (RFC 7386)
+  const mergePatch = (target, patch) => {
+    if (!isObject(patch) || Array.isArray(patch)) return patch;
+    const merged = isObject(target) && !Array.isArray(target) ? { ...target } : {};
+    for (const [key, value] of Object.entries(patch)) {
+      if (value === null) delete merged[key];
+      else merged[key] = mergePatch(merged[key], value);
+    }
+    return merged;
+  };
+
+  const stringArg = (operator, value) => {
+    if (typeof value !== "string") fail(operator, `${JSON.stringify(value)} is not a string`);
+    return value;
+  };
+
+  // Pad a string to a length in characters
+  const pad = (operator, string, length, fill) => {
+    const chars = Array.from(stringArg(operator, string));
+    if (!Number.isInteger(length) || length < 0) {
+      fail(operator, "length must be a non-negative integer");
+    }
+    if (length > 1024) fail(operator, "strings may be padded to at most 1024 characters");
+    const fillChars = Array.from(stringArg(operator, fill));
+    if (fillChars.length === 0) fail(operator, "the fill string must not be empty");
+    const padding = [];
+    for (let i = 0; padding.length < length - chars.length; i++) {
+      padding.push(fillChars[i % fillChars.length]);
+    }
+    const padded = operator === "pad_start" ? padding.concat(chars) : chars.concat(padding);
+    return padded.join("");
+  };
+
+  // Semantic versions, with ranges in Cargo's syntax for version
+  // requirements, as with the `semver` crate
+  const NUMERIC = "0|[1-9]\\d*";
+  const IDENTIFIER = `(?:${NUMERIC}|\\d*[A-Za-z-][0-9A-Za-z-]*)`;
+  const PRERELEASE = `${IDENTIFIER}(?:\\.${IDENTIFIER})*`;
+  const BUILD = "[0-9A-Za-z-]+(?:\\.[0-9A-Za-z-]+)*";
+  const VERSION = new RegExp(
+    `^(${NUMERIC})\\.(${NUMERIC})\\.(${NUMERIC})(?:-(${PRERELEASE}))?(?:\\+${BUILD})?$`
+  );
+  const COMPARATOR = new RegExp(
+    `^(=|>=?|<=?|~|\\^)? *(${NUMERIC})(?:\\.(?:([*xX])|(${NUMERIC}))` +
+      `(?:\\.(?:([*xX])|(${NUMERIC}))(?:-(${PRERELEASE}))?(?:\\+(${BUILD}))?)?)?$`
+  );
+
+  const parseVersion = (operator, value) => {
+    const match = VERSION.exec(stringArg(operator, value));
+    if (match === null) fail(operator, `'${value}' is not a semantic version`);
+    const [, major, minor, patch, pre] = match;
+    return { major: Number(major), minor: Number(minor), patch: Number(patch), pre: pre ? pre.split(".") : [] };
+  };
+
+  const parseComparator = (operator, text) => {
+    const match = COMPARATOR.exec(text);
+    if (match === null) fail(operator, `'${text}' is not a version comparator`);
+    const [, op, major, minorWildcard, minor, patchWildcard, patch, pre, build] = match;
+    if (minorWildcard && patch !== undefined) fail(operator, `'${text}' has a version after a wildcard`);
+    if ((pre || build) && patch === undefined) fail(operator, `'${text}' has a prerelease or build without a patch version`);
+    return {
+      op: op || "^",
+      major: Number(major),
+      minor: minor === undefined ? undefined : Number(minor),
+      patch: patch === undefined ? undefined : Number(patch),
+      pre: pre ? pre.split(".") : [],
+      wildcard: !op && Boolean(minorWildcard || patchWildcard),
+    };
+  };
+
+  // Each alternative of a range, as a list of comparators
+  const parseRange = (operator, value) =>
+    stringArg(operator, value)
+      .split("||")
+      .map((alternative) => alternative.replace(/^ +| +$/g, ""))
+      .map((alternative) =>
+        /^[*xX]$/.test(alternative)
+          ? []
+          : alternative.split(",").map((text) => parseComparator(operator, text.replace(/^ +| +$/g, "")))
+      );
+
+  // Prereleases are ordered by their identifiers, numerically if they're
+  // numbers, and precede releases
+  const comparePre = (a, b) => {
+    if (a.length === 0 || b.length === 0) return b.length - a.length;
+    for (let i = 0; i < Math.min(a.length, b.length); i++) {
+      const [aNumeric, bNumeric] = [/^\d+$/.test(a[i]), /^\d+$/.test(b[i])];
+      if (aNumeric !== bNumeric) return aNumeric ? -1 : 1;
+      const order = aNumeric ? a[i].length - b[i].length || (a[i] < b[i] ? -1 : a[i] > b[i] ? 1 : 0) : a[i] < b[i] ? -1 : a[i] > b[i] ? 1 : 0;
+      if (order !== 0) return order;
+    }
+    return a.length - b.length;
+  };
+
+  const compareVersions = (a, b) =>
+    Math.sign(a.major - b.major) || Math.sign(a.minor - b.minor) || Math.sign(a.patch - b.patch) || Math.sign(comparePre(a.pre, b.pre));
+
+  // The sign of the comparison of a version's numbers with a comparator's,
+  // or null if they're equal as far as the comparator specifies them
+  const compareNumbers = (cmp, ver) => {
+    if (ver.major !== cmp.major) return Math.sign(ver.major - cmp.major);
+    if (cmp.minor === undefined) return null;
+    if (ver.minor !== cmp.minor) return Math.sign(ver.minor - cmp.minor);
+    if (cmp.patch === undefined) return null;
+    return Math.sign(ver.patch - cmp.patch);
+  };
+
+  const matchesExact = (cmp, ver) =>
+    ver.major === cmp.major &&
+    (cmp.minor === undefined || ver.minor === cmp.minor) &&
+    (cmp.patch === undefined || ver.patch === cmp.patch) &&
+    comparePre(ver.pre, cmp.pre) === 0;
+
+  const matchesGreater = (cmp, ver) => {
+    const order = compareNumbers(cmp, ver);
+    return order === null ? false : order !== 0 ? order > 0 : comparePre(ver.pre, cmp.pre) > 0;
+  };
+
+  const matchesLess = (cmp, ver) => {
+    const order = compareNumbers(cmp, ver);
+    return order === null ? false : order !== 0 ? order < 0 : comparePre(ver.pre, cmp.pre) < 0;
+  };
+
+  const matchesTilde = (cmp, ver) => {
+    if (ver.major !== cmp.major) return false;
+    if (cmp.minor !== undefined && ver.minor !== cmp.minor) return false;
+    if (cmp.patch !== undefined && ver.patch !== cmp.patch) return ver.patch > cmp.patch;
+    return comparePre(ver.pre, cmp.pre) >= 0;
+  };
+
+  const matchesCaret = (cmp, ver) => {
+    if (ver.major !== cmp.major) return false;
+    if (cmp.minor === undefined) return true;
+    if (cmp.patch === undefined) return cmp.major > 0 ? ver.minor >= cmp.minor : ver.minor === cmp.minor;
+    if (cmp.major > 0) {
+      if (ver.minor !== cmp.minor) return ver.minor > cmp.minor;
+      if (ver.patch !== cmp.patch) return ver.patch > cmp.patch;
+    } else if (cmp.minor > 0) {
+      if (ver.minor !== cmp.minor) return false;
+      if (ver.patch !== cmp.patch) return ver.patch > cmp.patch;
+    } else if (ver.minor !== cmp.minor || ver.patch !== cmp.patch) {
+      return false;
+    }
+    return comparePre(ver.pre, cmp.pre) >= 0;
+  };
+
+  const matchesComparator = (cmp, ver) => {
+    if (cmp.wildcard) return matchesExact(cmp, ver);
+    switch (cmp.op) {
+      case "=":
+        return matchesExact(cmp, ver);
+      case ">":
+        return matchesGreater(cmp, ver);
+      case ">=":
+        return matchesExact(cmp, ver) || matchesGreater(cmp, ver);
+      case "<":
+        return matchesLess(cmp, ver);
+      case "<=":
+        return matchesExact(cmp, ver) || matchesLess(cmp, ver);
+      case "~":
+        return matchesTilde(cmp, ver);
+      default:
+        return matchesCaret(cmp, ver);
+    }
+  };
+
+  // A prerelease only satisfies comparators if one of them has a prerelease
+  // of the same version
+  const satisfies = (comparators, ver) =>
+    comparators.every((cmp) => matchesComparator(cmp, ver)) &&
+    (ver.pre.length === 0 ||
+      comparators.some(
+        (cmp) => cmp.major === ver.major && cmp.minor === ver.minor && cmp.patch === ver.patch && cmp.pre.length > 0
+      ));
+
+  // Money values, e.g. "12.30 USD", with exact decimal amounts
+  const MINOR_UNITS = {
+    BHD: 3, BIF: 0, CLF: 4, CLP: 0, DJF: 0, GNF: 0, IQD: 3, ISK: 0, JOD: 3, JPY: 0, KMF: 0, KRW: 0, KWD: 3,
+    LYD: 3, OMR: 3, PYG: 0, RWF: 0, TND: 3, UGX: 0, UYI: 0, UYW: 4, VND: 0, VUV: 0, XAF: 0, XOF: 0, XPF: 0,
+  };
+
+  const parseAmount = (text) => {
+    const match = /^([-+]?)(\d+)(?:\.(\d+))?$/.exec(text);
+    if (match === null) return null;
+    const [, sign, integer, fraction = ""] = match;
+    const units = BigInt(integer + fraction);
+    return { units: sign === "-" ? -units : units, scale: fraction.length };
+  };
+
+  const withScale = (amount, scale) =>
+    scale <= amount.scale ? amount : { units: amount.units * 10n ** BigInt(scale - amount.scale), scale };
+
+  const currencyArg = (operator, currency) => {
+    if (typeof currency !== "string") fail(operator, "currency codes must be strings");
+    if (!/^[A-Z]{3}$/.test(currency)) fail(operator, `'${currency}' is not a currency code, e.g. USD`);
+    return currency;
+  };
+
+  const makeMoney = (operator, amount, currency) => {
+    currencyArg(operator, currency);
+    return { amount: withScale(amount, has(MINOR_UNITS, currency) ? MINOR_UNITS[currency] : 2), currency };
+  };
+
+  const parseMoney = (operator, value) => {
+    const space = typeof value === "string" ? value.indexOf(" ") : -1;
+    const amount = space === -1 ? null : parseAmount(value.slice(0, space));
+    if (amount === null) fail(operator, 'money values must be strings of an amount and a currency code, e.g. "12.34 USD"');
+    return makeMoney(operator, amount, value.slice(space + 1));
+  };
+
+  const formatMoney = ({ amount: { units, scale }, currency }) => {
+    const digits = (units < 0n ? -units : units).toString().padStart(scale + 1, "0");
+    const integer = digits.slice(0, digits.length - scale);
+    const fraction = scale > 0 ? `.${digits.slice(digits.length - scale)}` : "";
+    return `${units < 0n ? "-" : ""}${integer}${fraction} ${currency}`;
+  };
+
+  // Two amounts of one currency, with the same number of decimal places
+  const alignedMoney = (operator, a, b) => {
+    if (a.currency !== b.currency) {
+      fail(operator, `amounts of ${a.currency} and ${b.currency} can't be combined without converting them to one currency`);
+    }
+    const scale = Math.max(a.amount.scale, b.amount.scale);
+    return [withScale(a.amount, scale), withScale(b.amount, scale)];
+  };
+
+  // Dates, as RFC 3339 strings, and durations, as ISO 8601 durations or
+  // shorthand like "1h30m", both in milliseconds, with durations' years and
+  // months apart
+  const MILLIS_PER_DAY = 86400000;
+  const ISO_DURATION = /^P(?:(\d+)Y)?(?:(\d+)M)?(?:(\d+)W)?(?:(\d+)D)?(?:T(?:(\d+)H)?(?:(\d+)M)?(?:(\d+)(?:[.,](\d+))?S)?)?$/;
+  const SHORTHAND_UNITS = { w: 7 * MILLIS_PER_DAY, d: MILLIS_PER_DAY, h: 3600000, m: 60000, s: 1000, ms: 1 };
+  const DATE = /^(\d{4})-(\d{2})-(\d{2})(?:[Tt ](\d{2}):(\d{2}):(\d{2})(?:\.(\d+))?(?:[Zz]|([+-])(\d{2}):(\d{2})))?$/;
+
+  const fractionMillis = (digits) => Number(digits.slice(0, 3).padEnd(3, "0"));
+
+  const parseDuration = (text) => {
+    const negative = text.startsWith("-");
+    const rest = negative ? text.slice(1) : text;
+    let months = 0;
+    let millis = 0;
+    const iso = ISO_DURATION.exec(rest);
+    if (iso !== null && rest !== "P" && !rest.endsWith("T")) {
+      const [years, monthCount, weeks, days, hours, minutes, seconds] = iso.slice(1, 8).map((n) => Number(n || 0));
+      months = years * 12 + monthCount;
+      millis =
+        (weeks * 7 + days) * MILLIS_PER_DAY +
+        (hours * 60 + minutes) * 60000 +
+        seconds * 1000 +
+        (iso[8] === undefined ? 0 : fractionMillis(iso[8]));
+    } else if (/^(?:\d+(?:ms|w|d|h|m|s))+$/.test(rest)) {
+      for (const [, count, unit] of rest.matchAll(/(\d+)(ms|w|d|h|m|s)/g)) {
+        millis += Number(count) * SHORTHAND_UNITS[unit];
+      }
+    } else {
+      return null;
+    }
+    return negative ? { months: -months, millis: -millis } : { months, millis };
+  };
+
+  const durationArg = (operator, value) => {
+    if (typeof value === "number") return { months: 0, millis: Math.trunc(value * 1000) };
+    const duration = typeof value === "string" ? parseDuration(value) : null;
+    if (duration === null) fail(operator, `${JSON.stringify(value)} is not a duration, e.g. "P1DT2H" or "7d"`);
+    return duration;
+  };
+
+  const isLeapYear = (year) => (year % 4 === 0 && year % 100 !== 0) || year % 400 === 0;
+  const daysInMonth = (year, month) =>
+    month === 2 ? (isLeapYear(year) ? 29 : 28) : [4, 6, 9, 11].includes(month) ? 30 : 31;
+
+  // Howard Hinnant's algorithms for the proleptic Gregorian calendar
+  const daysFromCivil = (year, month, day) => {
+    const y = month <= 2 ? year - 1 : year;
+    const era = Math.floor(y / 400);
+    const yearOfEra = y - era * 400;
+    const dayOfYear = Math.floor((153 * ((month + 9) % 12) + 2) / 5) + day - 1;
+    const dayOfEra = yearOfEra * 365 + Math.floor(yearOfEra / 4) - Math.floor(yearOfEra / 100) + dayOfYear;
+    return era * 146097 + dayOfEra - 719468;
+  };
+
+  const civilFromDays = (days) => {
+    const z = days + 719468;
+    const era = Math.floor(z / 146097);
+    const dayOfEra = z - era * 146097;
+    const yearOfEra = Math.floor(
+      (dayOfEra - Math.floor(dayOfEra / 1460) + Math.floor(dayOfEra / 36524) - Math.floor(dayOfEra / 146096)) / 365
+    );
+    const dayOfYear = dayOfEra - (365 * yearOfEra + Math.floor(yearOfEra / 4) - Math.floor(yearOfEra / 100));
+    const shiftedMonth = Math.floor((5 * dayOfYear + 2) / 153);
+    const day = dayOfYear - Math.floor((153 * shiftedMonth + 2) / 5) + 1;
+    const month = shiftedMonth < 10 ? shiftedMonth + 3 : shiftedMonth - 9;
+    return [yearOfEra + era * 400 + (month <= 2 ? 1 : 0), month, day];
+  };
+
+  const dateArg = (operator, value) => {
+    const match = typeof value === "string" ? DATE.exec(value) : null;
+    const invalid = () => fail(operator, 'dates must be RFC 3339 strings, e.g. "2024-03-01" or "2024-03-01T09:30:00Z"');
+    if (match === null) return invalid();
+    const [year, month, day, hour, minute, second] = match.slice(1, 7).map((n) => Number(n || 0));
+    const [sign, offsetHours, offsetMinutes] = [match[8], Number(match[9] || 0), Number(match[10] || 0)];
+    if (month < 1 || month > 12 || day < 1 || day > daysInMonth(year, month)) return invalid();
+    if (hour > 23 || minute > 59 || second > 59 || offsetHours > 23 || offsetMinutes > 59) return invalid();
+    const offset = (sign === "-" ? -1 : 1) * (offsetHours * 60 + offsetMinutes) * 60000;
+    const millis = match[7] === undefined ? 0 : fractionMillis(match[7]);
+    return daysFromCivil(year, month, day) * MILLIS_PER_DAY + ((hour * 60 + minute) * 60 + second) * 1000 + millis - offset;
+  };
+
+  const formatDate = (operator, millis) => {
+    const [days, time] = [Math.floor(millis / MILLIS_PER_DAY), ((millis % MILLIS_PER_DAY) + MILLIS_PER_DAY) % MILLIS_PER_DAY];
+    const [year, month, day] = civilFromDays(days);
+    if (year < 0 || year > 9999) fail(operator, "the result is outside the years 0 to 9999");
+    const two = (n) => String(n).padStart(2, "0");
+    const seconds = Math.floor(time / 1000);
+    const fraction = time % 1000 === 0 ? "" : `.${String(time % 1000).padStart(3, "0")}`;
+    return (
+      `${String(year).padStart(4, "0")}-${two(month)}-${two(day)}` +
+      `T${two(Math.floor(seconds / 3600))}:${two(Math.floor(seconds / 60) % 60)}:${two(seconds % 60)}${fraction}Z`
+    );
+  };
+
+  // Years and months are added on the calendar, clamping the day to the end
+  // of shorter months
+  const addDuration = (operator, date, { months, millis }) => {
+    let result = date;
+    if (months !== 0) {
+      const [days, time] = [Math.floor(date / MILLIS_PER_DAY), ((date % MILLIS_PER_DAY) + MILLIS_PER_DAY) % MILLIS_PER_DAY];
+      const [year, month, day] = civilFromDays(days);
+      const total = year * 12 + month - 1 + months;
+      const [newYear, newMonth] = [Math.floor(total / 12), (((total % 12) + 12) % 12) + 1];
+      if (newYear < 0 || newYear > 9999) fail(operator, "the result is outside the years 0 to 9999");
+      result = daysFromCivil(newYear, newMonth, Math.min(day, daysInMonth(newYear, newMonth))) * MILLIS_PER_DAY + time;
+    }
+    return formatDate(operator, result + millis);
+  };
+
+  const looseEq = (a, b) => (isObject(a) && isObject(b) ? false : a == b);
+  const strictEq = (a, b) => (isObject(a) && isObject(b) ? false : a === b);
+
+  const ops = {
+    eq: (a, b) => deepEqual(a, b),
+    ne: (a, b) => !deepEqual(a, b),
+    "==": looseEq,
+    "!=": (a, b) => !looseEq(a, b),
+    "===": strictEq,
+    "!==": (a, b) => !strictEq(a, b),
+    "!": (a) => !truthy(a),
+    "!!": (a) => truthy(a),
+    "<": compare((a, b) => a < b),
+    "<=": compare((a, b) => a <= b),
+    ">": compare((a, b) => a > b),
+    ">=": compare((a, b) => a >= b),
+    "+": (...args) =>
+      finite("+", args.reduce((total, arg) => total + parseNumber("+", arg), 0)),
+    "*": (...args) =>
+      finite("*", args.reduce((total, arg) => total * parseNumber("*", arg), 1)),
+    "-": (...args) =>
+      finite(
+        "-",
+        args.length === 1
+          ? -toNumber("-", args[0])
+          : toNumber("-", args[0]) - toNumber("-", args[1])
+      ),
+    "/": (a, b) => finite("/", toNumber("/", a) / toNumber("/", b)),
+    "%": (a, b) => finite("%", toNumber("%", a) % toNumber("%", b)),
+    max: (...args) => finite("max", Math.max(...args.map((arg) => toNumber("max", arg)))),
+    min: (...args) => finite("min", Math.min(...args.map((arg) => toNumber("min", arg)))),
+    add: (a, b) => {
+      if (typeof a !== "number" || typeof b !== "number") {
+        fail("add", "arguments to add must be numbers");
+      }
+      return finite("add", a + b);
+    },
+    merge: (...args) =>
+      args.reduce((merged, arg) => merged.concat(Array.isArray(arg) ? arg : [arg]), []),
+    zip: (...arrays) => zipArrays("zip", arrays),
+    reduced: (value) => ({ reduced: value, [REDUCED]: true }),
+    cartesian: (...arrays) =>
+      arrays
+        .map((array) => items("cartesian", array, false))
+        .reduce((product, array) => product.flatMap((tuple) => array.map((item) => tuple.concat([item]))), [[]]),
+    chunk: (array, size) => {
+      const values = items("chunk", array, false);
+      positiveInteger("chunk", size);
+      const chunks = [];
+      for (let i = 0; i < values.length; i += size) chunks.push(values.slice(i, i + size));
+      return chunks;
+    },
+    window: (array, size, step = 1) => {
+      const values = items("window", array, false);
+      positiveInteger("window", size);
+      positiveInteger("window", step);
+      const windows = [];
+      for (let i = 0; i + size <= values.length; i += step) windows.push(values.slice(i, i + size));
+      return windows;
+    },
+    take: (array, count) => {
+      const values = items("take", array, false);
+      if (!Number.isInteger(count)) fail("take", "counts must be integers");
+      return count >= 0 ? values.slice(0, count) : values.slice(Math.max(values.length + count, 0));
+    },
+    drop: (array, count) => {
+      const values = items("drop", array, false);
+      if (!Number.isInteger(count)) fail("drop", "counts must be integers");
+      return count >= 0 ? values.slice(count) : values.slice(0, Math.max(values.length + count, 0));
+    },
+    range: (...args) => {
+      for (const arg of args) {
+        if (typeof arg !== "number") fail("range", "bounds and steps must be numbers");
+      }
+      const [start, end] = args.length === 1 ? [0, args[0]] : args;
+      const step = args.length === 3 ? args[2] : 1;
+      if (step === 0) fail("range", "the step must not be zero");
+      const count = Math.max(Math.ceil((end - start) / step), 0);
+      return Array.from({ length: count }, (_, i) => start + i * step);
+    },
+    in: (needle, haystack) => {
+      if (haystack === null) return false;
+      if (Array.isArray(haystack)) return haystack.some((item) => deepEqual(item, needle));
+      if (typeof haystack === "string") {
+        if (typeof needle !== "string") {
+          fail("in", "if second argument is a string, first argument must also be a string");
+        }
+        return haystack.includes(needle);
+      }
+      return fail("in", "second argument must be an array or a string");
+    },
+    cat: (...args) => args.map((arg) => String(arg)).join(""),
+    substr: (string, start, limit) => {
+      if (typeof string !== "string") fail("substr", "first argument must be a string");
+      if (!Number.isInteger(start)) fail("substr", "second argument must be an integer");
+      if (limit !== undefined && !Number.isInteger(limit)) {
+        fail("substr", "third argument must be an integer");
+      }
+      const chars = Array.from(string);
+      const from = start < 0 ? Math.max(chars.length + start, 0) : Math.min(chars.length, start);
+      let to = chars.length;
+      if (limit !== undefined) {
+        to = limit < 0 ? Math.max(chars.length + limit, 0) : Math.min(chars.length, from + limit);
+      }
+      return chars.slice(from, Math.max(to, from)).join("");
+    },
+    patch: (document, operations) => {
+      if (!Array.isArray(operations)) fail("patch", "a JSON Patch must be an array of operations");
+      return operations.reduce(patchOperation, copy(document));
+    },
+    obj: (...args) => {
+      const object = {};
+      for (let i = 0; i < args.length; i += 2) {
+        if (typeof args[i] !== "string") fail("obj", "Object keys must be strings");
+        object[args[i]] = args[i + 1];
+      }
+      return object;
+    },
+    merge_patch: (document, patch) => mergePatch(document, patch),
+    diff: (from, to) => diff(from, to, "", []),
+    semver_gt: (a, b) => compareVersions(parseVersion("semver_gt", a), parseVersion("semver_gt", b)) > 0,
+    semver_satisfies: (version, range) => {
+      const ver = parseVersion("semver_satisfies", version);
+      return parseRange("semver_satisfies", range).some((comparators) => satisfies(comparators, ver));
+    },
+    money: (value, currency) => {
+      if (currency === undefined) return formatMoney(parseMoney("money", value));
+      const amount = typeof value === "number" || typeof value === "string" ? parseAmount(String(value)) : null;
+      if (amount === null) fail("money", "amounts must be numbers or decimal strings");
+      return formatMoney(makeMoney("money", amount, currency));
+    },
+    money_add: (...values) =>
+      formatMoney(
+        values.map((value) => parseMoney("money_add", value)).reduce((total, money) => {
+          const [a, b] = alignedMoney("money_add", total, money);
+          return { amount: { units: a.units + b.units, scale: a.scale }, currency: total.currency };
+        })
+      ),
+    money_sub: (first, second) => {
+      const money = parseMoney("money_sub", first);
+      const [a, b] = alignedMoney("money_sub", money, parseMoney("money_sub", second));
+      return formatMoney({ amount: { units: a.units - b.units, scale: a.scale }, currency: money.currency });
+    },
+    money_cmp: (first, second) => {
+      const [a, b] = alignedMoney("money_cmp", parseMoney("money_cmp", first), parseMoney("money_cmp", second));
+      return a.units < b.units ? -1 : a.units > b.units ? 1 : 0;
+    },
+    duration: (value) => {
+      const { months, millis } = durationArg("duration", stringArg("duration", value));
+      if (months !== 0) fail("duration", "years and months have no fixed number of seconds");
+      return millis / 1000;
+    },
+    date_add: (date, duration) => addDuration("date_add", dateArg("date_add", date), durationArg("date_add", duration)),
+    date_sub: (date, duration) => {
+      const { months, millis } = durationArg("date_sub", duration);
+      return addDuration("date_sub", dateArg("date_sub", date), { months: -months, millis: -millis });
+    },
+    upper: (string) => stringArg("upper", string).toUpperCase(),
+    lower: (string) => stringArg("lower", string).toLowerCase(),
+    trim: (string) => stringArg("trim", string).replace(/^\s+|\s+$/gu, ""),
+    trim_start: (string) => stringArg("trim_start", string).replace(/^\s+/u, ""),
+    trim_end: (string) => stringArg("trim_end", string).replace(/\s+$/u, ""),
+    pad_start: (string, length, fill = " ") => pad("pad_start", string, length, fill),
+    pad_end: (string, length, fill = " ") => pad("pad_end", string, length, fill),
+    starts_with: (string, prefix) =>
+      stringArg("starts_with", string).startsWith(stringArg("starts_with", prefix)),
+    ends_with: (string, suffix) =>
+      stringArg("ends_with", string).endsWith(stringArg("ends_with", suffix)),
+    split: (string, separator) => {
+      stringArg("split", string);
+      if (stringArg("split", separator) === "") return Array.from(string);
+      return string.split(separator);
+    },
+    join: (elements, separator) => {
+      if (!Array.isArray(elements)) fail("join", "first argument must be an array");
+      return elements.map((element) => String(element)).join(stringArg("join", separator));
+    },
+    template: (template, context, missing = "error") => {
+      if (typeof template !== "string") fail("template", "first argument must be a string");
+      if (missing !== "error" && missing !== "keep") {
+        fail("template", 'third argument must be "error" or "keep"');
+      }
+      return template.replace(/\{\{|\}\}|\{([^}]*)\}|[{}]/g, (match, path) => {
+        if (match === "{{" || match === "}}") return match[0];
+        if (path === undefined) fail("template", `unmatched '${match}' in template`);
+        const value = resolve(context, splitKey(path));
+        if (value !== undefined) return String(value);
+        if (missing === "keep") return match;
+        return fail("template", `the template's context has no value for '${path}'`);
+      });
+    },
+    log: (value) => {
+      console.log(value);
+      return value;
+    },
+  };
+
+  const lazy = {
+    if: (data, args) => {
+      for (let i = 0; i < args.length; i += 2) {
+        if (i === args.length - 1) return args[i](data);
+        if (truthy(args[i](data))) return args[i + 1](data);
+      }
+      return null;
+    },
+    or: (data, args) => {
+      let value = null;
+      for (const arg of args) {
+        value = arg(data);
+        if (truthy(value)) return value;
+      }
+      return value;
+    },
+    and: (data, args) => {
+      let value = null;
+      for (const arg of args) {
+        value = arg(data);
+        if (!truthy(value)) return value;
+      }
+      return value;
+    },
+    map: (data, [list, expression]) =>
+      items("map", list(data), false).map((item, i) => within(data, i, () => expression(item))),
+    zip_with: (data, args) =>
+      zipArrays(
+        "zip_with",
+        args.slice(0, -1).map((array) => array(data))
+      ).map((tuple, i) => within(data, i, () => args[args.length - 1](tuple))),
+    filter: (data, [list, expression]) =>
+      items("filter", list(data), false).filter((item, i) => truthy(within(data, i, () => expression(item)))),
+    reduce: (data, [list, expression, initial]) => {
+      const values = items("reduce", list(data), false);
+      return values.reduce(
+        (accumulator, current, i) => within(data, i, () => expression({ current, accumulator })),
+        initial(data)
+      );
+    },
+    switch: (data, [value, cases, fallback]) => {
+      const key = value(data);
+      if (key !== null && typeof key !== "string") fail("switch", `${JSON.stringify(key)} is not a string`);
+      if (key !== null && has(cases, key)) return cases[key](data);
+      return fallback === undefined ? null : fallback(data);
+    },
+    map_values: (data, [object, expression]) => {
+      const value = object(data);
+      if (value === null) return {};
+      if (!isObject(value) || Array.isArray(value)) fail("map_values", `${JSON.stringify(value)} is not an object`);
+      return Object.fromEntries(
+        Object.entries(value).map(([key, item]) => [key, within(data, null, () => expression(item))])
+      );
+    },
+    reduce_while: (data, [list, expression, initial]) => {
+      const values = items("reduce_while", list(data), false);
+      let accumulator = initial(data);
+      for (let i = 0; i < values.length; i++) {
+        accumulator = within(data, i, () => expression({ current: values[i], accumulator }));
+        if (isReduced(accumulator)) return accumulator.reduced;
+      }
+      return accumulator;
+    },
+    all: (data, [list, predicate]) => {
+      const values = items("all", list(data), true);
+      return values.length > 0 && values.every((item, i) => truthy(within(data, i, () => predicate(item))));
+    },
+    some: (data, [list, predicate]) =>
+      items("some", list(data), true).some((item, i) => truthy(within(data, i, () => predicate(item)))),
+    none: (data, [list, predicate]) =>
+      !items("none", list(data), true).some((item, i) => truthy(within(data, i, () => predicate(item)))),
+    find: (data, [list, predicate]) => {
+      const found = items("find", list(data), false).find((item) =>
+        truthy(within(data, null, () => predicate(item)))
+      );
+      return found === undefined ? null : found;
+    },
+    position: (data, [list, predicate]) =>
+      items("position", list(data), false).findIndex((item) =>
+        truthy(within(data, null, () => predicate(item)))
+      ),
+    min_by: (data, [list, key]) =>
+      selectBy(items("min_by", list(data), false), (item) => within(data, null, () => key(item)), (a, b) => a < b),
+    max_by: (data, [list, key]) =>
+      selectBy(items("max_by", list(data), false), (item) => within(data, null, () => key(item)), (a, b) => a > b),
+    sum_by: (data, [list, key]) =>
+      ops["+"](...items("sum_by", list(data), false).map((item) => within(data, null, () => key(item)))),
+    distinct_by: (data, [list, key]) => {
+      const keys = [];
+      return items("distinct_by", list(data), false).filter((item) => {
+        const result = within(data, null, () => key(item));
+        if (keys.some((seen) => deepEqual(seen, result))) return false;
+        keys.push(result);
+        return true;
+      });
+    },
+    sort_by: (data, [list, key, descending]) => {
+      const values = items("sort_by", list(data), false);
+      const isDescending = descendingFlags("sort_by", descending ? descending(data) : false);
+      const keys = values.map((value) => {
+        const result = within(data, null, () => key(value));
+        if (!sortKey(result)) fail("sort_by", `${JSON.stringify(result)} is not a sort key`);
+        return result;
+      });
+      const compare = (a, b, i) => (isDescending(i) ? compareKeys(b, a) : compareKeys(a, b));
+      return values
+        .map((_, i) => i)
+        .sort((a, b) => {
+          const [first, second] = [keys[a], keys[b]];
+          if (!Array.isArray(first) || !Array.isArray(second)) return compare(first, second, 0);
+          for (let i = 0; i < Math.min(first.length, second.length); i++) {
+            const order = compare(first[i], second[i], i);
+            if (order !== 0) return order;
+          }
+          return first.length - second.length;
+        })
+        .map((i) => values[i]);
+    },
+    sort_with: (data, [list, comparison, descending]) => {
+      const values = items("sort_with", list(data), false);
+      const isDescending = descendingFlags("sort_with", descending ? descending(data) : false);
+      return [...values].sort((a, b) => {
+        const result = within(data, null, () => comparison({ a, b }));
+        if (typeof result !== "number") fail("sort_with", "comparisons must evaluate to numbers");
+        return isDescending(0) ? -result : result;
+      });
+    },
+  };
+  lazy["?:"] = lazy.if;
+  lazy.coalesce = (data, args) => {
+    for (const arg of args) {
+      const value = arg(data);
+      if (value !== null) return value;
+    }
+    return null;
+  };
+  lazy.pipe = (data, [initial, ...steps]) =>
+    steps.reduce((result, step) => step(result), initial(data));
+
+  lazy.try = (data, [expr, fallback, name]) => {
+    try {
+      return expr(data);
+    } catch (error) {
+      if (name === undefined) return fallback(data);
+      const base = isObject(data) && !Array.isArray(data) ? data : {};
+      return fallback({ ...base, [name(data)]: error.message });
+    }
+  };
+
+  const kindOf = (value) => {
+    if (value === null) return "null";
+    if (Array.isArray(value)) return "array";
+    return typeof value;
+  };
+
+  // Whether a value matches a `match` pattern, adding any captures
+  const matchPattern = (pattern, value, captures) => {
+    const tag = isObject(pattern) && !Array.isArray(pattern) ? Object.keys(pattern) : [];
+    if (tag.length === 1 && tag[0] === "type") return kindOf(value) === pattern.type;
+    if (tag.length === 1 && tag[0] === "bind") {
+      captures[pattern.bind] = value;
+      return true;
+    }
+    if (tag.length === 1 && tag[0] === "shape") {
+      return (
+        kindOf(value) === "object" &&
+        Object.keys(pattern.shape).every(
+          (key) => has(value, key) && matchPattern(pattern.shape[key], value[key], captures)
+        )
+      );
+    }
+    return deepEqual(pattern, value);
+  };
+  lazy.match = (data, [scrutinee, ...arms]) => {
+    const value = scrutinee(data);
+    for (let i = 0; i < arms.length; i += 2) {
+      if (i + 1 === arms.length) return arms[i](data);
+      const captures = {};
+      if (matchPattern(arms[i](data), value, captures)) {
+        if (Object.keys(captures).length === 0) return arms[i + 1](data);
+        const base = kindOf(data) === "object" ? data : {};
+        return arms[i + 1]({ ...base, ...captures });
+      }
+    }
+    throw new Error(`No pattern matched the value ${JSON.stringify(value)}`);
+  };
+
+  const variable = (data, key, fallback) => {
+    const path = splitKey(key === undefined ? null : key);
+    let [target, current] = [data, scope];
+    for (; path[0] === PARENT; path.shift()) {
+      if (current === null) return fallback === undefined ? null : fallback(data);
+      [target, current] = [current.data, current.outer];
+    }
+    if (semantics >= 2 && path.length === 1 && path[0] === "#index" && current !== null && current.index !== null) {
+      return current.index;
+    }
+    const value = resolve(target, path);
+    if (value !== undefined) return value;
+    return fallback === undefined ? null : fallback(data);
+  };
+
+  // Before semantics version 2, a `var` default whose value is a rule is
+  // evaluated again, which a translated rule can't do
+  const defaultValue = (value) => {
+    const keys = kindOf(value) === "object" ? Object.keys(value) : [];
+    const operator = keys.length === 1 ? keys[0] : undefined;
+    if (
+      semantics < 2 &&
+      operator !== undefined &&
+      (has(ops, operator) || has(lazy, operator) || ["var", "missing", "missing_some", "lit"].includes(operator))
+    ) {
+      fail("var", "A default whose value is a rule can't be evaluated again");
+    }
+    return value;
+  };
+
+  const missing = (data, ...args) =>
+    (Array.isArray(args[0]) ? args[0] : args).filter(
+      (key) => key !== null && resolve(data, splitKey(key)) === undefined
+    );
+
+  const missingSome = (data, threshold, keys) => {
+    if (!Number.isInteger(threshold) || threshold < 0) {
+      fail("missing_some", "missing_some threshold must be a valid, positive integer");
+    }
+    if (!Array.isArray(keys)) fail("missing_some", "missing_some keys must be an array");
+    const missingKeys = [];
+    let present = 0;
+    for (const key of keys) {
+      if (present >= threshold) break;
+      if (key === null) continue;
+      if (
+        resolve(data, splitKey(key)) === undefined &&
+        !missingKeys.some((missingKey) => deepEqual(missingKey, key))
+      ) {
+        missingKeys.push(key);
+      } else {
+        present += 1;
+      }
+    }
+    return present >= threshold ? [] : missingKeys;
+  };
+
+  // Call a function's body, checking the `pre` and `post` conditions of its
+  // contract. `post` is applied to the body's result.
+  const contract = (name, data, params, pre, body, post) => {
+    const violation = (condition) => {
+      throw new Error(
+        `Contract violation - ${condition}condition of '${name}' failed for arguments ${JSON.stringify(params)}`,
+      );
+    };
+    if (pre !== null && !truthy(pre(data))) violation("pre");
+    const result = body();
+    if (post !== null && !truthy(post(result))) violation("post");
+    return result;
+  };
+
+  // Wrap a function's body to bind the arguments it's called with to its
+  // parameters: evaluating the defaults of any omitted trailing parameters,
+  // which see the parameters before them, and collecting any remaining
+  // arguments for a rest parameter
+  const bind = (body, required, defaults, rest) => (data, args) => {
+    const positional = required + defaults.length;
+    const params = args.slice(0, positional);
+    while (params.length < positional) {
+      params.push(defaults[params.length - required](data, params.slice()));
+    }
+    if (rest) params.push(args.slice(positional));
+    return body(data, params);
+  };
+
+  // The value of a function value's single key, if it has the given key
+  const functionValue = (value, key) => {
+    const keys = isObject(value) && !Array.isArray(value) ? Object.keys(value) : [];
+    return keys.length === 1 && keys[0] === key ? value[key] : undefined;
+  };
+
+  // The function value a closure calls, and the arguments it captured
+  const closureParts = (value) => {
+    const parts = functionValue(value, "closure");
+    return Array.isArray(parts) && parts.length === 2 && Array.isArray(parts[1])
+      ? parts
+      : undefined;
+  };
+
+  const isFunctionValue = (value) => {
+    const composed = functionValue(value, "comp");
+    const closed = closureParts(value);
+    return (
+      typeof functionValue(value, "fn") === "string" ||
+      (closed !== undefined && isFunctionValue(closed[0])) ||
+      (Array.isArray(composed) && composed.every(isFunctionValue))
+    );
+  };
+
+  ops.closure = (fn, ...captured) => {
+    if (!isFunctionValue(fn)) fail("closure", "Expected a function reference");
+    return { closure: [fn, captured] };
+  };
+
+  ops.partial = (fn, args) => {
+    if (!Array.isArray(args)) fail("partial", "Arguments must be an array");
+    if (!isFunctionValue(fn)) fail("partial", "Expected a function reference");
+    return { closure: [fn, args] };
+  };
+
+  ops.comp = (...fns) => {
+    if (!fns.every(isFunctionValue)) fail("comp", "Expected a function reference");
+    return { comp: fns };
+  };
+
+  // Call a function value: a reference to a function, e.g. `{"fn": "double"}`,
+  // a closure, or a composition of function values, given the functions that
+  // may be called and the least and greatest number of arguments each takes,
+  // the latter being null for functions with a rest parameter
+  const call = (functions, arities, data, fn, args) => {
+    if (!Array.isArray(args)) fail("call", "Arguments must be an array");
+    const callValue = (fn, args) => {
+      const composed = functionValue(fn, "comp");
+      if (Array.isArray(composed)) {
+        if (composed.length === 0) fail("call", "A composition must have at least one function");
+        return composed
+          .slice(0, -1)
+          .reduceRight((result, f) => callValue(f, [result]), callValue(composed.at(-1), args));
+      }
+      const closed = closureParts(fn);
+      if (closed !== undefined) return callValue(closed[0], [...closed[1], ...args]);
+      const name = functionValue(fn, "fn");
+      if (typeof name !== "string") fail("call", "Expected a function reference");
+      if (!Object.hasOwn(arities, name)) {
+        fail("call", `'${name}' is not a function referenced by the rule`);
+      }
+      const [min, max] = arities[name];
+      if (args.length < min || (max !== null && args.length > max)) {
+        throw new Error(`Wrong number of arguments for '${name}'`);
+      }
+      return functions[name](data, args);
+    };
+    return callValue(fn, args);
+  };
+
+  return { ops, lazy, var: variable, default: defaultValue, missing, missing_some: missingSome, contract, bind, call };
+})();
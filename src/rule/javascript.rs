@@ -0,0 +1,370 @@
+//! JavaScript Translation
+//!
+//! `Rule::to_javascript()` emits a self-contained JavaScript function with
+//! the same semantics as the rule, so that rules maintained in Rust can be
+//! executed client-side without shipping the wasm build of the crate.
+
+use serde_json::{json, Map, Value};
+
+use crate::error::Error;
+use crate::op;
+use crate::op::func::FunctionBody;
+use crate::op::logic;
+use crate::prelude::*;
+use crate::rule::Rule;
+use crate::value::Parsed;
+
+/// The operator implementations the translated rule calls into
+const RUNTIME: &str = include_str!("javascript.js");
+
+impl Rule {
+    /// Translate the rule into JavaScript source.
+    ///
+    /// The source is an expression evaluating to a function of the data,
+    /// e.g. `const rule = eval(source); rule({"age": 21})`. Functions
+    /// from the rule's namespace are translated along with it. Errors are
+    /// thrown as exceptions.
+    ///
+    /// Functions' contracts are always checked, regardless of
+    /// `Context::skip_contracts`. The function evaluates the rule with the
+    /// semantics version it's pinned to, or else the default one.
+    ///
+    /// Custom operators and `lookup` depend on the host, and so can't be
+    /// translated.
+    pub fn to_javascript(&self) -> Result<String, Error> {
+        let mut translator = Translator::default();
+        let mut functions: Vec<(&String, &FunctionBody)> = self
+            .functions
+            .iter()
+            .flat_map(|functions| functions.iter())
+            .collect();
+        functions.sort_by(|a, b| a.0.cmp(b.0));
+        let functions: Vec<String> = functions
+            .into_iter()
+            .map(|(name, body)| {
+                let expression = translator.expr(&body.expression);
+                let mut definition = if body.has_contract() {
+                    let mut condition = |cond: &Option<Parsed>| match cond {
+                        Some(cond) => format!("(data) => ({})", translator.expr(cond)),
+                        None => "null".into(),
+                    };
+                    let (pre, post) = (condition(&body.pre), condition(&body.post));
+                    format!(
+                        "  functions[{}] = (data, params) => $.contract({}, data, params, {}, () => ({}), {});\n",
+                        string(name),
+                        string(name),
+                        pre,
+                        expression,
+                        post
+                    )
+                } else {
+                    format!(
+                        "  functions[{}] = (data, params) => ({});\n",
+                        string(name),
+                        expression
+                    )
+                };
+                if !body.signature.is_positional() {
+                    let defaults: Vec<String> = body
+                        .defaults
+                        .iter()
+                        .map(|default| format!("(data, params) => ({})", translator.expr(default)))
+                        .collect();
+                    definition.push_str(&format!(
+                        "  functions[{}] = $.bind(functions[{}], {}, [{}], {});\n",
+                        string(name),
+                        string(name),
+                        body.signature.required,
+                        defaults.join(", "),
+                        body.signature.rest
+                    ));
+                }
+                definition
+            })
+            .collect();
+        let rule = translator.expr(&self.parsed);
+        let arities = if translator.dynamic_calls {
+            let arities: Map<String, Value> = self
+                .functions
+                .iter()
+                .flat_map(|functions| functions.iter())
+                .map(|(name, body)| {
+                    let signature = &body.signature;
+                    let max = if signature.rest {
+                        Value::Null
+                    } else {
+                        (signature.required + signature.optional).into()
+                    };
+                    (name.clone(), json!([signature.required, max]))
+                })
+                .collect();
+            format!("  const arities = {};\n", Value::Object(arities))
+        } else {
+            String::new()
+        };
+
+        if !translator.unsupported.is_empty() {
+            translator.unsupported.sort();
+            translator.unsupported.dedup();
+            return Err(Error::Untranslatable {
+                target: "JavaScript",
+                unsupported: translator.unsupported,
+            });
+        }
+        Ok(format!(
+            "(() => {{\n  \"use strict\";\n  const semantics = {};\n{}  const functions = {{}};\n{}{}  return (data) => ({});\n}})()",
+            self.semantics.unwrap_or_default(),
+            RUNTIME
+                .lines()
+                .map(|line| match line {
+                    "" => "\n".to_string(),
+                    line => format!("  {}\n", line),
+                })
+                .collect::<String>(),
+            functions.concat(),
+            arities,
+            rule
+        ))
+    }
+}
+
+#[derive(Default)]
+struct Translator {
+    unsupported: Vec<String>,
+    /// Whether functions are called by reference, with `call`
+    dynamic_calls: bool,
+}
+impl Translator {
+    fn expr(&mut self, parsed: &Parsed) -> String {
+        match parsed {
+            // These depend on the evaluation context
+            Parsed::Operation(op) if op::NONDETERMINISTIC.contains(&op.symbol()) => {
+                self.unsupported.push(op.symbol().into());
+                String::new()
+            }
+            // Their results depend on the context's locale, exchange rates,
+            // and units
+            Parsed::Operation(op)
+                if matches!(op.symbol(), "format" | "money_convert" | "convert") =>
+            {
+                self.unsupported.push(op.symbol().into());
+                String::new()
+            }
+            // The runtime doesn't include feature-gated operators
+            Parsed::Operation(op)
+                if op::FEATURE_OPERATORS
+                    .iter()
+                    .any(|feature| feature.symbol() == op.symbol()) =>
+            {
+                self.unsupported.push(op.symbol().into());
+                String::new()
+            }
+            Parsed::Operation(op) => format!(
+                "$.ops[{}]({})",
+                string(op.symbol()),
+                self.args(op.arguments())
+            ),
+            Parsed::LazyOperation(op) if matches!(op.symbol(), "call" | "apply") => {
+                self.dynamic_calls = true;
+                format!(
+                    "$.call(functions, arities, data, {})",
+                    self.args(op.arguments())
+                )
+            }
+            // Only the branch of the matching case is evaluated
+            Parsed::LazyOperation(op) if op.symbol() == logic::SWITCH => {
+                match op.arguments() {
+                    [scrutinee, Parsed::Object(cases), default @ ..] => {
+                        let cases = cases
+                            .entries()
+                            .iter()
+                            .map(|(key, branch)| {
+                                format!(
+                                    "{}: {}",
+                                    string(key),
+                                    self.thunks(core::slice::from_ref(branch))
+                                )
+                            })
+                            .collect::<Vec<String>>();
+                        let mut args = vec![
+                            self.thunks(core::slice::from_ref(scrutinee)),
+                            format!("{{{}}}", cases.join(", ")),
+                        ];
+                        if !default.is_empty() {
+                            args.push(self.thunks(default));
+                        }
+                        format!(
+                            "$.lazy[{}](data, [{}])",
+                            string(op.symbol()),
+                            args.join(", ")
+                        )
+                    }
+                    _ => {
+                        self.unsupported.push(op.symbol().into());
+                        String::new()
+                    }
+                }
+            }
+            Parsed::LazyOperation(op) if op.symbol() == "default" => {
+                self.lookups(op.arguments())
+            }
+            Parsed::LazyOperation(op) => format!(
+                "$.lazy[{}](data, [{}])",
+                string(op.symbol()),
+                self.thunks(op.arguments())
+            ),
+            // The runtime doesn't include feature-gated operators
+            Parsed::DataOperation(op)
+                if op::FEATURE_DATA_OPERATORS
+                    .iter()
+                    .any(|feature| feature.symbol() == op.symbol()) =>
+            {
+                self.unsupported.push(op.symbol().into());
+                String::new()
+            }
+            Parsed::DataOperation(op) => {
+                format!("$.{}(data, {})", op.symbol(), self.args(op.arguments()))
+            }
+            Parsed::Variable(var) => match var.arguments() {
+                [] => "$.var(data)".into(),
+                [key] => format!("$.var(data, {})", self.expr(key)),
+                [key, default, ..] => format!(
+                    "$.var(data, {}, (data) => $.default({}))",
+                    self.expr(key),
+                    self.expr(default)
+                ),
+            },
+            Parsed::CustomOperation(op) => {
+                self.unsupported.push(op.operator().name().into());
+                String::new()
+            }
+            Parsed::Call(call) => format!(
+                "functions[{}](data, [{}])",
+                string(call.name()),
+                self.args(call.arguments())
+            ),
+            Parsed::Param(param) => format!("params[{}]", param.index()),
+            Parsed::Object(obj) => format!(
+                "{{{}}}",
+                obj.entries()
+                    .iter()
+                    .map(|(key, value)| format!(
+                        "{}: {}",
+                        string(key),
+                        self.expr(value)
+                    ))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Parsed::Raw(raw) => raw.value().to_string(),
+        }
+    }
+
+    /// Arguments, evaluated eagerly
+    fn args(&mut self, args: &[Parsed]) -> String {
+        args.iter()
+            .map(|arg| self.expr(arg))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    /// The arguments to `default`, as lookups whose fallbacks are the
+    /// arguments that follow
+    fn lookups(&mut self, args: &[Parsed]) -> String {
+        match args {
+            [] => "null".into(),
+            [Parsed::Variable(var), rest @ ..] if var.arguments().len() < 2 => {
+                let key = match var.arguments() {
+                    [key] => self.expr(key),
+                    _ => "null".into(),
+                };
+                format!("$.var(data, {}, (data) => ({}))", key, self.lookups(rest))
+            }
+            [arg, ..] => self.expr(arg),
+        }
+    }
+
+    /// Arguments as functions of the data, to be evaluated lazily
+    fn thunks(&mut self, args: &[Parsed]) -> String {
+        args.iter()
+            .map(|arg| format!("(data) => ({})", self.expr(arg)))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+/// A JavaScript string literal
+fn string(s: &str) -> String {
+    Value::String(s.into()).to_string()
+}
+
+#[cfg(test)]
+mod test_javascript {
+    use super::*;
+    use serde_json::json;
+
+    use crate::op::NumParams;
+    use crate::pack::{CustomOperator, OperatorPack, PACK_API_VERSION};
+    use crate::Namespace;
+
+    struct Pack;
+    impl OperatorPack for Pack {
+        fn name(&self) -> &str {
+            "pack"
+        }
+        fn version(&self) -> &str {
+            "1.0.0"
+        }
+        fn api_version(&self) -> u32 {
+            PACK_API_VERSION
+        }
+        fn operators(&self) -> Vec<CustomOperator> {
+            vec![CustomOperator::new("shout", NumParams::Unary, |args| {
+                Ok(json!(args[0].to_string().to_uppercase()))
+            })]
+        }
+    }
+
+    #[test]
+    fn test_to_javascript() {
+        let mut ns = Namespace::new();
+        ns.define(&json!({"defn": ["double", ["n"], {"*": [{"param": "n"}, 2]}]}))
+            .unwrap();
+        let source = ns
+            .parse(&json!({"map": [{"var": "xs"}, {"double": [{"var": ""}]}]}))
+            .unwrap()
+            .to_javascript()
+            .unwrap();
+        assert!(
+            source.starts_with("(() => {\n  \"use strict\";\n  const semantics = 1;\n")
+        );
+        assert!(source.contains(
+            "  functions[\"double\"] = (data, params) => ($.ops[\"*\"](params[0], 2));\n"
+        ));
+        assert!(source.ends_with(
+            "  return (data) => ($.lazy[\"map\"](data, [(data) => ($.var(data, \"xs\")), \
+             (data) => (functions[\"double\"](data, [$.var(data, \"\")]))]));\n})()"
+        ));
+    }
+
+    #[test]
+    fn test_untranslatable() {
+        let mut ns = Namespace::new();
+        ns.load_pack(&Pack).unwrap();
+        let err = ns
+            .parse(&json!({"cat": [{"shout": "a"}, {"lookup": ["t", 1]}]}))
+            .unwrap()
+            .to_javascript()
+            .unwrap_err();
+        match err {
+            Error::Untranslatable {
+                target,
+                unsupported,
+            } => {
+                assert_eq!(target, "JavaScript");
+                assert_eq!(unsupported, vec!["lookup", "shout"]);
+            }
+            err => panic!("unexpected error: {}", err),
+        }
+    }
+}
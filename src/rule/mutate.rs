@@ -0,0 +1,443 @@
+//! Mutation Testing
+//!
+//! `Rule::mutation_test()` measures how well a rule's test cases constrain
+//! its behavior, by making small changes to the rule, each a "mutant", and
+//! running the tests against each. A mutant that fails some test is killed;
+//! one that passes every test survives, and points to behavior that the
+//! tests don't pin down, e.g. an untested boundary.
+//!
+//! Mutants are generated by:
+//!
+//!   - swapping an operator for a similar one: `<` and `<=`, `>` and `>=`,
+//!     `==` and `!=`, `===` and `!==`, `and` and `or`, and `min` and `max`
+//!   - moving a number compared with `<`, `<=`, `>`, or `>=` up or down by one
+//!   - deleting a branch: replacing a branch of `if` with `null`, or removing
+//!     an argument of `and` or `or`
+//!
+//! Only the rule itself is mutated, not the bodies of the functions it calls.
+
+use alloc::sync::Arc;
+use core::fmt;
+
+use serde_json::{Number, Value};
+
+use crate::prelude::*;
+use crate::rule::Rule;
+use crate::value::{Parsed, Raw};
+
+/// Pairs of operators that are swapped for one another
+const SWAPS: &[(&str, &str)] = &[
+    ("<", "<="),
+    (">", ">="),
+    ("==", "!="),
+    ("===", "!=="),
+    ("and", "or"),
+    ("min", "max"),
+];
+
+/// Operators whose numeric arguments are moved across their boundaries
+const COMPARISONS: &[&str] = &["<", "<=", ">", ">="];
+
+/// A change made to a rule to produce a mutant
+#[derive(Clone, Debug, PartialEq)]
+pub enum Mutation {
+    /// An operator replaced with a similar one, e.g. `<` with `<=`
+    SwapOperator {
+        from: &'static str,
+        to: &'static str,
+    },
+    /// A number in a comparison replaced with one a step away
+    Boundary { from: Value, to: Value },
+    /// A branch deleted: for `if`, replaced with `null`, and for `and` and
+    /// `or`, removed
+    DeleteBranch {
+        operator: &'static str,
+        index: usize,
+    },
+}
+impl fmt::Display for Mutation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SwapOperator { from, to } => {
+                write!(f, "replaced '{}' with '{}'", from, to)
+            }
+            Self::Boundary { from, to } => write!(f, "replaced {} with {}", from, to),
+            Self::DeleteBranch { operator, index } => {
+                write!(f, "deleted argument {} of '{}'", index, operator)
+            }
+        }
+    }
+}
+
+/// A mutated copy of a rule
+#[derive(Clone, Debug)]
+pub struct Mutant {
+    /// The JSON pointer to the mutated expression within the rule, e.g.
+    /// `/if/0/</1`
+    pub path: String,
+    /// The change that was made
+    pub mutation: Mutation,
+    /// The mutated rule, which has the original's id, functions, and tests
+    pub rule: Rule,
+}
+
+/// The outcome of running a rule's tests against a mutant
+#[derive(Clone, Debug)]
+pub struct MutantResult {
+    /// The mutant
+    pub mutant: Mutant,
+    /// The index of the first test the mutant failed, or None if it passed
+    /// every test and so survived
+    pub killed_by: Option<usize>,
+}
+
+/// The outcome of mutation testing a rule
+#[derive(Clone, Debug)]
+pub struct MutationReport {
+    /// The number of test cases run against each mutant
+    pub tests: usize,
+    /// Every mutant of the rule, in order
+    pub mutants: Vec<MutantResult>,
+}
+impl MutationReport {
+    /// The mutants that passed every test
+    pub fn survivors(&self) -> impl Iterator<Item = &Mutant> {
+        self.mutants
+            .iter()
+            .filter(|result| result.killed_by.is_none())
+            .map(|result| &result.mutant)
+    }
+
+    /// The fraction of mutants that were killed, which is 1 for rules
+    /// without mutants
+    pub fn score(&self) -> f64 {
+        if self.mutants.is_empty() {
+            return 1.0;
+        }
+        let killed = self
+            .mutants
+            .iter()
+            .filter(|result| result.killed_by.is_some());
+        killed.count() as f64 / self.mutants.len() as f64
+    }
+}
+
+impl Rule {
+    /// Every mutant of the rule, outermost expressions first.
+    pub fn mutants(&self) -> Vec<Mutant> {
+        mutants(&self.parsed)
+            .into_iter()
+            .map(|(path, mutation, parsed)| Mutant {
+                path,
+                mutation,
+                rule: Rule {
+                    parsed: Arc::new(parsed),
+                    ..self.clone()
+                },
+            })
+            .collect()
+    }
+
+    /// Run the rule's tests against each of its mutants, reporting which
+    /// survived.
+    ///
+    /// A rule without tests has every mutant survive.
+    pub fn mutation_test(&self) -> MutationReport {
+        let mutants = self
+            .mutants()
+            .into_iter()
+            .map(|mutant| MutantResult {
+                killed_by: mutant.rule.run_tests().iter().position(|r| !r.passed),
+                mutant,
+            })
+            .collect();
+        MutationReport {
+            tests: self.tests.len(),
+            mutants,
+        }
+    }
+}
+
+/// The mutants of an expression, with the paths of their mutations relative
+/// to it
+fn mutants(parsed: &Parsed) -> Vec<(String, Mutation, Parsed)> {
+    let mut found = Vec::new();
+    match parsed {
+        Parsed::Operation(op) => {
+            let symbol = op.symbol();
+            if let Some(swapped) = swap(symbol).and_then(|to| op.with_symbol(to)) {
+                found.push((
+                    String::new(),
+                    Mutation::SwapOperator {
+                        from: symbol,
+                        to: swapped.symbol(),
+                    },
+                    Parsed::Operation(swapped),
+                ));
+            }
+            if COMPARISONS.contains(&symbol) {
+                for (idx, arg) in op.arguments().iter().enumerate() {
+                    let from = match arg {
+                        Parsed::Raw(raw) if raw.value().is_number() => raw.value(),
+                        _ => continue,
+                    };
+                    for to in boundaries(from) {
+                        let mut mutated = op.clone();
+                        mutated.arguments_mut()[idx] =
+                            Parsed::Raw(Raw::new(to.clone()));
+                        found.push((
+                            format!("/{}/{}", escape(symbol), idx),
+                            Mutation::Boundary {
+                                from: from.clone(),
+                                to,
+                            },
+                            Parsed::Operation(mutated),
+                        ));
+                    }
+                }
+            }
+        }
+        Parsed::LazyOperation(op) => {
+            let symbol = op.symbol();
+            if let Some(swapped) = swap(symbol).and_then(|to| op.with_symbol(to)) {
+                found.push((
+                    String::new(),
+                    Mutation::SwapOperator {
+                        from: symbol,
+                        to: swapped.symbol(),
+                    },
+                    Parsed::LazyOperation(swapped),
+                ));
+            }
+            let len = op.arguments().len();
+            for idx in 0..len {
+                let mut mutated = op.clone();
+                match symbol {
+                    "if" | "?:" if idx % 2 == 1 || (idx == len - 1 && len > 1) => {
+                        if let Parsed::Raw(raw) = &op.arguments()[idx] {
+                            if raw.value().is_null() {
+                                continue;
+                            }
+                        }
+                        mutated.arguments_mut()[idx] =
+                            Parsed::Raw(Raw::new(Value::Null));
+                    }
+                    "and" | "or" if len > 1 => {
+                        mutated.arguments_mut().remove(idx);
+                    }
+                    _ => continue,
+                }
+                found.push((
+                    format!("/{}/{}", escape(symbol), idx),
+                    Mutation::DeleteBranch {
+                        operator: symbol,
+                        index: idx,
+                    },
+                    Parsed::LazyOperation(mutated),
+                ));
+            }
+        }
+        _ => {}
+    }
+
+    for (idx, (segment, child)) in children(parsed).into_iter().enumerate() {
+        for (path, mutation, mutated) in mutants(child) {
+            found.push((
+                format!("{}{}", segment, path),
+                mutation,
+                with_child(parsed, idx, mutated),
+            ));
+        }
+    }
+    found
+}
+
+/// The operator swapped for the given one, if any
+fn swap(symbol: &str) -> Option<&'static str> {
+    SWAPS.iter().find_map(|&(a, b)| {
+        if symbol == a {
+            Some(b)
+        } else if symbol == b {
+            Some(a)
+        } else {
+            None
+        }
+    })
+}
+
+/// The numbers a step below and above a number
+fn boundaries(value: &Value) -> Vec<Value> {
+    match value.as_i64() {
+        Some(int) => [int.checked_sub(1), int.checked_add(1)]
+            .iter()
+            .flatten()
+            .map(|int| Value::from(*int))
+            .collect(),
+        None => value
+            .as_f64()
+            .into_iter()
+            .flat_map(|float| [float - 1.0, float + 1.0])
+            .filter_map(Number::from_f64)
+            .map(Value::Number)
+            .collect(),
+    }
+}
+
+/// The sub-expressions of an expression that may be mutated, with the
+/// segments of their JSON pointers
+fn children(parsed: &Parsed) -> Vec<(String, &Parsed)> {
+    let (key, args): (&str, &[Parsed]) = match parsed {
+        Parsed::Operation(op) => (op.symbol(), op.arguments()),
+        Parsed::LazyOperation(op) => (op.symbol(), op.arguments()),
+        Parsed::DataOperation(op) => (op.symbol(), op.arguments()),
+        Parsed::CustomOperation(op) => (op.operator().name(), op.arguments()),
+        Parsed::Call(call) => (call.name(), call.arguments()),
+        Parsed::Object(obj) => {
+            return obj
+                .entries()
+                .iter()
+                .map(|(key, value)| (format!("/{}", escape(key)), value))
+                .collect()
+        }
+        Parsed::Variable(_) | Parsed::Param(_) | Parsed::Raw(_) => return Vec::new(),
+    };
+    args.iter()
+        .enumerate()
+        .map(|(idx, arg)| (format!("/{}/{}", escape(key), idx), arg))
+        .collect()
+}
+
+/// A copy of an expression with the sub-expression at `idx` among its
+/// `children()` replaced.
+fn with_child(parsed: &Parsed, idx: usize, child: Parsed) -> Parsed {
+    let mut parsed = parsed.clone();
+    match &mut parsed {
+        Parsed::Operation(op) => op.arguments_mut()[idx] = child,
+        Parsed::LazyOperation(op) => op.arguments_mut()[idx] = child,
+        Parsed::DataOperation(op) => op.arguments_mut()[idx] = child,
+        Parsed::CustomOperation(op) => op.arguments_mut()[idx] = child,
+        Parsed::Call(call) => call.arguments_mut()[idx] = child,
+        Parsed::Object(obj) => obj.entries_mut()[idx].1 = child,
+        Parsed::Variable(_) | Parsed::Param(_) | Parsed::Raw(_) => {}
+    }
+    parsed
+}
+
+/// Escape a key for use in a JSON pointer.
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod test_mutate {
+    use super::*;
+    use serde_json::json;
+
+    use crate::rule::TestCase;
+
+    fn parsed(value: Value) -> Value {
+        Value::from(Rule::from_value(&value).unwrap())
+    }
+
+    fn described(mutants: &[Mutant]) -> Vec<(&str, String)> {
+        mutants
+            .iter()
+            .map(|mutant| (mutant.path.as_str(), mutant.mutation.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_mutants() {
+        let rule = Rule::from_value(&json!({"if": [
+            {">=": [{"var": "age"}, 18]},
+            "adult",
+            {"and": [{"var": "guardian"}, {"==": [{"var": "consent"}, true]}]}
+        ]}))
+        .unwrap();
+        let mutants = rule.mutants();
+        assert_eq!(
+            described(&mutants),
+            vec![
+                ("/if/1", "deleted argument 1 of 'if'".into()),
+                ("/if/2", "deleted argument 2 of 'if'".into()),
+                ("/if/0", "replaced '>=' with '>'".into()),
+                ("/if/0/>=/1", "replaced 18 with 17".into()),
+                ("/if/0/>=/1", "replaced 18 with 19".into()),
+                ("/if/2", "replaced 'and' with 'or'".into()),
+                ("/if/2/and/0", "deleted argument 0 of 'and'".into()),
+                ("/if/2/and/1", "deleted argument 1 of 'and'".into()),
+                ("/if/2/and/1", "replaced '==' with '!='".into()),
+            ]
+        );
+        assert_eq!(
+            Value::from(mutants[4].rule.clone()),
+            parsed(json!({"if": [
+                {">=": [{"var": "age"}, 19]},
+                "adult",
+                {"and": [{"var": "guardian"}, {"==": [{"var": "consent"}, true]}]}
+            ]}))
+        );
+        assert_eq!(
+            Value::from(mutants[6].rule.clone()),
+            parsed(json!({"if": [
+                {">=": [{"var": "age"}, 18]},
+                "adult",
+                {"and": [{"==": [{"var": "consent"}, true]}]}
+            ]}))
+        );
+        assert!(Rule::from_value(&json!({"var": "x"}))
+            .unwrap()
+            .mutants()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_mutation_test() {
+        let rule = Rule::from_value(&json!({"if": [
+            {"<": [{"var": "age"}, 18]}, "minor", "adult"
+        ]}))
+        .unwrap();
+        let with_tests = |cases: Value| {
+            rule.clone()
+                .with_tests(TestCase::from_values(&cases).unwrap())
+        };
+
+        let report = with_tests(json!([
+            {"data": {"age": 30}, "expect": "adult"}
+        ]))
+        .mutation_test();
+        assert_eq!(report.tests, 1);
+        assert_eq!(
+            report
+                .survivors()
+                .map(|mutant| mutant.mutation.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "deleted argument 1 of 'if'",
+                "replaced '<' with '<='",
+                "replaced 18 with 17",
+                "replaced 18 with 19",
+            ]
+        );
+        assert!((report.score() - 0.2).abs() < f64::EPSILON);
+
+        let report = with_tests(json!([
+            {"data": {"age": 30}, "expect": "adult"},
+            {"data": {"age": 17}, "expect": "minor"},
+            {"data": {"age": 18}, "expect": "adult"}
+        ]))
+        .mutation_test();
+        assert_eq!(report.survivors().count(), 0);
+        assert_eq!(
+            report
+                .mutants
+                .iter()
+                .map(|result| result.killed_by)
+                .collect::<Vec<_>>(),
+            vec![Some(1), Some(0), Some(2), Some(1), Some(2)]
+        );
+        assert_eq!(report.score(), 1.0);
+
+        assert_eq!(rule.mutation_test().survivors().count(), 5);
+    }
+}
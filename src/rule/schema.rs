@@ -0,0 +1,463 @@
+//! Data Schemas
+//!
+//! Infer a JSON Schema for the data a rule is applied to, from the variables
+//! it reads and the operators it passes them to.
+
+use alloc::collections::{BTreeMap, BTreeSet};
+
+use serde_json::{Map, Value};
+
+use crate::ast::Ast;
+use crate::op::data::{self, key_path, PathSegment};
+use crate::op::{ITERATORS, NUMERIC};
+use crate::prelude::*;
+use crate::rule::Rule;
+use crate::value::Evaluated;
+
+const DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+const ARRAY: &[&str] = &["array"];
+const NUMBER: &[&str] = &["number"];
+const STRING: &[&str] = &["string"];
+const ARRAY_OR_STRING: &[&str] = &["array", "string"];
+const OBJECT: &[&str] = &["object"];
+
+impl Rule {
+    /// A JSON Schema (draft 2020-12) describing the data the rule expects.
+    ///
+    /// Each `var` and `missing` path becomes a property. Properties read by
+    /// a `var` without a default, outside of any `if`, `and`, or `or` branch
+    /// that might not be evaluated, are required. Types are inferred where
+    /// a variable is passed directly to an operator that expects them, e.g.
+    /// numbers for `+` and arrays for `map`, and the variables read within
+    /// `map`, `filter`, etc. describe the array's items. Paths are not
+    /// followed past array indices.
+    ///
+    /// The schema is a best guess: it can't describe data read via computed
+    /// paths, and data that it rejects may still evaluate successfully, e.g.
+    /// `"1"` for a variable added with `+`.
+    pub fn data_schema(&self) -> Value {
+        let mut inference = Inference::default();
+        inference.expr(&self.ast(), &Scope::default(), true, None);
+        let mut schema = Map::new();
+        schema.insert("$schema".into(), DIALECT.into());
+        schema.extend(inference.root.schema());
+        Value::Object(schema)
+    }
+}
+
+/// A step from one schema into a nested one
+#[derive(Clone, Debug)]
+enum Step {
+    Property(String),
+    Items,
+}
+
+/// The schema that variables are read from
+#[derive(Clone, Debug, Default)]
+struct Scope {
+    steps: Vec<Step>,
+    /// Whether this is the element scope of a `reduce`, whose data has the
+    /// element at `current`
+    reduce: bool,
+    /// For an element scope, the scope of the iterating operator, which
+    /// `../` variables read from
+    outer: Option<Box<Scope>>,
+}
+impl Scope {
+    fn items(&self, reduce: bool, outer: &Scope) -> Self {
+        let mut steps = self.steps.clone();
+        steps.push(Step::Items);
+        Self {
+            steps,
+            reduce,
+            outer: Some(Box::new(outer.clone())),
+        }
+    }
+}
+
+/// The schema of a value, as it is built up
+#[derive(Debug, Default)]
+struct Node {
+    types: BTreeSet<&'static str>,
+    properties: BTreeMap<String, Node>,
+    required: BTreeSet<String>,
+    items: Option<Box<Node>>,
+}
+impl Node {
+    fn get_mut(&mut self, steps: &[Step]) -> &mut Node {
+        match steps.split_first() {
+            None => self,
+            Some((Step::Property(key), rest)) => self
+                .properties
+                .entry(key.clone())
+                .or_default()
+                .get_mut(rest),
+            Some((Step::Items, rest)) => self
+                .items
+                .get_or_insert_with(Default::default)
+                .get_mut(rest),
+        }
+    }
+
+    fn schema(&self) -> Map<String, Value> {
+        let mut types = self.types.clone();
+        if !self.properties.is_empty() {
+            types.insert("object");
+        }
+        if self.items.is_some() {
+            types.insert("array");
+        }
+        let mut schema = Map::new();
+        match types.len() {
+            0 => {}
+            1 => {
+                schema.insert("type".into(), types.iter().next().copied().into());
+            }
+            _ => {
+                schema.insert("type".into(), types.into_iter().collect());
+            }
+        }
+        if !self.properties.is_empty() {
+            let properties = self
+                .properties
+                .iter()
+                .map(|(key, node)| (key.clone(), Value::Object(node.schema())))
+                .collect();
+            schema.insert("properties".into(), Value::Object(properties));
+        }
+        if !self.required.is_empty() {
+            schema.insert("required".into(), self.required.iter().cloned().collect());
+        }
+        if let Some(items) = &self.items {
+            schema.insert("items".into(), Value::Object(items.schema()));
+        }
+        schema
+    }
+}
+
+#[derive(Default)]
+struct Inference {
+    root: Node,
+}
+impl Inference {
+    /// Record the variables an expression reads within `scope`. Variables
+    /// read when `required` are required, and `hint` holds the types the
+    /// expression's value is expected to have.
+    fn expr(
+        &mut self,
+        ast: &Ast,
+        scope: &Scope,
+        required: bool,
+        hint: Option<&'static [&'static str]>,
+    ) {
+        match ast {
+            Ast::Var { path, default } => {
+                match path.as_deref() {
+                    // The index of an element isn't read from the data
+                    Some(Ast::Literal(Value::String(key)))
+                        if key == data::INDEX
+                            && matches!(scope.steps.last(), Some(Step::Items)) => {}
+                    Some(Ast::Literal(key)) => {
+                        self.variable(key, scope, required && default.is_none(), hint)
+                    }
+                    _ => {}
+                }
+                for arg in path.iter().chain(default) {
+                    self.expr(arg, scope, false, None);
+                }
+            }
+            Ast::Operator { name, args } => self.operator(name, args, scope, required),
+            Ast::FunctionCall { args, .. } => {
+                for arg in args {
+                    self.expr(arg, scope, required, None);
+                }
+            }
+            Ast::Object(entries) => {
+                for (_, value) in entries {
+                    self.expr(value, scope, required, None);
+                }
+            }
+            Ast::FunctionDef { .. } | Ast::Literal(_) | Ast::Param(_) => {}
+        }
+    }
+
+    fn operator(&mut self, name: &str, args: &[Ast], scope: &Scope, required: bool) {
+        match name {
+            "missing" => {
+                let keys: Vec<&Value> = match args {
+                    [Ast::Literal(Value::Array(keys))] => keys.iter().collect(),
+                    _ => args
+                        .iter()
+                        .filter_map(|arg| match arg {
+                            Ast::Literal(key) => Some(key),
+                            _ => None,
+                        })
+                        .collect(),
+                };
+                for key in keys {
+                    self.variable(key, scope, false, None);
+                }
+            }
+            "missing_some" => {
+                if let [_, Ast::Literal(Value::Array(keys))] = args {
+                    for key in keys {
+                        self.variable(key, scope, false, None);
+                    }
+                }
+            }
+            // Steps after the first read the previous step's result
+            "pipe" => {
+                if let Some(initial) = args.first() {
+                    self.expr(initial, scope, required, None);
+                }
+            }
+            // The expression reads the arrays' tuples
+            "zip_with" => {
+                for arg in &args[..args.len().saturating_sub(1)] {
+                    self.expr(arg, scope, required, Some(ARRAY));
+                }
+            }
+            // The comparison reads pairs of elements
+            "sort_with" => {
+                for (i, arg) in args.iter().enumerate() {
+                    match i {
+                        0 => self.expr(arg, scope, required, Some(ARRAY)),
+                        1 => {}
+                        _ => self.expr(arg, scope, required, None),
+                    }
+                }
+            }
+            // The expression reads the object's values
+            "map_values" => {
+                if let Some(object) = args.first() {
+                    self.expr(object, scope, required, Some(OBJECT));
+                }
+            }
+            "if" | "?:" | "and" | "or" | "match" | "switch" | "try" | "coalesce"
+            | "default" => {
+                for (i, arg) in args.iter().enumerate() {
+                    self.expr(arg, scope, required && i == 0, None);
+                }
+            }
+            _ if ITERATORS.contains(&name) => {
+                let mut elements = None;
+                for (i, arg) in args.iter().enumerate() {
+                    match i {
+                        0 => {
+                            self.expr(arg, scope, required, Some(ARRAY));
+                            elements = array_scope(
+                                arg,
+                                scope,
+                                matches!(name, "reduce" | "reduce_while"),
+                            );
+                        }
+                        1 => {
+                            if let Some(elements) = &elements {
+                                self.expr(arg, elements, true, None);
+                            }
+                        }
+                        _ => self.expr(arg, scope, required, None),
+                    }
+                }
+            }
+            _ => {
+                let hint = |i: usize| match (name, i) {
+                    _ if NUMERIC.contains(&name) => Some(NUMBER),
+                    ("in", 1) => Some(ARRAY_OR_STRING),
+                    ("substr", 0) => Some(STRING),
+                    ("substr", _) => Some(NUMBER),
+                    ("convert", 0) | ("range", _) => Some(NUMBER),
+                    _ => None,
+                };
+                for (i, arg) in args.iter().enumerate() {
+                    self.expr(arg, scope, required, hint(i));
+                }
+            }
+        }
+    }
+
+    fn variable(
+        &mut self,
+        key: &Value,
+        scope: &Scope,
+        required: bool,
+        hint: Option<&'static [&'static str]>,
+    ) {
+        let (scope, keys, complete) = match variable_steps(key, scope) {
+            Some(path) => path,
+            None => return,
+        };
+        let mut node = self.root.get_mut(&scope.steps);
+        for key in keys {
+            if required {
+                node.required.insert(key.clone());
+            }
+            node = node.properties.entry(key).or_default();
+        }
+        if let (Some(hint), true) = (hint, complete) {
+            node.types.extend(hint);
+        }
+    }
+}
+
+/// The scope a variable is read from, which is an outer one for `../`
+/// variables, the object keys of its path within the scope, and whether
+/// they are the whole path, or if the path continued with an array index.
+/// `None` if the variable isn't read from the scope.
+fn variable_steps(key: &Value, scope: &Scope) -> Option<(Scope, Vec<String>, bool)> {
+    let mut segments = key_path(Evaluated::Raw(key)).ok()?.into_iter().peekable();
+    let mut scope = scope;
+    while segments.next_if(PathSegment::is_parent).is_some() {
+        scope = scope.outer.as_deref()?;
+    }
+    if scope.reduce && segments.next()?.key() != "current" {
+        return None;
+    }
+    let mut keys = Vec::new();
+    for segment in segments {
+        if segment.index().is_some() || segment.is_wildcard() || segment.is_slice() {
+            return Some((scope.clone(), keys, false));
+        }
+        keys.push(segment.key().to_string());
+    }
+    Some((scope.clone(), keys, true))
+}
+
+/// The scope of the elements of an array, if it is read from a variable
+fn array_scope(ast: &Ast, scope: &Scope, reduce: bool) -> Option<Scope> {
+    match ast {
+        Ast::Var {
+            path: Some(path), ..
+        } => match path.as_ref() {
+            Ast::Literal(key) => match variable_steps(key, scope)? {
+                (mut array, keys, true) => {
+                    array.reduce = false;
+                    array.steps.extend(keys.into_iter().map(Step::Property));
+                    Some(array.items(reduce, scope))
+                }
+                (_, _, false) => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test_schema {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_data_schema() {
+        let rule = Rule::from_value(&json!({"and": [
+            {">=": [{"+": [{"var": "user.age"}, 1]}, 18]},
+            {"in": ["admin", {"var": "user.roles"}]},
+            {"all": [{"var": "orders"}, {">": [{"var": "total"}, 0]}]},
+            {"reduce": [
+                {"var": "orders"},
+                {"+": [{"var": "accumulator"}, {"var": "current.total"}]},
+                {"var": "base"}
+            ]},
+            {"if": [{"missing": ["email"]}, {"var": "phone"}, {"var": "email"}]},
+            {"var": ["nickname", "none"]},
+            {"substr": [{"var": "items.0.name"}, 0, 1]}
+        ]}))
+        .unwrap();
+        assert_eq!(
+            rule.data_schema(),
+            json!({
+                "$schema": DIALECT,
+                "type": "object",
+                "properties": {
+                    "base": {},
+                    "email": {},
+                    "items": {},
+                    "nickname": {},
+                    "orders": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {"total": {"type": "number"}},
+                            "required": ["total"]
+                        }
+                    },
+                    "phone": {},
+                    "user": {
+                        "type": "object",
+                        "properties": {
+                            "age": {"type": "number"},
+                            "roles": {"type": ["array", "string"]}
+                        },
+                        "required": ["age"]
+                    }
+                },
+                "required": ["user"]
+            })
+        );
+    }
+
+    #[test]
+    fn test_data_schema_outer_variables() {
+        let rule = Rule::from_value(&json!({"map": [
+            {"var": "teams"},
+            {"filter": [
+                {"var": "members"},
+                {"and": [
+                    {">=": [{"var": "age"}, {"var": "../../min_age"}]},
+                    {"!=": [{"var": "../name"}, {"var": "#index"}]}
+                ]}
+            ]}
+        ]}))
+        .unwrap();
+        assert_eq!(
+            rule.data_schema(),
+            json!({
+                "$schema": DIALECT,
+                "type": "object",
+                "properties": {
+                    "min_age": {},
+                    "teams": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "members": {
+                                    "type": "array",
+                                    "items": {
+                                        "type": "object",
+                                        "properties": {"age": {}},
+                                        "required": ["age"]
+                                    }
+                                },
+                                "name": {}
+                            },
+                            "required": ["members"]
+                        }
+                    }
+                },
+                "required": ["min_age", "teams"]
+            })
+        );
+    }
+
+    #[test]
+    fn test_data_schema_root() {
+        let rule =
+            Rule::from_value(&json!({"map": [{"var": ""}, {"*": [{"var": ""}, 2]}]}))
+                .unwrap();
+        assert_eq!(
+            rule.data_schema(),
+            json!({
+                "$schema": DIALECT,
+                "type": "array",
+                "items": {"type": "number"}
+            })
+        );
+        assert_eq!(
+            Rule::from_value(&json!(true)).unwrap().data_schema(),
+            json!({"$schema": DIALECT})
+        );
+    }
+}
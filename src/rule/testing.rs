@@ -0,0 +1,256 @@
+//! Rule Test Cases
+//!
+//! Test cases may be attached to a rule with `Rule::with_tests()`, or listed
+//! in a bundle's `$tests`, keyed by rule name:
+//!
+//! ```json
+//! {
+//!   "rules": {"adult": {">=": [{"var": "age"}, 18]}},
+//!   "$tests": {
+//!     "adult": [
+//!       {"name": "of age", "data": {"age": 18}, "expect": true},
+//!       {"data": {"age": "x"}, "error": "invalid_argument"}
+//!     ]
+//!   }
+//! }
+//! ```
+//!
+//! Each case gives the `data` to evaluate the rule against, which defaults to
+//! `null`, and either the result to `expect` or the code of the `error` the
+//! evaluation should fail with. `Rule::run_tests()` and
+//! `Bundle::run_tests()` evaluate the cases and report whether each passed.
+
+use core::fmt;
+
+use serde_json::Value;
+
+use crate::error::Error;
+use crate::prelude::*;
+use crate::rule::Rule;
+
+/// The key of a bundle's test cases
+pub(crate) const TESTS: &str = "$tests";
+
+/// A test case for a rule
+#[derive(Clone, Debug, PartialEq)]
+pub struct TestCase {
+    /// A name describing the case
+    pub name: Option<String>,
+    /// The data to evaluate the rule against
+    pub data: Value,
+    /// The expected outcome
+    pub expect: Expectation,
+}
+impl TestCase {
+    /// Parse a test case from a JSON object.
+    pub fn from_value(value: &Value) -> Result<Self, Error> {
+        let invalid = |reason: &str| Error::InvalidData {
+            value: value.clone(),
+            reason: reason.into(),
+        };
+        let obj = value
+            .as_object()
+            .ok_or_else(|| invalid("Expected a test case object"))?;
+        if let Some(key) = obj
+            .keys()
+            .find(|key| !matches!(key.as_str(), "name" | "data" | "expect" | "error"))
+        {
+            return Err(invalid(&format!("Unknown test case key '{}'", key)));
+        }
+        let name = match obj.get("name") {
+            Some(Value::String(name)) => Some(name.clone()),
+            Some(_) => return Err(invalid("Expected a string at 'name'")),
+            None => None,
+        };
+        let expect = match (obj.get("expect"), obj.get("error")) {
+            (Some(expected), None) => Expectation::Value(expected.clone()),
+            (None, Some(Value::String(code))) => Expectation::Error(code.clone()),
+            (None, Some(_)) => {
+                return Err(invalid("Expected an error code at 'error'"))
+            }
+            _ => return Err(invalid("Expected exactly one of 'expect' or 'error'")),
+        };
+        Ok(Self {
+            name,
+            data: obj.get("data").cloned().unwrap_or(Value::Null),
+            expect,
+        })
+    }
+
+    /// Parse an array of test cases.
+    pub fn from_values(value: &Value) -> Result<Vec<Self>, Error> {
+        value
+            .as_array()
+            .ok_or_else(|| Error::InvalidData {
+                value: value.clone(),
+                reason: "Expected an array of test cases".into(),
+            })?
+            .iter()
+            .map(Self::from_value)
+            .collect()
+    }
+}
+
+/// The expected outcome of a test case
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expectation {
+    /// The rule evaluates to the value
+    Value(Value),
+    /// The rule fails with an error with the code, e.g. `no_match`
+    Error(String),
+}
+impl Expectation {
+    fn is_met(&self, actual: &Result<Value, Error>) -> bool {
+        match (self, actual) {
+            (Self::Value(expected), Ok(actual)) => expected == actual,
+            (Self::Error(code), Err(err)) => err.code() == code,
+            _ => false,
+        }
+    }
+}
+impl fmt::Display for Expectation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Value(value) => write!(f, "{}", value),
+            Self::Error(code) => write!(f, "error '{}'", code),
+        }
+    }
+}
+
+/// The outcome of a test case
+#[derive(Debug)]
+pub struct TestResult {
+    /// The id of the rule tested, if it has one
+    pub rule: Option<String>,
+    /// The position of the case among the rule's tests
+    pub index: usize,
+    /// The case
+    pub case: TestCase,
+    /// The result of evaluating the rule
+    pub actual: Result<Value, Error>,
+    /// Whether the result was as expected
+    pub passed: bool,
+}
+impl fmt::Display for TestResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} test {}",
+            if self.passed { "passed" } else { "FAILED" },
+            self.index
+        )?;
+        if let Some(name) = &self.case.name {
+            write!(f, " ('{}')", name)?;
+        }
+        if let Some(rule) = &self.rule {
+            write!(f, " of '{}'", rule)?;
+        }
+        if self.passed {
+            return Ok(());
+        }
+        write!(f, ": expected {}, ", self.case.expect)?;
+        match &self.actual {
+            Ok(actual) => write!(f, "got {}", actual),
+            Err(e) => write!(f, "failed with error: {}", e),
+        }
+    }
+}
+
+impl Rule {
+    /// Attach test cases to the rule, replacing any it had.
+    pub fn with_tests(mut self, tests: Vec<TestCase>) -> Self {
+        self.tests = tests.into();
+        self
+    }
+
+    /// The test cases attached to the rule
+    pub fn tests(&self) -> &[TestCase] {
+        &self.tests
+    }
+
+    /// Evaluate the rule against each of its test cases, in order.
+    pub fn run_tests(&self) -> Vec<TestResult> {
+        self.tests
+            .iter()
+            .enumerate()
+            .map(|(index, case)| {
+                let actual = self.apply(&case.data);
+                TestResult {
+                    rule: self.id().map(String::from),
+                    index,
+                    case: case.clone(),
+                    passed: case.expect.is_met(&actual),
+                    actual,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test_testing {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_run_tests() {
+        let tests = TestCase::from_values(&json!([
+            {"name": "of age", "data": {"age": 18}, "expect": true},
+            {"data": {"age": 17}, "expect": true},
+            {"data": {"age": 17}, "error": "no_match"},
+            {"expect": false}
+        ]))
+        .unwrap();
+        let rule = Rule::from_value(&json!({">=": [{"var": "age"}, 18]}))
+            .unwrap()
+            .with_id("adult")
+            .with_tests(tests);
+        assert_eq!(rule.tests().len(), 4);
+        let results = rule.run_tests();
+        assert_eq!(
+            results
+                .iter()
+                .map(|result| result.passed)
+                .collect::<Vec<_>>(),
+            vec![true, false, false, true]
+        );
+        assert_eq!(
+            results[0].to_string(),
+            "passed test 0 ('of age') of 'adult'"
+        );
+        assert_eq!(
+            results[1].to_string(),
+            "FAILED test 1 of 'adult': expected true, got false"
+        );
+        assert_eq!(
+            results[2].to_string(),
+            "FAILED test 2 of 'adult': expected error 'no_match', got false"
+        );
+
+        let rule = Rule::from_value(&json!({"match": [{"var": ""}, 1, "one"]}))
+            .unwrap()
+            .with_tests(
+                TestCase::from_values(&json!([{"data": 2, "error": "no_match"}]))
+                    .unwrap(),
+            );
+        assert!(rule.run_tests()[0].passed);
+        assert!(Rule::from_value(&json!(1)).unwrap().run_tests().is_empty());
+    }
+
+    #[test]
+    fn test_invalid_cases() {
+        let error =
+            |value: Value| TestCase::from_value(&value).unwrap_err().to_string();
+        assert!(error(json!([])).contains("Expected a test case object"));
+        assert!(
+            error(json!({"data": 1})).contains("exactly one of 'expect' or 'error'")
+        );
+        assert!(error(json!({"expect": 1, "error": "x"})).contains("exactly one"));
+        assert!(error(json!({"expect": 1, "error": 1})).contains("exactly one"));
+        assert!(error(json!({"error": 1})).contains("error code"));
+        assert!(error(json!({"expect": 1, "nmae": "x"}))
+            .contains("Unknown test case key 'nmae'"));
+        assert!(error(json!({"expect": 1, "name": 1})).contains("string at 'name'"));
+        assert!(TestCase::from_values(&json!({})).is_err());
+    }
+}
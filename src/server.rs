@@ -0,0 +1,307 @@
+//! HTTP Service
+//!
+//! With the `server` feature, `router()` provides an HTTP service that
+//! evaluates rules, so that services written in other languages can use the
+//! engine with exactly the same semantics. Run it with `serve()`, or nest
+//! the router within an `axum` application of your own.
+//!
+//!   - `POST /evaluate`, with `{"rule": ..., "data": ...}`, responds with
+//!     `{"result": ...}`
+//!   - `POST /validate`, with `{"rule": ...}`, responds with
+//!     `{"valid": true}`, or `{"valid": false, "error": ...}`
+//!
+//! Failures respond with `{"error": ...}`, holding the error as serialized by
+//! `Error::to_json()`: a 400 status if the request or rule is invalid, a 422
+//! status if evaluation fails, and a 503 status if evaluation takes longer
+//! than the service's timeout. Rules are parsed within the service's
+//! namespace, so they may call its functions.
+//!
+//! Rules are evaluated on the runtime's blocking thread pool, so that slow
+//! evaluations don't hold up other requests. An evaluation that runs past
+//! the timeout is cancelled, and fails with a `Cancelled` error.
+//!
+//! Parsed rules are cached by a SHA-256 hash of their JSON, so that clients
+//! sending the same rules repeatedly only pay to parse them once.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::context::Context;
+use crate::error::Error;
+use crate::namespace::Namespace;
+use crate::registry::Registry;
+use crate::rule::Rule;
+
+/// The number of parsed rules the service caches by default
+pub const DEFAULT_CACHE_SIZE: usize = 1024;
+
+/// How long the service lets an evaluation run by default
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The HTTP service's routes, parsing rules in an empty namespace
+pub fn router() -> Router {
+    router_with(Namespace::new(), DEFAULT_CACHE_SIZE, DEFAULT_TIMEOUT)
+}
+
+/// The HTTP service's routes, parsing rules in the given namespace, caching
+/// up to `cache_size` of them, and cancelling evaluations that run for longer
+/// than `timeout`.
+pub fn router_with(
+    namespace: Namespace,
+    cache_size: usize,
+    timeout: Duration,
+) -> Router {
+    let service = Arc::new(Service {
+        namespace,
+        timeout,
+        cache: Mutex::new(RuleCache {
+            capacity: cache_size,
+            ..RuleCache::default()
+        }),
+    });
+    Router::new()
+        .route("/evaluate", post(evaluate))
+        .route("/validate", post(validate))
+        .with_state(service)
+}
+
+/// Serve the HTTP service at the given address, parsing rules in the given
+/// namespace, until the process exits.
+pub async fn serve(addr: SocketAddr, namespace: Namespace) -> std::io::Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    let router = router_with(namespace, DEFAULT_CACHE_SIZE, DEFAULT_TIMEOUT);
+    axum::serve(listener, router).await
+}
+
+#[derive(Debug)]
+struct Service {
+    namespace: Namespace,
+    timeout: Duration,
+    cache: Mutex<RuleCache>,
+}
+impl Service {
+    /// Parse the rule in a request, or retrieve it from the cache.
+    fn rule(&self, request: &Value) -> Result<Arc<Rule>, Error> {
+        let value = request.get("rule").ok_or_else(|| Error::InvalidData {
+            value: request.clone(),
+            reason: "Expected a 'rule' in the request".into(),
+        })?;
+        let key: String = Sha256::digest(value.to_string())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        if let Some(rule) = self.lock().get(&key) {
+            return Ok(rule);
+        }
+        let rule = Arc::new(self.namespace.parse(value)?);
+        self.lock().insert(key, rule.clone());
+        Ok(rule)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, RuleCache> {
+        self.cache.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Parsed rules, keyed by the hash of their JSON, evicted oldest first
+#[derive(Debug, Default)]
+struct RuleCache {
+    capacity: usize,
+    rules: HashMap<String, Arc<Rule>>,
+    order: VecDeque<String>,
+}
+impl RuleCache {
+    fn get(&self, key: &str) -> Option<Arc<Rule>> {
+        self.rules.get(key).cloned()
+    }
+
+    fn insert(&mut self, key: String, rule: Arc<Rule>) {
+        if self.capacity == 0 || self.rules.contains_key(&key) {
+            return;
+        }
+        while self.rules.len() >= self.capacity {
+            match self.order.pop_front() {
+                Some(oldest) => self.rules.remove(&oldest),
+                None => break,
+            };
+        }
+        self.order.push_back(key.clone());
+        self.rules.insert(key, rule);
+    }
+}
+
+/// An error response, with the error serialized by `Error::to_json()`
+fn error_response(status: StatusCode, error: &Error) -> Response {
+    (status, Json(json!({ "error": error.to_json() }))).into_response()
+}
+
+async fn evaluate(
+    State(service): State<Arc<Service>>,
+    Json(request): Json<Value>,
+) -> Response {
+    let rule = match service.rule(&request) {
+        Ok(rule) => rule,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, &e),
+    };
+    let data = request.get("data").cloned().unwrap_or(Value::Null);
+    // Each evaluation is recorded in a registry of its own, so that it may be
+    // cancelled if it runs past the timeout
+    let registry = Arc::new(Registry::new());
+    let ctx = Context {
+        registry: Some(registry.clone()),
+        ..Context::default()
+    };
+    let mut task = tokio::task::spawn_blocking(move || rule.apply_with(&data, &ctx));
+    let result = match tokio::time::timeout(service.timeout, &mut task).await {
+        Ok(result) => result,
+        Err(_) => {
+            for evaluation in registry.active() {
+                registry.cancel(evaluation.id);
+            }
+            task.await
+        }
+    };
+    match result {
+        Ok(Ok(result)) => Json(json!({ "result": result })).into_response(),
+        Ok(Err(e @ Error::Cancelled(_))) => {
+            error_response(StatusCode::SERVICE_UNAVAILABLE, &e)
+        }
+        Ok(Err(e)) => error_response(StatusCode::UNPROCESSABLE_ENTITY, &e),
+        Err(e) => error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            &Error::UnexpectedError(e.to_string()),
+        ),
+    }
+}
+
+async fn validate(
+    State(service): State<Arc<Service>>,
+    Json(request): Json<Value>,
+) -> Response {
+    if request.get("rule").is_none() {
+        let e = Error::InvalidData {
+            value: request,
+            reason: "Expected a 'rule' in the request".into(),
+        };
+        return error_response(StatusCode::BAD_REQUEST, &e);
+    }
+    match service.rule(&request) {
+        Ok(_) => Json(json!({ "valid": true })),
+        Err(e) => Json(json!({ "valid": false, "error": e.to_json() })),
+    }
+    .into_response()
+}
+
+#[cfg(test)]
+mod test_server {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    fn request(router: &Router, path: &str, body: Value) -> (StatusCode, Value) {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let request = Request::post(path)
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            let status = response.status();
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, serde_json::from_slice(&body).unwrap())
+        })
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let mut namespace = Namespace::new();
+        namespace
+            .define(&json!({"defn": ["double", ["x"], {"*": [{"param": "x"}, 2]}]}))
+            .unwrap();
+        let router = router_with(namespace, 1, DEFAULT_TIMEOUT);
+        let body = json!({"rule": {"double": [{"var": "x"}]}, "data": {"x": 2}});
+        assert_eq!(
+            request(&router, "/evaluate", body.clone()),
+            (StatusCode::OK, json!({"result": 4}))
+        );
+        // Cached rules give the same results
+        assert_eq!(
+            request(&router, "/evaluate", body),
+            (StatusCode::OK, json!({"result": 4}))
+        );
+        assert_eq!(
+            request(&router, "/evaluate", json!({"rule": {"var": "x"}})),
+            (StatusCode::OK, json!({"result": null}))
+        );
+
+        let (status, body) =
+            request(&router, "/evaluate", json!({"rule": {"nope": []}}));
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"]["code"], json!("unknown_operator"));
+        let (status, body) = request(&router, "/evaluate", json!({"data": {}}));
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"]["code"], json!("invalid_data"));
+        let (status, body) = request(
+            &router,
+            "/evaluate",
+            json!({"rule": {"/": [1, "x"]}, "data": {}}),
+        );
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(body["error"]["code"].is_string());
+    }
+
+    #[test]
+    fn test_timeout() {
+        let router = router_with(Namespace::new(), 1, Duration::from_millis(1));
+        let rule = json!({"reduce": [
+            {"range": [500000]},
+            {"+": [{"var": "accumulator"}, {"var": "current"}]},
+            0
+        ]});
+        let (status, body) = request(&router, "/evaluate", json!({ "rule": rule }));
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["error"]["code"], json!("cancelled"));
+    }
+
+    #[test]
+    fn test_validate() {
+        let router = router();
+        assert_eq!(
+            request(&router, "/validate", json!({"rule": {"==": [1, 1]}})),
+            (StatusCode::OK, json!({"valid": true}))
+        );
+        let (status, body) =
+            request(&router, "/validate", json!({"rule": {"nope": []}}));
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["valid"], json!(false));
+        assert_eq!(body["error"]["code"], json!("unknown_operator"));
+        let (status, _) = request(&router, "/validate", json!({}));
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_rule_cache() {
+        let rule = Arc::new(Rule::from_value(&json!(1)).unwrap());
+        let mut cache = RuleCache {
+            capacity: 2,
+            ..RuleCache::default()
+        };
+        for key in ["a", "b", "a", "c"] {
+            cache.insert(key.into(), rule.clone());
+        }
+        assert!(cache.get("a").is_none());
+        assert!(cache.get("b").is_some());
+        assert!(cache.get("c").is_some());
+    }
+}
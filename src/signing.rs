@@ -0,0 +1,167 @@
+//! Signed Rules
+//!
+//! With the `signing` feature, rule documents may be signed with a shared
+//! key before they're published, e.g. to a message bus, so that consumers
+//! can reject tampered or unauthorized rules before parsing them. A signed
+//! rule is an envelope:
+//!
+//! ```json
+//! {"alg": "HMAC-SHA256", "rule": {"==": [1, 1]}, "signature": "9a1f..."}
+//! ```
+//!
+//! The signature is the hex-encoded HMAC-SHA256 of the canonical JSON of the
+//! envelope's `alg` and `rule`: no whitespace, with object keys sorted. The
+//! signature is unaffected by how the envelope is formatted in transit, but
+//! any change to the rule invalidates it.
+
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+
+use crate::error::Error;
+use crate::prelude::*;
+
+/// The signing algorithm recorded in envelopes
+const ALGORITHM: &str = "HMAC-SHA256";
+
+const ALG: &str = "alg";
+const RULE: &str = "rule";
+const SIGNATURE: &str = "signature";
+
+/// Write a value as canonical JSON, with no whitespace and sorted keys.
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Array(items) => {
+            out.push('[');
+            for (idx, item) in items.iter().enumerate() {
+                if idx > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        Value::Object(obj) => {
+            let mut keys: Vec<&String> = obj.keys().collect();
+            keys.sort();
+            out.push('{');
+            for (idx, key) in keys.into_iter().enumerate() {
+                if idx > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::from(key.as_str()).to_string());
+                out.push(':');
+                write_canonical(&obj[key], out);
+            }
+            out.push('}');
+        }
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
+/// The MAC of a rule, as signed in an envelope
+fn mac(rule: &Value, key: &[u8]) -> Hmac<Sha256> {
+    let mut signed = String::new();
+    write_canonical(&json!({ ALG: ALGORITHM, RULE: rule }), &mut signed);
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(signed.as_bytes());
+    mac
+}
+
+/// Sign a rule document with a key, returning the signed envelope.
+pub fn sign_rule(rule: &Value, key: &[u8]) -> Value {
+    let signature: String = mac(rule, key)
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    json!({ ALG: ALGORITHM, RULE: rule, SIGNATURE: signature })
+}
+
+/// Verify a signed envelope with a key, returning the rule document it
+/// holds.
+///
+/// Fails with `Error::InvalidSignature` if the envelope is malformed, uses
+/// an unknown algorithm, or its signature doesn't match.
+pub fn verify_rule(signed: &Value, key: &[u8]) -> Result<Value, Error> {
+    let invalid = |reason: &str| Error::InvalidSignature(reason.into());
+    let field = |name: &str| {
+        signed
+            .get(name)
+            .ok_or_else(|| invalid(&format!("the envelope has no '{}'", name)))
+    };
+    let rule = field(RULE)?;
+    if field(ALG)?.as_str() != Some(ALGORITHM) {
+        return Err(invalid(&format!("the algorithm is not '{}'", ALGORITHM)));
+    }
+    let signature = field(SIGNATURE)?
+        .as_str()
+        .and_then(decode_hex)
+        .ok_or_else(|| invalid("the signature is not a hex string"))?;
+    mac(rule, key)
+        .verify_slice(&signature)
+        .map_err(|_| invalid("the signature does not match the rule"))?;
+    Ok(rule.clone())
+}
+
+/// Decode a hex string into bytes.
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|idx| {
+            hex.get(idx..idx + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test_signing {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let key = b"secret";
+        let rule = json!({"and": [{">=": [{"var": "age"}, 18]}, {"var": "verified"}]});
+        let signed = sign_rule(&rule, key);
+        assert_eq!(signed["alg"], json!("HMAC-SHA256"));
+        assert_eq!(verify_rule(&signed, key).unwrap(), rule);
+
+        // Formatting doesn't matter, but the rule and key do
+        let reparsed: Value =
+            serde_json::from_str(&serde_json::to_string_pretty(&signed).unwrap())
+                .unwrap();
+        assert_eq!(verify_rule(&reparsed, key).unwrap(), rule);
+        let error = |signed: &Value, key: &[u8]| {
+            let err = verify_rule(signed, key).unwrap_err();
+            assert_eq!(err.code(), "invalid_signature");
+            err.to_string()
+        };
+        assert!(error(&signed, b"other").contains("does not match"));
+        let mut tampered = signed.clone();
+        tampered["rule"]["and"][0][">="][1] = json!(16);
+        assert!(error(&tampered, key).contains("does not match"));
+
+        let mut malformed = signed.clone();
+        malformed["signature"] = json!("zz");
+        assert!(error(&malformed, key).contains("not a hex string"));
+        malformed["alg"] = json!("none");
+        assert!(error(&malformed, key).contains("algorithm"));
+        assert!(error(&rule, key).contains("no 'rule'"));
+    }
+
+    #[test]
+    fn test_canonical() {
+        let mut out = String::new();
+        write_canonical(
+            &json!({"b": [1, "x\"y", null], "a": {"d": true, "c": 1.5}}),
+            &mut out,
+        );
+        assert_eq!(out, r#"{"a":{"c":1.5,"d":true},"b":[1,"x\"y",null]}"#);
+    }
+}
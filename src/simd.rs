@@ -0,0 +1,133 @@
+//! simd-json Values
+//!
+//! With the `simd-json` feature, rules may be evaluated directly against
+//! data parsed with `simd-json`, via `Rule::apply_resolver()`. Only the
+//! parts of the data that the rule looks up are converted into `Value`s, so
+//! the cost of building a `Value` for each piece of data (e.g. each line of
+//! a large NDJSON stream) is avoided.
+
+use std::borrow::Cow;
+
+use serde_json::Number;
+use simd_json::prelude::{TypedScalarValue, ValueAsScalar, ValueObjectAccess};
+use simd_json::{BorrowedValue, OwnedValue};
+
+use crate::error::Error;
+use crate::resolver::{JsonLike, ValueKind};
+use crate::Rule;
+
+/// Implement `JsonLike` for one of simd-json's value types, which share the
+/// same variants.
+macro_rules! simd_value {
+    ($value:ident $(<$lt:lifetime>)?) => {
+        impl JsonLike for $value$(<$lt>)? {
+            fn kind(&self) -> ValueKind {
+                match self {
+                    $value::Static(node) if node.is_null() => ValueKind::Null,
+                    $value::Static(node) if node.is_bool() => ValueKind::Bool,
+                    $value::Static(_) => ValueKind::Number,
+                    $value::String(_) => ValueKind::String,
+                    $value::Array(_) => ValueKind::Array,
+                    $value::Object(_) => ValueKind::Object,
+                }
+            }
+
+            fn as_bool(&self) -> Option<bool> {
+                ValueAsScalar::as_bool(self)
+            }
+
+            fn as_number(&self) -> Option<Number> {
+                match self {
+                    $value::Static(node) if node.is_bool() => None,
+                    $value::Static(node) => node
+                        .as_i64()
+                        .map(Number::from)
+                        .or_else(|| node.as_u64().map(Number::from))
+                        .or_else(|| node.as_f64().and_then(Number::from_f64)),
+                    _ => None,
+                }
+            }
+
+            fn as_str(&self) -> Option<&str> {
+                ValueAsScalar::as_str(self)
+            }
+
+            fn as_array(&self) -> Option<&[Self]> {
+                match self {
+                    $value::Array(arr) => Some(arr.as_slice()),
+                    _ => None,
+                }
+            }
+
+            fn get(&self, key: &str) -> Option<&Self> {
+                ValueObjectAccess::get(self, key)
+            }
+
+            fn entries(&self) -> Box<dyn Iterator<Item = (Cow<'_, str>, &Self)> + '_> {
+                match self {
+                    $value::Object(obj) => {
+                        Box::new(obj.iter().map(|(key, value)| (Cow::Borrowed(&**key), value)))
+                    }
+                    _ => Box::new(std::iter::empty()),
+                }
+            }
+        }
+    };
+}
+
+simd_value!(BorrowedValue<'_>);
+simd_value!(OwnedValue);
+
+impl Rule {
+    /// Parse a rule from a simd-json value.
+    pub fn from_simd_value(value: &BorrowedValue) -> Result<Self, Error> {
+        Self::from_value(&value.to_json())
+    }
+}
+
+#[cfg(test)]
+mod test_simd {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_resolve_simd_values() {
+        let data = json!({
+            "a": [{"b": "xyz"}, 1, -2, 1.5, u64::MAX, true, null],
+            "é": "ü"
+        });
+        let mut bytes = data.to_string().into_bytes();
+        let borrowed = simd_json::to_borrowed_value(&mut bytes).unwrap();
+        let owned =
+            simd_json::to_owned_value(&mut data.to_string().into_bytes()).unwrap();
+
+        let rules = [
+            json!({"var": ""}),
+            json!({"var": "a.0.b"}),
+            json!({"var": "a.0.b.-1"}),
+            json!({"var": "a.0.b.1.0"}),
+            json!({"var": "é"}),
+            json!({"map": [{"var": "a"}, {"var": ""}]}),
+            json!({"+": [{"var": "a.1"}, {"var": "a.2"}, {"var": "a.3"}]}),
+            json!({"var": "a.4"}),
+            json!({"var": ["a.9", "default"]}),
+            json!({"missing": ["a.5", "a.6", "a.7", "a.0.c", "é"]}),
+        ];
+        for rule in rules.iter() {
+            let rule = Rule::from_value(rule).unwrap();
+            let expected = rule.apply(&data).unwrap();
+            assert_eq!(rule.apply_resolver(&borrowed).unwrap(), expected);
+            assert_eq!(rule.apply_resolver(&owned).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_rule_from_simd_value() {
+        let mut bytes = br#"{"if": [{"var": "x"}, "yes", 2.5]}"#.to_vec();
+        let rule =
+            Rule::from_simd_value(&simd_json::to_borrowed_value(&mut bytes).unwrap())
+                .unwrap();
+        assert_eq!(rule.apply(&json!({"x": true})).unwrap(), json!("yes"));
+        assert_eq!(rule.apply(&json!({})).unwrap(), json!(2.5));
+    }
+}
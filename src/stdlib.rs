@@ -0,0 +1,70 @@
+//! Standard Library
+//!
+//! `stdlib()` is a namespace of commonly needed pure functions, defined in
+//! JsonLogic itself, so that they needn't be reimplemented for every
+//! deployment. It isn't available unless it's asked for: mount it within a
+//! namespace of your own to use its functions alongside yours, e.g.
+//!
+//! ```
+//! use jsonlogic_plus::{stdlib, Namespace};
+//! use serde_json::json;
+//!
+//! let mut namespace = Namespace::new();
+//! namespace.mount("std", stdlib()).unwrap();
+//! let rule = namespace.parse(&json!({"std.clamp": [{"var": "x"}, 0, 10]})).unwrap();
+//! assert_eq!(rule.apply(&json!({"x": 12})).unwrap(), json!(10));
+//! ```
+//!
+//! The functions are:
+//!
+//! - `abs(x)`, `sign(x)`
+//! - `between(x, lo, hi)`, whether `lo <= x <= hi`
+//! - `clamp(x, lo, hi)`, `x` limited to the range `lo..=hi`
+//! - `percent_change(from, to)`, or null if `from` is 0
+//! - `sum(xs)`, `count(xs)`, and `mean(xs)`, which is null for no values
+//! - `first(xs)` and `last(xs)`, which are null for no values
+//! - `contains(xs, x)`
+//! - `is_empty(x)`, whether `x` is null, an empty string, or an empty array
+//! - `xor(a, b)`, whether exactly one of `a` and `b` is truthy
+//!
+//! The library is versioned by `STDLIB_VERSION`. Functions may be added in
+//! minor versions, but are only removed or changed in major ones.
+
+use serde_json::Value;
+
+use crate::namespace::Namespace;
+
+/// The version of the standard library
+pub const STDLIB_VERSION: &str = "1.0.0";
+
+/// The standard library's module document
+const MODULE: &str = include_str!("stdlib.json");
+
+/// The standard library of functions.
+pub fn stdlib() -> Namespace {
+    let module: Value =
+        serde_json::from_str(MODULE).expect("The standard library is valid JSON");
+    Namespace::from_value(&module).expect("The standard library is a valid module")
+}
+
+#[cfg(test)]
+mod test_stdlib {
+    use super::*;
+
+    #[test]
+    fn test_stdlib() {
+        let namespace = stdlib();
+        namespace.check().unwrap();
+        let failures = namespace.run_examples().unwrap();
+        assert!(failures.is_empty(), "{:?}", failures);
+        assert!(namespace
+            .functions()
+            .all(|func| !func.examples().is_empty() && func.doc().is_some()));
+
+        // Functions resolve one another when mounted
+        let mut mounted = Namespace::new();
+        mounted.mount("std", namespace).unwrap();
+        mounted.check().unwrap();
+        assert!(mounted.run_examples().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,477 @@
+//! Rule Stores
+//!
+//! A `RuleStore` holds named, versioned rules, for services that load their
+//! rules at runtime rather than building them in. `FileStore` loads rules
+//! from a directory of JSON files:
+//!
+//!   - `<name>.json` or `<name>@<version>.json` holds a rule
+//!   - `<prefix>.module.json` holds a module document (see
+//!     `Namespace::from_value()`), whose functions are mounted under
+//!     `prefix`, so that rules may call e.g. `{"pricing.discount": [...]}`
+//!
+//! A rule is either versioned or not. Versions are ordered by their
+//! dot-separated components, numerically where both are numbers, so that
+//! `1.10` comes after `1.9`, and the latest version of a rule is the one
+//! returned when no version is asked for. Versions whose components are
+//! equal, like `1.09` and `1.9`, are the same version, which only one file
+//! may hold.
+//!
+//! Reloading a store re-reads the whole directory and then swaps the new
+//! rules in at once, so that readers never see a mix of old and new files.
+//! If any file fails to load, the store keeps its previous rules. With the
+//! `notify` feature, `FileStore::watch()` reloads the store whenever the
+//! directory changes, so that rules may be deployed by copying files into
+//! place.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "notify")]
+use std::sync::Mutex;
+use std::sync::{Arc, RwLock};
+
+use serde_json::Value;
+
+use crate::error::Error;
+use crate::namespace::Namespace;
+use crate::rule::Rule;
+
+/// A source of named, versioned rules
+pub trait RuleStore: Send + Sync {
+    /// The rule with the given name at the given version, or at its latest
+    /// version if `version` is None.
+    fn get(&self, name: &str, version: Option<&str>) -> Option<Arc<Rule>>;
+
+    /// The names of the rules in the store, in order
+    fn names(&self) -> Vec<String>;
+
+    /// The versions of a rule, oldest first. Unversioned rules have none.
+    fn versions(&self, name: &str) -> Vec<String>;
+}
+
+/// A version of a rule, ordered by its dot-separated components
+///
+/// Versions are equal when their components are, so that e.g. `1.01` is the
+/// same version as `1.1`.
+#[derive(Clone, Debug)]
+struct Version(String);
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl Eq for Version {}
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let mut ours = self.0.split('.');
+        let mut theirs = other.0.split('.');
+        loop {
+            let ordering = match (ours.next(), theirs.next()) {
+                (None, None) => return Ordering::Equal,
+                (None, Some(_)) => return Ordering::Less,
+                (Some(_), None) => return Ordering::Greater,
+                (Some(a), Some(b)) => match (a.parse::<u64>(), b.parse::<u64>()) {
+                    (Ok(a), Ok(b)) => a.cmp(&b),
+                    _ => a.cmp(b),
+                },
+            };
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+    }
+}
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// The rules loaded from a store's files at one time
+#[derive(Debug, Default)]
+struct Snapshot {
+    /// Each rule's versions, keyed by None for an unversioned rule
+    rules: BTreeMap<String, BTreeMap<Option<Version>, Arc<Rule>>>,
+}
+impl Snapshot {
+    fn get(&self, name: &str, version: Option<&str>) -> Option<Arc<Rule>> {
+        let versions = self.rules.get(name)?;
+        match version {
+            Some(version) => versions.get(&Some(Version(version.into()))),
+            None => versions.values().next_back(),
+        }
+        .cloned()
+    }
+}
+
+/// A rule store backed by a directory of rule and module files
+///
+/// See the [module documentation](index.html) for the layout of the
+/// directory.
+#[derive(Debug)]
+pub struct FileStore {
+    inner: Arc<Inner>,
+    #[cfg(feature = "notify")]
+    _watcher: Option<notify::RecommendedWatcher>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    dir: PathBuf,
+    base: Namespace,
+    snapshot: RwLock<Arc<Snapshot>>,
+    #[cfg(feature = "notify")]
+    error: Mutex<Option<Error>>,
+}
+
+impl FileStore {
+    /// Load the rules in a directory.
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self, Error> {
+        Self::open_with(dir, Namespace::new())
+    }
+
+    /// Load the rules in a directory, parsing them with a namespace into
+    /// which the directory's modules are mounted, e.g. one with operator
+    /// packs loaded.
+    pub fn open_with<P: AsRef<Path>>(dir: P, base: Namespace) -> Result<Self, Error> {
+        let dir = dir.as_ref().to_path_buf();
+        let snapshot = load(&dir, &base)?;
+        Ok(Self {
+            inner: Arc::new(Inner {
+                dir,
+                base,
+                snapshot: RwLock::new(Arc::new(snapshot)),
+                #[cfg(feature = "notify")]
+                error: Mutex::new(None),
+            }),
+            #[cfg(feature = "notify")]
+            _watcher: None,
+        })
+    }
+
+    /// Load the rules in a directory, and reload them whenever the
+    /// directory changes, for as long as the store exists.
+    ///
+    /// Reloads that fail leave the store's rules as they were; retrieve the
+    /// error with [`take_error`](#method.take_error).
+    #[cfg(feature = "notify")]
+    pub fn watch<P: AsRef<Path>>(dir: P, base: Namespace) -> Result<Self, Error> {
+        use notify::{RecursiveMode, Watcher};
+
+        let mut store = Self::open_with(dir, base)?;
+        let inner = Arc::downgrade(&store.inner);
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                if let (Ok(event), Some(inner)) = (event, inner.upgrade()) {
+                    if !event.kind.is_access() {
+                        inner.reload_changed();
+                    }
+                }
+            })
+            .map_err(|e| file_error(&store.inner.dir, e))?;
+        watcher
+            .watch(&store.inner.dir, RecursiveMode::NonRecursive)
+            .map_err(|e| file_error(&store.inner.dir, e))?;
+        store._watcher = Some(watcher);
+        Ok(store)
+    }
+
+    /// Re-read the directory, replacing the store's rules if every file
+    /// loads successfully.
+    pub fn reload(&self) -> Result<(), Error> {
+        self.inner.reload()
+    }
+
+    /// Take the error from the last reload made by watching the directory,
+    /// if it failed.
+    #[cfg(feature = "notify")]
+    pub fn take_error(&self) -> Option<Error> {
+        self.inner
+            .error
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+    }
+
+    /// The directory the store loads from
+    pub fn dir(&self) -> &Path {
+        &self.inner.dir
+    }
+
+    fn snapshot(&self) -> Arc<Snapshot> {
+        self.inner
+            .snapshot
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+impl RuleStore for FileStore {
+    fn get(&self, name: &str, version: Option<&str>) -> Option<Arc<Rule>> {
+        self.snapshot().get(name, version)
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.snapshot().rules.keys().cloned().collect()
+    }
+
+    fn versions(&self, name: &str) -> Vec<String> {
+        self.snapshot()
+            .rules
+            .get(name)
+            .map_or_else(Vec::new, |versions| {
+                versions
+                    .keys()
+                    .flatten()
+                    .map(|version| version.0.clone())
+                    .collect()
+            })
+    }
+}
+
+impl Inner {
+    fn reload(&self) -> Result<(), Error> {
+        let snapshot = load(&self.dir, &self.base)?;
+        *self.snapshot.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(snapshot);
+        Ok(())
+    }
+
+    /// Reload in response to a change in the directory, holding on to any
+    /// error for `FileStore::take_error()`.
+    #[cfg(feature = "notify")]
+    fn reload_changed(&self) {
+        let result = self.reload();
+        #[cfg(feature = "tracing")]
+        if let Err(e) = &result {
+            crate::trace::reload_failed(&self.dir.display().to_string(), e);
+        }
+        *self.error.lock().unwrap_or_else(|e| e.into_inner()) = result.err();
+    }
+}
+
+const EXTENSION: &str = ".json";
+const MODULE_EXTENSION: &str = ".module.json";
+const VERSION_SEPARATOR: char = '@';
+
+fn file_error<E: std::fmt::Display>(path: &Path, error: E) -> Error {
+    Error::InvalidFile {
+        path: path.display().to_string(),
+        reason: error.to_string(),
+    }
+}
+
+fn read_json(path: &Path) -> Result<Value, Error> {
+    let text = fs::read_to_string(path).map_err(|e| file_error(path, e))?;
+    serde_json::from_str(&text).map_err(|e| file_error(path, e))
+}
+
+/// Load every rule and module in a directory.
+fn load(dir: &Path, base: &Namespace) -> Result<Snapshot, Error> {
+    let mut modules = Vec::new();
+    let mut rules = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| file_error(dir, e))? {
+        let path = entry.map_err(|e| file_error(dir, e))?.path();
+        let file_name = match path.file_name().and_then(|name| name.to_str()) {
+            // Skip hidden files, e.g. those editors write while saving
+            Some(name) if !name.starts_with('.') && path.is_file() => name.to_owned(),
+            _ => continue,
+        };
+        if let Some(prefix) = file_name.strip_suffix(MODULE_EXTENSION) {
+            modules.push((prefix.to_owned(), path));
+        } else if let Some(stem) = file_name.strip_suffix(EXTENSION) {
+            rules.push((stem.to_owned(), path));
+        }
+    }
+    // Mount modules in a stable order, so that conflicts are reported
+    // consistently
+    modules.sort();
+
+    let mut namespace = base.clone();
+    for (prefix, path) in modules {
+        Namespace::from_value(&read_json(&path)?)
+            .and_then(|module| namespace.mount(&prefix, module))
+            .map_err(|e| file_error(&path, e))?;
+    }
+
+    let mut snapshot = Snapshot::default();
+    for (stem, path) in rules {
+        let (name, version) = match stem.split_once(VERSION_SEPARATOR) {
+            Some((name, version)) => (name, Some(Version(version.into()))),
+            None => (stem.as_str(), None),
+        };
+        if name.is_empty() || version.as_ref().is_some_and(|v| v.0.is_empty()) {
+            return Err(file_error(
+                &path,
+                "rule files are named <name>.json or <name>@<version>.json",
+            ));
+        }
+        let id = match &version {
+            Some(version) => format!("{}{}{}", name, VERSION_SEPARATOR, version.0),
+            None => name.to_owned(),
+        };
+        let rule = namespace
+            .parse(&read_json(&path)?)
+            .map_err(|e| file_error(&path, e))?
+            .with_id(id);
+        let versions = snapshot.rules.entry(name.to_owned()).or_default();
+        if let Some(existing) = versions.insert(version, Arc::new(rule)) {
+            return Err(file_error(
+                &path,
+                format!(
+                    "rule '{}' is already defined by '{}'",
+                    name,
+                    existing.id().unwrap_or(name)
+                ),
+            ));
+        }
+        if versions.len() > 1 && versions.contains_key(&None) {
+            return Err(file_error(
+                &path,
+                format!("rule '{}' is both versioned and unversioned", name),
+            ));
+        }
+    }
+    Ok(snapshot)
+}
+
+#[cfg(test)]
+mod test_store {
+    use super::*;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+
+    /// A fresh, empty directory for a test
+    fn temp_dir() -> PathBuf {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "jsonlogic-store-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, AtomicOrdering::Relaxed)
+        ));
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(dir: &Path, name: &str, value: &Value) {
+        fs::write(dir.join(name), value.to_string()).unwrap();
+    }
+
+    #[test]
+    fn test_versions() {
+        let mut versions: Vec<Version> = ["1.10", "1.9", "2", "1.9.1", "1.a"]
+            .iter()
+            .map(|v| Version((*v).into()))
+            .collect();
+        versions.sort();
+        let versions: Vec<&str> = versions.iter().map(|v| v.0.as_str()).collect();
+        assert_eq!(versions, vec!["1.9", "1.9.1", "1.10", "1.a", "2"]);
+
+        // Equality agrees with the order
+        assert_eq!(Version("1.0".into()), Version("1.00".into()));
+        assert_ne!(Version("1.0".into()), Version("1.0.0".into()));
+    }
+
+    #[test]
+    fn test_file_store() {
+        let dir = temp_dir();
+        write(
+            &dir,
+            "pricing.module.json",
+            &json!({"functions": [
+                {"defn": ["discount", ["x"], {"*": [{"param": "x"}, 0.5]}]}
+            ]}),
+        );
+        write(&dir, "eligible.json", &json!({">=": [{"var": "age"}, 18]}));
+        write(&dir, "price@1.9.json", &json!({"var": "price"}));
+        write(
+            &dir,
+            "price@1.10.json",
+            &json!({"pricing.discount": [{"var": "price"}]}),
+        );
+        write(&dir, "notes.txt", &json!("ignored"));
+
+        let store = FileStore::open(&dir).unwrap();
+        assert_eq!(store.names(), vec!["eligible", "price"]);
+        assert_eq!(store.versions("price"), vec!["1.9", "1.10"]);
+        assert!(store.versions("eligible").is_empty());
+
+        let data = json!({"age": 20, "price": 10});
+        let latest = store.get("price", None).unwrap();
+        assert_eq!(latest.id(), Some("price@1.10"));
+        assert_eq!(latest.apply(&data).unwrap(), json!(5));
+        let old = store.get("price", Some("1.9")).unwrap();
+        assert_eq!(old.apply(&data).unwrap(), json!(10));
+        assert!(store.get("price", Some("2")).is_none());
+        let eligible = store.get("eligible", None).unwrap();
+        assert_eq!(eligible.apply(&data).unwrap(), json!(true));
+
+        // Reloads swap in the new rules, while rules already retrieved are
+        // unaffected
+        write(&dir, "eligible.json", &json!({">=": [{"var": "age"}, 21]}));
+        store.reload().unwrap();
+        assert_eq!(
+            store.get("eligible", None).unwrap().apply(&data).unwrap(),
+            json!(false)
+        );
+        assert_eq!(eligible.apply(&data).unwrap(), json!(true));
+
+        // Failed reloads keep the previous rules
+        write(&dir, "broken.json", &json!({"pricing.nope": [1]}));
+        let err = store.reload().unwrap_err();
+        assert_eq!(err.code(), "invalid_file");
+        assert!(err.to_string().contains("broken.json"), "{}", err);
+        assert!(store.get("broken", None).is_none());
+        assert!(store.get("eligible", None).is_some());
+        fs::remove_file(dir.join("broken.json")).unwrap();
+
+        write(&dir, "eligible@2.json", &json!(true));
+        store.reload().unwrap_err();
+        fs::remove_file(dir.join("eligible@2.json")).unwrap();
+        store.reload().unwrap();
+
+        // Two files can't hold the same version
+        write(&dir, "price@1.09.json", &json!(1));
+        let err = store.reload().unwrap_err();
+        assert!(err.to_string().contains("already defined"), "{}", err);
+        assert_eq!(store.versions("price"), vec!["1.9", "1.10"]);
+        fs::remove_file(dir.join("price@1.09.json")).unwrap();
+        store.reload().unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+        FileStore::open(&dir).unwrap_err();
+    }
+
+    #[cfg(feature = "notify")]
+    #[test]
+    fn test_watch() {
+        use std::time::{Duration, Instant};
+
+        let dir = temp_dir();
+        write(&dir, "rule.json", &json!(1));
+        let store = FileStore::watch(&dir, Namespace::new()).unwrap();
+        let value = || store.get("rule", None).unwrap().apply(&json!({})).unwrap();
+        assert_eq!(value(), json!(1));
+
+        write(&dir, "rule.json", &json!(2));
+        let start = Instant::now();
+        while value() != json!(2) && start.elapsed() < Duration::from_secs(10) {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(value(), json!(2));
+
+        write(&dir, "rule.json", &json!({"nope": [1]}));
+        let start = Instant::now();
+        let mut error = None;
+        while error.is_none() && start.elapsed() < Duration::from_secs(10) {
+            std::thread::sleep(Duration::from_millis(20));
+            error = store.take_error();
+        }
+        assert_eq!(error.unwrap().code(), "invalid_file");
+        assert_eq!(value(), json!(2));
+
+        drop(store);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
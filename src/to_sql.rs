@@ -0,0 +1,347 @@
+//! SQL Translation
+//!
+//! Compile a rule into a parameterized SQL predicate, e.g. for use in a
+//! `WHERE` clause, so that filters authored as JsonLogic can be pushed down
+//! to the database:
+//!
+//! ```
+//! use jsonlogic_plus::{to_sql, Rule};
+//! use serde_json::json;
+//!
+//! let rule = Rule::from_value(&json!({"and": [
+//!     {">=": [{"var": "age"}, 18]},
+//!     {"in": [{"var": "country"}, ["US", "CA"]]}
+//! ]}))
+//! .unwrap();
+//! let sql = to_sql::compile(&rule).unwrap();
+//! assert_eq!(sql.predicate, r#"("age" >= ? AND "country" IN (?, ?))"#);
+//! assert_eq!(sql.params, vec![json!(18), json!("US"), json!("CA")]);
+//! ```
+//!
+//! Only a subset of rules can be translated: comparisons, boolean logic,
+//! `in` with a literal array, and arithmetic, over `var`s with literal paths
+//! and literal values. A dotted `var` path refers to a qualified column, e.g.
+//! `users.age` to `"users"."age"`, so paths with wildcards, slices, or
+//! negative indexes, which have no column to refer to, can't be translated. The translated predicate has SQL
+//! semantics, so e.g. comparisons between values of different types behave
+//! as they do in the database, rather than as in JsonLogic.
+
+use serde_json::Value;
+
+use crate::ast::Ast;
+use crate::error::Error;
+use crate::prelude::*;
+use crate::rule::Rule;
+
+/// How parameters are referenced in the predicate
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Placeholder {
+    /// `?`, as in SQLite and MySQL
+    #[default]
+    Question,
+    /// `$1`, `$2`, etc., as in PostgreSQL
+    Numbered,
+}
+
+/// A parameterized SQL predicate
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sql {
+    pub predicate: String,
+    /// The values of the predicate's parameters, in order
+    pub params: Vec<Value>,
+}
+
+/// Compile a rule into a predicate with `?` placeholders.
+pub fn compile(rule: &Rule) -> Result<Sql, Error> {
+    compile_with(rule, Placeholder::default())
+}
+
+/// Compile a rule into a predicate with the given placeholders.
+///
+/// If any part of the rule can't be translated, the error lists every
+/// unsupported operator in the rule.
+pub fn compile_with(rule: &Rule, placeholder: Placeholder) -> Result<Sql, Error> {
+    let mut compiler = Compiler {
+        placeholder,
+        params: Vec::new(),
+        unsupported: Vec::new(),
+    };
+    let predicate = compiler.expr(&rule.ast());
+    if compiler.unsupported.is_empty() {
+        Ok(Sql {
+            predicate,
+            params: compiler.params,
+        })
+    } else {
+        compiler.unsupported.sort();
+        compiler.unsupported.dedup();
+        Err(Error::Untranslatable {
+            target: "SQL",
+            unsupported: compiler.unsupported,
+        })
+    }
+}
+
+struct Compiler {
+    placeholder: Placeholder,
+    params: Vec<Value>,
+    unsupported: Vec<String>,
+}
+impl Compiler {
+    fn expr(&mut self, ast: &Ast) -> String {
+        match ast {
+            Ast::Literal(Value::Null) => "NULL".into(),
+            Ast::Literal(value @ Value::Bool(_))
+            | Ast::Literal(value @ Value::Number(_))
+            | Ast::Literal(value @ Value::String(_)) => self.param(value.clone()),
+            Ast::Literal(Value::Array(_)) => self.unsupported("array literal"),
+            Ast::Literal(Value::Object(_)) => self.unsupported("object literal"),
+            Ast::Var { path, default } => {
+                let column = match path.as_deref() {
+                    Some(Ast::Literal(Value::String(path)))
+                        if path.split('.').any(|key| key == "*" || key == "**") =>
+                    {
+                        return self.unsupported("wildcard path")
+                    }
+                    Some(Ast::Literal(Value::String(path)))
+                        if path.split('.').any(is_slice) =>
+                    {
+                        return self.unsupported("slice path")
+                    }
+                    Some(Ast::Literal(Value::String(path)))
+                        if path.split('.').any(is_negative_index) =>
+                    {
+                        return self.unsupported("negative index path")
+                    }
+                    Some(Ast::Literal(Value::String(path))) if !path.is_empty() => path
+                        .split('.')
+                        .map(quote_identifier)
+                        .collect::<Vec<String>>()
+                        .join("."),
+                    _ => return self.unsupported("var without a literal path"),
+                };
+                match default.as_deref() {
+                    Some(default) => {
+                        format!("COALESCE({}, {})", column, self.expr(default))
+                    }
+                    None => column,
+                }
+            }
+            Ast::Operator { name, args } => self.operator(name, args),
+            Ast::FunctionCall { name, .. } => self.unsupported(name),
+            Ast::FunctionDef { .. } => self.unsupported("defn"),
+            Ast::Param(_) => self.unsupported("param"),
+            Ast::Object(_) => self.unsupported("object literal"),
+        }
+    }
+
+    fn operator(&mut self, name: &str, args: &[Ast]) -> String {
+        match (name, args) {
+            ("==", [a, Ast::Literal(Value::Null)])
+            | ("===", [a, Ast::Literal(Value::Null)])
+            | ("==", [Ast::Literal(Value::Null), a])
+            | ("===", [Ast::Literal(Value::Null), a]) => {
+                format!("{} IS NULL", self.expr(a))
+            }
+            ("!=", [a, Ast::Literal(Value::Null)])
+            | ("!==", [a, Ast::Literal(Value::Null)])
+            | ("!=", [Ast::Literal(Value::Null), a])
+            | ("!==", [Ast::Literal(Value::Null), a]) => {
+                format!("{} IS NOT NULL", self.expr(a))
+            }
+            ("==", [a, b]) | ("===", [a, b]) => self.binary("=", a, b),
+            ("!=", [a, b]) | ("!==", [a, b]) => self.binary("<>", a, b),
+            ("<", [a, b]) | ("<=", [a, b]) | (">", [a, b]) | (">=", [a, b]) => {
+                self.binary(name, a, b)
+            }
+            // Between, exclusive or inclusive
+            ("<", [a, b, c]) | ("<=", [a, b, c]) => {
+                // The middle operand is compiled twice, so that its
+                // parameters appear in the order they're referenced
+                let (a, b1, b2, c) = (
+                    self.operand(a),
+                    self.operand(b),
+                    self.operand(b),
+                    self.operand(c),
+                );
+                format!("({} {op} {} AND {} {op} {})", a, b1, b2, c, op = name)
+            }
+            ("and", [_, ..]) => self.join(" AND ", args),
+            ("or", [_, ..]) => self.join(" OR ", args),
+            ("!", [a]) => format!("NOT ({})", self.expr(a)),
+            ("in", [_, Ast::Literal(Value::Array(items))]) if items.is_empty() => {
+                "FALSE".into()
+            }
+            ("in", [a, Ast::Literal(Value::Array(items))]) => {
+                let a = self.operand(a);
+                let items: Vec<String> = items
+                    .iter()
+                    .map(|item| self.expr(&Ast::Literal(item.clone())))
+                    .collect();
+                format!("{} IN ({})", a, items.join(", "))
+            }
+            ("-", [a]) => format!("(-{})", self.expr(a)),
+            ("+", [_, _, ..]) => self.join(" + ", args),
+            ("*", [_, _, ..]) => self.join(" * ", args),
+            ("-", [a, b]) | ("/", [a, b]) | ("%", [a, b]) => self.binary(name, a, b),
+            _ => {
+                // Report anything unsupported within the arguments, too
+                args.iter().for_each(|arg| {
+                    self.expr(arg);
+                });
+                self.unsupported(name)
+            }
+        }
+    }
+
+    fn binary(&mut self, op: &str, a: &Ast, b: &Ast) -> String {
+        let (a, b) = (self.operand(a), self.operand(b));
+        match op {
+            "-" | "/" | "%" => format!("({} {} {})", a, op, b),
+            _ => format!("{} {} {}", a, op, b),
+        }
+    }
+
+    /// Compile an operand of a comparison or arithmetic operator,
+    /// parenthesizing it if it's a comparison itself. Betweens are already
+    /// parenthesized.
+    fn operand(&mut self, ast: &Ast) -> String {
+        let sql = self.expr(ast);
+        match ast {
+            Ast::Operator { name, args } if is_comparison(name) && args.len() == 2 => {
+                format!("({})", sql)
+            }
+            _ => sql,
+        }
+    }
+
+    fn join(&mut self, separator: &str, args: &[Ast]) -> String {
+        let args: Vec<String> = args.iter().map(|arg| self.expr(arg)).collect();
+        format!("({})", args.join(separator))
+    }
+
+    fn param(&mut self, value: Value) -> String {
+        self.params.push(value);
+        match self.placeholder {
+            Placeholder::Question => "?".into(),
+            Placeholder::Numbered => format!("${}", self.params.len()),
+        }
+    }
+
+    fn unsupported(&mut self, what: &str) -> String {
+        self.unsupported.push(what.into());
+        String::new()
+    }
+}
+
+fn is_comparison(operator: &str) -> bool {
+    matches!(
+        operator,
+        "==" | "===" | "!=" | "!==" | "<" | "<=" | ">" | ">=" | "in"
+    )
+}
+
+/// Quote an identifier, escaping any quotes within it.
+fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+/// Whether a key of a dotted path is a slice, e.g. `1:3`
+fn is_slice(key: &str) -> bool {
+    let bound = |bound: &str| bound.is_empty() || bound.parse::<i64>().is_ok();
+    key.split_once(':')
+        .is_some_and(|(start, end)| bound(start) && bound(end))
+}
+
+/// Whether a key of a dotted path is a negative index, e.g. `-1`, which
+/// counts from the end of an array
+fn is_negative_index(key: &str) -> bool {
+    key.parse::<i64>().is_ok_and(|index| index < 0)
+}
+
+#[cfg(test)]
+mod test_to_sql {
+    use super::*;
+    use serde_json::json;
+
+    fn sql(value: Value, placeholder: Placeholder) -> Result<Sql, Error> {
+        compile_with(&Rule::from_value(&value).unwrap(), placeholder)
+    }
+
+    #[test]
+    fn test_compile() {
+        let compiled = sql(
+            json!({"or": [
+                {"<=": [18, {"var": "users.age"}, 65]},
+                {"!": {"==": [{"var": "deleted_at"}, null]}},
+                {">": [{"*": [{"var": "price"}, {"-": [1, {"var": ["discount", 0]}]}]}, 100]},
+                {"in": [{"var": "tier"}, []]},
+                {"!=": [{"var": "a\"b"}, "x"]},
+                {"==": [{"<": [1, 2, {"var": "n"}]}, false]}
+            ]}),
+            Placeholder::Numbered,
+        )
+        .unwrap();
+        assert_eq!(
+            compiled.predicate,
+            concat!(
+                r#"(($1 <= "users"."age" AND "users"."age" <= $2)"#,
+                r#" OR NOT ("deleted_at" IS NULL)"#,
+                r#" OR ("price" * ($3 - COALESCE("discount", $4))) > $5"#,
+                r#" OR FALSE"#,
+                r#" OR "a""b" <> $6"#,
+                r#" OR ($7 < $8 AND $9 < "n") = $10)"#,
+            )
+        );
+        assert_eq!(
+            compiled.params,
+            vec![
+                json!(18),
+                json!(65),
+                json!(1),
+                json!(0),
+                json!(100),
+                json!("x"),
+                json!(1),
+                json!(2),
+                json!(2),
+                json!(false)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let err = sql(
+            json!({"and": [
+                {"map": [{"var": "xs"}, {"var": ""}]},
+                {"==": [{"cat": ["a", {"var": "b"}]}, {"var": {"var": "c"}}]},
+                {"==": [{"var": "xs.*.id"}, 1]},
+                {"==": [{"var": "xs.1:"}, 1]},
+                {"==": [{"var": "xs.-1"}, 1]}
+            ]}),
+            Placeholder::Question,
+        )
+        .unwrap_err();
+        match err {
+            Error::Untranslatable {
+                target,
+                unsupported,
+            } => {
+                assert_eq!(target, "SQL");
+                assert_eq!(
+                    unsupported,
+                    vec![
+                        "cat",
+                        "map",
+                        "negative index path",
+                        "slice path",
+                        "var without a literal path",
+                        "wildcard path"
+                    ]
+                );
+            }
+            err => panic!("unexpected error: {}", err),
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! Tracing
+//!
+//! With the `tracing` feature, parsing and evaluating rules emit `tracing`
+//! spans:
+//!
+//!   - `jsonlogic.parse` (debug) for each parsed rule
+//!   - `jsonlogic.apply` (debug) for each evaluation, with the rule's id
+//!   - `jsonlogic.operation` (trace) for each executed operation, with its
+//!     operator, its number of arguments, and its path: the operators
+//!     enclosing it in the rule, e.g. `and/if/==`
+//!
+//! Parsing a rule that uses a name a namespace has deprecated also emits a
+//! `jsonlogic.deprecated` warning event, with the name and its replacement.
+//!
+//! A `FileStore` watching its directory emits a `jsonlogic.reload_failed`
+//! warning event, with the directory and the error, when a reload fails.
+
+use std::cell::RefCell;
+
+use tracing::span::EnteredSpan;
+use tracing::{debug_span, field, trace_span};
+
+/// Enter the span for parsing a rule.
+pub(crate) fn parse() -> EnteredSpan {
+    debug_span!("jsonlogic.parse").entered()
+}
+
+/// Enter the span for an evaluation of the given rule.
+pub(crate) fn apply(rule_id: Option<&str>) -> EnteredSpan {
+    let span = debug_span!("jsonlogic.apply", rule = field::Empty);
+    if let Some(rule_id) = rule_id {
+        span.record("rule", rule_id);
+    }
+    span.entered()
+}
+
+/// Emit the event for the use of a deprecated name.
+pub(crate) fn deprecated(name: &str, replacement: &str) {
+    tracing::warn!(name, replacement, "jsonlogic.deprecated");
+}
+
+/// Emit the event for a failure to reload a rule store.
+#[cfg(feature = "notify")]
+pub(crate) fn reload_failed(dir: &str, error: &crate::Error) {
+    tracing::warn!(dir, error = %error, "jsonlogic.reload_failed");
+}
+
+/// The span of an operation, entered for as long as the guard is held
+pub(crate) struct Operation<'a> {
+    path: &'a RefCell<String>,
+    parent_len: usize,
+    _span: EnteredSpan,
+}
+impl<'a> Operation<'a> {
+    /// Enter the span for an operation, extending the path of operators
+    /// enclosing it.
+    pub(crate) fn enter(
+        path: &'a RefCell<String>,
+        operator: &str,
+        arguments: usize,
+    ) -> Self {
+        let parent_len = {
+            let mut path = path.borrow_mut();
+            let parent_len = path.len();
+            if parent_len > 0 {
+                path.push('/');
+            }
+            path.push_str(operator);
+            parent_len
+        };
+        let span = trace_span!(
+            "jsonlogic.operation",
+            operator,
+            arguments,
+            path = path.borrow().as_str()
+        );
+        Self {
+            path,
+            parent_len,
+            _span: span.entered(),
+        }
+    }
+}
+impl Drop for Operation<'_> {
+    fn drop(&mut self) {
+        self.path.borrow_mut().truncate(self.parent_len)
+    }
+}
+
+#[cfg(test)]
+mod test_trace {
+    use serde_json::json;
+    use std::fmt;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    use crate::Rule;
+
+    /// A subscriber recording the name and fields of every new span
+    #[derive(Clone, Default)]
+    struct Spans {
+        next_id: Arc<AtomicU64>,
+        spans: Arc<Mutex<Vec<String>>>,
+    }
+    struct Fields(String);
+    impl Visit for Fields {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0.push_str(&format!(" {}={:?}", field.name(), value));
+        }
+    }
+    impl Subscriber for Spans {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut fields = Fields(span.metadata().name().to_string());
+            span.record(&mut fields);
+            self.spans.lock().unwrap().push(fields.0);
+            Id::from_u64(self.next_id.fetch_add(1, Ordering::SeqCst) + 1)
+        }
+        fn record(&self, span: &Id, values: &Record<'_>) {
+            let mut fields = Fields(String::new());
+            values.record(&mut fields);
+            self.spans.lock().unwrap()[span.into_u64() as usize - 1]
+                .push_str(&fields.0);
+        }
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+        fn event(&self, _event: &Event<'_>) {}
+        fn enter(&self, _span: &Id) {}
+        fn exit(&self, _span: &Id) {}
+    }
+
+    #[test]
+    fn test_spans() {
+        let subscriber = Spans::default();
+        let spans = subscriber.spans.clone();
+        tracing::subscriber::with_default(subscriber, || {
+            Rule::from_value(&json!({"and": [true, {"==": [{"var": "a"}, 1]}]}))
+                .unwrap()
+                .with_id("rule-1")
+                .apply(&json!({"a": 1}))
+                .unwrap();
+        });
+        assert_eq!(
+            *spans.lock().unwrap(),
+            vec![
+                "jsonlogic.parse",
+                "jsonlogic.apply rule=\"rule-1\"",
+                "jsonlogic.operation operator=\"and\" arguments=2 path=\"and\"",
+                "jsonlogic.operation operator=\"==\" arguments=2 path=\"and/==\"",
+                "jsonlogic.operation operator=\"var\" arguments=1 path=\"and/==/var\"",
+            ]
+        );
+    }
+}
@@ -0,0 +1,483 @@
+//! Static Type Checking
+//!
+//! Find operator arguments whose type is known before evaluation to be the
+//! wrong one, such as the result of `cat` passed to `+`:
+//!
+//! ```
+//! use jsonlogic_plus::{typecheck, Rule};
+//! use serde_json::json;
+//!
+//! let rule = Rule::from_value(&json!({"+": [{"cat": ["a", "b"]}, 1]})).unwrap();
+//! let mismatches = typecheck::typecheck(&rule, None);
+//! assert_eq!(
+//!     mismatches[0].to_string(),
+//!     "at '/+/0': '+' expects number, found string"
+//! );
+//! ```
+//!
+//! Types are inferred from literals, from the results of built-in operators,
+//! and from the declared types of function parameters, e.g.
+//! `{"defn": ["double", [{"name": "x", "type": "number"}], ...]}`. Arguments
+//! whose types can't be inferred, such as `var`s, are assumed to be correct.
+
+use core::fmt;
+
+use crate::ast::Ast;
+use crate::collections::{HashMap, HashSet};
+use crate::error::Error;
+use crate::lint::push_segment;
+use crate::namespace::Namespace;
+use crate::op::data::Variable;
+use crate::op::func::Function;
+use crate::op::{ITERATORS, NUMERIC};
+use crate::prelude::*;
+use crate::resolver::{JsonLike, ValueKind};
+use crate::rule::Rule;
+
+/// An argument whose type doesn't match what its operator expects
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypeMismatch {
+    /// A JSON Pointer to the argument
+    pub path: String,
+    /// The function in which the argument was found, if it was found in a
+    /// function definition
+    pub function: Option<String>,
+    /// The operator or function the argument was passed to
+    pub operator: String,
+    /// The types the operator expects
+    pub expected: Vec<ValueKind>,
+    /// The type of the argument
+    pub found: ValueKind,
+}
+impl fmt::Display for TypeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(function) = &self.function {
+            write!(f, "in '{}' ", function)?;
+        }
+        let expected: Vec<&str> = self.expected.iter().map(ValueKind::name).collect();
+        write!(
+            f,
+            "at '{}': '{}' expects {}, found {}",
+            self.path,
+            self.operator,
+            expected.join(" or "),
+            self.found
+        )
+    }
+}
+
+/// Type check a rule.
+///
+/// If the rule was parsed from a namespace, pass the namespace so that calls
+/// may be checked against the functions' declared parameter types.
+pub fn typecheck(rule: &Rule, namespace: Option<&Namespace>) -> Vec<TypeMismatch> {
+    let mut checker = Checker::new(namespace.map(signatures).unwrap_or_default());
+    checker.check(&rule.ast(), &[]);
+    checker.mismatches
+}
+
+/// Type check the functions of a namespace, sorted by name. Paths are
+/// relative to each function's `defn`.
+pub fn typecheck_namespace(namespace: &Namespace) -> Result<Vec<TypeMismatch>, Error> {
+    let definitions = namespace.definitions()?;
+    let mut checker = Checker::new(signatures(namespace));
+    for def in definitions {
+        if let Ast::FunctionDef { name, body, .. } = def {
+            let params = checker.signatures[&name].params.clone();
+            checker.function = Some(name);
+            push_segment(&mut checker.path, Function::OPERATOR);
+            push_segment(&mut checker.path, "2");
+            checker.check(&body, &params);
+            checker.path.clear();
+        }
+    }
+    Ok(checker.mismatches)
+}
+
+/// The types an operator expects of its argument at the given position
+fn expected(operator: &str, position: usize) -> Option<&'static [ValueKind]> {
+    match (operator, position) {
+        _ if NUMERIC.contains(&operator) => Some(&[ValueKind::Number]),
+        ("substr", 0) => Some(&[ValueKind::String]),
+        ("substr", _) => Some(&[ValueKind::Number]),
+        ("pad_start" | "pad_end", 1) => Some(&[ValueKind::Number]),
+        ("join", 0) | ("zip" | "cartesian", _) => Some(&[ValueKind::Array]),
+        ("chunk" | "window" | "take" | "drop", 0) => Some(&[ValueKind::Array]),
+        ("chunk" | "window" | "take" | "drop", _) => Some(&[ValueKind::Number]),
+        ("join", 1) => Some(&[ValueKind::String]),
+        ("convert", 0) | ("range", _) => Some(&[ValueKind::Number]),
+        ("date_add" | "date_sub" | "within_last", 0) => Some(&[ValueKind::String]),
+        ("convert", _) => Some(&[ValueKind::String]),
+        (_, _) if STRINGS.contains(&operator) => Some(&[ValueKind::String]),
+        ("in", 1) => Some(&[ValueKind::Array, ValueKind::String]),
+        (_, 0) if ITERATORS.contains(&operator) => Some(&[ValueKind::Array]),
+        ("map_values", 0) => Some(&[ValueKind::Object]),
+        _ => None,
+    }
+}
+
+/// Operators whose results are always booleans
+const PREDICATES: &[&str] = &[
+    "==",
+    "===",
+    "!=",
+    "!==",
+    "eq",
+    "ne",
+    "<",
+    "<=",
+    ">",
+    ">=",
+    "!",
+    "!!",
+    "in",
+    "all",
+    "some",
+    "none",
+    "starts_with",
+    "ends_with",
+    "url_matches_pattern",
+    "semver_gt",
+    "semver_satisfies",
+    "geo_within",
+    "within_last",
+];
+
+/// Operators whose results are always arrays
+const COLLECTIONS: &[&str] = &[
+    "merge",
+    "zip",
+    "zip_with",
+    "cartesian",
+    "chunk",
+    "window",
+    "take",
+    "drop",
+    "range",
+    "map",
+    "filter",
+    "distinct_by",
+    "sort_by",
+    "sort_with",
+    "missing",
+    "missing_some",
+    "diff",
+    "split",
+];
+
+/// Operators whose arguments are all strings, other than the second of
+/// `pad_start` and `pad_end`
+const STRINGS: &[&str] = &[
+    "upper",
+    "lower",
+    "trim",
+    "trim_start",
+    "trim_end",
+    "pad_start",
+    "pad_end",
+    "starts_with",
+    "ends_with",
+    "split",
+    "compare",
+    "url_parse",
+    "url_host",
+    "url_matches_pattern",
+    "semver_gt",
+    "semver_satisfies",
+    "money_add",
+    "money_sub",
+    "money_cmp",
+    "money_convert",
+    "duration",
+];
+
+/// A function's parameters and their declared types, and its body
+#[derive(Clone)]
+struct Signature {
+    params: Vec<(String, Option<ValueKind>)>,
+    body: Ast,
+}
+
+fn signatures(namespace: &Namespace) -> HashMap<String, Signature> {
+    namespace
+        .definitions()
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|def| match def {
+            Ast::FunctionDef { name, params, body } => {
+                let types = namespace.get(&name)?.param_types();
+                let params = params.into_iter().zip(types.iter().copied()).collect();
+                Some((
+                    name,
+                    Signature {
+                        params,
+                        body: *body,
+                    },
+                ))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+struct Checker {
+    signatures: HashMap<String, Signature>,
+    /// The inferred return types of functions
+    returns: HashMap<String, Option<ValueKind>>,
+    /// Functions whose return types are being inferred, to stop recursion
+    inferring: HashSet<String>,
+    function: Option<String>,
+    path: String,
+    mismatches: Vec<TypeMismatch>,
+}
+impl Checker {
+    fn new(signatures: HashMap<String, Signature>) -> Self {
+        Self {
+            signatures,
+            returns: HashMap::new(),
+            inferring: HashSet::new(),
+            function: None,
+            path: String::new(),
+            mismatches: Vec::new(),
+        }
+    }
+
+    /// Check the arguments of every operation in an expression.
+    fn check(&mut self, ast: &Ast, params: &[(String, Option<ValueKind>)]) {
+        let (name, args): (&str, Vec<&Ast>) = match ast {
+            Ast::Operator { name, args } | Ast::FunctionCall { name, args } => {
+                (name.as_str(), args.iter().collect())
+            }
+            Ast::Var { path, default } => (
+                Variable::OPERATOR,
+                path.iter().chain(default).map(|arg| &**arg).collect(),
+            ),
+            Ast::Object(entries) => {
+                for (key, value) in entries {
+                    let parent_len = self.path.len();
+                    push_segment(&mut self.path, key);
+                    self.check(value, params);
+                    self.path.truncate(parent_len);
+                }
+                return;
+            }
+            Ast::Literal(_) | Ast::Param(_) | Ast::FunctionDef { .. } => return,
+        };
+        for (i, arg) in args.into_iter().enumerate() {
+            let parent_len = self.path.len();
+            push_segment(&mut self.path, name);
+            push_segment(&mut self.path, &i.to_string());
+            let expected = match ast {
+                Ast::FunctionCall { .. } => self
+                    .signatures
+                    .get(name)
+                    .and_then(|sig| sig.params.get(i))
+                    .and_then(|(_, kind)| *kind)
+                    .map(|kind| vec![kind]),
+                _ => expected(name, i).map(<[ValueKind]>::to_vec),
+            };
+            if let Some(expected) = expected {
+                match self.infer(arg, params) {
+                    Some(found) if !expected.contains(&found) => {
+                        self.mismatches.push(TypeMismatch {
+                            path: self.path.clone(),
+                            function: self.function.clone(),
+                            operator: name.into(),
+                            expected,
+                            found,
+                        })
+                    }
+                    _ => {}
+                }
+            }
+            self.check(arg, params);
+            self.path.truncate(parent_len);
+        }
+    }
+
+    /// The type an expression evaluates to, if it can be known ahead of time
+    fn infer(
+        &mut self,
+        ast: &Ast,
+        params: &[(String, Option<ValueKind>)],
+    ) -> Option<ValueKind> {
+        match ast {
+            Ast::Literal(value) => Some(value.kind()),
+            Ast::Object(_) => Some(ValueKind::Object),
+            Ast::Param(name) => params
+                .iter()
+                .find(|(param, _)| param == name)
+                .and_then(|(_, kind)| *kind),
+            Ast::FunctionCall { name, .. } => self.return_type(name),
+            Ast::Var { .. } | Ast::FunctionDef { .. } => None,
+            Ast::Operator { name, args } => match name.as_str() {
+                "cat" | "substr" | "upper" | "lower" | "trim" | "trim_start"
+                | "trim_end" | "pad_start" | "pad_end" | "join" | "money"
+                | "money_add" | "money_sub" | "money_convert" => {
+                    Some(ValueKind::String)
+                }
+                "convert" | "duration" | "position" | "sum_by" => {
+                    Some(ValueKind::Number)
+                }
+                "date_add" | "date_sub" => Some(ValueKind::String),
+                "obj" | "map_values" | "reduced" => Some(ValueKind::Object),
+                name if NUMERIC.contains(&name) => Some(ValueKind::Number),
+                name if PREDICATES.contains(&name) => Some(ValueKind::Bool),
+                name if COLLECTIONS.contains(&name) => Some(ValueKind::Array),
+                "if" | "?:" => {
+                    let mut branches: Vec<&Ast> =
+                        args.iter().skip(1).step_by(2).collect();
+                    match args.len() {
+                        len if len % 2 == 1 && len > 1 => branches.push(&args[len - 1]),
+                        _ => {
+                            return self.same(&branches, params, Some(ValueKind::Null))
+                        }
+                    }
+                    self.same(&branches, params, None)
+                }
+                "switch" => match args.as_slice() {
+                    [_, Ast::Object(cases), default @ ..] => {
+                        let branches: Vec<&Ast> = cases
+                            .iter()
+                            .map(|(_, branch)| branch)
+                            .chain(default)
+                            .collect();
+                        // Without a default, other values result in null
+                        let fallback = default.is_empty().then_some(ValueKind::Null);
+                        self.same(&branches, params, fallback)
+                    }
+                    _ => None,
+                },
+                "and" | "or" => {
+                    let args: Vec<&Ast> = args.iter().collect();
+                    self.same(&args, params, None)
+                }
+                "log" => self.infer(args.first()?, params),
+                _ => None,
+            },
+        }
+    }
+
+    /// The type shared by all of the given expressions (and `extra`, if set)
+    fn same(
+        &mut self,
+        asts: &[&Ast],
+        params: &[(String, Option<ValueKind>)],
+        extra: Option<ValueKind>,
+    ) -> Option<ValueKind> {
+        let mut kinds = Vec::with_capacity(asts.len() + 1);
+        kinds.extend(extra);
+        for ast in asts {
+            kinds.push(self.infer(ast, params)?);
+        }
+        match kinds.split_first() {
+            Some((first, rest)) if rest.iter().all(|kind| kind == first) => {
+                Some(*first)
+            }
+            _ => None,
+        }
+    }
+
+    fn return_type(&mut self, name: &str) -> Option<ValueKind> {
+        if let Some(kind) = self.returns.get(name) {
+            return *kind;
+        }
+        if !self.inferring.insert(name.into()) {
+            return None;
+        }
+        let kind = match self.signatures.get(name).cloned() {
+            Some(sig) => self.infer(&sig.body, &sig.params),
+            None => None,
+        };
+        self.inferring.remove(name);
+        self.returns.insert(name.into(), kind);
+        kind
+    }
+}
+
+#[cfg(test)]
+mod test_typecheck {
+    use super::*;
+    use serde_json::json;
+
+    fn paths(mismatches: &[TypeMismatch]) -> Vec<&str> {
+        mismatches.iter().map(|m| m.path.as_str()).collect()
+    }
+
+    #[test]
+    fn test_typecheck_rule() {
+        let rule = Rule::from_value(&json!({"and": [
+            {"==": [{"+": [{"cat": ["a", 1]}, {"var": "x"}]}, 2]},
+            {"map": [{"if": [{"var": "y"}, "abc", "def"]}, {"*": [{"var": ""}, 2]}]},
+            {"in": ["a", {"if": [{"var": "y"}, "abc", ["a"]]}]},
+            {"in": ["a", {"-": [3, 1]}]},
+            {"substr": [{"<": [1, 2]}, 0, {"var": "n"}]},
+            {"+": [{"if": [true, 1]}, 1]},
+            {"upper": [{"split": ["a b", " "]}]},
+            {"pad_start": ["a", "3"]},
+            {"join": [{"split": ["a", ""]}, {"trim": [" "]}]},
+            {"!": [{"starts_with": [{"lower": ["A"]}, "a"]}]},
+            {"+": [{"trim_end": ["1 "]}, 1]}
+        ]}))
+        .unwrap();
+        let mismatches = typecheck(&rule, None);
+        assert_eq!(
+            paths(&mismatches),
+            vec![
+                "/and/0/==/0/+/0",
+                "/and/1/map/0",
+                "/and/3/in/1",
+                "/and/4/substr/0",
+                "/and/6/upper/0",
+                "/and/7/pad_start/1",
+                "/and/10/+/0"
+            ]
+        );
+        assert_eq!(
+            mismatches[2],
+            TypeMismatch {
+                path: "/and/3/in/1".into(),
+                function: None,
+                operator: "in".into(),
+                expected: vec![ValueKind::Array, ValueKind::String],
+                found: ValueKind::Number,
+            }
+        );
+    }
+
+    #[test]
+    fn test_typecheck_namespace() {
+        let mut ns = Namespace::new();
+        ns.define(&json!({"defn": [
+            "greet",
+            [{"name": "name", "type": "string"}],
+            {"cat": ["Hello, ", {"param": "name"}]}
+        ]}))
+        .unwrap();
+        ns.define(&json!({"defn": [
+            "double",
+            [{"name": "x", "type": "number"}, "unused"],
+            {"*": [{"param": "x"}, 2]}
+        ]}))
+        .unwrap();
+        ns.define(&json!({"defn": [
+            "shout",
+            [{"name": "x", "type": "number"}],
+            {"-": [{"greet": [{"param": "x"}]}, 1]}
+        ]}))
+        .unwrap();
+        let mismatches = typecheck_namespace(&ns).unwrap();
+        assert_eq!(
+            mismatches.iter().map(ToString::to_string).collect::<Vec<String>>(),
+            vec![
+                "in 'shout' at '/defn/2/-/0': '-' expects number, found string",
+                "in 'shout' at '/defn/2/-/0/greet/0': 'greet' expects string, found number",
+            ]
+        );
+
+        let rule = ns
+            .parse(&json!({"+": [{"double": [{"greet": ["x"]}, null]}, {"double": [2, 1]}]}))
+            .unwrap();
+        assert_eq!(paths(&typecheck(&rule, Some(&ns))), vec!["/+/0/double/0"]);
+    }
+}
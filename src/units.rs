@@ -0,0 +1,269 @@
+//! Units of Measure
+//!
+//! The `convert` operator converts values between the units of the `Units`
+//! set on the evaluation's `Context`, e.g. `{"convert": [value, "km",
+//! "mi"]}`. Without one, the built-in units of `Units::default()` are used.
+
+use crate::collections::HashMap;
+use crate::error::Error;
+use crate::prelude::*;
+
+/// The built-in units, as their name, dimension, size in the dimension's
+/// base unit, and the value of their zero in the base unit
+const BUILTIN: &[(&str, &str, f64, f64)] = &[
+    // Length, in meters
+    ("m", "length", 1.0, 0.0),
+    ("km", "length", 1000.0, 0.0),
+    ("cm", "length", 0.01, 0.0),
+    ("mm", "length", 0.001, 0.0),
+    ("mi", "length", 1609.344, 0.0),
+    ("yd", "length", 0.9144, 0.0),
+    ("ft", "length", 0.3048, 0.0),
+    ("in", "length", 0.0254, 0.0),
+    ("nmi", "length", 1852.0, 0.0),
+    // Mass, in kilograms
+    ("kg", "mass", 1.0, 0.0),
+    ("g", "mass", 0.001, 0.0),
+    ("mg", "mass", 0.000_001, 0.0),
+    ("t", "mass", 1000.0, 0.0),
+    ("lb", "mass", 0.453_592_37, 0.0),
+    ("oz", "mass", 0.028_349_523_125, 0.0),
+    // Time, in seconds
+    ("ms", "time", 0.001, 0.0),
+    ("s", "time", 1.0, 0.0),
+    ("min", "time", 60.0, 0.0),
+    ("h", "time", 3600.0, 0.0),
+    ("d", "time", 86400.0, 0.0),
+    // Temperature, in kelvins
+    ("K", "temperature", 1.0, 0.0),
+    ("C", "temperature", 1.0, 273.15),
+    ("F", "temperature", 5.0 / 9.0, 255.372_222_222_222_2),
+    // Speed, in meters per second
+    ("m/s", "speed", 1.0, 0.0),
+    ("km/h", "speed", 1.0 / 3.6, 0.0),
+    ("mph", "speed", 0.447_04, 0.0),
+    ("kn", "speed", 1852.0 / 3600.0, 0.0),
+    // Volume, in liters
+    ("L", "volume", 1.0, 0.0),
+    ("mL", "volume", 0.001, 0.0),
+    ("m3", "volume", 1000.0, 0.0),
+    ("gal", "volume", 3.785_411_784, 0.0),
+    // Pressure, in pascals
+    ("Pa", "pressure", 1.0, 0.0),
+    ("kPa", "pressure", 1000.0, 0.0),
+    ("bar", "pressure", 100_000.0, 0.0),
+    ("psi", "pressure", 6_894.757_293_168, 0.0),
+];
+
+/// A unit of a dimension, e.g. length
+#[derive(Clone, Debug, PartialEq)]
+struct Unit {
+    dimension: String,
+    /// The size of the unit, in the dimension's base unit
+    scale: f64,
+    /// The value of the unit's zero, in the dimension's base unit
+    offset: f64,
+}
+
+/// A table of units for the `convert` operator
+///
+/// The default table has common units of length (`m`, `km`, `mi`, `ft`,
+/// ...), mass (`kg`, `g`, `lb`, `oz`, ...), time (`s`, `min`, `h`, `d`,
+/// ...), temperature (`C`, `F`, `K`), speed (`m/s`, `km/h`, `mph`, `kn`),
+/// volume (`L`, `mL`, `m3`, `gal`), and pressure (`Pa`, `kPa`, `bar`,
+/// `psi`). Add others with `with_unit()`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Units {
+    units: HashMap<String, Unit>,
+    builtin: bool,
+}
+impl Units {
+    /// A table with no units, not even the built-in ones
+    pub fn empty() -> Self {
+        Self {
+            units: HashMap::new(),
+            builtin: false,
+        }
+    }
+
+    /// The dimension, scale, and offset of the unit with the given name
+    fn unit(&self, name: &str) -> Option<(&str, f64, f64)> {
+        match self.units.get(name) {
+            Some(unit) => Some((&unit.dimension, unit.scale, unit.offset)),
+            None if self.builtin => BUILTIN
+                .iter()
+                .find(|(builtin, ..)| *builtin == name)
+                .map(|(_, dimension, scale, offset)| (*dimension, *scale, *offset)),
+            None => None,
+        }
+    }
+
+    /// Add a unit of a dimension, as its size in the dimension's base unit,
+    /// replacing any unit with the same name. Units may be converted to
+    /// others of the same dimension, e.g. `with_unit("furlong", "length",
+    /// 201.168)` for a unit of length, whose base unit is meters.
+    pub fn with_unit<N, D>(self, name: N, dimension: D, scale: f64) -> Self
+    where
+        N: Into<String>,
+        D: Into<String>,
+    {
+        self.with_offset_unit(name, dimension, scale, 0.0)
+    }
+
+    /// Add a unit whose zero is offset from the base unit's, like degrees
+    /// Celsius from kelvins, as its size and the value of its zero in the
+    /// dimension's base unit.
+    pub fn with_offset_unit<N, D>(
+        mut self,
+        name: N,
+        dimension: D,
+        scale: f64,
+        offset: f64,
+    ) -> Self
+    where
+        N: Into<String>,
+        D: Into<String>,
+    {
+        self.units.insert(
+            name.into(),
+            Unit {
+                dimension: dimension.into(),
+                scale,
+                offset,
+            },
+        );
+        self
+    }
+
+    /// Convert a value from one unit to another of the same dimension.
+    pub(crate) fn convert(
+        &self,
+        value: f64,
+        from: &str,
+        to: &str,
+    ) -> Result<f64, Error> {
+        let unit = |name: &str| {
+            self.unit(name).ok_or_else(|| {
+                Error::invalid_argument(
+                    name.into(),
+                    "convert",
+                    format!("No such unit: '{}'", name),
+                )
+            })
+        };
+        let (from_dimension, from_scale, from_offset) = unit(from)?;
+        let (to_dimension, to_scale, to_offset) = unit(to)?;
+        if from_dimension != to_dimension {
+            return Err(Error::invalid_argument(
+                to.into(),
+                "convert",
+                format!(
+                    "Cannot convert {} ({}) to {} ({})",
+                    from, from_dimension, to, to_dimension
+                ),
+            ));
+        }
+        if from == to {
+            return Ok(value);
+        }
+        Ok((value * from_scale + from_offset - to_offset) / to_scale)
+    }
+}
+impl Default for Units {
+    /// A table with the built-in units
+    fn default() -> Self {
+        Self {
+            units: HashMap::new(),
+            builtin: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_units {
+    use super::*;
+    use alloc::sync::Arc;
+    use serde_json::{json, Value};
+
+    use crate::{Context, Rule};
+
+    fn convert(args: Value, ctx: &Context) -> Result<Value, Error> {
+        Rule::from_value(&json!({ "convert": args }))?.apply_with(&json!({}), ctx)
+    }
+
+    fn close(value: Value, expected: f64) -> bool {
+        (value.as_f64().unwrap() - expected).abs() < 1e-9
+    }
+
+    #[test]
+    fn test_builtin_units() {
+        let ctx = Context::default();
+        assert!(close(
+            convert(json!([10, "km", "mi"]), &ctx).unwrap(),
+            6.213_711_922_373_34
+        ));
+        assert!(close(
+            convert(json!([1, "mi", "m"]), &ctx).unwrap(),
+            1609.344
+        ));
+        assert_eq!(convert(json!([1000, "m", "km"]), &ctx).unwrap(), json!(1));
+        assert!(close(convert(json!([100, "C", "F"]), &ctx).unwrap(), 212.0));
+        assert!(close(convert(json!([-40, "F", "C"]), &ctx).unwrap(), -40.0));
+        assert!(close(convert(json!([0, "C", "K"]), &ctx).unwrap(), 273.15));
+        assert!(close(
+            convert(json!([36, "km/h", "m/s"]), &ctx).unwrap(),
+            10.0
+        ));
+        assert_eq!(convert(json!([90, "min", "h"]), &ctx).unwrap(), json!(1.5));
+        assert_eq!(convert(json!([2.5, "kg", "kg"]), &ctx).unwrap(), json!(2.5));
+    }
+
+    #[test]
+    fn test_invalid_conversions() {
+        let ctx = Context::default();
+        for args in [
+            json!([1, "km", "kg"]),
+            json!([1, "km", "parsec"]),
+            json!(["1", "km", "m"]),
+            json!([1, "km", 1]),
+        ] {
+            assert_eq!(
+                convert(args.clone(), &ctx).unwrap_err().code(),
+                "invalid_argument",
+                "{}",
+                args
+            );
+        }
+    }
+
+    #[test]
+    fn test_custom_units() {
+        let units = Units::default()
+            .with_unit("furlong", "length", 201.168)
+            .with_unit("fortnight", "time", 1_209_600.0)
+            .with_offset_unit("Ra", "temperature", 5.0 / 9.0, 0.0);
+        let ctx = Context {
+            units: Some(Arc::new(units)),
+            ..Context::default()
+        };
+        assert!(close(
+            convert(json!([1, "mi", "furlong"]), &ctx).unwrap(),
+            8.0
+        ));
+        assert!(close(convert(json!([0, "C", "Ra"]), &ctx).unwrap(), 491.67));
+        let ctx = Context {
+            units: Some(Arc::new(Units::empty())),
+            ..Context::default()
+        };
+        assert_eq!(
+            convert(json!([1, "km", "m"]), &ctx).unwrap_err().code(),
+            "invalid_argument"
+        );
+    }
+
+    #[test]
+    fn test_untranslatable() {
+        let rule = Rule::from_value(&json!({"convert": [1, "km", "mi"]})).unwrap();
+        assert_eq!(rule.to_javascript().unwrap_err().code(), "untranslatable");
+    }
+}
@@ -1,7 +1,17 @@
-use serde_json::{Number, Value};
+use core::borrow::Borrow;
+use core::ops::Deref;
+use serde_json::{Map, Number, Value};
 
+use crate::arena::ArenaSlice;
+use crate::context::Env;
 use crate::error::Error;
-use crate::op::{DataOperation, LazyOperation, Operation};
+use crate::namespace::Scope;
+use crate::op::data::Variable;
+use crate::op::func::{Call, Param};
+use crate::op::{single_entry, DataOperation, LazyOperation, Operation};
+use crate::pack::CustomOperation;
+use crate::prelude::*;
+use crate::rule::MultiKeyObjects;
 use crate::Parser;
 
 /// A Parsed JSON value
@@ -9,75 +19,249 @@ use crate::Parser;
 /// Parsed values are one of:
 ///   - An operation whose arguments are eagerly evaluated
 ///   - An operation whose arguments are lazily evaluated
+///   - An operation that operates on the data
+///   - A variable lookup into the data
+///   - An operation using a custom operator from an operator pack
+///   - A call to a function in a namespace
+///   - A reference to a parameter of the enclosing function
+///   - An object whose values are evaluated, if multi-key objects are
+///     parsed with `MultiKeyObjects::Evaluate`
 ///   - A raw value: a non-rule, raw JSON value
-#[derive(Debug)]
-pub enum Parsed<'a> {
-    Operation(Operation<'a>),
-    LazyOperation(LazyOperation<'a>),
-    DataOperation(DataOperation<'a>),
-    Raw(Raw<'a>),
+///
+/// Parsed values own everything they need, so that they may be parsed once
+/// and evaluated any number of times.
+#[derive(Clone, Debug)]
+pub enum Parsed {
+    Operation(Operation),
+    LazyOperation(LazyOperation),
+    DataOperation(DataOperation),
+    Variable(Variable),
+    CustomOperation(CustomOperation),
+    Call(Call),
+    Param(Param),
+    Object(Object),
+    Raw(Raw),
 }
-impl<'a> Parsed<'a> {
+impl Parsed {
     /// Recursively parse a value
-    pub fn from_value(value: &'a Value) -> Result<Self, Error> {
-        Operation::from_value(value)?
-            .map(Self::Operation)
-            // .or(Operation::from_value(value)?.map(Self::Operation))
-            .or(LazyOperation::from_value(value)?.map(Self::LazyOperation))
-            .or(DataOperation::from_value(value)?.map(Self::DataOperation))
-            .or(Raw::from_value(value)?.map(Self::Raw))
-            .ok_or(Error::UnexpectedError(format!(
-                "Failed to parse Value {:?}",
-                value
-            )))
+    pub(crate) fn from_value(value: &Value, scope: &Scope) -> Result<Self, Error> {
+        let value = &*scope.unalias(value);
+        // Each parser is only tried if none before it matched, so that e.g.
+        // an operation's arguments aren't also parsed, or its value copied,
+        // by the parsers after it
+        if let Some(op) = Operation::from_value(value, scope)? {
+            return Ok(Self::Operation(op));
+        }
+        if let Some(op) = LazyOperation::from_value(value, scope)? {
+            return Ok(Self::LazyOperation(op));
+        }
+        if let Some(var) = Variable::from_value(value, scope)? {
+            return Ok(Self::Variable(var));
+        }
+        if let Some(op) = DataOperation::from_value(value, scope)? {
+            return Ok(Self::DataOperation(op));
+        }
+        if let Some(op) = CustomOperation::from_value(value, scope)? {
+            return Ok(Self::CustomOperation(op));
+        }
+        if let Some(call) = Call::from_value(value, scope)? {
+            return Ok(Self::Call(call));
+        }
+        if let Some(param) = Param::from_value(value, scope)? {
+            return Ok(Self::Param(param));
+        }
+        if let Some(obj) = Object::from_value(value, scope)? {
+            return Ok(Self::Object(obj));
+        }
+        Raw::from_value(value, scope)?
+            .map(Self::Raw)
+            .ok_or_else(|| {
+                Error::UnexpectedError(format!("Failed to parse Value {:?}", value))
+            })
     }
 
-    pub fn from_values(values: Vec<&'a Value>) -> Result<Vec<Self>, Error> {
-        values
-            .into_iter()
-            .map(Self::from_value)
-            .collect::<Result<Vec<Self>, Error>>()
+    pub(crate) fn from_values(
+        values: &[Value],
+        scope: &Scope,
+    ) -> Result<ArenaSlice<Self>, Error> {
+        scope.slice(values.iter().map(|value| Self::from_value(value, scope)))
     }
 
-    pub fn evaluate(&self, data: &'a Value) -> Result<Evaluated, Error> {
+    pub fn evaluate(&self, data: &Value, env: &Env) -> Result<Evaluated<'_>, Error> {
         match self {
-            Self::Operation(op) => op.evaluate(data),
-            Self::LazyOperation(op) => op.evaluate(data),
-            Self::DataOperation(op) => op.evaluate(data),
-            Self::Raw(val) => val.evaluate(data),
+            Self::Operation(op) => {
+                env.execute(op.symbol(), op.arguments().len(), || {
+                    op.evaluate(data, env)
+                })
+            }
+            Self::LazyOperation(op) => {
+                env.execute(op.symbol(), op.arguments().len(), || {
+                    op.evaluate(data, env)
+                })
+            }
+            Self::DataOperation(op) => {
+                env.execute(op.symbol(), op.arguments().len(), || {
+                    op.evaluate(data, env)
+                })
+            }
+            Self::Variable(var) => {
+                env.execute(Variable::OPERATOR, var.arguments().len(), || {
+                    var.evaluate(data, env)
+                })
+            }
+            Self::CustomOperation(op) => {
+                env.execute(op.operator().name(), op.arguments().len(), || {
+                    op.evaluate(data, env)
+                })
+            }
+            Self::Call(call) => {
+                env.execute(call.name(), call.arguments().len(), || {
+                    call.evaluate(data, env)
+                })
+            }
+            Self::Param(param) => param.evaluate(data, env),
+            Self::Object(obj) => obj.evaluate(data, env),
+            Self::Raw(val) => val.evaluate(data, env),
         }
     }
 }
-impl From<Parsed<'_>> for Value {
+impl From<Parsed> for Value {
     fn from(item: Parsed) -> Value {
         match item {
             Parsed::Operation(op) => Value::from(op),
             Parsed::LazyOperation(op) => Value::from(op),
             Parsed::DataOperation(op) => Value::from(op),
+            Parsed::Variable(var) => Value::from(var),
+            Parsed::CustomOperation(op) => Value::from(op),
+            Parsed::Call(call) => Value::from(call),
+            Parsed::Param(param) => Value::from(param),
+            Parsed::Object(obj) => Value::from(obj),
             Parsed::Raw(raw) => Value::from(raw),
         }
     }
 }
 
+/// An object with more than one key, whose values are evaluated
+#[derive(Clone, Debug)]
+pub struct Object {
+    entries: Vec<(String, Parsed)>,
+}
+impl Object {
+    pub(crate) fn new(entries: Vec<(String, Parsed)>) -> Self {
+        Self { entries }
+    }
+
+    /// The keys, and the expressions for their values
+    pub(crate) fn entries(&self) -> &[(String, Parsed)] {
+        &self.entries
+    }
+
+    /// The entries, to be replaced when generating mutants
+    pub(crate) fn entries_mut(&mut self) -> &mut Vec<(String, Parsed)> {
+        &mut self.entries
+    }
+}
+impl Parser for Object {
+    fn from_value(value: &Value, scope: &Scope) -> Result<Option<Self>, Error> {
+        let obj = match value {
+            Value::Object(obj) if obj.len() > 1 => obj,
+            _ => return Ok(None),
+        };
+        match scope.options().multi_key_objects {
+            MultiKeyObjects::Literal => Ok(None),
+            MultiKeyObjects::Error => Err(Error::InvalidOperation {
+                key: obj.keys().cloned().collect::<Vec<String>>().join(", "),
+                reason: "Objects with more than one key are not allowed".into(),
+            }),
+            MultiKeyObjects::Evaluate => Ok(Some(Self {
+                entries: obj
+                    .iter()
+                    .map(|(key, value)| {
+                        Parsed::from_value(value, scope)
+                            .map(|value| (key.clone(), value))
+                    })
+                    .collect::<Result<_, Error>>()?,
+            })),
+        }
+    }
+
+    fn evaluate(&self, data: &Value, env: &Env) -> Result<Evaluated<'_>, Error> {
+        self.entries
+            .iter()
+            .map(|(key, value)| {
+                value
+                    .evaluate(data, env)
+                    .map(|value| (key.clone(), Value::from(value)))
+            })
+            .collect::<Result<Map<String, Value>, Error>>()
+            .map(|obj| Evaluated::New(Value::Object(obj)))
+    }
+}
+impl From<Object> for Value {
+    fn from(obj: Object) -> Self {
+        Value::Object(
+            obj.entries
+                .into_iter()
+                .map(|(key, value)| (key, Value::from(value)))
+                .collect(),
+        )
+    }
+}
+
 /// A Raw JSON value
 ///
 /// Raw values are those that are not any known operation. A raw value may
 /// be of any valid JSON type.
-#[derive(Debug)]
-pub struct Raw<'a> {
-    value: &'a Value,
+///
+/// A value that would otherwise be an operation may be quoted with `lit`,
+/// e.g. `{"lit": {"var": "x"}}` is the object `{"var": "x"}`.
+#[derive(Clone, Debug)]
+pub struct Raw {
+    value: Value,
+    /// Whether the value was quoted with `lit`, and so must be again when
+    /// it's converted back into a rule
+    quoted: bool,
+}
+impl Raw {
+    pub(crate) const OPERATOR: &'static str = "lit";
+
+    pub(crate) fn new(value: Value) -> Self {
+        Self {
+            value,
+            quoted: false,
+        }
+    }
+
+    /// The raw value
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
 }
-impl<'a> Parser<'a> for Raw<'a> {
-    fn from_value(value: &'a Value) -> Result<Option<Self>, Error> {
-        Ok(Some(Self { value }))
+impl Parser for Raw {
+    fn from_value(value: &Value, scope: &Scope) -> Result<Option<Self>, Error> {
+        if let Some((key, val)) = single_entry(value)? {
+            if key == Self::OPERATOR {
+                return Ok(Some(Self {
+                    value: val.clone(),
+                    quoted: true,
+                }));
+            }
+        }
+        scope.check_operator(value)?;
+        Ok(Some(Self::new(scope.qualify(value))))
     }
-    fn evaluate(&self, _data: &Value) -> Result<Evaluated, Error> {
-        Ok(Evaluated::Raw(self.value))
+    fn evaluate(&self, _data: &Value, _env: &Env) -> Result<Evaluated<'_>, Error> {
+        Ok(Evaluated::Raw(&self.value))
     }
 }
-impl From<Raw<'_>> for Value {
+impl From<Raw> for Value {
     fn from(raw: Raw) -> Self {
-        raw.value.clone()
+        if raw.quoted {
+            let mut rv = Map::with_capacity(1);
+            rv.insert(Raw::OPERATOR.into(), raw.value);
+            return Value::Object(rv);
+        }
+        raw.value
     }
 }
 
@@ -92,6 +276,23 @@ pub enum Evaluated<'a> {
     Raw(&'a Value),
 }
 
+impl Deref for Evaluated<'_> {
+    type Target = Value;
+
+    fn deref(&self) -> &Value {
+        match self {
+            Evaluated::Raw(val) => val,
+            Evaluated::New(val) => val,
+        }
+    }
+}
+
+impl Borrow<Value> for Evaluated<'_> {
+    fn borrow(&self) -> &Value {
+        self
+    }
+}
+
 impl From<Evaluated<'_>> for Value {
     fn from(item: Evaluated) -> Self {
         match item {
@@ -102,7 +303,8 @@ impl From<Evaluated<'_>> for Value {
 }
 
 pub fn to_number_value(number: f64) -> Result<Value, Error> {
-    if number.fract() == 0.0 {
+    // `fract()` is only available with std
+    if number % 1.0 == 0.0 {
         Ok(Value::Number(Number::from(number as i64)))
     } else {
         Number::from_f64(number)
@@ -0,0 +1,147 @@
+//! YAML Values
+//!
+//! With the `serde_yaml` feature, `serde_yaml::Value` implements `JsonLike`,
+//! so configuration written in YAML may be evaluated directly via
+//! `Rule::apply_resolver()`. Tagged values are treated as the value they
+//! tag, and scalar mapping keys (e.g. the `1` in `1: one`) may be looked up
+//! by their string form.
+
+use std::borrow::Cow;
+
+use serde_json::Number;
+use serde_yaml::Value;
+
+use crate::resolver::{JsonLike, ValueKind};
+
+/// A mapping key as a string, if it is a scalar
+fn key_str(key: &Value) -> Option<Cow<'_, str>> {
+    match key {
+        Value::String(s) => Some(Cow::Borrowed(s)),
+        Value::Number(n) => Some(Cow::Owned(n.to_string())),
+        Value::Bool(b) => Some(Cow::Owned(b.to_string())),
+        _ => None,
+    }
+}
+
+impl JsonLike for Value {
+    fn kind(&self) -> ValueKind {
+        match self {
+            Value::Null => ValueKind::Null,
+            Value::Bool(_) => ValueKind::Bool,
+            Value::Number(_) => ValueKind::Number,
+            Value::String(_) => ValueKind::String,
+            Value::Sequence(_) => ValueKind::Array,
+            Value::Mapping(_) => ValueKind::Object,
+            Value::Tagged(tagged) => tagged.value.kind(),
+        }
+    }
+
+    fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Tagged(tagged) => JsonLike::as_bool(&tagged.value),
+            _ => self.as_bool(),
+        }
+    }
+
+    fn as_number(&self) -> Option<Number> {
+        match self {
+            Value::Number(n) => n
+                .as_i64()
+                .map(Number::from)
+                .or_else(|| n.as_u64().map(Number::from))
+                .or_else(|| n.as_f64().and_then(Number::from_f64)),
+            Value::Tagged(tagged) => tagged.value.as_number(),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Tagged(tagged) => JsonLike::as_str(&tagged.value),
+            _ => self.as_str(),
+        }
+    }
+
+    fn as_array(&self) -> Option<&[Self]> {
+        match self {
+            Value::Sequence(seq) => Some(seq),
+            Value::Tagged(tagged) => JsonLike::as_array(&tagged.value),
+            _ => None,
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&Self> {
+        match self {
+            Value::Mapping(map) => map.get(key).or_else(|| {
+                map.iter()
+                    .find(|(k, _)| key_str(k).as_deref() == Some(key))
+                    .map(|(_, value)| value)
+            }),
+            Value::Tagged(tagged) => JsonLike::get(&tagged.value, key),
+            _ => None,
+        }
+    }
+
+    fn entries(&self) -> Box<dyn Iterator<Item = (Cow<'_, str>, &Self)> + '_> {
+        match self {
+            Value::Mapping(map) => Box::new(
+                map.iter()
+                    .filter_map(|(key, value)| key_str(key).map(|key| (key, value))),
+            ),
+            Value::Tagged(tagged) => tagged.value.entries(),
+            _ => Box::new(std::iter::empty()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_yaml {
+    use super::*;
+    use serde_json::json;
+
+    use crate::resolver::DataResolver;
+    use crate::Rule;
+
+    #[test]
+    fn test_evaluate_yaml() {
+        let data: Value = serde_yaml::from_str(
+            "
+            service:
+              name: api
+              replicas: 3
+              ratio: 0.5
+              ports: [80, 443]
+              enabled: true
+              region: !Region us-east-1
+            1: one
+            ",
+        )
+        .unwrap();
+
+        let rule = Rule::from_value(&json!({"and": [
+            {"==": [{"var": "service.name"}, "api"]},
+            {">": [{"var": "service.replicas"}, 2]},
+            {"in": [443, {"var": "service.ports"}]},
+            {"var": "service.enabled"},
+            {"==": [{"var": "service.region"}, "us-east-1"]},
+            {"==": [{"var": "1"}, "one"]},
+            {"==": [{"var": "service.name.0"}, "a"]},
+            {"==": [{"var": "missing"}, null]}
+        ]}))
+        .unwrap();
+        assert_eq!(rule.apply_resolver(&data).unwrap(), json!(true));
+        assert_eq!(
+            data.resolve(&[crate::PathSegment::new("service")])
+                .unwrap()
+                .into_owned(),
+            json!({
+                "name": "api",
+                "replicas": 3,
+                "ratio": 0.5,
+                "ports": [80, 443],
+                "enabled": true,
+                "region": "us-east-1"
+            })
+        );
+    }
+}
@@ -0,0 +1,58 @@
+//! Count the allocations made while parsing rules.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde_json::{json, Value};
+
+use jsonlogic_plus::Rule;
+
+/// The system allocator, counting each allocation
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// The number of allocations made while parsing a rule
+fn parse_allocations(rule: &Value) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let parsed = Rule::from_value(rule).unwrap();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    drop(parsed);
+    allocations
+}
+
+/// A rule of `depth` nested `if`s, each with a variable and some literals
+fn nested(depth: usize) -> Value {
+    (0..depth).fold(
+        json!({"var": "x.y"}),
+        |rule, i| json!({"if": [{"<": [{"var": "x.y"}, i]}, rule, {"cat": ["a", i]}]}),
+    )
+}
+
+#[test]
+fn test_parse_allocations() {
+    // Each node is parsed once, so doubling the depth of a rule roughly
+    // doubles the allocations, rather than quadrupling them
+    let shallow = parse_allocations(&nested(100));
+    let deep = parse_allocations(&nested(200));
+    assert!(
+        deep < shallow * 5 / 2,
+        "{} allocations for 100 levels, but {} for 200",
+        shallow,
+        deep
+    );
+}
@@ -0,0 +1,18 @@
+//! Run the shared JsonLogic test suite through the compatibility runner.
+#![cfg(feature = "compat-tests")]
+
+use jsonlogic_plus::compat;
+
+#[test]
+fn run_official_suite() {
+    let report = compat::run(&compat::official_suite()).unwrap();
+    for failure in &report.failures {
+        println!("{}", failure);
+    }
+    assert!(report.passed > 0);
+    assert!(
+        report.is_success(),
+        "{} cases failed",
+        report.failures.len()
+    );
+}
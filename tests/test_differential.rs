@@ -0,0 +1,29 @@
+//! Compare generated rules' results with those of json-logic-js.
+//!
+//! Set `JSONLOGIC_JS` to the path of json-logic-js's `logic.js` to run
+//! these tests; they're skipped otherwise.
+#![cfg(feature = "differential")]
+
+use jsonlogic_plus::differential::{self, Engine};
+
+#[test]
+fn compare_generated_rules() {
+    let path = match std::env::var("JSONLOGIC_JS") {
+        Ok(path) => path,
+        Err(_) => {
+            println!("JSONLOGIC_JS is not set; skipping");
+            return;
+        }
+    };
+    let engine = Engine::new(&std::fs::read_to_string(path).unwrap()).unwrap();
+    let report = differential::run(&engine, 0, 1000);
+    for divergence in &report.divergences {
+        println!("{}", divergence);
+    }
+    assert!(report.checked > 0);
+    assert!(
+        report.is_success(),
+        "{} rules diverged",
+        report.divergences.len()
+    );
+}
@@ -0,0 +1,466 @@
+//! Tests for rules translated to JavaScript
+//!
+//! Each rule is translated with `Rule::to_javascript()` and run with node,
+//! and the results are compared with those of the Rust implementation.
+//! These tests are skipped if node isn't available.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use serde_json::{json, Value};
+
+use jsonlogic_plus::{
+    MultiKeyObjects, Namespace, ParseOptions, Rule, SemanticsVersion,
+};
+
+/// Run each rule against its data with node, returning the results, or
+/// `None` for rules that threw.
+fn run_with_node(cases: &[(String, Value)]) -> Option<Vec<Option<Value>>> {
+    let script: String = cases
+        .iter()
+        .map(|(source, data)| {
+            format!(
+                "try {{ results.push({{ ok: ({})({}) }}); }} catch (e) {{ results.push(null); }}\n",
+                source, data
+            )
+        })
+        .collect();
+    let mut node = match Command::new("node")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+    {
+        Ok(node) => node,
+        Err(_) => {
+            println!("node is not available; skipping");
+            return None;
+        }
+    };
+    write!(
+        node.stdin.take().unwrap(),
+        "const results = [];\n{}console.log(JSON.stringify(results));\n",
+        script
+    )
+    .unwrap();
+    let output = node.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let results: Vec<Value> = serde_json::from_slice(&output.stdout).unwrap();
+    Some(
+        results
+            .into_iter()
+            .map(|result| match result {
+                Value::Null => None,
+                result => Some(result["ok"].clone()),
+            })
+            .collect(),
+    )
+}
+
+/// Equality, with numbers compared by value
+fn same(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same(a, b))
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(key, a)| b.get(key).is_some_and(|b| same(a, b)))
+        }
+        (a, b) => a == b,
+    }
+}
+
+/// Check each rule under the default semantics version, and pinned to the
+/// latest one.
+fn check(rules: &[(Rule, Value)]) {
+    let rules: Vec<(Rule, Value)> = rules
+        .iter()
+        .flat_map(|(rule, data)| {
+            [
+                (rule.clone(), data.clone()),
+                (
+                    rule.clone().with_semantics(SemanticsVersion::LATEST),
+                    data.clone(),
+                ),
+            ]
+        })
+        .collect();
+    let cases: Vec<(String, Value)> = rules
+        .iter()
+        .map(|(rule, data)| (rule.to_javascript().unwrap(), data.clone()))
+        .collect();
+    let results = match run_with_node(&cases) {
+        Some(results) => results,
+        None => return,
+    };
+    let failures: Vec<String> = rules
+        .iter()
+        .zip(results)
+        .filter_map(|((rule, data), js)| {
+            let rust = rule.apply(data).ok();
+            let matches = match (&rust, &js) {
+                (Some(rust), Some(js)) => same(rust, js),
+                (None, None) => true,
+                _ => false,
+            };
+            (!matches).then(|| {
+                format!(
+                    "{} (semantics {:?}): rust {:?}, js {:?}",
+                    Value::from(rule.ast()),
+                    rule.semantics(),
+                    rust,
+                    js
+                )
+            })
+        })
+        .collect();
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn test_official_suite() {
+    let suite: Vec<Value> =
+        serde_json::from_str(include_str!("data/tests.json")).unwrap();
+    let rules: Vec<(Rule, Value)> = suite
+        .into_iter()
+        .filter_map(|case| match case {
+            Value::Array(case) => {
+                Some((Rule::from_value(&case[0]).unwrap(), case[1].clone()))
+            }
+            _ => None,
+        })
+        .collect();
+    check(&rules);
+}
+
+#[test]
+fn test_extensions() {
+    let mut ns = Namespace::new();
+    ns.define(&json!({"defn": ["double", ["n"], {"*": [{"param": "n"}, 2]}]}))
+        .unwrap();
+    ns.define(&json!({"defn": ["fact", ["n"], {"if": [
+        {"<=": [{"param": "n"}, 1]},
+        1,
+        {"*": [{"param": "n"}, {"fact": [{"-": [{"param": "n"}, 1]}]}]}
+    ]}]}))
+    .unwrap();
+    ns.define(&json!({"defn": [
+        "dec",
+        ["n"],
+        {"-": [{"param": "n"}, 1]},
+        {"pre": {">": [{"param": "n"}, 0]}, "post": {">": [{"var": ""}, 0]}}
+    ]}))
+    .unwrap();
+    ns.define(&json!({"defn": [
+        "pad",
+        ["s", ["width", 6], ["fill", {"substr": [{"param": "s"}, 0, 1]}], "...rest"],
+        {"cat": [{"param": "s"}, {"param": "width"}, {"param": "fill"}, {"param": "rest"}]}
+    ]}))
+    .unwrap();
+    ns.define(&json!({"defn": ["scale", ["k"], {"defn": [
+        "by",
+        ["x"],
+        {"*": [{"param": "x"}, {"param": "k"}]}
+    ]}]}))
+    .unwrap();
+    let data = json!({"xs": [1, "2", 3.5], "s": "héllo", "o": {"a": [1, {"b": null}]}, "r": {"reduced": 2}});
+    let mut rules: Vec<(Rule, Value)> = vec![
+        json!({"map": [{"var": "xs"}, {"double": [{"var": ""}]}]}),
+        json!({"fact": [{"var": "xs.2"}]}),
+        json!({"fact": [6]}),
+        json!({"dec": [{"var": "xs.2"}]}),
+        json!({"dec": [1]}),
+        json!({"dec": [-1]}),
+        json!({"call": [{"if": [{"var": "s"}, {"fn": "double"}, {"fn": "fact"}]}, [3]]}),
+        json!({"call": [{"fn": "double"}, [1, 2]]}),
+        json!({"call": [{"var": "o"}, [1]]}),
+        json!({"apply": [{"comp": [{"fn": "fact"}, {"fn": "double"}]}, [2]]}),
+        json!({"comp": [{"fn": "fact"}, 1]}),
+        json!({"pad": [{"var": "s"}]}),
+        json!({"pad": ["ab", 2]}),
+        json!({"pad": ["ab", 2, "-", 1, [2]]}),
+        json!({"call": [{"fn": "pad"}, ["x"]]}),
+        json!({"call": [{"fn": "pad"}, []]}),
+        json!({"map": [{"var": "xs"}, {"call": [{"scale": [3]}, {"merge": [{"var": ""}]}]}]}),
+        json!({"scale": [2]}),
+        json!({"closure": [{"fn": "double"}, 1, 2]}),
+        json!({"call": [{"partial": [{"fn": "pad"}, ["ab", 3]]}, ["*", "x"]]}),
+        json!({"partial": [{"fn": "double"}, {"var": "xs"}]}),
+        json!({"partial": [{"fn": "double"}, 2]}),
+        json!({"call": [{"closure": [{"fn": "double"}, 2]}, []]}),
+        json!({"pipe": [{"var": "xs"}, {"map": [{"var": ""}, {"double": [{"var": ""}]}]}, {"var": "0"}]}),
+        json!({"pipe": [{"var": "o"}, {"var": "a.1"}, {"missing": ["b", "c"]}]}),
+        json!({"merge": [{"lit": {"var": "s"}}, {"var": "s"}, {"lit": [1]}]}),
+        json!({"coalesce": [{"var": "o.a.1.b"}, {"var": "nope"}, {"var": "s"}, {"/": [1, 0]}]}),
+        json!({"coalesce": [{"var": "nope"}]}),
+        json!({"default": [{"var": "nope"}, {"var": "o.a.1.b"}, 1]}),
+        json!({"default": [{"var": "nope"}, {"var": ["nope", 2]}, 1]}),
+        json!({"default": [{"var": {"cat": ["x", "s"]}}, {"var": "nope"}]}),
+        json!({"default": [{"var": "nope"}, {"double": [{"var": "xs.0"}]}]}),
+        json!({"try": [{"dec": [{"var": "xs.0"}]}, {"var": "s"}]}),
+        json!({"try": [{"fact": [{"var": "xs.1"}]}, {"var": "s"}]}),
+        json!({"try": [{"+": [1, "abc"]}, {"missing": ["e", "s"]}, "e"]}),
+        json!({"match": [{"var": "o"}, {"shape": {"a": [1, {"bind": "b"}]}}, 1, {"shape": {"a": {"bind": "xs"}}}, {"var": "xs"}]}),
+        json!({"match": [{"var": "xs.1"}, {"type": "number"}, "n", {"type": "string"}, {"cat": [{"var": "s"}, "!"]}]}),
+        json!({"match": [{"var": "s"}, {"type": "array"}, 1, "héllo", 2, 3]}),
+        json!({"match": [{"var": "xs"}, [1, "2"], 1, {"type": "object"}, 2]}),
+        json!({"var": "xs.-1"}),
+        json!({"var": "s.1"}),
+        json!({"var": ["o.a.1.b", "default"]}),
+        json!({"var": ["o.a.1.c", {"var": "s"}]}),
+        json!({"var": {"pointer": "/o/a/1/b"}}),
+        json!({"var": [{"pointer": "/o/c"}, {"var": {"pointer": ""}}]}),
+        json!({"var": {"lit": {"pointer": "/xs/-1"}}}),
+        json!({"var": ["nope", {"lit": {"a": "s"}}]}),
+        json!({"$version": "2", "rule": {"var": ["nope", {"lit": {"var": "s"}}]}}),
+        json!({"var": "o.a.*.b"}),
+        json!({"var": "**.b"}),
+        json!({"var": ["nope.*", "default"]}),
+        json!({"var": "o.*"}),
+        json!({"missing": ["o.*.nope", "nope.**"]}),
+        json!({"var": "xs.1:3"}),
+        json!({"var": "xs.-2:"}),
+        json!({"var": "s.:-2"}),
+        json!({"var": "xs.:1.0"}),
+        json!({"var": "o.a.*.1:"}),
+        json!({"var": "o.1:"}),
+        json!({"patch": [{"var": "o"}, [{"op": "add", "path": "/a/0", "value": {"var": "s"}}, {"op": "remove", "path": "/a/2"}]]}),
+        json!({"patch": [{"var": "o"}, [{"op": "move", "from": "/a", "path": "/b"}, {"op": "copy", "from": "/b/1", "path": "/c"}]]}),
+        json!({"patch": [{"var": "xs"}, [{"op": "replace", "path": "/0", "value": 0}, {"op": "add", "path": "/-", "value": 9}]]}),
+        json!({"patch": [{"var": "xs"}, [{"op": "test", "path": "/0", "value": 2}]]}),
+        json!({"patch": [{"var": "xs"}, [{"op": "test", "path": "/0", "value": 1.0}, {"op": "remove", "path": "/0"}]]}),
+        json!({"patch": [{"var": "o"}, [{"op": "remove", "path": "/nope"}]]}),
+        json!({"patch": [{"var": "o"}, [{"op": "add", "path": "/a/01", "value": 1}]]}),
+        json!({"patch": [{"var": "o"}, {"op": "remove", "path": "/a"}]}),
+        json!({"merge_patch": [{"var": "o"}, {"lit": {"a": null, "b": {"c": 1, "d": null}}}]}),
+        json!({"merge_patch": [{"var": "xs"}, {"lit": {"a": [1]}}]}),
+        json!({"merge_patch": [{"var": "o"}, "s"]}),
+        json!({"diff": [{"var": "o"}, {"lit": {"a": [1, {"b": 2}, 3], "c~/": 4}}]}),
+        json!({"diff": [{"var": "xs"}, {"lit": [1]}]}),
+        json!({"diff": [{"var": "s"}, {"var": "xs"}]}),
+        json!({"diff": [{"var": "o"}, {"var": "o"}]}),
+        json!({"template": ["{{{s}}} {xs.1}{xs.-1} {o.a.1.b}", {"var": ""}]}),
+        json!({"template": ["{nope} {s}", {"var": ""}, "keep"]}),
+        json!({"template": ["{nope}", {"var": ""}]}),
+        json!({"template": ["{s", {"var": ""}]}),
+        json!({"upper": [{"cat": [{"var": "s"}, "ß"]}]}),
+        json!({"lower": ["ÀÉÎ"]}),
+        json!({"trim": ["\u{3000} a b\n"]}),
+        json!({"trim_start": ["  a "]}),
+        json!({"trim_end": [{"var": "xs"}]}),
+        json!({"pad_start": [{"var": "s"}, 8, "ab"]}),
+        json!({"pad_end": ["é", 3]}),
+        json!({"pad_end": ["é", 3, ""]}),
+        json!({"pad_end": ["é", 1025]}),
+        json!({"starts_with": [{"var": "s"}, "hé"]}),
+        json!({"ends_with": [{"var": "s"}, "x"]}),
+        json!({"split": [{"var": "s"}, ""]}),
+        json!({"split": ["a,b,,c", ","]}),
+        json!({"join": [{"var": "xs"}, "-"]}),
+        json!({"join": [{"var": "s"}, "-"]}),
+        json!({"semver_gt": ["1.10.0", "1.9.2"]}),
+        json!({"semver_gt": ["1.0.0-rc.10", "1.0.0-rc.9"]}),
+        json!({"semver_gt": ["1.0.0-alpha.beta", "1.0.0-alpha.1"]}),
+        json!({"semver_gt": ["1.0.0-alpha", "1.0.0-alpha.1"]}),
+        json!({"semver_gt": ["1.0.0", "1.0.0-rc.1"]}),
+        json!({"semver_gt": ["1.0.0+b", "1.0.0+a"]}),
+        json!({"semver_gt": ["1.02.0", "1.0.0"]}),
+        json!({"semver_gt": [{"var": "s"}, "1.0.0"]}),
+        json!({"semver_satisfies": ["1.9.0", "^1.4"]}),
+        json!({"semver_satisfies": ["0.3.5", "^0.3.1"]}),
+        json!({"semver_satisfies": ["0.4.0", "^0.3"]}),
+        json!({"semver_satisfies": ["0.0.4", "^0.0.3"]}),
+        json!({"semver_satisfies": ["1.4.9", "~1.4.2"]}),
+        json!({"semver_satisfies": ["1.5.0", "~1"]}),
+        json!({"semver_satisfies": ["1.5.0", ">=1.2, <1.5"]}),
+        json!({"semver_satisfies": ["1.5.0", "> 1.4 , <= 1.5.0"]}),
+        json!({"semver_satisfies": ["3.2.0", ">=1.2, <2 || ^3.1"]}),
+        json!({"semver_satisfies": ["1.2.7", "1.2.*"]}),
+        json!({"semver_satisfies": ["1.3.0", "=1.x"]}),
+        json!({"semver_satisfies": ["2.0.0", ">1.*"]}),
+        json!({"semver_satisfies": ["7.0.0", " * "]}),
+        json!({"semver_satisfies": ["7.0.0-rc.1", "*"]}),
+        json!({"semver_satisfies": ["1.5.0-beta", ">=1.4"]}),
+        json!({"semver_satisfies": ["1.5.0-beta.2", ">=1.5.0-beta.1"]}),
+        json!({"semver_satisfies": ["1.5.0-beta.2", "~1.5.0-beta.3"]}),
+        json!({"semver_satisfies": ["1.0.0", ">=1.2 <2"]}),
+        json!({"semver_satisfies": ["1.0.0", "1.*.3"]}),
+        json!({"semver_satisfies": ["1.0.0", "1.* || "]}),
+        json!({"semver_satisfies": ["1.0.0", "*, 1"]}),
+        json!({"money": "12.3 USD"}),
+        json!({"money": "-0.5 JPY"}),
+        json!({"money": [0.1, "KWD"]}),
+        json!({"money": [{"var": "xs.1"}, "EUR"]}),
+        json!({"money": [{"var": "xs.2"}, "CHF"]}),
+        json!({"money": ["+7", "usd"]}),
+        json!({"money": ["7", null]}),
+        json!({"money": "1e3 USD"}),
+        json!({"money": ".5 USD"}),
+        json!({"money_add": ["0.10 USD", "0.20 USD", "1.005 USD"]}),
+        json!({"money_add": ["1 USD"]}),
+        json!({"money_add": ["1 USD", "1 EUR"]}),
+        json!({"money_sub": ["5 USD", "7.25 USD"]}),
+        json!({"money_sub": ["0.001 BHD", "0.01 BHD"]}),
+        json!({"money_cmp": ["0.3 USD", "0.30 USD"]}),
+        json!({"money_cmp": ["-10 USD", "-9.99 USD"]}),
+        json!({"money_cmp": ["1 USD", 1]}),
+        json!({"duration": "7d"}),
+        json!({"duration": "1h30m15s250ms"}),
+        json!({"duration": "P1W2DT3H4M5.678S"}),
+        json!({"duration": "-PT0,5S"}),
+        json!({"duration": "P1Y"}),
+        json!({"duration": "PT"}),
+        json!({"duration": "1.5h"}),
+        json!({"duration": {"var": "xs.0"}}),
+        json!({"date_add": ["2024-03-01", "P1D"]}),
+        json!({"date_add": ["2024-01-31T10:00:00.5+05:30", "P1M"]}),
+        json!({"date_add": ["2023-02-28", "P1Y12M"]}),
+        json!({"date_add": ["2024-03-01t00:00:00z", {"var": "xs.2"}]}),
+        json!({"date_add": ["0000-01-01", "-1ms"]}),
+        json!({"date_add": ["2024-02-30", "1d"]}),
+        json!({"date_add": ["2024-03-01T10:00:00", "1d"]}),
+        json!({"date_sub": ["2024-03-31", "P1M"]}),
+        json!({"date_sub": ["1970-01-01", "1w1ms"]}),
+        json!({"date_sub": ["2024-03-01", {"var": "s"}]}),
+        json!({"range": [5]}),
+        json!({"range": [2, 5]}),
+        json!({"range": [10, 0, -3]}),
+        json!({"range": [0, 1, 0.25]}),
+        json!({"range": [0, 0.3, 0.1]}),
+        json!({"range": [5, 2]}),
+        json!({"range": [0, 5, 0]}),
+        json!({"range": [0, {"var": "xs.1"}]}),
+        json!({"map": [{"range": [1, 4]}, {"*": [{"var": ""}, 2]}]}),
+        json!({"zip": [{"var": "xs"}, ["a", "b"]]}),
+        json!({"zip": [[1, 2], null, [3]]}),
+        json!({"zip": [[1], {"var": "s"}]}),
+        json!({"zip_with": [{"var": "xs"}, [10, 20, 30], {"+": [{"var": "0"}, {"var": "1"}]}]}),
+        json!({"zip_with": [[1, 2], [3, 4], [5, 6], {"*": [{"var": "0"}, {"var": "1"}, {"var": "2"}]}]}),
+        json!({"zip_with": [[1], 2, {"var": "0"}]}),
+        json!({"cartesian": [[1, 2], ["a", "b", "c"]]}),
+        json!({"cartesian": [[1, 2], [], [3]]}),
+        json!({"cartesian": [[true], {"var": "xs"}, [null]]}),
+        json!({"cartesian": [[1], "ab"]}),
+        json!({"chunk": [[1, 2, 3, 4, 5], 2]}),
+        json!({"chunk": [{"var": "xs"}, 5]}),
+        json!({"chunk": [null, 2]}),
+        json!({"chunk": [[1, 2], 0]}),
+        json!({"window": [[1, 2, 3, 4], 2]}),
+        json!({"window": [[1, 2, 3, 4, 5], 2, 2]}),
+        json!({"window": [{"var": "xs"}, 4]}),
+        json!({"window": [[1, 2], 1.5]}),
+        json!({"take": [[1, 2, 3], 2]}),
+        json!({"take": [[1, 2, 3], -2]}),
+        json!({"take": [[1, 2, 3], -5]}),
+        json!({"take": [{"var": "s"}, 1]}),
+        json!({"drop": [[1, 2, 3], 1]}),
+        json!({"drop": [[1, 2, 3], -1]}),
+        json!({"drop": [[1, 2, 3], 9]}),
+        json!({"drop": [[1, 2, 3], "1"]}),
+        json!({"find": [{"var": "xs"}, {">": [{"var": ""}, 1]}]}),
+        json!({"find": [[1, 2], {">": [{"var": ""}, 5]}]}),
+        json!({"find": [{"var": "s"}, true]}),
+        json!({"position": [{"var": "xs"}, {"==": [{"var": ""}, 2]}]}),
+        json!({"position": [[], true]}),
+        json!({"min_by": [[{"n": 3}, {"n": 1}, {"n": 1, "x": 1}], {"var": "n"}]}),
+        json!({"max_by": [["b", "abc", "cd"], {"var": ""}]}),
+        json!({"max_by": [[], {"var": ""}]}),
+        json!({"sum_by": [[{"n": 3}, {"n": "4"}], {"var": "n"}]}),
+        json!({"sum_by": [null, {"var": "n"}]}),
+        json!({"sum_by": [[{"n": "x"}], {"var": "n"}]}),
+        json!({"distinct_by": [[{"n": 1, "i": 0}, {"n": 2, "i": 1}, {"n": 1, "i": 2}], {"var": "n"}]}),
+        json!({"distinct_by": [[{"k": {"a": 1, "b": [2]}}, {"k": {"b": [2], "a": 1}}, {"k": [1]}], {"var": "k"}]}),
+        json!({"distinct_by": [["a", "A", "b"], {"upper": [{"var": ""}]}]}),
+        json!({"distinct_by": [{"var": "s"}, {"var": ""}]}),
+        json!({"sort_by": [[{"n": 3, "i": 0}, {"n": 1, "i": 1}, {"n": 3, "i": 2}], {"var": "n"}]}),
+        json!({"sort_by": [[{"n": 3, "i": 0}, {"n": 1, "i": 1}, {"n": 3, "i": 2}], {"var": "n"}, true]}),
+        json!({"sort_by": [[3, "b", null, true, "a", [1], 1.5, false], {"var": ""}]}),
+        json!({"sort_by": [[["b", 1], ["a", 2], ["b", 2], ["a"]], {"var": ""}, [false, true]]}),
+        json!({"sort_by": [{"var": "xs"}, {"var": ""}]}),
+        json!({"sort_by": [[1, 2], {"var": "o"}]}),
+        json!({"sort_by": [[1, 2], {"var": ""}, "desc"]}),
+        json!({"sort_with": [["bb", "a", "ccc"], {"-": [{"var": "b.length"}, {"var": "a.length"}]}]}),
+        json!({"sort_with": [[2, 1, 3], {"-": [{"var": "a"}, {"var": "b"}]}, true]}),
+        json!({"sort_with": [[2, 1], "x"]}),
+        json!({"sort_with": [[2, 1], 0, [true]]}),
+        json!({"reduce_while": [[4, 5, 3], {"if": [{">": [{"var": "accumulator"}, 6]}, {"reduced": {"var": "accumulator"}}, {"+": [{"var": "accumulator"}, {"var": "current"}]}]}, 0]}),
+        json!({"reduce_while": [[1, "x"], {"reduced": {"var": "#index"}}, null]}),
+        json!({"reduce_while": [[1, 2], {"var": "../o"}, 0]}),
+        json!({"reduce_while": [[1, 2], {"var": "../r"}, 0]}),
+        json!({"reduce_while": [[1, 2], {"if": [{"reduced": 1}, {"var": "../r"}, 0]}, 0]}),
+        json!({"reduce_while": [[1, 2], {"merge": [{"var": "accumulator"}, {"var": "current"}]}, []]}),
+        json!({"reduce_while": [null, 1, "init"]}),
+        json!({"reduce_while": ["ab", 1, 0]}),
+        json!({"reduced": {"var": "xs"}}),
+        json!({"obj": [["a", {"var": "xs"}], [{"var": "s"}, 1], ["a", 2]]}),
+        json!({"obj": []}),
+        json!({"obj": [[1, 2]]}),
+        json!({"map_values": [{"obj": [["x", 1], ["y", 2]]}, {"+": [{"var": ""}, {"var": "../xs.0"}]}]}),
+        json!({"map_values": [null, 1]}),
+        json!({"map_values": [{"var": "xs"}, 1]}),
+        json!({"switch": [{"var": "s"}, {"abc": {"var": "xs"}, "d": 1}, 2]}),
+        json!({"switch": ["d", {"abc": {"/": [1, "x"]}, "d": {"var": "s"}}]}),
+        json!({"switch": [{"var": "xs"}, {"a": 1}]}),
+        json!({"switch": ["toString", {"a": 1}, "other"]}),
+        json!({"switch": ["a", {}]}),
+        json!({"switch": [{"var": "xs.0"}, {"1": 1}, "other"]}),
+        json!({"switch": [{"var": "nope"}, {"null": 1}, "other"]}),
+        json!({"map": [{"var": "xs"}, [{"var": "#index"}, {"var": ""}]]}),
+        json!({"map": [{"var": "xs"}, {"cat": [{"var": "#index"}, ":", {"var": ""}]}]}),
+        json!({"filter": [{"var": "xs"}, {"<": [{"var": "#index"}, 2]}]}),
+        json!({"reduce": [{"var": "xs"}, {"+": [{"var": "accumulator"}, {"var": "#index"}]}, 0]}),
+        json!({"all": [{"var": "s"}, {"<": [{"var": "#index"}, 5]}]}),
+        json!({"some": [[5, 1, 7], {"==": [{"var": "#index"}, {"var": ""}]}]}),
+        json!({"none": [[5, 1, 7], {"==": [{"var": "#index"}, {"var": ""}]}]}),
+        json!({"map": [[[1, 2], [3]], {"map": [{"var": ""}, {"var": "#index"}]}]}),
+        json!({"map": [[1, 2], {"cat": [{"var": "#index"}, {"map": [[3], {"var": "#index"}]}, {"var": "#index"}]}]}),
+        json!({"zip_with": [[1, 2], [3, 4], {"var": "#index"}]}),
+        json!({"var": "#index"}),
+        json!({"map": [[{"#index": "x"}], {"var": "#index"}]}),
+        json!({"filter": [[1, 3, 5], {">": [{"var": ""}, {"var": "../xs.1"}]}]}),
+        json!({"map": [[1, 2], {"cat": [{"var": "../s"}, {"var": ""}]}]}),
+        json!({"map": [[[1, 2], [3]], {"map": [{"var": ""}, [{"var": "../../s"}, {"var": "../#index"}, {"var": "#index"}]]}]}),
+        json!({"map": [[[1, 2], [3]], {"map": [{"var": ""}, {"cat": [{"var": "../../s"}, {"var": "../#index"}, {"var": "#index"}]}]}]}),
+        json!({"map": [[{"k": 1}], {"var": "../"}]}),
+        json!({"map": [[{"k": 1}], {"var": ".."}]}),
+        json!({"map": [[1], {"var": "../../s"}]}),
+        json!({"map": [[1], {"var": ["../../s", "fallback"]}]}),
+        json!({"var": "../s"}),
+        json!({"find": [[1, 2, 3], {"==": [{"var": ""}, {"var": "../xs.1"}]}]}),
+        json!({"sort_by": [[3, 1, 2], {"var": ""}, {"var": "o.desc"}]}),
+        json!({"sum_by": [[1, 2], {"*": [{"var": ""}, {"var": "../xs.0"}]}]}),
+        json!({"reduce": [[1, 2], {"+": [{"var": "accumulator"}, {"var": "../xs.0"}]}, 0]}),
+        json!({"eq": [{"var": "o"}, {"var": "o"}]}),
+        json!({"==": [{"var": "o"}, {"var": "o"}]}),
+        json!({"ne": [[1, 2], [1, 2]]}),
+        json!({"add": [1, 2]}),
+        json!({"add": [1, "2"]}),
+        json!({"+": [1, "abc"]}),
+        json!({"/": [1, 0]}),
+        json!({"substr": ["hello", -3, 2]}),
+        json!({"all": [{"var": "s"}, {"!=": [{"var": ""}, "x"]}]}),
+        json!({"some": [[], true]}),
+        json!({"missing": ["xs", "o.a.1.b", "o.a.1.c", "nope"]}),
+        json!({"missing_some": [1, ["nope", "xs"]]}),
+        json!({"missing_some": [-1, ["xs"]]}),
+        json!({"reduce": [{"var": "xs"}, {"cat": [{"var": "accumulator"}, {"var": "current"}]}, ""]}),
+    ]
+    .into_iter()
+    .map(|rule| (ns.parse(&rule).unwrap(), data.clone()))
+    .collect();
+    let options = ParseOptions {
+        multi_key_objects: MultiKeyObjects::Evaluate,
+        ..ParseOptions::default()
+    };
+    rules.push((
+        Rule::from_value_with(
+            &json!({"a": {"var": "s"}, "b": {"x": [1], "y": {"var": "xs.1"}}}),
+            &options,
+        )
+        .unwrap(),
+        data.clone(),
+    ));
+    check(&rules);
+}
@@ -78,3 +78,20 @@ fn run_cases() {
         )
     })
 }
+
+#[test]
+fn run_compiled_cases() {
+    load_tests().into_iter().for_each(|case| {
+        let compiled = jsonlogic_plus::Rule::from_value(&case.logic)
+            .unwrap()
+            .compile()
+            .unwrap();
+        assert_eq!(
+            compiled.apply(&case.data).unwrap(),
+            case.result,
+            "logic: {:?}, data: {:?}",
+            case.logic,
+            case.data
+        )
+    })
+}
@@ -23,3 +23,42 @@ fn test_python_dist() {
         .expect("Could not spawn make");
     assert!(py_test_res.status.success(), "{:?}", py_test_res);
 }
+
+/// Build the pyo3 extension module and run `test_pyo3.py` against it.
+#[cfg(feature = "pyo3")]
+#[test]
+fn test_pyo3_module() {
+    use std::env::consts::{DLL_PREFIX, DLL_SUFFIX};
+    use std::path::Path;
+    use std::process::Command;
+
+    // Build in a target dir of its own, so as not to contend with the build
+    // of these tests
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let target = root.join("target").join("pyo3");
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".into());
+    let build_res = Command::new(cargo)
+        .args(["build", "--lib", "--features", "pyo3", "--target-dir"])
+        .arg(&target)
+        .current_dir(root)
+        .output()
+        .expect("Could not spawn cargo");
+    assert!(build_res.status.success(), "{:?}", build_res);
+
+    let lib = target
+        .join("debug")
+        .join(format!("{}jsonlogic_plus{}", DLL_PREFIX, DLL_SUFFIX));
+    let module_dir = target.join("module");
+    std::fs::create_dir_all(&module_dir).unwrap();
+    let module_ext = if cfg!(windows) { "pyd" } else { "so" };
+    std::fs::copy(&lib, module_dir.join(format!("_jsonlogic.{}", module_ext)))
+        .unwrap_or_else(|e| panic!("Could not copy {:?}: {}", lib, e));
+
+    let python = std::env::var("PYTHON").unwrap_or_else(|_| "python3".into());
+    let py_test_res = Command::new(python)
+        .arg(root.join("tests").join("test_pyo3.py"))
+        .env("PYTHONPATH", module_dir)
+        .output()
+        .expect("Could not spawn python");
+    assert!(py_test_res.status.success(), "{:?}", py_test_res);
+}
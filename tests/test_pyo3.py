@@ -0,0 +1,64 @@
+"""Test the pyo3 extension module.
+
+The directory holding the built `_jsonlogic` module must be on the path.
+"""
+
+import json
+from pathlib import Path
+
+from _jsonlogic import JsonLogicError, Namespace, Rule, apply
+
+
+TEST_FILE = Path(__file__).parent / "data/tests.json"
+
+
+def test_cases() -> None:
+    """Assert we get the right output for the test json."""
+    with open(TEST_FILE) as f:
+        cases = [case for case in json.load(f) if not isinstance(case, str)]
+    for idx, (logic, data, exp) in enumerate(cases):
+        assert apply(logic, data) == exp, f"Failed test case {idx}: {logic}"
+        compiled = Rule(logic).compile()
+        assert compiled.apply(data) == exp, f"Failed test case {idx}: {logic}"
+
+
+def test_values() -> None:
+    """Assert python values convert to and from JSON."""
+    data = {"x": [1, 2.5, None, True, "s", {"y": (1, 2)}]}
+    exp = [1, 2.5, None, True, "s", {"y": [1, 2]}]
+    assert apply({"var": "x"}, data) == exp
+    assert apply({"==": [1, 1]}) is True
+    for bad in ({1: 2}, {"x": object()}):
+        try:
+            apply({"var": "x"}, bad)
+        except TypeError:
+            pass
+        else:
+            raise AssertionError(f"Expected a TypeError for {bad}")
+
+
+def test_namespace() -> None:
+    """Assert rules may be parsed in a namespace."""
+    adult = {"defn": ["adult", ["age"], {">=": [{"param": "age"}, 18]}]}
+    namespace = Namespace({"functions": [adult]})
+    namespace.check()
+    assert namespace.names() == ["adult"]
+    rule = namespace.parse({"adult": [{"var": "age"}]})
+    assert rule.apply({"age": 20}) is True
+    assert rule.compile().apply({"age": 17}) is False
+
+    try:
+        namespace.parse({"adlt": [1]})
+    except JsonLogicError as err:
+        message, payload = err.args
+        assert isinstance(err, ValueError)
+        assert payload["code"] == "unknown_operator", payload
+        assert payload["message"] == message
+    else:
+        raise AssertionError("Expected a JsonLogicError")
+
+
+if __name__ == "__main__":
+    test_cases()
+    test_values()
+    test_namespace()
@@ -0,0 +1,93 @@
+//! Check evaluation results against the recorded snapshots.
+//!
+//! Each file in `data/snapshots` records the expected results of a set of
+//! rules under a single semantics version. A failure here means an existing
+//! rule would produce a different result after upgrading the crate, so the
+//! change must instead be made under a new semantics version.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use jsonlogic_plus::{Context, Rule, SemanticsVersion};
+
+struct Snapshot {
+    description: String,
+    rule: Value,
+    data: Value,
+    result: Option<Value>,
+}
+
+fn snapshot_files() -> Vec<PathBuf> {
+    let dir = Path::join(Path::new(file!()).parent().unwrap(), "data/snapshots");
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension() == Some("json".as_ref()))
+        .collect();
+    files.sort();
+    files
+}
+
+fn load_snapshots(path: &Path) -> (SemanticsVersion, Vec<Snapshot>) {
+    let contents = fs::read_to_string(path).unwrap();
+    let file: Value = serde_json::from_str(&contents).unwrap();
+    let semantics = file["semantics"]
+        .as_str()
+        .expect("snapshot file has no semantics version")
+        .parse()
+        .unwrap();
+    let cases = file["cases"]
+        .as_array()
+        .expect("snapshot cases aren't an array")
+        .iter()
+        .map(|case| Snapshot {
+            description: case["description"].as_str().unwrap().into(),
+            rule: case["rule"].clone(),
+            data: case["data"].clone(),
+            result: match case.get("error") {
+                Some(Value::Bool(true)) => None,
+                _ => Some(case["result"].clone()),
+            },
+        })
+        .collect();
+    (semantics, cases)
+}
+
+#[test]
+fn run_snapshots() {
+    let files = snapshot_files();
+    assert!(!files.is_empty());
+    files.iter().for_each(|path| {
+        let (semantics, cases) = load_snapshots(path);
+        let ctx = Context {
+            semantics,
+            ..Context::default()
+        };
+        cases.into_iter().for_each(|case| {
+            println!("Running snapshot: {}", case.description);
+            let result = Rule::from_value(&case.rule)
+                .and_then(|rule| rule.apply_with(&case.data, &ctx));
+            match (result, case.result) {
+                (Ok(actual), Some(expected)) => {
+                    assert_eq!(actual, expected, "{:?}: {}", path, case.description)
+                }
+                (Err(_), None) => {}
+                (actual, expected) => panic!(
+                    "{:?}: {} - expected {:?}, got {:?}",
+                    path, case.description, expected, actual
+                ),
+            }
+        })
+    })
+}
+
+#[test]
+fn latest_semantics_has_snapshots() {
+    let versions: Vec<SemanticsVersion> = snapshot_files()
+        .iter()
+        .map(|path| load_snapshots(path).0)
+        .collect();
+    assert!(versions.contains(&SemanticsVersion::LATEST));
+}